@@ -0,0 +1,218 @@
+//! Score lots of positions at a fixed depth, as fast as the machine allows
+//!
+//! Tuning the evaluation means searching thousands of positions after every change to it. The
+//! normal entry points are built around answering a single move request, with a game timeout and
+//! tracing for every search, none of which a tuner wants. [batch_evaluate] skips all of that and
+//! just spreads the positions over some threads.
+
+use std::{
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+use battlesnake_game_types::{
+    types::*,
+    wire_representation::{NestedGame, Ruleset},
+};
+use itertools::Itertools;
+
+use crate::{
+    board_hash::BoardHashable,
+    paranoid::{MinimaxSnake, Scorable, ScoreMemo, SnakeOptions, WrappedScore},
+    Instruments,
+};
+
+/// What [batch_evaluate] found for a single position
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchEvaluation<ScoreType> {
+    /// The minimax score of the position, from the point of view of its `you` snake
+    pub score: WrappedScore<ScoreType>,
+    /// The move `you` would make, None if there was nothing to search
+    pub best_move: Option<Move>,
+    /// How many nodes the search visited, see [crate::paranoid::MinMaxReturn::node_count]
+    pub nodes: usize,
+}
+
+/// Search every position `depth` turns deep with `score_fn`, on `parallelism` threads
+///
+/// The results come back in the same order as the positions. Threads take the next position as
+/// soon as they finish one, so a few slow positions don't hold up the rest of the batch. Each
+/// thread keeps its own [ScoreMemo] and clears it between positions, so the memory for it is only
+/// allocated once per thread instead of once per position.
+///
+/// There is no timeout, every position is searched to the full depth, so keep the depth small
+pub fn batch_evaluate<GameType, ScoreType, ScorableType, const N_SNAKES: usize>(
+    positions: &[GameType],
+    score_fn: ScorableType,
+    depth: usize,
+    parallelism: usize,
+) -> Vec<BatchEvaluation<ScoreType>>
+where
+    GameType: SnakeIDGettableGame
+        + YouDeterminableGame
+        + PositionGettableGame
+        + HealthGettableGame
+        + VictorDeterminableGame
+        + HeadGettableGame
+        + NeighborDeterminableGame
+        + NeckQueryableGame
+        + SnakeBodyGettableGame
+        + BoardHashable
+        + SimulableGame<Instruments, N_SNAKES>
+        + Clone
+        + Sync
+        + Send
+        + Sized
+        + 'static,
+    GameType::SnakeIDType: Clone + Send + Sync,
+    ScoreType: Clone + Debug + PartialOrd + Ord + Send + Sync + Copy + 'static,
+    ScorableType: Scorable<GameType, ScoreType> + Sized + Send + Sync + Clone + 'static,
+{
+    let Some(first) = positions.first() else {
+        return vec![];
+    };
+
+    let template: MinimaxSnake<GameType, ScoreType, ScorableType, N_SNAKES> = MinimaxSnake::new(
+        first.clone(),
+        batch_game_info(),
+        0,
+        score_fn,
+        "batch_evaluate",
+        SnakeOptions::default(),
+    );
+    let next_position = AtomicUsize::new(0);
+
+    let evaluated = thread::scope(|scope| {
+        let workers = (0..parallelism.clamp(1, positions.len()))
+            .map(|_| {
+                let mut snake = template.clone();
+                let next_position = &next_position;
+
+                scope.spawn(move || {
+                    let score_memo = Arc::new(ScoreMemo::default());
+                    snake.score_memo = Some(score_memo.clone());
+                    let mut players = Vec::with_capacity(N_SNAKES);
+                    let mut evaluated = vec![];
+
+                    loop {
+                        let i = next_position.fetch_add(1, Ordering::Relaxed);
+                        let Some(position) = positions.get(i) else {
+                            break;
+                        };
+
+                        score_memo.clear();
+                        snake.game = position.clone();
+
+                        let my_id = position.you_id();
+                        players.clear();
+                        players.extend(position.get_snake_ids());
+                        players.sort_by_key(|snake_id| if snake_id == my_id { -1 } else { 1 });
+
+                        let result = snake.single_minimax_for_players(&players, depth);
+                        evaluated.push((
+                            i,
+                            BatchEvaluation {
+                                score: *result.score(),
+                                best_move: result.your_best_move(my_id),
+                                nodes: result.node_count(),
+                            },
+                        ));
+                    }
+
+                    evaluated
+                })
+            })
+            .collect_vec();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("A batch evaluation thread panicked"))
+            .collect_vec()
+    });
+
+    evaluated
+        .into_iter()
+        .sorted_unstable_by_key(|(i, _)| *i)
+        .map(|(_, evaluation)| evaluation)
+        .collect()
+}
+
+/// The searches only look at the game info for their logs and time limits, neither of which a
+/// batch uses
+fn batch_game_info() -> NestedGame {
+    NestedGame {
+        id: "batch_evaluate".to_owned(),
+        map: None,
+        source: None,
+        timeout: 0,
+        ruleset: Ruleset {
+            name: "standard".to_owned(),
+            version: "batch_evaluate".to_owned(),
+            settings: None,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use battlesnake_game_types::{
+        compact_representation::StandardCellBoard4Snakes11x11, wire_representation::Game,
+    };
+
+    use super::*;
+
+    fn fixture(json: &str) -> StandardCellBoard4Snakes11x11 {
+        let game: Game = serde_json::from_str(json).unwrap();
+        let id_map = build_snake_id_map(&game);
+
+        StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap()
+    }
+
+    fn health_score(board: &StandardCellBoard4Snakes11x11) -> i64 {
+        board.get_health_i64(board.you_id())
+    }
+
+    #[test]
+    fn test_batch_matches_single_searches_in_order() {
+        let positions = vec![
+            fixture(include_str!(
+                "../../battlesnake-rs/fixtures/start_of_game.json"
+            )),
+            fixture(include_str!("../../fixtures/forced_corridor.json")),
+            fixture(include_str!(
+                "../../battlesnake-rs/fixtures/start_of_game.json"
+            )),
+        ];
+        let batched = batch_evaluate(&positions, health_score, 2, 4);
+
+        assert_eq!(batched.len(), positions.len());
+        for (position, batched) in positions.iter().zip(&batched) {
+            let single = MinimaxSnake::new(
+                *position,
+                batch_game_info(),
+                0,
+                health_score,
+                "single",
+                SnakeOptions::default(),
+            )
+            .single_minimax(2);
+
+            assert_eq!(batched.score, *single.score());
+            assert_eq!(batched.best_move, single.your_best_move(position.you_id()));
+            assert_eq!(batched.nodes, single.node_count());
+            assert!(batched.nodes > 1);
+        }
+    }
+
+    #[test]
+    fn test_empty_batch() {
+        let batched =
+            batch_evaluate::<StandardCellBoard4Snakes11x11, _, _, 4>(&[], health_score, 2, 4);
+
+        assert_eq!(batched, vec![]);
+    }
+}
@@ -0,0 +1,258 @@
+//! A board hash that is stable across game representations
+//!
+//! The derived [Hash] implementations for the compact boards depend on the internal layout of the
+//! board, including which [SnakeId](battlesnake_game_types::types::SnakeId) each snake was
+//! assigned. That means the same position can hash differently between turns, or between the wire
+//! and compact representations.
+//!
+//! [BoardHashable] instead hashes the position in terms of wire [Position]s, and combines the
+//! snakes in an order independent way. This makes it safe to use as a key for anything that needs
+//! to recognize the same position again, like transposition tables or score caches.
+
+use std::hash::{Hash, Hasher};
+
+use battlesnake_game_types::{
+    types::{
+        FoodGettableGame, HazardQueryableGame, HealthGettableGame, PositionGettableGame,
+        SizeDeterminableGame, SnakeBodyGettableGame, SnakeIDGettableGame, VictorDeterminableGame,
+        YouDeterminableGame,
+    },
+    wire_representation::Position,
+};
+use fxhash::FxHasher64;
+use itertools::Itertools;
+
+/// Something that can produce a hash of the current position on the board
+///
+/// Equal positions are guaranteed to produce equal hashes, regardless of the representation of
+/// the board or the order the snakes are stored in.
+pub trait BoardHashable {
+    /// Returns the hash for the current position
+    fn board_hash(&self) -> u64;
+
+    /// Returns a hash of where everything is on the board, ignoring the health of the snakes
+    ///
+    /// Health goes down every turn, so [BoardHashable::board_hash] never sees the same position
+    /// twice in a game unless someone eats. This is the one to use for noticing that the snakes
+    /// are going around in circles
+    fn position_hash(&self) -> u64;
+}
+
+impl<T> BoardHashable for T
+where
+    T: SnakeIDGettableGame
+        + YouDeterminableGame
+        + SnakeBodyGettableGame
+        + HealthGettableGame
+        + VictorDeterminableGame
+        + FoodGettableGame
+        + HazardQueryableGame
+        + PositionGettableGame
+        + SizeDeterminableGame,
+{
+    fn board_hash(&self) -> u64 {
+        hash_board(self, true)
+    }
+
+    fn position_hash(&self) -> u64 {
+        hash_board(self, false)
+    }
+}
+
+fn hash_board<T>(board: &T, include_health: bool) -> u64
+where
+    T: SnakeIDGettableGame
+        + YouDeterminableGame
+        + SnakeBodyGettableGame
+        + HealthGettableGame
+        + VictorDeterminableGame
+        + FoodGettableGame
+        + HazardQueryableGame
+        + PositionGettableGame
+        + SizeDeterminableGame,
+{
+    let you_id = board.you_id();
+
+    // Each snake is hashed on its own, and then we sort the hashes so that the order of the
+    // snakes on the board doesn't change the final hash
+    let snake_hashes = board
+        .get_snake_ids()
+        .iter()
+        .filter(|sid| board.is_alive(sid))
+        .map(|sid| {
+            let mut hasher = FxHasher64::default();
+            (sid == you_id).hash(&mut hasher);
+            if include_health {
+                board.get_health_i64(sid).hash(&mut hasher);
+            }
+            for pos in board.get_snake_body_vec(sid) {
+                board.position_from_native(pos).hash(&mut hasher);
+            }
+
+            hasher.finish()
+        })
+        .sorted_unstable()
+        .collect_vec();
+
+    let food = board
+        .get_all_food_as_native_positions()
+        .into_iter()
+        .map(|pos| board.position_from_native(pos))
+        .sorted_unstable()
+        .collect_vec();
+
+    let width = board.get_width() as i32;
+    let height = board.get_height() as i32;
+    let hazards = (0..height)
+        .flat_map(|y| (0..width).map(move |x| Position { x, y }))
+        .filter(|pos| board.is_hazard(&board.native_from_position(*pos)))
+        .collect_vec();
+
+    let mut hasher = FxHasher64::default();
+    width.hash(&mut hasher);
+    height.hash(&mut hasher);
+    snake_hashes.hash(&mut hasher);
+    food.hash(&mut hasher);
+    hazards.hash(&mut hasher);
+
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use battlesnake_game_types::{
+        compact_representation::{StandardCellBoard4Snakes11x11, WrappedCellBoard4Snakes11x11},
+        types::build_snake_id_map,
+        wire_representation::Game,
+    };
+
+    use super::*;
+
+    const FIXTURES: [(&str, &str); 10] = [
+        (
+            "start_of_game",
+            include_str!("../../battlesnake-rs/fixtures/start_of_game.json"),
+        ),
+        (
+            "check_board_doubled_up",
+            include_str!("../../battlesnake-rs/fixtures/check_board_doubled_up.json"),
+        ),
+        (
+            "095b30fa_5",
+            include_str!("../../fixtures/095b30fa-f2c7-4826-ac93-90b4dde6b785_5.json"),
+        ),
+        (
+            "095b30fa_6",
+            include_str!("../../fixtures/095b30fa-f2c7-4826-ac93-90b4dde6b785_6.json"),
+        ),
+        (
+            "45e7de53",
+            include_str!("../../fixtures/45e7de53-bca5-4fa3-8771-d9914ed141bb.json"),
+        ),
+        (
+            "65401e8f",
+            include_str!("../../fixtures/65401e8f-a92a-445f-9617-94770044e117.json"),
+        ),
+        (
+            "6d9cd0b1_101",
+            include_str!("../../fixtures/6d9cd0b1-6829-4430-926c-562918397774_101.json"),
+        ),
+        (
+            "7a02e19b_192",
+            include_str!("../../fixtures/7a02e19b-f658-4639-8ace-ece46629a6ed_192.json"),
+        ),
+        (
+            "arcade_maze_end_game_duels",
+            include_str!("../../fixtures/arcade_maze_end_game_duels.json"),
+        ),
+        (
+            "arcade_maze_should_win",
+            include_str!("../../fixtures/arcade_maze_should_win.json"),
+        ),
+    ];
+
+    fn fits_in_11x11_board(game: &Game) -> bool {
+        game.board.width == 11 && game.board.height == 11 && game.board.snakes.len() <= 4
+    }
+
+    #[test]
+    fn test_hash_is_the_same_across_representations() {
+        for (name, fixture) in FIXTURES {
+            let wire: Game = serde_json::from_str(fixture).unwrap();
+            if !fits_in_11x11_board(&wire) {
+                continue;
+            }
+            let id_map = build_snake_id_map(&wire);
+            let wire_hash = wire.board_hash();
+
+            let standard =
+                StandardCellBoard4Snakes11x11::convert_from_game(wire.clone(), &id_map).unwrap();
+            let wrapped =
+                WrappedCellBoard4Snakes11x11::convert_from_game(wire.clone(), &id_map).unwrap();
+
+            assert_eq!(wire_hash, standard.board_hash(), "{name}: standard differs");
+            assert_eq!(wire_hash, wrapped.board_hash(), "{name}: wrapped differs");
+        }
+    }
+
+    #[test]
+    fn test_hash_ignores_snake_ordering() {
+        for (name, fixture) in FIXTURES {
+            let wire: Game = serde_json::from_str(fixture).unwrap();
+            let mut reordered = wire.clone();
+            reordered.board.snakes.reverse();
+
+            assert_eq!(wire.board_hash(), reordered.board_hash(), "{name}");
+
+            if !fits_in_11x11_board(&wire) {
+                continue;
+            }
+
+            let standard = StandardCellBoard4Snakes11x11::convert_from_game(
+                wire.clone(),
+                &build_snake_id_map(&wire),
+            )
+            .unwrap();
+            let reordered_standard = StandardCellBoard4Snakes11x11::convert_from_game(
+                reordered.clone(),
+                &build_snake_id_map(&reordered),
+            )
+            .unwrap();
+
+            assert_eq!(
+                standard.board_hash(),
+                reordered_standard.board_hash(),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_position_hash_ignores_health() {
+        for (name, fixture) in FIXTURES {
+            let wire: Game = serde_json::from_str(fixture).unwrap();
+            let mut healthier = wire.clone();
+            for snake in healthier.board.snakes.iter_mut() {
+                snake.health += 1;
+            }
+
+            assert_eq!(wire.position_hash(), healthier.position_hash(), "{name}");
+            assert_ne!(wire.board_hash(), healthier.board_hash(), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_no_collisions_between_archived_games() {
+        let mut seen: HashMap<u64, &str> = HashMap::new();
+
+        for (name, fixture) in FIXTURES {
+            let wire: Game = serde_json::from_str(fixture).unwrap();
+
+            if let Some(other) = seen.insert(wire.board_hash(), name) {
+                panic!("{name} and {other} have the same board hash");
+            }
+        }
+    }
+}
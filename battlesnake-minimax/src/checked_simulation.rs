@@ -0,0 +1,184 @@
+//! Sanity checks for the moves we hand to [SimulableGame::simulate_with_moves]
+//!
+//! The simulation in the types crate trusts its input. A move for a snake that is already dead, or
+//! a move straight back into a snake's own neck, doesn't fail there, it just quietly produces a
+//! board that makes no sense, or panics somewhere deep inside the types crate. These checks catch
+//! those inputs where they are made and say what was wrong with them.
+//!
+//! The searches run them in debug builds only, since they cost a little on every simulation
+
+use std::{borrow::Borrow, fmt::Debug};
+
+use battlesnake_game_types::types::{
+    Action, HeadGettableGame, HealthGettableGame, Move, NeckQueryableGame,
+    NeighborDeterminableGame, SimulableGame, SimulatorInstruments, SnakeBodyGettableGame,
+    SnakeIDGettableGame,
+};
+use color_eyre::eyre::{eyre, Result};
+
+use crate::neck::neck_of;
+
+/// Check that every move we are about to simulate is one the snake could actually make
+///
+/// Each snake needs to be alive, and only listed once. A snake can't be sent into its own neck
+/// while it has a free square to go to instead. When every other square next to its head is
+/// taken it is dead whichever way it goes, and sending it anywhere is fine, since that is what
+/// [battlesnake_game_types::types::ReasonableMovesGame] does for snakes with no reasonable moves
+pub fn check_simulation_moves<GameType, S>(
+    game: &GameType,
+    moves: &[(GameType::SnakeIDType, S)],
+) -> Result<()>
+where
+    GameType: SnakeIDGettableGame
+        + HealthGettableGame
+        + HeadGettableGame
+        + NeighborDeterminableGame
+        + NeckQueryableGame
+        + SnakeBodyGettableGame,
+    GameType::SnakeIDType: Debug,
+    S: Borrow<[Move]>,
+{
+    for (i, (snake_id, snake_moves)) in moves.iter().enumerate() {
+        if moves[..i].iter().any(|(other, _)| other == snake_id) {
+            return Err(eyre!("Snake {snake_id:?} was given moves more than once"));
+        }
+
+        if !game.is_alive(snake_id) {
+            return Err(eyre!(
+                "Snake {snake_id:?} is dead but was given the moves {:?}",
+                snake_moves.borrow()
+            ));
+        }
+
+        let neighbors = game
+            .possible_moves(&game.get_head_as_native_position(snake_id))
+            .collect::<Vec<_>>();
+        let neck = neck_of(game, snake_id);
+        let occupied = game
+            .get_snake_ids()
+            .into_iter()
+            .filter(|sid| game.is_alive(sid))
+            .flat_map(|sid| game.get_snake_body_vec(&sid))
+            .collect::<Vec<_>>();
+        let has_a_free_square = neighbors
+            .iter()
+            .any(|(_, pos)| Some(pos) != neck.as_ref() && !occupied.contains(pos));
+
+        for m in snake_moves.borrow() {
+            let into_neck = neighbors
+                .iter()
+                .any(|(neighbor_move, pos)| neighbor_move == m && Some(pos) == neck.as_ref());
+
+            if into_neck && has_a_free_square {
+                return Err(eyre!(
+                    "Snake {snake_id:?} was sent {m} into its own neck while it had a free square"
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// [SimulableGame::simulate_with_moves], but only after [check_simulation_moves] says the moves
+/// make sense
+#[allow(clippy::type_complexity)]
+pub fn checked_simulate_with_moves<GameType, Instruments, S, const N_SNAKES: usize>(
+    game: &GameType,
+    instruments: &Instruments,
+    moves: Vec<(GameType::SnakeIDType, S)>,
+) -> Result<Vec<(Action<N_SNAKES>, GameType)>>
+where
+    GameType: SnakeIDGettableGame
+        + HealthGettableGame
+        + HeadGettableGame
+        + NeighborDeterminableGame
+        + NeckQueryableGame
+        + SnakeBodyGettableGame
+        + SimulableGame<Instruments, N_SNAKES>,
+    GameType::SnakeIDType: Debug,
+    Instruments: SimulatorInstruments,
+    S: Borrow<[Move]>,
+{
+    check_simulation_moves(game, &moves)?;
+
+    Ok(game.simulate_with_moves(instruments, moves).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use battlesnake_game_types::{
+        compact_representation::StandardCellBoard4Snakes11x11,
+        types::{build_snake_id_map, SnakeId},
+        wire_representation::{Game, Position},
+    };
+
+    use super::*;
+    use crate::Instruments;
+
+    /// The start of game fixture, with us stretched out downwards so our neck is below our head
+    fn stretched_game() -> (StandardCellBoard4Snakes11x11, SnakeId, SnakeId) {
+        let mut wire: Game = serde_json::from_str(include_str!(
+            "../../battlesnake-rs/fixtures/start_of_game.json"
+        ))
+        .unwrap();
+        let body = [(9, 5), (9, 4), (9, 3)]
+            .into_iter()
+            .map(|(x, y)| Position { x, y })
+            .collect();
+        wire.board.snakes[0].body = body;
+        wire.you.body = wire.board.snakes[0].body.clone();
+        wire.board.snakes[1].health = 0;
+
+        let id_map = build_snake_id_map(&wire);
+        let you = id_map["you"];
+        let dead = id_map["#FF6c96"];
+        let board = StandardCellBoard4Snakes11x11::convert_from_game(wire, &id_map).unwrap();
+
+        (board, you, dead)
+    }
+
+    #[test]
+    fn sensible_moves_pass() {
+        let (board, you, _) = stretched_game();
+        let other = board
+            .get_snake_ids()
+            .into_iter()
+            .find(|sid| *sid != you && board.is_alive(sid))
+            .unwrap();
+
+        let moves = vec![(you, [Move::Up]), (other, [Move::Up])];
+        let simulated =
+            checked_simulate_with_moves(&board, &Instruments::default(), moves).unwrap();
+
+        assert_eq!(simulated.len(), 1);
+    }
+
+    #[test]
+    fn moves_for_dead_snakes_are_rejected() {
+        let (board, _, dead) = stretched_game();
+
+        let error = check_simulation_moves(&board, &[(dead, [Move::Left])]).unwrap_err();
+
+        assert!(error.to_string().contains("is dead"));
+    }
+
+    #[test]
+    fn moves_into_the_neck_are_rejected() {
+        let (board, you, _) = stretched_game();
+
+        let error = check_simulation_moves(&board, &[(you, [Move::Up, Move::Down])]).unwrap_err();
+
+        assert!(error.to_string().contains("into its own neck"));
+    }
+
+    #[test]
+    fn snakes_can_only_be_moved_once() {
+        let (board, you, _) = stretched_game();
+
+        let error =
+            check_simulation_moves(&board, &[(you, [Move::Up]), (you, [Move::Left])]).unwrap_err();
+
+        assert!(error.to_string().contains("more than once"));
+    }
+}
@@ -0,0 +1,267 @@
+//! Keeps the searches of games that run at the same time on their own cores
+//!
+//! When two games are going at once their searches share the [crate::search_pool], and the OS is
+//! free to move both of them across every core. They end up interleaving on the same cores and
+//! both get less depth than either would with half the machine to itself. With
+//! `GAME_CORE_LANES` set the cores are split into that many lanes, and each game that's searching
+//! gets a lane of its own. The search pool thread running a game's search is pinned to its lane
+//! for as long as the search runs.
+//!
+//! Which lane a search got is recorded on its span as `cores`, so it can be lined up with the
+//! depth it reached
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+/// A game that hasn't searched for this long is assumed to be over, even if we never heard about
+/// it ending, and its lane is handed to the next game that needs one
+const IDLE_AFTER: Duration = Duration::from_secs(60);
+
+/// The cores one game's searches run on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoreLane {
+    /// Which of the lanes this is, counting from 0
+    pub lane: usize,
+    /// The cores in the lane
+    pub cores: Vec<usize>,
+}
+
+impl Display for CoreLane {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let cores = self
+            .cores
+            .iter()
+            .map(|core| core.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        write!(f, "lane {} (cores {cores})", self.lane)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Assignment {
+    lane: usize,
+    last_search: Instant,
+}
+
+/// Hands out the lanes to the games that are searching
+///
+/// Most code wants the shared one from [game_cores]
+#[derive(Debug)]
+pub struct GameCores {
+    lanes: Vec<Vec<usize>>,
+    assignments: Mutex<HashMap<String, Assignment>>,
+}
+
+impl GameCores {
+    /// Split the `cores` into `lanes` lanes of about the same size
+    ///
+    /// Pinning is off with fewer than two lanes, or fewer cores than lanes, and then
+    /// [GameCores::assign] never gives out a lane
+    pub fn new(cores: Vec<usize>, lanes: usize) -> Self {
+        let lanes = if lanes < 2 || cores.len() < lanes {
+            vec![]
+        } else {
+            (0..lanes)
+                .map(|lane| {
+                    cores[lane * cores.len() / lanes..(lane + 1) * cores.len() / lanes].to_vec()
+                })
+                .collect()
+        };
+
+        Self {
+            lanes,
+            assignments: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether there are any lanes to give out
+    pub fn enabled(&self) -> bool {
+        !self.lanes.is_empty()
+    }
+
+    /// The lane for the next search of this game
+    ///
+    /// A game keeps its lane from one search to the next. A new game gets the first free lane,
+    /// and None when every lane is taken, in which case its search runs wherever the OS puts it
+    pub fn assign(&self, game_id: &str) -> Option<CoreLane> {
+        if !self.enabled() {
+            return None;
+        }
+
+        let now = Instant::now();
+        let mut assignments = self.assignments.lock().unwrap();
+        assignments.retain(|id, assignment| {
+            id == game_id || now.duration_since(assignment.last_search) < IDLE_AFTER
+        });
+
+        let lane = match assignments.get_mut(game_id) {
+            Some(assignment) => {
+                assignment.last_search = now;
+                assignment.lane
+            }
+            None => {
+                let lane = (0..self.lanes.len())
+                    .find(|lane| assignments.values().all(|a| a.lane != *lane))?;
+                assignments.insert(
+                    game_id.to_owned(),
+                    Assignment {
+                        lane,
+                        last_search: now,
+                    },
+                );
+                lane
+            }
+        };
+
+        Some(CoreLane {
+            lane,
+            cores: self.lanes[lane].clone(),
+        })
+    }
+
+    /// Free up the lane of a game that's over
+    pub fn release(&self, game_id: &str) {
+        self.assignments.lock().unwrap().remove(game_id);
+    }
+}
+
+static GAME_CORES: OnceLock<GameCores> = OnceLock::new();
+
+/// The lanes every search shares
+///
+/// `GAME_CORE_LANES` sets how many lanes the cores this process is allowed to run on are split
+/// into, and pinning is off unless it's at least 2
+pub fn game_cores() -> &'static GameCores {
+    GAME_CORES.get_or_init(|| {
+        let lanes = match std::env::var("GAME_CORE_LANES").map(|x| x.parse()) {
+            Ok(Ok(x)) => x,
+            _ => 0,
+        };
+
+        let cores = if lanes >= 2 {
+            affinity::current().unwrap_or_default()
+        } else {
+            vec![]
+        };
+
+        GameCores::new(cores, lanes)
+    })
+}
+
+/// Keeps the current thread on a [CoreLane] until it's dropped, and then puts it back on the
+/// cores it could run on before
+pub(crate) struct PinnedThread {
+    previous: Option<Vec<usize>>,
+}
+
+impl PinnedThread {
+    pub(crate) fn pin(lane: &CoreLane) -> Self {
+        let previous = affinity::current();
+        if previous.is_some() && !affinity::set(&lane.cores) {
+            tracing::warn!(%lane, "Couldn't pin the search to its lane");
+
+            return Self { previous: None };
+        }
+
+        Self { previous }
+    }
+}
+
+impl Drop for PinnedThread {
+    fn drop(&mut self) {
+        if let Some(previous) = &self.previous {
+            affinity::set(previous);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod affinity {
+    /// The same size as glibc's `cpu_set_t`, which is enough for 1024 cores
+    const MASK_WORDS: usize = 16;
+
+    extern "C" {
+        fn sched_getaffinity(pid: i32, cpusetsize: usize, mask: *mut u64) -> i32;
+        fn sched_setaffinity(pid: i32, cpusetsize: usize, mask: *const u64) -> i32;
+    }
+
+    /// The cores the current thread is allowed to run on
+    pub(super) fn current() -> Option<Vec<usize>> {
+        let mut mask = [0u64; MASK_WORDS];
+        // SAFETY: the mask is as big as we say it is. A pid of 0 is the calling thread
+        let result =
+            unsafe { sched_getaffinity(0, std::mem::size_of_val(&mask), mask.as_mut_ptr()) };
+        if result != 0 {
+            return None;
+        }
+
+        Some(
+            (0..MASK_WORDS * 64)
+                .filter(|core| mask[core / 64] & (1 << (core % 64)) != 0)
+                .collect(),
+        )
+    }
+
+    /// Only let the current thread run on `cores`. Returns whether that worked
+    pub(super) fn set(cores: &[usize]) -> bool {
+        let mut mask = [0u64; MASK_WORDS];
+        for core in cores.iter().filter(|core| **core < MASK_WORDS * 64) {
+            mask[core / 64] |= 1 << (core % 64);
+        }
+
+        // SAFETY: the mask is as big as we say it is
+        unsafe { sched_setaffinity(0, std::mem::size_of_val(&mask), mask.as_ptr()) == 0 }
+    }
+}
+
+/// Other platforms don't get pinned
+#[cfg(not(target_os = "linux"))]
+mod affinity {
+    pub(super) fn current() -> Option<Vec<usize>> {
+        None
+    }
+
+    pub(super) fn set(_cores: &[usize]) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_games_get_their_own_lane_until_they_end() {
+        let game_cores = GameCores::new((0..8).collect(), 2);
+
+        let first = game_cores.assign("first").unwrap();
+        let second = game_cores.assign("second").unwrap();
+        assert_eq!(first.cores, vec![0, 1, 2, 3]);
+        assert_eq!(second.cores, vec![4, 5, 6, 7]);
+        assert_eq!(first.to_string(), "lane 0 (cores 0,1,2,3)");
+
+        // Every lane is taken, so a third game isn't pinned
+        assert_eq!(game_cores.assign("third"), None);
+        assert_eq!(game_cores.assign("first"), Some(first.clone()));
+
+        game_cores.release("first");
+        assert_eq!(game_cores.assign("third"), Some(first));
+    }
+
+    #[test]
+    fn test_pinning_is_off_without_enough_lanes_or_cores() {
+        assert!(!GameCores::new((0..8).collect(), 1).enabled());
+        assert!(!GameCores::new(vec![0], 2).enabled());
+        assert_eq!(GameCores::new(vec![0], 2).assign("game"), None);
+
+        let uneven = GameCores::new(vec![0, 2, 4], 2);
+        assert_eq!(uneven.assign("a").unwrap().cores, vec![0]);
+        assert_eq!(uneven.assign("b").unwrap().cores, vec![2, 4]);
+    }
+}
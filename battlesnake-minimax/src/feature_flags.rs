@@ -0,0 +1,109 @@
+//! Toggles for the experimental parts of the search
+//!
+//! Each flag turns one experimental feature of the search on or off, so a deployment can try a
+//! feature out, or turn one off that is misbehaving, without a new build. The flags are passed in
+//! through [crate::paranoid::SnakeOptions::features], and every search records the ones that were
+//! on in its span
+
+use std::fmt::Display;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Which experimental search features are turned on
+///
+/// The defaults are what we play with when nothing is configured
+pub struct FeatureFlags {
+    /// Remember leaf scores by board hash within a deepened search, see
+    /// [crate::paranoid::ScoreMemo]
+    ///
+    /// Defaults to on
+    pub score_memo: bool,
+    /// When every move loses, pick the one that holds on the longest instead of the one the
+    /// search happened to rank first
+    ///
+    /// Defaults to on
+    pub panic_mode: bool,
+    /// Run background searches on the other cores in the [crate::lazy_smp::LazySmpSnake]
+    ///
+    /// With this off the Lazy SMP snake only runs its main search
+    ///
+    /// Defaults to on
+    pub parallel_search: bool,
+    /// Keep the [crate::paranoid::FeatureSnapshot] of every leaf on the leaf, so we can see what
+    /// each term of the score contributed at the end of the line we picked. See
+    /// [crate::paranoid::MinMaxReturn::principal_variation_breakdown]
+    ///
+    /// This runs the snapshot for every leaf we score, so it's only meant for tuning
+    ///
+    /// Defaults to off
+    pub leaf_breakdown: bool,
+    /// Run the deepened search on the thread that asked for it, a step at a time with a
+    /// [crate::paranoid::DeepeningStepper], instead of on the [crate::search_pool]
+    ///
+    /// This is for hosts without threads to spare, like Lambda and WASM, so it also turns off
+    /// the background searches of [FeatureFlags::parallel_search]
+    ///
+    /// Defaults to off
+    pub cooperative_search: bool,
+    /// Hold some of the budget back from the main search, and spend it searching only the move
+    /// it picked a little deeper. If that finds a forced loss we make the next best move instead,
+    /// see [crate::paranoid::Verification]
+    ///
+    /// Defaults to off
+    pub verify_move: bool,
+    /// Search through the food a snake's [crate::paranoid::FoodSpawner] says could spawn, at
+    /// every turn boundary, see [crate::paranoid::SnakeOptions::food_spawn_chance]
+    ///
+    /// Every spawn is another board to search, so this costs a lot of depth
+    ///
+    /// Defaults to off
+    pub food_spawns: bool,
+}
+
+impl Default for FeatureFlags {
+    fn default() -> Self {
+        Self {
+            score_memo: true,
+            panic_mode: true,
+            parallel_search: true,
+            leaf_breakdown: false,
+            cooperative_search: false,
+            verify_move: false,
+            food_spawns: false,
+        }
+    }
+}
+
+impl FeatureFlags {
+    /// The name of each flag, along with whether it is on
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, bool)> {
+        [
+            ("score_memo", self.score_memo),
+            ("panic_mode", self.panic_mode),
+            ("parallel_search", self.parallel_search),
+            ("leaf_breakdown", self.leaf_breakdown),
+            ("cooperative_search", self.cooperative_search),
+            ("verify_move", self.verify_move),
+            ("food_spawns", self.food_spawns),
+        ]
+        .into_iter()
+    }
+}
+
+/// The names of the flags that are on, separated by commas, or `none`
+///
+/// This is the form the flags are recorded in on spans
+impl Display for FeatureFlags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let enabled: Vec<&str> = self
+            .iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(name, _)| name)
+            .collect();
+
+        if enabled.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", enabled.join(","))
+        }
+    }
+}
@@ -1,16 +1,67 @@
-use std::{fmt::Debug, hash::Hash, sync::Arc, thread};
+use std::{
+    fmt::Debug,
+    hash::Hash,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use battlesnake_game_types::{types::*, wire_representation::NestedGame};
 use dashmap::DashMap;
 use derivative::Derivative;
 use fxhash::FxBuildHasher;
+use itertools::Itertools;
 use tracing::info_span;
 
 use crate::{
-    paranoid::{move_ordering::MoveOrdering, CachedScore, Scorable, SnakeOptions},
-    Instruments, ParanoidMinimaxSnake,
+    board_hash::BoardHashable,
+    paranoid::{
+        move_ordering::MoveOrdering, simulate_one, CachedScore, RootStrategy, Scorable,
+        SnakeOptions,
+    },
+    search_pool::{search_pool, Priority},
+    Instruments, ParanoidMinimaxSnake, SearchStatus, SearchStepper,
 };
 
+/// The scores a [LazySmpSnake] shares between its searches, keyed by the board
+///
+/// Every search that reaches a board someone else already scored gets the score from here, no
+/// matter which moves it took to get there. A snake that keeps the same table for every turn of a
+/// game, see [LazySmpSnake::with_transposition_table], also gets the boards it scored on earlier
+/// turns or while it was pondering
+pub type TranspositionTable<GameType, ScoreType> = Arc<DashMap<GameType, ScoreType, FxBuildHasher>>;
+
+/// A search of the board we expect on our next turn, for while we wait for the engine's next
+/// request, see [LazySmpSnake::choose_move_and_ponder]
+///
+/// It only searches when it's stepped, like any other [SearchStepper]. That leaves it to the host
+/// to decide when it has time to spare, and which of its games gets that time
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct Ponder<GameType> {
+    expected: GameType,
+    #[derivative(Debug = "ignore")]
+    stepper: Box<dyn SearchStepper + Send>,
+}
+
+impl<GameType: PartialEq> Ponder<GameType> {
+    /// Whether the engine sent us the board we pondered, so the search for it starts with its
+    /// positions already in the [TranspositionTable]
+    pub fn is_hit(&self, actual: &GameType) -> bool {
+        &self.expected == actual
+    }
+}
+
+impl<GameType> SearchStepper for Ponder<GameType> {
+    fn step(&mut self, budget: Duration) -> SearchStatus {
+        self.stepper.step(budget)
+    }
+
+    /// The move we'd make on the pondered board
+    fn best_move(&self) -> Option<Move> {
+        self.stepper.best_move()
+    }
+}
+
 #[derive(Derivative, Clone)]
 #[derivative(Debug)]
 #[allow(missing_docs)]
@@ -21,7 +72,7 @@ where
     ScorableType: Scorable<GameType, ScoreType> + Sized + Send + Sync + 'static + Clone,
     CachedScore<ScorableType, GameType, ScoreType>: Scorable<GameType, ScoreType>,
 {
-    cache: Arc<DashMap<GameType, ScoreType, FxBuildHasher>>,
+    cache: TranspositionTable<GameType, ScoreType>,
     main_snake: ParanoidMinimaxSnake<
         GameType,
         ScoreType,
@@ -47,6 +98,8 @@ where
         + HeadGettableGame
         + NeighborDeterminableGame
         + NeckQueryableGame
+        + SnakeBodyGettableGame
+        + BoardHashable
         + SimulableGame<Instruments, N_SNAKES>
         + Clone
         + Sync
@@ -69,13 +122,36 @@ where
         name: &'static str,
         options: SnakeOptions,
     ) -> Self {
-        let cache: DashMap<GameType, ScoreType, FxBuildHasher> = Default::default();
-        let cache = Arc::new(cache);
+        Self::with_transposition_table(
+            game,
+            game_info,
+            turn,
+            score_function,
+            name,
+            options,
+            Default::default(),
+        )
+    }
+
+    /// The same as [LazySmpSnake::new], but the searches share `cache` instead of starting with an
+    /// empty [TranspositionTable]
+    pub fn with_transposition_table(
+        game: GameType,
+        game_info: NestedGame,
+        turn: i32,
+        score_function: ScorableType,
+        name: &'static str,
+        options: SnakeOptions,
+        cache: TranspositionTable<GameType, ScoreType>,
+    ) -> Self {
         let cached_score = CachedScore::new(score_function, cache.clone());
 
+        // The main search runs on the thread that asked for the move. The helpers queue on the
+        // search pool, so they can never hold it up
         let main_options = {
-            let mut options = options;
+            let mut options = options.clone();
             options.move_ordering = MoveOrdering::BestFirst;
+            options.features.cooperative_search = true;
             options
         };
 
@@ -91,6 +167,9 @@ where
         let background_options = {
             let mut options = options;
             options.move_ordering = MoveOrdering::Random;
+            // Only the main snake's move gets played, so the background snakes don't need to
+            // spend any of their time on the root
+            options.root_strategy = RootStrategy::Paranoid;
             options
         };
 
@@ -111,33 +190,123 @@ where
     }
 
     pub fn choose_move(&self) -> Move {
+        self.choose_move_and_ponder(false).0
+    }
+
+    /// Pick the next move to make, the same as [LazySmpSnake::choose_move()]
+    ///
+    /// With `ponder` this also sets up a search of the board we expect next turn, where everyone
+    /// made the move our search expects of them. Stepping the [Ponder] fills the
+    /// [TranspositionTable] for our next turn. There's nothing to ponder when the game is about to
+    /// be over
+    pub fn choose_move_and_ponder(&self, ponder: bool) -> (Move, Option<Ponder<GameType>>) {
         info_span!(
           "lazy_smp",
-          snake_name = self.main_snake.name,
-          game_id = %&self.main_snake.game_info.id,
+          snake_name = self.main_snake.settings.name,
+          game_id = %&self.main_snake.settings.game_info.id,
           turn = self.main_snake.turn,
-          ruleset_name = %self.main_snake.game_info.ruleset.name,
-          ruleset_version = %self.main_snake.game_info.ruleset.version,
+          ruleset_name = %self.main_snake.settings.game_info.ruleset.name,
+          ruleset_version = %self.main_snake.settings.game_info.ruleset.version,
           depth = tracing::field::Empty,
+          pondering = tracing::field::Empty,
         )
         .in_scope(|| {
-            let num_background_snakes: usize = std::thread::available_parallelism()
-                .map(|x| x.into())
-                .map(|x: usize| x / 2)
-                .unwrap_or(1);
-
-            for _ in 0..num_background_snakes {
-                let snake = self.background_snake.clone();
-                thread::spawn(move || {
-                    snake.choose_move();
-                });
-            }
+            // A cooperative search is for hosts without threads to spare, so it doesn't get any
+            // background searches either. The main search always runs cooperatively, so the flag
+            // we were asked for is on the background snake
+            let features = self.background_snake.settings.options.features;
+            let num_background_snakes: usize =
+                if features.parallel_search && !features.cooperative_search {
+                    std::thread::available_parallelism()
+                        .map(|x| x.into())
+                        .map(|x: usize| x / 2)
+                        .unwrap_or(1)
+                        .min(search_pool().idle_threads())
+                } else {
+                    0
+                };
+
+            // The background searches only exist to fill the shared cache, so they only take
+            // threads the search pool has free, and can't make another game's search wait. They
+            // stop on their own at the deadline, and as soon as the main search is done
+            let background = &self.background_snake;
+            let sorted_ids = self.sorted_ids();
+            let deadline = Instant::now() + background.max_duration();
+            let background_workers = (0..num_background_snakes)
+                .map(|_| {
+                    let score_memo = background
+                        .settings
+                        .options
+                        .features
+                        .score_memo
+                        .then(Default::default);
+
+                    background.spawn_deepening_worker(
+                        sorted_ids.clone(),
+                        None,
+                        score_memo,
+                        Instruments::default(),
+                        deadline,
+                        Priority::Background,
+                    )
+                })
+                .collect_vec();
 
-            let (m, depth) = self.main_snake.choose_move().unwrap();
+            let (m, _, stats, scored) = self
+                .main_snake
+                .choose_move_with_search(0)
+                .expect("The main search always has a move for us");
             let current_span = tracing::Span::current();
-            current_span.record("depth", depth);
+            current_span.record("depth", stats.depth);
+
+            for worker in &background_workers {
+                worker.stop();
+            }
+
+            let route = scored.chosen_route();
+            let ponder = route
+                .get(..sorted_ids.len())
+                .filter(|_| ponder)
+                .and_then(|next_turn| self.ponder(next_turn));
+            current_span.record("pondering", ponder.is_some());
 
-            m
+            (m, ponder)
         })
     }
+
+    fn sorted_ids(&self) -> Vec<GameType::SnakeIDType> {
+        let my_id = self.main_snake.game.you_id();
+        let mut sorted_ids = self.main_snake.game.get_snake_ids();
+        sorted_ids.sort_by_key(|snake_id| if snake_id == my_id { -1 } else { 1 });
+
+        sorted_ids
+    }
+
+    fn ponder(&self, next_turn: &[(GameType::SnakeIDType, Move)]) -> Option<Ponder<GameType>> {
+        let expected = simulate_one::<_, N_SNAKES>(&self.main_snake.game, next_turn);
+        if expected
+            .get_snake_ids()
+            .iter()
+            .filter(|sid| expected.is_alive(sid))
+            .count()
+            < 2
+        {
+            return None;
+        }
+
+        let mut ponderer = self.main_snake.clone();
+        ponderer.game = expected;
+        ponderer.turn += 1;
+        let stepper = ponderer.stepper(self.sorted_ids(), None);
+
+        Some(Ponder {
+            expected,
+            stepper: Box::new(stepper),
+        })
+    }
+
+    /// How many boards are in the [TranspositionTable] the searches share
+    pub fn transposition_table_size(&self) -> usize {
+        self.cache.len()
+    }
 }
@@ -7,8 +7,9 @@
 //! This crate implements the minimax algorithm for the battlesnake game. You provide a 'scoring'
 //! function that turns a given board into anything that implements the `Ord` trait.
 //!
-//! There are multiple variants to multiplayer minimax. This crate currently only supports the
-//! `paranoid` variant, which can be found in the [paranoid] module
+//! There are multiple variants to multiplayer minimax. This crate supports the `paranoid`
+//! variant, which can be found in the [paranoid] module, and the `maxn` variant in the [maxn]
+//! module
 //! For more information check out my [Minimax Blog Post](https://coreyja.com/BattlesnakeMinimax/Minimax%20in%20Battlesnake/)
 //!
 //! We lean on the [types] crate for the game logic, and in particular for the
@@ -64,10 +65,38 @@ pub use battlesnake_game_types as types;
 
 pub mod paranoid;
 
+pub mod maxn;
+
 pub use paranoid::MinimaxSnake as ParanoidMinimaxSnake;
 
 pub use dashmap;
 
+pub mod board_hash;
+
+pub use board_hash::BoardHashable;
+
+pub mod feature_flags;
+
+pub mod checked_simulation;
+
+pub mod neck;
+
+pub mod search_pool;
+
+pub mod core_pinning;
+
+pub mod batch;
+
+pub mod stepper;
+
+pub use stepper::{SearchStatus, SearchStepper};
+
+pub mod simulation_stats;
+
+pub use simulation_stats::{Instruments, SimulationStats};
+
+pub use feature_flags::FeatureFlags;
+
 #[allow(missing_docs)]
 pub mod lazy_smp;
 
@@ -80,23 +109,36 @@ pub struct MoveOutput {
     pub shout: Option<String>,
 }
 
-#[derive(Debug, Clone, Copy)]
-/// Any empty struct that implements `SimulatorInstruments` as a no-op which can be used when you don't want
-/// to time the simulation
-pub struct Instruments {}
-
 #[cfg(test)]
 mod tests {
     use battlesnake_game_types::{
-        compact_representation::{dimensions::Custom, WrappedCellBoard},
-        types::{build_snake_id_map, Move, SimulableGame, SnakeIDGettableGame},
-        wire_representation::Game,
+        compact_representation::{
+            dimensions::Custom, StandardCellBoard4Snakes11x11, WrappedCellBoard,
+        },
+        types::{
+            build_snake_id_map, HealthGettableGame, Move, SimulableGame, SnakeIDGettableGame,
+            SnakeId, VictorDeterminableGame, YouDeterminableGame,
+        },
+        wire_representation::{Game, NestedGame},
     };
     use itertools::Itertools;
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            mpsc, Arc,
+        },
+        time::{Duration, Instant},
+    };
 
     use crate::{
-        paranoid::{MinMaxReturn, MinimaxSnake, SnakeOptions, WrappedScore},
-        Instruments,
+        lazy_smp::{LazySmpSnake, TranspositionTable},
+        paranoid::{
+            move_ordering::{MoveOrdering, MoveOrderingContext},
+            DepthCutoffs, MinMaxReturn, MinimaxSnake, RootProof, RootStrategy, ScoreMemo,
+            SnakeOptions, Verification, WrappedScore, MIN_SEARCH_BUDGET,
+        },
+        search_pool::{search_pool, Priority},
+        FeatureFlags, Instruments, SearchStatus, SearchStepper,
     };
 
     #[test]
@@ -121,7 +163,7 @@ mod tests {
 
         let result = explorer.deepend_minimax_to_turn(50);
 
-        let mut next_moves = game.simulate(&Instruments {}, game.get_snake_ids());
+        let mut next_moves = game.simulate(&Instruments::default(), game.get_snake_ids());
         let chosen_next = next_moves
             .find(|(action, _)| {
                 (*action).into_inner() == [Some(Move::Down), Some(Move::Left), None, None]
@@ -159,4 +201,1062 @@ mod tests {
             result.score()
         );
     }
+
+    #[test]
+    fn opponent_depth_reduction_still_finds_a_safe_move() {
+        let fixture = include_str!("../../battlesnake-rs/fixtures/start_of_game.json");
+        let wire_game: Game = serde_json::from_str(fixture).unwrap();
+        let snake_ids = build_snake_id_map(&wire_game);
+        let game_info = wire_game.game.clone();
+
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(wire_game, &snake_ids)
+            .expect("Fixture data should be a valid game");
+
+        let options = SnakeOptions {
+            opponent_depth_reduction: 1,
+            ..Default::default()
+        };
+        let reduced =
+            MinimaxSnake::from_fn_with_options(game, game_info, 0, &|_| (), "reduced", options);
+
+        let result = reduced.deepend_minimax_to_turn(4);
+
+        assert!(result.your_best_move(game.you_id()).is_some());
+        assert!(
+            !matches!(result.score(), WrappedScore::Lose(..)),
+            "The start of the game should never be a forced loss, but was {:?}",
+            result.score()
+        );
+    }
+
+    #[test]
+    fn custom_move_ordering_is_used_without_changing_the_result() {
+        let fixture = include_str!("../../battlesnake-rs/fixtures/start_of_game.json");
+        let wire_game: Game = serde_json::from_str(fixture).unwrap();
+        let snake_ids = build_snake_id_map(&wire_game);
+        let game_info = wire_game.game.clone();
+
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(wire_game, &snake_ids)
+            .expect("Fixture data should be a valid game");
+
+        let best_first = MinimaxSnake::from_fn(game, game_info.clone(), 0, &my_health, "best")
+            .deepend_minimax_to_turn(2);
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let options = SnakeOptions {
+            move_ordering: MoveOrdering::Custom({
+                let calls = calls.clone();
+                Arc::new(move |m: Move, context: &MoveOrderingContext| {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    assert!(context.health > 0);
+
+                    // Always look at our own Left first, whatever the last iteration thought of it
+                    if context.is_you && m == Move::Left {
+                        1
+                    } else {
+                        0
+                    }
+                })
+            }),
+            ..Default::default()
+        };
+        let custom =
+            MinimaxSnake::from_fn_with_options(game, game_info, 0, &my_health, "custom", options)
+                .deepend_minimax_to_turn(2);
+
+        assert!(calls.load(Ordering::SeqCst) > 0);
+        assert_eq!(custom.score(), best_first.score());
+    }
+
+    #[test]
+    fn cloned_options_share_their_custom_move_ordering() {
+        let options = SnakeOptions {
+            move_ordering: MoveOrdering::Custom(Arc::new(|_: Move, _: &MoveOrderingContext| 0)),
+            ..Default::default()
+        };
+        let cloned = options.clone();
+
+        let (MoveOrdering::Custom(original), MoveOrdering::Custom(cloned)) =
+            (&options.move_ordering, &cloned.move_ordering)
+        else {
+            panic!("Cloning the options should keep the custom move ordering");
+        };
+        assert!(Arc::ptr_eq(original, cloned));
+    }
+
+    #[test]
+    fn clones_share_their_search_settings() {
+        let fixture = include_str!("../../battlesnake-rs/fixtures/start_of_game.json");
+        let wire_game: Game = serde_json::from_str(fixture).unwrap();
+        let snake_ids = build_snake_id_map(&wire_game);
+        let game_info = wire_game.game.clone();
+
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(wire_game, &snake_ids)
+            .expect("Fixture data should be a valid game");
+
+        let snake = MinimaxSnake::from_fn(game, game_info, 0, &my_health, "shared");
+        let copy = snake.clone();
+        assert!(Arc::ptr_eq(&snake.settings, &copy.settings));
+
+        // Changing the settings of one snake leaves the snakes it was cloned from alone
+        let with_spawner = copy.with_food_spawner(Arc::new(count_food_spawns));
+        assert!(!Arc::ptr_eq(&snake.settings, &with_spawner.settings));
+    }
+
+    /// How many snakes move along the principal variation before we hit a leaf
+    fn principal_variation_length<ScoreType>(
+        result: &MinMaxReturn<StandardCellBoard4Snakes11x11, ScoreType>,
+    ) -> usize
+    where
+        ScoreType: Clone + std::fmt::Debug + PartialOrd + Ord + Copy,
+    {
+        let mut length = 0;
+        let mut current = result;
+        while let MinMaxReturn::Node { options, .. } = current {
+            length += 1;
+            current = &options.first().unwrap().1;
+        }
+
+        length
+    }
+
+    fn forced_corridor() -> (StandardCellBoard4Snakes11x11, NestedGame) {
+        let fixture = include_str!("../../fixtures/forced_corridor.json");
+        let wire_game: Game = serde_json::from_str(fixture).unwrap();
+        let snake_ids = build_snake_id_map(&wire_game);
+        let game_info = wire_game.game.clone();
+
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(wire_game, &snake_ids)
+            .expect("Fixture data should be a valid game");
+
+        (game, game_info)
+    }
+
+    #[test]
+    fn forced_opponent_moves_do_not_use_up_depth() {
+        let (game, game_info) = forced_corridor();
+
+        let normal = MinimaxSnake::from_fn(game, game_info.clone(), 0, &my_health, "normal")
+            .single_minimax(1);
+        assert_eq!(principal_variation_length(&normal), 2);
+
+        let options = SnakeOptions {
+            forced_move_extension: 2,
+            ..Default::default()
+        };
+        let extended =
+            MinimaxSnake::from_fn_with_options(game, game_info, 0, &my_health, "extended", options)
+                .single_minimax(1);
+        assert!(
+            principal_variation_length(&extended) >= 4,
+            "The corridor snake was forced, so we should have searched another turn"
+        );
+
+        // Our body blocks the corridor snake in, so going down is the only move we search for it
+        let MinMaxReturn::Node { options, .. } = &extended else {
+            panic!("The root should be a node");
+        };
+        let MinMaxReturn::Node {
+            options: corridor_options,
+            is_maximizing,
+            ..
+        } = &options[0].1
+        else {
+            panic!("The corridor snake should move next");
+        };
+        assert!(!is_maximizing);
+        assert_eq!(
+            corridor_options.iter().map(|(m, _)| *m).collect_vec(),
+            vec![Move::Down]
+        );
+    }
+
+    #[test]
+    fn forced_move_extension_keeps_scores_on_turn_boundaries() {
+        let (game, game_info) = forced_corridor();
+        let mut players = game.get_snake_ids();
+        players.sort_by_key(|id| if id == game.you_id() { -1 } else { 1 });
+
+        let options = SnakeOptions {
+            forced_move_extension: 3,
+            ..Default::default()
+        };
+        let result =
+            MinimaxSnake::from_fn_with_options(game, game_info, 0, &my_health, "extended", options)
+                .deepend_minimax_to_turn(3);
+
+        assert_moves_line_up_with_turns(&result, &players);
+    }
+
+    #[test]
+    fn alternatives_summary_lists_the_best_moves() {
+        let fixture = include_str!("../../battlesnake-rs/fixtures/start_of_game.json");
+        let wire_game: Game = serde_json::from_str(fixture).unwrap();
+        let snake_ids = build_snake_id_map(&wire_game);
+        let game_info = wire_game.game.clone();
+
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(wire_game, &snake_ids)
+            .expect("Fixture data should be a valid game");
+
+        let minimax = MinimaxSnake::from_fn(game, game_info, 0, &|_| (), "summary");
+        let result = minimax.deepend_minimax_to_turn(2);
+
+        let best_move = result.your_best_move(game.you_id()).unwrap();
+        let summary = result.alternatives_summary(game.you_id(), 3).unwrap();
+
+        assert!(summary.starts_with(&format!("{best_move}: ")), "{summary}");
+        assert_eq!(summary.split(" | ").count(), 3, "{summary}");
+        assert!(summary.len() <= 256);
+    }
+
+    #[test]
+    fn move_reasoning_explains_the_best_move() {
+        let fixture = include_str!("../../battlesnake-rs/fixtures/start_of_game.json");
+        let wire_game: Game = serde_json::from_str(fixture).unwrap();
+        let snake_ids = build_snake_id_map(&wire_game);
+        let game_info = wire_game.game.clone();
+
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(wire_game, &snake_ids)
+            .expect("Fixture data should be a valid game");
+
+        let minimax = MinimaxSnake::from_fn(game, game_info, 0, &my_health, "reasoning");
+        let result = minimax.deepend_minimax_to_turn(2);
+
+        let best_move = result.your_best_move(game.you_id()).unwrap();
+        let reasoning = result.move_reasoning(game.you_id()).unwrap();
+
+        assert_eq!(reasoning.chosen, best_move);
+        // Nothing can die in the first two turns, so every move is scored by the score function
+        assert!(reasoning.forced_losses.is_empty());
+        assert_eq!(reasoning.decided_in, None);
+        assert!(matches!(reasoning.score, WrappedScore::Scored(_)));
+        assert!(
+            reasoning
+                .to_string()
+                .starts_with(&format!("chose {best_move}: ")),
+            "{reasoning}"
+        );
+    }
+
+    /// A root where we're the one moving, with the given scores for our moves in the order the
+    /// search sorted them
+    fn root_with_scores(
+        you_id: SnakeId,
+        scores: Vec<(Move, WrappedScore<i64>)>,
+    ) -> MinMaxReturn<StandardCellBoard4Snakes11x11, i64> {
+        MinMaxReturn::Node {
+            is_maximizing: true,
+            score: scores[0].1,
+            options: scores
+                .into_iter()
+                .map(|(m, score)| {
+                    (
+                        m,
+                        MinMaxReturn::Leaf {
+                            score,
+                            breakdown: None,
+                        },
+                    )
+                })
+                .collect(),
+            moving_snake_id: you_id,
+            alpha_beta_cutoff: false,
+            depth: 0,
+            alpha: WrappedScore::worst_possible_score(),
+            beta: WrappedScore::best_possible_score(),
+        }
+    }
+
+    #[test]
+    fn root_proof_needs_every_other_move_to_lose() {
+        let fixture = include_str!("../../battlesnake-rs/fixtures/start_of_game.json");
+        let wire_game: Game = serde_json::from_str(fixture).unwrap();
+        let snake_ids = build_snake_id_map(&wire_game);
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(wire_game, &snake_ids)
+            .expect("Fixture data should be a valid game");
+        let you_id = *game.you_id();
+
+        let lose = |depth| WrappedScore::Lose(std::cmp::Reverse(2), depth);
+
+        let only_move = root_with_scores(
+            you_id,
+            vec![
+                (Move::Up, WrappedScore::Scored(10)),
+                (Move::Left, lose(6)),
+                (Move::Right, lose(2)),
+            ],
+        );
+        assert_eq!(
+            only_move.root_proof(&you_id),
+            Some(RootProof::OnlyMove { depth: 6 })
+        );
+
+        let forced_win = root_with_scores(
+            you_id,
+            vec![
+                (Move::Up, WrappedScore::Win(std::cmp::Reverse(4))),
+                (Move::Left, WrappedScore::Scored(10)),
+            ],
+        );
+        assert_eq!(
+            forced_win.root_proof(&you_id),
+            Some(RootProof::ForcedWin { depth: 4 })
+        );
+
+        let open = root_with_scores(
+            you_id,
+            vec![
+                (Move::Up, WrappedScore::Scored(10)),
+                (Move::Left, WrappedScore::Scored(5)),
+                (Move::Right, lose(2)),
+            ],
+        );
+        assert_eq!(open.root_proof(&you_id), None);
+
+        let losing_anyway =
+            root_with_scores(you_id, vec![(Move::Up, lose(8)), (Move::Left, lose(2))]);
+        assert_eq!(losing_anyway.root_proof(&you_id), None);
+    }
+
+    #[test]
+    fn cutoffs_are_added_up_by_depth() {
+        let fixture = include_str!("../../battlesnake-rs/fixtures/start_of_game.json");
+        let wire_game: Game = serde_json::from_str(fixture).unwrap();
+        let snake_ids = build_snake_id_map(&wire_game);
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(wire_game, &snake_ids)
+            .expect("Fixture data should be a valid game");
+        let you_id = *game.you_id();
+
+        let at_depth = |mut node: MinMaxReturn<_, _>, cutoff: bool| {
+            if let MinMaxReturn::Node {
+                depth,
+                alpha_beta_cutoff,
+                ..
+            } = &mut node
+            {
+                *depth = 1;
+                *alpha_beta_cutoff = cutoff;
+            }
+            node
+        };
+        // The third move we tried was good enough to cut off the fourth
+        let cut_off = at_depth(
+            root_with_scores(
+                you_id,
+                vec![
+                    (Move::Up, WrappedScore::Scored(3)),
+                    (Move::Left, WrappedScore::Scored(2)),
+                    (Move::Right, WrappedScore::Scored(1)),
+                ],
+            ),
+            true,
+        );
+        let searched = at_depth(
+            root_with_scores(you_id, vec![(Move::Up, WrappedScore::Scored(5))]),
+            false,
+        );
+
+        let mut root = root_with_scores(
+            you_id,
+            vec![
+                (Move::Up, WrappedScore::Scored(5)),
+                (Move::Left, WrappedScore::Scored(3)),
+            ],
+        );
+        if let MinMaxReturn::Node { options, .. } = &mut root {
+            options[0].1 = searched;
+            options[1].1 = cut_off;
+        }
+
+        let cutoffs = root.cutoffs_by_depth();
+        assert_eq!(
+            cutoffs,
+            vec![
+                DepthCutoffs {
+                    depth: 0,
+                    nodes: 1,
+                    cutoffs: 0,
+                    cutoff_move_index_sum: 0,
+                },
+                DepthCutoffs {
+                    depth: 1,
+                    nodes: 2,
+                    cutoffs: 1,
+                    cutoff_move_index_sum: 2,
+                },
+            ]
+        );
+        assert_eq!(cutoffs[0].average_cutoff_index(), None);
+        assert_eq!(cutoffs[1].average_cutoff_index(), Some(2.0));
+        assert_eq!(cutoffs[1].cutoff_rate(), Some(0.5));
+    }
+
+    #[test]
+    fn proven_searches_stop_before_their_budget() {
+        let fixture = include_str!("../../battlesnake-rs/fixtures/start_of_game.json");
+        let mut wire_game: Game = serde_json::from_str(fixture).unwrap();
+        // Long enough that running to the deadline would be obvious
+        wire_game.game.timeout = 2_000;
+        // We're boxed into the corner by our own body, so going up is the only move that doesn't
+        // run into something. The opponent is too far away to get in the way this turn
+        wire_game.board.snakes.truncate(2);
+        let you = &mut wire_game.board.snakes[0];
+        you.body = [(0, 0), (1, 0), (1, 1), (1, 2)]
+            .into_iter()
+            .map(|(x, y)| battlesnake_game_types::wire_representation::Position { x, y })
+            .collect();
+        you.head = you.body[0];
+        wire_game.you = you.clone();
+
+        let snake_ids = build_snake_id_map(&wire_game);
+        let game_info = wire_game.game.clone();
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(wire_game, &snake_ids)
+            .expect("Fixture data should be a valid game");
+        let mut players = game.get_snake_ids();
+        players.sort_by_key(|snake_id| if snake_id == game.you_id() { -1 } else { 1 });
+
+        let minimax = MinimaxSnake::from_fn(game, game_info, 0, &my_health, "proven");
+        let (stats, result) = minimax.deepened_minimax_with_stats(players, None);
+
+        assert_eq!(result.your_best_move(game.you_id()), Some(Move::Up));
+        assert!(
+            stats.time_used < stats.budget / 2,
+            "Used {:?} of {:?}",
+            stats.time_used,
+            stats.budget
+        );
+    }
+
+    static FOOD_SPAWNS: AtomicUsize = AtomicUsize::new(0);
+
+    /// Pretends food can spawn in two places, but doesn't change the board so the search should
+    /// come out the same as without spawns
+    fn count_food_spawns(
+        board: &StandardCellBoard4Snakes11x11,
+    ) -> Vec<StandardCellBoard4Snakes11x11> {
+        FOOD_SPAWNS.fetch_add(1, Ordering::SeqCst);
+
+        vec![*board, *board]
+    }
+
+    fn my_health(board: &StandardCellBoard4Snakes11x11) -> i64 {
+        board.get_health_i64(board.you_id())
+    }
+
+    #[test]
+    fn best_first_borrows_the_previous_iteration() {
+        let fixture = include_str!("../../battlesnake-rs/fixtures/start_of_game.json");
+        let wire_game: Game = serde_json::from_str(fixture).unwrap();
+        let snake_ids = build_snake_id_map(&wire_game);
+        let game_info = wire_game.game.clone();
+
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(wire_game, &snake_ids)
+            .expect("Fixture data should be a valid game");
+
+        let minimax = MinimaxSnake::from_fn(game, game_info, 0, &my_health, "borrowed");
+        let previous = minimax.deepend_minimax_to_turn(2);
+        let MinMaxReturn::Node { options, .. } = &previous else {
+            panic!("A two turn search should have a root node");
+        };
+
+        let ordered = MoveOrdering::BestFirst.order_moves(
+            &game,
+            game.you_id(),
+            0,
+            Some(&previous),
+            Move::all().into_iter(),
+        );
+
+        for (m, borrowed) in ordered {
+            let expected = options.iter().find(|(o, _)| *o == m).map(|(_, r)| r);
+            match (borrowed, expected) {
+                (Some(borrowed), Some(expected)) => assert!(std::ptr::eq(borrowed, expected)),
+                (None, None) => {}
+                _ => panic!("{m} should only have a previous result if the search looked at it"),
+            }
+        }
+    }
+
+    #[test]
+    fn score_memo_only_scores_each_board_once_per_parity() {
+        let memo = ScoreMemo::default();
+        let scored = AtomicUsize::new(0);
+        let score = |value: i64| {
+            scored.fetch_add(1, Ordering::SeqCst);
+            value
+        };
+
+        assert_eq!(memo.get_or_score(42, 2, || score(1)), 1);
+        assert_eq!(memo.get_or_score(42, 4, || score(2)), 1);
+        assert_eq!(memo.get_or_score(42, 3, || score(3)), 3);
+        assert_eq!(memo.get_or_score(7, 2, || score(4)), 4);
+
+        assert_eq!(scored.load(Ordering::SeqCst), 3);
+        assert_eq!(memo.lookups(), 4);
+        assert_eq!(memo.hits(), 1);
+    }
+
+    #[test]
+    fn deepened_search_reports_its_score_memo_hit_rate() {
+        let fixture = include_str!("../../battlesnake-rs/fixtures/start_of_game.json");
+        let wire_game: Game = serde_json::from_str(fixture).unwrap();
+        let snake_ids = build_snake_id_map(&wire_game);
+        let game_info = wire_game.game.clone();
+
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(wire_game, &snake_ids)
+            .expect("Fixture data should be a valid game");
+
+        // The fixture has a 500ms timeout, so this gives us a short search
+        let options = SnakeOptions {
+            network_latency_padding: Duration::from_millis(450),
+            ..Default::default()
+        };
+        let snake =
+            MinimaxSnake::from_fn_with_options(game, game_info, 0, &my_health, "memo", options);
+
+        let (stats, result) = snake.choose_move_with_stats(None);
+
+        assert!(result.your_best_move(game.you_id()).is_some());
+        assert!(stats.score_memo_lookups > 0);
+        assert!(stats.score_memo_hits <= stats.score_memo_lookups);
+        let hit_rate = stats.score_memo_hit_rate().unwrap();
+        assert!((0.0..=1.0).contains(&hit_rate));
+    }
+
+    #[test]
+    fn deepened_search_counts_its_simulations() {
+        let fixture = include_str!("../../battlesnake-rs/fixtures/start_of_game.json");
+        let wire_game: Game = serde_json::from_str(fixture).unwrap();
+        let snake_ids = build_snake_id_map(&wire_game);
+        let game_info = wire_game.game.clone();
+
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(wire_game, &snake_ids)
+            .expect("Fixture data should be a valid game");
+
+        let options = SnakeOptions {
+            network_latency_padding: Duration::from_millis(450),
+            ..Default::default()
+        };
+        let snake = MinimaxSnake::from_fn_with_options(
+            game,
+            game_info,
+            0,
+            &my_health,
+            "simulations",
+            options,
+        );
+
+        let (stats, _) = snake.choose_move_with_stats(None);
+
+        assert!(stats.simulations > 0);
+        assert!(stats.simulation_time <= stats.time_used);
+    }
+
+    #[test]
+    fn deadlines_shorter_than_the_padding_still_get_a_move() {
+        let fixture = include_str!("../../battlesnake-rs/fixtures/start_of_game.json");
+        let mut wire_game: Game = serde_json::from_str(fixture).unwrap();
+        // A proxy in front of us can leave us with less time than our 100ms padding
+        wire_game.game.timeout = 50;
+        let snake_ids = build_snake_id_map(&wire_game);
+        let game_info = wire_game.game.clone();
+
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(wire_game, &snake_ids)
+            .expect("Fixture data should be a valid game");
+
+        let snake = MinimaxSnake::from_fn(game, game_info, 0, &my_health, "late");
+        let (stats, result) = snake.choose_move_with_stats(None);
+
+        assert_eq!(stats.budget, MIN_SEARCH_BUDGET);
+        assert!(result.your_best_move(game.you_id()).is_some());
+    }
+
+    #[test]
+    fn turning_off_the_score_memo_flag_skips_the_memo() {
+        let fixture = include_str!("../../battlesnake-rs/fixtures/start_of_game.json");
+        let wire_game: Game = serde_json::from_str(fixture).unwrap();
+        let snake_ids = build_snake_id_map(&wire_game);
+        let game_info = wire_game.game.clone();
+
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(wire_game, &snake_ids)
+            .expect("Fixture data should be a valid game");
+
+        let options = SnakeOptions {
+            network_latency_padding: Duration::from_millis(450),
+            features: FeatureFlags {
+                score_memo: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let snake =
+            MinimaxSnake::from_fn_with_options(game, game_info, 0, &my_health, "no-memo", options);
+
+        let (stats, result) = snake.choose_move_with_stats(None);
+
+        assert!(result.your_best_move(game.you_id()).is_some());
+        assert_eq!(stats.score_memo_lookups, 0);
+        assert_eq!(stats.score_memo_hit_rate(), None);
+        assert_eq!(
+            FeatureFlags {
+                score_memo: false,
+                ..Default::default()
+            }
+            .to_string(),
+            "panic_mode,parallel_search"
+        );
+    }
+
+    #[test]
+    fn lazy_smp_answers_within_its_budget() {
+        let fixture = include_str!("../../battlesnake-rs/fixtures/start_of_game.json");
+        let wire_game: Game = serde_json::from_str(fixture).unwrap();
+        let snake_ids = build_snake_id_map(&wire_game);
+        let game_info = wire_game.game.clone();
+
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(wire_game, &snake_ids)
+            .expect("Fixture data should be a valid game");
+
+        let options = SnakeOptions {
+            network_latency_padding: Duration::from_millis(400),
+            features: FeatureFlags {
+                parallel_search: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let snake =
+            LazySmpSnake::<_, _, _, 4>::new(game, game_info, 0, &my_health, "lazy-smp", options);
+
+        // The main search runs on this thread, so it gets its 100ms no matter how many helpers
+        // there are or how busy the search pool is
+        let started_at = Instant::now();
+        snake.choose_move();
+
+        assert!(started_at.elapsed() < Duration::from_millis(400));
+    }
+
+    #[test]
+    fn lazy_smp_ponders_into_the_shared_transposition_table() {
+        let fixture = include_str!("../../battlesnake-rs/fixtures/start_of_game.json");
+        let wire_game: Game = serde_json::from_str(fixture).unwrap();
+        let snake_ids = build_snake_id_map(&wire_game);
+        let game_info = wire_game.game.clone();
+
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(wire_game, &snake_ids)
+            .expect("Fixture data should be a valid game");
+
+        let options = SnakeOptions {
+            network_latency_padding: Duration::from_millis(400),
+            ..Default::default()
+        };
+        let table: TranspositionTable<_, _> = Default::default();
+        let snake = LazySmpSnake::<_, _, _, 4>::with_transposition_table(
+            game,
+            game_info,
+            0,
+            &my_health,
+            "lazy-smp",
+            options,
+            table.clone(),
+        );
+
+        let (_, ponder) = snake.choose_move_and_ponder(true);
+        let scored_for_this_turn = table.len();
+        assert!(scored_for_this_turn > 0);
+        assert!(snake.transposition_table_size() >= scored_for_this_turn);
+
+        // Everyone has moved by next turn, so it's never the board we're on now
+        let mut ponder = ponder.expect("Nobody dies on the first turn");
+        assert!(!ponder.is_hit(&game));
+
+        ponder.step(Duration::from_millis(100));
+        assert!(table.len() > scored_for_this_turn);
+        assert!(ponder.best_move().is_some());
+    }
+
+    #[test]
+    fn cooperative_search_runs_on_this_thread() {
+        let fixture = include_str!("../../battlesnake-rs/fixtures/start_of_game.json");
+        let wire_game: Game = serde_json::from_str(fixture).unwrap();
+        let snake_ids = build_snake_id_map(&wire_game);
+        let game_info = wire_game.game.clone();
+
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(wire_game, &snake_ids)
+            .expect("Fixture data should be a valid game");
+
+        let options = SnakeOptions {
+            network_latency_padding: Duration::from_millis(450),
+            features: FeatureFlags {
+                cooperative_search: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let snake = MinimaxSnake::from_fn_with_options(
+            game,
+            game_info,
+            0,
+            &my_health,
+            "cooperative",
+            options,
+        );
+
+        let (stats, result) = snake.choose_move_with_stats(None);
+
+        assert!(result.your_best_move(game.you_id()).is_some());
+        assert!(stats.iterations > 0);
+        assert!(stats.simulations > 0);
+    }
+
+    #[test]
+    fn the_main_search_starts_when_the_pool_is_full_of_background_work() {
+        let fixture = include_str!("../../battlesnake-rs/fixtures/start_of_game.json");
+        let wire_game: Game = serde_json::from_str(fixture).unwrap();
+        let snake_ids = build_snake_id_map(&wire_game);
+        let game_info = wire_game.game.clone();
+
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(wire_game, &snake_ids)
+            .expect("Fixture data should be a valid game");
+
+        // More background work than the pool has threads, so some of it is still queued
+        let blocked_until = Instant::now() + Duration::from_secs(10);
+        let background = (0..search_pool().stats().threads + 2)
+            .map(|_| {
+                let (release, blocked) = mpsc::channel::<()>();
+                search_pool().spawn(blocked_until, Priority::Background, move || {
+                    blocked.recv().ok();
+                });
+
+                release
+            })
+            .collect_vec();
+
+        let options = SnakeOptions {
+            network_latency_padding: Duration::from_millis(400),
+            ..Default::default()
+        };
+        let snake = MinimaxSnake::from_fn_with_options(
+            game,
+            game_info,
+            0,
+            &my_health,
+            "saturated",
+            options,
+        );
+
+        let (stats, result) = snake.choose_move_with_stats(None);
+
+        for release in background {
+            release.send(()).ok();
+        }
+
+        // Either the reserved thread or this one deepened, instead of giving up after one turn
+        assert!(result.your_best_move(game.you_id()).is_some());
+        assert!(stats.iterations > 0);
+        assert!(
+            stats.time_used < stats.budget + Duration::from_millis(50),
+            "The search took {:?} of its {:?}",
+            stats.time_used,
+            stats.budget
+        );
+    }
+
+    #[test]
+    fn verifying_the_move_keeps_a_safe_opening() {
+        let fixture = include_str!("../../battlesnake-rs/fixtures/start_of_game.json");
+        let wire_game: Game = serde_json::from_str(fixture).unwrap();
+        let snake_ids = build_snake_id_map(&wire_game);
+        let game_info = wire_game.game.clone();
+
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(wire_game, &snake_ids)
+            .expect("Fixture data should be a valid game");
+
+        let options = SnakeOptions {
+            network_latency_padding: Duration::from_millis(450),
+            features: FeatureFlags {
+                verify_move: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let snake =
+            MinimaxSnake::from_fn_with_options(game, game_info, 0, &my_health, "verify", options);
+
+        let (stats, result) = snake.choose_move_with_stats(None);
+
+        assert!(result.your_best_move(game.you_id()).is_some());
+        // Nothing can force a loss this early, so the verification can't change our move
+        assert!(
+            matches!(
+                stats.verification,
+                Some(Verification::Confirmed | Verification::Unfinished | Verification::Skipped)
+            ),
+            "Expected the opening move to hold up, but got {:?}",
+            stats.verification
+        );
+    }
+
+    #[test]
+    fn the_stepper_picks_up_where_it_left_off() {
+        let fixture = include_str!("../../battlesnake-rs/fixtures/start_of_game.json");
+        let wire_game: Game = serde_json::from_str(fixture).unwrap();
+        let snake_ids = build_snake_id_map(&wire_game);
+        let game_info = wire_game.game.clone();
+
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(wire_game, &snake_ids)
+            .expect("Fixture data should be a valid game");
+
+        let snake = MinimaxSnake::from_fn(game, game_info, 0, &my_health, "stepper");
+        let mut players = game.get_snake_ids();
+        players.sort_by_key(|snake_id| if snake_id == game.you_id() { -1 } else { 1 });
+        let num_players = players.len();
+        let mut stepper = snake.stepper(players, None);
+
+        // Without any budget the first depth is started and given up straight away
+        assert_eq!(stepper.step(Duration::ZERO), SearchStatus::InProgress);
+        assert!(stepper.current().is_none());
+        assert_eq!(stepper.best_move(), None);
+
+        stepper.step(Duration::from_millis(100));
+        let (depth, _) = stepper.current().expect("The first turn fits in 100ms");
+        assert!(stepper.best_move().is_some());
+        assert_eq!(stepper.iterations(), depth / num_players);
+
+        // Giving up on the next depth keeps everything we already have
+        assert_eq!(stepper.step(Duration::ZERO), SearchStatus::InProgress);
+        assert_eq!(stepper.current().unwrap().0, depth);
+    }
+
+    #[test]
+    fn minimax_regret_root_keeps_the_chosen_option_first() {
+        let fixture = include_str!("../../battlesnake-rs/fixtures/start_of_game.json");
+        let wire_game: Game = serde_json::from_str(fixture).unwrap();
+        let snake_ids = build_snake_id_map(&wire_game);
+        let game_info = wire_game.game.clone();
+
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(wire_game, &snake_ids)
+            .expect("Fixture data should be a valid game");
+
+        let options = SnakeOptions {
+            network_latency_padding: Duration::from_millis(400),
+            root_strategy: RootStrategy::MinimaxRegret,
+            simultaneous_root_depth: 0,
+            ..Default::default()
+        };
+        let snake =
+            MinimaxSnake::from_fn_with_options(game, game_info, 0, &my_health, "regret", options);
+
+        let (_, result) = snake.choose_move_with_stats(None);
+
+        let root_options = result.first_options_for_snake(game.you_id()).unwrap();
+        let (chosen, chosen_return) = root_options.first().unwrap();
+        assert_eq!(result.your_best_move(game.you_id()), Some(*chosen));
+        assert_eq!(result.score(), chosen_return.score());
+        assert!(
+            !matches!(result.score(), WrappedScore::Lose(..)),
+            "The start of the game should never be a forced loss, but was {:?}",
+            result.score()
+        );
+    }
+
+    #[test]
+    fn food_spawn_chance_nodes_are_searched_at_turn_boundaries() {
+        let fixture = include_str!("../../battlesnake-rs/fixtures/start_of_game.json");
+        let wire_game: Game = serde_json::from_str(fixture).unwrap();
+        let snake_ids = build_snake_id_map(&wire_game);
+        let game_info = wire_game.game.clone();
+
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(wire_game, &snake_ids)
+            .expect("Fixture data should be a valid game");
+
+        let without_spawns =
+            MinimaxSnake::from_fn(game, game_info.clone(), 0, &my_health, "without")
+                .single_minimax(2);
+
+        let options = SnakeOptions {
+            food_spawn_chance: 0.5,
+            features: FeatureFlags {
+                food_spawns: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let with_spawns =
+            MinimaxSnake::from_fn_with_options(game, game_info, 0, &my_health, "with", options)
+                .with_food_spawner(Arc::new(count_food_spawns))
+                .single_minimax(2);
+
+        assert!(FOOD_SPAWNS.load(Ordering::SeqCst) > 0);
+        assert_eq!(with_spawns.score(), without_spawns.score());
+    }
+
+    static UNLIKELY_FOOD_SPAWNS: AtomicUsize = AtomicUsize::new(0);
+
+    #[test]
+    fn food_spawns_are_only_searched_when_they_can_be_the_median() {
+        let fixture = include_str!("../../battlesnake-rs/fixtures/start_of_game.json");
+        let wire_game: Game = serde_json::from_str(fixture).unwrap();
+        let snake_ids = build_snake_id_map(&wire_game);
+        let game_info = wire_game.game.clone();
+
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(wire_game, &snake_ids)
+            .expect("Fixture data should be a valid game");
+
+        let search = |food_spawn_chance, food_spawns| {
+            let options = SnakeOptions {
+                food_spawn_chance,
+                features: FeatureFlags {
+                    food_spawns,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            MinimaxSnake::from_fn_with_options(
+                game,
+                game_info.clone(),
+                0,
+                &my_health,
+                "unlikely",
+                options,
+            )
+            .with_food_spawner(Arc::new(|board: &StandardCellBoard4Snakes11x11| {
+                UNLIKELY_FOOD_SPAWNS.fetch_add(1, Ordering::SeqCst);
+
+                vec![*board]
+            }))
+            .single_minimax(2);
+        };
+
+        // Without the flag the chance never matters
+        search(1.0, false);
+        // The board without a spawn is more likely, so it would always be the median
+        search(0.3, true);
+        assert_eq!(UNLIKELY_FOOD_SPAWNS.load(Ordering::SeqCst), 0);
+
+        search(1.0, true);
+        assert!(UNLIKELY_FOOD_SPAWNS.load(Ordering::SeqCst) > 0);
+    }
+
+    fn assert_moves_line_up_with_turns<ScoreType>(
+        result: &MinMaxReturn<StandardCellBoard4Snakes11x11, ScoreType>,
+        players: &[SnakeId],
+    ) where
+        ScoreType: Clone + std::fmt::Debug + PartialOrd + Ord + Copy,
+    {
+        if let Some(terminal_depth) = result.score().terminal_depth() {
+            assert_eq!(
+                terminal_depth % players.len() as i64,
+                0,
+                "Terminal score {:?} wasn't on a turn boundary",
+                result.score()
+            );
+        }
+
+        if let MinMaxReturn::Node {
+            options,
+            moving_snake_id,
+            depth,
+            ..
+        } = result
+        {
+            assert_eq!(
+                &players[*depth as usize % players.len()],
+                moving_snake_id,
+                "The wrong snake moved at depth {depth}"
+            );
+
+            for (_, child) in options {
+                assert_moves_line_up_with_turns(child, players);
+            }
+        }
+    }
+
+    fn three_snake_elimination_game() -> (StandardCellBoard4Snakes11x11, Game) {
+        let fixture = include_str!("../../fixtures/three_snakes_mid_search_elimination.json");
+        let wire_game: Game = serde_json::from_str(fixture).unwrap();
+        let snake_ids = build_snake_id_map(&wire_game);
+
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(wire_game.clone(), &snake_ids)
+            .expect("Fixture data should be a valid game");
+
+        (game, wire_game)
+    }
+
+    #[test]
+    fn turn_boundaries_survive_a_mid_search_elimination() {
+        let (game, wire_game) = three_snake_elimination_game();
+
+        let mut players = game.get_snake_ids();
+        players.sort_by_key(|id| if id == game.you_id() { -1 } else { 1 });
+        assert_eq!(players.len(), 3);
+
+        let minimax = MinimaxSnake::from_fn(
+            game,
+            wire_game.game,
+            wire_game.turn,
+            &|_: &StandardCellBoard4Snakes11x11| (),
+            "elimination",
+        );
+
+        // The middle snake starves on its second move, so every search this deep sees it die
+        // part way through
+        let result = minimax.deepend_minimax_to_turn(4);
+
+        assert_moves_line_up_with_turns(&result, &players);
+        assert!(result.your_best_move(game.you_id()).is_some());
+    }
+
+    #[test]
+    fn eliminated_snakes_only_die_on_turn_boundaries() {
+        let (game, wire_game) = three_snake_elimination_game();
+
+        // Score by how many snakes are left, so a board that was advanced before every snake had
+        // moved would show up as a different score than the full turn
+        let minimax = MinimaxSnake::from_fn(
+            game,
+            wire_game.game,
+            wire_game.turn,
+            &|board: &StandardCellBoard4Snakes11x11| {
+                board
+                    .get_snake_ids()
+                    .iter()
+                    .filter(|id| board.is_alive(id))
+                    .count()
+            },
+            "alive",
+        );
+
+        let result = minimax.deepend_minimax_to_turn(3);
+
+        assert_eq!(
+            result.score(),
+            &WrappedScore::Scored(2),
+            "The starving snake should be dead at the end of the search, and we should be alive"
+        );
+    }
+
+    #[test]
+    fn dot_graph_labels_nodes_and_respects_the_depth_limit() {
+        let fixture = include_str!("../../battlesnake-rs/fixtures/start_of_game.json");
+        let wire_game: Game = serde_json::from_str(fixture).unwrap();
+        let snake_ids = build_snake_id_map(&wire_game);
+        let game_info = wire_game.game.clone();
+
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(wire_game, &snake_ids)
+            .expect("Fixture data should be a valid game");
+
+        let explorer = MinimaxSnake::from_fn(game, game_info, 0, &|_| (), "explorer");
+        let result = explorer.single_minimax(1);
+
+        let full = format!("{}", result.to_dot_graph(game.you_id()));
+        assert!(full.contains("Root"));
+        assert!(full.contains("to move"));
+        assert!(full.contains("Alpha: "));
+        assert!(full.contains("->"));
+
+        let root_only = format!("{}", result.to_dot_graph_to_depth(game.you_id(), 0));
+        assert!(root_only.contains("Root"));
+        assert!(!root_only.contains("->"));
+    }
 }
@@ -0,0 +1,556 @@
+use std::{borrow::Cow, cmp::Reverse, fmt::Debug, marker::PhantomData, time::Instant};
+
+use battlesnake_game_types::{
+    types::{
+        HeadGettableGame, HealthGettableGame, Move, NeckQueryableGame, NeighborDeterminableGame,
+        PositionGettableGame, SimulableGame, SnakeBodyGettableGame, SnakeIDGettableGame,
+        VictorDeterminableGame, YouDeterminableGame,
+    },
+    wire_representation::NestedGame,
+};
+use derivative::Derivative;
+use itertools::Itertools;
+use tracing::{info, info_span};
+
+use crate::{
+    checked_simulation::check_simulation_moves,
+    neck::moves_off_the_neck,
+    paranoid::{
+        is_turn_boundary, search_budget, AbortedEarly, DeepeningSearch, DeepeningStepper,
+        SnakeOptions, WrappedScore,
+    },
+    Instruments, SearchStepper,
+};
+
+use super::MaxnReturn;
+
+/// Scores a game board from the point of view of one of the snakes on it
+///
+/// This is the maxn version of [crate::paranoid::Scorable]. Each snake is only ever compared to
+/// itself, so the scores don't need to be on the same scale for every snake, but they do need to
+/// mean the same thing for a single snake on every board
+pub trait MaxnScorable<GameType: SnakeIDGettableGame, ScoreType> {
+    /// Score the board for the given snake, higher is better for it
+    fn score_for(&self, game: &GameType, snake_id: &GameType::SnakeIDType) -> ScoreType;
+}
+
+impl<GameType, ScoreType, FnLike> MaxnScorable<GameType, ScoreType> for FnLike
+where
+    GameType: SnakeIDGettableGame,
+    FnLike: Fn(&GameType, &GameType::SnakeIDType) -> ScoreType,
+{
+    fn score_for(&self, game: &GameType, snake_id: &GameType::SnakeIDType) -> ScoreType {
+        (self)(game, snake_id)
+    }
+}
+
+#[derive(Derivative, Clone)]
+#[derivative(Debug(bound = "GameType: Debug"))]
+/// This is the struct that wraps a game board and a [MaxnScorable] and can be used to run maxn
+///
+/// It takes the same [SnakeOptions] as [crate::paranoid::MinimaxSnake], but only uses the network
+/// latency padding to work out how long it can search for
+pub struct MaxnSnake<GameType, ScoreType, ScorableType, const N_SNAKES: usize> {
+    game: GameType,
+    game_info: NestedGame,
+    turn: i32,
+    #[derivative(Debug = "ignore")]
+    score_function: ScorableType,
+    name: &'static str,
+    options: SnakeOptions,
+    _phantom: PhantomData<ScoreType>,
+}
+
+impl<GameType, ScoreType, ScorableType, const N_SNAKES: usize>
+    MaxnSnake<GameType, ScoreType, ScorableType, N_SNAKES>
+where
+    GameType: SnakeIDGettableGame
+        + YouDeterminableGame
+        + PositionGettableGame
+        + HealthGettableGame
+        + VictorDeterminableGame
+        + HeadGettableGame
+        + NeighborDeterminableGame
+        + NeckQueryableGame
+        + SnakeBodyGettableGame
+        + SimulableGame<Instruments, N_SNAKES>
+        + Clone
+        + Debug,
+    GameType::SnakeIDType: Clone,
+    ScoreType: Clone + Debug + PartialOrd + Ord + Copy,
+    ScorableType: MaxnScorable<GameType, ScoreType>,
+{
+    /// Construct a new `MaxnSnake`
+    pub fn new(
+        game: GameType,
+        game_info: NestedGame,
+        turn: i32,
+        score_function: ScorableType,
+        name: &'static str,
+        options: SnakeOptions,
+    ) -> Self {
+        Self {
+            game,
+            game_info,
+            turn,
+            score_function,
+            name,
+            options,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// The players in the order they move in the tree, ourselves first
+    fn players(&self) -> Vec<GameType::SnakeIDType> {
+        let my_id = self.game.you_id();
+        let mut sorted_ids = self.game.get_snake_ids();
+        sorted_ids.sort_by_key(|snake_id| if snake_id == my_id { -1 } else { 1 });
+
+        sorted_ids
+    }
+
+    /// Pick the next move to make
+    ///
+    /// This uses [MaxnSnake::deepened_maxn_until_timelimit()] and returns the chosen move along
+    /// with the depth we searched to
+    pub fn choose_move(&self) -> Option<(Move, usize)> {
+        info_span!(
+          "deepened_maxn",
+          snake_name = self.name,
+          game_id = %&self.game_info.id,
+          turn = self.turn,
+          ruleset_name = %self.game_info.ruleset.name,
+          ruleset_version = %self.game_info.ruleset.version,
+          chosen_direction = tracing::field::Empty,
+          depth = tracing::field::Empty,
+        )
+        .in_scope(|| {
+            if self.game.get_snake_ids().len() == 1 {
+                info!("We are the only snake left on the board, lets go Right");
+
+                return Some((Move::Right, 0));
+            }
+
+            let (depth, scored) = self.deepened_maxn_until_timelimit();
+            let chosen = scored.best_move_for(self.game.you_id())?;
+
+            let current_span = tracing::Span::current();
+            current_span.record("chosen_direction", format!("{chosen}").as_str());
+            current_span.record("depth", depth);
+
+            Some((chosen, depth))
+        })
+    }
+
+    /// Search one turn deeper at a time until we run out of time, which is the game timeout minus
+    /// the network latency padding
+    ///
+    /// This runs a [MaxnSnake::stepper()] with the whole budget, the same way the paranoid search
+    /// does when [crate::FeatureFlags::cooperative_search] is on. If not even a single turn
+    /// finishes in time we search one turn without a deadline, so we always have a move
+    pub fn deepened_maxn_until_timelimit(&self) -> (usize, MaxnReturn<GameType, ScoreType>) {
+        let budget = search_budget(&self.game_info, &self.options);
+        let players_count = self.players().len();
+
+        let mut stepper = self.stepper();
+        stepper.step(budget);
+
+        match stepper.finish() {
+            Some((depth, result)) => (depth / players_count, result),
+            None => {
+                tracing::warn!(
+                    "Not even a single turn finished in time, searching a single turn instead"
+                );
+                (1, self.single_maxn(1))
+            }
+        }
+    }
+
+    /// Start an iterative deepening search of this game that runs on the caller's thread, a
+    /// [DeepeningStepper::step()] at a time
+    ///
+    /// The depths are levels of the tree, so divide them by the number of snakes for the turns
+    pub fn stepper(&self) -> DeepeningStepper<&Self> {
+        DeepeningStepper::new(self, self.players(), None)
+    }
+
+    /// This runs maxn to the specified number of turns, with no time limit
+    pub fn single_maxn(&self, max_turns: usize) -> MaxnReturn<GameType, ScoreType> {
+        let players = self.players();
+
+        self.maxn(
+            Cow::Borrowed(&self.game),
+            &players,
+            0,
+            max_turns * players.len(),
+            vec![],
+            None,
+        )
+        .expect("Searches without a deadline are never aborted")
+    }
+
+    /// The scores of a leaf for each of the players, or None if this isn't a leaf
+    ///
+    /// Like [crate::paranoid::WrappedScorable::wrapped_score], only turn boundaries can be leaves.
+    /// Snakes that are dead at the leaf lose, even if the game goes on without them
+    fn leaf_scores(
+        &self,
+        node: &GameType,
+        players: &[GameType::SnakeIDType],
+        depth: i64,
+        max_depth: i64,
+        at_turn_boundary: bool,
+    ) -> Option<Vec<WrappedScore<ScoreType>>> {
+        if !at_turn_boundary {
+            return None;
+        }
+
+        let alive_count = players.iter().filter(|id| node.is_alive(id)).count() as u8;
+
+        if node.is_over() {
+            let winner = node.get_winner();
+
+            return Some(
+                players
+                    .iter()
+                    .map(|snake_id| match &winner {
+                        Some(winner) if winner == snake_id => WrappedScore::Win(Reverse(depth)),
+                        Some(_) => WrappedScore::Lose(Reverse(alive_count), depth),
+                        None => WrappedScore::Tie(Reverse(alive_count), depth),
+                    })
+                    .collect(),
+            );
+        }
+
+        if depth >= max_depth {
+            return Some(
+                players
+                    .iter()
+                    .map(|snake_id| {
+                        if node.is_alive(snake_id) {
+                            WrappedScore::Scored(self.score_function.score_for(node, snake_id))
+                        } else {
+                            WrappedScore::Lose(Reverse(alive_count), depth)
+                        }
+                    })
+                    .collect(),
+            );
+        }
+
+        None
+    }
+
+    /// The scores when `stuck` has nowhere to move, so it's eliminated and everyone else is scored
+    /// on the board as it is
+    fn dead_end_scores(
+        &self,
+        node: &GameType,
+        players: &[GameType::SnakeIDType],
+        depth: i64,
+        stuck: &GameType::SnakeIDType,
+    ) -> Vec<WrappedScore<ScoreType>> {
+        let still_alive =
+            |snake_id: &GameType::SnakeIDType| snake_id != stuck && node.is_alive(snake_id);
+        let alive_count = players.iter().filter(|id| still_alive(id)).count() as u8;
+
+        players
+            .iter()
+            .map(|snake_id| {
+                if still_alive(snake_id) {
+                    WrappedScore::Scored(self.score_function.score_for(node, snake_id))
+                } else {
+                    WrappedScore::Lose(Reverse(alive_count), depth)
+                }
+            })
+            .collect()
+    }
+
+    fn maxn(
+        &self,
+        node: Cow<GameType>,
+        players: &[GameType::SnakeIDType],
+        depth: usize,
+        max_depth: usize,
+        mut pending_moves: Vec<(GameType::SnakeIDType, Move)>,
+        deadline: Option<Instant>,
+    ) -> Result<MaxnReturn<GameType, ScoreType>, AbortedEarly> {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Err(AbortedEarly);
+        }
+
+        let at_turn_boundary = is_turn_boundary(depth, players.len());
+
+        pending_moves.retain(|(snake_id, _)| node.is_alive(snake_id));
+
+        // The board only advances once every player has had their chance to move, the same as
+        // the paranoid search
+        let node = if at_turn_boundary && !pending_moves.is_empty() {
+            let moves = pending_moves
+                .drain(..)
+                .map(|(sid, m)| (sid, vec![m]))
+                .collect_vec();
+            if cfg!(debug_assertions) {
+                if let Err(e) = check_simulation_moves(&*node, &moves) {
+                    panic!("Maxn built impossible pending moves: {e}");
+                }
+            }
+            let new_node = node
+                .simulate_with_moves(&Instruments::default(), moves)
+                .next()
+                .expect("A single move for each snake always simulates a board")
+                .1;
+
+            Cow::Owned(new_node)
+        } else {
+            node
+        };
+
+        let new_depth = depth.try_into().unwrap();
+        if let Some(scores) = self.leaf_scores(
+            &node,
+            players,
+            new_depth,
+            max_depth.try_into().unwrap(),
+            at_turn_boundary,
+        ) {
+            return Ok(MaxnReturn::Leaf { scores });
+        }
+
+        let player_index = depth % players.len();
+        let snake_id = &players[player_index];
+
+        if !node.is_alive(snake_id) {
+            return self.maxn(node, players, depth + 1, max_depth, pending_moves, deadline);
+        }
+
+        let possible_moves = moves_off_the_neck(node.as_ref(), snake_id)
+            .into_iter()
+            .map(|(m, _)| m)
+            .collect_vec();
+
+        let mut options = Vec::with_capacity(possible_moves.len());
+        for dir in possible_moves {
+            let mut new_pending_moves = pending_moves.clone();
+            new_pending_moves.push((snake_id.clone(), dir));
+
+            let next_return = self.maxn(
+                node.clone(),
+                players,
+                depth + 1,
+                max_depth,
+                new_pending_moves,
+                deadline,
+            )?;
+            options.push((dir, next_return));
+        }
+
+        // The moving snake only cares about its own score. The sort is stable, so ties go to the
+        // first move we looked at
+        options.sort_by_key(|(_, option)| Reverse(option.scores()[player_index]));
+        let Some((_, best)) = options.first() else {
+            // Only possible on a board too small to have a cell next to the head besides the neck
+            return Ok(MaxnReturn::Leaf {
+                scores: self.dead_end_scores(&node, players, new_depth, snake_id),
+            });
+        };
+        let scores = best.scores().to_vec();
+
+        Ok(MaxnReturn::Node {
+            options,
+            moving_snake_id: snake_id.clone(),
+            scores,
+            depth: new_depth,
+        })
+    }
+}
+
+impl<'snake, GameType, ScoreType, ScorableType, const N_SNAKES: usize> DeepeningSearch
+    for &'snake MaxnSnake<GameType, ScoreType, ScorableType, N_SNAKES>
+where
+    GameType: SnakeIDGettableGame
+        + YouDeterminableGame
+        + PositionGettableGame
+        + HealthGettableGame
+        + VictorDeterminableGame
+        + HeadGettableGame
+        + NeighborDeterminableGame
+        + NeckQueryableGame
+        + SnakeBodyGettableGame
+        + SimulableGame<Instruments, N_SNAKES>
+        + Clone
+        + Debug,
+    GameType::SnakeIDType: Clone,
+    ScoreType: Clone + Debug + PartialOrd + Ord + Copy,
+    ScorableType: MaxnScorable<GameType, ScoreType>,
+{
+    type GameType = GameType;
+    type ScoreType = ScoreType;
+    type Return = MaxnReturn<GameType, ScoreType>;
+
+    fn game(&self) -> &GameType {
+        &self.game
+    }
+
+    fn search_depth(
+        &self,
+        players: &[GameType::SnakeIDType],
+        depth: usize,
+        _previous: Option<&Self::Return>,
+        deadline: Instant,
+    ) -> Result<Self::Return, AbortedEarly> {
+        self.maxn(
+            Cow::Borrowed(&self.game),
+            players,
+            0,
+            depth,
+            vec![],
+            Some(deadline),
+        )
+    }
+
+    fn best_move(&self, result: &Self::Return) -> Option<Move> {
+        result.best_move_for(self.game.you_id())
+    }
+
+    /// Our own score, since we're always the first player
+    fn score(&self, result: &Self::Return) -> WrappedScore<ScoreType> {
+        result.scores()[0]
+    }
+
+    fn line(&self, result: &Self::Return) -> Vec<Move> {
+        result.chosen_route().into_iter().map(|(_, m)| m).collect()
+    }
+
+    /// Searching deeper can't change anything once every snake's score is decided by the end of
+    /// the game
+    fn is_settled(&self, depth: usize, result: &Self::Return) -> bool {
+        let decided = result
+            .scores()
+            .iter()
+            .all(|score| score.terminal_depth().is_some());
+        if decided {
+            info!(
+                depth,
+                "Every snake's game is decided, no need to keep going"
+            );
+        }
+
+        decided
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use battlesnake_game_types::{
+        compact_representation::StandardCellBoard4Snakes11x11,
+        types::{build_snake_id_map, SnakeId},
+        wire_representation::{Game, Position},
+    };
+
+    use super::*;
+
+    fn board(setup: impl FnOnce(&mut Game)) -> (StandardCellBoard4Snakes11x11, NestedGame) {
+        let mut wire: Game = serde_json::from_str(include_str!(
+            "../../../battlesnake-rs/fixtures/start_of_game.json"
+        ))
+        .unwrap();
+        setup(&mut wire);
+        wire.you = wire.board.snakes[0].clone();
+        let game_info = wire.game.clone();
+
+        let id_map = build_snake_id_map(&wire);
+        let board = StandardCellBoard4Snakes11x11::convert_from_game(wire, &id_map).unwrap();
+
+        (board, game_info)
+    }
+
+    fn no_opinion(_board: &StandardCellBoard4Snakes11x11, _snake_id: &SnakeId) -> u8 {
+        0
+    }
+
+    fn own_health(board: &StandardCellBoard4Snakes11x11, snake_id: &SnakeId) -> i64 {
+        board.get_health_i64(snake_id)
+    }
+
+    #[test]
+    fn test_every_node_has_a_score_for_every_player() {
+        let (game, game_info) = board(|_| {});
+        let snake = MaxnSnake::new(
+            game,
+            game_info,
+            0,
+            own_health,
+            "maxn",
+            SnakeOptions::default(),
+        );
+
+        let result = snake.single_maxn(1);
+
+        let players = game.get_snake_ids().len();
+        assert_eq!(result.scores().len(), players);
+        assert_eq!(result.chosen_route().len(), players);
+        assert!(result.best_move_for(game.you_id()).is_some());
+    }
+
+    #[test]
+    fn test_we_dont_pick_a_move_that_kills_us() {
+        let (game, game_info) = board(|wire| {
+            wire.board.snakes[0].body = [(0, 5), (1, 5), (2, 5)]
+                .into_iter()
+                .map(|(x, y)| Position { x, y })
+                .collect();
+        });
+        let snake = MaxnSnake::new(
+            game,
+            game_info,
+            0,
+            no_opinion,
+            "maxn",
+            SnakeOptions::default(),
+        );
+
+        let chosen = snake.single_maxn(1).best_move_for(game.you_id());
+
+        assert!(matches!(chosen, Some(Move::Up) | Some(Move::Down)));
+    }
+
+    #[test]
+    fn test_deepening_searches_at_least_one_turn() {
+        let (game, game_info) = board(|_| {});
+        let snake = MaxnSnake::new(
+            game,
+            game_info,
+            0,
+            own_health,
+            "maxn",
+            SnakeOptions::default(),
+        );
+
+        let (_, depth) = snake.choose_move().unwrap();
+
+        assert!(depth >= 1);
+    }
+
+    #[test]
+    fn test_stepper_deepens_a_turn_at_a_time() {
+        let (game, game_info) = board(|_| {});
+        let snake = MaxnSnake::new(
+            game,
+            game_info,
+            0,
+            own_health,
+            "maxn",
+            SnakeOptions::default(),
+        );
+
+        let mut stepper = snake.stepper();
+        stepper.step(Duration::from_millis(100));
+
+        let (depth, _) = stepper.current().unwrap();
+        assert_eq!(depth % game.get_snake_ids().len(), 0);
+        assert!(stepper.iterations() >= 1);
+        assert!(stepper.best_move().is_some());
+    }
+}
@@ -0,0 +1,98 @@
+use battlesnake_game_types::types::{Move, SnakeIDGettableGame};
+use std::fmt::Debug;
+
+use crate::paranoid::WrappedScore;
+
+#[derive(Debug, Clone)]
+/// This is returned from an iteration of the maxn algorithm
+///
+/// Every node has a score for each of the players, in the same order as the players the search
+/// was given
+pub enum MaxnReturn<
+    GameType: SnakeIDGettableGame + Clone + Debug,
+    ScoreType: Clone + Debug + PartialOrd + Ord + Copy,
+> {
+    /// This is a non-leaf node in the game tree
+    Node {
+        /// Every move we looked at for the moving snake, best for that snake first. The scores of
+        /// this node are the scores of the first option
+        options: Vec<(Move, Self)>,
+        /// Which snake was moving at this node
+        moving_snake_id: GameType::SnakeIDType,
+        /// The chosen scores, one for each player
+        scores: Vec<WrappedScore<ScoreType>>,
+        /// Depth in the tree
+        depth: i64,
+    },
+    /// Represents a leaf node in the game tree
+    ///
+    /// This happens when the game is over or when we reach the maximum depth
+    Leaf {
+        /// The scores, one for each player
+        scores: Vec<WrappedScore<ScoreType>>,
+    },
+}
+
+impl<GameType, ScoreType> MaxnReturn<GameType, ScoreType>
+where
+    GameType: SnakeIDGettableGame + Debug + Clone,
+    ScoreType: Clone + Debug + PartialOrd + Ord + Copy,
+{
+    /// Returns the score for each player at this node
+    pub fn scores(&self) -> &[WrappedScore<ScoreType>] {
+        match self {
+            MaxnReturn::Node { scores, .. } => scores,
+            MaxnReturn::Leaf { scores } => scores,
+        }
+    }
+
+    /// Returns the snake that is moving at this node, None for leaves
+    pub fn moving_snake_id(&self) -> Option<&GameType::SnakeIDType> {
+        match self {
+            MaxnReturn::Node {
+                moving_snake_id, ..
+            } => Some(moving_snake_id),
+            MaxnReturn::Leaf { .. } => None,
+        }
+    }
+
+    /// The best move for the given snake, at the first level where it moves
+    pub fn best_move_for(&self, snake_id: &GameType::SnakeIDType) -> Option<Move> {
+        match self {
+            MaxnReturn::Leaf { .. } => None,
+            MaxnReturn::Node {
+                options,
+                moving_snake_id,
+                ..
+            } => {
+                if moving_snake_id == snake_id {
+                    options.first().map(|(m, _)| *m)
+                } else {
+                    options.first()?.1.best_move_for(snake_id)
+                }
+            }
+        }
+    }
+
+    /// The moves every snake is expected to make, in order, following the best option at each
+    /// level
+    pub fn chosen_route(&self) -> Vec<(GameType::SnakeIDType, Move)> {
+        let mut route = vec![];
+        let mut current = self;
+
+        while let MaxnReturn::Node {
+            options,
+            moving_snake_id,
+            ..
+        } = current
+        {
+            let Some((m, next)) = options.first() else {
+                break;
+            };
+            route.push((moving_snake_id.clone(), *m));
+            current = next;
+        }
+
+        route
+    }
+}
@@ -0,0 +1,46 @@
+//! This module is for the `maxn` variant of multiplayer minimax
+//!
+//! Where the [crate::paranoid] variant assumes every opponent is out to get us, maxn assumes each
+//! snake is looking out for itself. Every leaf is scored once for each snake, from that snake's
+//! point of view, and at each level the snake that is moving picks the move that is best for its
+//! own score. In games with three or four snakes this is often closer to how opponents really
+//! play, since they rarely team up against us, and paranoid searches can be too defensive to go
+//! after space that an opponent could take but has no reason to.
+//!
+//! The scores of different snakes can't be compared with each other, so there is no Alpha-Beta
+//! pruning and maxn searches a lot less deep than paranoid in the same time.
+//!
+//! ```rust
+//! use battlesnake_minimax::maxn::MaxnSnake;
+//! use battlesnake_minimax::paranoid::SnakeOptions;
+//! use battlesnake_game_types::{types::{build_snake_id_map, HealthGettableGame, SnakeId}, compact_representation::StandardCellBoard4Snakes11x11, wire_representation::Game};
+//!
+//! let game_state_from_server = include_str!("../../../battlesnake-rs/fixtures/start_of_game.json");
+//! let wire_game: Game = serde_json::from_str(game_state_from_server).unwrap();
+//! let game_info = wire_game.game.clone();
+//!
+//! let snake_id_map = build_snake_id_map(&wire_game);
+//! let compact_game = StandardCellBoard4Snakes11x11::convert_from_game(wire_game, &snake_id_map).unwrap();
+//!
+//! // The score function is given the snake to score the board for
+//! fn score_function(board: &StandardCellBoard4Snakes11x11, snake_id: &SnakeId) -> i64 {
+//!     board.get_health_i64(snake_id)
+//! }
+//!
+//! let maxn_snake = MaxnSnake::new(
+//!    compact_game,
+//!    game_info,
+//!    0,
+//!    score_function,
+//!    "maxn_snake",
+//!    SnakeOptions::default(),
+//! );
+//!
+//! let result = maxn_snake.single_maxn(1);
+//! ```
+
+mod eval;
+pub use eval::{MaxnScorable, MaxnSnake};
+
+mod maxn_return;
+pub use maxn_return::MaxnReturn;
@@ -0,0 +1,126 @@
+//! Which of a snake's moves would turn it back into its own neck
+//!
+//! We used to ask [NeckQueryableGame](battlesnake_game_types::types::NeckQueryableGame) for this,
+//! but on a wrapped board the neck can be on the opposite edge from the head, and a move that
+//! wraps around onto it is just as much a 180° turn. Here we go by the body itself, and the
+//! neighbors from `possible_moves` already wrap, so a neck across an edge is found the same way as
+//! any other
+
+use battlesnake_game_types::types::{
+    HeadGettableGame, Move, NeighborDeterminableGame, SnakeBodyGettableGame,
+};
+
+/// The cell right behind this snake's head, None while its body is still stacked on its head at
+/// the start of the game
+pub fn neck_of<GameType>(
+    game: &GameType,
+    snake_id: &GameType::SnakeIDType,
+) -> Option<GameType::NativePositionType>
+where
+    GameType: SnakeBodyGettableGame,
+{
+    // This runs for every snake at every node of the search, so we only walk the two cells we need
+    // instead of collecting the whole body
+    let mut body = game.get_snake_body_iter(snake_id);
+    let head = body.next()?;
+    let neck = body.next()?;
+
+    (head != neck).then_some(neck)
+}
+
+/// Every move for this snake along with where it ends up, except the one back into its neck
+pub fn moves_off_the_neck<GameType>(
+    game: &GameType,
+    snake_id: &GameType::SnakeIDType,
+) -> Vec<(Move, GameType::NativePositionType)>
+where
+    GameType: SnakeBodyGettableGame + HeadGettableGame + NeighborDeterminableGame,
+{
+    let neck = neck_of(game, snake_id);
+
+    game.possible_moves(&game.get_head_as_native_position(snake_id))
+        .filter(|(_, pos)| Some(pos) != neck.as_ref())
+        .collect()
+}
+
+/// The move that would send this snake back into its neck, if it has one
+pub fn neck_move<GameType>(game: &GameType, snake_id: &GameType::SnakeIDType) -> Option<Move>
+where
+    GameType: SnakeBodyGettableGame + HeadGettableGame + NeighborDeterminableGame,
+{
+    let neck = neck_of(game, snake_id)?;
+
+    game.possible_moves(&game.get_head_as_native_position(snake_id))
+        .find(|(_, pos)| *pos == neck)
+        .map(|(m, _)| m)
+}
+
+#[cfg(test)]
+mod tests {
+    use battlesnake_game_types::{
+        compact_representation::{StandardCellBoard4Snakes11x11, WrappedCellBoard4Snakes11x11},
+        types::{build_snake_id_map, SnakeId},
+        wire_representation::Game,
+    };
+    use itertools::Itertools;
+
+    use super::*;
+    use crate::checked_simulation::check_simulation_moves;
+
+    /// Our neck is across the left edge and the flipper's is across the bottom edge. Everything
+    /// else around our head is taken, so the only way out that looks open wraps back onto our neck
+    fn wrapped_necks() -> (WrappedCellBoard4Snakes11x11, SnakeId, SnakeId) {
+        let wire: Game = serde_json::from_str(include_str!(
+            "../../fixtures/wrapped_neck_across_edges.json"
+        ))
+        .unwrap();
+        let id_map = build_snake_id_map(&wire);
+        let board = WrappedCellBoard4Snakes11x11::convert_from_game(wire, &id_map).unwrap();
+
+        (board, id_map["you"], id_map["flipper"])
+    }
+
+    #[test]
+    fn test_necks_across_an_edge_are_found() {
+        let (board, you, flipper) = wrapped_necks();
+
+        assert_eq!(neck_move(&board, &you), Some(Move::Left));
+        assert_eq!(neck_move(&board, &flipper), Some(Move::Down));
+
+        let flipper_moves = moves_off_the_neck(&board, &flipper)
+            .into_iter()
+            .map(|(m, _)| m)
+            .collect_vec();
+        assert_eq!(flipper_moves.len(), 3);
+        assert!(!flipper_moves.contains(&Move::Down));
+    }
+
+    #[test]
+    fn test_a_wrapped_neck_is_not_an_escape() {
+        let (board, you, _) = wrapped_necks();
+
+        let moves = moves_off_the_neck(&board, &you)
+            .into_iter()
+            .map(|(m, _)| m)
+            .collect_vec();
+        assert!(!moves.contains(&Move::Left));
+
+        // Every other square is taken, so we're dead whichever way we go and sending us back
+        // into our neck is as good as anything else
+        assert!(check_simulation_moves(&board, &[(you, vec![Move::Left])]).is_ok());
+    }
+
+    #[test]
+    fn test_stacked_bodies_have_no_neck() {
+        let wire: Game = serde_json::from_str(include_str!(
+            "../../battlesnake-rs/fixtures/start_of_game.json"
+        ))
+        .unwrap();
+        let id_map = build_snake_id_map(&wire);
+        let you = id_map[&wire.you.id];
+        let board = StandardCellBoard4Snakes11x11::convert_from_game(wire, &id_map).unwrap();
+
+        assert_eq!(neck_move(&board, &you), None);
+        assert_eq!(moves_off_the_neck(&board, &you).len(), 4);
+    }
+}
@@ -0,0 +1,92 @@
+use std::{collections::BTreeMap, fmt::Debug};
+
+use battlesnake_game_types::types::SnakeIDGettableGame;
+
+use super::MinMaxReturn;
+
+/// How Alpha-Beta pruning went at one depth of a search tree, see
+/// [MinMaxReturn::cutoffs_by_depth]
+///
+/// The earlier in the move order a cutoff comes, the less of the tree we had to search, so the
+/// average index of the move that caused the cutoffs says how good the move ordering is at this
+/// depth. An index of 0 means the first move we tried was already good enough to cut the rest off
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DepthCutoffs {
+    /// The depth of the nodes in the tree, counting every snake's move
+    pub depth: i64,
+    /// The number of nodes searched at this depth, not counting leaves
+    pub nodes: usize,
+    /// How many of those nodes were cut off
+    pub cutoffs: usize,
+    /// The index in the move order of the move that caused each cutoff, added up
+    pub cutoff_move_index_sum: usize,
+}
+
+impl DepthCutoffs {
+    /// The fraction of nodes at this depth that were cut off, None if there weren't any nodes
+    pub fn cutoff_rate(&self) -> Option<f64> {
+        if self.nodes == 0 {
+            return None;
+        }
+
+        Some(self.cutoffs as f64 / self.nodes as f64)
+    }
+
+    /// The average index in the move order of the move that caused a cutoff, None if nothing was
+    /// cut off at this depth
+    pub fn average_cutoff_index(&self) -> Option<f64> {
+        if self.cutoffs == 0 {
+            return None;
+        }
+
+        Some(self.cutoff_move_index_sum as f64 / self.cutoffs as f64)
+    }
+}
+
+impl<GameType, ScoreType> MinMaxReturn<GameType, ScoreType>
+where
+    GameType: SnakeIDGettableGame + Debug + Clone,
+    ScoreType: Clone + Debug + PartialOrd + Ord + Copy,
+{
+    /// The Alpha-Beta cutoffs in this tree, added up for each depth and sorted by depth
+    ///
+    /// The search stops trying moves at a node as soon as one causes a cutoff, so that move is
+    /// always the last one it tried. The options are sorted by score afterwards, but their number
+    /// doesn't change, which makes the index of the move that caused the cutoff one less than the
+    /// number of options
+    ///
+    /// The tree only has the deepest iteration of iterative deepening in it, which is the search
+    /// the move we make comes from
+    pub fn cutoffs_by_depth(&self) -> Vec<DepthCutoffs> {
+        let mut by_depth = BTreeMap::new();
+        self.add_cutoffs(&mut by_depth);
+
+        by_depth.into_values().collect()
+    }
+
+    fn add_cutoffs(&self, by_depth: &mut BTreeMap<i64, DepthCutoffs>) {
+        let MinMaxReturn::Node {
+            options,
+            alpha_beta_cutoff,
+            depth,
+            ..
+        } = self
+        else {
+            return;
+        };
+
+        let stats = by_depth.entry(*depth).or_insert_with(|| DepthCutoffs {
+            depth: *depth,
+            ..Default::default()
+        });
+        stats.nodes += 1;
+        if *alpha_beta_cutoff {
+            stats.cutoffs += 1;
+            stats.cutoff_move_index_sum += options.len().saturating_sub(1);
+        }
+
+        for (_, option) in options {
+            option.add_cutoffs(by_depth);
+        }
+    }
+}
@@ -2,29 +2,61 @@ use std::{
     borrow::Cow,
     fmt::Debug,
     marker::PhantomData,
-    sync::mpsc,
-    thread,
+    sync::{mpsc, Arc},
     time::{Duration, Instant},
 };
 
 use battlesnake_game_types::{
     types::{
         HeadGettableGame, HealthGettableGame, Move, NeckQueryableGame, NeighborDeterminableGame,
-        PositionGettableGame, SimulableGame, SimulatorInstruments, SnakeIDGettableGame,
+        PositionGettableGame, SimulableGame, SnakeBodyGettableGame, SnakeIDGettableGame,
         VictorDeterminableGame, YouDeterminableGame,
     },
-    wire_representation::NestedGame,
+    wire_representation::{NestedGame, Position},
 };
 use derivative::Derivative;
 use itertools::Itertools;
 use tracing::{info, info_span};
 
-use crate::{paranoid::move_ordering::MoveOrdering, Instruments};
+use crate::{
+    board_hash::BoardHashable,
+    checked_simulation::check_simulation_moves,
+    core_pinning::{game_cores, PinnedThread},
+    neck::moves_off_the_neck,
+    paranoid::move_ordering::MoveOrdering,
+    search_pool::{search_pool, Priority, TaskHandle, TaskStatus},
+    FeatureFlags, Instruments, SearchStatus, SearchStepper, SimulationStats,
+};
 
-use super::{score::Scorable, MinMaxReturn, WrappedScorable, WrappedScore};
+use super::{
+    panic_mode::panic_move,
+    pv_stability::{feature_line, PvStability},
+    score::Scorable,
+    simultaneous_root::{minimax_regret_move, RootStrategy},
+    terminal_state::{classify_death, TerminalState},
+    verification::{fallback_move, Verification, VERIFICATION_SHARE, VERIFICATION_TURNS},
+    DeepeningStepper, MinMaxReturn, ScoreMemo, WrappedScorable, WrappedScore,
+};
+
+/// Returns every board that can result from nature spawning a single piece of food on the given
+/// board. Each of the returned boards is treated as equally likely, and returning none means no
+/// food can spawn on this board
+///
+/// The compact boards don't know the food spawning rules of every map, so it's up to the caller to
+/// provide this. It's shared between every clone of the snake, so it can hold on to whatever it
+/// needs from the game, like the id map the board was built with. See
+/// [MinimaxSnake::with_food_spawner]
+pub type FoodSpawner<GameType> = Arc<dyn Fn(&GameType) -> Vec<GameType> + Send + Sync>;
+
+/// Returns the named evaluation terms of the given board, like how much space each snake has or
+/// how far we are from food
+///
+/// These are only used for logging, so we can see which parts of the evaluation change when the
+/// search changes its mind. See [MinimaxSnake::with_feature_snapshot]
+pub type FeatureSnapshot<GameType> = fn(&GameType) -> Vec<(&'static str, f64)>;
 
 #[derive(Derivative, Clone)]
-#[derivative(Debug)]
+#[derivative(Debug(bound = "GameType: Debug, ScoreType: Debug"))]
 /// This is the struct that wraps a game board and a scoring function and can be used to run
 /// minimax
 ///
@@ -36,18 +68,62 @@ where
     ScorableType: Scorable<GameType, ScoreType> + Sized + Send + Sync + Clone,
 {
     pub(crate) game: GameType,
-    pub(crate) game_info: NestedGame,
     pub(crate) turn: i32,
+    pub(crate) settings: Arc<SearchSettings<GameType, ScorableType>>,
+    /// Only set while a deepened search is running, see
+    /// [MinimaxSnake::deepened_minimax_with_stats()]
     #[derivative(Debug = "ignore")]
-    score_function: ScorableType,
-    pub(crate) name: &'static str,
-    options: SnakeOptions,
+    pub(crate) score_memo: Option<Arc<ScoreMemo<ScoreType>>>,
+    /// Records every simulation while a deepened search is running, and is a no-op otherwise
+    #[derivative(Debug = "ignore")]
+    pub(crate) instruments: Instruments,
     _phantom: PhantomData<ScoreType>,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Derivative, Clone)]
+#[derivative(Debug(bound = ""))]
+/// The parts of a [MinimaxSnake] that stay the same for the whole search
+///
+/// Every search thread works on its own clone of the snake, so these live behind an [Arc] and are
+/// shared instead of copied. Anything that needs to change during a search, like a score cache,
+/// should use interior mutability the way [super::CachedScore] does
+pub(crate) struct SearchSettings<GameType, ScorableType> {
+    pub(crate) game_info: NestedGame,
+    pub(crate) name: &'static str,
+    #[derivative(Debug = "ignore")]
+    score_function: ScorableType,
+    pub(crate) options: SnakeOptions,
+    #[derivative(Debug = "ignore")]
+    food_spawner: Option<FoodSpawner<GameType>>,
+    #[derivative(Debug = "ignore")]
+    feature_snapshot: Option<FeatureSnapshot<GameType>>,
+}
+
+impl<GameType, ScorableType> SearchSettings<GameType, ScorableType> {
+    fn new(
+        game_info: NestedGame,
+        score_function: ScorableType,
+        name: &'static str,
+        options: SnakeOptions,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            game_info,
+            name,
+            score_function,
+            options,
+            food_spawner: None,
+            feature_snapshot: None,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
 /// Optional properties that can be defined for an [MinimaxSnake]
 ///
+/// These used to be `Copy`, but a [MoveOrdering::Custom] holds on to its function so now they
+/// have to be cloned instead. The function is behind an [Arc], so a clone is still cheap and every
+/// clone shares the same function
+///
 /// The defaults (as implemented by [Default]) are as follows:
 /// ```
 /// use std::time::Duration;
@@ -67,6 +143,56 @@ pub struct SnakeOptions {
     pub network_latency_padding: Duration,
     /// How should moves be ordered in the tree search
     pub move_ordering: MoveOrdering,
+    /// How many turns shallower we search our opponents' non-PV moves
+    ///
+    /// This is a form of Late Move Reduction that only applies to opponents. The first move in
+    /// the move ordering for an opponent is always searched to the full depth, but the rest are
+    /// searched this many turns shallower. If a reduced search finds a move that is better for
+    /// the opponent than anything we've seen so far, we re-search it at the full depth.
+    ///
+    /// Defaults to 0, which searches opponents to the same depth as ourselves
+    pub opponent_depth_reduction: usize,
+    /// The chance that nature spawns a piece of food at the end of each turn
+    ///
+    /// When the [FeatureFlags::food_spawns] flag is on, this is at least 0.5 and the snake has a
+    /// [FoodSpawner], see [MinimaxSnake::with_food_spawner], every turn boundary becomes a chance
+    /// node. This multiplies the branching factor by the number of places food can spawn, so
+    /// expect to search a lot less deep with it turned on.
+    ///
+    /// The chance node goes with the weighted median outcome, so a chance below 0.5 would always
+    /// pick the board without the spawn. Those aren't searched at all, since they can't change
+    /// anything.
+    ///
+    /// A chance of 1 means food always spawns when the [FoodSpawner] returns any boards, so the
+    /// board without the spawn isn't searched at all. That's how to model the spawns the ruleset
+    /// has to make, like when there's less food than its minimum
+    ///
+    /// Defaults to 0, which ignores food spawns
+    pub food_spawn_chance: f64,
+    /// How many plies deeper a line can be searched because opponents were forced along it
+    ///
+    /// When an opponent has only one move that doesn't run straight into a snake's body, that is
+    /// the only move we search for it and it doesn't use up any depth. This lets the same depth
+    /// see further when snakes are stuck in corridors. Each forced move uses up one ply of this
+    /// budget, and since we only score at the end of a turn the search goes on to the end of the
+    /// turn after it.
+    ///
+    /// Defaults to 0, which searches every opponent move at the normal depth
+    pub forced_move_extension: usize,
+    /// How we pick our move at the root, see [RootStrategy]
+    ///
+    /// Defaults to [RootStrategy::Paranoid]
+    pub root_strategy: RootStrategy,
+    /// How many turns deep each board in the payoff matrix is searched when the
+    /// [SnakeOptions::root_strategy] looks at the root as a simultaneous move game
+    ///
+    /// The matrix has a board for every combination of everyone's moves, so this is searched
+    /// before the main search and the time it takes comes out of the main search's budget.
+    ///
+    /// Defaults to 1, and 0 scores the boards right after everyone's moves
+    pub simultaneous_root_depth: usize,
+    /// Which of the experimental search features are turned on, see [FeatureFlags]
+    pub features: FeatureFlags,
 }
 
 impl Default for SnakeOptions {
@@ -74,7 +200,54 @@ impl Default for SnakeOptions {
         Self {
             network_latency_padding: Duration::from_millis(100),
             move_ordering: MoveOrdering::BestFirst,
+            opponent_depth_reduction: 0,
+            food_spawn_chance: 0.0,
+            forced_move_extension: 0,
+            root_strategy: RootStrategy::Paranoid,
+            simultaneous_root_depth: 1,
+            features: FeatureFlags::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// How a single run of [MinimaxSnake::deepened_minimax_with_stats()] went
+///
+/// This is what we keep around per turn to spot games where the search is consistently shallow or
+/// runs up against the timeout
+pub struct SearchStats {
+    /// The depth of the deepest iteration that finished in time
+    pub depth: usize,
+    /// The number of iterations of iterative deepening that finished in time
+    pub iterations: usize,
+    /// How long the search ran for, including waiting for the worker thread to hand back results
+    pub time_used: Duration,
+    /// How long we were allowed to search for, the game timeout minus the network latency padding
+    pub budget: Duration,
+    /// The number of times the chosen move changed between iterations
+    pub pv_flips: usize,
+    /// The number of leaves we needed a score for, across every iteration
+    pub score_memo_lookups: usize,
+    /// The number of leaves that had already been scored earlier in the search
+    pub score_memo_hits: usize,
+    /// The number of boards we simulated, across every iteration
+    pub simulations: u64,
+    /// How long those simulations took altogether
+    pub simulation_time: Duration,
+    /// What checking the chosen move once more found, None unless
+    /// [FeatureFlags::verify_move] is on. This is filled in by
+    /// [MinimaxSnake::choose_move_with_stats()]
+    pub verification: Option<Verification>,
+}
+
+impl SearchStats {
+    /// The fraction of leaves that were already in the [ScoreMemo], None if nothing was scored
+    pub fn score_memo_hit_rate(&self) -> Option<f64> {
+        if self.score_memo_lookups == 0 {
+            return None;
         }
+
+        Some(self.score_memo_hits as f64 / self.score_memo_lookups as f64)
     }
 }
 
@@ -84,20 +257,176 @@ impl Default for SnakeOptions {
 /// out of the current context
 pub struct AbortedEarly;
 
+/// What stops a [MinimaxSnake::minimax] search part way through, with [AbortedEarly]
+#[derive(Debug)]
+pub(crate) enum Halt {
+    /// Stop once the main thread sends anything, see [DeepeningWorker::stop], or once this
+    /// instant has passed, whichever comes first
+    Signal(mpsc::Receiver<()>, Instant),
+    /// Stop once this instant has passed, for searches that share their thread, see
+    /// [super::DeepeningStepper]
+    Deadline(Instant),
+}
+
+impl Halt {
+    fn should_stop(&self) -> bool {
+        match self {
+            Halt::Signal(receiver, deadline) => {
+                Instant::now() >= *deadline || receiver.try_recv().is_ok()
+            }
+            Halt::Deadline(deadline) => Instant::now() >= *deadline,
+        }
+    }
+}
+
+/// Each level of the tree is a single snake moving, and every snake in the `players` list gets a
+/// level each turn. This includes snakes that have already been eliminated, which keeps the turn
+/// boundaries fixed for the whole search no matter when a snake dies
+pub(crate) fn is_turn_boundary(depth: usize, num_players: usize) -> bool {
+    depth % num_players == 0
+}
+
+/// The least time we give a search, even when the network latency padding eats the whole timeout
+///
+/// A deadline header from a slow proxy can leave us with less time than the padding. Answering a
+/// little late is better than panicking, and the first iteration of the search is quick enough to
+/// fit in this
+pub(crate) const MIN_SEARCH_BUDGET: Duration = Duration::from_millis(10);
+
+/// How long a search for this game can take, the game timeout minus the network latency padding
+///
+/// This is never less than [MIN_SEARCH_BUDGET]
+pub(crate) fn search_budget(game_info: &NestedGame, options: &SnakeOptions) -> Duration {
+    let timeout = game_info.timeout.try_into().unwrap_or_default();
+    let timeout = Duration::from_millis(timeout);
+
+    timeout
+        .saturating_sub(options.network_latency_padding)
+        .max(MIN_SEARCH_BUDGET)
+}
+
+/// Simulate a single move for each of the given snakes, which always results in exactly one board
+pub(crate) fn simulate_one<GameType, const N_SNAKES: usize>(
+    board: &GameType,
+    moves: &[(GameType::SnakeIDType, Move)],
+) -> GameType
+where
+    GameType: SnakeIDGettableGame + SimulableGame<Instruments, N_SNAKES>,
+    GameType::SnakeIDType: Clone,
+{
+    board
+        .simulate_with_moves(
+            &Instruments::default(),
+            moves
+                .iter()
+                .map(|(sid, m)| (sid.clone(), vec![*m]))
+                .collect_vec(),
+        )
+        .next()
+        .expect("A single move for each snake always simulates a board")
+        .1
+}
+
+/// The only move this snake has that doesn't run straight into a snake's body, if there is
+/// exactly one
+///
+/// Tails move out of the way, so they don't count unless they're stacked because the snake just
+/// ate. This doesn't know about head to head collisions or hazards, so a move is only ever ruled
+/// out when it's certain to kill
+fn forced_move<GameType>(node: &GameType, possible_moves: &[(Move, Position)]) -> Option<Move>
+where
+    GameType:
+        SnakeIDGettableGame + SnakeBodyGettableGame + PositionGettableGame + HealthGettableGame,
+{
+    let mut occupied = vec![];
+    for snake_id in node.get_snake_ids() {
+        if !node.is_alive(&snake_id) {
+            continue;
+        }
+
+        let mut body = node
+            .get_snake_body_vec(&snake_id)
+            .into_iter()
+            .map(|pos| node.position_from_native(pos))
+            .collect_vec();
+        let tail_moves = matches!(body.as_slice(), [.., before_tail, tail] if before_tail != tail);
+        if tail_moves {
+            body.pop();
+        }
+
+        occupied.extend(body);
+    }
+
+    let mut open_moves = possible_moves
+        .iter()
+        .filter(|(_, pos)| !occupied.contains(pos))
+        .map(|(m, _)| *m);
+
+    match (open_moves.next(), open_moves.next()) {
+        (Some(only_move), None) => Some(only_move),
+        _ => None,
+    }
+}
+
+/// The longest the main search waits for a thread from the [search_pool] before it deepens on the
+/// thread that asked for it instead, see [MinimaxSnake::deepened_minimax_with_stats()]
+///
+/// A tenth of the budget when that's shorter, so a busy pool never costs us most of our time
+pub const MAX_QUEUE_DELAY: Duration = Duration::from_millis(20);
+
+/// Returns the depth of the first turn boundary strictly after `depth`
+fn next_turn_boundary(depth: usize, num_players: usize) -> usize {
+    (depth / num_players + 1) * num_players
+}
+
+/// Picks the outcome of a chance node
+///
+/// Our scores are only ordered, like the enums most of the snakes score with, so there is no
+/// expected value to take like a true expectimax would. The weighted median is the ordinal
+/// version of one: the score we'll do at least as well as half the time, which doesn't depend on
+/// how far apart the scores are, only on their order.
+///
+/// The catch is that an outcome with more than half of the weight is always the median, so a
+/// chance node whose board without a spawn is more likely than not never picks a spawn. See
+/// [SnakeOptions::food_spawn_chance] for how we skip those.
+///
+/// Returns the index of the chosen outcome, or None if there weren't any
+fn weighted_median<T: Ord>(outcomes: &[(f64, T)]) -> Option<usize> {
+    let total: f64 = outcomes.iter().map(|(p, _)| p).sum();
+    let sorted = outcomes
+        .iter()
+        .enumerate()
+        .sorted_by(|(_, (_, a)), (_, (_, b))| a.cmp(b))
+        .collect_vec();
+
+    let mut cumulative = 0.0;
+    for (i, (p, _)) in &sorted {
+        cumulative += p;
+        if cumulative >= total / 2.0 {
+            return Some(*i);
+        }
+    }
+
+    sorted.last().map(|(i, _)| *i)
+}
+
 impl<GameType, ScoreType, ScorableType, const N_SNAKES: usize> WrappedScorable<GameType, ScoreType>
     for MinimaxSnake<GameType, ScoreType, ScorableType, N_SNAKES>
 where
     ScoreType: Debug + PartialOrd + Ord + Copy,
-    GameType: YouDeterminableGame + VictorDeterminableGame + HealthGettableGame,
+    GameType: YouDeterminableGame + VictorDeterminableGame + HealthGettableGame + BoardHashable,
     ScorableType: Scorable<GameType, ScoreType> + Sized + Send + Sync + Clone,
 {
     fn score(&self, node: &GameType) -> ScoreType {
-        self.score_function.score(node)
+        self.settings.score_function.score(node)
     }
-}
 
-impl SimulatorInstruments for Instruments {
-    fn observe_simulation(&self, _duration: Duration) {}
+    fn score_at_depth(&self, node: &GameType, depth: i64) -> ScoreType {
+        match &self.score_memo {
+            Some(memo) => memo.get_or_score(node.board_hash(), depth, || self.score(node)),
+            None => self.score(node),
+        }
+    }
 }
 
 impl<GameType, ScoreType, const N_SNAKES: usize>
@@ -162,11 +491,10 @@ where
     ) -> Self {
         Self {
             game,
-            game_info,
             turn,
-            score_function,
-            name,
-            options: Default::default(),
+            settings: SearchSettings::new(game_info, score_function, name, Default::default()),
+            score_memo: None,
+            instruments: Instruments::default(),
             _phantom: Default::default(),
         }
     }
@@ -226,11 +554,10 @@ where
     ) -> Self {
         Self {
             game,
-            game_info,
             turn,
-            score_function,
-            name,
-            options,
+            settings: SearchSettings::new(game_info, score_function, name, options),
+            score_memo: None,
+            instruments: Instruments::default(),
             _phantom: Default::default(),
         }
     }
@@ -246,6 +573,8 @@ where
         + HeadGettableGame
         + NeighborDeterminableGame
         + NeckQueryableGame
+        + SnakeBodyGettableGame
+        + BoardHashable
         + SimulableGame<Instruments, N_SNAKES>
         + Clone
         + Sync
@@ -253,7 +582,7 @@ where
         + Sized,
     GameType::SnakeIDType: Clone + Send + Sync,
     ScoreType: Clone + Debug + PartialOrd + Ord + Send + Sync + Copy,
-    ScorableType: Scorable<GameType, ScoreType> + Sized + Send + Sync + Clone,
+    ScorableType: Scorable<GameType, ScoreType> + Sized + Send + Sync + Clone + 'static,
 {
     /// Construct a new `MinimaxSnake`
     pub fn new(
@@ -266,15 +595,31 @@ where
     ) -> Self {
         Self {
             game,
-            game_info,
             turn,
-            score_function,
-            name,
-            options,
+            settings: SearchSettings::new(game_info, score_function, name, options),
+            score_memo: None,
+            instruments: Instruments::default(),
             _phantom: Default::default(),
         }
     }
 
+    /// Use the given [FoodSpawner] to model food spawning at the end of each turn
+    ///
+    /// This only has an effect when [SnakeOptions::food_spawn_chance] is above 0
+    pub fn with_food_spawner(mut self, food_spawner: FoodSpawner<GameType>) -> Self {
+        Arc::make_mut(&mut self.settings).food_spawner = Some(food_spawner);
+        self
+    }
+
+    /// Log the [FeatureSnapshot] of the boards at the end of both lines whenever iterative
+    /// deepening changes the move it would pick
+    ///
+    /// Comparing the two tells us which evaluation terms are pulling the search back and forth
+    pub fn with_feature_snapshot(mut self, feature_snapshot: FeatureSnapshot<GameType>) -> Self {
+        Arc::make_mut(&mut self.settings).feature_snapshot = Some(feature_snapshot);
+        self
+    }
+
     ///
     /// Pick the next move to make
     ///
@@ -282,6 +627,18 @@ where
     /// return the chosen move. For more information on the inner working see the docs for
     /// [MinimaxSnake::deepened_minimax_until_timelimit()]
     pub fn choose_move(&self) -> Option<(Move, usize)> {
+        self.choose_move_with_alternatives(0)
+            .map(|(chosen, depth, _)| (chosen, depth))
+    }
+
+    /// Pick the next move to make, the same as [MinimaxSnake::choose_move()]
+    ///
+    /// This also returns a short summary of the best `alternatives` moves we considered and their
+    /// scores, which is useful to return as a shout. See [MinMaxReturn::alternatives_summary()]
+    pub fn choose_move_with_alternatives(
+        &self,
+        alternatives: usize,
+    ) -> Option<(Move, usize, Option<String>)> {
         let my_id = self.game.you_id();
         let (depth, scored) = self.choose_move_inner(None);
 
@@ -291,10 +648,86 @@ where
         if ids.len() == 1 {
             info!("We are the only snake left on the board, lets go Right");
 
-            return Some((Move::Right, 0));
+            return Some((Move::Right, 0, None));
         }
 
-        Some((scored_options.first()?.0, depth))
+        let summary = if alternatives > 0 {
+            scored.alternatives_summary(my_id, alternatives)
+        } else {
+            None
+        };
+
+        Some((scored_options.first()?.0, depth, summary))
+    }
+
+    /// Pick the next move to make, the same as [MinimaxSnake::choose_move_with_alternatives()]
+    ///
+    /// This also returns the [SearchStats] for the search and the result it settled on, for hosts
+    /// that want to log more about the search than the move, like its score or its
+    /// [MinMaxReturn::chosen_route()]
+    #[allow(clippy::type_complexity)]
+    pub fn choose_move_with_search(
+        &self,
+        alternatives: usize,
+    ) -> Option<(
+        Move,
+        Option<String>,
+        SearchStats,
+        MinMaxReturn<GameType, ScoreType>,
+    )> {
+        let my_id = self.game.you_id();
+        let (stats, scored) = self.choose_move_with_stats(None);
+
+        let chosen = if self.game.get_snake_ids().len() == 1 {
+            Move::Right
+        } else {
+            scored.your_best_move(my_id)?
+        };
+
+        let summary = if alternatives > 0 {
+            scored.alternatives_summary(my_id, alternatives)
+        } else {
+            None
+        };
+
+        Some((chosen, summary, stats, scored))
+    }
+
+    /// Pick the next move to make, the same as [MinimaxSnake::choose_move()]
+    ///
+    /// This also returns the [SearchStats] for the search, and every move we considered for
+    /// ourselves at the root along with its score, best first. This is meant for looking into why
+    /// we picked a move, not for playing games
+    ///
+    /// The last value is the score breakdown at the end of the line we expect, see
+    /// [MinMaxReturn::principal_variation_breakdown()]
+    #[allow(clippy::type_complexity)]
+    pub fn choose_move_with_root_options(
+        &self,
+    ) -> Option<(
+        Move,
+        SearchStats,
+        Vec<(Move, WrappedScore<ScoreType>)>,
+        Option<Vec<(&'static str, f64)>>,
+    )> {
+        let my_id = self.game.you_id();
+        let (stats, scored) = self.choose_move_with_stats(None);
+
+        let root_options = scored
+            .first_options_for_snake(my_id)?
+            .iter()
+            .map(|(m, r)| (*m, *r.score()))
+            .collect_vec();
+
+        let chosen = if self.game.get_snake_ids().len() == 1 {
+            Move::Right
+        } else {
+            root_options.first()?.0
+        };
+
+        let breakdown = scored.principal_variation_breakdown().map(<[_]>::to_vec);
+
+        Some((chosen, stats, root_options, breakdown))
     }
 
     #[allow(missing_docs)]
@@ -302,30 +735,106 @@ where
         &self,
         initial_return: Option<MinMaxReturn<GameType, ScoreType>>,
     ) -> (usize, MinMaxReturn<GameType, ScoreType>) {
+        let (stats, scored) = self.choose_move_with_stats(initial_return);
+
+        (stats.depth, scored)
+    }
+
+    /// The same as [MinimaxSnake::choose_move_inner()] but also returns the [SearchStats] for the
+    /// search
+    pub fn choose_move_with_stats(
+        &self,
+        initial_return: Option<MinMaxReturn<GameType, ScoreType>>,
+    ) -> (SearchStats, MinMaxReturn<GameType, ScoreType>) {
         let my_id = self.game.you_id();
         let mut sorted_ids = self.game.get_snake_ids();
         sorted_ids.sort_by_key(|snake_id| if snake_id == my_id { -1 } else { 1 });
 
         info_span!(
           "deepened_minmax",
-          snake_name = self.name,
-          game_id = %&self.game_info.id,
+          snake_name = self.settings.name,
+          game_id = %&self.settings.game_info.id,
           turn = self.turn,
-          ruleset_name = %self.game_info.ruleset.name,
-          ruleset_version = %self.game_info.ruleset.version,
+          ruleset_name = %self.settings.game_info.ruleset.name,
+          ruleset_version = %self.settings.game_info.ruleset.version,
           chosen_score = tracing::field::Empty,
           chosen_direction = tracing::field::Empty,
           depth = tracing::field::Empty,
+          pv_flips = tracing::field::Empty,
+          score_memo_hit_rate = tracing::field::Empty,
+          simulations = tracing::field::Empty,
+          simulation_ms = tracing::field::Empty,
+          simulation_share = tracing::field::Empty,
+          pool_queue_delay_ms = tracing::field::Empty,
+          terminal_state = tracing::field::Empty,
+          verification = tracing::field::Empty,
+          cores = tracing::field::Empty,
+          feature_flags = %self.settings.options.features,
         )
         .in_scope(|| {
-            let (depth, scored) = self
-                .clone()
-                .deepened_minimax_until_timelimit(sorted_ids, initial_return);
+            let mut snake = self.clone();
+
+            // The verification has to fit in the same deadline, so it's held back from the main
+            // search up front
+            let verify = self.settings.options.features.verify_move && sorted_ids.len() > 1;
+            let verification_deadline = Instant::now() + self.max_duration();
+            if verify {
+                Arc::make_mut(&mut snake.settings)
+                    .options
+                    .network_latency_padding += self.max_duration() / VERIFICATION_SHARE;
+            }
+
+            let simultaneous_payoffs = match self.settings.options.root_strategy {
+                RootStrategy::MinimaxRegret if sorted_ids.len() > 1 => {
+                    let started_at = Instant::now();
+                    let payoffs = self.simultaneous_payoffs(&sorted_ids);
+
+                    // The matrix has to fit in the same deadline, so the main search gets what's
+                    // left. We never take more than half of it though, so the main search still
+                    // has time to finish its first iteration
+                    let spent = started_at.elapsed().min(self.max_duration() / 2);
+                    Arc::make_mut(&mut snake.settings)
+                        .options
+                        .network_latency_padding += spent;
+
+                    Some(payoffs)
+                }
+                _ => None,
+            };
+
+            let (mut stats, mut scored) =
+                snake.deepened_minimax_with_stats(sorted_ids.clone(), initial_return);
+
+            let panicked = self.settings.options.features.panic_mode
+                && self.choose_panic_move(
+                    &mut scored,
+                    &sorted_ids,
+                    simultaneous_payoffs.as_deref(),
+                );
+            if !panicked {
+                if let Some(payoffs) = simultaneous_payoffs {
+                    self.choose_simultaneous_root_move(&mut scored, payoffs);
+                }
+            }
 
             let current_span = tracing::Span::current();
-            current_span.record("scored_depth", depth);
+            if verify && !panicked {
+                let verification = self.verify_chosen_move(
+                    &mut scored,
+                    &sorted_ids,
+                    stats.depth,
+                    verification_deadline,
+                );
+                current_span.record("verification", format!("{verification:?}").as_str());
+                stats.verification = Some(verification);
+            }
 
-            (depth, scored)
+            current_span.record("scored_depth", stats.depth);
+            if let Some(terminal_state) = self.terminal_state(&scored) {
+                current_span.record("terminal_state", terminal_state.to_string().as_str());
+            }
+
+            (stats, scored)
         })
     }
 
@@ -404,7 +913,7 @@ where
     // }
 
     #[allow(clippy::too_many_arguments)]
-    fn minimax(
+    pub(super) fn minimax(
         &self,
         node: Cow<GameType>,
         players: &[GameType::SnakeIDType],
@@ -412,26 +921,34 @@ where
         alpha: WrappedScore<ScoreType>,
         beta: WrappedScore<ScoreType>,
         max_depth: usize,
-        previous_return: Option<MinMaxReturn<GameType, ScoreType>>,
+        forced_extension_left: usize,
+        previous_return: Option<&MinMaxReturn<GameType, ScoreType>>,
         mut pending_moves: Vec<(GameType::SnakeIDType, Move)>,
-        worker_halt_reciever: Option<&mpsc::Receiver<()>>,
+        halt: Option<&Halt>,
     ) -> Result<MinMaxReturn<GameType, ScoreType>, AbortedEarly> {
         let mut alpha = alpha;
         let mut beta = beta;
 
-        let snake_ids = node.get_snake_ids();
+        let at_turn_boundary = is_turn_boundary(depth, players.len());
 
         // Remove pending moves for dead snakes
-        pending_moves.retain(|(snake_id, _)| snake_ids.contains(snake_id));
-
-        let node = if !snake_ids.is_empty() && pending_moves.len() == snake_ids.len() {
-            let mut simulate_result = node.simulate_with_moves(
-                &Instruments {},
-                pending_moves
-                    .into_iter()
-                    .map(|(sid, m)| (sid, vec![m]))
-                    .collect_vec(),
-            );
+        pending_moves.retain(|(snake_id, _)| node.is_alive(snake_id));
+
+        // We only advance the game once every player has had their chance to move. Dead snakes
+        // still take up a slot in `players` they just don't add a pending move, so the turn
+        // boundaries don't move around when a snake is eliminated mid-search
+        let simulated = at_turn_boundary && !pending_moves.is_empty();
+        let node = if simulated {
+            let moves = pending_moves
+                .into_iter()
+                .map(|(sid, m)| (sid, vec![m]))
+                .collect_vec();
+            if cfg!(debug_assertions) {
+                if let Err(e) = check_simulation_moves(&*node, &moves) {
+                    panic!("Minimax built impossible pending moves: {e}");
+                }
+            }
+            let mut simulate_result = node.simulate_with_moves(&self.instruments, moves);
             let new_node = simulate_result.next().unwrap().1;
             drop(simulate_result);
             pending_moves = vec![];
@@ -446,9 +963,28 @@ where
             &node,
             new_depth,
             max_depth.try_into().unwrap(),
-            players.len() as i64,
+            at_turn_boundary,
         ) {
-            return Ok(MinMaxReturn::Leaf { score: s });
+            return Ok(MinMaxReturn::Leaf {
+                score: s,
+                breakdown: self.leaf_breakdown(&node),
+            });
+        }
+
+        // Food spawns after every snake has moved, so we only branch on it right after we advance
+        // the board. The boards we branch into have no pending moves, so they won't branch again
+        if simulated {
+            if let Some(chance_return) = self.food_spawn_chance_node(
+                &node,
+                players,
+                depth,
+                max_depth,
+                forced_extension_left,
+                previous_return,
+                halt,
+            )? {
+                return Ok(chance_return);
+            }
         }
 
         let snake_id = &players[depth % players.len()];
@@ -457,7 +993,7 @@ where
 
         let is_maximizing = snake_id == node.you_id();
 
-        if node.get_health_i64(snake_id) == 0 {
+        if !node.is_alive(snake_id) {
             return self.minimax(
                 node,
                 players,
@@ -465,46 +1001,103 @@ where
                 alpha,
                 beta,
                 max_depth,
+                forced_extension_left,
                 previous_return,
                 pending_moves,
-                worker_halt_reciever,
+                halt,
             );
         }
 
         assert!(node.get_health_i64(snake_id) > 0);
-        let possible_moves = node
-            .possible_moves(&node.get_head_as_native_position(snake_id))
-            .filter(|(_, pos)| !node.is_neck(snake_id, pos))
-            .map(|(m, _)| m);
+        let mut possible_moves = moves_off_the_neck(node.as_ref(), snake_id)
+            .into_iter()
+            .map(|(m, pos)| (m, node.position_from_native(pos)))
+            .collect_vec();
+
+        // A forced opponent move doesn't add anything to search, so we take it without using up
+        // any depth
+        let mut max_depth = max_depth;
+        let mut forced_extension_left = forced_extension_left;
+        if !is_maximizing && forced_extension_left > 0 {
+            if let Some(forced) = forced_move(&*node, &possible_moves) {
+                possible_moves.retain(|(m, _)| *m == forced);
+                max_depth += 1;
+                forced_extension_left -= 1;
+            }
+        }
 
         #[allow(clippy::type_complexity)]
-        let possible_zipped: Vec<(Move, Option<MinMaxReturn<GameType, ScoreType>>)> = self
-            .options
-            .move_ordering
-            .order_moves(previous_return, possible_moves);
+        let possible_zipped: Vec<(Move, Option<&MinMaxReturn<GameType, ScoreType>>)> =
+            self.settings.options.move_ordering.order_moves(
+                &*node,
+                snake_id,
+                depth,
+                previous_return,
+                possible_moves.into_iter().map(|(m, _)| m),
+            );
 
         let mut alpha_beta_cutoff = false;
 
-        for (dir, previous_return) in possible_zipped.into_iter() {
-            if let Some(worker_halt_reciever) = worker_halt_reciever {
-                if worker_halt_reciever.try_recv().is_ok() {
-                    return Err(AbortedEarly);
-                }
+        for (i, (dir, previous_return)) in possible_zipped.into_iter().enumerate() {
+            if halt.is_some_and(Halt::should_stop) {
+                return Err(AbortedEarly);
             }
 
             let mut new_pending_moves = pending_moves.clone();
             new_pending_moves.push((snake_id.clone(), dir));
-            let next_move_return = self.minimax(
-                node.clone(),
-                players,
-                depth + 1,
-                alpha,
-                beta,
-                max_depth,
-                previous_return,
-                new_pending_moves,
-                worker_halt_reciever,
-            )?;
+
+            let reduced_max_depth = if is_maximizing || i == 0 {
+                None
+            } else {
+                self.reduced_max_depth(depth, max_depth, players.len())
+            };
+
+            let next_move_return = if let Some(reduced_max_depth) = reduced_max_depth {
+                let reduced_return = self.minimax(
+                    node.clone(),
+                    players,
+                    depth + 1,
+                    alpha,
+                    beta,
+                    reduced_max_depth,
+                    forced_extension_left,
+                    previous_return,
+                    new_pending_moves.clone(),
+                    halt,
+                )?;
+
+                // The reduced search says this move is better for the opponent than the moves
+                // we've already looked at, so we can't trust it without a full depth search
+                if *reduced_return.score() < beta {
+                    self.minimax(
+                        node.clone(),
+                        players,
+                        depth + 1,
+                        alpha,
+                        beta,
+                        max_depth,
+                        forced_extension_left,
+                        previous_return,
+                        new_pending_moves,
+                        halt,
+                    )?
+                } else {
+                    reduced_return
+                }
+            } else {
+                self.minimax(
+                    node.clone(),
+                    players,
+                    depth + 1,
+                    alpha,
+                    beta,
+                    max_depth,
+                    forced_extension_left,
+                    previous_return,
+                    new_pending_moves,
+                    halt,
+                )?
+            };
             let value = *next_move_return.score();
             options.push((dir, next_move_return));
 
@@ -544,15 +1137,432 @@ where
         })
     }
 
-    fn max_duration(&self) -> Duration {
-        let timeout = self
-          .game_info
-          .timeout
-          .try_into()
-          .expect("We are dealing with things on the order of hundreds of millis or a couple seconds. We shouldn't have a padding that can't convert from an i64 to a u64");
-        let timeout = Duration::from_millis(timeout);
+    /// Search each way food could spawn on this board, and pick the outcome from the weighted
+    /// median score
+    ///
+    /// Returns None when food spawns are turned off, the spawn chance is too low to ever be the
+    /// median, or there is nowhere for food to spawn
+    fn food_spawn_chance_node(
+        &self,
+        node: &GameType,
+        players: &[GameType::SnakeIDType],
+        depth: usize,
+        max_depth: usize,
+        forced_extension_left: usize,
+        previous_return: Option<&MinMaxReturn<GameType, ScoreType>>,
+        halt: Option<&Halt>,
+    ) -> Result<Option<MinMaxReturn<GameType, ScoreType>>, AbortedEarly> {
+        let spawn_chance = self.settings.options.food_spawn_chance.clamp(0.0, 1.0);
+        if !self.settings.options.features.food_spawns || spawn_chance < 0.5 {
+            return Ok(None);
+        }
+        let Some(food_spawner) = &self.settings.food_spawner else {
+            return Ok(None);
+        };
 
-        timeout - self.options.network_latency_padding
+        let spawned = food_spawner(node);
+        if spawned.is_empty() {
+            return Ok(None);
+        }
+        let chance_per_spawn = spawn_chance / spawned.len() as f64;
+
+        // When the spawn is certain there's no board without it to search
+        let no_spawn = (spawn_chance < 1.0).then(|| (1.0 - spawn_chance, node.clone()));
+        let outcomes = no_spawn
+            .into_iter()
+            .chain(spawned.into_iter().map(|board| (chance_per_spawn, board)))
+            .map(|(chance, board)| {
+                // The median doesn't work with alpha-beta bounds, so every outcome gets a full
+                // window
+                let outcome = self.minimax(
+                    Cow::Owned(board),
+                    players,
+                    depth,
+                    WrappedScore::<ScoreType>::worst_possible_score(),
+                    WrappedScore::<ScoreType>::best_possible_score(),
+                    max_depth,
+                    forced_extension_left,
+                    previous_return,
+                    vec![],
+                    halt,
+                )?;
+
+                Ok((chance, outcome))
+            })
+            .collect::<Result<Vec<_>, AbortedEarly>>()?;
+
+        let scores = outcomes
+            .iter()
+            .map(|(chance, outcome)| (*chance, *outcome.score()))
+            .collect_vec();
+        let chosen = weighted_median(&scores).expect("There is always at least one spawn");
+
+        Ok(outcomes.into_iter().nth(chosen).map(|(_, outcome)| outcome))
+    }
+
+    /// Returns the max depth to use for a reduced opponent search, or None if reducing wouldn't
+    /// save us anything
+    ///
+    /// We only ever score nodes on turn boundaries, so a reduction that doesn't move the next turn
+    /// boundary past the reduced max depth would be the same search with extra overhead
+    fn reduced_max_depth(
+        &self,
+        depth: usize,
+        max_depth: usize,
+        num_players: usize,
+    ) -> Option<usize> {
+        let reduction = self.settings.options.opponent_depth_reduction * num_players;
+        if reduction == 0 {
+            return None;
+        }
+
+        let next_turn_boundary = next_turn_boundary(depth, num_players);
+        if next_turn_boundary >= max_depth {
+            return None;
+        }
+
+        Some(max_depth.saturating_sub(reduction))
+    }
+
+    pub(crate) fn max_duration(&self) -> Duration {
+        search_budget(&self.settings.game_info, &self.settings.options)
+    }
+
+    /// The payoff of each of our moves against every joint reply of our opponents, when everyone
+    /// moves at the same time
+    ///
+    /// Each board is scored with a paranoid search [SnakeOptions::simultaneous_root_depth] turns
+    /// deep. The replies are in the same order for each of our moves, so the rows line up
+    fn simultaneous_payoffs(
+        &self,
+        players: &[GameType::SnakeIDType],
+    ) -> Vec<(Move, Vec<WrappedScore<ScoreType>>)> {
+        let node = &self.game;
+        let you_id = node.you_id();
+        let moves_for = |snake_id: &GameType::SnakeIDType| {
+            moves_off_the_neck(node, snake_id)
+                .into_iter()
+                .map(|(m, _)| m)
+                .collect_vec()
+        };
+
+        let opponents = players
+            .iter()
+            .filter(|snake_id| *snake_id != you_id && node.is_alive(snake_id))
+            .collect_vec();
+        if opponents.is_empty() {
+            return vec![];
+        }
+        let replies = opponents
+            .iter()
+            .map(|snake_id| moves_for(*snake_id))
+            .multi_cartesian_product()
+            .collect_vec();
+
+        let max_depth = self.settings.options.simultaneous_root_depth * players.len();
+
+        moves_for(you_id)
+            .into_iter()
+            .map(|our_move| {
+                let payoffs = replies
+                    .iter()
+                    .map(|reply| {
+                        let moves = std::iter::once((you_id.clone(), vec![our_move]))
+                            .chain(
+                                opponents
+                                    .iter()
+                                    .zip(reply)
+                                    .map(|(snake_id, m)| ((*snake_id).clone(), vec![*m])),
+                            )
+                            .collect_vec();
+                        let board = node
+                            .simulate_with_moves(&Instruments::default(), moves)
+                            .next()
+                            .expect("A single move for each snake always simulates a board")
+                            .1;
+
+                        let result = self.minimax(
+                            Cow::Owned(board),
+                            players,
+                            0,
+                            WrappedScore::<ScoreType>::worst_possible_score(),
+                            WrappedScore::<ScoreType>::best_possible_score(),
+                            max_depth,
+                            0,
+                            None,
+                            vec![],
+                            None,
+                        );
+
+                        *result
+                            .expect("Without a halt receiver the search never aborts")
+                            .score()
+                    })
+                    .collect_vec();
+
+                (our_move, payoffs)
+            })
+            .collect_vec()
+    }
+
+    /// Replace the move the paranoid search chose at the root with the one the
+    /// [RootStrategy::MinimaxRegret] picks from the `payoffs`
+    ///
+    /// Moves the full search sees as a loss are left out, unless every move is one
+    fn choose_simultaneous_root_move(
+        &self,
+        scored: &mut MinMaxReturn<GameType, ScoreType>,
+        payoffs: Vec<(Move, Vec<WrappedScore<ScoreType>>)>,
+    ) {
+        let you_id = self.game.you_id();
+        let Some(root_options) = scored.first_options_for_snake(you_id) else {
+            return;
+        };
+        let not_losing = root_options
+            .iter()
+            .filter(|(_, r)| !matches!(r.score(), WrappedScore::Lose(..)))
+            .map(|(m, _)| *m)
+            .collect_vec();
+        let paranoid_move = root_options.first().map(|(m, _)| *m);
+
+        let candidates = payoffs
+            .into_iter()
+            .filter(|(m, _)| not_losing.is_empty() || not_losing.contains(m))
+            .collect_vec();
+        let Some(regret_move) = minimax_regret_move(&candidates) else {
+            return;
+        };
+
+        if Some(regret_move) != paranoid_move {
+            info!(
+                paranoid_move = ?paranoid_move,
+                regret_move = ?regret_move,
+                "The simultaneous root picked a different move than the paranoid search"
+            );
+            scored.choose_option(regret_move);
+        }
+    }
+
+    /// When the search proves every one of our moves loses, replace its choice with the one from
+    /// [panic_move]. Returns whether we were panicking
+    ///
+    /// The payoff matrix is only built here if the [RootStrategy] didn't already, since panicking is
+    /// rare and the boards where it happens have few moves left to look at
+    fn choose_panic_move(
+        &self,
+        scored: &mut MinMaxReturn<GameType, ScoreType>,
+        players: &[GameType::SnakeIDType],
+        payoffs: Option<&[(Move, Vec<WrappedScore<ScoreType>>)]>,
+    ) -> bool {
+        let you_id = self.game.you_id();
+        let Some(root_options) = scored.first_options_for_snake(you_id) else {
+            return false;
+        };
+        let root_options = root_options
+            .iter()
+            .map(|(m, r)| (*m, *r.score()))
+            .collect_vec();
+        if root_options.is_empty()
+            || !root_options
+                .iter()
+                .all(|(_, score)| matches!(score, WrappedScore::Lose(..)))
+        {
+            return false;
+        }
+
+        let built_payoffs;
+        let payoffs = match payoffs {
+            Some(payoffs) => payoffs,
+            None if players.len() > 1 => {
+                built_payoffs = self.simultaneous_payoffs(players);
+                &built_payoffs
+            }
+            None => &[],
+        };
+
+        let Some(panic_move) = panic_move(&root_options, payoffs) else {
+            return false;
+        };
+        let paranoid_move = root_options.first().map(|(m, _)| *m);
+        if Some(panic_move) != paranoid_move {
+            info!(
+                paranoid_move = ?paranoid_move,
+                panic_move = ?panic_move,
+                "Every move loses, panicking to the one that holds on the longest"
+            );
+            scored.choose_option(panic_move);
+        }
+
+        true
+    }
+
+    /// Search only the move the main search picked, [VERIFICATION_TURNS] turns deeper than the
+    /// `searched_depth` it got to, and switch to the [fallback_move] if that finds a forced loss
+    ///
+    /// This stops at the `deadline`, and keeps the move if it didn't finish
+    fn verify_chosen_move(
+        &self,
+        scored: &mut MinMaxReturn<GameType, ScoreType>,
+        players: &[GameType::SnakeIDType],
+        searched_depth: usize,
+        deadline: Instant,
+    ) -> Verification {
+        let you_id = self.game.you_id();
+        let Some(root) = scored.first_options_for_snake(you_id) else {
+            return Verification::Skipped;
+        };
+        let root_options = root.iter().map(|(m, r)| (*m, *r.score())).collect_vec();
+        // A win or a loss is already as deep as that line goes
+        let (Some((chosen, WrappedScore::Scored(_))), Some((_, chosen_return))) =
+            (root_options.first().copied(), root.first())
+        else {
+            return Verification::Skipped;
+        };
+
+        let halt = Halt::Deadline(deadline);
+        let verified = self.minimax(
+            Cow::Borrowed(&self.game),
+            players,
+            1,
+            WrappedScore::<ScoreType>::worst_possible_score(),
+            WrappedScore::<ScoreType>::best_possible_score(),
+            searched_depth + VERIFICATION_TURNS * players.len(),
+            self.settings.options.forced_move_extension,
+            Some(chosen_return),
+            vec![(you_id.clone(), chosen)],
+            Some(&halt),
+        );
+
+        let verification = match verified {
+            Err(AbortedEarly) => Verification::Unfinished,
+            Ok(result) if !matches!(result.score(), WrappedScore::Lose(..)) => {
+                Verification::Confirmed
+            }
+            Ok(_) => match fallback_move(&root_options) {
+                Some(fallback) => {
+                    scored.choose_option(fallback);
+                    Verification::Overturned {
+                        refuted: chosen,
+                        fallback,
+                    }
+                }
+                None => Verification::Refuted,
+            },
+        };
+
+        let (verified_moves, overturned_moves) = verification.record();
+        if let Verification::Overturned { refuted, fallback } = verification {
+            info!(
+                refuted = ?refuted,
+                fallback = ?fallback,
+                verified_moves,
+                overturned_moves,
+                "Verifying our move found a forced loss, falling back to the next best one"
+            );
+        }
+
+        verification
+    }
+
+    /// Every turn of the line the search expects, as the board at the start of the turn and the
+    /// moves made in it, by playing out its chosen route
+    ///
+    /// Moves are collected until a snake comes up for a second time, which means a new turn has
+    /// started, and then simulated together like the search does at every turn boundary
+    #[allow(clippy::type_complexity)]
+    fn principal_variation_turns(
+        &self,
+        result: &MinMaxReturn<GameType, ScoreType>,
+    ) -> Vec<(GameType, Vec<(GameType::SnakeIDType, Move)>)> {
+        let mut turns = vec![];
+        let mut board = self.game.clone();
+        let mut pending: Vec<(GameType::SnakeIDType, Move)> = vec![];
+        for (sid, m) in result.chosen_route() {
+            if pending.iter().any(|(pending_sid, _)| *pending_sid == sid) {
+                let moves = std::mem::take(&mut pending);
+                let next = simulate_one(&board, &moves);
+                turns.push((std::mem::replace(&mut board, next), moves));
+            }
+            pending.push((sid, m));
+        }
+        if !pending.is_empty() {
+            turns.push((board, pending));
+        }
+
+        turns
+    }
+
+    /// The board at the end of the line the search expects
+    fn principal_variation_leaf(&self, result: &MinMaxReturn<GameType, ScoreType>) -> GameType {
+        match self.principal_variation_turns(result).pop() {
+            Some((board, moves)) => simulate_one(&board, &moves),
+            None => self.game.clone(),
+        }
+    }
+
+    /// How we die at the end of the line the search expects, if it ends in a loss or a tie
+    ///
+    /// The score only says that a line loses, so this plays the line out to the turn we die in to
+    /// see why. That's cheap enough to do once per search, but not for every leaf
+    pub fn terminal_state(
+        &self,
+        result: &MinMaxReturn<GameType, ScoreType>,
+    ) -> Option<TerminalState> {
+        if !matches!(
+            result.score(),
+            WrappedScore::Lose(..) | WrappedScore::Tie(..)
+        ) {
+            return None;
+        }
+
+        let you_id = self.game.you_id();
+        let (before, moves) = self.principal_variation_turns(result).pop()?;
+        let after = simulate_one(&before, &moves);
+
+        classify_death(&before, &after, &moves, you_id)
+    }
+
+    /// The [FeatureSnapshot] to keep on a leaf, when the [FeatureFlags::leaf_breakdown] flag is on
+    fn leaf_breakdown(&self, node: &GameType) -> Option<Arc<[(&'static str, f64)]>> {
+        if !self.settings.options.features.leaf_breakdown {
+            return None;
+        }
+        let feature_snapshot = self.settings.feature_snapshot?;
+
+        Some(feature_snapshot(node).into())
+    }
+
+    /// When the move we'd pick changed between two iterations, log the [FeatureSnapshot] at the
+    /// end of each of their lines. Does nothing without a [FeatureSnapshot]
+    pub(super) fn log_flipped_features(
+        &self,
+        (shallow_depth, shallow): (usize, &MinMaxReturn<GameType, ScoreType>),
+        (deep_depth, deep): (usize, &MinMaxReturn<GameType, ScoreType>),
+    ) {
+        let Some(feature_snapshot) = self.settings.feature_snapshot else {
+            return;
+        };
+        let you_id = self.game.you_id();
+        let shallow_move = shallow.your_best_move(you_id);
+        let deep_move = deep.your_best_move(you_id);
+        if shallow_move == deep_move {
+            return;
+        }
+
+        let shallow_features = feature_snapshot(&self.principal_variation_leaf(shallow));
+        let deep_features = feature_snapshot(&self.principal_variation_leaf(deep));
+
+        info!(
+            shallow_depth,
+            shallow_move = ?shallow_move,
+            shallow_score = ?shallow.score(),
+            shallow_features = %feature_line(&shallow_features),
+            deep_depth,
+            deep_move = ?deep_move,
+            deep_score = ?deep.score(),
+            deep_features = %feature_line(&deep_features),
+            "The chosen move flipped between depths"
+        );
     }
 
     /// This will do a iterative deepening minimax until we reach the time limit [with some padding
@@ -570,115 +1580,369 @@ where
         players: Vec<GameType::SnakeIDType>,
         initial_return: Option<MinMaxReturn<GameType, ScoreType>>,
     ) -> (usize, MinMaxReturn<GameType, ScoreType>) {
-        let current_span = tracing::Span::current();
+        let (stats, scored) = self.deepened_minimax_with_stats(players, initial_return);
 
-        let max_duration = self.max_duration();
-        let node = &self.game;
+        (stats.depth, scored)
+    }
 
-        let started_at = Instant::now();
-        let you_id = node.you_id().clone();
-        let threads_you_id = you_id.clone();
-
-        let (to_main_thread, from_worker_thread) = mpsc::channel();
-        let (suspend_worker, worker_halt_reciever) = mpsc::channel();
-
-        thread::scope(|s| {
-            s.spawn(move || {
-                let you_id = threads_you_id;
-                let mut current_depth = players.len();
-                let mut current_return = initial_return;
-                let copy = self.clone();
-
-                loop {
-                    let next = {
-                        let result: Result<MinMaxReturn<_, _>, AbortedEarly> = copy.minimax(
-                            Cow::Borrowed(&self.game),
-                            &players,
-                            0,
-                            WrappedScore::<ScoreType>::worst_possible_score(),
-                            WrappedScore::<ScoreType>::best_possible_score(),
-                            current_depth,
-                            current_return,
-                            vec![],
-                            Some(&worker_halt_reciever),
+    /// Hand an iterative deepening search of this game to the [search_pool]
+    ///
+    /// Every iteration that finishes is sent back through [DeepeningWorker::results]. The search
+    /// keeps going until it proves how the game ends, its results are dropped, it is stopped with
+    /// [DeepeningWorker::stop] or `deadline` passes. If the pool can't get to it before `deadline`
+    /// it never starts. The `priority` says whether this search answers a move, or only helps one
+    /// along
+    ///
+    /// While it runs it's pinned to this game's lane from [game_cores], if it gets one
+    pub(crate) fn spawn_deepening_worker(
+        &self,
+        players: Vec<GameType::SnakeIDType>,
+        initial_return: Option<MinMaxReturn<GameType, ScoreType>>,
+        score_memo: Option<Arc<ScoreMemo<ScoreType>>>,
+        instruments: Instruments,
+        deadline: Instant,
+        priority: Priority,
+    ) -> DeepeningWorker<GameType, ScoreType> {
+        let (to_main_thread, results) = mpsc::channel();
+        let (halt, worker_halt_reciever) = mpsc::channel();
+        let worker_halt = Halt::Signal(worker_halt_reciever, deadline);
+
+        let mut copy = self.clone();
+        copy.score_memo = score_memo;
+        copy.instruments = instruments;
+
+        let lane = game_cores().assign(&self.settings.game_info.id);
+        if let Some(lane) = &lane {
+            tracing::Span::current().record("cores", lane.to_string().as_str());
+        }
+
+        let handle = search_pool().spawn(deadline, priority, move || {
+            let _pinned = lane.as_ref().map(PinnedThread::pin);
+            let you_id = copy.game.you_id().clone();
+            let root = copy.game.clone();
+            let mut current_depth = players.len();
+            let mut current_return = initial_return.map(Arc::new);
+
+            loop {
+                let next = {
+                    let result: Result<MinMaxReturn<_, _>, AbortedEarly> = copy.minimax(
+                        Cow::Borrowed(&root),
+                        &players,
+                        0,
+                        WrappedScore::<ScoreType>::worst_possible_score(),
+                        WrappedScore::<ScoreType>::best_possible_score(),
+                        current_depth,
+                        copy.settings.options.forced_move_extension,
+                        current_return.as_deref(),
+                        vec![],
+                        Some(&worker_halt),
+                    );
+
+                    if let Ok(ref result) = result {
+                        let current_span = tracing::Span::current();
+                        current_span.record("score", format!("{:?}", result.score()).as_str());
+                        current_span.record(
+                            "direction",
+                            format!("{:?}", result.your_best_move(&you_id)).as_str(),
                         );
+                    }
 
-                        if let Ok(ref result) = result {
-                            let current_span = tracing::Span::current();
-                            current_span.record("score", format!("{:?}", result.score()).as_str());
-                            current_span.record(
-                                "direction",
-                                format!("{:?}", result.your_best_move(&you_id)).as_str(),
-                            );
-                        }
+                    result
+                };
+
+                let next = match next {
+                    Ok(x) => Arc::new(x),
+                    Err(AbortedEarly) => break,
+                };
+
+                let game_over = matches!(
+                    next.score().terminal_depth(),
+                    Some(terminal_depth) if current_depth >= terminal_depth.try_into().unwrap()
+                );
+                // Once our move is proven, deeper iterations would only spend the time on a move
+                // we're going to make anyway
+                let action = if game_over || next.root_proof(&you_id).is_some() {
+                    FromWorkerAction::Stop
+                } else {
+                    FromWorkerAction::KeepGoing
+                };
+
+                let send_result = to_main_thread.send((action, current_depth, Arc::clone(&next)));
+
+                if send_result.is_err() || matches!(action, FromWorkerAction::Stop) {
+                    return;
+                }
 
-                        result
-                    };
+                current_return = Some(next);
 
-                    let next = match next {
-                        Ok(x) => x,
-                        Err(AbortedEarly) => break,
-                    };
+                current_depth += players.len();
+            }
+        });
 
-                    let current_score = next.score();
-                    let terminal_depth = current_score.terminal_depth();
+        DeepeningWorker {
+            handle,
+            results,
+            halt,
+        }
+    }
 
-                    let action = match terminal_depth {
-                        Some(terminal_depth) => {
-                            if current_depth >= terminal_depth.try_into().unwrap() {
-                                FromWorkerAction::Stop
-                            } else {
-                                FromWorkerAction::KeepGoing
-                            }
-                        }
-                        None => FromWorkerAction::KeepGoing,
-                    };
+    /// The same as [MinimaxSnake::deepened_minimax_until_timelimit()] but also returns the
+    /// [SearchStats] for the search
+    ///
+    /// Leaf scores are remembered in a [ScoreMemo] for the length of the search, so a board that is
+    /// reached by more than one line of moves is only scored once
+    ///
+    /// The deepening itself runs on the [search_pool]. If the pool is too busy to start it within
+    /// [MAX_QUEUE_DELAY], we deepen on this thread instead. With
+    /// [FeatureFlags::cooperative_search] on it always runs on this thread, with a
+    /// [DeepeningStepper]
+    ///
+    /// Every simulation is counted in a [SimulationStats], whose totals end up on the span
+    pub fn deepened_minimax_with_stats(
+        mut self,
+        players: Vec<GameType::SnakeIDType>,
+        initial_return: Option<MinMaxReturn<GameType, ScoreType>>,
+    ) -> (SearchStats, MinMaxReturn<GameType, ScoreType>) {
+        let current_span = tracing::Span::current();
 
-                    let send_result = to_main_thread.send((action, current_depth, next.clone()));
+        let max_duration = self.max_duration();
+        let node = &self.game;
 
-                    if send_result.is_err() || matches!(action, FromWorkerAction::Stop) {
-                        return;
-                    }
+        let started_at = Instant::now();
+        let you_id = node.you_id().clone();
 
-                    current_return = Some(next);
+        let score_memo = Arc::new(ScoreMemo::default());
+        let simulation_stats = Arc::new(SimulationStats::default());
+        self.instruments = Instruments::recording(simulation_stats.clone());
 
-                    current_depth += players.len();
-                }
-            });
+        let deadline = started_at + max_duration;
+        let features = self.settings.options.features;
+        let search_memo = features.score_memo.then(|| score_memo.clone());
+        let (current, iterations, pv_flips) = if features.cooperative_search {
+            self.deepen_on_this_thread(players, initial_return, search_memo, deadline)
+        } else {
+            self.deepen_on_search_pool(players, initial_return, search_memo, deadline)
+        };
 
-            let mut current = None;
+        if let Some((depth, result)) = &current {
+            current_span.record("chosen_score", format!("{:?}", result.score()).as_str());
+            current_span.record(
+                "chosen_direction",
+                format!("{:?}", result.your_best_move(&you_id)).as_str(),
+            );
+            current_span.record("depth", depth);
+        }
+        current_span.record("pv_flips", pv_flips);
+
+        let (depth, result) = current.expect("We weren't able to do even a single layer of minmax");
+        // The worker may still be holding on to this result for its next depth, in which case
+        // this is the one time the tree gets copied
+        let result = Arc::try_unwrap(result).unwrap_or_else(|shared| (*shared).clone());
+        let stats = SearchStats {
+            depth,
+            iterations,
+            time_used: started_at.elapsed(),
+            budget: max_duration,
+            pv_flips,
+            score_memo_lookups: score_memo.lookups(),
+            score_memo_hits: score_memo.hits(),
+            simulations: simulation_stats.simulations(),
+            simulation_time: simulation_stats.total_time(),
+            verification: None,
+        };
+        if let Some(hit_rate) = stats.score_memo_hit_rate() {
+            current_span.record("score_memo_hit_rate", hit_rate);
+        }
+        simulation_stats.record(&current_span, max_duration);
 
-            while started_at.elapsed() < max_duration {
-                if let Ok((action, depth, result)) = from_worker_thread.try_recv() {
-                    // println!("{}", self.game.evaluate_moves(&result.all_moves()));
-                    current = Some((depth, result));
+        (stats, result)
+    }
 
-                    match action {
-                        FromWorkerAction::KeepGoing => {}
-                        FromWorkerAction::Stop => {
-                            info!(depth, "This game is over, no need to keep going");
-                            break;
+    /// Run the iterative deepening for [MinimaxSnake::deepened_minimax_with_stats()] on the
+    /// [search_pool], while this thread waits for its results until the deadline
+    ///
+    /// Returns the deepest result, the number of iterations that finished and the number of
+    /// times the chosen move flipped between them
+    #[allow(clippy::type_complexity)]
+    fn deepen_on_search_pool(
+        &self,
+        players: Vec<GameType::SnakeIDType>,
+        initial_return: Option<MinMaxReturn<GameType, ScoreType>>,
+        score_memo: Option<Arc<ScoreMemo<ScoreType>>>,
+        deadline: Instant,
+    ) -> (
+        Option<(usize, Arc<MinMaxReturn<GameType, ScoreType>>)>,
+        usize,
+        usize,
+    ) {
+        let current_span = tracing::Span::current();
+        let you_id = self.game.you_id().clone();
+
+        let queued_at = Instant::now();
+        let queue_cutoff =
+            queued_at + MAX_QUEUE_DELAY.min(deadline.saturating_duration_since(queued_at) / 10);
+        let worker = self.spawn_deepening_worker(
+            players.clone(),
+            initial_return,
+            score_memo.clone(),
+            self.instruments.clone(),
+            deadline,
+            Priority::Answer,
+        );
+
+        let mut current = None;
+        let mut iterations = 0;
+        let mut pv_stability = PvStability::new();
+
+        while Instant::now() < deadline {
+            if let Ok((action, depth, result)) = worker.results.try_recv() {
+                // println!("{}", self.game.evaluate_moves(&result.all_moves()));
+                if let Some((previous_depth, previous)) = &current {
+                    self.log_flipped_features((*previous_depth, &**previous), (depth, &*result));
+                }
+                pv_stability.record(
+                    depth,
+                    result.your_best_move(&you_id),
+                    *result.score(),
+                    result.chosen_route().into_iter().map(|(_, m)| m),
+                );
+                let proof = result.root_proof(&you_id);
+                current = Some((depth, result));
+                iterations += 1;
+
+                match action {
+                    FromWorkerAction::KeepGoing => {}
+                    FromWorkerAction::Stop => {
+                        let banked_ms = deadline
+                            .saturating_duration_since(Instant::now())
+                            .as_millis();
+                        match proof {
+                            Some(proof) => info!(
+                                depth,
+                                proof = ?proof,
+                                proof_depth = proof.depth(),
+                                banked_ms,
+                                "Our move is proven, no need to keep going"
+                            ),
+                            None => {
+                                info!(depth, banked_ms, "This game is over, no need to keep going")
+                            }
                         }
+                        break;
                     }
                 }
+            } else if worker.handle.status() == TaskStatus::Panicked {
+                panic!("The search worker panicked");
+            } else if Instant::now() >= queue_cutoff && worker.handle.cancel() {
+                let queue_delay_ms = queued_at.elapsed().as_millis() as u64;
+                current_span.record("pool_queue_delay_ms", queue_delay_ms);
+                tracing::warn!(
+                    queue_delay_ms,
+                    "The search pool didn't get to this search in time, deepening on this thread instead"
+                );
+
+                return self.deepen_on_this_thread(players, None, score_memo, deadline);
             }
+        }
+
+        // We can't kill the worker so we use this to help it know when to stop
+        let never_started = worker.stop();
+        if let Some(queue_delay) = worker.handle.queue_delay() {
+            current_span.record("pool_queue_delay_ms", queue_delay.as_millis() as u64);
+        }
+
+        if current.is_none() && never_started {
+            tracing::warn!(
+                "The search pool didn't get to this search in time, searching a single turn instead"
+            );
+            current = Some(self.search_one_turn(&players));
+        }
+        pv_stability.warn_on_discontinuity();
 
-            // We send a signal to the worker thread to stop
-            // We can't kill the thread so we use this to help the
-            // worker know when to stop
-            let _ = suspend_worker.send(());
+        (current, iterations, pv_stability.flips())
+    }
+
+    /// Run the iterative deepening for [MinimaxSnake::deepened_minimax_with_stats()] right here
+    /// with a [DeepeningStepper], for when [FeatureFlags::cooperative_search] is on
+    ///
+    /// Returns the same as [MinimaxSnake::deepen_on_search_pool()]
+    #[allow(clippy::type_complexity)]
+    fn deepen_on_this_thread(
+        &self,
+        players: Vec<GameType::SnakeIDType>,
+        initial_return: Option<MinMaxReturn<GameType, ScoreType>>,
+        score_memo: Option<Arc<ScoreMemo<ScoreType>>>,
+        deadline: Instant,
+    ) -> (
+        Option<(usize, Arc<MinMaxReturn<GameType, ScoreType>>)>,
+        usize,
+        usize,
+    ) {
+        let mut copy = self.clone();
+        copy.score_memo = score_memo;
+
+        let mut stepper = copy.stepper(players.clone(), initial_return);
+        // A single step with the whole budget, there's nothing else waiting on this thread
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if stepper.step(remaining) == SearchStatus::Done {
+            let banked_ms = deadline
+                .saturating_duration_since(Instant::now())
+                .as_millis();
+            info!(banked_ms, "Finished searching before the deadline");
+        }
 
-            if let Some((depth, result)) = &current {
-                current_span.record("chosen_score", format!("{:?}", result.score()).as_str());
-                current_span.record(
-                    "chosen_direction",
-                    format!("{:?}", result.your_best_move(&you_id)).as_str(),
+        let iterations = stepper.iterations();
+        let pv_flips = stepper.pv_flips();
+        let current = match stepper.finish() {
+            Some((depth, result)) => (depth, Arc::new(result)),
+            None => {
+                tracing::warn!(
+                    "Not even a single depth finished in time, searching a single turn instead"
                 );
-                current_span.record("depth", depth);
+                self.search_one_turn(&players)
             }
+        };
 
-            current.expect("We weren't able to do even a single layer of minmax")
-        })
+        (Some(current), iterations, pv_flips)
+    }
+
+    /// Search a single turn with nothing to stop it, for when the deepening didn't leave us with
+    /// anything at all
+    fn search_one_turn(
+        &self,
+        players: &[GameType::SnakeIDType],
+    ) -> (usize, Arc<MinMaxReturn<GameType, ScoreType>>) {
+        let depth = players.len();
+        let result = self
+            .minimax(
+                Cow::Borrowed(&self.game),
+                players,
+                0,
+                WrappedScore::<ScoreType>::worst_possible_score(),
+                WrappedScore::<ScoreType>::best_possible_score(),
+                depth,
+                0,
+                None,
+                vec![],
+                None,
+            )
+            .expect("Without a halt receiver the search never aborts");
+
+        (depth, Arc::new(result))
+    }
+
+    /// Start an iterative deepening search of this game that runs on the caller's thread, a
+    /// [DeepeningStepper::step()] at a time
+    ///
+    /// `players` should have ourselves first. Unlike [MinimaxSnake::deepened_minimax_with_stats()]
+    /// this keeps no [ScoreMemo] or [SimulationStats] of its own, the stepper uses whatever this
+    /// snake was given
+    pub fn stepper(
+        self,
+        players: Vec<GameType::SnakeIDType>,
+        initial_return: Option<MinMaxReturn<GameType, ScoreType>>,
+    ) -> DeepeningStepper<Self> {
+        DeepeningStepper::new(self, players, initial_return)
     }
 
     // /// This differs from the `deepened_minimax_until_timelimit` in that not only do we start a
@@ -920,13 +2184,24 @@ where
         let mut sorted_ids = self.game.get_snake_ids();
         sorted_ids.sort_by_key(|snake_id| if snake_id == my_id { -1 } else { 1 });
 
+        self.single_minimax_for_players(&sorted_ids, max_turns)
+    }
+
+    /// The same as [MinimaxSnake::single_minimax()], for callers that already have the players in
+    /// order with ourselves first
+    pub(crate) fn single_minimax_for_players(
+        &self,
+        players: &[GameType::SnakeIDType],
+        max_turns: usize,
+    ) -> MinMaxReturn<GameType, ScoreType> {
         self.minimax(
             Cow::Borrowed(&self.game),
-            &sorted_ids,
+            players,
             0,
             WrappedScore::<ScoreType>::worst_possible_score(),
             WrappedScore::<ScoreType>::best_possible_score(),
-            max_turns * sorted_ids.len(),
+            max_turns * players.len(),
+            self.settings.options.forced_move_extension,
             None,
             vec![],
             None,
@@ -961,7 +2236,8 @@ where
                     WrappedScore::<ScoreType>::worst_possible_score(),
                     WrappedScore::<ScoreType>::best_possible_score(),
                     current_depth,
-                    current_return,
+                    self.settings.options.forced_move_extension,
+                    current_return.as_ref(),
                     vec![],
                     None,
                 )
@@ -984,7 +2260,32 @@ where
 }
 
 #[derive(Debug, Copy, Clone)]
-enum FromWorkerAction {
+pub(crate) enum FromWorkerAction {
     KeepGoing,
     Stop,
 }
+
+/// An iterative deepening search running on the [search_pool], see
+/// [MinimaxSnake::spawn_deepening_worker]
+#[derive(Debug)]
+#[allow(clippy::type_complexity)]
+pub(crate) struct DeepeningWorker<GameType, ScoreType> {
+    pub(crate) handle: TaskHandle,
+    pub(crate) results: mpsc::Receiver<(
+        FromWorkerAction,
+        usize,
+        Arc<MinMaxReturn<GameType, ScoreType>>,
+    )>,
+    halt: mpsc::Sender<()>,
+}
+
+impl<GameType, ScoreType> DeepeningWorker<GameType, ScoreType> {
+    /// Tell the search to stop, and take it off the queue if it never started
+    ///
+    /// Returns true if it never started
+    pub(crate) fn stop(&self) -> bool {
+        let _ = self.halt.send(());
+
+        self.handle.cancel()
+    }
+}
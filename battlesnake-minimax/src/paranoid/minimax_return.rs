@@ -1,9 +1,39 @@
 use battlesnake_game_types::types::{Move, SnakeIDGettableGame};
 use dotavious::{attributes::NodeAttributes, EdgeBuilder, GraphBuilder, NodeBuilder};
-use std::{fmt::Debug, sync::atomic::AtomicUsize};
+use itertools::Itertools;
+use std::{cmp::Reverse, fmt::Debug, sync::Arc};
 use text_trees::StringTreeNode;
 
-use super::WrappedScore;
+use super::{MoveReasoning, WrappedScore};
+
+/// The engine truncates anything longer than this
+pub const MAX_SHOUT_LENGTH: usize = 256;
+
+/// Why searching deeper can't change the move we pick, see [MinMaxReturn::root_proof]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RootProof {
+    /// Our best move wins whatever the opponents do
+    ForcedWin {
+        /// The depth in the tree the win is found at
+        depth: i64,
+    },
+    /// Every other move loses whatever the opponents do, so our best move is the only one that
+    /// doesn't. This includes having only one move to begin with
+    OnlyMove {
+        /// The depth of the deepest of those losses, which is how far the search had to look to
+        /// prove them all
+        depth: i64,
+    },
+}
+
+impl RootProof {
+    /// How deep in the tree the search had to look for the proof
+    pub fn depth(&self) -> i64 {
+        match self {
+            RootProof::ForcedWin { depth } | RootProof::OnlyMove { depth } => *depth,
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 /// This is returned from an iteration of the minimax algorithm
@@ -45,6 +75,9 @@ pub enum MinMaxReturn<
     Leaf {
         #[allow(missing_docs)]
         score: WrappedScore<ScoreType>,
+        /// The terms of the score for this leaf's board, only kept when the
+        /// [crate::FeatureFlags::leaf_breakdown] flag is on
+        breakdown: Option<Arc<[(&'static str, f64)]>>,
     },
 }
 
@@ -57,7 +90,20 @@ where
     pub fn score(&self) -> &WrappedScore<ScoreType> {
         match self {
             MinMaxReturn::Node { score, .. } => score,
-            MinMaxReturn::Leaf { score } => score,
+            MinMaxReturn::Leaf { score, .. } => score,
+        }
+    }
+
+    /// The number of nodes in this tree, including this one and every leaf
+    ///
+    /// Branches cut off by Alpha-Beta pruning are never added to the tree, so this is the number of
+    /// nodes the search actually visited
+    pub fn node_count(&self) -> usize {
+        match self {
+            MinMaxReturn::Node { options, .. } => {
+                1 + options.iter().map(|(_, o)| o.node_count()).sum::<usize>()
+            }
+            MinMaxReturn::Leaf { .. } => 1,
         }
     }
 
@@ -109,6 +155,113 @@ where
         }
     }
 
+    /// Make the given move the chosen option of this node, by moving it to the front of the
+    /// options
+    ///
+    /// The score of this node is updated to match, so it stays the score of the chosen option.
+    /// Leaves, and moves that aren't one of the options, are left alone
+    pub fn choose_option(&mut self, chosen_move: Move) {
+        if let MinMaxReturn::Node { options, score, .. } = self {
+            if let Some(index) = options.iter().position(|(m, _)| *m == chosen_move) {
+                let chosen = options.remove(index);
+                *score = *chosen.1.score();
+                options.insert(0, chosen);
+            }
+        }
+    }
+
+    /// Describe the best `count` moves for the given snake along with their scores
+    ///
+    /// This is short enough to fit in a shout, which lets us see how close a decision was when
+    /// watching a replay of the game
+    pub fn alternatives_summary(
+        &self,
+        sid: &GameType::SnakeIDType,
+        count: usize,
+    ) -> Option<String> {
+        let summary = self
+            .first_options_for_snake(sid)?
+            .iter()
+            .take(count)
+            .map(|(m, r)| format!("{m}: {:?}", r.score()))
+            .join(" | ");
+
+        Some(summary.chars().take(MAX_SHOUT_LENGTH).collect())
+    }
+
+    /// Explain why the given snake picked its best move, see [MoveReasoning]
+    ///
+    /// Wins and losses are found at a depth in the tree, where every snake moving is one level.
+    /// We turn those into turns using how many snakes move in the chosen route
+    pub fn move_reasoning(
+        &self,
+        you_id: &GameType::SnakeIDType,
+    ) -> Option<MoveReasoning<ScoreType>> {
+        let options = self.first_options_for_snake(you_id)?;
+        let (chosen, chosen_return) = options.first()?;
+
+        let mut movers = vec![];
+        for (sid, _) in self.chosen_route() {
+            if !movers.contains(&sid) {
+                movers.push(sid);
+            }
+        }
+        let snakes_per_turn = movers.len().max(1) as i64;
+        let turns = |depth: i64| (depth.max(0) + snakes_per_turn - 1) / snakes_per_turn;
+
+        let score = *chosen_return.score();
+        let decided_in = match score {
+            WrappedScore::Win(depth) => Some(turns(depth.0)),
+            WrappedScore::Lose(_, depth) | WrappedScore::Tie(_, depth) => Some(turns(depth)),
+            WrappedScore::Scored(_) => None,
+        };
+
+        let forced_losses = options
+            .iter()
+            .skip(1)
+            .filter_map(|(m, r)| match r.score() {
+                WrappedScore::Lose(_, depth) => Some((*m, turns(*depth))),
+                _ => None,
+            })
+            .collect();
+
+        Some(MoveReasoning {
+            chosen: *chosen,
+            score,
+            decided_in,
+            forced_losses,
+        })
+    }
+
+    /// Whether the search has proven the move the given snake would pick, so it can stop before its
+    /// time is up
+    ///
+    /// Our move is proven when it's a forced win, or when every other move is a forced loss. Losses
+    /// we find in a branch that was cut off by Alpha-Beta pruning are still losses, the pruning
+    /// only means the branch could be even worse. A best move that loses anyway isn't a proof, a
+    /// deeper search can still find a way to lose later
+    pub fn root_proof(&self, you_id: &GameType::SnakeIDType) -> Option<RootProof> {
+        let options = self.first_options_for_snake(you_id)?;
+        let (_, chosen) = options.first()?;
+
+        match chosen.score() {
+            WrappedScore::Win(Reverse(depth)) => {
+                return Some(RootProof::ForcedWin { depth: *depth })
+            }
+            WrappedScore::Lose(..) => return None,
+            WrappedScore::Tie(..) | WrappedScore::Scored(_) => {}
+        }
+
+        options
+            .iter()
+            .skip(1)
+            .try_fold(0, |deepest, (_, r)| match r.score() {
+                WrappedScore::Lose(_, depth) => Some(deepest.max(*depth)),
+                _ => None,
+            })
+            .map(|depth| RootProof::OnlyMove { depth })
+    }
+
     /// Return the option that matches the given move
     /// Returns None if we are at a leaf or the move does not exist
     pub fn option_for_move(&self, chosen_move: Move) -> Option<&Self> {
@@ -158,10 +311,42 @@ where
         }
     }
 
-    /// To dot graph to view elsewhere
+    /// The score breakdown of the leaf at the end of the [MinMaxReturn::chosen_route()], so we can
+    /// see why the search prefers this line
+    ///
+    /// None unless the search ran with the [crate::FeatureFlags::leaf_breakdown] flag and a
+    /// [super::FeatureSnapshot]
+    pub fn principal_variation_breakdown(&self) -> Option<&[(&'static str, f64)]> {
+        match self {
+            MinMaxReturn::Leaf { breakdown, .. } => breakdown.as_deref(),
+            MinMaxReturn::Node { options, .. } => {
+                options.first()?.1.principal_variation_breakdown()
+            }
+        }
+    }
+
+    /// Export the whole tree as a DOT graph, to render with Graphviz
+    ///
+    /// See [MinMaxReturn::to_dot_graph_to_depth] for what each node shows. Full trees get big
+    /// quickly, so limiting the depth is usually a good idea
     pub fn to_dot_graph(&self, you_id: &GameType::SnakeIDType) -> dotavious::Dot {
-        let mut builder = GraphBuilder::new_named_directed("example");
-        self.to_dot_graph_recursive(&mut builder, you_id);
+        self.to_dot_graph_to_depth(you_id, usize::MAX)
+    }
+
+    /// Export the tree as a DOT graph, stopping after `max_depth` levels
+    ///
+    /// Each node is labeled with the snake and move that led to it and its score. Nodes that
+    /// aren't leaves also show which snake is moving there and the alpha and beta values when
+    /// the node was finished. Nodes where our snake is moving are blue and opponents' are red,
+    /// and nodes that saw an Alpha-Beta cutoff get a double border
+    pub fn to_dot_graph_to_depth(
+        &self,
+        you_id: &GameType::SnakeIDType,
+        max_depth: usize,
+    ) -> dotavious::Dot {
+        let mut builder = GraphBuilder::new_named_directed("minimax");
+        let mut next_id = 0;
+        self.to_dot_graph_recursive(&mut builder, you_id, None, max_depth, &mut next_id);
         builder.add_attribute(
             dotavious::attributes::AttributeType::Graph,
             "overlap",
@@ -181,14 +366,21 @@ where
         &self,
         builder: &mut GraphBuilder,
         you_id: &GameType::SnakeIDType,
+        arrived_by: Option<(&GameType::SnakeIDType, Move)>,
+        remaining_depth: usize,
+        next_id: &mut usize,
     ) -> String {
-        static COUNTER: AtomicUsize = AtomicUsize::new(1);
+        let me_id = format!("{next_id}");
+        *next_id += 1;
+
+        let arrived_by = match arrived_by {
+            Some((sid, m)) => format!("{sid:?} {m}"),
+            None => "Root".to_owned(),
+        };
 
         match self {
             MinMaxReturn::Leaf { score, .. } => {
-                let id = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                let me_id = format!("{id}");
-                let me_label = format!("{score:?}");
+                let me_label = format!("{arrived_by}\nScore: {score:?}");
                 let node = NodeBuilder::new(&*me_id)
                     .add_attribute("label", me_label.into())
                     .add_attribute("style", "filled".into())
@@ -209,25 +401,37 @@ where
                 beta,
                 ..
             } => {
-                let id = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                let me_id = format!("{id}");
+                let cutoff = if *alpha_beta_cutoff { "\nCutoff" } else { "" };
                 let me_label = format!(
-                    "{depth}\n{score:?}\n{alpha_beta_cutoff}\nAlpha: {alpha:?}\nBeta: {beta:?}",
+                    "{arrived_by}\nDepth: {depth}\n{moving_snake_id:?} to move\nScore: {score:?}\nAlpha: {alpha:?}\nBeta: {beta:?}{cutoff}",
                 );
                 let color = if moving_snake_id == you_id {
                     "lightblue"
                 } else {
                     "lightcoral"
                 };
+                let peripheries = if *alpha_beta_cutoff { "2" } else { "1" };
                 let node = NodeBuilder::new(&*me_id)
                     .add_attribute("label", me_label.into())
                     .add_attribute("style", "filled".into())
                     .add_attribute("fillcolor", color.into())
+                    .add_attribute("peripheries", peripheries.into())
                     .build()
                     .unwrap();
                 builder.add_node(node);
+
+                if remaining_depth == 0 {
+                    return me_id;
+                }
+
                 for (m, result) in options {
-                    let next_node_id = result.to_dot_graph_recursive(builder, you_id);
+                    let next_node_id = result.to_dot_graph_recursive(
+                        builder,
+                        you_id,
+                        Some((moving_snake_id, *m)),
+                        remaining_depth - 1,
+                        next_id,
+                    );
 
                     let edge = EdgeBuilder::new(&*me_id, &*next_node_id)
                         .add_attribute("xlabel", format!("{moving_snake_id:?} {m}").into())
@@ -1,5 +1,5 @@
 //! There are multiple multiplayer variations to minimax, this module is for the `paranoid`
-//! variant. [This is currently the only variant supported by this trait]
+//! variant. See [crate::maxn] for the other one we support
 //!
 //! This variant assumes all your opponents are working together to minimize your score. The
 //! implementation uses Alpha-Beta pruning to be efficient
@@ -60,13 +60,42 @@ mod score;
 pub use score::{Scorable, WrappedScorable, WrappedScore};
 
 mod minimax_return;
-pub use minimax_return::MinMaxReturn;
+pub use minimax_return::{MinMaxReturn, RootProof, MAX_SHOUT_LENGTH};
+
+mod cutoff_stats;
+pub use cutoff_stats::DepthCutoffs;
 
 mod eval;
-pub use eval::{MinimaxSnake, SnakeOptions};
+pub(crate) use eval::{is_turn_boundary, search_budget, simulate_one, MIN_SEARCH_BUDGET};
+pub use eval::{
+    AbortedEarly, FeatureSnapshot, FoodSpawner, MinimaxSnake, SearchStats, SnakeOptions,
+    MAX_QUEUE_DELAY,
+};
+
+mod pv_stability;
+
+mod stepper;
+pub use stepper::{DeepeningSearch, DeepeningStepper};
+
+mod reasoning;
+pub use reasoning::MoveReasoning;
 
 mod cached_score;
 pub use cached_score::CachedScore;
 
+mod score_memo;
+pub use score_memo::ScoreMemo;
+
+mod panic_mode;
+
+mod verification;
+pub use verification::Verification;
+
+mod terminal_state;
+pub use terminal_state::{classify_death, TerminalState};
+
+mod simultaneous_root;
+pub use simultaneous_root::RootStrategy;
+
 #[allow(missing_docs)]
 pub mod move_ordering;
@@ -1,39 +1,92 @@
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::Arc};
 
 use itertools::Itertools;
 use rand::seq::SliceRandom;
 use rand::thread_rng;
 
-use battlesnake_game_types::types::{Move, SnakeIDGettableGame};
+use battlesnake_game_types::{
+    types::{
+        HeadGettableGame, HealthGettableGame, Move, PositionGettableGame, SnakeIDGettableGame,
+        YouDeterminableGame,
+    },
+    wire_representation::Position,
+};
 
 use super::MinMaxReturn;
 
-#[derive(Debug, Clone, Copy)]
+/// Gives each move a priority for [MoveOrdering::Custom], higher priorities are searched first
+pub type MoveOrderingFn = Arc<dyn Fn(Move, &MoveOrderingContext) -> i64 + Send + Sync>;
+
+#[derive(Clone)]
 pub enum MoveOrdering {
     BestFirst,
     Random,
+    /// Search the moves with the highest priority first
+    ///
+    /// Moves with the same priority keep their [MoveOrdering::BestFirst] order, so this can be
+    /// used to nudge the search toward moves we expect to be good without losing what the
+    /// previous iteration learned. The function is called for every node in the search, so it
+    /// needs to be cheap
+    Custom(MoveOrderingFn),
+}
+
+impl Debug for MoveOrdering {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoveOrdering::BestFirst => write!(f, "BestFirst"),
+            MoveOrdering::Random => write!(f, "Random"),
+            MoveOrdering::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+/// What a [MoveOrdering::Custom] function knows about the node it is ordering the moves of
+///
+/// The board itself isn't included, since the search is generic over the board type. Anything
+/// else the function needs, like where the food was at the root, can be captured by the closure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MoveOrderingContext {
+    /// How many moves into the search this node is, counting one for each snake
+    pub depth: usize,
+    /// Whether the snake that is moving is the one we are searching for
+    pub is_you: bool,
+    /// The health of the snake that is moving
+    pub health: i64,
+    /// The head of the snake that is moving
+    pub head: Position,
+}
+
+impl MoveOrderingContext {
+    /// Build the context for `snake_id` moving at this node
+    pub fn new<GameType>(node: &GameType, snake_id: &GameType::SnakeIDType, depth: usize) -> Self
+    where
+        GameType: SnakeIDGettableGame
+            + YouDeterminableGame
+            + HealthGettableGame
+            + HeadGettableGame
+            + PositionGettableGame,
+    {
+        Self {
+            depth,
+            is_you: snake_id == node.you_id(),
+            health: node.get_health_i64(snake_id),
+            head: node.position_from_native(node.get_head_as_native_position(snake_id)),
+        }
+    }
 }
 
 fn best_first<GameType, ScoreType>(
-    previous_return: Option<MinMaxReturn<GameType, ScoreType>>,
+    previous_return: Option<&MinMaxReturn<GameType, ScoreType>>,
     possible_moves: impl Iterator<Item = Move>,
-) -> Vec<(Move, Option<MinMaxReturn<GameType, ScoreType>>)>
+) -> Vec<(Move, Option<&MinMaxReturn<GameType, ScoreType>>)>
 where
     GameType: Debug + Clone + SnakeIDGettableGame,
     ScoreType: Copy + Ord + PartialOrd + Debug,
 {
-    if let Some(MinMaxReturn::Node { mut options, .. }) = previous_return {
+    if let Some(MinMaxReturn::Node { options, .. }) = previous_return {
         let mut v: Vec<_> = possible_moves
             .into_iter()
-            .map(|m| {
-                (
-                    m,
-                    options
-                        .iter()
-                        .position(|x| x.0 == m)
-                        .map(|x| options.remove(x).1),
-                )
-            })
+            .map(|m| (m, options.iter().find(|x| x.0 == m).map(|x| &x.1)))
             .collect();
         v.sort_by_cached_key(|(_, r)| r.as_ref().map(|x| *x.score()));
         v.reverse();
@@ -44,13 +97,26 @@ where
 }
 
 impl MoveOrdering {
-    pub fn order_moves<GameType, ScoreType>(
+    /// Order the moves `snake_id` can make at `node`, pairing each with what the previous
+    /// iteration found for it
+    ///
+    /// The previous iteration's results are borrowed, so ordering moves never copies the tree
+    pub fn order_moves<'previous, GameType, ScoreType>(
         &self,
-        previous_return: Option<MinMaxReturn<GameType, ScoreType>>,
+        node: &GameType,
+        snake_id: &GameType::SnakeIDType,
+        depth: usize,
+        previous_return: Option<&'previous MinMaxReturn<GameType, ScoreType>>,
         possible_moves: impl Iterator<Item = Move>,
-    ) -> Vec<(Move, Option<MinMaxReturn<GameType, ScoreType>>)>
+    ) -> Vec<(Move, Option<&'previous MinMaxReturn<GameType, ScoreType>>)>
     where
-        GameType: Debug + Clone + SnakeIDGettableGame,
+        GameType: Debug
+            + Clone
+            + SnakeIDGettableGame
+            + YouDeterminableGame
+            + HealthGettableGame
+            + HeadGettableGame
+            + PositionGettableGame,
         ScoreType: Copy + Ord + PartialOrd + Debug,
     {
         match &self {
@@ -59,6 +125,15 @@ impl MoveOrdering {
                 let mut moves = possible_moves.map(|x| (x, None)).collect_vec();
                 moves.shuffle(&mut thread_rng());
 
+                moves
+            }
+            MoveOrdering::Custom(priority) => {
+                let context = MoveOrderingContext::new(node, snake_id, depth);
+
+                let mut moves = best_first(previous_return, possible_moves);
+                // This sort is stable, so moves with the same priority stay best first
+                moves.sort_by_cached_key(|(m, _)| std::cmp::Reverse(priority(*m, &context)));
+
                 moves
             }
         }
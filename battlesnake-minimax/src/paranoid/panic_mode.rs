@@ -0,0 +1,101 @@
+use std::fmt::Debug;
+
+use battlesnake_game_types::types::Move;
+
+use super::WrappedScore;
+
+/// Our last resort when the search proves that every one of our moves loses
+///
+/// [WrappedScore] orders losses by how many snakes are left alive first, which is a fine way to
+/// rank lines when we have a choice, but once every move loses we only care about holding on. So
+/// this picks the move that loses the deepest, and breaks ties with the move where the most of our
+/// opponents' replies next turn don't lose for us. The more precise our opponents need to be to
+/// kill us, the more likely one of them gets it wrong.
+///
+/// `root_options` are our moves with their scores from the search, and `payoffs` the rows of the
+/// simultaneous payoff matrix, see [super::MinimaxSnake]. Moves the search cut off only have an
+/// upper bound on their loss depth, so they can lose ties they should have won but never beat a
+/// move that really lasts longer.
+///
+/// None unless every option is a loss
+pub(crate) fn panic_move<ScoreType>(
+    root_options: &[(Move, WrappedScore<ScoreType>)],
+    payoffs: &[(Move, Vec<WrappedScore<ScoreType>>)],
+) -> Option<Move>
+where
+    ScoreType: PartialOrd + Ord + Debug + Clone + Copy,
+{
+    let loss_depths = root_options
+        .iter()
+        .map(|(m, score)| match score {
+            WrappedScore::Lose(_, depth) => Some((*m, *depth)),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    let forgiving_replies = |m: Move| {
+        payoffs
+            .iter()
+            .find(|(payoff_move, _)| *payoff_move == m)
+            .map_or(0, |(_, row)| {
+                row.iter()
+                    .filter(|payoff| !matches!(payoff, WrappedScore::Lose(..)))
+                    .count()
+            })
+    };
+
+    // max_by_key keeps the last of equal moves, so go backwards to keep the search's order on ties
+    loss_depths
+        .into_iter()
+        .rev()
+        .max_by_key(|(m, depth)| (*depth, forgiving_replies(*m)))
+        .map(|(m, _)| m)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Reverse;
+
+    use super::*;
+
+    #[test]
+    fn panic_prefers_the_deepest_loss_over_fewer_snakes_alive() {
+        let root_options = vec![
+            (Move::Up, WrappedScore::<i32>::Lose(Reverse(1), 2)),
+            (Move::Down, WrappedScore::Lose(Reverse(3), 6)),
+        ];
+
+        assert_eq!(panic_move(&root_options, &[]), Some(Move::Down));
+    }
+
+    #[test]
+    fn panic_breaks_ties_with_the_most_forgiving_replies() {
+        let root_options = vec![
+            (Move::Up, WrappedScore::<i32>::Lose(Reverse(2), 4)),
+            (Move::Left, WrappedScore::Lose(Reverse(2), 4)),
+        ];
+        let payoffs = vec![
+            (
+                Move::Up,
+                vec![WrappedScore::Lose(Reverse(2), 2), WrappedScore::Scored(1)],
+            ),
+            (
+                Move::Left,
+                vec![WrappedScore::Scored(0), WrappedScore::Scored(0)],
+            ),
+        ];
+
+        assert_eq!(panic_move(&root_options, &payoffs), Some(Move::Left));
+    }
+
+    #[test]
+    fn panic_only_applies_when_every_move_loses() {
+        let root_options = vec![
+            (Move::Up, WrappedScore::<i32>::Lose(Reverse(2), 4)),
+            (Move::Left, WrappedScore::Scored(0)),
+        ];
+
+        assert_eq!(panic_move(&root_options, &[]), None);
+        assert_eq!(panic_move::<i32>(&[], &[]), None);
+    }
+}
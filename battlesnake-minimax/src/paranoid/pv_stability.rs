@@ -0,0 +1,175 @@
+use std::{fmt::Debug, mem::discriminant};
+
+use battlesnake_game_types::types::Move;
+use itertools::Itertools;
+use tracing::warn;
+
+use super::WrappedScore;
+
+/// Format a [super::FeatureSnapshot] as `name=value` pairs, in the order they were given, so it can
+/// be pulled back out of the logs
+pub(crate) fn feature_line(features: &[(&str, f64)]) -> String {
+    features
+        .iter()
+        .map(|(name, value)| format!("{name}={value}"))
+        .join(" ")
+}
+
+/// What one iteration of iterative deepening thought of the position
+#[derive(Debug, Clone)]
+struct Iteration<ScoreType>
+where
+    ScoreType: PartialOrd + Ord + Debug + Clone + Copy,
+{
+    depth: usize,
+    best_move: Option<Move>,
+    score: WrappedScore<ScoreType>,
+    line: String,
+}
+
+/// Tracks how the principal variation (PV) changes between the iterations of iterative deepening
+///
+/// When the move we'd pick keeps changing as we search deeper, the score function is telling a
+/// different story at different depths. That tends to show up as erratic play, so we want to be
+/// able to see it in our traces.
+#[derive(Debug)]
+pub(crate) struct PvStability<ScoreType>
+where
+    ScoreType: PartialOrd + Ord + Debug + Clone + Copy,
+{
+    iterations: Vec<Iteration<ScoreType>>,
+}
+
+impl<ScoreType> PvStability<ScoreType>
+where
+    ScoreType: PartialOrd + Ord + Debug + Clone + Copy,
+{
+    pub(crate) fn new() -> Self {
+        Self { iterations: vec![] }
+    }
+
+    /// Record the result of a finished iteration
+    pub(crate) fn record(
+        &mut self,
+        depth: usize,
+        best_move: Option<Move>,
+        score: WrappedScore<ScoreType>,
+        line: impl IntoIterator<Item = Move>,
+    ) {
+        self.iterations.push(Iteration {
+            depth,
+            best_move,
+            score,
+            line: line.into_iter().join(" "),
+        });
+    }
+
+    /// The number of times the root move changed from one iteration to the next
+    pub(crate) fn flips(&self) -> usize {
+        self.iterations
+            .iter()
+            .tuple_windows()
+            .filter(|(a, b)| a.best_move != b.best_move)
+            .count()
+    }
+
+    /// Finds a shallow iteration, at most half the depth of the final one, that picked a different
+    /// move than the final iteration and thought the game was going a different way
+    ///
+    /// Our scores are only ordered so we can't measure how far apart two scores are. Instead we
+    /// call it a huge swing when the kind of score changes, like going from a normal score to a
+    /// forced loss
+    fn discontinuity(&self) -> Option<(&Iteration<ScoreType>, &Iteration<ScoreType>)> {
+        let last = self.iterations.last()?;
+
+        self.iterations
+            .iter()
+            .take_while(|i| i.depth * 2 <= last.depth)
+            .find(|i| {
+                i.best_move != last.best_move && discriminant(&i.score) != discriminant(&last.score)
+            })
+            .map(|shallow| (shallow, last))
+    }
+
+    /// Log a warning with both lines if the final move disagrees with a much shallower search
+    pub(crate) fn warn_on_discontinuity(&self) {
+        if let Some((shallow, last)) = self.discontinuity() {
+            warn!(
+                shallow_depth = shallow.depth,
+                shallow_move = ?shallow.best_move,
+                shallow_score = ?shallow.score,
+                shallow_line = %shallow.line,
+                final_depth = last.depth,
+                final_move = ?last.best_move,
+                final_score = ?last.score,
+                final_line = %last.line,
+                "The chosen move flipped with a huge score swing since a much shallower search"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Reverse;
+
+    use super::*;
+
+    #[test]
+    fn test_feature_line_keeps_the_order() {
+        let features = [
+            ("my_area", 34.0),
+            ("my_health", 87.0),
+            ("food_denial", -1.0),
+        ];
+
+        assert_eq!(
+            feature_line(&features),
+            "my_area=34 my_health=87 food_denial=-1"
+        );
+    }
+
+    #[test]
+    fn test_counts_flips_between_iterations() {
+        let mut pv = PvStability::<i32>::new();
+        pv.record(2, Some(Move::Up), WrappedScore::Scored(1), [Move::Up]);
+        pv.record(4, Some(Move::Up), WrappedScore::Scored(2), [Move::Up]);
+        pv.record(6, Some(Move::Left), WrappedScore::Scored(1), [Move::Left]);
+        pv.record(8, Some(Move::Up), WrappedScore::Scored(3), [Move::Up]);
+
+        assert_eq!(pv.flips(), 2);
+        assert!(pv.discontinuity().is_none());
+    }
+
+    #[test]
+    fn test_finds_a_flip_with_a_score_swing() {
+        let mut pv = PvStability::<i32>::new();
+        pv.record(2, Some(Move::Up), WrappedScore::Scored(1), [Move::Up]);
+        pv.record(4, Some(Move::Up), WrappedScore::Scored(1), [Move::Up]);
+        pv.record(
+            8,
+            Some(Move::Left),
+            WrappedScore::Lose(Reverse(1), 8),
+            [Move::Left],
+        );
+
+        let (shallow, last) = pv.discontinuity().unwrap();
+        assert_eq!(shallow.depth, 2);
+        assert_eq!(last.depth, 8);
+    }
+
+    #[test]
+    fn test_ignores_swings_from_similar_depths() {
+        let mut pv = PvStability::<i32>::new();
+        pv.record(6, Some(Move::Up), WrappedScore::Scored(1), [Move::Up]);
+        pv.record(
+            8,
+            Some(Move::Left),
+            WrappedScore::Lose(Reverse(1), 8),
+            [Move::Left],
+        );
+
+        assert_eq!(pv.flips(), 1);
+        assert!(pv.discontinuity().is_none());
+    }
+}
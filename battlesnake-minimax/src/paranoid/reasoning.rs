@@ -0,0 +1,65 @@
+use std::fmt::{Debug, Display};
+
+use battlesnake_game_types::types::Move;
+use itertools::Itertools;
+
+use super::{minimax_return::MAX_SHOUT_LENGTH, WrappedScore};
+
+/// A short explanation of why the search picked a move, read off the scores it found
+///
+/// This doesn't look at the board at all, everything comes from the [super::MinMaxReturn] so it
+/// always agrees with what the search actually did. The score function's own values are described
+/// with their [Display] implementation
+#[derive(Debug, Clone)]
+pub struct MoveReasoning<ScoreType>
+where
+    ScoreType: Clone + Debug + PartialOrd + Ord + Copy,
+{
+    /// The move we picked
+    pub chosen: Move,
+    /// What the search expects to happen after the chosen move
+    pub score: WrappedScore<ScoreType>,
+    /// For wins, losses and ties, how many turns until the game is decided
+    pub decided_in: Option<i64>,
+    /// The moves we didn't pick because our opponents can force a loss after them, along with
+    /// how many turns until they can
+    pub forced_losses: Vec<(Move, i64)>,
+}
+
+impl<ScoreType> MoveReasoning<ScoreType>
+where
+    ScoreType: Clone + Debug + PartialOrd + Ord + Copy + Display,
+{
+    /// The explanation, cut down to fit in a shout
+    pub fn shout(&self) -> String {
+        self.to_string().chars().take(MAX_SHOUT_LENGTH).collect()
+    }
+}
+
+impl<ScoreType> Display for MoveReasoning<ScoreType>
+where
+    ScoreType: Clone + Debug + PartialOrd + Ord + Copy + Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut reasons = vec![];
+
+        if !self.forced_losses.is_empty() {
+            let losses = self
+                .forced_losses
+                .iter()
+                .map(|(m, turns)| format!("in {turns} by {m}"))
+                .join(", ");
+            reasons.push(format!("avoids forced loss {losses}"));
+        }
+
+        let turns = self.decided_in.unwrap_or_default();
+        reasons.push(match &self.score {
+            WrappedScore::Win(_) => format!("forces a win in {turns}"),
+            WrappedScore::Lose(..) => format!("loses anyway in {turns}"),
+            WrappedScore::Tie(..) => format!("ties in {turns}"),
+            WrappedScore::Scored(score) => format!("{score}"),
+        });
+
+        write!(f, "chose {}: {}", self.chosen, reasons.join("; "))
+    }
+}
@@ -91,18 +91,29 @@ where
     /// The score for all non end state nodes will be defined by this score
     fn score(&self, node: &GameType) -> ScoreType;
 
-    /// `wrapped_score` takes into account the depth and number of players. It checks the game
-    /// board and decides if this is a leaf in our Minimax tree. If it IS a leaf we score it based
-    /// on the outcome of the game board. If we've hit the maximum depth, we use the scoring
-    /// function provided by `score`
+    /// Score a node that `wrapped_score` decided is a leaf because it hit the maximum depth
+    ///
+    /// This defaults to `score`, but lets implementers that know more about the search, like
+    /// [super::MinimaxSnake] and its [super::ScoreMemo], skip scoring the same board twice
+    fn score_at_depth(&self, node: &GameType, _depth: i64) -> ScoreType {
+        self.score(node)
+    }
+
+    /// `wrapped_score` takes into account the depth and whether every player has moved this turn.
+    /// It checks the game board and decides if this is a leaf in our Minimax tree. If it IS a leaf
+    /// we score it based on the outcome of the game board. If we've hit the maximum depth, we use
+    /// the scoring function provided by `score`
+    ///
+    /// Nodes that aren't on a turn boundary are never leaves, since only some of the snakes have
+    /// moved and the board hasn't been advanced yet
     fn wrapped_score(
         &self,
         node: &GameType,
         depth: i64,
         max_depth: i64,
-        num_players: i64,
+        at_turn_boundary: bool,
     ) -> Option<WrappedScore<ScoreType>> {
-        if depth % num_players != 0 {
+        if !at_turn_boundary {
             return None;
         }
 
@@ -130,7 +141,7 @@ where
         }
 
         if depth >= max_depth {
-            return Some(WrappedScore::Scored(self.score(node)));
+            return Some(WrappedScore::Scored(self.score_at_depth(node, depth)));
         }
 
         None
@@ -0,0 +1,71 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use dashmap::DashMap;
+use fxhash::FxBuildHasher;
+
+/// Remembers the leaf scores from a single deepened search
+///
+/// The same board is reached over and over along different lines of moves, since a snake's body
+/// only remembers its last few moves, and the flood fill scorers are too slow to run on it every
+/// time. The memo is keyed by the [crate::board_hash::BoardHashable::board_hash] of the board and
+/// the parity of the depth it was scored at, so a board is never shared between levels where a
+/// different snake is about to move.
+///
+/// Unlike [super::CachedScore] this only lives as long as one search, so it doesn't need the board
+/// itself as the key and won't grow across turns
+#[derive(Debug)]
+pub struct ScoreMemo<ScoreType> {
+    scores: DashMap<(u64, bool), ScoreType, FxBuildHasher>,
+    lookups: AtomicUsize,
+    hits: AtomicUsize,
+}
+
+impl<ScoreType> Default for ScoreMemo<ScoreType> {
+    fn default() -> Self {
+        Self {
+            scores: DashMap::default(),
+            lookups: AtomicUsize::new(0),
+            hits: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl<ScoreType: Copy> ScoreMemo<ScoreType> {
+    /// Return the remembered score for this board, or score it with `score` and remember that
+    pub fn get_or_score(
+        &self,
+        board_hash: u64,
+        depth: i64,
+        score: impl FnOnce() -> ScoreType,
+    ) -> ScoreType {
+        self.lookups.fetch_add(1, Ordering::Relaxed);
+
+        let key = (board_hash, depth % 2 == 0);
+        if let Some(score) = self.scores.get(&key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return *score;
+        }
+
+        let score = score();
+        self.scores.insert(key, score);
+        score
+    }
+
+    /// Forget every score and reset the counts, but hold on to the memory so the next search can
+    /// reuse it
+    pub(crate) fn clear(&self) {
+        self.scores.clear();
+        self.lookups.store(0, Ordering::Relaxed);
+        self.hits.store(0, Ordering::Relaxed);
+    }
+
+    /// How many leaves we were asked to score
+    pub fn lookups(&self) -> usize {
+        self.lookups.load(Ordering::Relaxed)
+    }
+
+    /// How many of the leaves we already had a score for
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+}
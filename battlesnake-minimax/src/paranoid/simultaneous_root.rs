@@ -0,0 +1,97 @@
+use std::str::FromStr;
+
+use battlesnake_game_types::types::Move;
+use color_eyre::eyre::{eyre, Report};
+
+/// How we pick our move at the root of the search
+///
+/// Everywhere else in the tree the paranoid search assumes our opponents see our move before they
+/// pick theirs. In the real game everyone moves at the same time, so at the root we can instead
+/// look at every one of our moves against every joint reply of our opponents, a payoff matrix, and
+/// pick from that. Below the root the search is paranoid either way
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RootStrategy {
+    /// Pick the move with the best worst case, the same as the rest of the tree
+    ///
+    /// This is already the maximin move of the payoff matrix, since our opponents pick their
+    /// replies after seeing our move
+    Paranoid,
+    /// Pick the move we are least likely to regret once we see what our opponents did
+    ///
+    /// The payoff matrix is searched [super::SnakeOptions::simultaneous_root_depth] turns deep, and
+    /// only our moves the full search doesn't see as a loss are considered. See
+    /// [minimax_regret_move] for how the regret is counted
+    MinimaxRegret,
+}
+
+impl FromStr for RootStrategy {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "paranoid" => Ok(RootStrategy::Paranoid),
+            "minimax-regret" => Ok(RootStrategy::MinimaxRegret),
+            _ => Err(eyre!("Unknown root strategy {s}")),
+        }
+    }
+}
+
+/// Our move with the smallest worst case regret
+///
+/// `payoffs` has a row for each of our moves, with its payoff against each joint reply of our
+/// opponents, and every row has the replies in the same order. Our scores are only ordered, so
+/// regret is counted in places instead of points: the regret of a move against a reply is how many
+/// of our other moves would have done strictly better against that reply.
+///
+/// Ties are broken by the best worst case, so when every move has the same regret this is the
+/// maximin move. None if there are no moves
+pub(crate) fn minimax_regret_move<T: Ord>(payoffs: &[(Move, Vec<T>)]) -> Option<Move> {
+    let worst_regret = |row: &[T]| {
+        row.iter()
+            .enumerate()
+            .map(|(reply, payoff)| {
+                payoffs
+                    .iter()
+                    .filter(|(_, other)| other.get(reply).is_some_and(|other| other > payoff))
+                    .count()
+            })
+            .max()
+            .unwrap_or(0)
+    };
+
+    payoffs
+        .iter()
+        .min_by(|(_, a), (_, b)| {
+            worst_regret(a)
+                .cmp(&worst_regret(b))
+                .then_with(|| b.iter().min().cmp(&a.iter().min()))
+        })
+        .map(|(m, _)| *m)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regret_prefers_the_move_that_is_never_far_from_the_best() {
+        // Up is the safest against the worst reply, but it is the worst move against every other
+        // reply. Down is never more than one place from the best move
+        let payoffs = vec![
+            (Move::Up, vec![3, 3, 3]),
+            (Move::Down, vec![2, 8, 8]),
+            (Move::Left, vec![1, 9, 4]),
+            (Move::Right, vec![0, 4, 9]),
+        ];
+
+        assert_eq!(minimax_regret_move(&payoffs), Some(Move::Down));
+    }
+
+    #[test]
+    fn equal_regret_falls_back_to_the_maximin_move() {
+        let payoffs = vec![(Move::Up, vec![1, 5]), (Move::Down, vec![2, 2])];
+
+        assert_eq!(minimax_regret_move(&payoffs), Some(Move::Down));
+        assert_eq!(minimax_regret_move::<i32>(&[]), None);
+    }
+}
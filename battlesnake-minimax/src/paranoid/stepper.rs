@@ -0,0 +1,291 @@
+use std::{
+    borrow::Cow,
+    fmt::Debug,
+    time::{Duration, Instant},
+};
+
+use battlesnake_game_types::types::{
+    HeadGettableGame, HealthGettableGame, Move, NeckQueryableGame, NeighborDeterminableGame,
+    PositionGettableGame, SimulableGame, SnakeBodyGettableGame, SnakeIDGettableGame,
+    VictorDeterminableGame, YouDeterminableGame,
+};
+use derivative::Derivative;
+use tracing::info;
+
+use crate::{board_hash::BoardHashable, Instruments, SearchStatus, SearchStepper};
+
+use super::{
+    eval::Halt, pv_stability::PvStability, score::Scorable, AbortedEarly, MinMaxReturn,
+    MinimaxSnake, WrappedScore,
+};
+
+/// A search that a [DeepeningStepper] can run to one depth after another
+///
+/// Depths are levels of the tree, and every player gets a level each turn, so the stepper goes
+/// one turn deeper by adding a level for each player. This is what lets the paranoid search and
+/// [crate::maxn] share the same iterative deepening
+pub trait DeepeningSearch {
+    /// The board being searched
+    type GameType: SnakeIDGettableGame;
+    /// What the score function gives back
+    type ScoreType: PartialOrd + Ord + Debug + Clone + Copy;
+    /// What searching to a single depth comes back with
+    type Return;
+
+    /// The board the search starts from
+    fn game(&self) -> &Self::GameType;
+
+    /// Search to `depth`, giving up with [AbortedEarly] once `deadline` passes
+    ///
+    /// `previous` is the result of a shallower search of the same board, for the search to order
+    /// its moves by
+    fn search_depth(
+        &self,
+        players: &[<Self::GameType as SnakeIDGettableGame>::SnakeIDType],
+        depth: usize,
+        previous: Option<&Self::Return>,
+        deadline: Instant,
+    ) -> Result<Self::Return, AbortedEarly>;
+
+    /// The move the result picks for us
+    fn best_move(&self, result: &Self::Return) -> Option<Move>;
+
+    /// How good the result is for us
+    fn score(&self, result: &Self::Return) -> WrappedScore<Self::ScoreType>;
+
+    /// The moves the result expects everyone to make, in the order they're made
+    fn line(&self, result: &Self::Return) -> Vec<Move>;
+
+    /// Whether searching deeper than `depth` can't change our move any more, logging why if so
+    fn is_settled(&self, depth: usize, result: &Self::Return) -> bool;
+
+    /// Called with the deeper of two depths in a row once it finishes, to log anything
+    /// interesting that changed between them
+    fn compare_depths(&self, _shallow: (usize, &Self::Return), _deep: (usize, &Self::Return)) {}
+}
+
+/// Iterative deepening that runs on the caller's thread, a step at a time
+///
+/// Each step searches one depth after another until its budget runs out, the same way the
+/// [crate::search_pool] worker does in [MinimaxSnake::deepened_minimax_with_stats()]. A depth that
+/// doesn't finish within the step is thrown away and started over on the next step, so a step
+/// needs enough budget for the next depth to get anywhere. Build one with
+/// [MinimaxSnake::stepper()] or [crate::maxn::MaxnSnake::stepper()]
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""))]
+pub struct DeepeningStepper<Search>
+where
+    Search: DeepeningSearch,
+{
+    #[derivative(Debug = "ignore")]
+    search: Search,
+    #[derivative(Debug = "ignore")]
+    players: Vec<<Search::GameType as SnakeIDGettableGame>::SnakeIDType>,
+    /// Used to order the moves of the first depth, until we have one of our own
+    #[derivative(Debug = "ignore")]
+    initial_return: Option<Search::Return>,
+    next_depth: usize,
+    #[derivative(Debug = "ignore")]
+    current: Option<(usize, Search::Return)>,
+    iterations: usize,
+    #[derivative(Debug = "ignore")]
+    pv_stability: PvStability<Search::ScoreType>,
+    done: bool,
+}
+
+impl<Search> DeepeningStepper<Search>
+where
+    Search: DeepeningSearch,
+{
+    pub(crate) fn new(
+        search: Search,
+        players: Vec<<Search::GameType as SnakeIDGettableGame>::SnakeIDType>,
+        initial_return: Option<Search::Return>,
+    ) -> Self {
+        Self {
+            next_depth: players.len(),
+            search,
+            players,
+            initial_return,
+            current: None,
+            iterations: 0,
+            pv_stability: PvStability::new(),
+            done: false,
+        }
+    }
+
+    /// The deepest depth that finished, along with its result
+    pub fn current(&self) -> Option<(usize, &Search::Return)> {
+        self.current
+            .as_ref()
+            .map(|(depth, result)| (*depth, result))
+    }
+
+    /// The number of depths that have finished
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    /// The number of times the move we'd pick changed from one depth to the next
+    pub fn pv_flips(&self) -> usize {
+        self.pv_stability.flips()
+    }
+
+    /// Stop stepping and take the deepest result, warning about a principal variation that jumped
+    /// around the same way [MinimaxSnake::deepened_minimax_with_stats()] does
+    pub fn finish(self) -> Option<(usize, Search::Return)> {
+        self.pv_stability.warn_on_discontinuity();
+
+        self.current
+    }
+}
+
+impl<Search> SearchStepper for DeepeningStepper<Search>
+where
+    Search: DeepeningSearch,
+{
+    fn step(&mut self, budget: Duration) -> SearchStatus {
+        if self.done {
+            return SearchStatus::Done;
+        }
+
+        let deadline = Instant::now() + budget;
+
+        loop {
+            let depth = self.next_depth;
+            let previous = match &self.current {
+                Some((_, previous)) => Some(previous),
+                None => self.initial_return.as_ref(),
+            };
+            let Ok(result) = self
+                .search
+                .search_depth(&self.players, depth, previous, deadline)
+            else {
+                return SearchStatus::InProgress;
+            };
+
+            if let Some((previous_depth, previous)) = &self.current {
+                self.search
+                    .compare_depths((*previous_depth, previous), (depth, &result));
+            }
+            self.pv_stability.record(
+                depth,
+                self.search.best_move(&result),
+                self.search.score(&result),
+                self.search.line(&result),
+            );
+
+            let settled = self.search.is_settled(depth, &result);
+
+            self.current = Some((depth, result));
+            self.initial_return = None;
+            self.iterations += 1;
+            self.next_depth += self.players.len();
+
+            if settled {
+                self.done = true;
+
+                return SearchStatus::Done;
+            }
+        }
+    }
+
+    fn best_move(&self) -> Option<Move> {
+        let (_, result) = self.current.as_ref()?;
+
+        self.search.best_move(result)
+    }
+}
+
+impl<GameType, ScoreType, ScorableType, const N_SNAKES: usize> DeepeningSearch
+    for MinimaxSnake<GameType, ScoreType, ScorableType, N_SNAKES>
+where
+    GameType: SnakeIDGettableGame
+        + YouDeterminableGame
+        + PositionGettableGame
+        + HealthGettableGame
+        + VictorDeterminableGame
+        + HeadGettableGame
+        + NeighborDeterminableGame
+        + NeckQueryableGame
+        + SnakeBodyGettableGame
+        + BoardHashable
+        + SimulableGame<Instruments, N_SNAKES>
+        + Clone
+        + Sync
+        + Send
+        + Sized
+        + 'static,
+    GameType::SnakeIDType: Clone + Send + Sync,
+    ScoreType: Clone + Debug + PartialOrd + Ord + Send + Sync + Copy + 'static,
+    ScorableType: Scorable<GameType, ScoreType> + Sized + Send + Sync + Clone + 'static,
+{
+    type GameType = GameType;
+    type ScoreType = ScoreType;
+    type Return = MinMaxReturn<GameType, ScoreType>;
+
+    fn game(&self) -> &GameType {
+        &self.game
+    }
+
+    fn search_depth(
+        &self,
+        players: &[GameType::SnakeIDType],
+        depth: usize,
+        previous: Option<&Self::Return>,
+        deadline: Instant,
+    ) -> Result<Self::Return, AbortedEarly> {
+        let halt = Halt::Deadline(deadline);
+
+        self.minimax(
+            Cow::Borrowed(&self.game),
+            players,
+            0,
+            WrappedScore::<ScoreType>::worst_possible_score(),
+            WrappedScore::<ScoreType>::best_possible_score(),
+            depth,
+            self.settings.options.forced_move_extension,
+            previous,
+            vec![],
+            Some(&halt),
+        )
+    }
+
+    fn best_move(&self, result: &Self::Return) -> Option<Move> {
+        result.your_best_move(self.game.you_id())
+    }
+
+    fn score(&self, result: &Self::Return) -> WrappedScore<ScoreType> {
+        *result.score()
+    }
+
+    fn line(&self, result: &Self::Return) -> Vec<Move> {
+        result.chosen_route().into_iter().map(|(_, m)| m).collect()
+    }
+
+    fn is_settled(&self, depth: usize, result: &Self::Return) -> bool {
+        let game_over = matches!(
+            result.score().terminal_depth(),
+            Some(terminal_depth) if depth >= terminal_depth.try_into().unwrap()
+        );
+
+        // Once our move is proven, deeper depths would only spend the time on a move we're going
+        // to make anyway
+        match result.root_proof(self.game.you_id()) {
+            Some(proof) => info!(
+                depth,
+                proof = ?proof,
+                proof_depth = proof.depth(),
+                "Our move is proven, no need to keep going"
+            ),
+            None if game_over => info!(depth, "This game is over, no need to keep going"),
+            None => return false,
+        }
+
+        true
+    }
+
+    fn compare_depths(&self, shallow: (usize, &Self::Return), deep: (usize, &Self::Return)) {
+        self.log_flipped_features(shallow, deep);
+    }
+}
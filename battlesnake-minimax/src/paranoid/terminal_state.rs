@@ -0,0 +1,207 @@
+use std::fmt::Display;
+
+use battlesnake_game_types::{
+    types::{
+        HeadGettableGame, HealthGettableGame, Move, NeighborDeterminableGame, PositionGettableGame,
+        SnakeBodyGettableGame, SnakeIDGettableGame,
+    },
+    wire_representation::Position,
+};
+use itertools::Itertools;
+
+/// How a snake died
+///
+/// [super::WrappedScore] only keeps how many snakes were left and how deep a loss is, which is
+/// what the search needs to rank lines. Looking back over games we also want to know why the lines
+/// we picked lost, so we can see which kind of death the evaluation keeps walking into. See
+/// [super::MinimaxSnake::terminal_state]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TerminalState {
+    /// Ran out of health without any help from hazards
+    Starved,
+    /// Lost a head to head collision with a snake at least as long
+    HeadToHead,
+    /// Moved off the edge of the board
+    Wall,
+    /// Ran into its own body
+    SelfCollision,
+    /// Ran into another snake's body
+    BodyCollision,
+    /// Ran out of health because of hazard damage
+    Hazard,
+}
+
+impl Display for TerminalState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            TerminalState::Starved => "starved",
+            TerminalState::HeadToHead => "head_to_head",
+            TerminalState::Wall => "wall",
+            TerminalState::SelfCollision => "self_collision",
+            TerminalState::BodyCollision => "body_collision",
+            TerminalState::Hazard => "hazard",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// Why `snake_id` died in the turn that took `before` to `after` with the given `moves`
+///
+/// None if the snake is still alive afterwards, or was already dead or had no move. Collisions are
+/// checked before health, and a snake that had more than one health left but still ran out must
+/// have taken hazard damage
+pub fn classify_death<GameType>(
+    before: &GameType,
+    after: &GameType,
+    moves: &[(GameType::SnakeIDType, Move)],
+    snake_id: &GameType::SnakeIDType,
+) -> Option<TerminalState>
+where
+    GameType: SnakeIDGettableGame
+        + PositionGettableGame
+        + HealthGettableGame
+        + HeadGettableGame
+        + NeighborDeterminableGame
+        + SnakeBodyGettableGame,
+{
+    if !before.is_alive(snake_id) || after.is_alive(snake_id) {
+        return None;
+    }
+
+    let destination = |sid: &GameType::SnakeIDType, m: Move| -> Option<Position> {
+        before
+            .possible_moves(&before.get_head_as_native_position(sid))
+            .find(|(possible, _)| *possible == m)
+            .map(|(_, pos)| before.position_from_native(pos))
+    };
+    // Tails move out of the way, unless they're stacked because the snake just ate
+    let blocking_body = |sid: &GameType::SnakeIDType| {
+        let mut body = before
+            .get_snake_body_vec(sid)
+            .into_iter()
+            .map(|pos| before.position_from_native(pos))
+            .collect_vec();
+        let tail_moves = matches!(body.as_slice(), [.., before_tail, tail] if before_tail != tail);
+        if tail_moves {
+            body.pop();
+        }
+
+        body
+    };
+
+    let (_, our_move) = moves.iter().find(|(sid, _)| sid == snake_id)?;
+    let Some(new_head) = destination(snake_id, *our_move) else {
+        return Some(TerminalState::Wall);
+    };
+
+    let our_length = before.get_snake_body_vec(snake_id).len();
+    let others = moves
+        .iter()
+        .filter(|(sid, _)| sid != snake_id && before.is_alive(sid))
+        .collect_vec();
+
+    if others.iter().any(|(sid, m)| {
+        destination(sid, *m) == Some(new_head) && before.get_snake_body_vec(sid).len() >= our_length
+    }) {
+        return Some(TerminalState::HeadToHead);
+    }
+
+    if blocking_body(snake_id).contains(&new_head) {
+        return Some(TerminalState::SelfCollision);
+    }
+
+    if before
+        .get_snake_ids()
+        .iter()
+        .filter(|sid| *sid != snake_id && before.is_alive(sid))
+        .any(|sid| blocking_body(sid).contains(&new_head))
+    {
+        return Some(TerminalState::BodyCollision);
+    }
+
+    if before.get_health_i64(snake_id) <= 1 {
+        Some(TerminalState::Starved)
+    } else {
+        Some(TerminalState::Hazard)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use battlesnake_game_types::{
+        compact_representation::StandardCellBoard4Snakes11x11,
+        types::{build_snake_id_map, SimulableGame, SnakeId},
+        wire_representation::Game,
+    };
+
+    use super::*;
+    use crate::Instruments;
+
+    /// The start of game fixture with our snake set up by `setup`, and everyone else dead so only
+    /// our move matters
+    fn solo_game(setup: impl FnOnce(&mut Game)) -> (StandardCellBoard4Snakes11x11, SnakeId) {
+        let mut wire: Game = serde_json::from_str(include_str!(
+            "../../../battlesnake-rs/fixtures/start_of_game.json"
+        ))
+        .unwrap();
+        for snake in wire.board.snakes.iter_mut().skip(1) {
+            snake.health = 0;
+        }
+        setup(&mut wire);
+        wire.you = wire.board.snakes[0].clone();
+
+        let id_map = build_snake_id_map(&wire);
+        let you = id_map["you"];
+        let board = StandardCellBoard4Snakes11x11::convert_from_game(wire, &id_map).unwrap();
+
+        (board, you)
+    }
+
+    fn classify(board: &StandardCellBoard4Snakes11x11, you: SnakeId, m: Move) -> TerminalState {
+        let moves = [(you, m)];
+        let (_, after) = board
+            .simulate_with_moves(&Instruments::default(), vec![(you, vec![m])])
+            .next()
+            .unwrap();
+
+        classify_death(board, &after, &moves, &you).unwrap()
+    }
+
+    fn body(points: &[(i32, i32)]) -> Vec<Position> {
+        points.iter().map(|&(x, y)| Position { x, y }).collect()
+    }
+
+    #[test]
+    fn test_moving_off_the_board_is_a_wall_death() {
+        let (board, you) = solo_game(|wire| {
+            wire.board.snakes[0].body = body(&[(0, 5), (1, 5), (2, 5)]).into();
+        });
+
+        assert_eq!(classify(&board, you, Move::Left), TerminalState::Wall);
+    }
+
+    #[test]
+    fn test_running_into_ourselves_is_a_self_collision() {
+        let (board, you) = solo_game(|wire| {
+            wire.board.snakes[0].body =
+                body(&[(5, 5), (5, 4), (6, 4), (6, 5), (6, 6), (7, 6)]).into();
+        });
+
+        assert_eq!(
+            classify(&board, you, Move::Right),
+            TerminalState::SelfCollision
+        );
+    }
+
+    #[test]
+    fn test_running_out_of_health_without_hazards_is_starving() {
+        let (board, you) = solo_game(|wire| {
+            wire.board.snakes[0].body = body(&[(5, 5), (5, 4), (5, 3)]).into();
+            wire.board.snakes[0].health = 1;
+            wire.board.food.clear();
+        });
+
+        assert_eq!(classify(&board, you, Move::Up), TerminalState::Starved);
+    }
+}
@@ -0,0 +1,112 @@
+use std::{
+    fmt::Debug,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use battlesnake_game_types::types::Move;
+
+use super::WrappedScore;
+
+/// The main search gets all but this fraction of the budget when [crate::FeatureFlags::verify_move]
+/// is on, the rest is held back for verifying its move
+pub(crate) const VERIFICATION_SHARE: u32 = 8;
+
+/// How many turns deeper than the main search got we look at our chosen move
+pub(crate) const VERIFICATION_TURNS: usize = 2;
+
+/// What checking our chosen move once more found, see [crate::FeatureFlags::verify_move]
+///
+/// The check searches only our chosen move, a few turns deeper than the main search got, so a
+/// principal variation that only held up because the search ran out of depth gets caught before
+/// we send it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verification {
+    /// The deeper search didn't find a way for our opponents to force a loss
+    Confirmed,
+    /// The deeper search found a forced loss, so we switched to the next best move the main search
+    /// didn't see losing
+    Overturned {
+        /// The move the main search picked
+        refuted: Move,
+        /// The move we made instead
+        fallback: Move,
+    },
+    /// The deeper search found a forced loss, but the main search already saw every other move
+    /// losing, so we kept it
+    Refuted,
+    /// The budget ran out before the deeper search finished, so we kept the move
+    Unfinished,
+    /// There was nothing to check, since the main search already knew how the move ends
+    Skipped,
+}
+
+/// Every verification this process has finished, and how many of those changed our move
+static VERIFIED: AtomicUsize = AtomicUsize::new(0);
+static OVERTURNED: AtomicUsize = AtomicUsize::new(0);
+
+impl Verification {
+    /// Count this towards how often verifying changes our move, and return the totals so far as
+    /// the number of moves verified and the number overturned
+    pub(crate) fn record(&self) -> (usize, usize) {
+        let overturned = match self {
+            Verification::Skipped | Verification::Unfinished => {
+                return (
+                    VERIFIED.load(Ordering::Relaxed),
+                    OVERTURNED.load(Ordering::Relaxed),
+                )
+            }
+            Verification::Overturned { .. } => OVERTURNED.fetch_add(1, Ordering::Relaxed) + 1,
+            Verification::Confirmed | Verification::Refuted => OVERTURNED.load(Ordering::Relaxed),
+        };
+
+        (VERIFIED.fetch_add(1, Ordering::Relaxed) + 1, overturned)
+    }
+}
+
+/// The move to make when ours is refuted: the best of the rest of `root_options` that the main
+/// search didn't see losing
+///
+/// `root_options` are our moves with their scores from the main search, best first, so the first
+/// one is the move that was refuted
+pub(crate) fn fallback_move<ScoreType>(
+    root_options: &[(Move, WrappedScore<ScoreType>)],
+) -> Option<Move>
+where
+    ScoreType: PartialOrd + Ord + Debug + Clone + Copy,
+{
+    root_options
+        .iter()
+        .skip(1)
+        .find(|(_, score)| !matches!(score, WrappedScore::Lose(..)))
+        .map(|(m, _)| *m)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Reverse;
+
+    use super::*;
+
+    #[test]
+    fn the_fallback_is_the_next_move_that_doesnt_lose() {
+        let root_options = vec![
+            (Move::Up, WrappedScore::Scored(5)),
+            (Move::Left, WrappedScore::Lose(Reverse(2), 4)),
+            (Move::Right, WrappedScore::Scored(3)),
+            (Move::Down, WrappedScore::Scored(1)),
+        ];
+
+        assert_eq!(fallback_move(&root_options), Some(Move::Right));
+    }
+
+    #[test]
+    fn there_is_no_fallback_when_everything_else_loses() {
+        let root_options = vec![
+            (Move::Up, WrappedScore::Scored(5)),
+            (Move::Left, WrappedScore::<i32>::Lose(Reverse(2), 4)),
+        ];
+
+        assert_eq!(fallback_move(&root_options), None);
+        assert_eq!(fallback_move::<i32>(&root_options[..1]), None);
+    }
+}
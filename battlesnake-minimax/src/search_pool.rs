@@ -0,0 +1,602 @@
+//! A shared pool of threads for the searches to run their work on
+//!
+//! Spawning a fresh OS thread for every search adds the spawn to the latency of every move, and
+//! under load lets the number of threads grow without any bound. Instead the searches hand their
+//! work to this pool along with a deadline. The pool has a fixed number of threads, so a burst of
+//! requests queues up instead of oversubscribing the CPU, and work that is still waiting when its
+//! deadline passes is dropped without running since nobody wants its result anymore.
+//!
+//! Not all work is as urgent. A search that has to answer a move goes ahead of the
+//! [Priority::Background] work that only helps another search along, and one of the threads only
+//! ever runs the searches that answer. So however many helpers are going, the next move's search
+//! has a thread to start on.
+//!
+//! How long work waits in the queue is the number to watch, see [SearchPool::stats]
+
+use std::{
+    collections::VecDeque,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicU64, AtomicU8, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, OnceLock,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use tracing::{warn, Span};
+
+/// Where a task handed to the [SearchPool] is at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskStatus {
+    /// Waiting for a free thread
+    Queued,
+    /// Running on one of the pool's threads
+    Running,
+    /// Ran to completion
+    Finished,
+    /// Never ran, because its deadline passed or it was cancelled while it was still queued
+    Expired,
+    /// Started running but panicked
+    Panicked,
+}
+
+impl TaskStatus {
+    fn from_u8(status: u8) -> Self {
+        match status {
+            0 => TaskStatus::Queued,
+            1 => TaskStatus::Running,
+            2 => TaskStatus::Finished,
+            3 => TaskStatus::Expired,
+            _ => TaskStatus::Panicked,
+        }
+    }
+}
+
+/// How urgently a task handed to the [SearchPool] needs a thread
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// A search whose result answers a move, which goes ahead of any background work
+    Answer,
+    /// Work that only helps another search along, like filling a shared cache. It never runs on
+    /// the thread the pool keeps for [Priority::Answer] tasks
+    Background,
+}
+
+/// Shared between a task and its [TaskHandle]
+#[derive(Debug)]
+struct TaskState {
+    status: AtomicU8,
+    /// Microseconds between being queued and being picked up, u64::MAX until it's picked up
+    queue_delay_micros: AtomicU64,
+}
+
+/// Lets the code that handed a task to the [SearchPool] keep an eye on it
+#[derive(Debug, Clone)]
+pub struct TaskHandle {
+    state: Arc<TaskState>,
+}
+
+impl TaskHandle {
+    /// Where the task is at right now
+    pub fn status(&self) -> TaskStatus {
+        TaskStatus::from_u8(self.state.status.load(Ordering::Acquire))
+    }
+
+    /// How long the task waited for a thread, None if it hasn't been picked up yet
+    pub fn queue_delay(&self) -> Option<Duration> {
+        match self.state.queue_delay_micros.load(Ordering::Acquire) {
+            u64::MAX => None,
+            micros => Some(Duration::from_micros(micros)),
+        }
+    }
+
+    /// Take the task out of the queue if it hasn't started yet
+    ///
+    /// Returns true if it was still queued, and so will never run
+    pub fn cancel(&self) -> bool {
+        self.state
+            .status
+            .compare_exchange(
+                TaskStatus::Queued as u8,
+                TaskStatus::Expired as u8,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_ok()
+    }
+}
+
+struct Task {
+    queued_at: Instant,
+    deadline: Instant,
+    state: Arc<TaskState>,
+    span: Span,
+    run: Box<dyn FnOnce() + Send>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    /// Tasks waiting in the queue, including cancelled ones no thread has taken off it yet
+    queued: AtomicUsize,
+    /// Tasks running on a thread right now
+    running: AtomicUsize,
+    started: AtomicU64,
+    expired: AtomicU64,
+    total_queue_delay_micros: AtomicU64,
+    max_queue_delay_micros: AtomicU64,
+}
+
+/// A snapshot of how the [SearchPool] has been keeping up, since it started
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchPoolStats {
+    /// The number of threads in the pool
+    pub threads: usize,
+    /// The number of tasks that got a thread before their deadline
+    pub tasks_started: u64,
+    /// The number of tasks that were dropped because their deadline passed, or they were
+    /// cancelled, while they were still queued
+    pub tasks_expired: u64,
+    /// The longest any task that started waited for a thread
+    pub max_queue_delay: Duration,
+    /// The average time the tasks that started waited for a thread, None if none have started
+    pub mean_queue_delay: Option<Duration>,
+}
+
+/// The tasks waiting for a thread, one queue for each [Priority]
+#[derive(Default)]
+struct Queues {
+    answer: VecDeque<Task>,
+    background: VecDeque<Task>,
+    /// Set when the pool is dropped, so its threads stop once they're out of work
+    closed: bool,
+}
+
+impl Queues {
+    /// The next task a thread should run, only ever an answer if it's `reserved` for them
+    fn next(&mut self, reserved: bool) -> Option<Task> {
+        if let Some(task) = self.answer.pop_front() {
+            return Some(task);
+        }
+
+        if reserved {
+            None
+        } else {
+            self.background.pop_front()
+        }
+    }
+}
+
+#[derive(Default)]
+struct Shared {
+    queues: Mutex<Queues>,
+    /// Signalled whenever a task is queued, or the pool is closed
+    work: Condvar,
+}
+
+/// A fixed set of threads that runs search work until its deadline
+///
+/// Most code wants the shared pool from [search_pool]
+pub struct SearchPool {
+    shared: Arc<Shared>,
+    counters: Arc<Counters>,
+    threads: usize,
+    /// How many of the threads only run [Priority::Answer] tasks
+    reserved: usize,
+}
+
+impl std::fmt::Debug for SearchPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SearchPool")
+            .field("threads", &self.threads)
+            .field("reserved", &self.reserved)
+            .field("counters", &self.counters)
+            .finish()
+    }
+}
+
+impl SearchPool {
+    /// Start a pool with the given number of threads, at least one
+    ///
+    /// When there's more than one, the first is kept for [Priority::Answer] tasks
+    pub fn new(threads: usize) -> Self {
+        let threads = threads.max(1);
+        let reserved = usize::from(threads > 1);
+        let shared = Arc::new(Shared::default());
+        let counters = Arc::new(Counters::default());
+
+        for i in 0..threads {
+            let shared = shared.clone();
+            let counters = counters.clone();
+            let reserved = i < reserved;
+
+            thread::Builder::new()
+                .name(format!("search-pool-{i}"))
+                .spawn(move || loop {
+                    // The lock is only held while taking the next task, so the other threads are
+                    // free to run theirs
+                    let task = {
+                        let Ok(mut queues) = shared.queues.lock() else {
+                            return;
+                        };
+                        loop {
+                            if let Some(task) = queues.next(reserved) {
+                                break task;
+                            }
+                            if queues.closed {
+                                return;
+                            }
+                            queues = match shared.work.wait(queues) {
+                                Ok(queues) => queues,
+                                Err(_) => return,
+                            };
+                        }
+                    };
+
+                    run_task(task, &counters);
+                })
+                .expect("Couldn't start a search pool thread");
+        }
+
+        Self {
+            shared,
+            counters,
+            threads,
+            reserved,
+        }
+    }
+
+    /// Queue `run` to start on the next free thread, as long as that happens before `deadline`
+    ///
+    /// [Priority::Answer] tasks are picked up before any [Priority::Background] ones, whenever
+    /// they were queued. It runs inside the span that was current when it was spawned, so its logs
+    /// line up with the request that asked for it
+    pub fn spawn<F>(&self, deadline: Instant, priority: Priority, run: F) -> TaskHandle
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let state = Arc::new(TaskState {
+            status: AtomicU8::new(TaskStatus::Queued as u8),
+            queue_delay_micros: AtomicU64::new(u64::MAX),
+        });
+
+        let task = Task {
+            queued_at: Instant::now(),
+            deadline,
+            state: state.clone(),
+            span: Span::current(),
+            run: Box::new(run),
+        };
+        self.counters.queued.fetch_add(1, Ordering::Relaxed);
+        {
+            let mut queues = self
+                .shared
+                .queues
+                .lock()
+                .expect("The search pool threads never panic while holding the queue");
+            match priority {
+                Priority::Answer => queues.answer.push_back(task),
+                Priority::Background => queues.background.push_back(task),
+            }
+        }
+        // Reserved threads skip background work, so wake them all to be sure one that can run it
+        // does
+        self.shared.work.notify_all();
+
+        TaskHandle { state }
+    }
+
+    /// How many threads would pick up a [Priority::Background] task right away, because nothing
+    /// is running on them and nothing is queued for them
+    ///
+    /// This leaves out the thread kept for [Priority::Answer] tasks. Work that only helps another
+    /// search along should stay within this, so it doesn't sit in the queue for nothing
+    pub fn idle_threads(&self) -> usize {
+        let busy = self.counters.queued.load(Ordering::Relaxed)
+            + self.counters.running.load(Ordering::Relaxed);
+
+        (self.threads - self.reserved).saturating_sub(busy)
+    }
+
+    /// How the pool has been keeping up so far
+    pub fn stats(&self) -> SearchPoolStats {
+        let tasks_started = self.counters.started.load(Ordering::Relaxed);
+        let total_queue_delay = self
+            .counters
+            .total_queue_delay_micros
+            .load(Ordering::Relaxed);
+
+        SearchPoolStats {
+            threads: self.threads,
+            tasks_started,
+            tasks_expired: self.counters.expired.load(Ordering::Relaxed),
+            max_queue_delay: Duration::from_micros(
+                self.counters.max_queue_delay_micros.load(Ordering::Relaxed),
+            ),
+            mean_queue_delay: (tasks_started > 0)
+                .then(|| Duration::from_micros(total_queue_delay / tasks_started)),
+        }
+    }
+}
+
+impl Drop for SearchPool {
+    fn drop(&mut self) {
+        if let Ok(mut queues) = self.shared.queues.lock() {
+            queues.closed = true;
+        }
+        self.shared.work.notify_all();
+    }
+}
+
+fn run_task(task: Task, counters: &Counters) {
+    counters.queued.fetch_sub(1, Ordering::Relaxed);
+    let queue_delay = task.queued_at.elapsed();
+    let queue_delay_micros = queue_delay.as_micros().try_into().unwrap_or(u64::MAX - 1);
+
+    let claimed = Instant::now() < task.deadline
+        && task
+            .state
+            .status
+            .compare_exchange(
+                TaskStatus::Queued as u8,
+                TaskStatus::Running as u8,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_ok();
+
+    if !claimed {
+        task.state
+            .status
+            .store(TaskStatus::Expired as u8, Ordering::Release);
+        counters.expired.fetch_add(1, Ordering::Relaxed);
+        let _entered = task.span.enter();
+        warn!(
+            queue_delay_ms = queue_delay.as_millis() as u64,
+            "Dropping a search task that didn't get a thread before its deadline"
+        );
+
+        return;
+    }
+
+    task.state
+        .queue_delay_micros
+        .store(queue_delay_micros, Ordering::Release);
+    counters.started.fetch_add(1, Ordering::Relaxed);
+    counters
+        .total_queue_delay_micros
+        .fetch_add(queue_delay_micros, Ordering::Relaxed);
+    counters
+        .max_queue_delay_micros
+        .fetch_max(queue_delay_micros, Ordering::Relaxed);
+
+    let span = task.span;
+    let run = task.run;
+    counters.running.fetch_add(1, Ordering::Relaxed);
+    let status = match catch_unwind(AssertUnwindSafe(|| span.in_scope(run))) {
+        Ok(()) => TaskStatus::Finished,
+        Err(_) => TaskStatus::Panicked,
+    };
+    counters.running.fetch_sub(1, Ordering::Relaxed);
+    task.state.status.store(status as u8, Ordering::Release);
+}
+
+static SEARCH_POOL: OnceLock<SearchPool> = OnceLock::new();
+
+/// The pool every search shares
+///
+/// It's started the first time it's asked for. `SEARCH_POOL_THREADS` sets how many threads it has,
+/// and defaults to the number of CPUs
+pub fn search_pool() -> &'static SearchPool {
+    SEARCH_POOL.get_or_init(|| {
+        let threads = match std::env::var("SEARCH_POOL_THREADS").map(|x| x.parse()) {
+            Ok(Ok(x)) => x,
+            _ => thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+        };
+
+        SearchPool::new(threads)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use itertools::Itertools;
+
+    use super::*;
+
+    fn wait_for(handle: &TaskHandle, status: TaskStatus) {
+        let started_at = Instant::now();
+        while handle.status() != status {
+            assert!(
+                started_at.elapsed() < Duration::from_secs(5),
+                "Task never got to {status:?}, it's {:?}",
+                handle.status()
+            );
+            thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn test_tasks_run_and_record_their_queue_delay() {
+        let pool = SearchPool::new(1);
+        let (sender, receiver) = mpsc::channel();
+
+        let handle = pool.spawn(
+            Instant::now() + Duration::from_secs(5),
+            Priority::Answer,
+            move || {
+                sender.send(42).unwrap();
+            },
+        );
+
+        assert_eq!(receiver.recv().unwrap(), 42);
+        wait_for(&handle, TaskStatus::Finished);
+        assert!(handle.queue_delay().is_some());
+        assert_eq!(pool.stats().tasks_started, 1);
+    }
+
+    #[test]
+    fn test_tasks_past_their_deadline_never_run() {
+        let pool = SearchPool::new(1);
+        let (release, blocked) = mpsc::channel::<()>();
+        let busy = pool.spawn(
+            Instant::now() + Duration::from_secs(5),
+            Priority::Answer,
+            move || {
+                blocked.recv().unwrap();
+            },
+        );
+        wait_for(&busy, TaskStatus::Running);
+
+        let late = pool.spawn(
+            Instant::now() + Duration::from_millis(1),
+            Priority::Answer,
+            || {
+                panic!("This task should have expired");
+            },
+        );
+        thread::sleep(Duration::from_millis(5));
+        release.send(()).unwrap();
+
+        wait_for(&late, TaskStatus::Expired);
+        assert_eq!(late.queue_delay(), None);
+        assert_eq!(pool.stats().tasks_expired, 1);
+    }
+
+    #[test]
+    fn test_cancelled_tasks_never_run_and_panics_are_reported() {
+        let pool = SearchPool::new(1);
+        let (release, blocked) = mpsc::channel::<()>();
+        let busy = pool.spawn(
+            Instant::now() + Duration::from_secs(5),
+            Priority::Answer,
+            move || {
+                blocked.recv().unwrap();
+            },
+        );
+        wait_for(&busy, TaskStatus::Running);
+
+        let cancelled = pool.spawn(
+            Instant::now() + Duration::from_secs(5),
+            Priority::Answer,
+            || {
+                panic!("This task was cancelled");
+            },
+        );
+        assert!(cancelled.cancel());
+        let panicking = pool.spawn(
+            Instant::now() + Duration::from_secs(5),
+            Priority::Answer,
+            || {
+                panic!("Panics stay inside the task");
+            },
+        );
+        release.send(()).unwrap();
+
+        wait_for(&panicking, TaskStatus::Panicked);
+        assert_eq!(cancelled.status(), TaskStatus::Expired);
+        assert!(!cancelled.cancel());
+    }
+
+    fn blocked_task(pool: &SearchPool, priority: Priority) -> (TaskHandle, mpsc::Sender<()>) {
+        let (release, blocked) = mpsc::channel::<()>();
+        let handle = pool.spawn(
+            Instant::now() + Duration::from_secs(5),
+            priority,
+            move || {
+                blocked.recv().unwrap();
+            },
+        );
+
+        (handle, release)
+    }
+
+    #[test]
+    fn test_idle_threads_leave_out_running_and_queued_tasks() {
+        let pool = SearchPool::new(3);
+        // One of the three is kept for answers
+        assert_eq!(pool.idle_threads(), 2);
+
+        let (busy, release) = blocked_task(&pool, Priority::Background);
+        wait_for(&busy, TaskStatus::Running);
+        assert_eq!(pool.idle_threads(), 1);
+
+        let (other, release_other) = blocked_task(&pool, Priority::Background);
+        let queued = pool.spawn(
+            Instant::now() + Duration::from_secs(5),
+            Priority::Background,
+            || {},
+        );
+        wait_for(&other, TaskStatus::Running);
+        // The reserved thread leaves background work in the queue
+        assert_eq!(queued.status(), TaskStatus::Queued);
+        assert_eq!(pool.idle_threads(), 0);
+
+        release.send(()).unwrap();
+        release_other.send(()).unwrap();
+        for handle in [&busy, &other, &queued] {
+            wait_for(handle, TaskStatus::Finished);
+        }
+        assert_eq!(pool.idle_threads(), 2);
+    }
+
+    #[test]
+    fn test_answers_start_on_a_pool_saturated_with_background_work() {
+        let pool = SearchPool::new(2);
+
+        let background = (0..3)
+            .map(|_| blocked_task(&pool, Priority::Background))
+            .collect_vec();
+        wait_for(&background[0].0, TaskStatus::Running);
+        assert_eq!(background[1].0.status(), TaskStatus::Queued);
+
+        let (sender, receiver) = mpsc::channel();
+        let answer = pool.spawn(
+            Instant::now() + Duration::from_secs(5),
+            Priority::Answer,
+            move || sender.send(42).unwrap(),
+        );
+
+        // The answer runs on the reserved thread while all the background work is still stuck
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(5)), Ok(42));
+        wait_for(&answer, TaskStatus::Finished);
+        assert!(background[1..]
+            .iter()
+            .all(|(handle, _)| handle.status() == TaskStatus::Queued));
+
+        for (_, release) in &background {
+            release.send(()).unwrap();
+        }
+        for (handle, _) in &background {
+            wait_for(handle, TaskStatus::Finished);
+        }
+    }
+
+    #[test]
+    fn test_answers_go_ahead_of_queued_background_work() {
+        let pool = SearchPool::new(1);
+        let (busy, release) = blocked_task(&pool, Priority::Background);
+        wait_for(&busy, TaskStatus::Running);
+
+        let (sender, receiver) = mpsc::channel();
+        let background_sender = sender.clone();
+        pool.spawn(
+            Instant::now() + Duration::from_secs(5),
+            Priority::Background,
+            move || background_sender.send(Priority::Background).unwrap(),
+        );
+        pool.spawn(
+            Instant::now() + Duration::from_secs(5),
+            Priority::Answer,
+            move || sender.send(Priority::Answer).unwrap(),
+        );
+        release.send(()).unwrap();
+
+        assert_eq!(receiver.recv().unwrap(), Priority::Answer);
+        assert_eq!(receiver.recv().unwrap(), Priority::Background);
+    }
+}
@@ -0,0 +1,106 @@
+//! Counting how many simulations a search runs and how long they take
+//!
+//! Every search simulates through an [Instruments]. Most of the time that is the no-op
+//! [Instruments::default()], but a search that wants to know where its time went can hand out
+//! [Instruments::recording()] with a [SimulationStats] and record the totals onto its span when
+//! it's done with [SimulationStats::record()]
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use battlesnake_game_types::types::SimulatorInstruments;
+
+/// Implements `SimulatorInstruments` for the simulate calls in our searches
+///
+/// The default doesn't record anything, which is what you want when you aren't timing the
+/// simulation. Clones share the same [SimulationStats], so one can be handed to every thread of a
+/// search
+#[derive(Debug, Clone, Default)]
+pub struct Instruments {
+    stats: Option<Arc<SimulationStats>>,
+}
+
+impl Instruments {
+    /// Instruments that add every simulation to `stats`
+    pub fn recording(stats: Arc<SimulationStats>) -> Self {
+        Self { stats: Some(stats) }
+    }
+}
+
+impl SimulatorInstruments for Instruments {
+    fn observe_simulation(&self, duration: Duration) {
+        if let Some(stats) = &self.stats {
+            stats.observe(duration);
+        }
+    }
+}
+
+/// The number of simulations a search ran and the time spent in them
+///
+/// The counters are atomics so the threads of a search can share one without locking
+#[derive(Debug, Default)]
+pub struct SimulationStats {
+    simulations: AtomicU64,
+    nanos: AtomicU64,
+}
+
+impl SimulationStats {
+    fn observe(&self, duration: Duration) {
+        self.simulations.fetch_add(1, Ordering::Relaxed);
+        self.nanos.fetch_add(
+            duration.as_nanos().try_into().unwrap_or(u64::MAX),
+            Ordering::Relaxed,
+        );
+    }
+
+    /// How many simulations have been observed
+    pub fn simulations(&self) -> u64 {
+        self.simulations.load(Ordering::Relaxed)
+    }
+
+    /// The total time spent in the simulations that have been observed
+    pub fn total_time(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Ordering::Relaxed))
+    }
+
+    /// Record the totals onto `span`, along with the share of `budget` they took up
+    ///
+    /// The span needs the `simulations`, `simulation_ms` and `simulation_share` fields
+    pub fn record(&self, span: &tracing::Span, budget: Duration) {
+        let total_time = self.total_time();
+
+        span.record("simulations", self.simulations());
+        span.record("simulation_ms", total_time.as_secs_f64() * 1000.0);
+        if !budget.is_zero() {
+            span.record(
+                "simulation_share",
+                total_time.as_secs_f64() / budget.as_secs_f64(),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clones_share_the_stats() {
+        let stats = Arc::new(SimulationStats::default());
+        let instruments = Instruments::recording(stats.clone());
+
+        instruments.observe_simulation(Duration::from_micros(3));
+        instruments
+            .clone()
+            .observe_simulation(Duration::from_micros(4));
+        Instruments::default().observe_simulation(Duration::from_micros(100));
+
+        assert_eq!(stats.simulations(), 2);
+        assert_eq!(stats.total_time(), Duration::from_micros(7));
+    }
+}
@@ -0,0 +1,97 @@
+//! Searches that can run a little at a time on the caller's thread
+//!
+//! Our searches normally run on background threads while the request thread waits on the clock,
+//! see [crate::search_pool]. That doesn't work everywhere: WASM has no threads to spare, and on a
+//! Lambda that was just cold started the threads are one more thing eating into the timeout. A
+//! [SearchStepper] does a bounded amount of work each time it's stepped and picks up where it left
+//! off on the next step, so the host can loop it for as long as it has time
+
+use std::time::{Duration, Instant};
+
+use battlesnake_game_types::types::Move;
+
+/// Where a [SearchStepper] got to at the end of a step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchStatus {
+    /// There is more to search, so step again if there's time left
+    InProgress,
+    /// Searching any further wouldn't change our move, so there's no point stepping again
+    Done,
+}
+
+/// A search that is driven by its caller, one step at a time
+pub trait SearchStepper {
+    /// Search for at most about `budget`, and report whether there's anything left to do
+    ///
+    /// The budget is checked between units of work, so a step can run over by a little
+    fn step(&mut self, budget: Duration) -> SearchStatus;
+
+    /// The move we'd make if the search stopped now, None until the first unit of work finishes
+    fn best_move(&self) -> Option<Move>;
+}
+
+/// Step the search in slices of at most `slice` until it's done or `deadline` passes
+///
+/// This is the loop a single threaded host runs, and the slices are where it would hand control
+/// back to anything else that needs the thread
+pub fn step_until<Stepper: SearchStepper + ?Sized>(
+    stepper: &mut Stepper,
+    deadline: Instant,
+    slice: Duration,
+) -> SearchStatus {
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return SearchStatus::InProgress;
+        }
+
+        if stepper.step(remaining.min(slice)) == SearchStatus::Done {
+            return SearchStatus::Done;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Finishes after a fixed number of steps, no matter the budget
+    struct Countdown(usize);
+
+    impl SearchStepper for Countdown {
+        fn step(&mut self, _budget: Duration) -> SearchStatus {
+            self.0 = self.0.saturating_sub(1);
+
+            if self.0 == 0 {
+                SearchStatus::Done
+            } else {
+                SearchStatus::InProgress
+            }
+        }
+
+        fn best_move(&self) -> Option<Move> {
+            None
+        }
+    }
+
+    #[test]
+    fn test_step_until_stops_when_the_search_is_done() {
+        let mut stepper = Countdown(3);
+        let deadline = Instant::now() + Duration::from_secs(60);
+
+        let status = step_until(&mut stepper, deadline, Duration::from_millis(1));
+
+        assert_eq!(status, SearchStatus::Done);
+        assert_eq!(stepper.0, 0);
+    }
+
+    #[test]
+    fn test_step_until_stops_at_the_deadline() {
+        let mut stepper = Countdown(usize::MAX);
+
+        let status = step_until(&mut stepper, Instant::now(), Duration::from_millis(1));
+
+        assert_eq!(status, SearchStatus::InProgress);
+        assert_eq!(stepper.0, usize::MAX);
+    }
+}
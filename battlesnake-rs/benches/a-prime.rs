@@ -1,6 +1,6 @@
 use battlesnake_game_types::types::*;
 
-use battlesnake_rs::a_prime::{APrimeCalculable, ClosestFoodCalculable};
+use battlesnake_rs::a_prime::{APrimeCalculable, APrimeOptions, ClosestFoodCalculable};
 use battlesnake_rs::*;
 
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
@@ -40,7 +40,7 @@ pub fn criterion_benchmark(c: &mut Criterion) {
             game.shortest_distance(
                 &game.get_head_as_native_position(game.you_id()),
                 &game.get_all_food_as_native_positions(),
-                None,
+                &APrimeOptions::default(),
             )
         })
     });
@@ -57,7 +57,7 @@ pub fn criterion_benchmark(c: &mut Criterion) {
             game.shortest_distance(
                 &game.get_head_as_native_position(game.you_id()),
                 &game.get_all_food_as_native_positions(),
-                None,
+                &APrimeOptions::default(),
             )
         })
     });
@@ -71,7 +71,10 @@ pub fn criterion_benchmark(c: &mut Criterion) {
 
         b.iter(|| {
             let game = black_box(&game);
-            game.dist_to_closest_food(&game.get_head_as_native_position(game.you_id()), None)
+            game.dist_to_closest_food(
+                &game.get_head_as_native_position(game.you_id()),
+                &APrimeOptions::default(),
+            )
         })
     });
 
@@ -84,7 +87,10 @@ pub fn criterion_benchmark(c: &mut Criterion) {
 
         b.iter(|| {
             let game = black_box(&game);
-            game.dist_to_closest_food(&game.get_head_as_native_position(game.you_id()), None)
+            game.dist_to_closest_food(
+                &game.get_head_as_native_position(game.you_id()),
+                &APrimeOptions::default(),
+            )
         })
     });
 }
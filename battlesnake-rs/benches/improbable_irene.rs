@@ -1,4 +1,6 @@
-use battlesnake_rs::{improbable_irene::ImprobableIrene, StandardCellBoard4Snakes11x11};
+use battlesnake_rs::{
+    improbable_irene::ImprobableIrene, mcts::SnakeOptions, StandardCellBoard4Snakes11x11,
+};
 
 use battlesnake_game_types::{
     compact_representation::WrappedCellBoard4Snakes11x11,
@@ -29,6 +31,25 @@ pub fn criterion_benchmark(c: &mut Criterion) {
         })
     });
 
+    // Every expansion with four snakes alive simulates up to 81 next states, which is where
+    // streaming them into the reused expansion buffers saves the most
+    let crowded_json = include_str!("../../fixtures/65401e8f-a92a-445f-9617-94770044e117.json");
+    g.bench_function("MCTS Compact Four Snakes", |b| {
+        b.iter(|| {
+            let game: Game = serde_json::from_str(crowded_json).unwrap();
+            let game_info = game.game.clone();
+            let turn = game.turn;
+            let id_map = build_snake_id_map(&game);
+
+            let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+
+            let snake = ImprobableIrene::new(black_box(game), game_info, turn);
+
+            let mut arena = Arena::new();
+            snake.mcts_bench(10000, &mut arena);
+        })
+    });
+
     g.bench_function("MCTS Wrapped", |b| {
         b.iter(|| {
             let mut game: Game = serde_json::from_str(game_json).unwrap();
@@ -48,6 +69,38 @@ pub fn criterion_benchmark(c: &mut Criterion) {
             snake.mcts_bench(10000, &mut arena);
         });
     });
+
+    // The rollout cache only kicks in near the end of a game, so this uses a wrapped duel that is
+    // close to being decided
+    let duel_json = include_str!("../../fixtures/b6a045ae-abf2-4f6f-b04c-a80ace7881b4_399.json");
+    for (name, rollout_cache_turns) in [
+        (
+            "MCTS Wrapped Duel",
+            SnakeOptions::default().rollout_cache_turns,
+        ),
+        ("MCTS Wrapped Duel without rollout cache", 0),
+    ] {
+        g.bench_function(name, |b| {
+            b.iter(|| {
+                let game: Game = serde_json::from_str(duel_json).unwrap();
+                let game_info = game.game.clone();
+                let turn = game.turn;
+                let id_map = build_snake_id_map(&game);
+
+                let game = WrappedCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+
+                let options = SnakeOptions {
+                    rollout_cache_turns,
+                    ..Default::default()
+                };
+                let snake =
+                    ImprobableIrene::new_with_options(black_box(game), game_info, turn, options);
+
+                let mut arena = Arena::new();
+                snake.mcts_bench(10000, &mut arena);
+            });
+        });
+    }
 }
 
 criterion_group! {
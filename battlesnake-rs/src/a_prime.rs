@@ -11,6 +11,8 @@ use rustc_hash::FxHashMap;
 use std::cmp::Ordering;
 use std::collections::BinaryHeap;
 
+use crate::maze_passages::MazePassages;
+
 const NEIGHBOR_DISTANCE: i32 = 1;
 const HEURISTIC_MAX: i32 = 500;
 
@@ -20,8 +22,16 @@ pub struct APrimeResult<T> {
     best_target: T,
 }
 
+/// The extra cost of stepping off a cell, on top of the usual 1 for moving at all
+///
+/// Each snake wants something different from its paths: [crate::famished_frank] is happy to walk
+/// over food on the way, while [crate::eremetic_eric] loops back to its tail and wants to stay off
+/// it. So every caller passes the options it wants, and each snake keeps its own next to its code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct APrimeOptions {
+    /// Extra cost for walking over food that isn't one of the targets
     pub food_penalty: i32,
+    /// Extra cost for walking through hazards
     pub hazard_penalty: i32,
 }
 
@@ -39,7 +49,7 @@ pub trait APrimeNextDirection: APrimeCalculable {
         &self,
         start: &Self::NativePositionType,
         targets: &[Self::NativePositionType],
-        options: Option<APrimeOptions>,
+        options: &APrimeOptions,
     ) -> Option<Move>;
 }
 
@@ -48,7 +58,7 @@ impl APrimeNextDirection for Game {
         &self,
         start: &Self::NativePositionType,
         targets: &[Self::NativePositionType],
-        options: Option<APrimeOptions>,
+        options: &APrimeOptions,
     ) -> Option<Move> {
         let shortest_path = self.shortest_path(start, targets, options);
         let next_coordinate = shortest_path.get(1);
@@ -63,7 +73,7 @@ pub trait APrimeCalculable: PositionGettableGame + NeighborDeterminableGame {
         &self,
         start: &Self::NativePositionType,
         targets: &[Self::NativePositionType],
-        options: Option<APrimeOptions>,
+        options: &APrimeOptions,
     ) -> Option<i32> {
         self.a_prime_inner(start, targets, options)
             .map(|r| r.best_cost)
@@ -73,7 +83,7 @@ pub trait APrimeCalculable: PositionGettableGame + NeighborDeterminableGame {
         &self,
         start: &Self::NativePositionType,
         targets: &[Self::NativePositionType],
-        options: Option<APrimeOptions>,
+        options: &APrimeOptions,
     ) -> Vec<Self::NativePositionType> {
         let result = self.a_prime_inner(start, targets, options);
 
@@ -101,7 +111,7 @@ pub trait APrimeCalculable: PositionGettableGame + NeighborDeterminableGame {
         &self,
         start: &Self::NativePositionType,
         targets: &[Self::NativePositionType],
-        options: Option<APrimeOptions>,
+        options: &APrimeOptions,
     ) -> Option<APrimeResult<Self::NativePositionType>>;
 }
 
@@ -140,9 +150,8 @@ impl<T: CellNum, D: Dimensions, const BOARD_SIZE: usize, const MAX_SNAKES: usize
         &self,
         start: &Self::NativePositionType,
         targets: &[Self::NativePositionType],
-        options: Option<APrimeOptions>,
+        options: &APrimeOptions,
     ) -> Option<APrimeResult<Self::NativePositionType>> {
-        let options = options.unwrap_or_default();
         let mut paths_from: FxHashMap<Self::NativePositionType, Option<Self::NativePositionType>> =
             FxHashMap::default();
 
@@ -208,9 +217,8 @@ impl<T: CellNum, D: Dimensions, const BOARD_SIZE: usize, const MAX_SNAKES: usize
         &self,
         start: &Self::NativePositionType,
         targets: &[Self::NativePositionType],
-        options: Option<APrimeOptions>,
+        options: &APrimeOptions,
     ) -> Option<APrimeResult<Self::NativePositionType>> {
-        let options = options.unwrap_or_default();
         let mut paths_from: FxHashMap<Self::NativePositionType, Option<Self::NativePositionType>> =
             FxHashMap::default();
 
@@ -218,6 +226,14 @@ impl<T: CellNum, D: Dimensions, const BOARD_SIZE: usize, const MAX_SNAKES: usize
             return None;
         }
 
+        // On maze maps the hazards are walls we can't go through, and the passages through the
+        // outer wall make the plain distance guess too high
+        let maze = MazePassages::for_board(self);
+        let target_positions: Vec<Position> = targets
+            .iter()
+            .map(|t| self.position_from_native(*t))
+            .collect();
+
         let mut to_search: BinaryHeap<Node<Self::NativePositionType>> = BinaryHeap::new();
 
         let mut known_score: FxHashMap<Self::NativePositionType, i32> = FxHashMap::default();
@@ -251,15 +267,25 @@ impl<T: CellNum, D: Dimensions, const BOARD_SIZE: usize, const MAX_SNAKES: usize
             for neighbor in neighbors
                 .into_iter()
                 .filter(|n| targets.contains(n) || !self.position_is_snake_body(coordinate))
+                .filter(|n| maze.is_none() || !self.is_hazard(n))
             {
                 if &tentative < known_score.get(&neighbor).unwrap_or(&i32::MAX) {
+                    let hueristic = match &maze {
+                        Some(maze) => {
+                            let position = self.position_from_native(neighbor);
+                            target_positions
+                                .iter()
+                                .map(|target| maze.distance(&position, target))
+                                .min()
+                        }
+                        None => Self::hueristic(&neighbor, targets, self.get_width()),
+                    };
+
                     known_score.insert(neighbor, tentative);
                     paths_from.insert(neighbor, Some(coordinate));
                     to_search.push(Node {
                         coordinate: neighbor,
-                        cost: tentative
-                            + Self::hueristic(&neighbor, targets, self.get_width())
-                                .unwrap_or(HEURISTIC_MAX),
+                        cost: tentative + hueristic.unwrap_or(HEURISTIC_MAX),
                     });
                 }
             }
@@ -312,15 +338,21 @@ impl<T: CellNum, D: Dimensions, const BOARD_SIZE: usize, const MAX_SNAKES: usize
             .min()
     }
 
+    /// The Manhattan distance between two cells
+    ///
+    /// The cells are numbered row by row, so we split each index back into its column and row
+    /// for the board's width. Just looking at how far apart the indexes are overestimates cells
+    /// on either side of a row boundary, which A* can't have
     fn dist_between_cell(
         a: &Self::NativePositionType,
         b: &Self::NativePositionType,
         width: u32,
     ) -> i32 {
         let width = width as i32;
-        let diff = (a.0.as_usize() as i32 - b.0.as_usize() as i32).abs();
+        let a = a.0.as_usize() as i32;
+        let b = b.0.as_usize() as i32;
 
-        (diff / width) + (diff % width)
+        (a % width - b % width).abs() + (a / width - b / width).abs()
     }
 }
 
@@ -359,9 +391,8 @@ impl APrimeCalculable for Game {
         &self,
         start: &Position,
         targets: &[Position],
-        options: Option<APrimeOptions>,
+        options: &APrimeOptions,
     ) -> Option<APrimeResult<Position>> {
-        let options = options.unwrap_or_default();
         let mut paths_from: FxHashMap<Position, Option<Position>> = FxHashMap::default();
 
         if targets.is_empty() {
@@ -425,7 +456,7 @@ pub trait ClosestFoodCalculable: PositionGettableGame {
     fn dist_to_closest_food(
         &self,
         start: &Self::NativePositionType,
-        options: Option<APrimeOptions>,
+        options: &APrimeOptions,
     ) -> Option<i32>;
 }
 
@@ -433,8 +464,8 @@ impl<T: APrimeCalculable + FoodGettableGame> ClosestFoodCalculable for T {
     default fn dist_to_closest_food(
         &self,
         start: &<Self as PositionGettableGame>::NativePositionType,
-        options: std::option::Option<APrimeOptions>,
-    ) -> std::option::Option<i32> {
+        options: &APrimeOptions,
+    ) -> Option<i32> {
         self.shortest_distance(start, &self.get_all_food_as_native_positions(), options)
     }
 }
@@ -443,7 +474,7 @@ impl ClosestFoodCalculable for StandardCellBoard4Snakes11x11 {
     fn dist_to_closest_food(
         &self,
         start: &Self::NativePositionType,
-        options: Option<APrimeOptions>,
+        options: &APrimeOptions,
     ) -> Option<i32> {
         let width = self.get_width();
         let all_foods = self.get_all_food_as_native_positions();
@@ -452,7 +483,6 @@ impl ClosestFoodCalculable for StandardCellBoard4Snakes11x11 {
             return None;
         }
 
-        let options = options.unwrap_or_default();
         let mut paths_from: FxHashMap<Self::NativePositionType, Option<Self::NativePositionType>> =
             FxHashMap::default();
 
@@ -506,15 +536,63 @@ impl ClosestFoodCalculable for StandardCellBoard4Snakes11x11 {
 mod tests {
     use super::*;
     use battlesnake_game_types::compact_representation::{
+        dimensions::{Custom, Square},
         CellIndex, StandardCellBoard4Snakes11x11 as CellBoard4Snakes11x11,
     };
 
     fn cell_index_from_position_default_width(pos: Position) -> CellIndex<u8> {
-        let width = ((11 * 11) as f32).sqrt() as u8;
+        cell_index(pos, 11)
+    }
 
+    fn cell_index(pos: Position, width: u8) -> CellIndex<u8> {
         CellIndex::new(pos, width)
     }
 
+    fn standard_game(fixture: &str) -> Game {
+        serde_json::from_str(fixture).unwrap()
+    }
+
+    /// A* on the compact board has to find the same distances as on the wire game, which uses
+    /// positions directly and so can't get the board width wrong
+    fn assert_compact_distances_match_the_wire_game<T>(game: &Game, board: &T)
+    where
+        T: APrimeCalculable + PositionGettableGame,
+    {
+        let targets: Vec<Position> = game
+            .board
+            .food
+            .iter()
+            .copied()
+            .chain([
+                Position { x: 0, y: 0 },
+                Position {
+                    x: game.board.width as i32 - 1,
+                    y: game.board.height as i32 - 1,
+                },
+                Position {
+                    x: game.board.width as i32 / 2,
+                    y: 1,
+                },
+            ])
+            .filter(|p| game.board.snakes.iter().all(|s| !s.body.contains(p)))
+            .collect();
+
+        for snake in &game.board.snakes {
+            for target in &targets {
+                assert_eq!(
+                    board.shortest_distance(
+                        &board.native_from_position(snake.head),
+                        &[board.native_from_position(*target)],
+                        &APrimeOptions::default()
+                    ),
+                    game.shortest_distance(&snake.head, &[*target], &APrimeOptions::default()),
+                    "{} to {target:?}",
+                    snake.id
+                );
+            }
+        }
+    }
+
     #[test]
     fn test_heuristic() {
         assert_eq!(
@@ -546,6 +624,195 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_heuristic_across_rows() {
+        // These are only 2 apart, but their indexes are a whole row minus one apart
+        for width in [7, 11, 19] {
+            assert_eq!(
+                CellBoard4Snakes11x11::hueristic(
+                    &cell_index(Position { x: 0, y: 1 }, width),
+                    &[cell_index(Position { x: 1, y: 0 }, width)],
+                    width as u32
+                ),
+                Some(2),
+                "width {width}"
+            );
+        }
+
+        assert_eq!(
+            CellBoard4Snakes11x11::hueristic(
+                &cell_index(Position { x: 6, y: 0 }, 7),
+                &[cell_index(Position { x: 0, y: 6 }, 7)],
+                7
+            ),
+            Some(12)
+        );
+        assert_eq!(
+            CellBoard4Snakes11x11::hueristic(
+                &cell_index(Position { x: 18, y: 3 }, 19),
+                &[cell_index(Position { x: 2, y: 12 }, 19)],
+                19
+            ),
+            Some(25)
+        );
+    }
+
+    #[test]
+    fn test_a_prime_on_a_7x7_board() {
+        let game = standard_game(include_str!("../../fixtures/standard_7x7.json"));
+        let id_map = build_snake_id_map(&game);
+        let board =
+            StandardCellBoard::<u8, Square, { 7 * 7 }, 4>::convert_from_game(game.clone(), &id_map)
+                .unwrap();
+
+        assert_compact_distances_match_the_wire_game(&game, &board);
+        assert_eq!(
+            board.dist_to_closest_food(
+                &board.get_head_as_native_position(board.you_id()),
+                &APrimeOptions::default()
+            ),
+            Some(4)
+        );
+    }
+
+    #[test]
+    fn test_a_prime_on_a_19x19_board() {
+        let game = standard_game(include_str!("../../fixtures/standard_19x19.json"));
+        let id_map = build_snake_id_map(&game);
+        let board = StandardCellBoard::<u16, Square, { 19 * 19 }, 4>::convert_from_game(
+            game.clone(),
+            &id_map,
+        )
+        .unwrap();
+
+        assert_compact_distances_match_the_wire_game(&game, &board);
+        assert_eq!(
+            board.dist_to_closest_food(
+                &board.get_head_as_native_position(board.you_id()),
+                &APrimeOptions::default()
+            ),
+            Some(10)
+        );
+    }
+
+    #[test]
+    fn test_arcade_maze_paths_take_the_tunnels_and_not_the_walls() {
+        let game: Game =
+            serde_json::from_str(include_str!("../../fixtures/arcade_maze_tunnel_race.json"))
+                .unwrap();
+        let id_map = build_snake_id_map(&game);
+        let board: WrappedCellBoard<u16, Custom, { 19 * 21 }, 4> =
+            game.as_wrapped_cell_board(&id_map).unwrap();
+        let head = board.get_head_as_native_position(board.you_id());
+
+        // Left through the tunnel gets us to the food first, we used to think it was 11 away
+        assert_eq!(
+            board.shortest_distance(
+                &head,
+                &board.get_all_food_as_native_positions(),
+                &APrimeOptions::default()
+            ),
+            Some(5)
+        );
+        // We used to cut through the walls to get here, and think it was only 14 away
+        assert_eq!(
+            board.shortest_distance(
+                &head,
+                &[board.native_from_position(Position { x: 17, y: 3 })],
+                &APrimeOptions::default()
+            ),
+            Some(18)
+        );
+    }
+
+    #[test]
+    fn test_arcade_maze_tunnels_in_a_real_game() {
+        // Turn 639 of a duel on arcade maze, with us at (2, 3) in the bottom left
+        let game: Game = serde_json::from_str(include_str!(
+            "../../fixtures/arcade_maze_end_game_duels.json"
+        ))
+        .unwrap();
+        let id_map = build_snake_id_map(&game);
+        let board: WrappedCellBoard<u16, Custom, { 19 * 21 }, 4> =
+            game.as_wrapped_cell_board(&id_map).unwrap();
+        let head = board.get_head_as_native_position(board.you_id());
+
+        // Down through the tunnel at (1, 0) comes out at the top, around the walls is much further
+        assert_eq!(
+            board.shortest_distance(
+                &head,
+                &[board.native_from_position(Position { x: 4, y: 17 })],
+                &APrimeOptions::default()
+            ),
+            Some(11)
+        );
+    }
+
+    /// Us alone in the bottom corner, with a line of food between us and `(4, 0)`
+    fn food_lane() -> Game {
+        let mut game = standard_game(include_str!("../fixtures/start_of_game.json"));
+        let corner = Position { x: 0, y: 0 };
+        game.you.head = corner;
+        game.you.body = [corner; 3].into_iter().collect();
+        game.board.snakes = vec![game.you.clone()];
+        game.board.food = (1..4).map(|x| Position { x, y: 0 }).collect();
+        game.board.hazards = vec![];
+
+        game
+    }
+
+    #[test]
+    fn test_food_penalties_pick_between_paths() {
+        let game = food_lane();
+        let target = [Position { x: 4, y: 0 }];
+
+        // Frank doesn't mind the food, so straight through it is shortest
+        let frank = crate::famished_frank::A_PRIME_OPTIONS;
+        assert_eq!(
+            game.shortest_path_next_direction(&game.you.head, &target, &frank),
+            Some(Move::Right)
+        );
+        assert_eq!(
+            game.shortest_distance(&game.you.head, &target, &frank),
+            Some(4)
+        );
+
+        // Eric would rather go two cells out of the way than eat
+        let eric = crate::eremetic_eric::A_PRIME_OPTIONS;
+        assert_eq!(
+            game.shortest_path_next_direction(&game.you.head, &target, &eric),
+            Some(Move::Up)
+        );
+        assert_eq!(
+            game.shortest_distance(&game.you.head, &target, &eric),
+            Some(6)
+        );
+    }
+
+    #[test]
+    fn test_compact_boards_follow_the_same_options() {
+        let game = food_lane();
+        let id_map = build_snake_id_map(&game);
+        let board = CellBoard4Snakes11x11::convert_from_game(game.clone(), &id_map).unwrap();
+        let head = board.get_head_as_native_position(board.you_id());
+
+        for options in [
+            crate::famished_frank::A_PRIME_OPTIONS,
+            crate::eremetic_eric::A_PRIME_OPTIONS,
+            APrimeOptions::default(),
+        ] {
+            assert_eq!(
+                board.shortest_distance(
+                    &head,
+                    &[board.native_from_position(Position { x: 4, y: 0 })],
+                    &options
+                ),
+                game.shortest_distance(&game.you.head, &[Position { x: 4, y: 0 }], &options),
+                "{options:?}"
+            );
+        }
+    }
+
     // #[test]
     // fn test_basic_a_prime() {
     //     let json = b"{\"game\":{\"id\":\"\",\"ruleset\":{\"name\":\"royale\",\"version\":\"v1.0.17\"},\"timeout\":500},\"turn\":60,\"board\":{\"height\":11,\"width\":11,\"snakes\":[{\"id\":\"\",\"name\":\"\",\"latency\":\"100\",\"health\":86,\"body\":[{\"x\":10,\"y\":4}],\"head\":{\"x\":10,\"y\":4},\"length\":1,\"shout\":\"\"}],\"food\":[],\"hazards\":[]},\"you\":{\"id\":\"\",\"name\":\"\",\"latency\":\"100\",\"health\":86,\"body\":[{\"x\":10,\"y\":4}],\"head\":{\"x\":10,\"y\":4},\"length\":1,\"shout\":\"\"}}";
@@ -130,6 +130,7 @@ impl BattlesnakeFactory for AmphibiousArthurFactory {
             head: Some("trans-rights-scarf".to_owned()),
             tail: Some("swirl".to_owned()),
             version: None,
+            ..Default::default()
         }
     }
 }
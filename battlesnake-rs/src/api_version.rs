@@ -0,0 +1,168 @@
+//! Which version of the Battlesnake API we speak to the engine
+//!
+//! Everything we have today is version 1, and that's what the engine gets unless it asks for
+//! something else. A request can list the versions it understands in the [API_VERSION_HEADER],
+//! and gets the newest one we share with it. Each version is a [WireFormat] that reads its
+//! requests into the [Game] our snakes play and writes their answers back out, so supporting a
+//! new version of the API is a new [WireFormat] instead of a change to every snake
+
+use std::fmt::Display;
+
+use serde_json::Value;
+
+use crate::{AboutMe, Game, MoveOutput};
+
+/// The header a request lists the API versions it understands in, like `2, 1`
+pub const API_VERSION_HEADER: &str = "x-battlesnake-api-version";
+
+/// A version of the Battlesnake API, in the order they came out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum ApiVersion {
+    #[default]
+    V1,
+}
+
+impl ApiVersion {
+    /// The version from its number, None for one we don't speak
+    pub fn from_number(number: u32) -> Option<Self> {
+        match number {
+            1 => Some(Self::V1),
+            _ => None,
+        }
+    }
+
+    /// The number the version goes by in the `apiversion` field
+    pub fn number(&self) -> u32 {
+        match self {
+            Self::V1 => 1,
+        }
+    }
+
+    /// The newest version in the value of the [API_VERSION_HEADER] that we speak too
+    ///
+    /// Versions can be written as `2` or `v2`. Without the header, or when none of the versions in
+    /// it are ones we speak, this is version 1, which is what the engine expects from everyone
+    pub fn negotiate(requested: Option<&str>) -> Self {
+        requested
+            .into_iter()
+            .flat_map(|versions| versions.split(','))
+            .filter_map(|version| {
+                let version = version.trim();
+                let version = version
+                    .strip_prefix(['v', 'V'])
+                    .unwrap_or(version)
+                    .parse()
+                    .ok()?;
+
+                Self::from_number(version)
+            })
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// How requests and responses look in this version
+    pub fn wire_format(&self) -> &'static dyn WireFormat {
+        match self {
+            Self::V1 => &V1,
+        }
+    }
+}
+
+impl Display for ApiVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.number())
+    }
+}
+
+/// How a snake looks on the board, which the engine calls its customizations
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Customizations {
+    pub color: Option<String>,
+    pub head: Option<String>,
+    pub tail: Option<String>,
+}
+
+impl AboutMe {
+    /// The customizations this snake is shown with
+    pub fn customizations(&self) -> Customizations {
+        Customizations {
+            color: self.color.clone(),
+            head: self.head.clone(),
+            tail: self.tail.clone(),
+        }
+    }
+}
+
+/// Reads the requests and writes the responses of one [ApiVersion]
+pub trait WireFormat: Send + Sync {
+    fn version(&self) -> ApiVersion;
+
+    /// Read the game out of the body of a `/start`, `/move` or `/end` request
+    fn parse_game(&self, body: &[u8]) -> serde_json::Result<Game>;
+
+    /// The body of the `/` response
+    fn info(&self, about: AboutMe) -> Value;
+
+    /// The body of the `/move` response
+    fn move_response(&self, output: &MoveOutput) -> Value;
+}
+
+/// Version 1, where the requests are already a [Game] and the customizations are at the top level
+/// of the `/` response
+struct V1;
+
+impl WireFormat for V1 {
+    fn version(&self) -> ApiVersion {
+        ApiVersion::V1
+    }
+
+    fn parse_game(&self, body: &[u8]) -> serde_json::Result<Game> {
+        crate::request_tracing::parse_game(body)
+    }
+
+    fn info(&self, about: AboutMe) -> Value {
+        serde_json::to_value(AboutMe {
+            apiversion: self.version().to_string(),
+            ..about
+        })
+        .expect("AboutMe is always valid JSON")
+    }
+
+    fn move_response(&self, output: &MoveOutput) -> Value {
+        serde_json::to_value(output).expect("MoveOutput is always valid JSON")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_negotiation_falls_back_to_version_1() {
+        assert_eq!(ApiVersion::negotiate(None), ApiVersion::V1);
+        assert_eq!(ApiVersion::negotiate(Some("")), ApiVersion::V1);
+        assert_eq!(ApiVersion::negotiate(Some("3, v2")), ApiVersion::V1);
+        assert_eq!(ApiVersion::negotiate(Some("v2, V1")), ApiVersion::V1);
+        assert_eq!(ApiVersion::negotiate(Some("not a version")), ApiVersion::V1);
+    }
+
+    #[test]
+    fn test_version_1_keeps_the_customizations_at_the_top_level() {
+        let about = AboutMe {
+            color: Some("#f5a623".to_owned()),
+            head: Some("smart-caterpillar".to_owned()),
+            ..AboutMe::default()
+        };
+        assert_eq!(
+            json!(about.customizations()),
+            json!({ "color": "#f5a623", "head": "smart-caterpillar", "tail": null })
+        );
+
+        let info = ApiVersion::V1.wire_format().info(about);
+        assert_eq!(info["apiversion"], "1");
+        assert_eq!(info["color"], "#f5a623");
+        assert_eq!(info.get("customizations"), None);
+    }
+}
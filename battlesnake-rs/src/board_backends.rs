@@ -0,0 +1,377 @@
+//! Pick which compact board to use for 11x11 games by timing them on the machine we're running on
+//!
+//! [ToBestCellBoard] always picks the smallest board a game fits in, but that isn't always the
+//! fastest one to simulate. Byte sized cells pack tighter, but depending on the hardware the
+//! wider cells or the extra snake slots of a bigger board can come out ahead. At startup
+//! [benchmark_at_startup] times a short burst of random games on each candidate and remembers
+//! the results, and [backend_for] then picks the fastest candidate that can hold a given game.
+
+use std::{
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use battlesnake_minimax::Instruments;
+use tracing::{info, warn};
+
+use crate::{famished_frank::FamishedFrank, *};
+
+/// How many turns each random game in the benchmark plays at most
+const BENCHMARK_GAME_TURNS: usize = 50;
+
+/// A compact board that can hold an 11x11 game
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// `u8` cells with room for 4 snakes. This is what [ToBestCellBoard] picks
+    U8Cells4Snakes,
+    /// `u16` cells with room for 4 snakes
+    U16Cells4Snakes,
+    /// `u8` cells with room for 8 snakes
+    U8Cells8Snakes,
+}
+
+impl Backend {
+    pub fn all() -> [Backend; 3] {
+        [
+            Backend::U8Cells4Snakes,
+            Backend::U16Cells4Snakes,
+            Backend::U8Cells8Snakes,
+        ]
+    }
+
+    pub fn max_snakes(&self) -> usize {
+        match self {
+            Backend::U8Cells4Snakes | Backend::U16Cells4Snakes => 4,
+            Backend::U8Cells8Snakes => 8,
+        }
+    }
+
+    /// Can this backend hold the given game
+    pub fn fits(&self, game: &Game) -> bool {
+        is_11x11(game) && game.board.snakes.len() <= self.max_snakes() && !game.is_arcade_maze_map()
+    }
+}
+
+/// Is this game on the 11x11 board that every backend, and the snakes built on a fixed size
+/// board, are sized for
+pub fn is_11x11(game: &Game) -> bool {
+    game.board.width == 11 && game.board.height == 11
+}
+
+/// The most cells of any board [ToBestCellBoard] has, which is the 19x21 arcade maze
+pub const MAX_COMPACT_CELLS: usize = 19 * 21;
+
+/// The most snakes of any board [ToBestCellBoard] has
+pub const MAX_COMPACT_SNAKES: usize = 8;
+
+/// Does [ToBestCellBoard] have a board this game fits on
+///
+/// Custom games can be any size, and converting one that's bigger than every compact board blows
+/// up. The snakes that search on a compact board check this first, and play anything else with an
+/// [oversized_board_snake]
+pub fn fits_compact_boards(game: &Game) -> bool {
+    let cells = game.board.width as usize * game.board.height as usize;
+
+    cells <= MAX_COMPACT_CELLS && game.board.snakes.len() <= MAX_COMPACT_SNAKES
+}
+
+/// What we play a game that doesn't [fits_compact_boards] with, so we still make a move
+///
+/// This is [FamishedFrank] on the wire representation, which works at any size. He's much weaker
+/// than our searches, but that beats not showing up
+pub fn oversized_board_snake(game: Game) -> BoxedSnake {
+    warn!(
+        game_id = %game.game.id,
+        width = game.board.width,
+        height = game.board.height,
+        snakes = game.board.snakes.len(),
+        "This board is too big for our compact boards, playing it on the wire representation"
+    );
+
+    Box::new(FamishedFrank::new(game))
+}
+
+/// How fast one backend simulated on this machine
+#[derive(Debug, Clone, Copy)]
+pub struct Measurement {
+    pub backend: Backend,
+    pub wrapped: bool,
+    pub simulated_turns_per_ms: f64,
+}
+
+/// The measurements for every backend, for both standard and wrapped games
+#[derive(Debug, Clone, Default)]
+pub struct BackendSelection {
+    pub measurements: Vec<Measurement>,
+}
+
+impl BackendSelection {
+    /// The fastest backend that can hold this game, if we measured any
+    pub fn fastest_for(&self, game: &Game) -> Option<Backend> {
+        let wrapped = crate::wire_game::is_wrapped(&game.game);
+
+        self.measurements
+            .iter()
+            .filter(|m| m.wrapped == wrapped && m.backend.fits(game))
+            .max_by(|a, b| {
+                a.simulated_turns_per_ms
+                    .total_cmp(&b.simulated_turns_per_ms)
+            })
+            .map(|m| m.backend)
+    }
+}
+
+static SELECTION: OnceLock<BackendSelection> = OnceLock::new();
+
+/// How long to time each backend for at startup
+///
+/// This is read from `BOARD_BACKEND_BENCHMARK_MS` and defaults to 50 milliseconds. Setting it to 0
+/// skips the benchmark, and every game uses [ToBestCellBoard]
+pub fn benchmark_budget() -> Duration {
+    let ms = match std::env::var("BOARD_BACKEND_BENCHMARK_MS").map(|x| x.parse()) {
+        Ok(Ok(x)) => x,
+        _ => 50,
+    };
+
+    Duration::from_millis(ms)
+}
+
+/// Time every backend and remember the results for [backend_for]
+///
+/// Only the first call runs the benchmark, later calls return the same results
+pub fn benchmark_at_startup(budget: Duration) -> &'static BackendSelection {
+    SELECTION.get_or_init(|| {
+        if budget.is_zero() {
+            return BackendSelection::default();
+        }
+
+        let game: Game =
+            serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap();
+
+        let mut measurements = vec![];
+        for wrapped in [false, true] {
+            let mut game = game.clone();
+            if wrapped {
+                game.game.ruleset.name = "wrapped".to_owned();
+            }
+
+            for backend in Backend::all() {
+                let simulated_turns_per_ms = if wrapped {
+                    crate::with_backend!(WrappedCellBoard, game.clone(), backend, |board| {
+                        simulated_turns_per_ms(board, budget)
+                    })
+                } else {
+                    crate::with_backend!(StandardCellBoard, game.clone(), backend, |board| {
+                        simulated_turns_per_ms(board, budget)
+                    })
+                };
+
+                info!(
+                    backend = ?backend,
+                    wrapped,
+                    simulated_turns_per_ms,
+                    "Measured board backend"
+                );
+                measurements.push(Measurement {
+                    backend,
+                    wrapped,
+                    simulated_turns_per_ms,
+                });
+            }
+        }
+
+        BackendSelection { measurements }
+    })
+}
+
+/// The backend to use for this game, or None if we didn't benchmark or none of them fit
+pub fn backend_for(game: &Game) -> Option<Backend> {
+    SELECTION.get()?.fastest_for(game)
+}
+
+/// Play random games on the board until the budget runs out, and count the turns we simulated
+fn simulated_turns_per_ms<BoardType, const N_SNAKES: usize>(
+    board: BoardType,
+    budget: Duration,
+) -> f64
+where
+    BoardType: SimulableGame<Instruments, N_SNAKES>
+        + RandomReasonableMovesGame
+        + VictorDeterminableGame
+        + Clone,
+{
+    let mut rng = rand::thread_rng();
+    let started_at = Instant::now();
+    let mut turns = 0;
+
+    while started_at.elapsed() < budget {
+        let mut current = board.clone();
+
+        for _ in 0..BENCHMARK_GAME_TURNS {
+            if current.is_over() {
+                break;
+            }
+
+            let moves = current
+                .random_reasonable_move_for_each_snake(&mut rng)
+                .map(|(sid, m)| (sid, [m]));
+            current = match current
+                .simulate_with_moves(&Instruments::default(), moves)
+                .next()
+            {
+                Some((_, next)) => next,
+                None => break,
+            };
+            turns += 1;
+        }
+    }
+
+    turns as f64 / started_at.elapsed().as_secs_f64() / 1000.0
+}
+
+/// Convert the wire game into the given kind of board for a [Backend], and evaluate the body with it
+///
+/// The kind is either `StandardCellBoard` or `WrappedCellBoard`. The body is expanded once for
+/// every backend, so it can use the board generically
+#[macro_export]
+macro_rules! with_backend {
+    ( $board_kind:ident, $wire_game:expr, $backend:expr, |$board:ident| $body:expr ) => {{
+        use battlesnake_game_types::compact_representation::{dimensions::Square, $board_kind};
+        use $crate::{board_backends::Backend, *};
+
+        let game = $wire_game;
+        let id_map = build_snake_id_map(&game);
+
+        match $backend {
+            Backend::U8Cells4Snakes => {
+                let $board =
+                    $board_kind::<u8, Square, { 11 * 11 }, 4>::convert_from_game(game, &id_map)
+                        .unwrap();
+                $body
+            }
+            Backend::U16Cells4Snakes => {
+                let $board =
+                    $board_kind::<u16, Square, { 11 * 11 }, 4>::convert_from_game(game, &id_map)
+                        .unwrap();
+                $body
+            }
+            Backend::U8Cells8Snakes => {
+                let $board =
+                    $board_kind::<u8, Square, { 11 * 11 }, 8>::convert_from_game(game, &id_map)
+                        .unwrap();
+                $body
+            }
+        }
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn measurement(backend: Backend, simulated_turns_per_ms: f64) -> Measurement {
+        Measurement {
+            backend,
+            wrapped: false,
+            simulated_turns_per_ms,
+        }
+    }
+
+    #[test]
+    fn test_picks_the_fastest_backend_that_fits() {
+        let mut game: Game =
+            serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap();
+        let selection = BackendSelection {
+            measurements: vec![
+                measurement(Backend::U8Cells4Snakes, 10.0),
+                measurement(Backend::U16Cells4Snakes, 30.0),
+                measurement(Backend::U8Cells8Snakes, 20.0),
+            ],
+        };
+
+        assert_eq!(selection.fastest_for(&game), Some(Backend::U16Cells4Snakes));
+
+        // Nothing was measured for wrapped games
+        game.game.ruleset.name = "wrapped".to_owned();
+        assert_eq!(selection.fastest_for(&game), None);
+    }
+
+    #[test]
+    fn test_skips_backends_without_room_for_every_snake() {
+        let mut game: Game =
+            serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap();
+        while game.board.snakes.len() < 5 {
+            let mut extra = game.board.snakes[0].clone();
+            extra.id = format!("extra-{}", game.board.snakes.len());
+            game.board.snakes.push(extra);
+        }
+
+        assert!(!Backend::U16Cells4Snakes.fits(&game));
+        assert!(Backend::U8Cells8Snakes.fits(&game));
+    }
+
+    #[test]
+    fn test_every_snake_plays_7x7_and_19x19_boards() {
+        let fixtures = [
+            include_str!("../../fixtures/standard_7x7.json"),
+            include_str!("../../fixtures/standard_19x19.json"),
+        ];
+
+        for fixture in fixtures {
+            let game: Game = serde_json::from_str(fixture).unwrap();
+            // None of the backends fit, so each snake has to pick a board some other way
+            assert!(Backend::all().iter().all(|backend| !backend.fits(&game)));
+
+            let factories = all_factories()
+                .into_iter()
+                .chain([Box::new(hovering_hobbs::Factory) as BoxedFactory]);
+            for factory in factories {
+                let output = factory
+                    .create_from_wire_game(game.clone())
+                    .make_move()
+                    .unwrap();
+                let m = Move::all_iter()
+                    .find(|m| m.to_string() == output.r#move)
+                    .expect("The snake should return a real move");
+
+                assert!(
+                    !move_guard::is_fatal(&game, &game.you, m),
+                    "{} moved {m} on the {}x{} board",
+                    factory.name(),
+                    game.board.width,
+                    game.board.height
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_every_snake_plays_boards_too_big_for_the_compact_boards() {
+        let mut game: Game =
+            serde_json::from_str(include_str!("../../fixtures/standard_19x19.json")).unwrap();
+        assert!(fits_compact_boards(&game));
+
+        game.board.width = 25;
+        game.board.height = 25;
+        assert!(!fits_compact_boards(&game));
+
+        let factories = all_factories()
+            .into_iter()
+            .chain([Box::new(hovering_hobbs::Factory) as BoxedFactory]);
+        for factory in factories {
+            let output = factory
+                .create_from_wire_game(game.clone())
+                .make_move()
+                .unwrap();
+            let m = Move::all_iter()
+                .find(|m| m.to_string() == output.r#move)
+                .expect("The snake should return a real move");
+
+            assert!(
+                !move_guard::is_fatal(&game, &game.you, m),
+                "{} moved {m} on the 25x25 board",
+                factory.name()
+            );
+        }
+    }
+}
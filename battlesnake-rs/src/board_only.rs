@@ -0,0 +1,82 @@
+use tracing::info;
+
+use crate::*;
+
+/// Is `you` still one of the snakes on the board
+///
+/// The engine keeps sending us the game after we die, `/end` always and `/move` from some custom
+/// servers. In those requests `you` is our last known state but we aren't on the board anymore
+pub fn you_on_board(game: &Game) -> bool {
+    game.board.snakes.iter().any(|s| s.id == game.you.id)
+}
+
+/// What we run in place of a snake when `you` isn't on the board
+///
+/// Our snakes all expect to find themselves when they convert the game, so they can panic on these
+/// requests. This only keeps the board around, and never looks for `you`
+pub struct BoardOnlySnake {
+    game: Game,
+}
+
+impl BoardOnlySnake {
+    pub fn new(game: Game) -> Self {
+        Self { game }
+    }
+}
+
+impl BattlesnakeAI for BoardOnlySnake {
+    fn end(&self) {
+        info!(
+            game_id = %self.game.game.id,
+            turn = self.game.turn,
+            snakes_left = self.game.board.snakes.len(),
+            "Game ended without us on the board"
+        );
+    }
+
+    fn make_move(&self) -> Result<MoveOutput> {
+        // We are already dead, so any move is as good as another
+        Ok(MoveOutput {
+            r#move: format!("{}", Move::Up),
+            shout: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game_without_you() -> Game {
+        let mut game: Game =
+            serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap();
+        let you_id = game.you.id.clone();
+        game.board.snakes.retain(|s| s.id != you_id);
+
+        game
+    }
+
+    #[test]
+    fn test_you_on_board() {
+        let game: Game =
+            serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap();
+
+        assert!(you_on_board(&game));
+        assert!(!you_on_board(&game_without_you()));
+    }
+
+    #[test]
+    fn test_every_snake_handles_a_dead_you() {
+        for factory in all_factories() {
+            let snake = factory.create_from_wire_game(game_without_you());
+
+            snake.end();
+            assert_eq!(
+                snake.make_move().unwrap().r#move,
+                "up",
+                "{}",
+                factory.name()
+            );
+        }
+    }
+}
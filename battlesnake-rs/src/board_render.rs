@@ -0,0 +1,181 @@
+//! Turn boards and paths into text people can read, for debug output like the DOT dumps
+//!
+//! The compact boards number their cells, so printing a native position directly gives something
+//! like `CellIndex(60)`. Everything here goes through the board's own conversion to x/y instead,
+//! and on wrapped boards it points out each step of a path that goes across an edge, since those
+//! look like a jump across the whole board otherwise
+
+use std::fmt::Write;
+
+use battlesnake_game_types::wire_representation::Position;
+
+use crate::*;
+
+/// A native position as `(x, y)`
+pub fn describe_position<T: PositionGettableGame>(
+    game: &T,
+    position: &T::NativePositionType,
+) -> String {
+    let Position { x, y } = game.position_from_native(position.clone());
+
+    format!("({x}, {y})")
+}
+
+/// The direction a step between two neighboring cells went off the edge of the board, None for a
+/// step that stayed on it
+///
+/// Neighbors are never more than one apart unless the step wrapped, so a step from `(10, 5)` to
+/// `(0, 5)` went off the right edge
+pub fn wrapped_across(from: Position, to: Position) -> Option<Move> {
+    match (to.x - from.x, to.y - from.y) {
+        (dx, _) if dx < -1 => Some(Move::Right),
+        (dx, _) if dx > 1 => Some(Move::Left),
+        (_, dy) if dy < -1 => Some(Move::Up),
+        (_, dy) if dy > 1 => Some(Move::Down),
+        _ => None,
+    }
+}
+
+/// A path as its `(x, y)` positions, like `(9, 5) -> (10, 5) -wraps right-> (0, 5)`
+pub fn describe_path<T: PositionGettableGame>(game: &T, path: &[T::NativePositionType]) -> String {
+    let positions: Vec<Position> = path
+        .iter()
+        .map(|p| game.position_from_native(p.clone()))
+        .collect();
+
+    let mut description = String::new();
+    for (i, Position { x, y }) in positions.iter().enumerate() {
+        if i > 0 {
+            match wrapped_across(positions[i - 1], positions[i]) {
+                Some(edge) => write!(description, " -wraps {edge}-> ").unwrap(),
+                None => description.push_str(" -> "),
+            }
+        }
+        write!(description, "({x}, {y})").unwrap();
+    }
+
+    description
+}
+
+/// Draw the board as a grid of characters, with `path` marked on it and written out underneath
+///
+/// Up is up, like in the game, and the rows and columns are labeled with their y and x, with
+/// only the last digit of x so each column stays one character wide. Snakes are letters in the
+/// order the board lists them, upper case for the head, and the rest is `o` for the path, `*` for
+/// food, `~` for hazards and `.` for empty cells
+pub fn ascii_board<T>(game: &T, path: &[T::NativePositionType]) -> String
+where
+    T: PositionGettableGame
+        + SizeDeterminableGame
+        + SnakeIDGettableGame
+        + SnakeBodyGettableGame
+        + FoodQueryableGame
+        + HazardQueryableGame,
+{
+    let width = game.get_width() as i32;
+    let height = game.get_height() as i32;
+    let index = |p: Position| (p.y * width + p.x) as usize;
+
+    let mut cells = vec!['.'; (width * height) as usize];
+    for y in 0..height {
+        for x in 0..width {
+            let native = game.native_from_position(Position { x, y });
+            if game.is_food(&native) {
+                cells[index(Position { x, y })] = '*';
+            } else if game.is_hazard(&native) {
+                cells[index(Position { x, y })] = '~';
+            }
+        }
+    }
+    for p in path {
+        cells[index(game.position_from_native(p.clone()))] = 'o';
+    }
+    for (sid, letter) in game.get_snake_ids().iter().zip('a'..='z') {
+        let body = game.get_snake_body_vec(sid);
+        // Drawn tail first, so the head wins when the body is stacked up on it
+        for (i, p) in body.iter().enumerate().rev() {
+            cells[index(game.position_from_native(p.clone()))] = if i == 0 {
+                letter.to_ascii_uppercase()
+            } else {
+                letter
+            };
+        }
+    }
+
+    let mut board = String::new();
+    for y in (0..height).rev() {
+        write!(board, "{y:>2} ").unwrap();
+        for x in 0..width {
+            write!(board, " {}", cells[index(Position { x, y })]).unwrap();
+        }
+        board.push('\n');
+    }
+    board.push_str("   ");
+    for x in 0..width {
+        write!(board, " {}", x % 10).unwrap();
+    }
+    board.push('\n');
+
+    if !path.is_empty() {
+        writeln!(board, "Path: {}", describe_path(game, path)).unwrap();
+    }
+
+    board
+}
+
+#[cfg(test)]
+mod tests {
+    use battlesnake_game_types::compact_representation::WrappedCellBoard4Snakes11x11;
+
+    use super::*;
+
+    fn wrapped_board() -> WrappedCellBoard4Snakes11x11 {
+        let game: Game = serde_json::from_str(include_str!(
+            "../../fixtures/wrapped_neck_across_edges.json"
+        ))
+        .unwrap();
+        let id_map = build_snake_id_map(&game);
+
+        WrappedCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap()
+    }
+
+    #[test]
+    fn test_paths_point_out_where_they_wrap() {
+        let board = wrapped_board();
+        let mut body = board.get_snake_body_vec(board.you_id());
+        body.reverse();
+
+        assert_eq!(
+            describe_path(&board, &body),
+            "(9, 5) -> (10, 5) -wraps right-> (0, 5)"
+        );
+        assert_eq!(
+            wrapped_across(Position { x: 3, y: 0 }, Position { x: 3, y: 10 }),
+            Some(Move::Down)
+        );
+        assert_eq!(
+            wrapped_across(Position { x: 3, y: 4 }, Position { x: 3, y: 5 }),
+            None
+        );
+    }
+
+    #[test]
+    fn test_the_ascii_board_uses_x_and_y() {
+        let board = wrapped_board();
+        let path: Vec<_> = [(9, 8), (10, 8), (0, 8)]
+            .into_iter()
+            .map(|(x, y)| board.native_from_position(Position { x, y }))
+            .collect();
+
+        let ascii = ascii_board(&board, &path);
+        let lines: Vec<&str> = ascii.lines().collect();
+
+        // The top row is y = 10
+        assert_eq!(lines[0], "10  . . . . . c . . . . .");
+        assert_eq!(lines[2], " 8  o . . . . . . . . o o");
+        assert_eq!(lines[5], " 5  A b . . . . . . . a a");
+        assert_eq!(lines[8], " 2  . . . . . . . . * . .");
+        assert_eq!(lines[11], "    0 1 2 3 4 5 6 7 8 9 0");
+        assert_eq!(lines[12], "Path: (9, 8) -> (10, 8) -wraps right-> (0, 8)");
+    }
+}
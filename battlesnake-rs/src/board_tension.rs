@@ -0,0 +1,128 @@
+//! How much of the board is up for grabs between the snakes
+//!
+//! A square is contested when at least two snakes could get to it within a few turns. The flood
+//! fills only say who gets to each square first, so a board where two snakes are nose to nose
+//! looks the same to them as one where they are far apart with the same share of the space. The
+//! number of contested squares tells those apart: the more there are, the more the next few turns
+//! are a fight instead of each snake filling in its own side
+
+use battlesnake_game_types::types::{
+    HeadGettableGame, HealthGettableGame, NeighborDeterminableGame, PositionGettableGame,
+    SizeDeterminableGame, SnakeBodyGettableGame, SnakeIDGettableGame,
+};
+
+/// How many turns ahead Hovering Hobbs looks for squares the snakes could fight over
+pub const TENSION_TURNS: usize = 3;
+
+/// The squares that at least two living snakes can reach within `turns` moves
+///
+/// Each snake walks around every body as it is right now, since whose tail gets out of the way in
+/// time depends on who eats
+pub fn contested_squares<BoardType>(node: &BoardType, turns: usize) -> usize
+where
+    BoardType: SnakeIDGettableGame
+        + PositionGettableGame
+        + SizeDeterminableGame
+        + NeighborDeterminableGame
+        + HeadGettableGame
+        + HealthGettableGame
+        + SnakeBodyGettableGame,
+{
+    let width = node.get_width() as i32;
+    let index = |pos: &BoardType::NativePositionType| {
+        let pos = node.position_from_native(pos.clone());
+
+        (pos.y * width + pos.x) as usize
+    };
+    let cells = (node.get_width() * node.get_height()) as usize;
+
+    let snakes: Vec<_> = node
+        .get_snake_ids()
+        .into_iter()
+        .filter(|sid| node.is_alive(sid))
+        .collect();
+
+    let mut blocked = vec![false; cells];
+    for sid in &snakes {
+        for pos in node.get_snake_body_vec(sid) {
+            blocked[index(&pos)] = true;
+        }
+    }
+
+    let mut reached_by = vec![0u8; cells];
+    for sid in &snakes {
+        let mut seen = blocked.clone();
+        let mut frontier = vec![node.get_head_as_native_position(sid)];
+
+        for _ in 0..turns {
+            let mut next = vec![];
+            for pos in &frontier {
+                for neighbor in node.neighbors(pos) {
+                    let i = index(&neighbor);
+                    if !seen[i] {
+                        seen[i] = true;
+                        reached_by[i] = reached_by[i].saturating_add(1);
+                        next.push(neighbor);
+                    }
+                }
+            }
+            frontier = next;
+        }
+    }
+
+    reached_by.iter().filter(|count| **count >= 2).count()
+}
+
+/// [contested_squares] as a share of the whole board, from 0 when the snakes can't get in each
+/// other's way within `turns` to 1 when every square is contested
+pub fn board_tension<BoardType>(node: &BoardType, turns: usize) -> f64
+where
+    BoardType: SnakeIDGettableGame
+        + PositionGettableGame
+        + SizeDeterminableGame
+        + NeighborDeterminableGame
+        + HeadGettableGame
+        + HealthGettableGame
+        + SnakeBodyGettableGame,
+{
+    let cells = (node.get_width() * node.get_height()) as f64;
+
+    contested_squares(node, turns) as f64 / cells
+}
+
+#[cfg(test)]
+mod tests {
+    use battlesnake_game_types::{
+        compact_representation::StandardCellBoard4Snakes11x11, types::build_snake_id_map,
+        wire_representation::Game,
+    };
+
+    use super::*;
+
+    fn start_of_game(change: impl FnOnce(&mut Game)) -> StandardCellBoard4Snakes11x11 {
+        let mut game: Game =
+            serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap();
+        change(&mut game);
+        let id_map = build_snake_id_map(&game);
+
+        StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap()
+    }
+
+    #[test]
+    fn test_squares_are_contested_once_two_snakes_reach_them() {
+        let board = start_of_game(|_| {});
+
+        // Our head is at (9, 5) and the closest opponent's at (5, 9), eight moves apart, so the
+        // first squares we can both get to are the five halfway along the diagonal
+        assert_eq!(contested_squares(&board, 3), 0);
+        assert_eq!(contested_squares(&board, 4), 5);
+        assert_eq!(board_tension(&board, 4), 5.0 / 121.0);
+    }
+
+    #[test]
+    fn test_a_snake_alone_has_nothing_to_contest() {
+        let board = start_of_game(|game| game.board.snakes.retain(|s| s.id == "you"));
+
+        assert_eq!(contested_squares(&board, 10), 0);
+    }
+}
@@ -1,25 +1,38 @@
+use std::hash::{Hash, Hasher};
+
 use battlesnake_game_types::types::{
     RandomReasonableMovesGame, SnakeIDGettableGame, YouDeterminableGame,
 };
-use rand::thread_rng;
+use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
+use rustc_hash::FxHasher;
 
 use super::*;
 
+/// Bob picks a random reasonable move every turn
 pub struct BombasticBob<T> {
     game: T,
+    /// When this is set, the moves come from an RNG seeded with it instead of the thread's RNG
+    seed: Option<u64>,
+}
+
+impl<T: RandomReasonableMovesGame + SnakeIDGettableGame + YouDeterminableGame> BombasticBob<T> {
+    fn choose_move(&self, rng: &mut impl Rng) -> Move {
+        self.game
+            .random_reasonable_move_for_each_snake(rng)
+            .find(|(s, _)| s == self.game.you_id())
+            .map(|x| x.1)
+            .unwrap_or(Move::Right)
+    }
 }
 
 impl<T: RandomReasonableMovesGame + SnakeIDGettableGame + YouDeterminableGame> BattlesnakeAI
     for BombasticBob<T>
 {
     fn make_move(&self) -> Result<MoveOutput> {
-        let mut rng = thread_rng();
-        let chosen = self
-            .game
-            .random_reasonable_move_for_each_snake(&mut rng)
-            .find(|(s, _)| s == self.game.you_id())
-            .map(|x| x.1);
-        let dir = chosen.unwrap_or(Move::Right);
+        let dir = match self.seed {
+            Some(seed) => self.choose_move(&mut StdRng::seed_from_u64(seed)),
+            None => self.choose_move(&mut thread_rng()),
+        };
 
         Ok(MoveOutput {
             r#move: format!("{dir}"),
@@ -28,7 +41,46 @@ impl<T: RandomReasonableMovesGame + SnakeIDGettableGame + YouDeterminableGame> B
     }
 }
 
-pub struct BombasticBobFactory;
+/// Builds [BombasticBob]s, which are random unless they are given a seed
+#[derive(Default)]
+pub struct BombasticBobFactory {
+    seed: Option<u64>,
+}
+
+impl BombasticBobFactory {
+    /// A Bob that moves at random
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A Bob that makes the same moves every time it plays the same game
+    ///
+    /// The seed is mixed with the game id, the turn and which snake Bob is playing, so Bob doesn't
+    /// repeat itself from turn to turn or between the snakes it controls
+    pub fn seeded(seed: u64) -> Self {
+        Self { seed: Some(seed) }
+    }
+
+    /// Read the seed from `BOMBASTIC_BOB_SEED`, and move at random if it isn't set
+    pub fn from_env() -> Self {
+        match std::env::var("BOMBASTIC_BOB_SEED").map(|x| x.parse()) {
+            Ok(Ok(seed)) => Self::seeded(seed),
+            _ => Self::new(),
+        }
+    }
+
+    fn seed_for(&self, game: &Game) -> Option<u64> {
+        let seed = self.seed?;
+
+        let mut hasher = FxHasher::default();
+        seed.hash(&mut hasher);
+        game.game.id.hash(&mut hasher);
+        game.turn.hash(&mut hasher);
+        game.you.id.hash(&mut hasher);
+
+        Some(hasher.finish())
+    }
+}
 
 impl BattlesnakeFactory for BombasticBobFactory {
     fn name(&self) -> String {
@@ -36,7 +88,9 @@ impl BattlesnakeFactory for BombasticBobFactory {
     }
 
     fn create_from_wire_game(&self, game: Game) -> BoxedSnake {
-        Box::new(BombasticBob { game })
+        let seed = self.seed_for(&game);
+
+        Box::new(BombasticBob { game, seed })
     }
 
     fn about(&self) -> AboutMe {
@@ -48,3 +102,30 @@ impl BattlesnakeFactory for BombasticBobFactory {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moves_for_game(factory: &BombasticBobFactory, turns: i32) -> Vec<String> {
+        let mut game: Game =
+            serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap();
+
+        (0..turns)
+            .map(|turn| {
+                game.turn = turn;
+                let snake = factory.create_from_wire_game(game.clone());
+
+                snake.make_move().unwrap().r#move
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_seeded_bobs_repeat_their_moves() {
+        let first = moves_for_game(&BombasticBobFactory::seeded(7), 20);
+        let second = moves_for_game(&BombasticBobFactory::seeded(7), 20);
+
+        assert_eq!(first, second);
+    }
+}
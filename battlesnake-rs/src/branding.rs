@@ -0,0 +1,175 @@
+//! How our snakes look in the arenas, so the same code can be entered more than once under
+//! different identities. Like an experiment entered next to the main Improbable Irene, that should
+//! be easy to tell apart from it on the leaderboard
+
+use std::{collections::HashMap, path::Path, sync::OnceLock};
+
+use color_eyre::eyre::{Context, Result};
+use tracing::warn;
+
+use crate::AboutMe;
+
+/// The parts of a snake's `/` response a deployment can change. Anything left as None keeps what
+/// the snake says about itself
+#[derive(Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Branding {
+    pub author: Option<String>,
+    pub color: Option<String>,
+    pub head: Option<String>,
+    pub tail: Option<String>,
+    pub version: Option<String>,
+}
+
+/// The branding file has a table for each snake it changes, named after the snake
+///
+/// ```toml
+/// [improbable-irene]
+/// color = "#f5a623"
+/// head = "smart-caterpillar"
+/// version = "rollout-experiment"
+/// ```
+type BrandingFile = HashMap<String, Branding>;
+
+/// The `BRANDING_` prefix for a snake's variables, `BRANDING_IMPROBABLE_IRENE_` for
+/// `improbable-irene`
+fn env_prefix(snake_name: &str) -> String {
+    format!("BRANDING_{}_", snake_name.to_uppercase().replace('-', "_"))
+}
+
+/// Build the branding for a snake from the branding file, if there is one, and then the
+/// environment
+///
+/// Each field can be overridden with a variable named after the snake and the field, like
+/// `BRANDING_IMPROBABLE_IRENE_COLOR`. A file that isn't valid TOML or has a field we don't know
+/// about is an error
+fn load(
+    file: Option<&str>,
+    snake_name: &str,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<Branding> {
+    let mut from_file: BrandingFile = match file {
+        Some(contents) => toml::from_str(contents).wrap_err("Invalid branding file")?,
+        None => BrandingFile::default(),
+    };
+    let from_file = from_file.remove(snake_name).unwrap_or_default();
+
+    let prefix = env_prefix(snake_name);
+    let field = |name: &str, from_file: Option<String>| {
+        env(&format!("{prefix}{}", name.to_uppercase())).or(from_file)
+    };
+
+    Ok(Branding {
+        author: field("author", from_file.author),
+        color: field("color", from_file.color),
+        head: field("head", from_file.head),
+        tail: field("tail", from_file.tail),
+        version: field("version", from_file.version),
+    })
+}
+
+static BRANDING_FILE: OnceLock<Option<String>> = OnceLock::new();
+
+/// The branding this deployment gives the snake
+///
+/// The file at `BRANDING_PATH` is read the first time any snake asks for its branding, and the
+/// `BRANDING_` variables are checked on every call. A file we can't read or parse is logged and
+/// ignored
+pub fn branding_for(snake_name: &str) -> Branding {
+    let file = BRANDING_FILE.get_or_init(|| {
+        let path = std::env::var("BRANDING_PATH").ok()?;
+
+        match std::fs::read_to_string(Path::new(&path)) {
+            Ok(contents) => Some(contents),
+            Err(e) => {
+                warn!(path = %path, error = %e, "Couldn't read the branding file, ignoring it");
+                None
+            }
+        }
+    });
+
+    let env = |name: &str| std::env::var(name).ok();
+    load(file.as_deref(), snake_name, env).unwrap_or_else(|e| {
+        warn!(error = ?e, "Ignoring the branding file");
+        load(None, snake_name, env).expect("Loading without a file never fails")
+    })
+}
+
+impl AboutMe {
+    /// This response with anything the branding sets swapped in
+    pub fn branded(self, branding: Branding) -> Self {
+        Self {
+            author: branding.author.or(self.author),
+            color: branding.color.or(self.color),
+            head: branding.head.or(self.head),
+            tail: branding.tail.or(self.tail),
+            version: branding.version.or(self.version),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_env(_: &str) -> Option<String> {
+        None
+    }
+
+    #[test]
+    fn test_no_branding_without_a_file_or_env() {
+        assert_eq!(
+            load(None, "improbable-irene", no_env).unwrap(),
+            Branding::default()
+        );
+    }
+
+    #[test]
+    fn test_only_the_snakes_own_table_applies() {
+        let file = "[improbable-irene]\ncolor = \"#f5a623\"\n\n[hovering-hobbs]\nhead = \"bee\"\n";
+
+        let branding = load(Some(file), "improbable-irene", no_env).unwrap();
+
+        assert_eq!(branding.color.as_deref(), Some("#f5a623"));
+        assert_eq!(branding.head, None);
+    }
+
+    #[test]
+    fn test_env_overrides_the_file() {
+        let file = "[improbable-irene]\ncolor = \"#f5a623\"\ntail = \"bolt\"\n";
+        let env =
+            |name: &str| (name == "BRANDING_IMPROBABLE_IRENE_COLOR").then(|| "#000000".to_owned());
+
+        let branding = load(Some(file), "improbable-irene", env).unwrap();
+
+        assert_eq!(branding.color.as_deref(), Some("#000000"));
+        assert_eq!(branding.tail.as_deref(), Some("bolt"));
+    }
+
+    #[test]
+    fn test_unknown_fields_in_the_file_are_an_error() {
+        assert!(load(
+            Some("[improbable-irene]\nshout = \"hi\""),
+            "improbable-irene",
+            no_env
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_branding_only_replaces_what_it_sets() {
+        let about = AboutMe {
+            color: Some("#5a25a8".to_owned()),
+            head: Some("hydra".to_owned()),
+            ..Default::default()
+        }
+        .branded(Branding {
+            color: Some("#f5a623".to_owned()),
+            ..Default::default()
+        });
+
+        assert_eq!(about.color.as_deref(), Some("#f5a623"));
+        assert_eq!(about.head.as_deref(), Some("hydra"));
+    }
+}
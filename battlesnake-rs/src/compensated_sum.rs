@@ -0,0 +1,101 @@
+//! Running float sums that don't drift, for statistics built up from a huge number of small adds
+//!
+//! Every float add rounds, and once the sum is much bigger than the values going into it those
+//! rounding errors pile up. An MCTS node can have hundreds of thousands of scores backed up
+//! through it in a turn, and the variance UCB1-Normal takes from the sum and the sum of squares
+//! subtracts two nearly equal numbers, so the drift ends up a lot bigger than the variance when
+//! the scores are close together
+
+use std::{fmt::Debug, sync::atomic::Ordering};
+
+use atomic_float::AtomicF64;
+
+/// A float sum that keeps the rounding error of every add on the side and adds it back when it's
+/// read, which is Neumaier's version of Kahan summation
+///
+/// It has the same `load`, `store` and `fetch_add` as [AtomicF64], so it can stand in for one.
+/// The sum and the error are updated separately though, so two threads adding at the same time
+/// can lose one of the adds, which an [AtomicF64] never does. That's fine for the MCTS nodes,
+/// which are only ever touched from the thread searching their tree
+pub struct CompensatedF64 {
+    sum: AtomicF64,
+    compensation: AtomicF64,
+}
+
+impl CompensatedF64 {
+    pub fn new(value: f64) -> Self {
+        Self {
+            sum: AtomicF64::new(value),
+            compensation: AtomicF64::new(0.0),
+        }
+    }
+
+    /// The sum, with the rounding errors added back
+    pub fn load(&self, order: Ordering) -> f64 {
+        self.sum.load(order) + self.compensation.load(order)
+    }
+
+    /// Start over from `value`
+    pub fn store(&self, value: f64, order: Ordering) {
+        self.sum.store(value, order);
+        self.compensation.store(0.0, order);
+    }
+
+    /// Add `value`, returning the sum from before
+    pub fn fetch_add(&self, value: f64, order: Ordering) -> f64 {
+        let previous = self.load(order);
+        let sum = self.sum.load(order);
+        let next = sum + value;
+        let error = if sum.abs() >= value.abs() {
+            (sum - next) + value
+        } else {
+            (value - next) + sum
+        };
+        self.sum.store(next, order);
+        self.compensation.fetch_add(error, order);
+
+        previous
+    }
+}
+
+impl Default for CompensatedF64 {
+    fn default() -> Self {
+        Self::new(0.0)
+    }
+}
+
+/// Shows the sum like [AtomicF64] does, so the debug output and graphs read the same as before
+impl Debug for CompensatedF64 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.load(Ordering::Relaxed), f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_long_sums_stay_exact() {
+        let compensated = CompensatedF64::default();
+        let mut naive = 0.0;
+        for _ in 0..1_000_000 {
+            compensated.fetch_add(0.1, Ordering::Relaxed);
+            naive += 0.1;
+        }
+
+        assert_eq!(compensated.load(Ordering::Relaxed), 100_000.0);
+        assert_ne!(naive, 100_000.0);
+    }
+
+    #[test]
+    fn test_store_starts_over() {
+        let sum = CompensatedF64::new(1.0);
+        assert_eq!(sum.fetch_add(0.5, Ordering::Relaxed), 1.0);
+        assert_eq!(sum.load(Ordering::Relaxed), 1.5);
+
+        sum.store(10.0, Ordering::Relaxed);
+        assert_eq!(sum.load(Ordering::Relaxed), 10.0);
+        assert_eq!(format!("{sum:?}"), "10.0");
+    }
+}
@@ -1,13 +1,38 @@
 use tracing::info;
 
 use super::*;
+use crate::move_guard::is_fatal;
 
-pub struct ConstantCarter {}
+/// Carter always goes the same way
+///
+/// It walks down its list of preferred moves and takes the first one that doesn't kill it on the
+/// spot, or the first preference if they all do. With the default list of just `right` that means
+/// it goes right every turn, no matter what
+pub struct ConstantCarter {
+    game: Game,
+    preferences: Vec<Move>,
+}
 
 impl BattlesnakeAI for ConstantCarter {
     fn make_move(&self) -> Result<MoveOutput> {
+        let me = self
+            .game
+            .board
+            .snakes
+            .iter()
+            .find(|s| s.id == self.game.you.id);
+        let chosen = me
+            .and_then(|me| {
+                self.preferences
+                    .iter()
+                    .find(|m| !is_fatal(&self.game, me, **m))
+            })
+            .or_else(|| self.preferences.first())
+            .copied()
+            .unwrap_or(Move::Right);
+
         Ok(MoveOutput {
-            r#move: format!("{}", Move::Right),
+            r#move: format!("{chosen}"),
             shout: None,
         })
     }
@@ -17,15 +42,60 @@ impl BattlesnakeAI for ConstantCarter {
     }
 }
 
-pub struct ConstantCarterFactory;
+/// Builds [ConstantCarter]s that prefer the moves in the given order
+pub struct ConstantCarterFactory {
+    preferences: Vec<Move>,
+}
+
+impl ConstantCarterFactory {
+    /// A Carter that only ever goes right
+    pub fn new() -> Self {
+        Self::with_preferences(vec![Move::Right])
+    }
+
+    /// A Carter that takes the first of these moves that doesn't kill it
+    pub fn with_preferences(preferences: Vec<Move>) -> Self {
+        Self { preferences }
+    }
+
+    /// Read the preferences from `CONSTANT_CARTER_MOVES`, a comma separated list like
+    /// `up,left,down,right`. Anything we can't parse falls back to [ConstantCarterFactory::new]
+    pub fn from_env() -> Self {
+        let preferences: Option<Vec<Move>> =
+            std::env::var("CONSTANT_CARTER_MOVES")
+                .ok()
+                .and_then(|moves| {
+                    moves
+                        .split(',')
+                        .map(|m| {
+                            Move::all_iter().find(|candidate| candidate.to_string() == m.trim())
+                        })
+                        .collect()
+                });
+
+        match preferences {
+            Some(preferences) if !preferences.is_empty() => Self::with_preferences(preferences),
+            _ => Self::new(),
+        }
+    }
+}
+
+impl Default for ConstantCarterFactory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl BattlesnakeFactory for ConstantCarterFactory {
     fn name(&self) -> String {
         "constant-carter".to_owned()
     }
 
-    fn create_from_wire_game(&self, _game: Game) -> BoxedSnake {
-        Box::new(ConstantCarter {})
+    fn create_from_wire_game(&self, game: Game) -> BoxedSnake {
+        Box::new(ConstantCarter {
+            game,
+            preferences: self.preferences.clone(),
+        })
     }
     fn about(&self) -> AboutMe {
         AboutMe {
@@ -36,3 +106,42 @@ impl BattlesnakeFactory for ConstantCarterFactory {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start_of_game() -> Game {
+        serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap()
+    }
+
+    #[test]
+    fn test_defaults_to_always_going_right() {
+        let snake = ConstantCarterFactory::new().create_from_wire_game(start_of_game());
+
+        assert_eq!(snake.make_move().unwrap().r#move, "right");
+    }
+
+    #[test]
+    fn test_skips_preferences_that_are_fatal() {
+        let mut game = start_of_game();
+        let you_id = game.you.id.clone();
+        let me = game
+            .board
+            .snakes
+            .iter_mut()
+            .find(|s| s.id == you_id)
+            .unwrap();
+        // Pin us against the left wall, so going left is fatal
+        let head_x = me.body[0].x;
+        for segment in me.body.iter_mut() {
+            segment.x -= head_x;
+        }
+        me.head = me.body[0];
+
+        let factory = ConstantCarterFactory::with_preferences(vec![Move::Left, Move::Up]);
+        let snake = factory.create_from_wire_game(game);
+
+        assert_eq!(snake.make_move().unwrap().r#move, "up");
+    }
+}
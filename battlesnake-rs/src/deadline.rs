@@ -0,0 +1,61 @@
+use crate::Game;
+
+/// The header a proxy can set to tell us how many milliseconds we really have left to respond
+///
+/// When there is a proxy between the engine and us, it eats into the time we have to answer so
+/// the `timeout` in the game no longer matches reality
+pub const DEADLINE_HEADER: &str = "x-deadline-ms";
+
+/// Parse the value of the [DEADLINE_HEADER]
+///
+/// Returns None for anything that isn't a positive number of milliseconds, so a bad header falls
+/// back to the game's own timeout instead of failing the request
+pub fn parse_deadline(value: &str) -> Option<i64> {
+    value.trim().parse().ok().filter(|&ms: &i64| ms > 0)
+}
+
+/// Replace the game's timeout with the deadline from the request, if there was one
+///
+/// Both the minimax and MCTS snakes budget their search from the game's timeout, so this is all it
+/// takes for them to respect the deadline. Each snake still subtracts its own network latency
+/// padding from it
+pub fn apply_deadline(game: &mut Game, header_value: Option<&str>) {
+    if let Some(deadline) = header_value.and_then(parse_deadline) {
+        game.game.timeout = deadline;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start_of_game() -> Game {
+        serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap()
+    }
+
+    #[test]
+    fn test_parse_deadline() {
+        assert_eq!(parse_deadline("250"), Some(250));
+        assert_eq!(parse_deadline(" 250 "), Some(250));
+        assert_eq!(parse_deadline("0"), None);
+        assert_eq!(parse_deadline("-10"), None);
+        assert_eq!(parse_deadline("soon"), None);
+    }
+
+    #[test]
+    fn test_deadline_overrides_the_timeout() {
+        let mut game = start_of_game();
+        apply_deadline(&mut game, Some("320"));
+
+        assert_eq!(game.game.timeout, 320);
+    }
+
+    #[test]
+    fn test_missing_or_bad_deadline_keeps_the_timeout() {
+        let mut game = start_of_game();
+        apply_deadline(&mut game, None);
+        apply_deadline(&mut game, Some("soon"));
+
+        assert_eq!(game.game.timeout, 500);
+    }
+}
@@ -1,32 +1,30 @@
-use crate::a_prime::{APrimeCalculable, ClosestFoodCalculable};
+use crate::a_prime::{APrimeCalculable, APrimeOptions, ClosestFoodCalculable};
+use crate::board_backends::{fits_compact_boards, oversized_board_snake};
+use crate::feature_flags::minimax_options;
+use crate::food_spawns::FoodSpawns;
 use crate::*;
-use battlesnake_minimax::paranoid::MinimaxSnake;
+use battlesnake_minimax::paranoid::{MinimaxSnake, SnakeOptions};
 
 pub struct Factory;
 
 #[derive(Serialize, PartialEq, PartialOrd, Ord, Eq, Debug, Copy, Clone)]
+/// How Devin scores a board that isn't over yet
+///
+/// Wins, ties and losses are left to the search, which wraps this in a
+/// [battlesnake_minimax::paranoid::WrappedScore] the same way it does for every other minimax snake
 pub enum ScoreEndState {
-    /// depth: i64
-    Lose(i64),
-    /// depth: i64
-    Tie(i64),
     /// difference_in_snake_length, negative_distance_to_nearest_food, health
     ShorterThanOpponent(i64, Option<i32>, i64),
     /// negative_distance_to_opponent, difference_in_snake_length, health
     LongerThanOpponent(Option<i32>, i64, i64),
-    /// depth: i64
-    Win(i64),
 }
 
-impl ScoreEndState {
-    pub fn terminal_depth(&self) -> Option<i64> {
-        match &self {
-            ScoreEndState::Win(d) => Some(-d),
-            ScoreEndState::Tie(d) | ScoreEndState::Lose(d) => Some(*d),
-            _ => None,
-        }
-    }
-}
+/// Devin walks the same A* paths whether he's chasing food or an opponent, with a step over food
+/// or hazard costing one extra
+pub const A_PRIME_OPTIONS: APrimeOptions = APrimeOptions {
+    food_penalty: 1,
+    hazard_penalty: 1,
+};
 
 pub fn score<
     T: SnakeIDGettableGame
@@ -65,7 +63,9 @@ pub fn score<
     let my_health = node.get_health_i64(me_id);
 
     if max_opponent_length >= my_length || my_health < 20 {
-        let negative_closest_food_distance = node.dist_to_closest_food(&my_head, None).map(|x| -x);
+        let negative_closest_food_distance = node
+            .dist_to_closest_food(&my_head, &A_PRIME_OPTIONS)
+            .map(|x| -x);
 
         return ScoreEndState::ShorterThanOpponent(
             length_difference,
@@ -75,7 +75,7 @@ pub fn score<
     }
 
     let negative_distance_to_opponent = node
-        .shortest_distance(&my_head, &opponent_heads, None)
+        .shortest_distance(&my_head, &opponent_heads, &A_PRIME_OPTIONS)
         .map(|dist| -dist);
 
     ScoreEndState::LongerThanOpponent(
@@ -91,79 +91,62 @@ impl Factory {
     }
 
     pub fn create(&self, game: Game) -> BoxedSnake {
+        if !fits_compact_boards(&game) {
+            return oversized_board_snake(game);
+        }
+
         let game_info = game.game.clone();
         let turn = game.turn;
         let name = "devious-devin";
+        let spawns = FoodSpawns::new(build_snake_id_map(&game), game_info.clone());
+        // Food only might spawn above the minimum, and the median of a chance node never goes with
+        // a spawn that only might happen. So the spawns we search are the ones that have to happen,
+        // when the `food_spawns` feature flag turns them on
+        let options = SnakeOptions {
+            food_spawn_chance: 1.0,
+            ..minimax_options()
+        };
+
+        macro_rules! devin {
+            ($board:expr) => {
+                Box::new(
+                    MinimaxSnake::from_fn_with_options(
+                        $board, game_info, turn, &score, name, options,
+                    )
+                    .with_food_spawner(spawns.spawner()),
+                )
+            };
+        }
 
-        if game_info.ruleset.name == "wrapped" {
+        if crate::wire_game::is_wrapped(&game_info) {
             use battlesnake_game_types::compact_representation::wrapped::*;
 
             match ToBestCellBoard::to_best_cell_board(game).unwrap() {
-                BestCellBoard::Tiny(game) => {
-                    Box::new(MinimaxSnake::from_fn(*game, game_info, turn, &score, name))
-                }
-                BestCellBoard::SmallExact(game) => {
-                    Box::new(MinimaxSnake::from_fn(*game, game_info, turn, &score, name))
-                }
-                BestCellBoard::Standard(game) => {
-                    Box::new(MinimaxSnake::from_fn(*game, game_info, turn, &score, name))
-                }
-                BestCellBoard::MediumExact(game) => {
-                    Box::new(MinimaxSnake::from_fn(*game, game_info, turn, &score, name))
-                }
-                BestCellBoard::LargestU8(game) => {
-                    Box::new(MinimaxSnake::from_fn(*game, game_info, turn, &score, name))
-                }
-                BestCellBoard::LargeExact(game) => {
-                    Box::new(MinimaxSnake::from_fn(*game, game_info, turn, &score, name))
-                }
-                BestCellBoard::ArcadeMaze(game) => {
-                    Box::new(MinimaxSnake::from_fn(*game, game_info, turn, &score, name))
-                }
-                BestCellBoard::ArcadeMaze8Snake(game) => {
-                    Box::new(MinimaxSnake::from_fn(*game, game_info, turn, &score, name))
-                }
-                BestCellBoard::Large(game) => {
-                    Box::new(MinimaxSnake::from_fn(*game, game_info, turn, &score, name))
-                }
-                BestCellBoard::Silly(game) => {
-                    Box::new(MinimaxSnake::from_fn(*game, game_info, turn, &score, name))
-                }
+                BestCellBoard::Tiny(game) => devin!(*game),
+                BestCellBoard::SmallExact(game) => devin!(*game),
+                BestCellBoard::Standard(game) => devin!(*game),
+                BestCellBoard::MediumExact(game) => devin!(*game),
+                BestCellBoard::LargestU8(game) => devin!(*game),
+                BestCellBoard::LargeExact(game) => devin!(*game),
+                BestCellBoard::ArcadeMaze(game) => devin!(*game),
+                BestCellBoard::ArcadeMaze8Snake(game) => devin!(*game),
+                BestCellBoard::Large(game) => devin!(*game),
+                BestCellBoard::Silly(game) => devin!(*game),
             }
         } else {
             use battlesnake_game_types::compact_representation::standard::*;
 
             match ToBestCellBoard::to_best_cell_board(game).unwrap() {
-                BestCellBoard::Tiny(game) => {
-                    Box::new(MinimaxSnake::from_fn(*game, game_info, turn, &score, name))
-                }
-                BestCellBoard::SmallExact(game) => {
-                    Box::new(MinimaxSnake::from_fn(*game, game_info, turn, &score, name))
-                }
-                BestCellBoard::Standard(game) => {
-                    Box::new(MinimaxSnake::from_fn(*game, game_info, turn, &score, name))
-                }
-                BestCellBoard::MediumExact(game) => {
-                    Box::new(MinimaxSnake::from_fn(*game, game_info, turn, &score, name))
-                }
-                BestCellBoard::LargestU8(game) => {
-                    Box::new(MinimaxSnake::from_fn(*game, game_info, turn, &score, name))
-                }
-                BestCellBoard::LargeExact(game) => {
-                    Box::new(MinimaxSnake::from_fn(*game, game_info, turn, &score, name))
-                }
-                BestCellBoard::ArcadeMaze(game) => {
-                    Box::new(MinimaxSnake::from_fn(*game, game_info, turn, &score, name))
-                }
-                BestCellBoard::ArcadeMaze8Snake(game) => {
-                    Box::new(MinimaxSnake::from_fn(*game, game_info, turn, &score, name))
-                }
-                BestCellBoard::Large(game) => {
-                    Box::new(MinimaxSnake::from_fn(*game, game_info, turn, &score, name))
-                }
-                BestCellBoard::Silly(game) => {
-                    Box::new(MinimaxSnake::from_fn(*game, game_info, turn, &score, name))
-                }
+                BestCellBoard::Tiny(game) => devin!(*game),
+                BestCellBoard::SmallExact(game) => devin!(*game),
+                BestCellBoard::Standard(game) => devin!(*game),
+                BestCellBoard::MediumExact(game) => devin!(*game),
+                BestCellBoard::LargestU8(game) => devin!(*game),
+                BestCellBoard::LargeExact(game) => devin!(*game),
+                BestCellBoard::ArcadeMaze(game) => devin!(*game),
+                BestCellBoard::ArcadeMaze8Snake(game) => devin!(*game),
+                BestCellBoard::Large(game) => devin!(*game),
+                BestCellBoard::Silly(game) => devin!(*game),
             }
         }
     }
@@ -192,9 +175,95 @@ impl BattlesnakeFactory for Factory {
             head: Some("trans-rights-scarf".to_owned()),
             tail: Some("rbc-necktie".to_owned()),
             version: None,
+            ..Default::default()
         }
     }
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use battlesnake_game_types::{
+        compact_representation::{dimensions::Square, StandardCellBoard},
+        wire_representation::Position,
+    };
+    use battlesnake_minimax::{paranoid::WrappedScore, FeatureFlags};
+
+    use super::*;
+
+    type Board7x7 = StandardCellBoard<u8, Square, { 7 * 7 }, 4>;
+
+    /// Devin on the left wall with no food anywhere, and a longer opponent on the right wall
+    fn hungry_game() -> Game {
+        let mut game: Game =
+            serde_json::from_str(include_str!("../../fixtures/standard_7x7.json")).unwrap();
+        let column = |x, ys: &[i32]| ys.iter().map(|&y| Position { x, y }).collect();
+
+        game.board.food = vec![];
+        game.board.snakes[0].body = column(0, &[3, 2, 1]);
+        game.board.snakes[0].head = Position { x: 0, y: 3 };
+        game.board.snakes[0].health = 100;
+        game.board.snakes[1].body = column(6, &[3, 2, 1, 0]);
+        game.board.snakes[1].head = Position { x: 6, y: 3 };
+        game.you = game.board.snakes[0].clone();
+
+        game
+    }
+
+    fn root_scores(
+        game: Game,
+        with_spawns: bool,
+    ) -> (Option<Move>, Vec<(Move, WrappedScore<ScoreEndState>)>) {
+        let id_map = build_snake_id_map(&game);
+        let spawns = FoodSpawns::new(id_map.clone(), game.game.clone());
+        let game_info = game.game.clone();
+        let board = Board7x7::convert_from_game(game, &id_map).unwrap();
+        let options = SnakeOptions {
+            food_spawn_chance: 1.0,
+            features: FeatureFlags {
+                food_spawns: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let mut devin =
+            MinimaxSnake::from_fn_with_options(board, game_info, 0, &score, "devin", options);
+        if with_spawns {
+            devin = devin.with_food_spawner(spawns.spawner());
+        }
+
+        let result = devin.deepend_minimax_to_turn(2);
+        let scores = result
+            .first_options_for_snake(board.you_id())
+            .unwrap()
+            .iter()
+            .map(|(m, option)| (*m, *option.score()))
+            .collect();
+
+        (result.your_best_move(board.you_id()), scores)
+    }
+
+    fn score_of(
+        scores: &[(Move, WrappedScore<ScoreEndState>)],
+        m: Move,
+    ) -> WrappedScore<ScoreEndState> {
+        scores.iter().find(|(option, _)| *option == m).unwrap().1
+    }
+
+    #[test]
+    fn test_certain_food_spawns_change_the_chosen_move() {
+        // Without food Devin has nothing to head towards, so going up the wall is as good as
+        // heading into the middle of the board
+        let (_, without_spawns) = root_scores(hungry_game(), false);
+        assert_eq!(
+            score_of(&without_spawns, Move::Up),
+            score_of(&without_spawns, Move::Right)
+        );
+
+        // The board is below the minimum food, so some has to spawn at the end of the turn. From
+        // the middle of the board Devin is closer to wherever it lands
+        let (best_move, with_spawns) = root_scores(hungry_game(), true);
+        assert_eq!(best_move, Some(Move::Right));
+        assert!(score_of(&with_spawns, Move::Right) > score_of(&with_spawns, Move::Up));
+    }
+}
@@ -0,0 +1,191 @@
+//! Write search trees out as DOT files, so we can look at them with Graphviz
+//!
+//! This is meant to be safe to leave on in a deployed snake while we debug something live, so
+//! everything about it comes from the environment and there's a cap on how many files a process
+//! will ever write
+//!
+//! - `DOT_DUMP_DIR` is the directory the files go in, and defaults to `battlesnake-dot` in the
+//!   temp dir. It's created if it doesn't exist
+//! - `DOT_DUMP_INTERVAL` writes the tree every this many iterations, defaulting to 64
+//! - `DOT_DUMP_MAX_FILES` is the most files this process will write, defaulting to 32
+//! - `DOT_DUMP_GZIP` gzips the files when it's set
+//! - `DOT_DUMP_FINAL_ONLY` only writes the tree the search ended with when it's set
+
+use std::{
+    fs::{create_dir_all, File},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        OnceLock,
+    },
+};
+
+use color_eyre::eyre::Result;
+use dotavious::Dot;
+use flate2::{write::GzEncoder, Compression};
+use tracing::{info, warn};
+
+/// Where and how often to write trees, see the module docs
+#[derive(Debug, Clone)]
+pub struct DotDumpConfig {
+    pub dir: PathBuf,
+    pub interval: usize,
+    pub max_files: usize,
+    pub gzip: bool,
+    pub final_only: bool,
+}
+
+impl DotDumpConfig {
+    pub fn from_env() -> Self {
+        Self {
+            dir: std::env::var("DOT_DUMP_DIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| std::env::temp_dir().join("battlesnake-dot")),
+            interval: match std::env::var("DOT_DUMP_INTERVAL").map(|x| x.parse()) {
+                Ok(Ok(x)) if x > 0 => x,
+                _ => 64,
+            },
+            max_files: match std::env::var("DOT_DUMP_MAX_FILES").map(|x| x.parse()) {
+                Ok(Ok(x)) => x,
+                _ => 32,
+            },
+            gzip: std::env::var("DOT_DUMP_GZIP").is_ok(),
+            final_only: std::env::var("DOT_DUMP_FINAL_ONLY").is_ok(),
+        }
+    }
+}
+
+/// The files written by every [DotDump] in this process, so [DotDumpConfig::max_files] holds no
+/// matter how many searches are graphed
+static FILES_WRITTEN: AtomicUsize = AtomicUsize::new(0);
+
+/// Writes the trees for a single search
+#[derive(Debug)]
+pub struct DotDump {
+    config: &'static DotDumpConfig,
+    prefix: String,
+}
+
+impl DotDump {
+    /// Files are named after the game and turn, so dumps from different searches don't overwrite
+    /// each other
+    pub fn new(game_id: &str, turn: i32) -> Self {
+        static CONFIG: OnceLock<DotDumpConfig> = OnceLock::new();
+
+        Self::with_config(CONFIG.get_or_init(DotDumpConfig::from_env), game_id, turn)
+    }
+
+    pub fn with_config(config: &'static DotDumpConfig, game_id: &str, turn: i32) -> Self {
+        let game_id: String = game_id
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+
+        Self {
+            config,
+            prefix: format!("{game_id}_{turn}"),
+        }
+    }
+
+    /// Call this every iteration, it only builds the graph when it's time to write one
+    pub fn iteration(&self, iteration: usize, graph: impl FnOnce() -> Dot) {
+        if self.config.final_only || iteration == 0 || iteration % self.config.interval != 0 {
+            return;
+        }
+
+        self.dump(&format!("iteration_{iteration}"), graph);
+    }
+
+    /// Call this with the tree the search ended with
+    pub fn finish(&self, iterations: usize, graph: impl FnOnce() -> Dot) {
+        self.dump(&format!("final_{iterations}"), graph);
+    }
+
+    fn dump(&self, name: &str, graph: impl FnOnce() -> Dot) {
+        let reserved = FILES_WRITTEN
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |written| {
+                (written < self.config.max_files).then_some(written + 1)
+            })
+            .is_ok();
+        if !reserved {
+            return;
+        }
+
+        let extension = if self.config.gzip { "dot.gz" } else { "dot" };
+        let path = self
+            .config
+            .dir
+            .join(format!("{}_{name}.{extension}", self.prefix));
+
+        match self.write(&path, format!("{}", graph())) {
+            Ok(()) => info!(path = %path.display(), "Wrote a DOT file of the search tree"),
+            Err(e) => warn!(path = %path.display(), error = %e, "Couldn't write a DOT file"),
+        }
+    }
+
+    fn write(&self, path: &Path, contents: String) -> Result<()> {
+        create_dir_all(&self.config.dir)?;
+        let mut file = File::create(path)?;
+
+        if self.config.gzip {
+            let mut encoder = GzEncoder::new(file, Compression::default());
+            encoder.write_all(contents.as_bytes())?;
+            encoder.finish()?;
+        } else {
+            file.write_all(contents.as_bytes())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dotavious::GraphBuilder;
+
+    use super::*;
+
+    #[test]
+    fn test_writes_every_interval_and_the_final_tree_up_to_the_cap() {
+        let dir = std::env::temp_dir().join(format!("dot-dump-test-{}", std::process::id()));
+        let config = Box::leak(Box::new(DotDumpConfig {
+            dir: dir.clone(),
+            interval: 2,
+            max_files: 3,
+            gzip: true,
+            final_only: false,
+        }));
+        let dump = DotDump::with_config(config, "some/game", 7);
+        let graph = || Dot {
+            graph: GraphBuilder::new_named_directed("test").build().unwrap(),
+        };
+
+        for iteration in 0..=6 {
+            dump.iteration(iteration, graph);
+        }
+        dump.finish(6, graph);
+
+        let mut written: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        written.sort();
+        assert_eq!(
+            written,
+            vec![
+                "some_game_7_iteration_2.dot.gz",
+                "some_game_7_iteration_4.dot.gz",
+                "some_game_7_iteration_6.dot.gz",
+            ]
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
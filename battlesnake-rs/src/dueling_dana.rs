@@ -0,0 +1,429 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use battlesnake_game_types::compact_representation::{CellIndex, CellNum};
+use battlesnake_game_types::wire_representation::NestedGame;
+use battlesnake_minimax::{
+    board_hash::BoardHashable,
+    lazy_smp::{LazySmpSnake, Ponder, TranspositionTable},
+    paranoid::{move_ordering::MoveOrdering, SnakeOptions},
+    Instruments,
+};
+use decorum::N64;
+use tracing::info;
+
+use crate::a_prime::APrimeCalculable;
+use crate::board_backends::backend_for;
+use crate::board_only::{you_on_board, BoardOnlySnake};
+use crate::feature_flags::feature_flags;
+use crate::flood_fill::spread_from_head::{Grid, SpreadFromHead};
+use crate::flood_fill::spread_from_head_wrapped::SpreadFromHeadWrapped;
+use crate::game_store;
+use crate::hovering_hobbs::{
+    forced_move_extension, opponent_depth_reduction, root_strategy, standard_score, wrapped_score,
+    Score,
+};
+use crate::ponder_scheduler::{self, PonderPriority};
+use crate::*;
+
+/// Dana is our entry for the duel arenas
+///
+/// It runs the same iterative deepening search as [hovering_hobbs], with the [duel_score] and
+/// three things that only pay off with two snakes left, where the same positions come up over and
+/// over:
+///
+/// - Background searches that order their moves at random share a score cache with the main
+///   search
+/// - The cache is a [TranspositionTable] Dana keeps for the whole game, instead of starting over
+///   every turn
+/// - Once Dana has answered, it ponders the board it expects next turn into that table while the
+///   engine waits on the opponent. The [ponder_scheduler] shares that time out between every game
+///   we're in, and pauses it while any of them needs a real move
+///
+/// Anything that isn't a duel on a board we have a backend for is played by
+/// [hovering_hobbs::Factory], so Dana still moves sensibly if it ends up somewhere else. Keeping
+/// Dana separate means we can try things out in duels without touching Hobbs or Irene
+pub struct DuelingDanaFactory;
+
+fn is_duel(game: &Game) -> bool {
+    game.board.snakes.len() == 2
+}
+
+/// How much each point of length we have on the opponent is worth, compared to the share of the
+/// board we control
+const LENGTH_LEAD_WEIGHT: f64 = 0.05;
+
+/// Being further ahead than this doesn't win any more head-to-heads, so it isn't worth any more
+const MAX_LENGTH_LEAD: i64 = 3;
+
+/// How Dana scores a duel, which is Hobbs' [standard_score] plus a bonus for being longer than
+/// the opponent
+///
+/// With a single opponent every head-to-head is against the same snake, and the longer snake wins
+/// all of them. So in a duel length is worth more than Hobbs gives it
+pub fn duel_score<BoardType, CellType, const MAX_SNAKES: usize>(node: &BoardType) -> Score
+where
+    BoardType: SnakeIDGettableGame<SnakeIDType = SnakeId>
+        + YouDeterminableGame
+        + SpreadFromHead<CellType, MAX_SNAKES, GridType = Grid<BoardType>>
+        + PositionGettableGame<NativePositionType = CellIndex<CellType>>
+        + APrimeCalculable
+        + HeadGettableGame
+        + HazardQueryableGame
+        + HealthGettableGame
+        + LengthGettableGame
+        + FoodGettableGame
+        + SizeDeterminableGame
+        + SnakeBodyGettableGame
+        + MaxSnakes<MAX_SNAKES>,
+    CellType: CellNum,
+{
+    with_length_lead(node, standard_score::<_, _, MAX_SNAKES>(node))
+}
+
+/// [duel_score] for wrapped duels, built on [wrapped_score]
+pub fn wrapped_duel_score<BoardType, CellType, const MAX_SNAKES: usize>(node: &BoardType) -> Score
+where
+    BoardType: SnakeIDGettableGame<SnakeIDType = SnakeId>
+        + YouDeterminableGame
+        + SpreadFromHead<CellType, MAX_SNAKES, GridType = Grid<BoardType>>
+        + SpreadFromHeadWrapped<CellType, MAX_SNAKES, GridType = Grid<BoardType>>
+        + PositionGettableGame<NativePositionType = CellIndex<CellType>>
+        + APrimeCalculable
+        + HeadGettableGame
+        + HazardQueryableGame
+        + HealthGettableGame
+        + LengthGettableGame
+        + FoodGettableGame
+        + SizeDeterminableGame
+        + SnakeBodyGettableGame
+        + MaxSnakes<MAX_SNAKES>,
+    CellType: CellNum,
+{
+    with_length_lead(node, wrapped_score::<_, _, MAX_SNAKES>(node))
+}
+
+fn with_length_lead<BoardType>(node: &BoardType, score: Score) -> Score
+where
+    BoardType: SnakeIDGettableGame<SnakeIDType = SnakeId>
+        + YouDeterminableGame
+        + HealthGettableGame
+        + LengthGettableGame,
+{
+    let me = node.you_id();
+    let longest_opponent = node
+        .get_snake_ids()
+        .into_iter()
+        .filter(|sid| sid != me && node.is_alive(sid))
+        .map(|sid| node.get_length_i64(&sid))
+        .max();
+    let Some(longest_opponent) = longest_opponent else {
+        return score;
+    };
+    let lead =
+        (node.get_length_i64(me) - longest_opponent).clamp(-MAX_LENGTH_LEAD, MAX_LENGTH_LEAD);
+
+    score.nudged(N64::from(LENGTH_LEAD_WEIGHT * lead as f64))
+}
+
+/// The most boards we keep in a game's [TranspositionTable]. Most of what's in there is from
+/// turns that are long gone by the time it gets this big, so past this we start it over
+const MAX_TRANSPOSITION_TABLE_SIZE: usize = 200_000;
+
+/// What Dana keeps between the turns of a game
+struct DanaGame<BoardType> {
+    table: TranspositionTable<BoardType, Score>,
+}
+
+/// What Dana keeps in the [game_store] is kept as this
+const KEPT_AS: &str = "dueling-dana";
+
+/// Take what we kept from the last turn of this game
+fn take_game<BoardType: 'static>(game_id: &str) -> Option<DanaGame<BoardType>> {
+    game_store::take(KEPT_AS, game_id)
+}
+
+fn keep_game<BoardType: Send + Sync + 'static>(game_id: &str, game: DanaGame<BoardType>) {
+    game_store::keep(KEPT_AS, game_id, game);
+}
+
+/// How many games Dana is keeping a [TranspositionTable] for
+pub fn kept_games() -> usize {
+    game_store::games_kept_by(KEPT_AS)
+}
+
+/// Drop everything we kept for the game, and stop pondering it
+fn forget_game(game_id: &str) {
+    game_store::forget(KEPT_AS, game_id);
+    ponder_scheduler::cancel(game_id);
+}
+
+/// Turns we pondered the board for, since this process started
+static PONDERED_TURNS: AtomicU64 = AtomicU64::new(0);
+
+/// Pondered turns the engine then sent us the board we pondered for
+static PONDER_HITS: AtomicU64 = AtomicU64::new(0);
+
+/// How often the board Dana pondered turned out to be the one the engine sent, None until Dana
+/// has pondered a turn
+///
+/// A miss isn't wasted, the opponent's other replies lead to a lot of the same positions, but the
+/// hits are where pondering pays off
+pub fn ponder_hit_rate() -> Option<f64> {
+    let pondered = PONDERED_TURNS.load(Ordering::Relaxed);
+
+    (pondered > 0).then(|| PONDER_HITS.load(Ordering::Relaxed) as f64 / pondered as f64)
+}
+
+fn options() -> SnakeOptions {
+    // The Lazy SMP snake sets the move ordering for each of its searches itself
+    SnakeOptions {
+        network_latency_padding: Duration::from_millis(120),
+        move_ordering: MoveOrdering::BestFirst,
+        opponent_depth_reduction: opponent_depth_reduction(),
+        food_spawn_chance: 0.0,
+        forced_move_extension: forced_move_extension(),
+        root_strategy: root_strategy(),
+        simultaneous_root_depth: 1,
+        features: feature_flags(),
+    }
+}
+
+/// A turn of a duel for Dana to search, with the [TranspositionTable] and pondering kept across
+/// turns
+struct DanaSnake<BoardType: 'static, const N_SNAKES: usize> {
+    board: BoardType,
+    game_info: NestedGame,
+    turn: i32,
+    score_function: &'static (dyn Fn(&BoardType) -> Score + Send + Sync),
+    options: SnakeOptions,
+}
+
+impl<BoardType, const N_SNAKES: usize> BattlesnakeAI for DanaSnake<BoardType, N_SNAKES>
+where
+    BoardType: SnakeIDGettableGame
+        + YouDeterminableGame
+        + PositionGettableGame
+        + HeadGettableGame
+        + HealthGettableGame
+        + VictorDeterminableGame
+        + NeighborDeterminableGame
+        + NeckQueryableGame
+        + SnakeBodyGettableGame
+        + SimulableGame<Instruments, N_SNAKES>
+        + Clone
+        + Sync
+        + Copy
+        + FoodGettableGame
+        + BoardHashable
+        + Send
+        + Eq
+        + PartialEq
+        + Hash
+        + 'static,
+    BoardType::SnakeIDType: Copy + Send + Sync,
+{
+    fn end(&self) {
+        forget_game(&self.game_info.id);
+    }
+
+    fn make_move(&self) -> Result<MoveOutput> {
+        let game_id = &self.game_info.id;
+        let previous = take_game::<BoardType>(game_id);
+
+        let ponder_hit = ponder_scheduler::take::<Ponder<BoardType>>(game_id)
+            .map(|ponder| ponder.is_hit(&self.board));
+        if let Some(hit) = ponder_hit {
+            PONDERED_TURNS.fetch_add(1, Ordering::Relaxed);
+            if hit {
+                PONDER_HITS.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let table = previous.map(|previous| previous.table).unwrap_or_default();
+        if table.len() > MAX_TRANSPOSITION_TABLE_SIZE {
+            table.clear();
+        }
+        info!(
+            game_id = %game_id,
+            turn = self.turn,
+            ponder_hit,
+            transposition_table_size = table.len(),
+            "Starting Dana's search"
+        );
+
+        let snake = LazySmpSnake::with_transposition_table(
+            self.board,
+            self.game_info.clone(),
+            self.turn,
+            self.score_function,
+            "dueling-dana",
+            self.options.clone(),
+            table.clone(),
+        );
+
+        // A cooperative search is for hosts that freeze us between requests, so there's nothing
+        // to ponder on
+        let (m, ponder) = snake.choose_move_and_ponder(!self.options.features.cooperative_search);
+        keep_game(game_id, DanaGame { table });
+
+        // The next request comes in by the end of the opponent's timeout
+        if let Some(ponder) = ponder {
+            let until = Instant::now()
+                + Duration::from_millis(self.game_info.timeout.try_into().unwrap_or(0));
+            ponder_scheduler::schedule(
+                game_id,
+                PonderPriority::for_game(&self.game_info),
+                until,
+                ponder,
+            );
+        }
+
+        Ok(MoveOutput {
+            r#move: format!("{m}"),
+            shout: None,
+        })
+    }
+}
+
+impl BattlesnakeFactory for DuelingDanaFactory {
+    fn name(&self) -> String {
+        "dueling-dana".to_owned()
+    }
+
+    fn create_from_wire_game(&self, game: Game) -> BoxedSnake {
+        if !you_on_board(&game) {
+            forget_game(&game.game.id);
+            return Box::new(BoardOnlySnake::new(game));
+        }
+
+        let backend = match backend_for(&game) {
+            Some(backend) if is_duel(&game) => backend,
+            _ => {
+                forget_game(&game.game.id);
+                return hovering_hobbs::Factory.create_from_wire_game(game);
+            }
+        };
+
+        let game_info = game.game.clone();
+        let turn = game.turn;
+
+        let options = options();
+
+        if crate::wire_game::is_wrapped(&game_info) {
+            crate::with_backend!(WrappedCellBoard, game, backend, |board| {
+                Box::new(DanaSnake {
+                    board,
+                    game_info,
+                    turn,
+                    score_function: &wrapped_duel_score,
+                    options,
+                }) as BoxedSnake
+            })
+        } else {
+            crate::with_backend!(StandardCellBoard, game, backend, |board| {
+                Box::new(DanaSnake {
+                    board,
+                    game_info,
+                    turn,
+                    score_function: &duel_score,
+                    options,
+                }) as BoxedSnake
+            })
+        }
+    }
+
+    fn about(&self) -> AboutMe {
+        AboutMe {
+            author: Some("coreyja".to_owned()),
+            color: Some("#3b5998".to_owned()),
+            head: Some("beach-puffin-special".to_owned()),
+            tail: Some("beach-puffin-special".to_owned()),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use battlesnake_game_types::compact_representation::StandardCellBoard4Snakes11x11;
+
+    use super::*;
+
+    fn duel() -> Game {
+        serde_json::from_str(include_str!(
+            "../../fixtures/7a02e19b-f658-4639-8ace-ece46629a6ed_192.json"
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_duel_score_rewards_a_length_lead_up_to_the_cap() {
+        // We're 7 long here and the opponent is 29
+        let score_grown_by = |extra: usize| {
+            let mut game = duel();
+            let you = game
+                .board
+                .snakes
+                .iter_mut()
+                .find(|snake| snake.id == game.you.id)
+                .unwrap();
+            let tail = *you.body.back().unwrap();
+            you.body.extend(std::iter::repeat(tail).take(extra));
+            game.you = you.clone();
+
+            let id_map = build_snake_id_map(&game);
+            let board = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+
+            (
+                duel_score::<_, _, 4>(&board),
+                standard_score::<_, _, 4>(&board),
+            )
+        };
+        let nudge = |lead: f64| N64::from(LENGTH_LEAD_WEIGHT * lead);
+
+        let (duel, standard) = score_grown_by(0);
+        assert_eq!(duel, standard.nudged(nudge(-3.0)));
+
+        let (duel, standard) = score_grown_by(23);
+        assert_eq!(duel, standard.nudged(nudge(1.0)));
+
+        let (duel, standard) = score_grown_by(40);
+        assert_eq!(duel, standard.nudged(nudge(3.0)));
+    }
+
+    #[test]
+    fn test_dana_keeps_the_transposition_table_for_the_game() {
+        let mut game = duel();
+        game.game.id = "test-dana-keeps-the-transposition-table".to_owned();
+        game.game.timeout = 300;
+
+        let id_map = build_snake_id_map(&game);
+        let board =
+            StandardCellBoard4Snakes11x11::convert_from_game(game.clone(), &id_map).unwrap();
+        let dana = DanaSnake {
+            board,
+            game_info: game.game.clone(),
+            turn: game.turn,
+            score_function: &duel_score,
+            options: options(),
+        };
+
+        dana.make_move().unwrap();
+        let after_first_turn = games().lock().unwrap().contains_key(&game.game.id);
+        assert!(after_first_turn);
+
+        // Searching the same board again starts from everything the first search scored
+        dana.make_move().unwrap();
+        let kept = take_game::<StandardCellBoard4Snakes11x11>(&game.game.id).unwrap();
+        assert!(!kept.table.is_empty());
+
+        // The game is over once we're off the board, so there's nothing left to keep
+        keep_game(&game.game.id, kept);
+        game.board.snakes.retain(|snake| snake.id != game.you.id);
+        DuelingDanaFactory.create_from_wire_game(game.clone());
+        assert!(!games().lock().unwrap().contains_key(&game.game.id));
+    }
+}
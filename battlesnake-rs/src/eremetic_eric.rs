@@ -7,6 +7,13 @@ use crate::a_prime::{dist_between_new, APrimeCalculable, APrimeNextDirection, AP
 
 use super::*;
 
+/// Eric circles back to his tail and only eats when he has to, so food he isn't heading for is
+/// worth going a few cells out of the way to stay off
+pub const A_PRIME_OPTIONS: APrimeOptions = APrimeOptions {
+    food_penalty: 5,
+    hazard_penalty: 1,
+};
+
 pub struct EremeticEric<T> {
     pub game: T,
 }
@@ -40,9 +47,11 @@ where
         let body = self.game.get_snake_body_vec(self.game.you_id());
         let modified_board = {
             let mut b = self.game.clone();
-            let mut path_to_complete_circle =
-                self.game
-                    .shortest_path(&body[0], &[body.last().unwrap().clone()], None);
+            let mut path_to_complete_circle = self.game.shortest_path(
+                &body[0],
+                &[body.last().unwrap().clone()],
+                &A_PRIME_OPTIONS,
+            );
             path_to_complete_circle.reverse();
             for c in path_to_complete_circle.into_iter() {
                 if !body.contains(&c) {
@@ -98,7 +107,7 @@ where
 
                 let dist_back_from_food_to_tail = {
                     modified_board
-                        .shortest_distance(food, &[would_be_tail], None)
+                        .shortest_distance(food, &[would_be_tail], &A_PRIME_OPTIONS)
                         .unwrap_or(5000)
                 };
 
@@ -140,7 +149,7 @@ where
         if &you_head == closest_body_part && cant_survive_another_loop {
             let d = self
                 .game
-                .shortest_path_next_direction(&you_head, &[best_food.clone()], None)
+                .shortest_path_next_direction(&you_head, &[best_food.clone()], &A_PRIME_OPTIONS)
                 .unwrap();
 
             return Ok(MoveOutput {
@@ -157,7 +166,7 @@ where
                         .shortest_path_next_direction(
                             &you_head,
                             &self.game.get_all_food_as_native_positions(),
-                            None
+                            &A_PRIME_OPTIONS
                         )
                         .unwrap()
                 ),
@@ -170,10 +179,7 @@ where
             .shortest_path_next_direction(
                 &you_head,
                 &[self.game.get_snake_body_vec(you_id).last().unwrap().clone()],
-                Some(APrimeOptions {
-                    food_penalty: 1,
-                    ..Default::default()
-                }),
+                &A_PRIME_OPTIONS,
             )
             .unwrap();
 
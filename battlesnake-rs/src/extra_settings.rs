@@ -0,0 +1,206 @@
+//! The ruleset settings the wire types drop
+//!
+//! `Settings` from `battlesnake-game-types` only keeps the food and hazard settings, so the royale
+//! shrink interval and the squad rules are gone by the time a request is a [Game]. Every request
+//! body goes through [crate::request_tracing::parse_game], which reads them out with
+//! [ExtraSettings::from_request] and keeps them with [observe]. Anything that only has the
+//! [NestedGame] can then look them up with [for_game]
+
+use battlesnake_game_types::wire_representation::NestedGame;
+
+use crate::*;
+
+/// How many turns apart the royale hazards close in when the game doesn't say, which is what the
+/// engine defaults to
+pub const DEFAULT_SHRINK_EVERY_N_TURNS: i32 = 25;
+
+/// The settings of each game are kept in the [game_store] as this
+const KEPT_AS: &str = "extra-settings";
+
+/// The rules that let the snakes in a squad play as a team
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SquadSettings {
+    /// Snakes can move through the bodies of their squad
+    pub allow_body_collisions: bool,
+    /// Once a snake is eliminated, so is the rest of its squad
+    pub shared_elimination: bool,
+    /// Every snake in a squad has the health of the healthiest of them
+    pub shared_health: bool,
+    /// Every snake in a squad is as long as the longest of them
+    pub shared_length: bool,
+}
+
+/// The settings of a game that `Settings` doesn't have room for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExtraSettings {
+    /// How many turns apart the royale hazards close in, None when the game doesn't say
+    pub shrink_every_n_turns: Option<i32>,
+    /// None unless the game is played in squads
+    pub squad: Option<SquadSettings>,
+}
+
+#[derive(Deserialize)]
+struct Request {
+    game: RequestGame,
+}
+
+#[derive(Deserialize)]
+struct RequestGame {
+    id: String,
+    ruleset: RequestRuleset,
+}
+
+#[derive(Deserialize)]
+struct RequestRuleset {
+    #[serde(default)]
+    settings: Option<RequestSettings>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct RequestSettings {
+    royale: Option<RequestRoyale>,
+    squad: Option<SquadSettings>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RequestRoyale {
+    shrink_every_n_turns: Option<i32>,
+}
+
+impl ExtraSettings {
+    /// Read the settings out of a request body from the engine, along with the id of the game
+    /// they're for. None if the body isn't a game
+    pub fn from_request(body: &[u8]) -> Option<(String, Self)> {
+        let request: Request = serde_json::from_slice(body).ok()?;
+        let settings = request.game.ruleset.settings.unwrap_or_default();
+
+        Some((
+            request.game.id,
+            Self {
+                shrink_every_n_turns: settings
+                    .royale
+                    .and_then(|royale| royale.shrink_every_n_turns)
+                    .filter(|n| *n > 0),
+                squad: settings.squad,
+            },
+        ))
+    }
+
+    /// How many turns apart the royale hazards close in, going with the engine's default when the
+    /// game doesn't say
+    pub fn shrink_every_n_turns(&self) -> i32 {
+        self.shrink_every_n_turns
+            .unwrap_or(DEFAULT_SHRINK_EVERY_N_TURNS)
+    }
+}
+
+/// Keep the settings a request had for its game, for [for_game]
+pub fn observe(game_id: &str, settings: ExtraSettings) {
+    if settings == ExtraSettings::default() {
+        game_store::forget(KEPT_AS, game_id);
+    } else {
+        game_store::keep(KEPT_AS, game_id, settings);
+    }
+}
+
+/// The settings we last saw for the game, the defaults if it never had any
+pub fn for_game(game_info: &NestedGame) -> ExtraSettings {
+    game_store::copy_of(KEPT_AS, &game_info.id).unwrap_or_default()
+}
+
+/// How many games we're keeping [ExtraSettings] for
+pub fn observed_games() -> usize {
+    game_store::games_kept_by(KEPT_AS)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    /// The start of a game with the usual food and hazard settings, plus `extra` ones
+    fn request_with_settings(game_id: &str, extra: Value) -> Vec<u8> {
+        let mut request: Value =
+            serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap();
+        let mut settings = json!({
+            "foodSpawnChance": 15,
+            "minimumFood": 1,
+            "hazardDamagePerTurn": 14,
+            "hazardMap": null,
+            "hazardMapAuthor": null,
+            "royale": null,
+        });
+        for (key, value) in extra.as_object().unwrap() {
+            settings[key] = value.clone();
+        }
+        request["game"]["id"] = json!(game_id);
+        request["game"]["ruleset"]["settings"] = settings;
+
+        serde_json::to_vec(&request).unwrap()
+    }
+
+    #[test]
+    fn test_reads_the_royale_and_squad_settings() {
+        let body = request_with_settings(
+            "test-reads-the-royale-and-squad-settings",
+            json!({
+                "royale": { "shrinkEveryNTurns": 10 },
+                "squad": {
+                    "allowBodyCollisions": true,
+                    "sharedElimination": true,
+                    "sharedHealth": false,
+                    "sharedLength": true,
+                },
+            }),
+        );
+
+        let (game_id, settings) = ExtraSettings::from_request(&body).unwrap();
+        assert_eq!(game_id, "test-reads-the-royale-and-squad-settings");
+        assert_eq!(settings.shrink_every_n_turns, Some(10));
+        assert_eq!(
+            settings.squad,
+            Some(SquadSettings {
+                allow_body_collisions: true,
+                shared_elimination: true,
+                shared_health: false,
+                shared_length: true,
+            })
+        );
+
+        // The wire types still read the same request, they just leave these out
+        let game: Game = serde_json::from_slice(&body).unwrap();
+        assert_eq!(game.game.id, game_id);
+    }
+
+    #[test]
+    fn test_games_without_them_get_the_defaults() {
+        let body = request_with_settings("test-games-without-them-get-the-defaults", json!({}));
+
+        let (_, settings) = ExtraSettings::from_request(&body).unwrap();
+        assert_eq!(settings, ExtraSettings::default());
+        assert_eq!(
+            settings.shrink_every_n_turns(),
+            DEFAULT_SHRINK_EVERY_N_TURNS
+        );
+        assert_eq!(ExtraSettings::from_request(b"not a game"), None);
+    }
+
+    #[test]
+    fn test_parsing_a_request_keeps_its_settings_for_the_game() {
+        let body = request_with_settings(
+            "test-parsing-a-request-keeps-its-settings",
+            json!({ "royale": { "shrinkEveryNTurns": 5 } }),
+        );
+
+        let game = crate::request_tracing::parse_game(&body).unwrap();
+        assert_eq!(for_game(&game.game).shrink_every_n_turns(), 5);
+
+        // Seeing the game without them forgets them
+        observe(&game.game.id, ExtraSettings::default());
+        assert_eq!(for_game(&game.game), ExtraSettings::default());
+    }
+}
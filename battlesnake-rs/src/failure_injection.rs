@@ -0,0 +1,180 @@
+//! Failures we can inject into a request on purpose, to check that the HTTP layer degrades the way
+//! we expect instead of finding out during a tournament
+//!
+//! This is only for test deployments, so nothing here does anything unless `FAILURE_INJECTION` is
+//! set. With it set, a request can ask for a [Failure] with the [FAILURE_HEADER]. Each host applies
+//! it before its handlers see the request, [crate::request_handler::handle_request] for the hosts
+//! that share it and a middleware in web-axum
+
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::deadline::parse_deadline;
+
+/// The header a request asks for a [Failure] with, see [Failure::parse] for the values
+pub const FAILURE_HEADER: &str = "x-inject-failure";
+
+/// The shortest deadline a delay leaves us with, which is more than the network latency padding
+/// any of our snakes takes off it
+const MIN_REMAINING_DEADLINE_MS: i64 = 200;
+
+/// Should we listen to the [FAILURE_HEADER]
+pub fn failure_injection_enabled() -> bool {
+    std::env::var("FAILURE_INJECTION").is_ok()
+}
+
+/// Something going wrong with a request before it gets to us
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    /// The request takes this much longer to arrive, which comes out of our time to answer
+    Delay(Duration),
+    /// The body never arrives
+    DropBody,
+    /// Only the first half of the body arrives
+    MalformedBody,
+}
+
+impl Failure {
+    /// Parse the value of the [FAILURE_HEADER], which is one of `delay=<ms>`, `drop-body` or
+    /// `malformed-body`
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "drop-body" => Some(Self::DropBody),
+            "malformed-body" => Some(Self::MalformedBody),
+            other => other
+                .strip_prefix("delay=")
+                .and_then(|ms| ms.parse().ok())
+                .map(Duration::from_millis)
+                .map(Self::Delay),
+        }
+    }
+
+    /// The failure a request asked for, if it asked for one and injection is enabled
+    pub fn requested(header_value: Option<&str>) -> Option<Self> {
+        if !failure_injection_enabled() {
+            return None;
+        }
+
+        header_value.and_then(Self::parse)
+    }
+
+    /// How long to wait before handling the request
+    pub fn delay(&self) -> Duration {
+        match self {
+            Self::Delay(delay) => *delay,
+            Self::DropBody | Self::MalformedBody => Duration::ZERO,
+        }
+    }
+
+    /// The body as it arrives with this failure
+    pub fn apply_to_body<'body>(&self, body: &'body [u8]) -> &'body [u8] {
+        match self {
+            Self::Delay(_) => body,
+            Self::DropBody => &[],
+            Self::MalformedBody => &body[..body.len() / 2],
+        }
+    }
+
+    /// The value for the [crate::deadline::DEADLINE_HEADER] once this failure's delay has passed
+    ///
+    /// The time left is counted from the deadline header if there is one, and from the game's
+    /// timeout otherwise. None when neither is there to count from. A delay that eats the whole
+    /// deadline still leaves [MIN_REMAINING_DEADLINE_MS], so the snakes keep their padding and
+    /// answer as late as they would on a real slow request
+    pub fn remaining_deadline(&self, deadline: Option<&str>, body: &[u8]) -> Option<String> {
+        let deadline = deadline
+            .and_then(parse_deadline)
+            .or_else(|| timeout_from_body(body))?;
+        let delay: i64 = self.delay().as_millis().try_into().unwrap_or(i64::MAX);
+        let floor = deadline.min(MIN_REMAINING_DEADLINE_MS);
+
+        Some(deadline.saturating_sub(delay).max(floor).to_string())
+    }
+}
+
+#[derive(Deserialize)]
+struct TimeoutGame {
+    timeout: i64,
+}
+
+/// Only the part of the body we need for the timeout
+#[derive(Deserialize)]
+struct TimeoutBody {
+    game: TimeoutGame,
+}
+
+fn timeout_from_body(body: &[u8]) -> Option<i64> {
+    serde_json::from_slice::<TimeoutBody>(body)
+        .ok()
+        .map(|body| body.game.timeout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START_OF_GAME: &str = include_str!("../fixtures/start_of_game.json");
+
+    #[test]
+    fn test_parse_failure() {
+        assert_eq!(
+            Failure::parse("delay=120"),
+            Some(Failure::Delay(Duration::from_millis(120)))
+        );
+        assert_eq!(Failure::parse(" drop-body "), Some(Failure::DropBody));
+        assert_eq!(
+            Failure::parse("malformed-body"),
+            Some(Failure::MalformedBody)
+        );
+        assert_eq!(Failure::parse("delay=soon"), None);
+        assert_eq!(Failure::parse("explode"), None);
+    }
+
+    #[test]
+    fn test_body_failures() {
+        let body = START_OF_GAME.as_bytes();
+
+        assert_eq!(Failure::DropBody.apply_to_body(body), b"");
+        assert!(serde_json::from_slice::<serde_json::Value>(
+            Failure::MalformedBody.apply_to_body(body)
+        )
+        .is_err());
+        assert_eq!(
+            Failure::Delay(Duration::from_millis(5)).apply_to_body(body),
+            body
+        );
+    }
+
+    #[test]
+    fn test_delays_come_out_of_the_deadline() {
+        let delay = Failure::Delay(Duration::from_millis(120));
+        let body = START_OF_GAME.as_bytes();
+
+        assert_eq!(
+            delay.remaining_deadline(Some("300"), body).as_deref(),
+            Some("180")
+        );
+        // The fixture has a 500ms timeout
+        assert_eq!(delay.remaining_deadline(None, body).as_deref(), Some("380"));
+        assert_eq!(delay.remaining_deadline(None, b""), None);
+    }
+
+    #[test]
+    fn test_long_delays_leave_room_for_the_padding() {
+        let body = START_OF_GAME.as_bytes();
+
+        assert_eq!(
+            Failure::Delay(Duration::from_secs(1))
+                .remaining_deadline(Some("300"), body)
+                .as_deref(),
+            Some("200")
+        );
+        assert_eq!(
+            Failure::Delay(Duration::from_millis(100))
+                .remaining_deadline(Some("150"), body)
+                .as_deref(),
+            Some("150")
+        );
+    }
+}
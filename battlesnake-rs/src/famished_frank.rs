@@ -1,12 +1,31 @@
+use crate::{
+    a_prime::{APrimeNextDirection, APrimeOptions},
+    game_seed::{rng_for, turn_seed, RandomStream},
+};
 use battlesnake_game_types::types::*;
-use rand::thread_rng;
-
-use crate::a_prime::{APrimeNextDirection, APrimeOptions};
 
 use super::*;
 
+/// Frank is trying to grow, so walking over food costs nothing extra, but hazards are still best
+/// avoided
+pub const A_PRIME_OPTIONS: APrimeOptions = APrimeOptions {
+    food_penalty: 0,
+    hazard_penalty: 100,
+};
+
 pub struct FamishedFrank<T> {
     game: T,
+    /// The seed for this turn, which the random fallback move is drawn from
+    seed: u64,
+}
+
+impl FamishedFrank<Game> {
+    /// Frank only needs the wire game, so he can play on a board of any size
+    pub fn new(game: Game) -> Self {
+        let seed = turn_seed(&game);
+
+        Self { game, seed }
+    }
 }
 
 impl<T> BattlesnakeAI for FamishedFrank<T>
@@ -52,14 +71,9 @@ where
             .collect();
 
         let head = you_body.first().unwrap();
-        let dir = self.game.shortest_path_next_direction(
-            head,
-            &targets,
-            Some(APrimeOptions {
-                hazard_penalty: 100,
-                ..Default::default()
-            }),
-        );
+        let dir = self
+            .game
+            .shortest_path_next_direction(head, &targets, &A_PRIME_OPTIONS);
 
         let dir = if let Some(s) = dir {
             s
@@ -69,13 +83,10 @@ where
                 .shortest_path_next_direction(
                     head,
                     &[you_body.last().unwrap().clone()],
-                    Some(APrimeOptions {
-                        hazard_penalty: 100,
-                        ..Default::default()
-                    }),
+                    &A_PRIME_OPTIONS,
                 )
                 .unwrap_or_else(|| {
-                    let mut rng = thread_rng();
+                    let mut rng = rng_for(self.seed, RandomStream::Fallback);
                     let next_move = self
                         .game
                         .random_reasonable_move_for_each_snake(&mut rng)
@@ -101,7 +112,7 @@ impl BattlesnakeFactory for FamishedFrankFactory {
     }
 
     fn create_from_wire_game(&self, game: Game) -> BoxedSnake {
-        Box::new(FamishedFrank { game })
+        Box::new(FamishedFrank::new(game))
     }
     fn about(&self) -> AboutMe {
         AboutMe {
@@ -0,0 +1,155 @@
+use std::{path::Path, sync::OnceLock};
+
+use battlesnake_minimax::{paranoid::SnakeOptions, FeatureFlags};
+use color_eyre::eyre::{Context, Result};
+use tracing::warn;
+
+/// The flags as they are written in the TOML file, where any flag that is left out keeps its
+/// default
+///
+/// ```toml
+/// score_memo = true
+/// panic_mode = false
+/// ```
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct FeatureFlagsFile {
+    score_memo: Option<bool>,
+    panic_mode: Option<bool>,
+    parallel_search: Option<bool>,
+    leaf_breakdown: Option<bool>,
+    cooperative_search: Option<bool>,
+    verify_move: Option<bool>,
+    food_spawns: Option<bool>,
+}
+
+/// Build the flags from the contents of a TOML file, if there is one, and then the environment
+///
+/// Each flag can be overridden with a `FEATURE_` variable named after it, like
+/// `FEATURE_PANIC_MODE=false`. Variables that don't parse as a bool are ignored, but a file that
+/// isn't valid TOML or names a flag we don't have is an error
+fn load(file: Option<&str>, env: impl Fn(&str) -> Option<String>) -> Result<FeatureFlags> {
+    let from_file: FeatureFlagsFile = match file {
+        Some(contents) => toml::from_str(contents).wrap_err("Invalid feature flags file")?,
+        None => FeatureFlagsFile::default(),
+    };
+
+    let flag = |name: &str, from_file: Option<bool>, default: bool| match env(&format!(
+        "FEATURE_{}",
+        name.to_uppercase()
+    ))
+    .map(|x| x.parse())
+    {
+        Some(Ok(x)) => x,
+        _ => from_file.unwrap_or(default),
+    };
+
+    let defaults = FeatureFlags::default();
+    Ok(FeatureFlags {
+        score_memo: flag("score_memo", from_file.score_memo, defaults.score_memo),
+        panic_mode: flag("panic_mode", from_file.panic_mode, defaults.panic_mode),
+        parallel_search: flag(
+            "parallel_search",
+            from_file.parallel_search,
+            defaults.parallel_search,
+        ),
+        leaf_breakdown: flag(
+            "leaf_breakdown",
+            from_file.leaf_breakdown,
+            defaults.leaf_breakdown,
+        ),
+        cooperative_search: flag(
+            "cooperative_search",
+            from_file.cooperative_search,
+            defaults.cooperative_search,
+        ),
+        verify_move: flag("verify_move", from_file.verify_move, defaults.verify_move),
+        food_spawns: flag("food_spawns", from_file.food_spawns, defaults.food_spawns),
+    })
+}
+
+fn read_file(path: &Path) -> Result<String> {
+    std::fs::read_to_string(path)
+        .wrap_err_with(|| format!("Couldn't read the feature flags from {}", path.display()))
+}
+
+static FEATURE_FLAGS: OnceLock<FeatureFlags> = OnceLock::new();
+
+/// The experimental search features this deployment has turned on
+///
+/// These are loaded the first time they're asked for, from the TOML file at `FEATURE_FLAGS_PATH`
+/// if it's set and then the `FEATURE_` variables, and stay the same until the process restarts.
+/// Anything we can't read is logged and falls back to the defaults
+pub fn feature_flags() -> FeatureFlags {
+    *FEATURE_FLAGS.get_or_init(|| {
+        let file = std::env::var("FEATURE_FLAGS_PATH").ok().and_then(|path| {
+            match read_file(Path::new(&path)) {
+                Ok(contents) => Some(contents),
+                Err(e) => {
+                    warn!(error = ?e, "Ignoring the feature flags file");
+                    None
+                }
+            }
+        });
+
+        let env = |name: &str| std::env::var(name).ok();
+        load(file.as_deref(), env).unwrap_or_else(|e| {
+            warn!(error = ?e, "Ignoring the feature flags file");
+            load(None, env).expect("Loading without a file never fails")
+        })
+    })
+}
+
+/// The [SnakeOptions] for a minimax snake that doesn't tune any of them, the defaults with this
+/// deployment's [feature_flags]
+///
+/// [battlesnake_minimax::paranoid::MinimaxSnake::from_fn] only knows the default flags, so every
+/// snake goes through here, or sets [SnakeOptions::features] itself, for the flags to reach it
+pub fn minimax_options() -> SnakeOptions {
+    SnakeOptions {
+        features: feature_flags(),
+        ..SnakeOptions::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_env(_: &str) -> Option<String> {
+        None
+    }
+
+    #[test]
+    fn test_defaults_without_a_file_or_env() {
+        assert_eq!(load(None, no_env).unwrap(), FeatureFlags::default());
+    }
+
+    #[test]
+    fn test_env_overrides_the_file() {
+        let file = "panic_mode = false\nparallel_search = false\n";
+        let env = |name: &str| (name == "FEATURE_PARALLEL_SEARCH").then(|| "true".to_owned());
+
+        let flags = load(Some(file), env).unwrap();
+
+        assert!(flags.score_memo);
+        assert!(!flags.panic_mode);
+        assert!(flags.parallel_search);
+    }
+
+    #[test]
+    fn test_unknown_flags_in_the_file_are_an_error() {
+        assert!(load(Some("transposition_table = true"), no_env).is_err());
+    }
+
+    #[test]
+    fn test_minimax_options_carry_the_flags() {
+        let options = minimax_options();
+
+        assert_eq!(options.features, feature_flags());
+        assert_eq!(
+            options.network_latency_padding,
+            SnakeOptions::default().network_latency_padding
+        );
+    }
+}
@@ -1,3 +1,4 @@
 pub mod jump_flooding;
 pub mod spread_from_head;
 pub mod spread_from_head_arcade_maze;
+pub mod spread_from_head_wrapped;
@@ -4,13 +4,44 @@ use std::ops::Deref;
 use battlesnake_game_types::{
     compact_representation::{CellNum, *},
     types::{
-        FoodQueryableGame, HazardQueryableGame, HeadGettableGame, LengthGettableGame,
-        NeighborDeterminableGame, PositionGettableGame, SizeDeterminableGame,
+        FoodQueryableGame, HazardQueryableGame, HeadGettableGame, HealthGettableGame,
+        LengthGettableGame, NeighborDeterminableGame, PositionGettableGame, SizeDeterminableGame,
         SnakeBodyGettableGame, SnakeIDGettableGame, SnakeId,
     },
 };
 use tinyvec::TinyVec;
 
+/// How much health a snake loses for ending a turn in a hazard, on top of the one it loses every
+/// turn. The compact boards only know whether a cell is a hazard, so we assume the standard
+/// ruleset's damage
+pub const HAZARD_DAMAGE: i64 = 14;
+
+const MAX_HEALTH: i64 = 100;
+
+/// The health a snake has left after moving from a cell where it had `health` onto `pos`
+///
+/// Zero or less means it starves getting there, so the flood fill doesn't claim the cell
+pub(crate) fn health_after_step<BoardType>(
+    board: &BoardType,
+    pos: &BoardType::NativePositionType,
+    health: i64,
+) -> i64
+where
+    BoardType: PositionGettableGame + HazardQueryableGame + FoodQueryableGame + ?Sized,
+{
+    if board.is_food(pos) {
+        return MAX_HEALTH;
+    }
+
+    let damage = if board.is_hazard(pos) {
+        1 + HAZARD_DAMAGE
+    } else {
+        1
+    };
+
+    health - damage
+}
+
 pub struct Grid<BoardType>
 where
     BoardType: SnakeIDGettableGame + ?Sized,
@@ -29,13 +60,27 @@ pub struct Scores {
 pub trait SpreadFromHead<CellType, const MAX_SNAKES: usize> {
     type GridType;
 
+    /// Spread out from each snake's head, one cell further each cycle
+    ///
+    /// A snake only spreads as far as its health lets it. Each step costs one health plus
+    /// [HAZARD_DAMAGE] for hazards, and food fills it back up, so cells it would starve before
+    /// reaching aren't counted as its territory
     fn calculate(&self, number_of_cycles: usize) -> Self::GridType;
-    fn squares_per_snake(&self, number_of_cycles: usize) -> [u8; MAX_SNAKES];
+    /// How many cells each snake reaches first. This is a `u16` since one snake can have more
+    /// than 255 cells to itself on a 19x19 board
+    fn squares_per_snake(&self, number_of_cycles: usize) -> [u16; MAX_SNAKES];
     fn squares_per_snake_with_scores(
         &self,
         number_of_cycles: usize,
         scores: Scores,
     ) -> [u16; MAX_SNAKES];
+    /// The same as [SpreadFromHead::squares_per_snake_with_scores] but for a grid that was already
+    /// calculated, so callers that need the grid for something else don't flood fill twice
+    fn squares_per_snake_with_scores_for_grid(
+        &self,
+        grid: &Self::GridType,
+        scores: Scores,
+    ) -> [u16; MAX_SNAKES];
 }
 
 pub struct CellWrapper<CellType: CellNum>(pub(crate) CellIndex<CellType>);
@@ -65,6 +110,7 @@ where
         + LengthGettableGame
         + NeighborDeterminableGame
         + HeadGettableGame
+        + HealthGettableGame
         + SnakeBodyGettableGame,
     CellType: CellNum,
 {
@@ -82,7 +128,8 @@ where
             sids
         };
 
-        let mut todos: TinyVec<[CellWrapper<CellType>; 16]> = TinyVec::new();
+        // Each cell to spread from, along with the health the snake has left when it gets there
+        let mut todos: TinyVec<[(CellWrapper<CellType>, i64); 16]> = TinyVec::new();
         let mut todos_per_snake: [u8; MAX_SNAKES] = [0; MAX_SNAKES];
 
         for sid in &sorted_snake_ids {
@@ -93,7 +140,7 @@ where
 
         for sid in &sorted_snake_ids {
             let head = self.get_head_as_native_position(sid);
-            todos.push(CellWrapper(head));
+            todos.push((CellWrapper(head), self.get_health_i64(sid)));
             todos_per_snake[sid.as_usize()] += 1;
         }
 
@@ -110,12 +157,17 @@ where
             for sid in &sorted_snake_ids {
                 for _ in 0..todos_per_snake[sid.as_usize()] {
                     // Mark Neighbors
-                    let pos = todos_iter.next().unwrap();
+                    let (pos, health) = todos_iter.next().unwrap();
 
                     for neighbor in self.neighbors(&pos) {
-                        if grid.cells[neighbor.as_usize()].is_none() {
+                        if grid.cells[neighbor.as_usize()].is_some() {
+                            continue;
+                        }
+
+                        let health = health_after_step(self, &neighbor, health);
+                        if health > 0 {
                             grid.cells[neighbor.as_usize()] = Some(*sid);
-                            new_todos.push(CellWrapper(neighbor));
+                            new_todos.push((CellWrapper(neighbor), health));
                             new_todos_per_snake[sid.as_usize()] += 1;
                         }
                     }
@@ -129,7 +181,7 @@ where
         grid
     }
 
-    fn squares_per_snake(&self, number_of_cycles: usize) -> [u8; MAX_SNAKES] {
+    fn squares_per_snake(&self, number_of_cycles: usize) -> [u16; MAX_SNAKES] {
         let result = SpreadFromHead::<CellType, MAX_SNAKES>::calculate(self, number_of_cycles);
         let cell_sids = result.cells.iter().filter_map(|x| *x);
 
@@ -149,6 +201,16 @@ where
     ) -> [u16; MAX_SNAKES] {
         let grid = SpreadFromHead::<CellType, MAX_SNAKES>::calculate(self, number_of_cycles);
 
+        SpreadFromHead::<CellType, MAX_SNAKES>::squares_per_snake_with_scores_for_grid(
+            self, &grid, scores,
+        )
+    }
+
+    fn squares_per_snake_with_scores_for_grid(
+        &self,
+        grid: &Self::GridType,
+        scores: Scores,
+    ) -> [u16; MAX_SNAKES] {
         let sid_and_values = grid
             .cells
             .iter()
@@ -179,3 +241,125 @@ where
         total_values
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use battlesnake_game_types::{
+        compact_representation::dimensions::Square,
+        types::{build_snake_id_map, YouDeterminableGame},
+        wire_representation::{Game, Position},
+    };
+
+    use super::*;
+
+    fn board_19x19(game: Game) -> StandardCellBoard<u16, Square, { 19 * 19 }, 4> {
+        let id_map = build_snake_id_map(&game);
+
+        StandardCellBoard::convert_from_game(game, &id_map).unwrap()
+    }
+
+    /// The 7x7 fixture with no food, and our snake's health set to `health`
+    fn board_7x7_with_health(
+        health: i32,
+        setup: impl FnOnce(&mut Game),
+    ) -> StandardCellBoard<u8, Square, { 7 * 7 }, 4> {
+        let mut game: Game =
+            serde_json::from_str(include_str!("../../../fixtures/standard_7x7.json")).unwrap();
+        game.board.food.clear();
+        setup(&mut game);
+        game.you.health = health;
+        for snake in game.board.snakes.iter_mut() {
+            if snake.id == game.you.id {
+                snake.health = health;
+            }
+        }
+
+        let id_map = build_snake_id_map(&game);
+        StandardCellBoard::convert_from_game(game, &id_map).unwrap()
+    }
+
+    #[test]
+    fn test_low_health_snakes_have_less_territory() {
+        let healthy = board_7x7_with_health(90, |_| {});
+        let starving = board_7x7_with_health(2, |_| {});
+        let me = healthy.you_id().as_usize();
+
+        let healthy_squares = SpreadFromHead::<u8, 4>::squares_per_snake(&healthy, 20);
+        let starving_squares = SpreadFromHead::<u8, 4>::squares_per_snake(&starving, 20);
+
+        assert!(starving_squares[me] < healthy_squares[me]);
+
+        let scores = Scores {
+            food: 20,
+            hazard: 1,
+            empty: 5,
+        };
+        let healthy_score =
+            SpreadFromHead::<u8, 4>::squares_per_snake_with_scores(&healthy, 20, scores);
+        let starving_score =
+            SpreadFromHead::<u8, 4>::squares_per_snake_with_scores(&starving, 20, scores);
+        assert!(starving_score[me] < healthy_score[me]);
+    }
+
+    #[test]
+    fn test_hazards_cut_the_spread_short() {
+        let all_hazards = |game: &mut Game| {
+            game.board.hazards = (0..7)
+                .flat_map(|x| (0..7).map(move |y| Position { x, y }))
+                .collect();
+        };
+        let safe = board_7x7_with_health(20, |_| {});
+        let hazardous = board_7x7_with_health(20, all_hazards);
+        let me = safe.you_id().as_usize();
+
+        let safe_squares = SpreadFromHead::<u8, 4>::squares_per_snake(&safe, 20);
+        let hazardous_squares = SpreadFromHead::<u8, 4>::squares_per_snake(&hazardous, 20);
+
+        assert!(hazardous_squares[me] < safe_squares[me]);
+    }
+
+    #[test]
+    fn test_spreads_over_a_whole_7x7_board() {
+        let game: Game =
+            serde_json::from_str(include_str!("../../../fixtures/standard_7x7.json")).unwrap();
+        let id_map = build_snake_id_map(&game);
+        let board = StandardCellBoard::<u8, Square, { 7 * 7 }, 4>::convert_from_game(game, &id_map)
+            .unwrap();
+
+        let squares = SpreadFromHead::<u8, 4>::squares_per_snake(&board, 20);
+
+        // Nothing is walled off, so every cell ends up with someone
+        assert_eq!(squares.iter().sum::<u16>(), 7 * 7);
+    }
+
+    #[test]
+    fn test_spreads_over_a_whole_19x19_board() {
+        let board = board_19x19(
+            serde_json::from_str(include_str!("../../../fixtures/standard_19x19.json")).unwrap(),
+        );
+
+        let squares = SpreadFromHead::<u16, 4>::squares_per_snake(&board, 40);
+        assert_eq!(squares.iter().sum::<u16>(), 19 * 19);
+
+        // Each snake starts near a different corner, and the corners are indexed with the real
+        // width of the board
+        let grid = SpreadFromHead::<u16, 4>::calculate(&board, 40);
+        let owner = |x: usize, y: usize| grid.cells[y * 19 + x];
+        assert_eq!(owner(0, 0), Some(*board.you_id()));
+        assert_ne!(owner(18, 0), Some(*board.you_id()));
+        assert_ne!(owner(0, 18), Some(*board.you_id()));
+        assert_ne!(owner(18, 18), Some(*board.you_id()));
+    }
+
+    #[test]
+    fn test_one_snake_can_have_more_than_255_squares() {
+        let mut game: Game =
+            serde_json::from_str(include_str!("../../../fixtures/standard_19x19.json")).unwrap();
+        game.board.snakes.truncate(1);
+        let board = board_19x19(game);
+
+        let squares = SpreadFromHead::<u16, 4>::squares_per_snake(&board, 40);
+
+        assert_eq!(squares[0], 19 * 19);
+    }
+}
@@ -11,6 +11,7 @@ use battlesnake_game_types::{
 use tinyvec::TinyVec;
 
 pub use super::spread_from_head::*;
+use crate::maze_passages::MazePassages;
 
 pub trait SpreadFromHeadArcadeMaze<CellType, const MAX_SNAKES: usize> {
     type GridType;
@@ -62,6 +63,9 @@ where
             sids
         };
 
+        // Cross the passages ourselves, instead of counting on the board to wrap around its edges
+        let maze = MazePassages::for_board(self);
+
         let mut todos: TinyVec<[CellWrapper<CellType>; 16]> = TinyVec::new();
         let mut todos_per_snake: [u8; MAX_SNAKES] = [0; MAX_SNAKES];
 
@@ -92,7 +96,16 @@ where
                     // Mark Neighbors
                     let pos = todos_iter.next().unwrap();
 
-                    for neighbor in self.neighbors(&pos).filter(|p| !self.is_hazard(p)) {
+                    let through_passage = maze
+                        .as_ref()
+                        .and_then(|maze| maze.other_end(&self.position_from_native(pos.0)))
+                        .map(|other_end| self.native_from_position(other_end));
+
+                    for neighbor in self
+                        .neighbors(&pos)
+                        .filter(|p| !self.is_hazard(p))
+                        .chain(through_passage)
+                    {
                         if grid.cells[neighbor.as_usize()].is_none() {
                             grid.cells[neighbor.as_usize()] = Some(*sid);
                             new_todos.push(CellWrapper(neighbor));
@@ -131,3 +144,55 @@ where
         total_values
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use battlesnake_game_types::{
+        compact_representation::{dimensions::Custom, WrappedCellBoard},
+        types::{build_snake_id_map, YouDeterminableGame},
+        wire_representation::{Game, Position},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_wins_the_race_through_the_tunnel() {
+        let game: Game = serde_json::from_str(include_str!(
+            "../../../fixtures/arcade_maze_tunnel_race.json"
+        ))
+        .unwrap();
+        let id_map = build_snake_id_map(&game);
+        let board: WrappedCellBoard<u16, Custom, { 19 * 21 }, 4> =
+            game.as_wrapped_cell_board(&id_map).unwrap();
+
+        let grid = SpreadFromHeadArcadeMaze::<u16, 4>::calculate(&board, 8);
+        let owner = |x, y| grid.cells[board.native_from_position(Position { x, y }).as_usize()];
+
+        // The food is 5 moves away for us through the tunnel, and 6 for them around the walls
+        assert_eq!(owner(16, 11), Some(*board.you_id()));
+        assert_eq!(owner(18, 11), Some(*board.you_id()));
+    }
+
+    #[test]
+    fn test_claims_the_far_side_of_the_tunnel_in_a_real_game() {
+        // Turn 639 of a duel on arcade maze, with us at (2, 3) next to the tunnel at (1, 0) and
+        // them at (17, 19) near the top
+        let game: Game = serde_json::from_str(include_str!(
+            "../../../fixtures/arcade_maze_end_game_duels.json"
+        ))
+        .unwrap();
+        let id_map = build_snake_id_map(&game);
+        let board: WrappedCellBoard<u16, Custom, { 19 * 21 }, 4> =
+            game.as_wrapped_cell_board(&id_map).unwrap();
+
+        let grid = SpreadFromHeadArcadeMaze::<u16, 4>::calculate(&board, 20);
+        let owner = |x, y| grid.cells[board.native_from_position(Position { x, y }).as_usize()];
+
+        // Through the tunnel we get to the top left first, around the walls they would
+        assert_eq!(owner(1, 20), Some(*board.you_id()));
+        assert_eq!(owner(3, 19), Some(*board.you_id()));
+        assert_eq!(owner(4, 17), Some(*board.you_id()));
+        // The tunnel doesn't get us anywhere near them
+        assert_ne!(owner(9, 17), Some(*board.you_id()));
+    }
+}
@@ -0,0 +1,269 @@
+use std::cmp::Reverse;
+
+use battlesnake_game_types::{
+    compact_representation::{CellIndex, CellNum},
+    types::{
+        FoodQueryableGame, HazardQueryableGame, HeadGettableGame, HealthGettableGame,
+        LengthGettableGame, PositionGettableGame, SizeDeterminableGame, SnakeBodyGettableGame,
+        SnakeIDGettableGame, SnakeId,
+    },
+    wire_representation::Position,
+};
+use tinyvec::TinyVec;
+
+pub use super::spread_from_head::*;
+
+pub trait SpreadFromHeadWrapped<CellType, const MAX_SNAKES: usize> {
+    type GridType;
+
+    /// Spread out from each snake's head, where moving off one edge of the board brings you back
+    /// in on the opposite edge. Like [SpreadFromHead::calculate] a snake stops spreading where it
+    /// would starve
+    fn calculate_wrapped(&self, number_of_cycles: usize) -> Self::GridType;
+    fn squares_per_snake_wrapped(&self, number_of_cycles: usize) -> [u8; MAX_SNAKES];
+}
+
+/// The four cells next to `pos`, wrapping through the edges of a `width` x `height` board
+pub(crate) fn wrapped_neighbors(pos: Position, width: i32, height: i32) -> [Position; 4] {
+    [(0, 1), (0, -1), (-1, 0), (1, 0)].map(|(dx, dy)| Position {
+        x: (pos.x + dx).rem_euclid(width),
+        y: (pos.y + dy).rem_euclid(height),
+    })
+}
+
+impl<BoardType, CellType, const MAX_SNAKES: usize> SpreadFromHeadWrapped<CellType, MAX_SNAKES>
+    for BoardType
+where
+    BoardType: SnakeIDGettableGame<SnakeIDType = SnakeId>
+        + PositionGettableGame<NativePositionType = CellIndex<CellType>>
+        + SizeDeterminableGame
+        + LengthGettableGame
+        + HeadGettableGame
+        + HazardQueryableGame
+        + FoodQueryableGame
+        + HealthGettableGame
+        + SnakeBodyGettableGame,
+    CellType: CellNum,
+{
+    type GridType = Grid<BoardType>;
+
+    fn calculate_wrapped(&self, number_of_cycles: usize) -> Self::GridType {
+        let width = self.get_width() as i32;
+        let height = self.get_height() as i32;
+
+        let mut grid: Grid<BoardType> = Grid {
+            cells: vec![None; (width * height) as usize],
+        };
+
+        let sorted_snake_ids = {
+            let mut sids = self.get_snake_ids();
+            sids.sort_unstable_by_key(|sid| Reverse(self.get_length(sid)));
+
+            sids
+        };
+
+        let mut todos: TinyVec<[(CellWrapper<CellType>, i64); 16]> = TinyVec::new();
+        let mut todos_per_snake: [u8; MAX_SNAKES] = [0; MAX_SNAKES];
+
+        for sid in &sorted_snake_ids {
+            for pos in self.get_snake_body_iter(sid) {
+                grid.cells[pos.as_usize()] = Some(*sid);
+            }
+        }
+
+        for sid in &sorted_snake_ids {
+            let head = self.get_head_as_native_position(sid);
+            todos.push((CellWrapper(head), self.get_health_i64(sid)));
+            todos_per_snake[sid.as_usize()] += 1;
+        }
+
+        for _ in 0..number_of_cycles {
+            if todos.is_empty() {
+                break;
+            }
+
+            let mut new_todos = TinyVec::new();
+            let mut new_todos_per_snake = [0; MAX_SNAKES];
+
+            let mut todos_iter = todos.into_iter();
+
+            for sid in &sorted_snake_ids {
+                for _ in 0..todos_per_snake[sid.as_usize()] {
+                    // Mark Neighbors, including the ones on the other side of the board
+                    let (pos, health) = todos_iter.next().unwrap();
+                    let pos = self.position_from_native(*pos);
+
+                    for neighbor in wrapped_neighbors(pos, width, height) {
+                        let neighbor = self.native_from_position(neighbor);
+                        if grid.cells[neighbor.as_usize()].is_some() {
+                            continue;
+                        }
+
+                        let health = health_after_step(self, &neighbor, health);
+                        if health > 0 {
+                            grid.cells[neighbor.as_usize()] = Some(*sid);
+                            new_todos.push((CellWrapper(neighbor), health));
+                            new_todos_per_snake[sid.as_usize()] += 1;
+                        }
+                    }
+                }
+            }
+
+            todos = new_todos;
+            todos_per_snake = new_todos_per_snake;
+        }
+
+        grid
+    }
+
+    fn squares_per_snake_wrapped(&self, number_of_cycles: usize) -> [u8; MAX_SNAKES] {
+        let result = SpreadFromHeadWrapped::<CellType, MAX_SNAKES>::calculate_wrapped(
+            self,
+            number_of_cycles,
+        );
+        let cell_sids = result.cells.iter().filter_map(|x| *x);
+
+        let mut total_values = [0; MAX_SNAKES];
+
+        for sid in cell_sids {
+            total_values[sid.as_usize()] += 1;
+        }
+
+        total_values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use battlesnake_game_types::{
+        compact_representation::WrappedCellBoard4Snakes11x11,
+        types::{build_snake_id_map, YouDeterminableGame},
+        wire_representation::Game,
+    };
+
+    use super::*;
+
+    /// Build a wrapped board from the start of game fixture, with the snakes' heads moved to the
+    /// given positions. Each snake gets a different length so ties are always broken the same way
+    fn board_with_heads(heads: &[Position]) -> WrappedCellBoard4Snakes11x11 {
+        let mut game =
+            serde_json::from_str::<Game>(include_str!("../../fixtures/start_of_game.json"))
+                .unwrap();
+        game.game.ruleset.name = "wrapped".to_owned();
+        game.board.food = vec![];
+        game.board.snakes.truncate(heads.len());
+
+        for (i, (snake, head)) in game.board.snakes.iter_mut().zip(heads).enumerate() {
+            snake.head = *head;
+            snake.body = std::iter::repeat(*head).take(2 + i).collect();
+        }
+        game.you = game.board.snakes[0].clone();
+
+        let id_map = build_snake_id_map(&game);
+        WrappedCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap()
+    }
+
+    /// A deliberately simple version of the spread, that scans every cell on every round instead
+    /// of keeping a frontier
+    ///
+    /// A cell is claimed in a round by the longest snake that claimed one of its neighbors in the
+    /// round before
+    fn brute_force_counts(board: &WrappedCellBoard4Snakes11x11, rounds: usize) -> [u8; 4] {
+        let width = board.get_width() as i32;
+        let height = board.get_height() as i32;
+        let index = |p: Position| (p.y * width + p.x) as usize;
+
+        let mut owner: Vec<Option<SnakeId>> = vec![None; (width * height) as usize];
+        let mut claimed_in: Vec<Option<usize>> = vec![None; (width * height) as usize];
+
+        for sid in board.get_snake_ids() {
+            for pos in board.get_snake_body_iter(&sid) {
+                owner[index(board.position_from_native(pos))] = Some(sid);
+            }
+        }
+        for sid in board.get_snake_ids() {
+            let head = board.position_from_native(board.get_head_as_native_position(&sid));
+            claimed_in[index(head)] = Some(0);
+        }
+
+        for round in 1..=rounds {
+            for y in 0..height {
+                for x in 0..width {
+                    let pos = Position { x, y };
+                    if owner[index(pos)].is_some() {
+                        continue;
+                    }
+
+                    owner[index(pos)] = wrapped_neighbors(pos, width, height)
+                        .iter()
+                        .copied()
+                        .filter(|n| claimed_in[index(*n)] == Some(round - 1))
+                        .filter_map(|n| owner[index(n)])
+                        .max_by_key(|sid| board.get_length(sid));
+                    if owner[index(pos)].is_some() {
+                        claimed_in[index(pos)] = Some(round);
+                    }
+                }
+            }
+        }
+
+        let mut counts = [0; 4];
+        for sid in owner.into_iter().flatten() {
+            counts[sid.as_usize()] += 1;
+        }
+
+        counts
+    }
+
+    #[test]
+    fn test_spreads_through_the_corner() {
+        let board = board_with_heads(&[Position { x: 0, y: 0 }]);
+        let grid = board.calculate_wrapped(1);
+
+        for pos in [
+            Position { x: 1, y: 0 },
+            Position { x: 0, y: 1 },
+            Position { x: 10, y: 0 },
+            Position { x: 0, y: 10 },
+        ] {
+            let cell = board.native_from_position(pos);
+            assert_eq!(
+                grid.cells[cell.as_usize()],
+                Some(*board.you_id()),
+                "{pos:?}"
+            );
+        }
+        assert_eq!(board.squares_per_snake_wrapped(1)[0], 5);
+    }
+
+    #[test]
+    fn test_matches_brute_force_near_the_edges() {
+        let cases = [
+            vec![Position { x: 0, y: 5 }, Position { x: 10, y: 5 }],
+            vec![Position { x: 0, y: 0 }, Position { x: 10, y: 10 }],
+            vec![
+                Position { x: 5, y: 0 },
+                Position { x: 5, y: 10 },
+                Position { x: 0, y: 3 },
+            ],
+            vec![
+                Position { x: 1, y: 10 },
+                Position { x: 9, y: 0 },
+                Position { x: 10, y: 2 },
+                Position { x: 3, y: 1 },
+            ],
+        ];
+
+        for heads in cases {
+            let board = board_with_heads(&heads);
+
+            for rounds in [1, 2, 5, 8, 20] {
+                assert_eq!(
+                    board.squares_per_snake_wrapped(rounds),
+                    brute_force_counts(&board, rounds),
+                    "{heads:?} after {rounds} rounds"
+                );
+            }
+        }
+    }
+}
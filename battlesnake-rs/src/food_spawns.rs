@@ -0,0 +1,217 @@
+//! The food the ruleset has to spawn, for the minimax snakes that search through food spawns
+//!
+//! At the end of every turn the standard rules spawn food whenever there's less of it on the
+//! board than the ruleset's minimum, and otherwise only with the ruleset's spawn chance. A chance
+//! node in the search goes with the median outcome, so a spawn that only might happen never
+//! changes it. The spawns that have to happen do, since whichever empty cell the food lands on is
+//! a lot better than no food at all for a hungry snake. So those are the only ones we search, see
+//! [FoodSpawns]
+//!
+//! This is behind the `food_spawns` feature flag, since it costs a lot of depth
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use battlesnake_game_types::{
+    compact_representation::{
+        dimensions::Dimensions, CellNum, StandardCellBoard, WrappedCellBoard,
+    },
+    wire_representation::{NestedGame, Position},
+};
+use battlesnake_minimax::paranoid::FoodSpawner;
+use itertools::Itertools;
+
+use crate::{
+    wire_game::{is_constrictor, ToWireGame},
+    *,
+};
+
+/// The most places we search food spawning in at one chance node
+///
+/// Each one is a whole subtree, and searching one for every empty cell of an 11x11 board leaves
+/// no time to look past the first turn
+pub const MAX_SPAWN_CELLS: usize = 8;
+
+/// A board we can build from the wire game, which is all of the compact boards
+pub trait FromWireGame: Sized {
+    /// The board for this game, None if it doesn't fit on this kind of board
+    fn from_wire_game(game: Game, id_map: &HashMap<String, SnakeId>) -> Option<Self>;
+}
+
+impl<T: CellNum, D: Dimensions, const BOARD_SIZE: usize, const MAX_SNAKES: usize> FromWireGame
+    for StandardCellBoard<T, D, BOARD_SIZE, MAX_SNAKES>
+{
+    fn from_wire_game(game: Game, id_map: &HashMap<String, SnakeId>) -> Option<Self> {
+        Self::convert_from_game(game, id_map).ok()
+    }
+}
+
+impl<T: CellNum, D: Dimensions, const BOARD_SIZE: usize, const MAX_SNAKES: usize> FromWireGame
+    for WrappedCellBoard<T, D, BOARD_SIZE, MAX_SNAKES>
+{
+    fn from_wire_game(game: Game, id_map: &HashMap<String, SnakeId>) -> Option<Self> {
+        Self::convert_from_game(game, id_map).ok()
+    }
+}
+
+/// The least food the ruleset keeps on the board
+///
+/// Constrictor games never spawn any, and a request that doesn't say gets the standard minimum
+/// of 1
+pub fn minimum_food(game_info: &NestedGame) -> usize {
+    if is_constrictor(game_info) {
+        return 0;
+    }
+
+    game_info
+        .ruleset
+        .settings
+        .as_ref()
+        .map_or(Some(1), |settings| settings.minimum_food.try_into().ok())
+        .unwrap_or(0)
+}
+
+/// Spawns food on the boards of one game the way its ruleset has to
+///
+/// The compact boards don't know the ruleset or the string ids of the snakes, so this keeps both
+/// from the request to get back to the wire game, where we can place the food
+#[derive(Debug, Clone)]
+pub struct FoodSpawns {
+    id_map: HashMap<String, SnakeId>,
+    game_info: NestedGame,
+}
+
+impl FoodSpawns {
+    /// `id_map` has to be the map the boards were converted with
+    pub fn new(id_map: HashMap<String, SnakeId>, game_info: NestedGame) -> Self {
+        Self { id_map, game_info }
+    }
+
+    /// The boards that can come from the ruleset spawning food on `board`, for up to
+    /// [MAX_SPAWN_CELLS] of the empty cells
+    ///
+    /// This is empty unless the board has less than the [minimum_food]. When more than one piece
+    /// is missing, only the first is spawned. Food never spawns in the walls of the arcade maze,
+    /// or anywhere a snake is
+    ///
+    /// When there are more empty cells than that, we take every nth one in reading order so the
+    /// sample is spread across the board. It only depends on the board, so the search sees the
+    /// same spawns every time it gets back here
+    pub fn certain_spawns<B>(&self, board: &B) -> Vec<B>
+    where
+        B: ToWireGame + FromWireGame + FoodGettableGame + HealthGettableGame + YouDeterminableGame,
+    {
+        if board.get_all_food_as_native_positions().len() >= minimum_food(&self.game_info)
+            || !board.is_alive(board.you_id())
+        {
+            return vec![];
+        }
+
+        let wire = board.to_wire_game(&self.id_map, &self.game_info, 0);
+        let avoid_hazards = wire.is_arcade_maze_map();
+        let taken: HashSet<Position> = wire
+            .board
+            .snakes
+            .iter()
+            .flat_map(|snake| snake.body.iter().copied())
+            .chain(wire.board.food.iter().copied())
+            .chain(wire.board.hazards.iter().copied().filter(|_| avoid_hazards))
+            .collect();
+
+        let (width, height) = (wire.board.width as i32, wire.board.height as i32);
+        let empty = (0..height)
+            .flat_map(|y| (0..width).map(move |x| Position { x, y }))
+            .filter(|pos| !taken.contains(pos))
+            .collect_vec();
+        let every = empty.len().div_ceil(MAX_SPAWN_CELLS).max(1);
+
+        empty
+            .into_iter()
+            .step_by(every)
+            .filter_map(|pos| {
+                let mut spawned = wire.clone();
+                spawned.board.food.push(pos);
+
+                B::from_wire_game(spawned, &self.id_map)
+            })
+            .collect()
+    }
+
+    /// [FoodSpawns::certain_spawns] as a [FoodSpawner] for
+    /// [battlesnake_minimax::paranoid::MinimaxSnake::with_food_spawner]
+    pub fn spawner<B>(&self) -> FoodSpawner<B>
+    where
+        B: ToWireGame
+            + FromWireGame
+            + FoodGettableGame
+            + HealthGettableGame
+            + YouDeterminableGame
+            + 'static,
+    {
+        let spawns = self.clone();
+
+        Arc::new(move |board: &B| spawns.certain_spawns(board))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use battlesnake_game_types::compact_representation::{dimensions::Square, StandardCellBoard};
+
+    use super::*;
+
+    type Board7x7 = StandardCellBoard<u8, Square, { 7 * 7 }, 4>;
+
+    fn hungry_game() -> (Board7x7, FoodSpawns) {
+        let mut game: Game =
+            serde_json::from_str(include_str!("../../fixtures/standard_7x7.json")).unwrap();
+        game.board.food = vec![];
+        let id_map = build_snake_id_map(&game);
+        let spawns = FoodSpawns::new(id_map.clone(), game.game.clone());
+        let board = Board7x7::convert_from_game(game, &id_map).unwrap();
+
+        (board, spawns)
+    }
+
+    fn spawned_cells(spawned: &[Board7x7]) -> Vec<usize> {
+        spawned
+            .iter()
+            .map(|board| {
+                let food = board.get_all_food_as_native_positions();
+                assert_eq!(food.len(), 1);
+                food[0].as_usize()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_food_spawns_on_a_spread_of_empty_cells_when_the_board_has_none() {
+        let (board, spawns) = hungry_game();
+
+        let spawned = spawns.certain_spawns(&board);
+
+        // Both snakes are 3 long and the food is gone, which leaves 43 empty cells. Every 6th of
+        // them is 8 cells
+        assert_eq!(spawned.len(), MAX_SPAWN_CELLS);
+
+        let cells = spawned_cells(&spawned);
+        assert!(cells.iter().all_unique());
+        // The sample reaches from the bottom of the board to the top
+        let rows = cells.iter().map(|cell| cell / 7).collect_vec();
+        assert_eq!(rows.iter().min(), Some(&0));
+        assert!(*rows.iter().max().unwrap() >= 5, "{rows:?}");
+
+        // The same board always gets the same sample
+        assert_eq!(spawned_cells(&spawns.certain_spawns(&board)), cells);
+    }
+
+    #[test]
+    fn test_food_only_has_to_spawn_below_the_minimum() {
+        let (board, spawns) = hungry_game();
+        let spawned = spawns.certain_spawns(&board);
+
+        assert!(spawns.certain_spawns(&spawned[0]).is_empty());
+    }
+}
@@ -0,0 +1,98 @@
+//! One seed for each game that everything our snakes do at random is drawn from
+//!
+//! The seed only depends on the game id, so replaying an archived game locally draws the same
+//! rollouts, tie breaks and root noise that production did. The search still stops on a time
+//! budget, so to get the exact same move the replay has to run the same number of iterations too.
+//! The seed is recorded on every request span as `game_seed`, see
+//! [crate::request_tracing::request_span]
+
+use std::hash::{Hash, Hasher};
+
+use rand::{rngs::StdRng, SeedableRng};
+use rustc_hash::FxHasher;
+
+use crate::Game;
+
+/// The different things we draw random numbers for
+///
+/// Each one gets its own RNG, so drawing more numbers for one of them, like running more rollouts,
+/// doesn't change what the others draw
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RandomStream {
+    Rollouts,
+    TieBreaks,
+    RootNoise,
+    /// Moves picked at random when a snake has nothing better to go on
+    Fallback,
+}
+
+/// The seed for a game, which is a hash of its id
+pub fn game_seed(game_id: &str) -> u64 {
+    let mut hasher = FxHasher::default();
+    game_id.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The seed for our snake's turn in this game
+///
+/// The turn and the snake are mixed in, so we don't draw the same numbers every turn or for each
+/// of our snakes in the same game
+pub fn turn_seed(game: &Game) -> u64 {
+    let mut hasher = FxHasher::default();
+    game_seed(&game.game.id).hash(&mut hasher);
+    game.turn.hash(&mut hasher);
+    game.you.id.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The RNG for one kind of random draw this turn
+pub fn rng_for(turn_seed: u64, stream: RandomStream) -> StdRng {
+    let mut hasher = FxHasher::default();
+    turn_seed.hash(&mut hasher);
+    stream.hash(&mut hasher);
+    StdRng::seed_from_u64(hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+
+    fn start_of_game() -> Game {
+        serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap()
+    }
+
+    #[test]
+    fn test_the_same_turn_draws_the_same_numbers() {
+        let seed = turn_seed(&start_of_game());
+
+        let draws = |stream| {
+            let mut rng = rng_for(seed, stream);
+            (0..8).map(|_| rng.gen::<u64>()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(draws(RandomStream::Rollouts), draws(RandomStream::Rollouts));
+        assert_ne!(
+            draws(RandomStream::Rollouts),
+            draws(RandomStream::TieBreaks)
+        );
+    }
+
+    #[test]
+    fn test_each_turn_and_snake_gets_its_own_seed() {
+        let game = start_of_game();
+
+        let mut next_turn = game.clone();
+        next_turn.turn += 1;
+        assert_ne!(turn_seed(&game), turn_seed(&next_turn));
+
+        let mut other_snake = game.clone();
+        other_snake.you = game.board.snakes[1].clone();
+        assert_ne!(turn_seed(&game), turn_seed(&other_snake));
+
+        let mut other_game = game.clone();
+        other_game.game.id = "another-game".to_owned();
+        assert_ne!(turn_seed(&game), turn_seed(&other_game));
+    }
+}
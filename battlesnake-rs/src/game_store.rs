@@ -0,0 +1,278 @@
+//! Keeps things for each game our snakes are in, without letting them pile up
+//!
+//! [GameStore] is the capped store itself. The snakes that carry something from one turn of a
+//! game to the next, like Dana's transposition table or Irene's tree, all keep it in the one
+//! shared store behind [keep] and [take], so between them they never hold more than
+//! `MAX_GAMES_PER_STORE` games
+
+use std::{
+    any::Any,
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+/// Reads the most games any one [GameStore] keeps from `MAX_GAMES_PER_STORE`, defaulting to 64
+fn max_games_per_store() -> usize {
+    match std::env::var("MAX_GAMES_PER_STORE").map(|x| x.parse()) {
+        Ok(Ok(x)) if x > 0 => x,
+        _ => 64,
+    }
+}
+
+/// Every game evicted from a [GameStore] since this deployment started
+static EVICTED_GAMES: AtomicU64 = AtomicU64::new(0);
+
+pub fn evicted_games() -> u64 {
+    EVICTED_GAMES.load(Ordering::Relaxed)
+}
+
+/// Something we keep for each game one of our snakes is in, keyed by the snake's name and the
+/// game id
+///
+/// Games are normally dropped when the engine sends `/end`, or once they go stale. An engine that
+/// never sends `/end` and keeps starting games faster than they go stale would still grow the
+/// store forever, so it never holds more than `MAX_GAMES_PER_STORE` games. A new game past that
+/// evicts the game that was used least recently, which is the one least likely to still be going
+#[derive(Debug)]
+pub struct GameStore<V> {
+    games: HashMap<(String, String), (V, u64)>,
+    capacity: usize,
+    /// Goes up on every use, so the game with the lowest last use is the least recently used
+    uses: u64,
+}
+
+impl<V> Default for GameStore<V> {
+    fn default() -> Self {
+        Self::with_capacity(max_games_per_store())
+    }
+}
+
+impl<V> GameStore<V> {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            games: HashMap::new(),
+            capacity,
+            uses: 0,
+        }
+    }
+
+    fn key(snake_name: &str, game_id: &str) -> (String, String) {
+        (snake_name.to_owned(), game_id.to_owned())
+    }
+
+    fn next_use(&mut self) -> u64 {
+        self.uses += 1;
+        self.uses
+    }
+
+    pub fn len(&self) -> usize {
+        self.games.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.games.is_empty()
+    }
+
+    pub fn contains(&self, snake_name: &str, game_id: &str) -> bool {
+        self.games.contains_key(&Self::key(snake_name, game_id))
+    }
+
+    /// The game's value, without counting as a use
+    pub fn get(&self, snake_name: &str, game_id: &str) -> Option<&V> {
+        self.games
+            .get(&Self::key(snake_name, game_id))
+            .map(|(value, _)| value)
+    }
+
+    pub fn get_mut(&mut self, snake_name: &str, game_id: &str) -> Option<&mut V> {
+        let last_use = self.next_use();
+        let (value, used) = self.games.get_mut(&Self::key(snake_name, game_id))?;
+        *used = last_use;
+
+        Some(value)
+    }
+
+    /// Keep `value` for the game, evicting the least recently used game if the store is full
+    pub fn insert(&mut self, snake_name: &str, game_id: &str, value: V) {
+        let key = Self::key(snake_name, game_id);
+        if !self.games.contains_key(&key) && self.games.len() >= self.capacity {
+            let least_recent = self
+                .games
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| key.clone());
+            if let Some((snake, game_id)) = least_recent {
+                tracing::warn!(
+                    snake = %snake,
+                    game_id = %game_id,
+                    capacity = self.capacity,
+                    "Evicted a game we never saw end to make room for a new one"
+                );
+                self.games.remove(&(snake, game_id));
+                EVICTED_GAMES.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let last_use = self.next_use();
+        self.games.insert(key, (value, last_use));
+    }
+
+    pub fn remove(&mut self, snake_name: &str, game_id: &str) -> Option<V> {
+        self.games
+            .remove(&Self::key(snake_name, game_id))
+            .map(|(value, _)| value)
+    }
+
+    pub fn retain(&mut self, mut keep: impl FnMut(&V) -> bool) {
+        self.games.retain(|_, (value, _)| keep(value));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&(String, String), &V)> {
+        self.games.iter().map(|(key, (value, _))| (key, value))
+    }
+}
+
+/// Games we haven't had a request for in this long ended without telling us, so what the snakes
+/// kept for them is dropped even if the store isn't full
+const STALE_AFTER: Duration = Duration::from_secs(10 * 60);
+
+/// Something a snake kept for a game, along with when we last had a request for it
+///
+/// Each game is on whichever board fits it, so what's kept is stored as [Any]
+type Kept = (Box<dyn Any + Send>, Instant);
+
+fn kept() -> &'static Mutex<GameStore<Kept>> {
+    static KEPT: OnceLock<Mutex<GameStore<Kept>>> = OnceLock::new();
+
+    KEPT.get_or_init(Default::default)
+}
+
+/// Keep `value` for `owner` until the next turn of the game, replacing anything it already kept
+///
+/// `owner` is usually the snake's name, and keeps what different snakes keep for the same game
+/// apart
+pub fn keep<T: Send + 'static>(owner: &str, game_id: &str, value: T) {
+    let now = Instant::now();
+    let mut kept = kept().lock().unwrap();
+
+    kept.retain(|(_, last_used)| now.saturating_duration_since(*last_used) < STALE_AFTER);
+    kept.insert(owner, game_id, (Box::new(value), now));
+}
+
+/// Take back what `owner` kept for the game. None if it didn't keep anything, or what it kept was
+/// evicted or isn't a `T`
+pub fn take<T: 'static>(owner: &str, game_id: &str) -> Option<T> {
+    let (value, _) = kept().lock().unwrap().remove(owner, game_id)?;
+
+    value.downcast().ok().map(|value| *value)
+}
+
+/// A copy of what `owner` kept for the game, which stays kept. This counts as a use, so it's the
+/// last thing to be evicted
+pub fn copy_of<T: Clone + 'static>(owner: &str, game_id: &str) -> Option<T> {
+    let mut kept = kept().lock().unwrap();
+    let (value, last_used) = kept.get_mut(owner, game_id)?;
+    *last_used = Instant::now();
+
+    value.downcast_ref().cloned()
+}
+
+/// Drop whatever `owner` kept for the game
+pub fn forget(owner: &str, game_id: &str) {
+    kept().lock().unwrap().remove(owner, game_id);
+}
+
+/// How many games `owner` is keeping something for
+pub fn games_kept_by(owner: &str) -> usize {
+    kept()
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|((kept_by, _), _)| kept_by == owner)
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_a_full_store_evicts_the_least_recently_used_game() {
+        let mut store = GameStore::with_capacity(2);
+        store.insert("hobbs", "first", 1);
+        store.insert("hobbs", "second", 2);
+
+        // Using the first game makes the second the least recently used
+        *store.get_mut("hobbs", "first").unwrap() += 10;
+        store.insert("hobbs", "third", 3);
+
+        assert_eq!(store.len(), 2);
+        assert_eq!(store.get("hobbs", "first"), Some(&11));
+        assert_eq!(store.get("hobbs", "second"), None);
+        assert_eq!(store.get("hobbs", "third"), Some(&3));
+    }
+
+    #[test]
+    fn test_replacing_a_game_never_evicts() {
+        let mut store = GameStore::with_capacity(2);
+        store.insert("hobbs", "first", 1);
+        store.insert("devin", "first", 2);
+        store.insert("hobbs", "first", 3);
+
+        assert_eq!(store.len(), 2);
+        assert_eq!(store.get("hobbs", "first"), Some(&3));
+        assert_eq!(store.get("devin", "first"), Some(&2));
+    }
+
+    #[test]
+    fn test_get_does_not_count_as_a_use() {
+        let mut store = GameStore::with_capacity(2);
+        store.insert("hobbs", "first", 1);
+        store.insert("hobbs", "second", 2);
+
+        assert!(store.get("hobbs", "first").is_some());
+        store.insert("hobbs", "third", 3);
+
+        assert!(!store.contains("hobbs", "first"));
+        assert!(store.contains("hobbs", "second"));
+    }
+
+    #[test]
+    fn test_retain_drops_the_games_it_is_told_to() {
+        let mut store = GameStore::with_capacity(4);
+        for (game_id, value) in [("first", 1), ("second", 2), ("third", 3)] {
+            store.insert("hobbs", game_id, value);
+        }
+
+        store.retain(|value| value % 2 == 1);
+
+        assert_eq!(store.len(), 2);
+        assert!(store.contains("hobbs", "first"));
+        assert!(!store.contains("hobbs", "second"));
+        assert!(store.contains("hobbs", "third"));
+        assert_eq!(store.remove("hobbs", "third"), Some(3));
+        assert_eq!(store.len(), 1);
+    }
+
+    #[test]
+    fn test_snakes_keep_their_own_things_for_the_same_game() {
+        let game_id = "test-snakes-keep-their-own-things";
+
+        keep("dana", game_id, 1_u32);
+        keep("irene", game_id, "tree".to_owned());
+        assert_eq!(games_kept_by("dana"), 1);
+
+        assert_eq!(copy_of::<u32>("dana", game_id), Some(1));
+        // Asking for the wrong type gets nothing, and doesn't lose what was kept
+        assert_eq!(copy_of::<String>("dana", game_id), None);
+        assert_eq!(take::<u32>("dana", game_id), Some(1));
+        assert_eq!(take::<u32>("dana", game_id), None);
+
+        forget("irene", game_id);
+        assert_eq!(take::<String>("irene", game_id), None);
+    }
+}
@@ -0,0 +1,385 @@
+//! The MCTS tree [ImprobableIrene] keeps between the turns of a game
+//!
+//! A search grows its tree in an [Arena] that's dropped as soon as the move is answered, so the
+//! part worth keeping is copied out into a [GameTree] first. Once the next request tells us what
+//! everyone did, [GameTree::next_turn] re-roots the tree on the child for those moves and drops
+//! every other subtree, and the next search grows what's left back into its own arena instead of
+//! starting from a bare root
+//!
+//! [ImprobableIrene]: crate::improbable_irene::ImprobableIrene
+
+use std::{mem::size_of, sync::atomic::Ordering};
+
+use decorum::N64;
+use typed_arena::Arena;
+
+use crate::{
+    compensated_sum::CompensatedF64,
+    mcts::{Node, SomeonesMove},
+};
+
+use super::*;
+
+/// A [Node] without the arena, see [GameTree]
+#[derive(Debug)]
+struct TreeNode<BoardType> {
+    game_state: BoardType,
+    total_score: f64,
+    sum_of_square_scores: f64,
+    number_of_visits: usize,
+    prior: N64,
+    /// The move that led here from the parent, None for the root
+    snake_move: Option<SomeonesMove>,
+    /// None until the node was expanded
+    children: Option<Vec<usize>>,
+}
+
+impl<BoardType: Clone> TreeNode<BoardType> {
+    fn copied_from(node: &Node<'_, BoardType>) -> Self {
+        Self {
+            game_state: node.game_state.clone(),
+            total_score: node.total_score.load(Ordering::Relaxed),
+            sum_of_square_scores: node.sum_of_square_scores.load(Ordering::Relaxed),
+            number_of_visits: node.number_of_visits.load(Ordering::Relaxed),
+            prior: node.prior,
+            snake_move: node
+                .tree_context
+                .as_ref()
+                .map(|context| context.snake_move.clone()),
+            children: None,
+        }
+    }
+
+    /// Put the statistics we kept back into a freshly made `node`
+    fn restore<'arena>(&self, mut node: Node<'arena, BoardType>) -> Node<'arena, BoardType> {
+        node.total_score = CompensatedF64::new(self.total_score);
+        node.sum_of_square_scores = CompensatedF64::new(self.sum_of_square_scores);
+        node.number_of_visits = self.number_of_visits.into();
+        node.prior = self.prior;
+
+        node
+    }
+}
+
+/// What [GameTree::next_turn] let go of
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Reclaimed {
+    pub nodes: usize,
+    pub bytes: usize,
+}
+
+/// An MCTS tree that outlives the [Arena] it was searched in
+///
+/// The nodes are stored in a flat list, root first, with each one's children by their index.
+/// Re-rooting moves the subtree that's kept into a new list, so the tree is never any bigger
+/// than what can still come up from the current turn
+#[derive(Debug)]
+pub struct GameTree<BoardType> {
+    nodes: Vec<TreeNode<BoardType>>,
+    /// The move we answered with, until the tree moves on to the next turn
+    chosen: Option<Move>,
+}
+
+impl<BoardType: Clone> GameTree<BoardType> {
+    /// Copy the root of a search along with everything under `chosen`, the move we answered with,
+    /// since that's the only part of the tree the next turn can be in
+    ///
+    /// This runs before we answer, so a tree of more than `max_nodes` isn't copied at all. None if
+    /// it's too big, or `chosen` isn't one of the root's children
+    pub(crate) fn from_search(
+        root: &Node<'_, BoardType>,
+        chosen: Move,
+        max_nodes: usize,
+    ) -> Option<Self> {
+        let children = root.children.borrow();
+        let chosen_child = children.as_ref()?.iter().find(|child| {
+            child
+                .tree_context
+                .as_ref()
+                .map(|context| &context.snake_move)
+                == Some(&SomeonesMove::MyMove(chosen))
+        })?;
+
+        let mut nodes = vec![TreeNode::copied_from(root)];
+        let chosen_child = copy_subtree(chosen_child, &mut nodes, max_nodes)?;
+        nodes[0].children = Some(vec![chosen_child]);
+
+        Some(Self {
+            nodes,
+            chosen: Some(chosen),
+        })
+    }
+
+    /// How many nodes the tree has
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Roughly how much memory the tree holds on to
+    pub fn bytes(&self) -> usize {
+        self.nodes.capacity() * size_of::<TreeNode<BoardType>>()
+            + self
+                .nodes
+                .iter()
+                .flat_map(|node| &node.children)
+                .map(|children| children.capacity() * size_of::<usize>())
+                .sum::<usize>()
+    }
+
+    /// The board at the root, which the search grows the tree back from
+    pub fn root_state(&self) -> &BoardType {
+        &self.nodes[0].game_state
+    }
+
+    /// The move we answered with on the turn the tree was searched for, None once
+    /// [GameTree::next_turn] has moved it on
+    pub fn chosen(&self) -> Option<Move> {
+        self.chosen
+    }
+
+    fn children(&self, index: usize) -> impl Iterator<Item = usize> + '_ {
+        self.nodes[index].children.iter().flatten().copied()
+    }
+
+    /// Re-root the tree on the moves everyone made this turn, us included, and drop every other
+    /// subtree
+    ///
+    /// None if the search never got as far as expanding those moves, in which case the tree is
+    /// left the way it was
+    pub fn next_turn(&mut self, observed: &Action<4>) -> Option<Reclaimed> {
+        let my_move = SomeonesMove::MyMove(observed.own_move());
+        let other_moves = SomeonesMove::OtherMoves(observed.clone());
+
+        let mine = self
+            .children(0)
+            .find(|child| self.nodes[*child].snake_move.as_ref() == Some(&my_move))?;
+        let theirs = self
+            .children(mine)
+            .find(|child| self.nodes[*child].snake_move.as_ref() == Some(&other_moves))?;
+
+        let nodes_before = self.node_count();
+        let bytes_before = self.bytes();
+
+        let mut old: Vec<Option<TreeNode<BoardType>>> = std::mem::take(&mut self.nodes)
+            .into_iter()
+            .map(Some)
+            .collect();
+        let mut nodes = vec![];
+        move_subtree(&mut old, theirs, &mut nodes);
+        drop(old);
+
+        nodes[0].snake_move = None;
+        nodes.shrink_to_fit();
+        self.nodes = nodes;
+        self.chosen = None;
+
+        Some(Reclaimed {
+            nodes: nodes_before - self.node_count(),
+            bytes: bytes_before.saturating_sub(self.bytes()),
+        })
+    }
+
+    /// Grow the tree back into `arena`, statistics and all, for a search to carry on from
+    pub(crate) fn grow_into<'arena>(
+        &self,
+        arena: &'arena Arena<Node<'arena, BoardType>>,
+    ) -> &'arena Node<'arena, BoardType> {
+        let kept = &self.nodes[0];
+        let root: &'arena Node<'arena, BoardType> =
+            arena.alloc(kept.restore(Node::new(kept.game_state.clone())));
+        self.grow_children(0, root, arena);
+
+        root
+    }
+
+    fn grow_children<'arena>(
+        &self,
+        index: usize,
+        node: &'arena Node<'arena, BoardType>,
+        arena: &'arena Arena<Node<'arena, BoardType>>,
+    ) {
+        let Some(children) = &self.nodes[index].children else {
+            return;
+        };
+
+        let grown = children
+            .iter()
+            .map(|child| {
+                let kept = &self.nodes[*child];
+                let snake_move = kept
+                    .snake_move
+                    .clone()
+                    .expect("Only the root doesn't have a move");
+                let grown: &'arena Node<'arena, BoardType> = arena.alloc(kept.restore(
+                    Node::new_with_parent(kept.game_state.clone(), node, snake_move),
+                ));
+                self.grow_children(*child, grown, arena);
+
+                grown
+            })
+            .collect();
+
+        node.children.replace(Some(grown));
+    }
+}
+
+/// Copy `node` and everything under it onto the end of `nodes`, returning its index. None once
+/// there would be more than `max_nodes`
+fn copy_subtree<BoardType: Clone>(
+    node: &Node<'_, BoardType>,
+    nodes: &mut Vec<TreeNode<BoardType>>,
+    max_nodes: usize,
+) -> Option<usize> {
+    if nodes.len() >= max_nodes {
+        return None;
+    }
+
+    let index = nodes.len();
+    nodes.push(TreeNode::copied_from(node));
+
+    let children = match node.children.borrow().as_ref() {
+        Some(children) => Some(
+            children
+                .iter()
+                .map(|child| copy_subtree(child, nodes, max_nodes))
+                .collect::<Option<Vec<_>>>()?,
+        ),
+        None => None,
+    };
+    nodes[index].children = children;
+
+    Some(index)
+}
+
+/// Move the node at `index` and everything under it out of `old` onto the end of `nodes`,
+/// returning its new index
+fn move_subtree<BoardType>(
+    old: &mut [Option<TreeNode<BoardType>>],
+    index: usize,
+    nodes: &mut Vec<TreeNode<BoardType>>,
+) -> usize {
+    let mut node = old[index].take().expect("Every node only has one parent");
+    let children = node.children.take();
+
+    let new_index = nodes.len();
+    nodes.push(node);
+    nodes[new_index].children = children.map(|children| {
+        children
+            .into_iter()
+            .map(|child| move_subtree(old, child, nodes))
+            .collect()
+    });
+
+    new_index
+}
+
+#[cfg(test)]
+mod tests {
+    use battlesnake_game_types::compact_representation::standard::CellBoard4Snakes11x11;
+
+    use crate::improbable_irene::ImprobableIrene;
+
+    use super::*;
+
+    /// The tree after a fixed search of the start of a game, kept for the move it would play
+    fn searched_tree() -> GameTree<CellBoard4Snakes11x11> {
+        let game: Game =
+            serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap();
+        let game_info = game.game.clone();
+        let id_map = build_snake_id_map(&game);
+        let board = CellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+
+        let snake = ImprobableIrene::new(board, game_info, 0).with_seed(7);
+        let mut arena = Arena::new();
+        let root = snake.mcts_bench(3_000, &mut arena);
+        let chosen = root
+            .highest_average_score_child()
+            .and_then(|child| child.tree_context.as_ref())
+            .unwrap()
+            .snake_move
+            .my_move();
+
+        GameTree::from_search(root, chosen, usize::MAX).unwrap()
+    }
+
+    /// How many nodes are in the subtree under `index`, itself included
+    fn subtree_len<BoardType>(tree: &GameTree<BoardType>, index: usize) -> usize {
+        1 + tree.nodes[index]
+            .children
+            .iter()
+            .flatten()
+            .map(|child| subtree_len(tree, *child))
+            .sum::<usize>()
+    }
+
+    /// The most visited of a node's children
+    fn most_visited<BoardType>(tree: &GameTree<BoardType>, index: usize) -> Option<usize> {
+        tree.nodes[index]
+            .children
+            .iter()
+            .flatten()
+            .copied()
+            .max_by_key(|child| tree.nodes[*child].number_of_visits)
+    }
+
+    #[test]
+    fn test_only_the_chosen_move_is_kept() {
+        let tree = searched_tree();
+
+        assert_eq!(tree.nodes[0].snake_move, None);
+        assert_eq!(tree.children(0).count(), 1);
+        assert!(tree.chosen().is_some());
+        assert_eq!(subtree_len(&tree, 0), tree.node_count());
+    }
+
+    #[test]
+    fn test_next_turn_keeps_only_the_subtree_for_the_observed_moves() {
+        let mut tree = searched_tree();
+        let mut turns = 0;
+
+        // Follow the line the search looked at most for as long as it was expanded
+        while let Some(theirs) = most_visited(&tree, 0).and_then(|mine| most_visited(&tree, mine)) {
+            let Some(SomeonesMove::OtherMoves(observed)) = tree.nodes[theirs].snake_move.clone()
+            else {
+                panic!("Our moves are always followed by everyone's moves");
+            };
+            let before = tree.node_count();
+            let kept = subtree_len(&tree, theirs);
+
+            let reclaimed = tree.next_turn(&observed).unwrap();
+            assert_eq!(tree.node_count(), kept);
+            assert_eq!(reclaimed.nodes, before - kept);
+            assert_eq!(tree.nodes[0].snake_move, None);
+            assert_eq!(subtree_len(&tree, 0), tree.node_count());
+
+            turns += 1;
+        }
+
+        assert!(turns > 1);
+    }
+
+    #[test]
+    fn test_next_turn_leaves_the_tree_alone_for_moves_it_never_searched() {
+        let mut tree = searched_tree();
+        let before = tree.node_count();
+
+        let mut moves = [None; 4];
+        moves[0] = Some(tree.chosen().unwrap());
+        assert_eq!(tree.next_turn(&Action::new(moves)), None);
+        assert_eq!(tree.node_count(), before);
+    }
+
+    #[test]
+    fn test_grown_trees_keep_their_statistics() {
+        let tree = searched_tree();
+        let arena = Arena::new();
+        let root = tree.grow_into(&arena);
+
+        assert_eq!(
+            root.number_of_visits.load(Ordering::Relaxed),
+            tree.nodes[0].number_of_visits
+        );
+        assert_eq!(root.audit_statistics(), Vec::<String>::new());
+        assert_eq!(arena.len(), tree.node_count());
+    }
+}
@@ -0,0 +1,392 @@
+use std::cmp::Reverse;
+
+use battlesnake_game_types::wire_representation::BattleSnake;
+
+use crate::*;
+
+/// What happens when two snakes make their moves this turn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeadToHead {
+    /// Their heads don't end up on the same cell, or one of them left the board
+    NoCollision,
+    FirstWins,
+    SecondWins,
+    /// They were the same length, so they both die
+    BothDie,
+}
+
+fn move_offset(m: Move) -> (i32, i32) {
+    match m {
+        Move::Up => (0, 1),
+        Move::Down => (0, -1),
+        Move::Left => (-1, 0),
+        Move::Right => (1, 0),
+    }
+}
+
+/// Where a snake's head ends up after making the given move, or None if it ran into a wall
+pub fn next_head(game: &Game, head: Position, m: Move) -> Option<Position> {
+    let (dx, dy) = move_offset(m);
+    let (x, y) = (head.x + dx, head.y + dy);
+    let width = game.board.width as i32;
+    let height = game.board.height as i32;
+
+    if crate::wire_game::is_wrapped(&game.game) {
+        Some(Position {
+            x: x.rem_euclid(width),
+            y: y.rem_euclid(height),
+        })
+    } else if (0..width).contains(&x) && (0..height).contains(&y) {
+        Some(Position { x, y })
+    } else {
+        None
+    }
+}
+
+/// How long the snake is once it has moved its head to `pos`, which is what the head-to-head is
+/// decided on
+///
+/// The engine feeds snakes before it checks for collisions, so eating the food at `pos` counts. A
+/// tail that is still stacked from eating last turn is already part of the body, so it counts too
+pub fn length_after_move(game: &Game, snake: &BattleSnake, pos: Position) -> usize {
+    let ate = game.board.food.contains(&pos);
+
+    snake.body.len() + usize::from(ate)
+}
+
+/// Who wins if snake `a` makes `move_a` and snake `b` makes `move_b` at the same time
+pub fn head_to_head_outcome(
+    game: &Game,
+    a: &BattleSnake,
+    b: &BattleSnake,
+    move_a: Move,
+    move_b: Move,
+) -> HeadToHead {
+    let (head_a, head_b) = match (
+        next_head(game, a.head, move_a),
+        next_head(game, b.head, move_b),
+    ) {
+        (Some(head_a), Some(head_b)) if head_a == head_b => (head_a, head_b),
+        _ => return HeadToHead::NoCollision,
+    };
+
+    let length_a = length_after_move(game, a, head_a);
+    let length_b = length_after_move(game, b, head_b);
+
+    match length_a.cmp(&length_b) {
+        std::cmp::Ordering::Greater => HeadToHead::FirstWins,
+        std::cmp::Ordering::Less => HeadToHead::SecondWins,
+        std::cmp::Ordering::Equal => HeadToHead::BothDie,
+    }
+}
+
+/// How willing we are to take a head-to-head with a snake the same length as us, which kills both
+/// of us
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EqualTradeRisk {
+    /// Count the trade as a loss, we're doing well enough that we'd rather keep playing
+    Avoid,
+    /// Count the trade as surviving, since we're behind and taking an opponent with us is the best
+    /// we can hope for
+    Accept,
+}
+
+/// Where we stand against the other snakes, which decides the [EqualTradeRisk]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Standing {
+    /// How much longer we are than the longest opponent, negative when we're shorter
+    pub length_lead: i32,
+    /// The cells we reach before any other snake, compared to an even split between the snakes
+    /// left. 1 is an even split and below 1 is less than our share
+    pub space_share: f64,
+}
+
+impl Standing {
+    pub fn of(game: &Game, me: &BattleSnake) -> Self {
+        let longest_opponent = game
+            .board
+            .snakes
+            .iter()
+            .filter(|s| s.id != me.id)
+            .map(|s| s.body.len())
+            .max()
+            .unwrap_or_default();
+
+        Self {
+            length_lead: me.body.len() as i32 - longest_opponent as i32,
+            space_share: space_share(game, me),
+        }
+    }
+}
+
+/// Spread out from every head at once, one cell per turn, and compare the cells `me` gets to first
+/// with an even split. Longer snakes claim the cells they reach at the same time as a shorter one,
+/// since they'd win the head-to-head for it
+fn space_share(game: &Game, me: &BattleSnake) -> f64 {
+    let width = game.board.width as usize;
+    let index = |p: Position| p.y as usize * width + p.x as usize;
+
+    let mut claimed = vec![false; width * game.board.height as usize];
+    for pos in game.board.snakes.iter().flat_map(|s| s.body.iter()) {
+        claimed[index(*pos)] = true;
+    }
+
+    let mut snakes: Vec<&BattleSnake> = game.board.snakes.iter().collect();
+    snakes.sort_by_key(|s| Reverse(s.body.len()));
+    let mut counts = vec![0usize; snakes.len()];
+
+    let mut frontier: Vec<(usize, Position)> = snakes
+        .iter()
+        .enumerate()
+        .map(|(i, s)| (i, s.head))
+        .collect();
+    while !frontier.is_empty() {
+        let mut next = vec![];
+        for (i, pos) in frontier {
+            for p in Move::all_iter().filter_map(|m| next_head(game, pos, m)) {
+                if !claimed[index(p)] {
+                    claimed[index(p)] = true;
+                    counts[i] += 1;
+                    next.push((i, p));
+                }
+            }
+        }
+        frontier = next;
+    }
+
+    let total: usize = counts.iter().sum();
+    let Some(mine) = snakes.iter().position(|s| s.id == me.id) else {
+        return 0.0;
+    };
+    if total == 0 {
+        return 1.0;
+    }
+
+    counts[mine] as f64 * snakes.len() as f64 / total as f64
+}
+
+/// When we're far enough behind to accept an [EqualTradeRisk]
+///
+/// We accept trades when the longest opponent is at least `length_deficit` longer than us, or our
+/// [Standing::space_share] is at or below `space_share`. These are read from
+/// `H2H_TRADE_LENGTH_DEFICIT` and `H2H_TRADE_SPACE_SHARE`, and default to 2 and 0.5
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TradeThresholds {
+    pub length_deficit: i32,
+    pub space_share: f64,
+}
+
+impl Default for TradeThresholds {
+    fn default() -> Self {
+        Self {
+            length_deficit: 2,
+            space_share: 0.5,
+        }
+    }
+}
+
+impl TradeThresholds {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+
+        Self {
+            length_deficit: match std::env::var("H2H_TRADE_LENGTH_DEFICIT").map(|x| x.parse()) {
+                Ok(Ok(x)) => x,
+                _ => defaults.length_deficit,
+            },
+            space_share: match std::env::var("H2H_TRADE_SPACE_SHARE").map(|x| x.parse()) {
+                Ok(Ok(x)) => x,
+                _ => defaults.space_share,
+            },
+        }
+    }
+
+    pub fn risk(&self, standing: &Standing) -> EqualTradeRisk {
+        if -standing.length_lead >= self.length_deficit || standing.space_share <= self.space_share
+        {
+            EqualTradeRisk::Accept
+        } else {
+            EqualTradeRisk::Avoid
+        }
+    }
+}
+
+/// The [EqualTradeRisk] for `me` in this game, with the [TradeThresholds] from the environment
+pub fn equal_trade_risk(game: &Game, me: &BattleSnake) -> EqualTradeRisk {
+    TradeThresholds::from_env().risk(&Standing::of(game, me))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+
+    fn game_with_bodies(bodies: Vec<Vec<(i32, i32)>>, food: Vec<(i32, i32)>) -> Game {
+        let mut game: Game =
+            serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap();
+        game.board.food = food.into_iter().map(|(x, y)| Position { x, y }).collect();
+        game.board.snakes.truncate(bodies.len());
+
+        for (snake, body) in game.board.snakes.iter_mut().zip(bodies) {
+            snake.body = body
+                .into_iter()
+                .map(|(x, y)| Position { x, y })
+                .collect::<VecDeque<_>>();
+            snake.head = snake.body[0];
+        }
+        game.you = game.board.snakes[0].clone();
+
+        game
+    }
+
+    fn outcome(game: &Game, move_a: Move, move_b: Move) -> HeadToHead {
+        head_to_head_outcome(
+            game,
+            &game.board.snakes[0],
+            &game.board.snakes[1],
+            move_a,
+            move_b,
+        )
+    }
+
+    #[test]
+    fn test_no_collision_when_heads_miss() {
+        let game = game_with_bodies(
+            vec![vec![(3, 5), (2, 5), (1, 5)], vec![(5, 5), (6, 5), (7, 5)]],
+            vec![],
+        );
+
+        assert_eq!(
+            outcome(&game, Move::Up, Move::Left),
+            HeadToHead::NoCollision
+        );
+    }
+
+    #[test]
+    fn test_longer_snake_wins() {
+        let game = game_with_bodies(
+            vec![
+                vec![(3, 5), (2, 5), (1, 5), (0, 5)],
+                vec![(5, 5), (6, 5), (7, 5)],
+            ],
+            vec![],
+        );
+
+        assert_eq!(
+            outcome(&game, Move::Right, Move::Left),
+            HeadToHead::FirstWins
+        );
+    }
+
+    #[test]
+    fn test_both_grow_when_colliding_on_food() {
+        // They both eat the food before the collision is checked, so equal snakes still trade
+        let game = game_with_bodies(
+            vec![vec![(3, 5), (2, 5), (1, 5)], vec![(5, 5), (6, 5), (7, 5)]],
+            vec![(4, 5)],
+        );
+
+        assert_eq!(outcome(&game, Move::Right, Move::Left), HeadToHead::BothDie);
+    }
+
+    #[test]
+    fn test_stacked_tail_counts_towards_length() {
+        // The first snake ate last turn, so it only covers three cells but is four long. That
+        // makes it equal to the second snake once they collide
+        let game = game_with_bodies(
+            vec![
+                vec![(3, 5), (2, 5), (1, 5), (1, 5)],
+                vec![(5, 5), (6, 5), (7, 5), (8, 5)],
+            ],
+            vec![],
+        );
+
+        assert_eq!(outcome(&game, Move::Right, Move::Left), HeadToHead::BothDie);
+    }
+
+    #[test]
+    fn test_standing_against_a_longer_opponent() {
+        let game = game_with_bodies(
+            vec![
+                vec![(3, 5), (2, 5), (1, 5)],
+                vec![(7, 5), (8, 5), (9, 5), (10, 5), (10, 6)],
+            ],
+            vec![],
+        );
+        let standing = Standing::of(&game, &game.board.snakes[0]);
+
+        assert_eq!(standing.length_lead, -2);
+        assert_eq!(
+            TradeThresholds::default().risk(&standing),
+            EqualTradeRisk::Accept
+        );
+
+        let level = Standing {
+            length_lead: 0,
+            ..standing
+        };
+        assert_eq!(
+            TradeThresholds::default().risk(&level),
+            EqualTradeRisk::Avoid
+        );
+    }
+
+    #[test]
+    fn test_boxed_in_snakes_accept_trades() {
+        // We're stuck in the corner behind the other snake, which has the rest of the board
+        let game = game_with_bodies(
+            vec![
+                vec![(0, 0), (1, 0), (2, 0)],
+                vec![(1, 1), (2, 1), (3, 1), (3, 0)],
+            ],
+            vec![],
+        );
+        let standing = Standing::of(&game, &game.board.snakes[0]);
+
+        assert!(standing.space_share < 0.5);
+        assert_eq!(
+            TradeThresholds::default().risk(&standing),
+            EqualTradeRisk::Accept
+        );
+
+        let opponent = Standing::of(&game, &game.board.snakes[1]);
+        assert!(opponent.space_share > 1.0);
+        assert_eq!(
+            TradeThresholds::default().risk(&opponent),
+            EqualTradeRisk::Avoid
+        );
+    }
+
+    #[test]
+    fn test_collisions_across_the_wrapped_edge() {
+        let mut game = game_with_bodies(
+            vec![
+                vec![(0, 5), (1, 5), (2, 5), (3, 5)],
+                vec![(9, 5), (8, 5), (7, 5)],
+            ],
+            vec![],
+        );
+        game.game.ruleset.name = "wrapped".to_owned();
+
+        assert_eq!(
+            outcome(&game, Move::Left, Move::Right),
+            HeadToHead::FirstWins
+        );
+    }
+
+    #[test]
+    fn test_wrapped_constrictor_heads_wrap() {
+        let mut game = game_with_bodies(vec![vec![(0, 5), (1, 5), (2, 5)]], vec![]);
+        game.game.ruleset.name = crate::wire_game::WRAPPED_CONSTRICTOR.to_owned();
+
+        assert_eq!(
+            next_head(&game, Position { x: 0, y: 5 }, Move::Left),
+            Some(Position { x: 10, y: 5 })
+        );
+        assert_eq!(
+            next_head(&game, Position { x: 4, y: 10 }, Move::Up),
+            Some(Position { x: 4, y: 0 })
+        );
+    }
+}
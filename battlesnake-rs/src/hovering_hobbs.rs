@@ -1,15 +1,37 @@
 use std::time::Duration;
 
-use crate::a_prime::APrimeCalculable;
-use crate::flood_fill::spread_from_head::{Scores, SpreadFromHead};
+use crate::a_prime::{APrimeCalculable, APrimeOptions};
+use crate::board_backends::{backend_for, fits_compact_boards, oversized_board_snake};
+use crate::board_only::{you_on_board, BoardOnlySnake};
+use crate::board_tension::{board_tension, TENSION_TURNS};
+use crate::feature_flags::feature_flags;
+use crate::flood_fill::spread_from_head::{Grid, Scores, SpreadFromHead};
 use crate::flood_fill::spread_from_head_arcade_maze::SpreadFromHeadArcadeMaze;
+use crate::flood_fill::spread_from_head_wrapped::SpreadFromHeadWrapped;
 use crate::*;
 
+use battlesnake_game_types::compact_representation::{CellIndex, CellNum};
 use battlesnake_minimax::{
-    paranoid::{move_ordering::MoveOrdering, SnakeOptions},
+    paranoid::{move_ordering::MoveOrdering, RootStrategy, SnakeOptions},
     ParanoidMinimaxSnake,
 };
 use decorum::N64;
+use itertools::Itertools;
+
+/// How Hobbs measures the way to food when he's low on health, with a step over food or hazard
+/// costing one extra
+pub const A_PRIME_OPTIONS: APrimeOptions = APrimeOptions {
+    food_penalty: 1,
+    hazard_penalty: 1,
+};
+
+/// What each cell a snake reaches first is worth in Hobbs' flood fill. Food is worth the most,
+/// since reaching it first is how we stay fed, and hazards the least
+const FLOOD_FILL_SCORES: Scores = Scores {
+    food: 20,
+    hazard: 1,
+    empty: 5,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Score {
@@ -17,25 +39,213 @@ pub enum Score {
     FloodFill(N64),
 }
 
+impl Score {
+    /// Move the share of the board we control by `amount`, without changing what kind of score
+    /// this is
+    pub fn nudged(self, amount: N64) -> Self {
+        match self {
+            Score::LowOnHealth(dist, ratio) => Score::LowOnHealth(dist, ratio + amount),
+            Score::FloodFill(ratio) => Score::FloodFill(ratio + amount),
+        }
+    }
+}
+
+impl std::fmt::Display for Score {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Score::LowOnHealth(Some(negative_distance), ratio) => write!(
+                f,
+                "low on health, food in {}; {:.0}% space control",
+                -negative_distance,
+                ratio.into_inner() * 100.0
+            ),
+            Score::LowOnHealth(None, ratio) => write!(
+                f,
+                "low on health, no food in reach; {:.0}% space control",
+                ratio.into_inner() * 100.0
+            ),
+            Score::FloodFill(ratio) => {
+                write!(f, "{:.0}% space control", ratio.into_inner() * 100.0)
+            }
+        }
+    }
+}
+
+/// Opponents below this health need food soon, so any food we can keep from them is worth a lot
+const HUNGRY_OPPONENT_HEALTH: i64 = 30;
+
+/// How much each denied food is worth, compared to the ratio of the board we control
+const FOOD_DENIAL_WEIGHT: f64 = 0.02;
+
+/// How much a board full of contested squares is worth, compared to the ratio of the board we
+/// control. See [tension_preference]
+const TENSION_WEIGHT: f64 = 0.02;
+
+/// How we feel about the snakes being in each other's way, see [crate::board_tension]
+///
+/// When we're longer than every opponent a fight only gives them a chance, so we'd rather keep the
+/// board calm, and when we're shorter we'd rather stir things up than get slowly squeezed out.
+/// This is `TENSION_WEIGHT * (1 - tension)` for the lead in length, and the opposite when we're
+/// behind. It never gets better for us when an opponent grows, since the lead can only go down
+pub fn tension_preference<BoardType>(node: &BoardType) -> f64
+where
+    BoardType: SnakeIDGettableGame<SnakeIDType = SnakeId>
+        + YouDeterminableGame
+        + PositionGettableGame
+        + SizeDeterminableGame
+        + NeighborDeterminableGame
+        + HeadGettableGame
+        + HealthGettableGame
+        + LengthGettableGame
+        + SnakeBodyGettableGame,
+{
+    let me = node.you_id();
+    let longest_opponent = node
+        .get_snake_ids()
+        .into_iter()
+        .filter(|sid| sid != me && node.is_alive(sid))
+        .map(|sid| node.get_length_i64(&sid))
+        .max();
+    let Some(longest_opponent) = longest_opponent else {
+        return 0.0;
+    };
+    let lead = (node.get_length_i64(me) - longest_opponent).signum() as f64;
+
+    lead * TENSION_WEIGHT * (1.0 - board_tension(node, TENSION_TURNS))
+}
+
+/// Counts the food we reach before any opponent, when there is a shorter opponent that is low on
+/// health and needs it
+///
+/// Food that those hungry opponents reach first counts against us. This pushes us to cut hungry
+/// opponents off from food and starve them out, instead of just controlling space
+pub fn food_denial<BoardType, CellType>(node: &BoardType, grid: &Grid<BoardType>) -> i64
+where
+    BoardType: SnakeIDGettableGame<SnakeIDType = SnakeId>
+        + YouDeterminableGame
+        + PositionGettableGame<NativePositionType = CellIndex<CellType>>
+        + HealthGettableGame
+        + LengthGettableGame
+        + FoodGettableGame,
+    CellType: CellNum,
+{
+    let me = node.you_id();
+    let my_length = node.get_length_i64(me);
+
+    let hungry_opponents = node
+        .get_snake_ids()
+        .into_iter()
+        .filter(|sid| {
+            sid != me
+                && node.is_alive(sid)
+                && node.get_health_i64(sid) < HUNGRY_OPPONENT_HEALTH
+                && node.get_length_i64(sid) < my_length
+        })
+        .collect_vec();
+
+    if hungry_opponents.is_empty() {
+        return 0;
+    }
+
+    node.get_all_food_as_native_positions()
+        .iter()
+        .map(|food| match grid.cells[food.as_usize()] {
+            Some(owner) if &owner == me => 1,
+            Some(owner) if hungry_opponents.contains(&owner) => -1,
+            _ => 0,
+        })
+        .sum()
+}
+
 pub fn standard_score<BoardType, CellType, const MAX_SNAKES: usize>(node: &BoardType) -> Score
 where
     BoardType: SnakeIDGettableGame<SnakeIDType = SnakeId>
         + YouDeterminableGame
-        + SpreadFromHead<CellType, MAX_SNAKES>
+        + SpreadFromHead<CellType, MAX_SNAKES, GridType = Grid<BoardType>>
+        + PositionGettableGame<NativePositionType = CellIndex<CellType>>
         + APrimeCalculable
         + HeadGettableGame
         + HazardQueryableGame
         + HealthGettableGame
         + LengthGettableGame
         + FoodGettableGame
+        + SizeDeterminableGame
+        + SnakeBodyGettableGame
         + MaxSnakes<MAX_SNAKES>,
+    CellType: CellNum,
 {
-    let scores = Scores {
-        food: 20,
-        hazard: 1,
-        empty: 5,
-    };
-    let square_counts = node.squares_per_snake_with_scores(5, scores);
+    let grid = SpreadFromHead::<CellType, MAX_SNAKES>::calculate(node, 5);
+
+    score_for_grid(node, &grid)
+}
+
+/// The same as [standard_score], but the flood fill spreads through the edges of the board so
+/// space on the other side of a wall counts as close by
+pub fn wrapped_score<BoardType, CellType, const MAX_SNAKES: usize>(node: &BoardType) -> Score
+where
+    BoardType: SnakeIDGettableGame<SnakeIDType = SnakeId>
+        + YouDeterminableGame
+        + SpreadFromHead<CellType, MAX_SNAKES, GridType = Grid<BoardType>>
+        + SpreadFromHeadWrapped<CellType, MAX_SNAKES, GridType = Grid<BoardType>>
+        + PositionGettableGame<NativePositionType = CellIndex<CellType>>
+        + APrimeCalculable
+        + HeadGettableGame
+        + HazardQueryableGame
+        + HealthGettableGame
+        + LengthGettableGame
+        + FoodGettableGame
+        + SizeDeterminableGame
+        + SnakeBodyGettableGame
+        + MaxSnakes<MAX_SNAKES>,
+    CellType: CellNum,
+{
+    let grid = node.calculate_wrapped(5);
+
+    score_for_grid(node, &grid)
+}
+
+/// The share of the board `snake_id` controls, measured the same way as in [standard_score]
+///
+/// This is for [battlesnake_minimax::maxn], which scores every board once for each snake from
+/// that snake's point of view. It leaves out the hunger and tension parts of [standard_score],
+/// which are only worked out for us
+pub fn maxn_score<BoardType, CellType, const MAX_SNAKES: usize>(
+    node: &BoardType,
+    snake_id: &SnakeId,
+) -> N64
+where
+    BoardType: SnakeIDGettableGame<SnakeIDType = SnakeId>
+        + SpreadFromHead<CellType, MAX_SNAKES, GridType = Grid<BoardType>>
+        + PositionGettableGame<NativePositionType = CellIndex<CellType>>,
+    CellType: CellNum,
+{
+    let square_counts = node.squares_per_snake_with_scores(5, FLOOD_FILL_SCORES);
+    let total_space: f64 = square_counts.iter().sum::<u16>() as f64;
+
+    N64::from(square_counts[snake_id.as_usize()] as f64 / total_space)
+}
+
+fn score_for_grid<BoardType, CellType, const MAX_SNAKES: usize>(
+    node: &BoardType,
+    grid: &Grid<BoardType>,
+) -> Score
+where
+    BoardType: SnakeIDGettableGame<SnakeIDType = SnakeId>
+        + YouDeterminableGame
+        + SpreadFromHead<CellType, MAX_SNAKES, GridType = Grid<BoardType>>
+        + PositionGettableGame<NativePositionType = CellIndex<CellType>>
+        + APrimeCalculable
+        + HeadGettableGame
+        + HazardQueryableGame
+        + HealthGettableGame
+        + LengthGettableGame
+        + FoodGettableGame
+        + SizeDeterminableGame
+        + SnakeBodyGettableGame
+        + MaxSnakes<MAX_SNAKES>,
+    CellType: CellNum,
+{
+    let square_counts = node.squares_per_snake_with_scores_for_grid(grid, FLOOD_FILL_SCORES);
 
     let me = node.you_id();
     let my_space: f64 = square_counts[me.as_usize()] as f64;
@@ -47,13 +257,237 @@ where
             .shortest_distance(
                 &node.get_head_as_native_position(me),
                 &node.get_all_food_as_native_positions(),
-                None,
+                &A_PRIME_OPTIONS,
             )
             .map(|x| -x);
         return Score::LowOnHealth(dist, my_ratio);
     }
 
-    Score::FloodFill(my_ratio)
+    let denial = N64::from(FOOD_DENIAL_WEIGHT * food_denial(node, grid) as f64);
+    let tension = N64::from(tension_preference(node));
+
+    Score::FloodFill(my_ratio + denial + tension)
+}
+
+/// How Hobbs scores constrictor games, where every snake grows each turn and there is no food
+///
+/// Nobody ever gets hungry, so this is all about space. Food and hunger never come into it, even
+/// if the board we're handed has some food on it or a snake low on health
+pub fn constrictor_score<BoardType, CellType, const MAX_SNAKES: usize>(node: &BoardType) -> Score
+where
+    BoardType: SnakeIDGettableGame<SnakeIDType = SnakeId>
+        + YouDeterminableGame
+        + SpreadFromHead<CellType, MAX_SNAKES, GridType = Grid<BoardType>>
+        + PositionGettableGame<NativePositionType = CellIndex<CellType>>
+        + APrimeCalculable
+        + HeadGettableGame
+        + HazardQueryableGame
+        + HealthGettableGame
+        + LengthGettableGame
+        + FoodGettableGame
+        + SizeDeterminableGame
+        + SnakeBodyGettableGame
+        + MaxSnakes<MAX_SNAKES>,
+    CellType: CellNum,
+{
+    let grid = SpreadFromHead::<CellType, MAX_SNAKES>::calculate(node, 5);
+
+    constrictor_score_for_grid(node, &grid)
+}
+
+/// [constrictor_score] for the wrapped constrictor arena, with the flood fill spreading through the
+/// edges of the board like [wrapped_score]
+pub fn wrapped_constrictor_score<BoardType, CellType, const MAX_SNAKES: usize>(
+    node: &BoardType,
+) -> Score
+where
+    BoardType: SnakeIDGettableGame<SnakeIDType = SnakeId>
+        + YouDeterminableGame
+        + SpreadFromHead<CellType, MAX_SNAKES, GridType = Grid<BoardType>>
+        + SpreadFromHeadWrapped<CellType, MAX_SNAKES, GridType = Grid<BoardType>>
+        + PositionGettableGame<NativePositionType = CellIndex<CellType>>
+        + APrimeCalculable
+        + HeadGettableGame
+        + HazardQueryableGame
+        + HealthGettableGame
+        + LengthGettableGame
+        + FoodGettableGame
+        + SizeDeterminableGame
+        + SnakeBodyGettableGame
+        + MaxSnakes<MAX_SNAKES>,
+    CellType: CellNum,
+{
+    let grid = node.calculate_wrapped(5);
+
+    constrictor_score_for_grid(node, &grid)
+}
+
+fn constrictor_score_for_grid<BoardType, CellType, const MAX_SNAKES: usize>(
+    node: &BoardType,
+    grid: &Grid<BoardType>,
+) -> Score
+where
+    BoardType: SnakeIDGettableGame<SnakeIDType = SnakeId>
+        + YouDeterminableGame
+        + SpreadFromHead<CellType, MAX_SNAKES, GridType = Grid<BoardType>>
+        + PositionGettableGame<NativePositionType = CellIndex<CellType>>
+        + APrimeCalculable
+        + HeadGettableGame
+        + HazardQueryableGame
+        + HealthGettableGame
+        + LengthGettableGame
+        + FoodGettableGame
+        + SizeDeterminableGame
+        + SnakeBodyGettableGame
+        + MaxSnakes<MAX_SNAKES>,
+    CellType: CellNum,
+{
+    let scores = Scores {
+        food: 5,
+        hazard: 1,
+        empty: 5,
+    };
+    let square_counts = node.squares_per_snake_with_scores_for_grid(grid, scores);
+
+    let me = node.you_id();
+    let my_space: f64 = square_counts[me.as_usize()] as f64;
+    let total_space: f64 = square_counts.iter().sum::<u16>() as f64;
+    let my_ratio = N64::from(my_space / total_space);
+
+    Score::FloodFill(my_ratio + N64::from(tension_preference(node)))
+}
+
+/// The terms that go into [standard_score], for logging when the search changes its mind
+pub fn standard_features<BoardType, CellType, const MAX_SNAKES: usize>(
+    node: &BoardType,
+) -> Vec<(&'static str, f64)>
+where
+    BoardType: SnakeIDGettableGame<SnakeIDType = SnakeId>
+        + YouDeterminableGame
+        + SpreadFromHead<CellType, MAX_SNAKES, GridType = Grid<BoardType>>
+        + PositionGettableGame<NativePositionType = CellIndex<CellType>>
+        + APrimeCalculable
+        + HeadGettableGame
+        + HazardQueryableGame
+        + HealthGettableGame
+        + LengthGettableGame
+        + FoodGettableGame
+        + SizeDeterminableGame
+        + SnakeBodyGettableGame
+        + MaxSnakes<MAX_SNAKES>,
+    CellType: CellNum,
+{
+    let grid = SpreadFromHead::<CellType, MAX_SNAKES>::calculate(node, 5);
+
+    features_for_grid(node, &grid)
+}
+
+/// The terms that go into [wrapped_score], for logging when the search changes its mind
+pub fn wrapped_features<BoardType, CellType, const MAX_SNAKES: usize>(
+    node: &BoardType,
+) -> Vec<(&'static str, f64)>
+where
+    BoardType: SnakeIDGettableGame<SnakeIDType = SnakeId>
+        + YouDeterminableGame
+        + SpreadFromHead<CellType, MAX_SNAKES, GridType = Grid<BoardType>>
+        + SpreadFromHeadWrapped<CellType, MAX_SNAKES, GridType = Grid<BoardType>>
+        + PositionGettableGame<NativePositionType = CellIndex<CellType>>
+        + APrimeCalculable
+        + HeadGettableGame
+        + HazardQueryableGame
+        + HealthGettableGame
+        + LengthGettableGame
+        + FoodGettableGame
+        + SizeDeterminableGame
+        + SnakeBodyGettableGame
+        + MaxSnakes<MAX_SNAKES>,
+    CellType: CellNum,
+{
+    let grid = node.calculate_wrapped(5);
+
+    features_for_grid(node, &grid)
+}
+
+/// Unlike [score_for_grid] this counts plain squares, so the areas can be compared across boards
+///
+/// The opponent features are for the best placed opponent in each, which isn't always the same
+/// snake. Food distance is left out when we can't reach any food
+fn features_for_grid<BoardType, CellType, const MAX_SNAKES: usize>(
+    node: &BoardType,
+    grid: &Grid<BoardType>,
+) -> Vec<(&'static str, f64)>
+where
+    BoardType: SnakeIDGettableGame<SnakeIDType = SnakeId>
+        + YouDeterminableGame
+        + SpreadFromHead<CellType, MAX_SNAKES, GridType = Grid<BoardType>>
+        + PositionGettableGame<NativePositionType = CellIndex<CellType>>
+        + APrimeCalculable
+        + HeadGettableGame
+        + HazardQueryableGame
+        + HealthGettableGame
+        + LengthGettableGame
+        + FoodGettableGame
+        + SizeDeterminableGame
+        + SnakeBodyGettableGame
+        + MaxSnakes<MAX_SNAKES>,
+    CellType: CellNum,
+{
+    let scores = Scores {
+        food: 1,
+        hazard: 1,
+        empty: 1,
+    };
+    let areas = node.squares_per_snake_with_scores_for_grid(grid, scores);
+
+    let me = node.you_id();
+    let opponents = node
+        .get_snake_ids()
+        .into_iter()
+        .filter(|sid| sid != me && node.is_alive(sid))
+        .collect_vec();
+
+    let mut features = vec![
+        ("my_area", areas[me.as_usize()] as f64),
+        (
+            "best_opponent_area",
+            opponents
+                .iter()
+                .map(|sid| areas[sid.as_usize()])
+                .max()
+                .unwrap_or(0) as f64,
+        ),
+        ("my_length", node.get_length_i64(me) as f64),
+        (
+            "longest_opponent_length",
+            opponents
+                .iter()
+                .map(|sid| node.get_length_i64(sid))
+                .max()
+                .unwrap_or(0) as f64,
+        ),
+        ("my_health", node.get_health_i64(me) as f64),
+        (
+            "lowest_opponent_health",
+            opponents
+                .iter()
+                .map(|sid| node.get_health_i64(sid))
+                .min()
+                .unwrap_or(0) as f64,
+        ),
+        ("opponents_alive", opponents.len() as f64),
+        ("food_denial", food_denial(node, grid) as f64),
+        ("board_tension", board_tension(node, TENSION_TURNS)),
+    ];
+
+    if let Some(dist) = node.shortest_distance(
+        &node.get_head_as_native_position(me),
+        &node.get_all_food_as_native_positions(),
+        &A_PRIME_OPTIONS,
+    ) {
+        features.push(("my_food_distance", dist as f64));
+    }
+
+    features
 }
 
 pub fn arcade_maze_score<BoardType, CellType, const MAX_SNAKES: usize>(node: &BoardType) -> Score
@@ -82,7 +516,7 @@ where
             .shortest_distance(
                 &node.get_head_as_native_position(me),
                 &node.get_all_food_as_native_positions(),
-                None,
+                &A_PRIME_OPTIONS,
             )
             .map(|x| -x);
         return Score::LowOnHealth(dist, my_ratio);
@@ -103,32 +537,158 @@ where
     Score::FloodFill(my_ratio * length_diff_multiplier)
 }
 
+/// The terms that go into [arcade_maze_score], for logging when the search changes its mind
+///
+/// The areas come from the same hazard aware flood fill the score uses
+pub fn arcade_maze_features<BoardType, CellType, const MAX_SNAKES: usize>(
+    node: &BoardType,
+) -> Vec<(&'static str, f64)>
+where
+    BoardType: SnakeIDGettableGame<SnakeIDType = SnakeId>
+        + YouDeterminableGame
+        + SpreadFromHead<CellType, MAX_SNAKES>
+        + SpreadFromHeadArcadeMaze<CellType, MAX_SNAKES>
+        + APrimeCalculable
+        + HeadGettableGame
+        + HazardQueryableGame
+        + HealthGettableGame
+        + LengthGettableGame
+        + FoodGettableGame
+        + MaxSnakes<MAX_SNAKES>,
+{
+    let areas = node.squares_per_snake_hazard_maze(8);
+
+    let me = node.you_id();
+    let opponents = node
+        .get_snake_ids()
+        .into_iter()
+        .filter(|sid| sid != me && node.is_alive(sid))
+        .collect_vec();
+
+    let mut features = vec![
+        ("my_area", areas[me.as_usize()] as f64),
+        (
+            "best_opponent_area",
+            opponents
+                .iter()
+                .map(|sid| areas[sid.as_usize()])
+                .max()
+                .unwrap_or(0) as f64,
+        ),
+        ("my_length", node.get_length_i64(me) as f64),
+        (
+            "longest_opponent_length",
+            opponents
+                .iter()
+                .map(|sid| node.get_length_i64(sid))
+                .max()
+                .unwrap_or(0) as f64,
+        ),
+        ("my_health", node.get_health_i64(me) as f64),
+        ("opponents_alive", opponents.len() as f64),
+    ];
+
+    if let Some(dist) = node.shortest_distance(
+        &node.get_head_as_native_position(me),
+        &node.get_all_food_as_native_positions(),
+        &A_PRIME_OPTIONS,
+    ) {
+        features.push(("my_food_distance", dist as f64));
+    }
+
+    features
+}
+
+/// Reads the number of turns to reduce opponent searches by from the environment
+///
+/// This lets us run two copies of the server with different values and compare them against
+/// each other with `script/duels.rb`
+pub fn opponent_depth_reduction() -> usize {
+    match std::env::var("OPPONENT_DEPTH_REDUCTION").map(|x| x.parse()) {
+        Ok(Ok(x)) => x,
+        _ => 0,
+    }
+}
+
+/// Reads how many plies forced opponent moves can extend a line of the search by from the
+/// environment, see [SnakeOptions::forced_move_extension]
+///
+/// Defaults to 4, which is two extra turns in a duel
+pub fn forced_move_extension() -> usize {
+    match std::env::var("FORCED_MOVE_EXTENSION").map(|x| x.parse()) {
+        Ok(Ok(x)) => x,
+        _ => 4,
+    }
+}
+
+/// Reads how we pick our move at the root of the search from `MINIMAX_ROOT_STRATEGY`, either
+/// `paranoid` or `minimax-regret`, see [SnakeOptions::root_strategy]
+///
+/// Defaults to `paranoid`
+pub fn root_strategy() -> RootStrategy {
+    match std::env::var("MINIMAX_ROOT_STRATEGY").map(|x| x.parse()) {
+        Ok(Ok(x)) => x,
+        _ => RootStrategy::Paranoid,
+    }
+}
+
 pub struct Factory;
 
 #[macro_export]
 macro_rules! build_from_best_cell_board {
-    ( $wire_game:expr, $game_info:expr, $turn:expr, $score_function:ident, $name:expr, $options:expr ) => {{
+    ( $wire_game:expr, $game_info:expr, $turn:expr, $score_function:ident, $features:ident, $name:expr, $options:expr ) => {{
         let game = $wire_game;
         let game_info = $game_info;
         let turn = $turn;
         let name = $name;
         let options = $options;
 
-        if game_info.ruleset.name == "wrapped" {
+        if $crate::wire_game::is_wrapped(&game_info) {
             use battlesnake_game_types::compact_representation::wrapped::*;
 
-            build_from_best_cell_board_inner!(game, game_info, turn, $score_function, name, options)
+            build_from_best_cell_board_inner!(
+                game,
+                game_info,
+                turn,
+                $score_function,
+                $features,
+                name,
+                options
+            )
         } else {
             use battlesnake_game_types::compact_representation::standard::*;
 
-            build_from_best_cell_board_inner!(game, game_info, turn, $score_function, name, options)
+            build_from_best_cell_board_inner!(
+                game,
+                game_info,
+                turn,
+                $score_function,
+                $features,
+                name,
+                options
+            )
         }
     }};
 }
 
+/// Builds the snake for whichever [BestCellBoard] the game fits in, with the feature snapshot
+/// applied in one place so every board size logs the same terms
 #[macro_export]
 macro_rules! build_from_best_cell_board_inner {
-    ( $wire_game:expr, $game_info:expr, $turn:expr, $score_function:ident, $name:expr, $options:expr ) => {{
+    ( @snake $board:expr, $game_info:expr, $turn:expr, $score_function:ident, $features:ident, $name:expr, $options:expr ) => {
+        Box::new(
+            ParanoidMinimaxSnake::new(
+                $board,
+                $game_info,
+                $turn,
+                &$score_function,
+                $name,
+                $options,
+            )
+            .with_feature_snapshot($features),
+        )
+    };
+    ( $wire_game:expr, $game_info:expr, $turn:expr, $score_function:ident, $features:ident, $name:expr, $options:expr ) => {{
         {
             let game = $wire_game;
             let game_info = $game_info;
@@ -137,107 +697,162 @@ macro_rules! build_from_best_cell_board_inner {
             let options = $options;
 
             match ToBestCellBoard::to_best_cell_board(game).unwrap() {
-                BestCellBoard::Tiny(game) => Box::new(ParanoidMinimaxSnake::new(
-                    *game,
-                    game_info,
-                    turn,
-                    &$score_function,
-                    name,
-                    options,
-                )),
-                BestCellBoard::SmallExact(game) => Box::new(ParanoidMinimaxSnake::new(
-                    *game,
-                    game_info,
-                    turn,
-                    &$score_function,
-                    name,
-                    options,
-                )),
-                BestCellBoard::Standard(game) => Box::new(ParanoidMinimaxSnake::new(
-                    *game,
-                    game_info,
-                    turn,
-                    &$score_function,
-                    name,
-                    options,
-                )),
-                BestCellBoard::MediumExact(game) => Box::new(ParanoidMinimaxSnake::new(
-                    *game,
-                    game_info,
-                    turn,
-                    &$score_function,
-                    name,
-                    options,
-                )),
-                BestCellBoard::LargestU8(game) => Box::new(ParanoidMinimaxSnake::new(
-                    *game,
-                    game_info,
-                    turn,
-                    &$score_function,
-                    name,
-                    options,
-                )),
-                BestCellBoard::LargeExact(game) => Box::new(ParanoidMinimaxSnake::new(
-                    *game,
-                    game_info,
-                    turn,
-                    &$score_function,
-                    name,
-                    options,
-                )),
-                BestCellBoard::ArcadeMaze(game) => Box::new(ParanoidMinimaxSnake::new(
-                    *game,
-                    game_info,
-                    turn,
-                    &$score_function,
-                    name,
-                    options,
-                )),
-                BestCellBoard::ArcadeMaze8Snake(game) => Box::new(ParanoidMinimaxSnake::new(
-                    *game,
-                    game_info,
-                    turn,
-                    &$score_function,
-                    name,
-                    options,
-                )),
-                BestCellBoard::Large(game) => Box::new(ParanoidMinimaxSnake::new(
-                    *game,
-                    game_info,
-                    turn,
-                    &$score_function,
-                    name,
-                    options,
-                )),
-                BestCellBoard::Silly(game) => Box::new(ParanoidMinimaxSnake::new(
-                    *game,
-                    game_info,
-                    turn,
-                    &$score_function,
-                    name,
-                    options,
-                )),
+                BestCellBoard::Tiny(game) => build_from_best_cell_board_inner!(
+                    @snake *game, game_info, turn, $score_function, $features, name, options
+                ),
+                BestCellBoard::SmallExact(game) => build_from_best_cell_board_inner!(
+                    @snake *game, game_info, turn, $score_function, $features, name, options
+                ),
+                BestCellBoard::Standard(game) => build_from_best_cell_board_inner!(
+                    @snake *game, game_info, turn, $score_function, $features, name, options
+                ),
+                BestCellBoard::MediumExact(game) => build_from_best_cell_board_inner!(
+                    @snake *game, game_info, turn, $score_function, $features, name, options
+                ),
+                BestCellBoard::LargestU8(game) => build_from_best_cell_board_inner!(
+                    @snake *game, game_info, turn, $score_function, $features, name, options
+                ),
+                BestCellBoard::LargeExact(game) => build_from_best_cell_board_inner!(
+                    @snake *game, game_info, turn, $score_function, $features, name, options
+                ),
+                BestCellBoard::ArcadeMaze(game) => build_from_best_cell_board_inner!(
+                    @snake *game, game_info, turn, $score_function, $features, name, options
+                ),
+                BestCellBoard::ArcadeMaze8Snake(game) => build_from_best_cell_board_inner!(
+                    @snake *game, game_info, turn, $score_function, $features, name, options
+                ),
+                BestCellBoard::Large(game) => build_from_best_cell_board_inner!(
+                    @snake *game, game_info, turn, $score_function, $features, name, options
+                ),
+                BestCellBoard::Silly(game) => build_from_best_cell_board_inner!(
+                    @snake *game, game_info, turn, $score_function, $features, name, options
+                ),
             }
         }
     }};
 }
 
 impl Factory {
+    /// The options Hobbs searches with, with the feature flags read from the environment
+    pub fn options() -> SnakeOptions {
+        SnakeOptions {
+            network_latency_padding: Duration::from_millis(120),
+            move_ordering: MoveOrdering::BestFirst,
+            opponent_depth_reduction: opponent_depth_reduction(),
+            food_spawn_chance: 0.0,
+            forced_move_extension: forced_move_extension(),
+            root_strategy: root_strategy(),
+            simultaneous_root_depth: 1,
+            features: feature_flags(),
+        }
+    }
+
     pub fn create_from_wire_game(&self, game: Game) -> BoxedSnake {
+        self.create_with_options(game, Self::options())
+    }
+
+    /// Like [Factory::create_from_wire_game] but searching with the given options instead of
+    /// the ones from the environment
+    pub fn create_with_options(&self, game: Game, options: SnakeOptions) -> BoxedSnake {
+        if !you_on_board(&game) {
+            return Box::new(BoardOnlySnake::new(game));
+        }
+        if !fits_compact_boards(&game) {
+            return oversized_board_snake(game);
+        }
+
         let game_info = game.game.clone();
         let turn = game.turn;
 
         let name = "hovering-hobbs";
 
-        let options: SnakeOptions = SnakeOptions {
-            network_latency_padding: Duration::from_millis(120),
-            move_ordering: MoveOrdering::BestFirst,
-        };
+        // Constrictor games never have food, so they're scored on space alone
+        let wrapped = crate::wire_game::is_wrapped(&game_info);
+        let constrictor = crate::wire_game::is_constrictor(&game_info);
+
+        macro_rules! hobbs {
+            ($board:expr, $score_function:ident, $features:ident) => {
+                Box::new(
+                    ParanoidMinimaxSnake::new(
+                        $board,
+                        game_info,
+                        turn,
+                        &$score_function,
+                        name,
+                        options,
+                    )
+                    .with_feature_snapshot($features),
+                ) as BoxedSnake
+            };
+        }
+
+        if let Some(backend) = backend_for(&game) {
+            return match (wrapped, constrictor) {
+                (true, true) => crate::with_backend!(WrappedCellBoard, game, backend, |board| {
+                    hobbs!(board, wrapped_constrictor_score, wrapped_features)
+                }),
+                (true, false) => crate::with_backend!(WrappedCellBoard, game, backend, |board| {
+                    hobbs!(board, wrapped_score, wrapped_features)
+                }),
+                (false, true) => crate::with_backend!(StandardCellBoard, game, backend, |board| {
+                    hobbs!(board, constrictor_score, standard_features)
+                }),
+                (false, false) => crate::with_backend!(StandardCellBoard, game, backend, |board| {
+                    hobbs!(board, standard_score, standard_features)
+                }),
+            };
+        }
 
         if game.is_arcade_maze_map() {
-            build_from_best_cell_board!(game, game_info, turn, arcade_maze_score, name, options)
+            build_from_best_cell_board!(
+                game,
+                game_info,
+                turn,
+                arcade_maze_score,
+                arcade_maze_features,
+                name,
+                options
+            )
+        } else if wrapped && constrictor {
+            build_from_best_cell_board!(
+                game,
+                game_info,
+                turn,
+                wrapped_constrictor_score,
+                wrapped_features,
+                name,
+                options
+            )
+        } else if wrapped {
+            build_from_best_cell_board!(
+                game,
+                game_info,
+                turn,
+                wrapped_score,
+                wrapped_features,
+                name,
+                options
+            )
+        } else if constrictor {
+            build_from_best_cell_board!(
+                game,
+                game_info,
+                turn,
+                constrictor_score,
+                standard_features,
+                name,
+                options
+            )
         } else {
-            build_from_best_cell_board!(game, game_info, turn, standard_score, name, options)
+            build_from_best_cell_board!(
+                game,
+                game_info,
+                turn,
+                standard_score,
+                standard_features,
+                name,
+                options
+            )
         }
     }
 
@@ -249,10 +864,25 @@ impl Factory {
             head: Some("beach-puffin-special".to_owned()),
             tail: Some("beach-puffin-special".to_owned()),
             version: None,
+            ..Default::default()
         }
     }
 }
 
+impl BattlesnakeFactory for Factory {
+    fn name(&self) -> String {
+        "hovering-hobbs".to_owned()
+    }
+
+    fn create_from_wire_game(&self, game: Game) -> BoxedSnake {
+        Factory::create_from_wire_game(self, game)
+    }
+
+    fn about(&self) -> AboutMe {
+        Factory::about(self)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use battlesnake_game_types::{
@@ -261,8 +891,59 @@ mod tests {
         wire_representation::Game,
     };
 
-    use crate::hovering_hobbs::standard_score;
-    use battlesnake_minimax::ParanoidMinimaxSnake;
+    use battlesnake_game_types::{
+        compact_representation::StandardCellBoard4Snakes11x11, wire_representation::Position,
+    };
+
+    use std::cmp::Reverse;
+
+    use crate::flood_fill::spread_from_head::SpreadFromHead;
+    use crate::hovering_hobbs::{
+        food_denial, maxn_score, standard_score, tension_preference, wrapped_constrictor_score,
+        wrapped_score, Factory, Score,
+    };
+    use crate::{BattlesnakeAI, BattlesnakeFactory};
+    use battlesnake_minimax::{paranoid::WrappedScore, ParanoidMinimaxSnake};
+    use itertools::Itertools;
+
+    fn denial_for_food(food: Vec<Position>, opponent_health: i32) -> i64 {
+        let mut game =
+            serde_json::from_str::<Game>(include_str!("../fixtures/start_of_game.json")).unwrap();
+        game.board.food = food;
+
+        let opponent = &mut game.board.snakes[1];
+        opponent.health = opponent_health;
+        opponent.body.pop_back();
+
+        let id_map = build_snake_id_map(&game);
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+        let grid = SpreadFromHead::<u8, 4>::calculate(&game, 5);
+
+        food_denial(&game, &grid)
+    }
+
+    #[test]
+    fn test_food_denial_rewards_food_we_reach_first() {
+        assert_eq!(denial_for_food(vec![Position { x: 9, y: 6 }], 10), 1);
+    }
+
+    #[test]
+    fn test_food_denial_penalizes_food_a_hungry_opponent_reaches_first() {
+        assert_eq!(denial_for_food(vec![Position { x: 5, y: 8 }], 10), -1);
+    }
+
+    #[test]
+    fn test_food_denial_ignores_eliminated_opponents() {
+        // An eliminated snake has no health left, but it isn't hungry for anything
+        assert_eq!(denial_for_food(vec![Position { x: 5, y: 8 }], 0), 0);
+    }
+
+    #[test]
+    fn test_food_denial_ignores_opponents_that_are_not_hungry() {
+        let food = vec![Position { x: 9, y: 6 }, Position { x: 5, y: 8 }];
+
+        assert_eq!(denial_for_food(food, 100), 0);
+    }
 
     #[test]
     #[ignore]
@@ -338,4 +1019,283 @@ mod tests {
 
         scored_options.first().unwrap().0.to_string()
     }
+
+    fn score_after(change: impl FnOnce(&mut Game)) -> Score {
+        let mut game =
+            serde_json::from_str::<Game>(include_str!("../fixtures/start_of_game.json")).unwrap();
+        change(&mut game);
+
+        let id_map = build_snake_id_map(&game);
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+
+        standard_score::<_, _, 4>(&game)
+    }
+
+    fn set_our_health(game: &mut Game, health: i32) {
+        game.you.health = health;
+        for snake in game.board.snakes.iter_mut().filter(|s| s.id == "you") {
+            snake.health = health;
+        }
+    }
+
+    /// Growing an opponent only lets it win more of the cells we both reach at the same time, so
+    /// it can never make the position better for us. This is a duel since with a third snake the
+    /// cells one opponent takes from the other can end up as ours
+    #[test]
+    fn test_growing_an_opponent_never_helps_us() {
+        let scores = (0..5)
+            .map(|growth| {
+                score_after(|game| {
+                    game.board.snakes.retain(|s| s.id != "#FF6444");
+
+                    let opponent = &mut game.board.snakes[1];
+                    let tail = *opponent.body.back().unwrap();
+                    for _ in 0..growth {
+                        opponent.body.push_back(tail);
+                    }
+                })
+            })
+            .collect_vec();
+
+        for (shorter, longer) in scores.iter().tuple_windows() {
+            assert!(longer <= shorter, "{longer:?} is better than {shorter:?}");
+        }
+    }
+
+    /// When we're hungry the distance to food is the first thing we compare, so food that is
+    /// closer always scores at least as well wherever it is on the board
+    #[test]
+    fn test_closer_food_never_hurts_when_we_are_hungry() {
+        let head = Position { x: 9, y: 5 };
+        let heads = [head, Position { x: 5, y: 9 }, Position { x: 1, y: 1 }];
+
+        let scored_food = (0..11)
+            .cartesian_product(0..11)
+            .map(|(x, y)| Position { x, y })
+            .filter(|food| !heads.contains(food))
+            .map(|food| {
+                let score = score_after(|game| {
+                    set_our_health(game, 50);
+                    game.board.food = vec![food];
+                });
+                let distance = (food.x - head.x).abs() + (food.y - head.y).abs();
+
+                (distance, food, score)
+            })
+            .collect_vec();
+
+        for (closer_distance, closer, closer_score) in &scored_food {
+            assert!(matches!(closer_score, Score::LowOnHealth(Some(_), _)));
+
+            for (distance, food, score) in &scored_food {
+                if closer_distance < distance {
+                    assert!(
+                        closer_score >= score,
+                        "Food at {closer:?} scored {closer_score:?}, worse than {score:?} for {food:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    /// With the snakes the same length the board's tension doesn't matter. Ahead we'd rather it
+    /// was calm and behind we'd rather it wasn't, so the preference flips sign with the lead
+    #[test]
+    fn test_tension_preference_follows_the_length_lead() {
+        let preference = |grow: &str| {
+            let mut game =
+                serde_json::from_str::<Game>(include_str!("../fixtures/start_of_game.json"))
+                    .unwrap();
+            for snake in game.board.snakes.iter_mut().filter(|s| s.id == grow) {
+                let tail = *snake.body.back().unwrap();
+                snake.body.push_back(tail);
+            }
+
+            let id_map = build_snake_id_map(&game);
+            let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+
+            tension_preference(&game)
+        };
+
+        assert_eq!(preference("nobody"), 0.0);
+        assert!(preference("you") > 0.0);
+        assert!(preference("#FF6c96") < 0.0);
+    }
+
+    /// The search wraps these scores, and has to prefer any win over them and them over any loss
+    /// or tie, however good or bad the score is
+    #[test]
+    fn test_terminal_states_outrank_every_score() {
+        let scores = [
+            score_after(|_| {}),
+            score_after(|game| set_our_health(game, 50)),
+            score_after(|game| {
+                set_our_health(game, 50);
+                game.board.food.clear();
+            }),
+            Score::FloodFill(1.0.into()),
+            Score::LowOnHealth(Some(-1), 1.0.into()),
+        ];
+
+        for score in scores {
+            for depth in [0, 1, 10, 100] {
+                let scored = WrappedScore::Scored(score);
+
+                assert!(WrappedScore::Win(Reverse(depth)) > scored);
+                assert!(WrappedScore::Tie(Reverse(1), depth) < scored);
+                assert!(WrappedScore::Lose(Reverse(0), depth) < scored);
+            }
+        }
+    }
+
+    #[test]
+    fn test_being_low_on_health_is_worse_than_any_healthy_score() {
+        let hungry = score_after(|game| {
+            set_our_health(game, 50);
+            game.board.food = vec![Position { x: 9, y: 6 }];
+        });
+
+        assert!(hungry < score_after(|_| {}));
+        assert!(hungry < Score::FloodFill(0.0.into()));
+    }
+
+    fn factory_move(fixture: &str) -> String {
+        let game = serde_json::from_str::<Game>(fixture).unwrap();
+
+        Factory
+            .create_from_wire_game(game)
+            .make_move()
+            .unwrap()
+            .r#move
+    }
+
+    #[test]
+    fn test_the_summary_describes_the_search_behind_the_move() {
+        let game: Game =
+            serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap();
+
+        let (output, summary) = Factory
+            .create_from_wire_game(game)
+            .make_move_with_summary()
+            .unwrap();
+
+        assert_eq!(summary.algorithm, Some("paranoid_minimax"));
+        assert!(summary.depth.unwrap() > 0);
+        // The line the search expects starts with the move we made
+        assert_eq!(summary.pv.first(), Some(&output.r#move));
+        assert!(summary.budget_ms.is_some());
+    }
+
+    fn leaf_breakdown_names(fixture: &str) -> Vec<&'static str> {
+        let game: Game = serde_json::from_str(fixture).unwrap();
+        let mut options = Factory::options();
+        options.features.leaf_breakdown = true;
+
+        let explanation = Factory
+            .create_with_options(game, options)
+            .explain_move()
+            .unwrap();
+
+        explanation
+            .leaf_breakdown
+            .expect("the search should keep the feature snapshot of its leaves")
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    /// Without a board backend picked every game goes through [build_from_best_cell_board],
+    /// which has to record the features just like the backend path does
+    #[test]
+    fn test_the_fallback_boards_keep_their_feature_snapshots() {
+        let arcade_maze = leaf_breakdown_names(include_str!(
+            "../../fixtures/arcade_maze_end_game_duels.json"
+        ));
+        assert!(arcade_maze.contains(&"my_area"), "{arcade_maze:?}");
+        assert!(!arcade_maze.contains(&"food_denial"), "{arcade_maze:?}");
+
+        let standard = leaf_breakdown_names(include_str!("../fixtures/start_of_game.json"));
+        assert!(standard.contains(&"food_denial"), "{standard:?}");
+    }
+
+    #[test]
+    fn test_wrapped_constrictor_escapes_across_the_edge() {
+        let fixture = include_str!("../../fixtures/wrapped_constrictor_edge_escape.json");
+
+        assert_eq!(factory_move(fixture), "left");
+    }
+
+    #[test]
+    fn test_wrapped_constrictor_stays_out_of_the_pocket() {
+        let fixture = include_str!("../../fixtures/wrapped_constrictor_pocket.json");
+        let next_move = factory_move(fixture);
+
+        // Left is a two cell dead end walled off by the opponent. Down wraps around to the open
+        // side of the board
+        let allowed_moves = vec!["right", "down"];
+        assert!(
+            allowed_moves.contains(&next_move.as_str()),
+            "{next_move} not in {allowed_moves:?}"
+        );
+    }
+
+    /// There is never any food in constrictor and nobody gets hungry, so the score has to stay
+    /// about space
+    #[test]
+    fn test_wrapped_constrictor_is_scored_on_space() {
+        let wire_game = serde_json::from_str::<Game>(include_str!(
+            "../../fixtures/wrapped_constrictor_opening.json"
+        ))
+        .unwrap();
+        let id_map = build_snake_id_map(&wire_game);
+        let board = |game: &Game| {
+            WrappedCellBoard4Snakes11x11::convert_from_game(game.clone(), &id_map).unwrap()
+        };
+
+        let score = wrapped_constrictor_score::<_, _, 4>(&board(&wire_game));
+        assert!(matches!(score, Score::FloodFill(_)));
+
+        // Hunger and food would send the wrapped score looking for food, but they can't happen in
+        // constrictor so they don't change anything
+        let mut hungry = wire_game.clone();
+        hungry.board.food.push(Position { x: 5, y: 5 });
+        hungry.board.snakes[0].health = 10;
+        hungry.you.health = 10;
+
+        assert!(matches!(
+            wrapped_score::<_, _, 4>(&board(&hungry)),
+            Score::LowOnHealth(..)
+        ));
+        assert_eq!(wrapped_constrictor_score::<_, _, 4>(&board(&hungry)), score);
+    }
+
+    #[test]
+    fn test_scores_describe_themselves_for_move_explanations() {
+        assert_eq!(
+            Score::FloodFill(0.62.into()).to_string(),
+            "62% space control"
+        );
+        assert_eq!(
+            Score::LowOnHealth(Some(-3), 0.4.into()).to_string(),
+            "low on health, food in 3; 40% space control"
+        );
+    }
+
+    #[test]
+    fn test_maxn_score_splits_the_board_between_the_snakes() {
+        let game =
+            serde_json::from_str::<Game>(include_str!("../fixtures/start_of_game.json")).unwrap();
+        let id_map = build_snake_id_map(&game);
+        let board = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+
+        let shares = board
+            .get_snake_ids()
+            .iter()
+            .map(|sid| maxn_score::<_, _, 4>(&board, sid))
+            .collect_vec();
+
+        assert!(shares.iter().all(|share| f64::from(*share) > 0.0));
+        let total: f64 = shares.iter().map(|share| f64::from(*share)).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+    }
 }
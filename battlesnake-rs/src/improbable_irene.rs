@@ -1,43 +1,138 @@
 use color_eyre::eyre::eyre;
 
 use std::{
-    borrow::Cow,
-    cell::RefCell,
+    cell::OnceCell,
     convert::TryInto,
-    fs::{create_dir, remove_dir_all, OpenOptions},
-    io::Write,
-    sync::atomic::{AtomicUsize, Ordering},
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
 };
 
-use atomic_float::AtomicF64;
 use battlesnake_game_types::{
     compact_representation::WrappedCellBoard4Snakes11x11, wire_representation::NestedGame,
 };
-use decorum::{Infinite, Real, N64};
-use dotavious::{Dot, Edge, GraphBuilder};
+use battlesnake_minimax::{
+    paranoid::MAX_SHOUT_LENGTH, Instruments, SearchStatus, SearchStepper, SimulationStats,
+};
+use decorum::N64;
 use itertools::Itertools;
-use rand::prelude::ThreadRng;
+use rand::{rngs::StdRng, SeedableRng};
 use tracing::{info, info_span};
 pub use typed_arena::Arena;
 
-use crate::flood_fill::spread_from_head_arcade_maze::{Scores, SpreadFromHead};
+use crate::{
+    a_prime::{APrimeOptions, ClosestFoodCalculable},
+    board_backends::is_11x11,
+    dot_dump::DotDump,
+    flood_fill::spread_from_head_arcade_maze::SpreadFromHead,
+    game_seed::{rng_for, turn_seed, RandomStream},
+    game_store,
+    game_tree::GameTree,
+    mcts::{ExpansionBuffers, Node, RolloutCache, RootProof, Scorable, SnakeOptions},
+    opponent_book::{self, OpponentModel},
+};
 
 use super::*;
 
+/// The most snakes the rollouts can play with, since they only run on the boards for 4 snakes
+pub const MAX_ROLLOUT_SNAKES: usize = 4;
+
+/// The fewest milliseconds we run iterations for, even when the padding eats the whole timeout
+const MIN_SEARCH_MS: u128 = 10;
+
+/// The most nodes we copy out of a search to keep for the next turn, see [GameTree]. The copy is
+/// made before we answer, so past this it costs more time than starting over next turn does
+const MAX_KEPT_NODES: usize = 100_000;
+
+/// What Irene keeps in the [game_store] is kept as this
+const KEPT_AS: &str = "improbable-irene";
+
+fn take_tree<BoardType: 'static>(game_id: &str) -> Option<GameTree<BoardType>> {
+    game_store::take(KEPT_AS, game_id)
+}
+
+fn keep_tree<BoardType: Send + 'static>(game_id: &str, tree: GameTree<BoardType>) {
+    game_store::keep(KEPT_AS, game_id, tree);
+}
+
+/// How many games Irene is keeping a [GameTree] for
+pub fn games_with_trees() -> usize {
+    game_store::games_kept_by(KEPT_AS)
+}
+
 pub struct ImprobableIrene<BoardType> {
     game: BoardType,
     game_info: NestedGame,
     turn: i32,
+    options: SnakeOptions,
+    opponents: OpponentModel,
+    /// The seed for this turn, see [crate::game_seed]. Without one everything random comes from
+    /// entropy
+    seed: Option<u64>,
 }
 
 impl<BoardType> ImprobableIrene<BoardType> {
     pub fn new(game: BoardType, game_info: NestedGame, turn: i32) -> Self {
+        Self::new_with_options(game, game_info, turn, SnakeOptions::default())
+    }
+
+    pub fn new_with_options(
+        game: BoardType,
+        game_info: NestedGame,
+        turn: i32,
+        options: SnakeOptions,
+    ) -> Self {
         Self {
             game,
             game_info,
             turn,
+            options,
+            opponents: OpponentModel::default(),
+            seed: None,
         }
     }
+
+    /// Have the opponents in our rollouts play like the [OpponentModel] expects, instead of
+    /// completely at random
+    pub fn with_opponent_model(self, opponents: OpponentModel) -> Self {
+        Self { opponents, ..self }
+    }
+
+    /// Draw everything random from `seed`, so the same search makes the same decisions every
+    /// time it's run
+    pub fn with_seed(self, seed: u64) -> Self {
+        Self {
+            seed: Some(seed),
+            ..self
+        }
+    }
+
+    fn rng(&self, stream: RandomStream) -> StdRng {
+        match self.seed {
+            Some(seed) => rng_for(seed, stream),
+            None => StdRng::from_entropy(),
+        }
+    }
+
+    /// How long we can keep running iterations for, in milliseconds
+    ///
+    /// A deadline shorter than the network latency padding still leaves [MIN_SEARCH_MS], so we
+    /// get a few iterations in instead of answering from an empty tree
+    fn max_duration(&self) -> u128 {
+        let timeout: u128 = self.game_info.timeout.try_into().unwrap_or_default();
+
+        timeout
+            .saturating_sub(self.options.network_latency_padding.as_millis())
+            .max(MIN_SEARCH_MS)
+    }
+}
+
+/// What a fixed size search thought of our moves, see [ImprobableIrene::search_policy]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchPolicy {
+    /// The move we would play, which is sampled by visits when [SnakeOptions::temperature] is set
+    pub chosen_move: Move,
+    /// How many times the search visited each of our moves, indexed by [Move::as_index]
+    pub visits: [usize; 4],
 }
 
 pub struct ImprobableIreneFactory;
@@ -48,20 +143,45 @@ impl BattlesnakeFactory for ImprobableIreneFactory {
     }
 
     fn create_from_wire_game(&self, game: Game) -> BoxedSnake {
+        // The rollouts only run on 11x11 boards, so Hobbs plays anything else
+        if !is_11x11(&game) {
+            return hovering_hobbs::Factory.create_from_wire_game(game);
+        }
+        // Royale games can have more snakes than the rollouts' boards have room for, and Hobbs
+        // can pick a board with room for up to 8
+        if game.board.snakes.len() > MAX_ROLLOUT_SNAKES {
+            info!(
+                snakes = game.board.snakes.len(),
+                "Too many snakes for the rollouts, Hobbs is playing this turn"
+            );
+            return hovering_hobbs::Factory.create_from_wire_game(game);
+        }
+
         let game_info = game.game.clone();
         let id_map = build_snake_id_map(&game);
         let turn = game.turn;
+        let seed = turn_seed(&game);
 
-        if game_info.ruleset.name == "wrapped" {
+        // The session has to see this turn before the profile is picked, since it's what notices
+        // an opponent timing out
+        opponent_book::observe_request(&game);
+        let opponents = opponent_book::session_model(&game, &id_map);
+        let options = tuning::profile_for(&game).mcts.with_env_overrides();
+
+        if crate::wire_game::is_wrapped(&game_info) {
             let game = WrappedCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
 
-            let snake = ImprobableIrene::new(game, game_info, turn);
+            let snake = ImprobableIrene::new_with_options(game, game_info, turn, options)
+                .with_opponent_model(opponents)
+                .with_seed(seed);
 
             Box::new(snake)
         } else {
             let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
 
-            let snake = ImprobableIrene::new(game, game_info, turn);
+            let snake = ImprobableIrene::new_with_options(game, game_info, turn, options)
+                .with_opponent_model(opponents)
+                .with_seed(seed);
 
             Box::new(snake)
         }
@@ -81,7 +201,7 @@ impl BattlesnakeFactory for ImprobableIreneFactory {
 impl<BoardType> ImprobableIrene<BoardType>
 where
     BoardType: Clone
-        + SimulableGame<Instrument, 4>
+        + SimulableGame<Instruments, 4>
         + PartialEq
         + RandomReasonableMovesGame
         + ReasonableMovesGame
@@ -90,63 +210,179 @@ where
         + 'static
         + SnakeIDGettableGame<SnakeIDType = SnakeId>
         + SpreadFromHead<u8, 4>
+        + ClosestFoodCalculable
+        + HeadGettableGame
         + Clone
         + HazardQueryableGame
-        + YouDeterminableGame,
+        + NeighborDeterminableGame
+        + NeckQueryableGame
+        + SnakeBodyGettableGame
+        + FoodGettableGame
+        + YouDeterminableGame
+        + Hash,
 {
     #[tracing::instrument(
         level = "info",
         skip_all,
-        fields(total_number_of_iterations, total_score, average_score, game_id, turn)
+        fields(
+            total_number_of_iterations,
+            total_score,
+            average_score,
+            game_id,
+            turn,
+            iterations_per_ms,
+            rollout_cache_hits,
+            rollout_cache_hit_rate,
+            rollout_cache_entries,
+            max_tree_depth,
+            capped_leaf_evaluations,
+            simulations,
+            simulation_ms,
+            simulation_share,
+            reused_visits
+        )
     )]
     fn mcts<'arena>(
         &self,
         while_condition: &dyn Fn(&Node<BoardType>, usize) -> bool,
         arena: &'arena mut Arena<Node<'arena, BoardType>>,
+        kept: Option<&GameTree<BoardType>>,
     ) -> &'arena Node<'arena, BoardType> {
         let current_span = tracing::Span::current();
 
-        let mut rng = rand::thread_rng();
-
-        let cloned = self.game.clone();
-        let root_node: &mut Node<BoardType> = arena.alloc(Node::new(cloned));
+        let started_at = std::time::Instant::now();
+        let mut stepper = self.stepper_from(arena, kept);
+        current_span.record("reused_visits", stepper.reused_visits);
 
-        root_node.expand(arena);
+        while while_condition(stepper.root, stepper.iterations) {
+            stepper.iterate();
+        }
 
-        let mut total_number_of_iterations = 0;
+        let root_node = stepper.root;
+        debug_assert_eq!(root_node.audit_statistics(), Vec::<String>::new());
 
-        while while_condition(root_node, total_number_of_iterations) {
-            total_number_of_iterations += 1;
+        let total_number_of_iterations = stepper.iterations;
+        current_span.record("total_number_of_iterations", total_number_of_iterations);
+        current_span.record("total_score", root_node.total_score.load(Ordering::Relaxed));
+        current_span.record("average_score", root_node.average_score());
+        current_span.record("game_id", &self.game_info.id);
+        current_span.record("turn", self.turn);
+        current_span.record(
+            "iterations_per_ms",
+            total_number_of_iterations as f64 / started_at.elapsed().as_millis().max(1) as f64,
+        );
+        current_span.record("rollout_cache_hits", stepper.rollout_cache.hits());
+        current_span.record("rollout_cache_hit_rate", stepper.rollout_cache.hit_rate());
+        current_span.record("rollout_cache_entries", stepper.rollout_cache.entries());
+        current_span.record("max_tree_depth", self.options.max_tree_depth);
+        current_span.record("capped_leaf_evaluations", stepper.capped_leaf_evaluations);
+        stepper.simulation_stats.record(
+            &current_span,
+            Duration::from_millis(self.max_duration() as u64),
+        );
 
-            let mut next_leaf_node = root_node.next_leaf_node(total_number_of_iterations);
+        root_node
+    }
 
-            next_leaf_node = {
-                // If next_leaf_node HAS been visited, then we expand it
-                if next_leaf_node.number_of_visits.load(Ordering::Relaxed) > 0
-                    && !next_leaf_node.has_been_expanded()
-                {
-                    next_leaf_node.expand(arena);
+    /// Start a search that runs on the caller's thread a [MctsStepper::step()] at a time, with its
+    /// tree in `arena`
+    ///
+    /// The root is expanded straight away, so a stepper that's never stepped still knows about
+    /// moves the first turn proves
+    pub fn stepper<'snake, 'arena>(
+        &'snake self,
+        arena: &'arena Arena<Node<'arena, BoardType>>,
+    ) -> MctsStepper<'snake, 'arena, BoardType> {
+        self.stepper_from(arena, None)
+    }
 
-                    next_leaf_node.next_leaf_node(total_number_of_iterations)
-                } else {
-                    next_leaf_node
-                }
-            };
+    /// A [ImprobableIrene::stepper] that carries on from the tree we `kept` from last turn, which
+    /// has to be rooted at our board
+    fn stepper_from<'snake, 'arena>(
+        &'snake self,
+        arena: &'arena Arena<Node<'arena, BoardType>>,
+        kept: Option<&GameTree<BoardType>>,
+    ) -> MctsStepper<'snake, 'arena, BoardType> {
+        let simulation_stats = Arc::new(SimulationStats::default());
+        let instruments = Instruments::recording(simulation_stats.clone());
+        let mut expansion_buffers =
+            ExpansionBuffers::with_noise_rng(self.rng(RandomStream::RootNoise))
+                .recording(instruments.clone());
+
+        let root: &Node<BoardType> = match kept {
+            Some(kept) => {
+                debug_assert!(kept.root_state() == &self.game);
+                kept.grow_into(arena)
+            }
+            None => arena.alloc(Node::new(self.game.clone())),
+        };
+        if !root.has_been_expanded() {
+            root.expand(
+                arena,
+                &self.options,
+                &self.opponents,
+                &mut expansion_buffers,
+            );
+        }
 
-            //Now we do a simulation for this leaf node
-            let score = next_leaf_node.simulate(&mut rng);
+        MctsStepper {
+            snake: self,
+            arena,
+            root,
+            proof: root.root_proof(),
+            rng: self.rng(RandomStream::Rollouts),
+            rollout_cache: RolloutCache::new(self.options.rollout_cache_size),
+            simulation_stats,
+            instruments,
+            expansion_buffers,
+            reused_visits: root.number_of_visits.load(Ordering::Relaxed),
+            iterations: 0,
+            capped_leaf_evaluations: 0,
+        }
+    }
 
-            //We now need to backpropagate the score
-            next_leaf_node.backpropagate(score);
+    /// Last turn's tree for this game, re-rooted on the moves everyone made since. None if we
+    /// didn't keep one, if the search never expanded those moves, or if food spawned so our board
+    /// isn't the one the tree expected
+    fn kept_tree(&self) -> Option<GameTree<BoardType>> {
+        let mut tree = take_tree::<BoardType>(&self.game_info.id)?;
+        let chosen = tree.chosen()?;
+
+        let mut observed = [None; 4];
+        for sid in self.game.get_snake_ids() {
+            if let Some(slot) = observed.get_mut(sid.as_usize()) {
+                *slot = if self.game.is_you(&sid) {
+                    Some(chosen)
+                } else {
+                    self.opponents.last_move(&sid)
+                };
+            }
         }
 
-        current_span.record("total_number_of_iterations", total_number_of_iterations);
-        current_span.record("total_score", root_node.total_score.load(Ordering::Relaxed));
-        current_span.record("average_score", root_node.average_score());
-        current_span.record("game_id", &self.game_info.id);
-        current_span.record("turn", self.turn);
+        let reclaimed = tree.next_turn(&Action::new(observed));
+        let reused = reclaimed.is_some() && tree.root_state() == &self.game;
+        info!(
+            game_id = %self.game_info.id,
+            turn = self.turn,
+            reused,
+            reclaimed_nodes = reclaimed.map(|r| r.nodes),
+            reclaimed_bytes = reclaimed.map(|r| r.bytes),
+            kept_nodes = tree.node_count(),
+            "Re-rooted last turn's tree"
+        );
 
-        root_node
+        reused.then_some(tree)
+    }
+
+    /// Keep what's under the move we're answering with, for [ImprobableIrene::kept_tree] to pick
+    /// up next turn
+    fn keep_for_next_turn(&self, root_node: &Node<BoardType>, chosen: Move)
+    where
+        BoardType: Send,
+    {
+        if let Some(tree) = GameTree::from_search(root_node, chosen, MAX_KEPT_NODES) {
+            keep_tree(&self.game_info.id, tree);
+        }
     }
 
     pub fn mcts_bench<'arena>(
@@ -158,7 +394,34 @@ where
             total_number_of_iterations < max_iterations
         };
 
-        self.mcts(&while_condition, arena)
+        self.mcts(&while_condition, arena, None)
+    }
+
+    /// Search for a fixed number of iterations and report how often each of our moves was
+    /// visited along with the move we would play, which is what the self-play exporter records
+    pub fn search_policy(&self, max_iterations: usize) -> Result<SearchPolicy> {
+        let mut arena = Arena::new();
+        let root_node = self.mcts_bench(max_iterations, &mut arena);
+
+        let mut visits = [0; 4];
+        for child in root_node.children.borrow().iter().flatten() {
+            if let Some(tree_context) = &child.tree_context {
+                visits[tree_context.snake_move.my_move().as_index()] =
+                    child.number_of_visits.load(Ordering::Relaxed);
+            }
+        }
+
+        let chosen_move = root_node
+            .choose_root_child(&self.options, &mut self.rng(RandomStream::TieBreaks))
+            .and_then(|child| child.tree_context.as_ref())
+            .ok_or_else(|| eyre!("The root should have a child"))?
+            .snake_move
+            .my_move();
+
+        Ok(SearchPolicy {
+            chosen_move,
+            visits,
+        })
     }
 
     pub fn graph_move<'arena>(
@@ -168,33 +431,27 @@ where
         info!(player_count =? self.game.get_snake_ids(), "Graphing MCTS");
         let start = std::time::Instant::now();
 
-        const NETWORK_LATENCY_PADDING: i64 = 000;
-        let max_duration = self.game_info.timeout - NETWORK_LATENCY_PADDING;
+        let max_duration = self.max_duration();
 
-        remove_dir_all("/Users/coreyja/Projects/battlesnake-rs/tmp/")?;
-        create_dir("/Users/coreyja/Projects/battlesnake-rs/tmp/")?;
+        let dot_dump = DotDump::new(&self.game_info.id, self.turn);
 
         let while_condition = |root_node: &Node<BoardType>, total_number_of_iterations: usize| {
-            if total_number_of_iterations % 64 == 0 && total_number_of_iterations != 0 {
-                let mut file = OpenOptions::new()
-                    .write(true)
-                    .create(true)
-                    .truncate(true)
-                    .open(format!("/Users/coreyja/Projects/battlesnake-rs/tmp/iteration_{total_number_of_iterations}.dot"))
-                    .unwrap();
-                file.write_all(
-                    format!("{}", root_node.graph(total_number_of_iterations)).as_bytes(),
-                )
-                .unwrap();
-            }
+            dot_dump.iteration(total_number_of_iterations, || {
+                root_node.graph(total_number_of_iterations, &self.options)
+            });
 
-            start.elapsed().as_millis() < max_duration.try_into().unwrap()
+            start.elapsed().as_millis() < max_duration
         };
 
-        let root_node = self.mcts(&while_condition, arena);
+        let root_node = self.mcts(&while_condition, arena, None);
+
+        let total_number_of_iterations = root_node.number_of_visits.load(Ordering::Relaxed);
+        dot_dump.finish(total_number_of_iterations, || {
+            root_node.graph(total_number_of_iterations, &self.options)
+        });
 
         let best_child = root_node
-            .highest_average_score_child()
+            .choose_root_child(&self.options, &mut self.rng(RandomStream::TieBreaks))
             .ok_or_else(|| eyre!("The root should have a child"))?;
         let chosen_move = &best_child
             .tree_context
@@ -213,31 +470,187 @@ where
     }
 }
 
+/// An [ImprobableIrene] search that's driven by its caller, see [ImprobableIrene::stepper]
+///
+/// Every step runs whole iterations until its budget is up, and the tree stays in the arena
+/// between steps, so nothing is thrown away when a step ends
+pub struct MctsStepper<'snake, 'arena, BoardType> {
+    snake: &'snake ImprobableIrene<BoardType>,
+    arena: &'arena Arena<Node<'arena, BoardType>>,
+    root: &'arena Node<'arena, BoardType>,
+    /// Checked once, right after the root is expanded
+    proof: Option<RootProof>,
+    rng: StdRng,
+    rollout_cache: RolloutCache,
+    simulation_stats: Arc<SimulationStats>,
+    instruments: Instruments,
+    expansion_buffers: ExpansionBuffers<BoardType>,
+    /// The visits the root already had from the turns before, when it's a kept [GameTree]
+    reused_visits: usize,
+    iterations: usize,
+    capped_leaf_evaluations: usize,
+}
+
+impl<'snake, 'arena, BoardType> MctsStepper<'snake, 'arena, BoardType>
+where
+    BoardType: Clone
+        + SimulableGame<Instruments, 4>
+        + PartialEq
+        + RandomReasonableMovesGame
+        + ReasonableMovesGame
+        + VictorDeterminableGame
+        + HealthGettableGame
+        + 'static
+        + SnakeIDGettableGame<SnakeIDType = SnakeId>
+        + SpreadFromHead<u8, 4>
+        + ClosestFoodCalculable
+        + HeadGettableGame
+        + Clone
+        + HazardQueryableGame
+        + NeighborDeterminableGame
+        + NeckQueryableGame
+        + SnakeBodyGettableGame
+        + FoodGettableGame
+        + YouDeterminableGame
+        + Hash,
+{
+    /// The root of the tree, which has every iteration so far backpropagated into it
+    pub fn root(&self) -> &'arena Node<'arena, BoardType> {
+        self.root
+    }
+
+    /// Select a leaf, expand it if it's been visited before, roll it out and backpropagate the
+    /// score
+    fn iterate(&mut self) {
+        let options = &self.snake.options;
+        self.iterations += 1;
+        // The exploration terms weigh children against every visit to the root, including the
+        // ones from before the tree was kept
+        let total_visits = self.reused_visits + self.iterations;
+
+        let mut next_leaf_node = self.root.next_leaf_node(total_visits, options);
+
+        next_leaf_node = {
+            // If next_leaf_node HAS been visited, then we expand it
+            if next_leaf_node.number_of_visits.load(Ordering::Relaxed) > 0
+                && !next_leaf_node.has_been_expanded()
+                && !next_leaf_node.is_at_depth_cap(options)
+            {
+                next_leaf_node.expand(
+                    self.arena,
+                    options,
+                    &self.snake.opponents,
+                    &mut self.expansion_buffers,
+                );
+
+                next_leaf_node.next_leaf_node(total_visits, options)
+            } else {
+                next_leaf_node
+            }
+        };
+
+        if next_leaf_node.is_at_depth_cap(options) {
+            self.capped_leaf_evaluations += 1;
+        }
+
+        //Now we do a simulation for this leaf node
+        let score = next_leaf_node.simulate(
+            &mut self.rng,
+            options,
+            &mut self.rollout_cache,
+            &self.snake.opponents,
+            &self.instruments,
+        );
+
+        //We now need to backpropagate the score
+        next_leaf_node.backpropagate(score);
+    }
+}
+
+impl<'snake, 'arena, BoardType> SearchStepper for MctsStepper<'snake, 'arena, BoardType>
+where
+    BoardType: Clone
+        + SimulableGame<Instruments, 4>
+        + PartialEq
+        + RandomReasonableMovesGame
+        + ReasonableMovesGame
+        + VictorDeterminableGame
+        + HealthGettableGame
+        + 'static
+        + SnakeIDGettableGame<SnakeIDType = SnakeId>
+        + SpreadFromHead<u8, 4>
+        + ClosestFoodCalculable
+        + HeadGettableGame
+        + Clone
+        + HazardQueryableGame
+        + NeighborDeterminableGame
+        + NeckQueryableGame
+        + SnakeBodyGettableGame
+        + FoodGettableGame
+        + YouDeterminableGame
+        + Hash,
+{
+    fn step(&mut self, budget: Duration) -> SearchStatus {
+        if self.proof.is_some() || self.root.game_state.is_over() {
+            return SearchStatus::Done;
+        }
+
+        let deadline = std::time::Instant::now() + budget;
+        while std::time::Instant::now() < deadline {
+            self.iterate();
+        }
+
+        SearchStatus::InProgress
+    }
+
+    fn best_move(&self) -> Option<Move> {
+        if let Some(proof) = self.proof {
+            return Some(proof.chosen());
+        }
+        if self.iterations == 0 {
+            return None;
+        }
+
+        let best_child = self.root.highest_average_score_child()?;
+
+        Some(best_child.tree_context.as_ref()?.snake_move.my_move())
+    }
+}
+
 impl<BoardType> BattlesnakeAI for ImprobableIrene<BoardType>
 where
     BoardType: Clone
-        + SimulableGame<Instrument, 4>
+        + SimulableGame<Instruments, 4>
         + PartialEq
         + RandomReasonableMovesGame
         + ReasonableMovesGame
         + VictorDeterminableGame
         + YouDeterminableGame
+        + Send
         + 'static,
-    BoardType: SimulableGame<Instrument, 4>
+    BoardType: SimulableGame<Instruments, 4>
         + SnakeIDGettableGame<SnakeIDType = SnakeId>
         + RandomReasonableMovesGame
         + SpreadFromHead<u8, 4>
+        + ClosestFoodCalculable
+        + HeadGettableGame
         + Clone
         + VictorDeterminableGame
         + HealthGettableGame
         + HazardQueryableGame
-        + YouDeterminableGame,
+        + NeighborDeterminableGame
+        + NeckQueryableGame
+        + SnakeBodyGettableGame
+        + FoodGettableGame
+        + YouDeterminableGame
+        + Hash,
 {
     fn make_move(&self) -> Result<MoveOutput> {
         info_span!(
             "improbable_irene_make_move",
             chosen_move = tracing::field::Empty,
             best_child_average_score = tracing::field::Empty,
+            explanation = tracing::field::Empty,
         )
         .in_scope(|| {
             let ids = self.game.get_snake_ids();
@@ -254,19 +667,41 @@ where
 
             let start = std::time::Instant::now();
 
-            const NETWORK_LATENCY_PADDING: i64 = 120;
-            let max_duration = self.game_info.timeout - NETWORK_LATENCY_PADDING;
+            let max_duration = self.max_duration();
 
+            // The root is expanded before the first iteration, so that's when we can check if the
+            // first turn already decides our move
+            let proof = OnceCell::new();
             let while_condition =
-                |_root_node: &Node<BoardType>, _total_number_of_iterations: usize| {
-                    start.elapsed().as_millis() < max_duration.try_into().unwrap()
+                |root_node: &Node<BoardType>, _total_number_of_iterations: usize| {
+                    proof.get_or_init(|| root_node.root_proof()).is_none()
+                        && start.elapsed().as_millis() < max_duration
                 };
 
+            let kept = self.kept_tree();
             let mut arena = Arena::new();
-            let root_node = self.mcts(&while_condition, &mut arena);
+            let root_node = self.mcts(&while_condition, &mut arena, kept.as_ref());
+            drop(kept);
+
+            if let Some(proof) = proof.get().copied().flatten() {
+                self.keep_for_next_turn(root_node, proof.chosen());
+                let chosen_move = format!("{}", proof.chosen());
+                info!(
+                    proof = ?proof,
+                    proof_depth_turns = 1,
+                    banked_ms = max_duration.saturating_sub(start.elapsed().as_millis()),
+                    "Our move is proven, no need to search"
+                );
+                current_span.record("chosen_move", &chosen_move);
+
+                return Ok(MoveOutput {
+                    r#move: chosen_move,
+                    shout: alternatives_summary(root_node, shout_alternatives()),
+                });
+            }
 
             let best_child = root_node
-                .highest_average_score_child()
+                .choose_root_child(&self.options, &mut self.rng(RandomStream::TieBreaks))
                 .ok_or_else(|| eyre!("The root should have a child"))?;
             let chosen_move = &best_child
                 .tree_context
@@ -274,105 +709,155 @@ where
                 .expect(
                     "We found the best child of the root node, so it _should_ have a tree_context",
                 )
-                .snake_move;
-            let chosen_move = format!("{}", chosen_move.my_move());
+                .snake_move
+                .my_move();
+            self.keep_for_next_turn(root_node, chosen_move);
+            let chosen_move = format!("{chosen_move}");
 
             current_span.record("chosen_move", &chosen_move);
             current_span.record("best_child_average_score", best_child.average_score());
+            current_span.record("explanation", explain_root(root_node).as_str());
 
             Ok(MoveOutput {
                 r#move: chosen_move,
-                shout: None,
+                shout: alternatives_summary(root_node, shout_alternatives()),
             })
         })
     }
 
     fn end(&self) {
-        info!("Mcts has ended");
+        game_store::forget(KEPT_AS, &self.game_info.id);
+        let opponent_shouts = opponent_book::opponent_shouts(&self.game_info.id).len();
+        let predictions = opponent_book::finish_game(&self.game_info.id).unwrap_or_default();
+
+        info!(
+            game_id = %self.game_info.id,
+            opponent_move_predictions = predictions.predictions,
+            opponent_move_prediction_hits = predictions.hits,
+            opponent_move_prediction_hit_rate = predictions.hit_rate(),
+            opponent_shouts,
+            "Mcts has ended"
+        );
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
-enum SomeonesMove {
-    MyMove(Move),
-    OtherMoves(Action<4>),
-}
+/// Our moves at the root, with the average score and visits the search gave each of them, best
+/// first. None if the root was never expanded
+fn ranked_root_moves<BoardType>(root_node: &Node<BoardType>) -> Option<Vec<(String, f64, usize)>> {
+    let children = root_node.children.borrow();
+    let children = children.as_ref()?;
 
-impl SomeonesMove {
-    fn my_move(&self) -> Move {
-        match self {
-            SomeonesMove::MyMove(m) => *m,
-            SomeonesMove::OtherMoves(_) => panic!("We aren't at a my move"),
-        }
-    }
-}
+    let mut ranked: Vec<(String, f64, usize)> = children
+        .iter()
+        .map(|child| {
+            let m = child
+                .tree_context
+                .as_ref()
+                .map(|c| format!("{}", c.snake_move.my_move()))
+                .unwrap_or_default();
+            let visits = child.number_of_visits.load(Ordering::Relaxed);
+            let average = if visits == 0 {
+                f64::MIN
+            } else {
+                child.total_score.load(Ordering::Relaxed) / visits as f64
+            };
 
-#[derive(Debug)]
-struct TreeContext<'arena, T> {
-    parent: RefCell<&'arena Node<'arena, T>>,
-    snake_move: SomeonesMove,
-}
+            (m, average, visits)
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
 
-#[derive(Debug)]
-pub struct Node<'arena, T> {
-    game_state: T,
-    total_score: AtomicF64,
-    sum_of_square_scores: AtomicF64,
-    number_of_visits: AtomicUsize,
-    children: RefCell<Option<Vec<&'arena Node<'arena, T>>>>,
-    tree_context: Option<TreeContext<'arena, T>>,
-    depth: usize,
+    Some(ranked)
 }
 
-#[derive(Debug)]
-pub struct Instrument {}
-impl SimulatorInstruments for Instrument {
-    fn observe_simulation(&self, _duration: std::time::Duration) {
-        //No-oping here
+/// Describe how our moves compared at the root, from the visits and scores the search gave them
+///
+/// For example `chose Up: 0.62 avg over 1400 visits; next best Left: 0.55 avg over 300 visits`
+fn explain_root<BoardType>(root_node: &Node<BoardType>) -> String {
+    let Some(ranked) = ranked_root_moves(root_node) else {
+        return "the root was never expanded".to_owned();
+    };
+
+    let describe = |(m, average, visits): &(String, f64, usize)| {
+        if *visits == 0 {
+            format!("{m}: never visited")
+        } else {
+            format!("{m}: {average:.2} avg over {visits} visits")
+        }
+    };
+
+    match ranked.as_slice() {
+        [] => "there were no moves to choose from".to_owned(),
+        [chosen] => format!("chose {}; it was the only move", describe(chosen)),
+        [chosen, next_best, ..] => format!(
+            "chose {}; next best {}",
+            describe(chosen),
+            describe(next_best)
+        ),
     }
 }
 
-impl<'arena, T> Node<'arena, T> {
-    fn new(game_state: T) -> Self {
-        Self {
-            game_state,
-            total_score: AtomicF64::new(0.0),
-            sum_of_square_scores: AtomicF64::new(0.0),
-            number_of_visits: AtomicUsize::new(0),
-            children: RefCell::new(None),
-            tree_context: None,
-            depth: 0,
-        }
+/// The best `count` of our moves at the root with their average scores and visits, short enough
+/// for a shout, like `up: 0.62 (1400) | left: 0.55 (300)`. None when `count` is 0, see
+/// [shout_alternatives]
+fn alternatives_summary<BoardType>(root_node: &Node<BoardType>, count: usize) -> Option<String> {
+    if count == 0 {
+        return None;
     }
 
-    fn new_with_parent(game_state: T, parent: &'arena Self, r#move: SomeonesMove) -> Self {
-        Self {
-            game_state,
-            total_score: AtomicF64::new(0.0),
-            sum_of_square_scores: AtomicF64::new(0.0),
-            number_of_visits: AtomicUsize::new(0),
-            children: RefCell::new(None),
-            tree_context: Some(TreeContext {
-                parent: RefCell::new(parent),
-                snake_move: r#move,
-            }),
-            depth: parent.depth + 1,
-        }
-    }
+    let summary = ranked_root_moves(root_node)?
+        .iter()
+        .take(count)
+        .map(|(m, average, visits)| {
+            if *visits == 0 {
+                format!("{m}: unvisited")
+            } else {
+                format!("{m}: {average:.2} ({visits})")
+            }
+        })
+        .join(" | ");
+
+    Some(summary.chars().take(MAX_SHOUT_LENGTH).collect())
 }
 
-pub trait Scorable<BoardType> {
-    type ScoreType;
+/// Irene only measures the walk to food, to see how much health it would cost, with a step over
+/// food or hazard costing one extra
+const A_PRIME_OPTIONS: APrimeOptions = APrimeOptions {
+    food_penalty: 1,
+    hazard_penalty: 1,
+};
+
+/// How long we can keep going on the health we have, from 0 to 1
+///
+/// This is the health we'd have left after walking to the closest food we can reach. With no food
+/// in reach it's just our health, since that's all we have. A rollout that ends with us at 3
+/// health and food 5 moves away scores 0 here, even if we have plenty of space
+fn health_margin<BoardType>(node: &BoardType) -> f64
+where
+    BoardType: ClosestFoodCalculable
+        + HeadGettableGame
+        + HealthGettableGame
+        + YouDeterminableGame
+        + SnakeIDGettableGame<SnakeIDType = SnakeId>,
+{
+    let me = node.you_id();
+    let health = node.get_health_i64(me) as f64;
+    let food_distance = node
+        .dist_to_closest_food(&node.get_head_as_native_position(me), &A_PRIME_OPTIONS)
+        .unwrap_or(0) as f64;
 
-    fn score(board: &BoardType) -> Self::ScoreType;
+    ((health - food_distance) / 100.0).clamp(0.0, 1.0)
 }
 
 impl<'arena, BoardType> Scorable<BoardType> for Node<'arena, BoardType>
 where
-    BoardType: SimulableGame<Instrument, 4>
+    BoardType: SimulableGame<Instruments, 4>
         + SnakeIDGettableGame<SnakeIDType = SnakeId>
         + RandomReasonableMovesGame
         + SpreadFromHead<u8, 4>
+        + ClosestFoodCalculable
+        + HeadGettableGame
+        + HealthGettableGame
         + Clone
         + VictorDeterminableGame
         + HazardQueryableGame
@@ -380,12 +865,8 @@ where
 {
     type ScoreType = N64;
 
-    fn score(node: &BoardType) -> N64 {
-        let scores = Scores {
-            food: 5,
-            hazard: 1,
-            empty: 5,
-        };
+    fn score(node: &BoardType, options: &SnakeOptions) -> N64 {
+        let scores = options.flood_fill_scores;
 
         let me = node.you_id();
 
@@ -406,603 +887,46 @@ where
 
             let my_space: f64 = square_counts[me.as_usize()] as f64;
             let total_space: f64 = square_counts.iter().sum::<u16>() as f64;
+            let space = my_space / total_space;
 
-            N64::from(my_space / total_space)
-        }
-    }
-}
-
-impl<'arena, BoardType> Node<'arena, BoardType>
-where
-    BoardType: SimulableGame<Instrument, 4>
-        + SnakeIDGettableGame<SnakeIDType = SnakeId>
-        + HealthGettableGame
-        + RandomReasonableMovesGame
-        + ReasonableMovesGame
-        + Clone
-        + VictorDeterminableGame
-        + YouDeterminableGame,
-    Node<'arena, BoardType>: Scorable<BoardType, ScoreType = N64>,
-{
-    fn simulate(&self, rng: &mut ThreadRng) -> N64 {
-        let mut current_state: Cow<BoardType> = Cow::Borrowed(&self.game_state);
-        let mut number_of_iterations = 0;
-
-        while number_of_iterations < 25 && !current_state.is_over() {
-            number_of_iterations += 1;
-
-            let random_moves = current_state
-                .random_reasonable_move_for_each_snake(rng)
-                .map(|(sid, mv)| (sid, [mv]));
-
-            let next_state = {
-                let mut simulation_result =
-                    current_state.simulate_with_moves(&Instrument {}, random_moves);
-
-                // TODO: This unwrap might NOT be safe
-                simulation_result.next().unwrap().1
-            };
-
-            current_state = Cow::Owned(next_state);
-        }
-
-        Self::score(current_state.as_ref())
-    }
-
-    fn has_been_expanded(&self) -> bool {
-        self.children.borrow().is_some()
-    }
-
-    #[allow(dead_code)]
-    fn ucb1_score(&self, total_number_of_iterations: usize) -> N64 {
-        let constant: N64 = 2.0.into();
-
-        // TODO: This should be fine when we are single threaded
-        // But if/when we get to multi-threaded, we might want to think about if this wants
-        // to use the same visits value like this.
-        // Or do we need to re-load it for each usage?
-        let number_of_visits = self.number_of_visits.load(Ordering::Relaxed);
-        let total_score = self.total_score.load(Ordering::Relaxed);
-        let total_score: N64 = total_score.into();
-
-        if number_of_visits == 0 {
-            return N64::INFINITY;
-        }
-
-        let number_of_visits = number_of_visits as f64;
-        let number_of_visits: N64 = number_of_visits.into();
-
-        let average_score = total_score / number_of_visits;
-        let total_number_of_iterations: N64 = (total_number_of_iterations as f64).into();
-
-        let ln_total_number_of_iterations = total_number_of_iterations.ln();
-
-        let right_hand_side = constant * (ln_total_number_of_iterations / number_of_visits).sqrt();
-
-        average_score + right_hand_side
-    }
-
-    fn ucb1_normal_score(&self, total_number_of_iterations: usize) -> N64 {
-        let constant: N64 = 16.0.into();
-
-        let number_of_visits = self.number_of_visits.load(Ordering::Relaxed);
-        let total_score = self.total_score.load(Ordering::Relaxed);
-        let total_score: N64 = total_score.into();
-        let some_of_squares: N64 = self.sum_of_square_scores.load(Ordering::Relaxed).into();
-
-        let number_of_visits = number_of_visits as f64;
-
-        // TODO: Future Optimization
-        // We could re-work the surrounding code to do this eagerly so that we don't waste time on
-        // doing the rest of the math if we find a branch that matches this
-        if number_of_visits <= 8.0 * ((total_number_of_iterations) as f64).ln() {
-            return N64::INFINITY;
-        }
-
-        let number_of_visits: N64 = number_of_visits.into();
-
-        let average_score = total_score / number_of_visits;
-        let total_number_of_iterations_minus_one: N64 =
-            ((total_number_of_iterations - 1) as f64).into();
-
-        let ln_total_number_of_iterations_minus_one = total_number_of_iterations_minus_one.ln();
-
-        let first_fraction = (some_of_squares - (number_of_visits * average_score.powi(2)))
-            / (number_of_visits - 1.0);
-        let second_fraction = ln_total_number_of_iterations_minus_one / number_of_visits;
-
-        let right_hand_side = (constant * first_fraction * second_fraction).sqrt();
-
-        average_score + right_hand_side
-    }
-
-    fn average_score(&self) -> Option<f64> {
-        let number_of_visits = self.number_of_visits.load(Ordering::Relaxed);
-        let total_score = self.total_score.load(Ordering::Relaxed);
-
-        if number_of_visits == 0 {
-            return None;
-        }
-
-        let number_of_visits = number_of_visits as f64;
-
-        let average_score = total_score / number_of_visits;
-        Some(average_score)
-    }
-
-    fn next_leaf_node(
-        &'arena self,
-        total_number_of_iterations: usize,
-    ) -> &'arena Node<'arena, BoardType> {
-        let mut best_node: &'arena Node<'arena, BoardType> = self;
-
-        while best_node.has_been_expanded() {
-            if let Some(next) = best_node.next_child_to_explore(total_number_of_iterations) {
-                best_node = next;
-            } else {
-                break;
+            let weight = options.health_weight;
+            if weight == 0.0 {
+                return N64::from(space);
             }
-        }
-
-        best_node
-    }
-
-    fn next_child_to_explore(
-        &self,
-        total_number_of_iterations: usize,
-    ) -> Option<&'arena Node<BoardType>> {
-        debug_assert!(self.has_been_expanded());
-
-        let borrowed = self.children.borrow();
-        let children = borrowed
-            .as_ref()
-            .expect("We debug asserts that we are expanded already");
-
-        children
-            .iter()
-            .cloned()
-            .max_by_key(|child| child.ucb1_normal_score(total_number_of_iterations))
-    }
-
-    fn highest_average_score_child(&self) -> Option<&'arena Node<BoardType>> {
-        debug_assert!(self.has_been_expanded());
-        let borrowed = self.children.borrow();
-        let children = borrowed
-            .as_ref()
-            .expect("We debug asserts that we are expanded already");
-
-        children
-            .iter()
-            .cloned()
-            .max_by_key(|child| child.average_score().map(N64::from))
-    }
-
-    fn expand(&'arena self, arena: &'arena Arena<Node<'arena, BoardType>>) {
-        debug_assert!(!self.has_been_expanded());
-
-        if self.game_state.is_over() {
-            self.children.replace(Some(vec![]));
-
-            return;
-        }
-
-        let moves_to_sim = self.game_state.reasonable_moves_for_each_snake();
-        let next_states = self
-            .game_state
-            .simulate_with_moves(&Instrument {}, moves_to_sim)
-            .collect_vec();
-
-        let mut opponent_moves: [Option<Vec<(Action<4>, BoardType)>>; 4] = Default::default();
-        for (actions, game_state) in next_states {
-            let own_move = actions.own_move();
-            if opponent_moves[own_move.as_index()].is_none() {
-                opponent_moves[own_move.as_index()] = Some(vec![]);
-            }
-            opponent_moves[own_move.as_index()]
-                .as_mut()
-                .unwrap()
-                .push((actions, game_state));
-        }
-
-        let mut children: Vec<&'arena _> = Vec::with_capacity(4);
-        for (own_move, next_states) in opponent_moves
-            .into_iter()
-            .enumerate()
-            .filter_map(|(own_move, next_states)| next_states.map(|n| (own_move, n)))
-        {
-            let own_move = Move::from_index(own_move);
-            // TODO: Passing `game_state` here is WRONG
-            // Really self move nodes can't have a game state, since it depends on the opponent
-            // moves too. We are keeping the 'old' one around here since our types can't model
-            // the real shape of the tree
-            let new_node: &'arena _ = arena.alloc(Node::new_with_parent(
-                self.game_state.clone(),
-                self,
-                SomeonesMove::MyMove(own_move),
-            ));
-            children.push(new_node);
-
-            let new_node_children: Vec<&'arena _> = next_states
-                .into_iter()
-                .map(|next_state| {
-                    let newer_node = arena.alloc(Node::new_with_parent(
-                        next_state.1,
-                        new_node,
-                        SomeonesMove::OtherMoves(next_state.0),
-                    ));
-
-                    &*newer_node
-                })
-                .collect_vec();
-
-            new_node.children.replace(Some(new_node_children));
-        }
-
-        debug_assert!(self.children.borrow().is_none());
 
-        self.children.replace(Some(children));
-    }
-
-    fn backpropagate(&self, score: N64) {
-        self.number_of_visits.fetch_add(1, Ordering::Relaxed);
-        {
-            let score: f64 = score.into();
-            self.total_score.fetch_add(score, Ordering::Relaxed);
-            self.sum_of_square_scores
-                .fetch_add(score.powi(2), Ordering::Relaxed);
-        }
-
-        if let Some(tree_context) = &self.tree_context {
-            tree_context.parent.borrow().backpropagate(score)
+            N64::from((1.0 - weight) * space + weight * health_margin(node))
         }
     }
-
-    fn graph(&self, total_number_of_iterations: usize) -> Dot {
-        let mut builder = GraphBuilder::new_named_directed("example");
-        self.graph_with(&mut builder, 0, vec![], total_number_of_iterations);
-
-        let graph = builder.build().unwrap();
-        Dot { graph }
-    }
-
-    // Takes in a builder and adds itself and all children as nodes in the graph
-    // Returns a string that corresponds to the name of the current node
-    fn graph_with(
-        &self,
-        builder: &mut GraphBuilder,
-        depth: usize,
-        child_id: Vec<usize>,
-        total_number_of_iterations: usize,
-    ) -> String {
-        // TODO: Submit a clippy bug report for this
-        #[allow(clippy::useless_asref)]
-        let me_id: String = format!(
-            "Depth: {depth}\nChild ID: {:?}\nMove: {:?}\nTotal Score: {:?}\nVisits: {:?}\nUCB1: {}\nAvg Score: {:?}\nIs Over: {:?}",
-            child_id,
-            &self.tree_context.as_ref().map(|t| t.snake_move.clone()),
-            self.total_score,
-            self.number_of_visits,
-            self.ucb1_normal_score(total_number_of_iterations),
-            self.average_score(),
-            self.game_state.is_over()
-        );
-
-        builder.add_node(dotavious::Node::new(me_id.as_str()));
-
-        let borrow = self.children.borrow();
-        let children = borrow.as_ref();
-
-        if let Some(children) = children {
-            for (i, child) in children.iter().enumerate() {
-                let mut new_child_id = child_id.clone();
-                new_child_id.push(i);
-                let child_id =
-                    child.graph_with(builder, depth + 1, new_child_id, total_number_of_iterations);
-
-                builder.add_edge(Edge::new(me_id.as_str(), child_id.as_str()));
-            }
-        }
-
-        me_id
-    }
 }
 
 #[cfg(test)]
 mod test {
+    use std::time::Duration;
 
-    use battlesnake_game_types::compact_representation::standard::CellBoard4Snakes11x11;
-    use decorum::Infinite;
-    use itertools::Itertools;
+    use battlesnake_game_types::{
+        compact_representation::standard::CellBoard4Snakes11x11, wire_representation::Position,
+    };
 
     use super::*;
 
-    #[test]
-    fn test_ucb1_empty_score() {
-        let fixture = include_str!("../fixtures/start_of_game.json");
-        let game = serde_json::from_str::<Game>(fixture).unwrap();
-        let id_map = build_snake_id_map(&game);
-        let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
-        let n = Node::new(game);
-
-        assert_eq!(n.ucb1_score(1), N64::INFINITY);
-        assert_eq!(n.ucb1_score(0), N64::INFINITY);
-    }
-
-    #[test]
-    fn test_ucb1_non_empty() {
-        let fixture = include_str!("../fixtures/start_of_game.json");
-        let game = serde_json::from_str::<Game>(fixture).unwrap();
-        let id_map = build_snake_id_map(&game);
-        let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
-
-        let n = Node::new(game);
-        n.number_of_visits.store(1, Ordering::Relaxed);
-        n.total_score.store(10.0, Ordering::Relaxed);
-
-        assert_eq!(n.ucb1_score(1), 10.0);
-        assert!(n.ucb1_score(2) > 11.6);
-        assert!(n.ucb1_score(2) < 11.7);
-    }
-
-    #[test]
-    fn test_average_empty_score() {
-        let fixture = include_str!("../fixtures/start_of_game.json");
-        let game = serde_json::from_str::<Game>(fixture).unwrap();
-        let id_map = build_snake_id_map(&game);
-        let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
-        let n = Node::new(game);
-
-        assert_eq!(n.average_score(), None);
-    }
-
-    #[test]
-    fn test_average_non_empty() {
-        let fixture = include_str!("../fixtures/start_of_game.json");
-        let game = serde_json::from_str::<Game>(fixture).unwrap();
-        let id_map = build_snake_id_map(&game);
-        let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
-
-        let n = Node::new(game);
-        n.number_of_visits.store(1, Ordering::Relaxed);
-        n.total_score.store(10.0, Ordering::Relaxed);
-
-        assert_eq!(n.average_score(), Some(10.0));
-
-        n.number_of_visits.store(2, Ordering::Relaxed);
-        n.total_score.store(25.0, Ordering::Relaxed);
-
-        assert_eq!(n.average_score(), Some(12.5));
-    }
-
-    #[test]
-    fn test_backpropagate_root() {
-        let fixture = include_str!("../fixtures/start_of_game.json");
-        let game = serde_json::from_str::<Game>(fixture).unwrap();
-        let id_map = build_snake_id_map(&game);
-        let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
-        let n = Node::new(game);
-
-        n.backpropagate(10.0.into());
-
-        assert_eq!(n.number_of_visits.load(Ordering::Relaxed), 1);
-        assert_eq!(n.total_score.load(Ordering::Relaxed), 10.0);
-    }
-
-    #[test]
-    fn test_backpropagate_first_child() {
-        let fixture = include_str!("../fixtures/start_of_game.json");
-        let game = serde_json::from_str::<Game>(fixture).unwrap();
-        let id_map = build_snake_id_map(&game);
-        let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
-
-        let root = Node::new(game);
-
-        let child = Node::new_with_parent(game, &root, SomeonesMove::MyMove(Move::Up));
-
-        child.backpropagate(10.0.into());
-
-        assert_eq!(child.number_of_visits.load(Ordering::Relaxed), 1);
-        assert_eq!(child.total_score.load(Ordering::Relaxed), 10.0);
-
-        assert_eq!(root.number_of_visits.load(Ordering::Relaxed), 1);
-        assert_eq!(root.total_score.load(Ordering::Relaxed), 10.0);
-
-        let other_child = Node::new_with_parent(game, &root, SomeonesMove::MyMove(Move::Down));
-        other_child.backpropagate(20.0.into());
-
-        assert_eq!(other_child.number_of_visits.load(Ordering::Relaxed), 1);
-        assert_eq!(other_child.total_score.load(Ordering::Relaxed), 20.0);
-
-        assert_eq!(root.number_of_visits.load(Ordering::Relaxed), 2);
-        assert_eq!(root.total_score.load(Ordering::Relaxed), 30.0);
-    }
-
-    #[test]
-    fn test_board_repr() {
-        // This test was a sanity check to make sure the Board knew I died when running into my
-        // tail
-        // For somet reason MCTS is sometimes saying thats the best move, even though its instant
-        // death for me
-        let fixture = include_str!("../fixtures/check_board_doubled_up.json");
-        let game = serde_json::from_str::<Game>(fixture).unwrap();
-        let id_map = build_snake_id_map(&game);
-        let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
-
-        let you_id = game.you_id();
-        let body = game.get_snake_body_vec(you_id);
-
-        assert_eq!(body.len(), 5);
-
-        let other_id = game
-            .get_snake_ids()
-            .into_iter()
-            .find(|&id| id != *you_id)
-            .unwrap();
-
-        let result: Vec<_> = game
-            .simulate_with_moves(
-                &Instrument {},
-                [(*you_id, vec![Move::Up]), (other_id, vec![Move::Down])],
-            )
-            .collect();
-        assert_eq!(result.len(), 1);
-        let (_, new_state) = result[0];
-
-        assert!(new_state.is_over());
-        assert_eq!(new_state.get_winner(), Some(other_id));
-    }
-
-    #[test]
-    fn test_basic_expand() {
-        let game =
-            serde_json::from_str::<Game>(include_str!("../fixtures/start_of_game.json")).unwrap();
-
-        let id_map = build_snake_id_map(&game);
-        let game = CellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
-
-        let arena = Arena::new();
-        let root_node = arena.alloc(Node::new(game));
-
-        assert!(!root_node.has_been_expanded());
-
-        root_node.expand(&arena);
-
-        assert!(root_node.has_been_expanded());
-
-        let children = root_node.children.borrow();
-        let children = children.as_ref().unwrap();
-        assert_eq!(children.len(), 4);
-
-        let my_moves = children
-            .iter()
-            .map(|child| child.tree_context.as_ref().unwrap().snake_move.clone())
-            .collect::<Vec<_>>();
-
-        for m in Move::all() {
-            assert!(my_moves.contains(&SomeonesMove::MyMove(m)));
-        }
-
-        for child in children {
-            let m = child.tree_context.as_ref().unwrap().snake_move.clone();
-            let m = m.my_move();
-
-            let opponent_moves = child
-                .children
-                .borrow()
-                .as_ref()
-                .unwrap()
-                .iter()
-                .map(|child| child.tree_context.as_ref().unwrap().snake_move.clone())
-                .map(|m| {
-                    if let SomeonesMove::OtherMoves(m) = m {
-                        m
-                    } else {
-                        panic!("Expected an opponents move");
-                    }
-                })
-                .collect::<Vec<_>>();
-
-            for v in Move::all_iter().permutations(2) {
-                let mut actions: [Option<Move>; 4] = Default::default();
-                actions[0] = Some(m);
-                actions[1] = Some(v[0]);
-                actions[2] = Some(v[1]);
-                let actions = Action::new(actions);
-
-                dbg!(&opponent_moves, &actions);
-                assert!(opponent_moves.contains(&actions));
-            }
-
-            let inner_childer = child.children.borrow();
-            let inner_childer = inner_childer.as_ref().unwrap();
-            assert_eq!(inner_childer.len(), 16);
-        }
-    }
-
-    #[test]
-    fn test_less_basic_expand() {
-        let game = serde_json::from_str::<Game>(include_str!(
-            "../../fixtures/less_basic_expand_mcts.json"
-        ))
-        .unwrap();
-
-        let id_map = build_snake_id_map(&game);
-        let game = CellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
-
-        let arena = Arena::new();
-        let root_node = arena.alloc(Node::new(game));
-
-        assert!(!root_node.has_been_expanded());
-
-        root_node.expand(&arena);
-
-        assert!(root_node.has_been_expanded());
-
-        let children = root_node.children.borrow();
-        let children = children.as_ref().unwrap();
-        assert_eq!(children.len(), 2);
-
-        let my_moves = children
-            .iter()
-            .map(|child| child.tree_context.as_ref().unwrap().snake_move.clone())
-            .collect::<Vec<_>>();
-
-        for m in [Move::Up, Move::Down] {
-            assert!(my_moves.contains(&SomeonesMove::MyMove(m)));
-        }
-
-        for child in children {
-            let m = child.tree_context.as_ref().unwrap().snake_move.clone();
-            let m = m.my_move();
-
-            let opponent_moves = child
-                .children
-                .borrow()
-                .as_ref()
-                .unwrap()
-                .iter()
-                .map(|child| child.tree_context.as_ref().unwrap().snake_move.clone())
-                .map(|m| {
-                    if let SomeonesMove::OtherMoves(m) = m {
-                        m
-                    } else {
-                        panic!("Expected an opponents move");
-                    }
-                })
-                .collect::<Vec<_>>();
-
-            for v in [Move::Right, Move::Down].iter() {
-                let mut actions: [Option<Move>; 4] = Default::default();
-                actions[0] = Some(m);
-                actions[1] = Some(*v);
-                let actions = Action::new(actions);
-
-                dbg!(&opponent_moves, &actions);
-                assert!(opponent_moves.contains(&actions));
-            }
-
-            let inner_childer = child.children.borrow();
-            let inner_childer = inner_childer.as_ref().unwrap();
-            assert_eq!(inner_childer.len(), 2);
-        }
-    }
-
     // ----------------- FIXTURE TESTS DOWN BELOW -----------------
 
     fn test_fixture(fixture: &'static str, allowed_moves: Vec<Move>) {
-        const NETWORK_LATENCY_PADDING: i64 = 50;
+        let options = SnakeOptions {
+            network_latency_padding: Duration::from_millis(50),
+            ..Default::default()
+        };
 
         let game = serde_json::from_str::<Game>(fixture).unwrap();
 
         let game_info = game.game.clone();
         let id_map = build_snake_id_map(&game);
-        let max_duration = game_info.timeout - NETWORK_LATENCY_PADDING;
-        let max_duration = max_duration.try_into().unwrap();
         // let max_duration = Duration::from_secs(5).as_millis();
 
         let game = CellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
 
-        let snake = ImprobableIrene::new(game, game_info, 0);
+        let snake = ImprobableIrene::new_with_options(game, game_info, 0, options);
+        let max_duration = snake.max_duration();
 
         let start = std::time::Instant::now();
 
@@ -1010,7 +934,7 @@ mod test {
             start.elapsed().as_millis() < max_duration
         };
         let mut arena = Arena::new();
-        let root_node = snake.mcts(&while_condition, &mut arena);
+        let root_node = snake.mcts(&while_condition, &mut arena, None);
 
         let best_child = root_node
             .highest_average_score_child()
@@ -1029,7 +953,7 @@ mod test {
             .iter()
             .map(|n| (
                 n.average_score(),
-                n.ucb1_normal_score(total_iterations),
+                n.ucb1_normal_score(total_iterations, &snake.options),
                 n.number_of_visits.load(Ordering::Relaxed),
                 n.tree_context.as_ref().unwrap().snake_move.clone(),
                 // n.children
@@ -1055,25 +979,47 @@ mod test {
 
     #[allow(unused)]
     fn test_fixture_wrapped(fixture: &'static str, allowed_moves: Vec<Move>) {
+        let options = SnakeOptions {
+            network_latency_padding: Duration::from_millis(400),
+            ..Default::default()
+        };
+
+        test_fixture_wrapped_with_options(fixture, allowed_moves, options);
+    }
+
+    /// Runs the fixture with the options from the tuning profile the factory would pick for it
+    fn test_fixture_wrapped_with_profile(fixture: &'static str, allowed_moves: Vec<Move>) {
+        let game = serde_json::from_str::<Game>(fixture).unwrap();
+        let options = SnakeOptions {
+            network_latency_padding: Duration::from_millis(400),
+            ..tuning::profile_for(&game).mcts
+        };
+
+        test_fixture_wrapped_with_options(fixture, allowed_moves, options);
+    }
+
+    fn test_fixture_wrapped_with_options(
+        fixture: &'static str,
+        allowed_moves: Vec<Move>,
+        options: SnakeOptions,
+    ) {
         let game = serde_json::from_str::<Game>(fixture).unwrap();
 
         let game_info = game.game.clone();
         let id_map = build_snake_id_map(&game);
-        let max_duration = game_info.timeout - NETWORK_LATENCY_PADDING;
 
         let game = WrappedCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
 
-        let snake = ImprobableIrene::new(game, game_info, 0);
+        let snake = ImprobableIrene::new_with_options(game, game_info, 0, options);
+        let max_duration = snake.max_duration();
 
         let start = std::time::Instant::now();
 
-        const NETWORK_LATENCY_PADDING: i64 = 400;
-
         let while_condition = |_root_node: &Node<_>, _total_number_of_iterations: usize| {
-            start.elapsed().as_millis() < max_duration.try_into().unwrap()
+            start.elapsed().as_millis() < max_duration
         };
         let mut arena = Arena::new();
-        let root_node = snake.mcts(&while_condition, &mut arena);
+        let root_node = snake.mcts(&while_condition, &mut arena, None);
 
         let best_child = root_node
             .highest_average_score_child()
@@ -1092,7 +1038,7 @@ mod test {
             .iter()
             .map(|n| (
                 n.average_score(),
-                n.ucb1_normal_score(total_iterations),
+                n.ucb1_normal_score(total_iterations, &snake.options),
                 n.number_of_visits.load(Ordering::Relaxed),
                 n.tree_context.as_ref().unwrap().snake_move.clone(),
                 // n.children
@@ -1179,6 +1125,28 @@ mod test {
         test_fixture_wrapped(fixture, vec![Move::Up, Move::Right]);
     }
 
+    #[test]
+    fn test_duel_profile_b6a045ae_abf2_4f6f_b04c_a80ace7881b4_399() {
+        let fixture = include_str!("../../fixtures/b6a045ae-abf2-4f6f-b04c-a80ace7881b4_399.json");
+
+        test_fixture_wrapped_with_profile(fixture, vec![Move::Up]);
+    }
+
+    #[test]
+    fn test_duel_profile_c2aee0d9_30dc_47ee_bd25_38e67e0fee9d_96() {
+        let fixture = include_str!("../../fixtures/c2aee0d9-30dc-47ee-bd25-38e67e0fee9d_96.json");
+
+        test_fixture_wrapped_with_profile(fixture, vec![Move::Up, Move::Right]);
+    }
+
+    #[test]
+    fn test_duel_profile_4f198c01_d613_4109_b8b9_226208cde009_505() {
+        // A late hazard islands duel where going left walks into the smaller half of the board
+        let fixture = include_str!("../../fixtures/4f198c01-d613-4109-b8b9-226208cde009_505.json");
+
+        test_fixture_wrapped_with_profile(fixture, vec![Move::Right]);
+    }
+
     #[test]
     #[ignore]
     fn test_move_7311099d_b98a_4589_9b05_32dc80362bcc_135() {
@@ -1196,4 +1164,130 @@ mod test {
 
         test_fixture(fixture, vec![Move::Down]);
     }
+
+    fn rollout_score_with_health(health: i32, options: &SnakeOptions) -> N64 {
+        let mut game: Game =
+            serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap();
+        let you_id = game.you.id.clone();
+        for snake in game.board.snakes.iter_mut().filter(|s| s.id == you_id) {
+            snake.health = health;
+        }
+        game.you.health = health;
+
+        let id_map = build_snake_id_map(&game);
+        let board = CellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+
+        Node::<CellBoard4Snakes11x11>::score(&board, options)
+    }
+
+    #[test]
+    fn test_rollouts_ending_low_on_health_score_worse() {
+        let options = SnakeOptions::default();
+
+        assert!(rollout_score_with_health(3, &options) < rollout_score_with_health(100, &options));
+
+        let space_only = SnakeOptions {
+            health_weight: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(
+            rollout_score_with_health(3, &space_only),
+            rollout_score_with_health(100, &space_only)
+        );
+    }
+
+    #[test]
+    fn test_hobbs_plays_games_with_too_many_snakes_for_the_rollouts() {
+        let mut game: Game =
+            serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap();
+        for (i, (x, y)) in [(9, 1), (1, 9), (9, 9)].into_iter().enumerate() {
+            let mut extra = game.board.snakes[2].clone();
+            extra.id = format!("extra-{i}");
+            extra.head = Position { x, y };
+            extra.body = [Position { x, y }; 3].into_iter().collect();
+            game.board.snakes.push(extra);
+        }
+        assert!(game.board.snakes.len() > MAX_ROLLOUT_SNAKES);
+
+        let output = ImprobableIreneFactory
+            .create_from_wire_game(game.clone())
+            .make_move()
+            .unwrap();
+        let m = Move::all_iter()
+            .find(|m| m.to_string() == output.r#move)
+            .expect("Irene should return a real move");
+
+        assert!(!move_guard::is_fatal(&game, &game.you, m));
+    }
+
+    #[test]
+    fn test_deadlines_shorter_than_the_padding_still_get_a_move() {
+        let mut game: Game =
+            serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap();
+        game.game.timeout = 50;
+        let game_info = game.game.clone();
+        let id_map = build_snake_id_map(&game);
+        let board = CellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+
+        let snake = ImprobableIrene::new(board, game_info, 0).with_seed(7);
+        assert_eq!(snake.max_duration(), MIN_SEARCH_MS);
+
+        let output = snake.make_move().unwrap();
+        assert!(Move::all_iter().any(|m| m.to_string() == output.r#move));
+    }
+
+    #[test]
+    fn test_the_stepper_keeps_its_tree_between_steps() {
+        let game: Game =
+            serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap();
+        let game_info = game.game.clone();
+        let id_map = build_snake_id_map(&game);
+        let board = CellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+
+        let snake = ImprobableIrene::new(board, game_info, 0).with_seed(7);
+        let arena = Arena::new();
+        let mut stepper = snake.stepper(&arena);
+
+        assert_eq!(stepper.best_move(), None);
+
+        assert_eq!(
+            stepper.step(Duration::from_millis(5)),
+            SearchStatus::InProgress
+        );
+        let after_one_step = stepper.root().number_of_visits.load(Ordering::Relaxed);
+        assert!(after_one_step > 0);
+        assert!(stepper.best_move().is_some());
+
+        stepper.step(Duration::from_millis(5));
+        let after_two_steps = stepper.root().number_of_visits.load(Ordering::Relaxed);
+        assert!(after_two_steps > after_one_step);
+    }
+
+    #[test]
+    fn test_alternatives_summary_lists_the_best_root_moves() {
+        let game: Game =
+            serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap();
+        let game_info = game.game.clone();
+        let id_map = build_snake_id_map(&game);
+        let board = CellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+
+        let snake = ImprobableIrene::new(board, game_info, 0).with_seed(7);
+        let arena = Arena::new();
+        let mut stepper = snake.stepper(&arena);
+        // The root is expanded before the first step, so there's something to shout already
+        let before = alternatives_summary(stepper.root(), 2).unwrap();
+        assert!(before.ends_with(": unvisited"), "{before}");
+
+        stepper.step(Duration::from_millis(20));
+        let best = stepper.best_move().unwrap();
+        let summary = alternatives_summary(stepper.root(), 4).unwrap();
+        assert!(summary.contains(&format!("{best}: ")), "{summary}");
+        assert!(!summary.contains("unvisited"), "{summary}");
+
+        let top_two = alternatives_summary(stepper.root(), 2).unwrap();
+        assert_eq!(top_two.split(" | ").count(), 2, "{top_two}");
+        assert!(summary.starts_with(&top_two), "{summary}");
+
+        assert_eq!(alternatives_summary(stepper.root(), 0), None);
+    }
 }
@@ -1,3 +1,5 @@
+use crate::board_backends::is_11x11;
+use crate::feature_flags::minimax_options;
 use crate::flood_fill::jump_flooding::JumpFlooding;
 use crate::*;
 
@@ -28,13 +30,25 @@ impl BattlesnakeFactory for JumpFloodingSnakeFactory {
     }
 
     fn create_from_wire_game(&self, game: Game) -> BoxedSnake {
+        // The jump flooding grid is hardcoded to 11x11, so Hobbs plays anything else
+        if !is_11x11(&game) {
+            return hovering_hobbs::Factory.create_from_wire_game(game);
+        }
+
         let game_info = game.game.clone();
         let turn = game.turn;
         let id_map = build_snake_id_map(&game);
 
         let game = WrappedCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
 
-        let snake = MinimaxSnake::from_fn(game, game_info, turn, &score, "jump-flooding");
+        let snake = MinimaxSnake::from_fn_with_options(
+            game,
+            game_info,
+            turn,
+            &score,
+            "jump-flooding",
+            minimax_options(),
+        );
 
         Box::new(snake)
     }
@@ -47,6 +61,7 @@ impl BattlesnakeFactory for JumpFloodingSnakeFactory {
             head: Some("trans-rights-scarf".to_owned()),
             tail: None,
             version: None,
+            ..Default::default()
         }
     }
 }
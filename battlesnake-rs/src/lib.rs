@@ -12,21 +12,56 @@ pub use battlesnake_game_types::{
     compact_representation::StandardCellBoard4Snakes11x11, types::*, wire_representation::Game,
 };
 
+pub use battlesnake_minimax::checked_simulation;
+
 pub mod amphibious_arthur;
 pub mod bombastic_bob;
 pub mod constant_carter;
 pub mod devious_devin_eval;
+pub mod dueling_dana;
 pub mod eremetic_eric;
 pub mod famished_frank;
 pub mod gigantic_george;
 pub mod hovering_hobbs;
 pub mod jump_flooding_snake;
 
+pub mod game_tree;
 pub mod improbable_irene;
+pub mod mcts;
 
 pub mod a_prime;
 pub mod flood_fill;
 
+pub mod api_version;
+pub mod board_backends;
+pub mod board_only;
+pub mod board_render;
+pub mod board_tension;
+pub mod branding;
+pub mod compensated_sum;
+pub mod deadline;
+pub mod dot_dump;
+pub mod extra_settings;
+pub mod failure_injection;
+pub mod feature_flags;
+pub mod food_spawns;
+pub mod game_seed;
+pub mod game_store;
+pub mod head_to_head;
+pub mod maze_passages;
+pub mod move_guard;
+pub mod opening;
+pub mod opponent_book;
+pub mod ponder_scheduler;
+pub mod repetition;
+pub mod request_handler;
+pub mod request_tracing;
+pub mod ruleset_version;
+pub mod shouts;
+pub mod simulation_parity;
+pub mod tuning;
+pub mod wire_game;
+
 #[derive(Serialize)]
 pub struct AboutMe {
     apiversion: String,
@@ -35,6 +70,10 @@ pub struct AboutMe {
     head: Option<String>,
     tail: Option<String>,
     version: Option<String>,
+    /// Ruleset versions we've played with that our simulation hasn't been validated against, see
+    /// [ruleset_version]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    unvalidated_ruleset_versions: Vec<String>,
 }
 
 impl Default for AboutMe {
@@ -46,6 +85,7 @@ impl Default for AboutMe {
             head: None,
             tail: None,
             version: None,
+            unvalidated_ruleset_versions: ruleset_version::unvalidated_versions_seen(),
         }
     }
 }
@@ -177,7 +217,28 @@ impl MoveableGame for Game {
     }
 }
 
-#[derive(Serialize, Debug)]
+/// How many of the best moves to include in the shout, along with their scores
+///
+/// This is off unless `SHOUT_ALTERNATIVES` is set, since it's only useful when watching a replay of
+/// a test game. Set it to a number to choose how many moves to include, any other value gives the
+/// top 3
+pub fn shout_alternatives() -> usize {
+    match std::env::var("SHOUT_ALTERNATIVES").map(|x| x.parse()) {
+        Ok(Ok(x)) => x,
+        Ok(Err(_)) => 3,
+        Err(_) => 0,
+    }
+}
+
+/// Should we shout why we picked each move, instead of nothing
+///
+/// This is off unless `SHOUT_EXPLANATION` is set. When [shout_alternatives] is also on, the
+/// alternatives win since they hold more detail
+pub fn shout_explanation() -> bool {
+    std::env::var("SHOUT_EXPLANATION").is_ok()
+}
+
+#[derive(Serialize, Debug, Clone)]
 pub struct MoveOutput {
     pub r#move: String,
     pub shout: Option<String>,
@@ -186,9 +247,123 @@ pub struct MoveOutput {
 pub type BoxedSnake = Box<dyn BattlesnakeAI + Send + Sync>;
 pub type BoxedFactory = Box<dyn BattlesnakeFactory + Send + Sync>;
 
+/// A move along with what the snake can tell us about how it picked it
+///
+/// See [BattlesnakeAI::explain_move]
+#[derive(Debug)]
+pub struct MoveExplanation {
+    pub output: MoveOutput,
+    /// Every move the snake considered for itself along with its score, best first
+    pub root_options: Vec<(Move, String)>,
+    pub depth: Option<usize>,
+    pub iterations: Option<usize>,
+    /// What each term of the score was at the end of the line the snake expects, for snakes
+    /// searching with the `leaf_breakdown` feature flag
+    pub leaf_breakdown: Option<Vec<(&'static str, f64)>>,
+}
+
+/// What a snake can tell us about the search behind a move, small enough to log with every move
+///
+/// See [BattlesnakeAI::make_move_with_summary]
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct SearchSummary {
+    /// Which search picked the move, None for snakes that don't search
+    pub algorithm: Option<&'static str>,
+    pub depth: Option<usize>,
+    pub iterations: Option<usize>,
+    /// The score of the move we picked, as the search prints it
+    pub score: Option<String>,
+    /// The line the search expects, every snake's move in the order the search made them starting
+    /// with ours
+    pub pv: Vec<String>,
+    pub time_used_ms: Option<u64>,
+    pub budget_ms: Option<u64>,
+    /// How Alpha-Beta pruning went at each depth of the search, for the searches that prune
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub cutoffs: Vec<CutoffSummary>,
+}
+
+/// How Alpha-Beta pruning went at one depth of a search, see [DepthCutoffs]
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CutoffSummary {
+    pub depth: i64,
+    pub nodes: usize,
+    pub cutoffs: usize,
+    /// The average index in the move order of the move that caused each cutoff, so the closer to
+    /// 0 the better the move ordering did. None when nothing was cut off
+    pub average_cutoff_index: Option<f64>,
+}
+
+impl From<DepthCutoffs> for CutoffSummary {
+    fn from(cutoffs: DepthCutoffs) -> Self {
+        Self {
+            depth: cutoffs.depth,
+            nodes: cutoffs.nodes,
+            cutoffs: cutoffs.cutoffs,
+            average_cutoff_index: cutoffs.average_cutoff_index(),
+        }
+    }
+}
+
+impl SearchSummary {
+    /// The summary of a paranoid minimax search that settled on `scored`
+    pub fn paranoid<T, ScoreType>(stats: &SearchStats, scored: &MinMaxReturn<T, ScoreType>) -> Self
+    where
+        T: SnakeIDGettableGame + Debug + Clone,
+        ScoreType: Clone + Debug + PartialOrd + Ord + Copy,
+    {
+        Self {
+            algorithm: Some("paranoid_minimax"),
+            depth: Some(stats.depth),
+            iterations: Some(stats.iterations),
+            score: Some(format!("{:?}", scored.score())),
+            pv: scored
+                .chosen_route()
+                .into_iter()
+                .map(|(_, m)| format!("{m}"))
+                .collect(),
+            time_used_ms: Some(stats.time_used.as_millis() as u64),
+            budget_ms: Some(stats.budget.as_millis() as u64),
+            cutoffs: scored
+                .cutoffs_by_depth()
+                .into_iter()
+                .map(CutoffSummary::from)
+                .collect(),
+        }
+    }
+}
+
+impl From<MoveOutput> for MoveExplanation {
+    fn from(output: MoveOutput) -> Self {
+        Self {
+            output,
+            root_options: vec![],
+            depth: None,
+            iterations: None,
+            leaf_breakdown: None,
+        }
+    }
+}
+
 pub trait BattlesnakeAI {
     fn end(&self) {}
     fn make_move(&self) -> Result<MoveOutput>;
+
+    /// Make a move, and explain how we got there
+    ///
+    /// This is for debugging decisions locally. Snakes that don't search only have the move to
+    /// give us
+    fn explain_move(&self) -> Result<MoveExplanation> {
+        self.make_move().map(Into::into)
+    }
+
+    /// Make a move, along with a [SearchSummary] of how we got there that hosts can log
+    ///
+    /// Snakes that don't search only have the move to give us, so their summary is empty
+    fn make_move_with_summary(&self) -> Result<(MoveOutput, SearchSummary)> {
+        self.make_move()
+            .map(|output| (output, SearchSummary::default()))
+    }
 }
 
 pub trait BattlesnakeFactory {
@@ -217,13 +392,19 @@ impl SnakeTailPushableGame for Game {
 }
 
 pub use battlesnake_minimax::paranoid::MinimaxSnake;
-use battlesnake_minimax::{lazy_smp::LazySmpSnake, paranoid::Scorable, Instruments};
+use battlesnake_minimax::{
+    board_hash::BoardHashable,
+    lazy_smp::LazySmpSnake,
+    paranoid::{DepthCutoffs, MinMaxReturn, Scorable, SearchStats},
+    Instruments,
+};
 
 use crate::{
     amphibious_arthur::AmphibiousArthurFactory, bombastic_bob::BombasticBobFactory,
-    constant_carter::ConstantCarterFactory, eremetic_eric::EremeticEricFactory,
-    famished_frank::FamishedFrankFactory, gigantic_george::GiganticGeorgeFactory,
-    improbable_irene::ImprobableIreneFactory, jump_flooding_snake::JumpFloodingSnakeFactory,
+    constant_carter::ConstantCarterFactory, dueling_dana::DuelingDanaFactory,
+    eremetic_eric::EremeticEricFactory, famished_frank::FamishedFrankFactory,
+    gigantic_george::GiganticGeorgeFactory, improbable_irene::ImprobableIreneFactory,
+    jump_flooding_snake::JumpFloodingSnakeFactory,
 };
 
 impl<T, ScoreType, ScoreableType, const N_SNAKES: usize> BattlesnakeAI
@@ -237,28 +418,63 @@ where
         + VictorDeterminableGame
         + NeighborDeterminableGame
         + NeckQueryableGame
+        + SnakeBodyGettableGame
         // + ReasonableMoveDeterminableGame
         + SimulableGame<Instruments, N_SNAKES>
         + Clone
         + Sync
         + Copy
         + FoodGettableGame
+        + BoardHashable
         + Send,
     T::SnakeIDType: Copy + Send + Sync,
     ScoreType: Clone + Debug + PartialOrd + Ord + Send + Sync + Copy,
-    ScoreableType: Scorable<T, ScoreType> + Sized + Send + Sync + Clone,
+    ScoreableType: Scorable<T, ScoreType> + Sized + Send + Sync + Clone + 'static,
 {
     fn make_move(&self) -> Result<MoveOutput> {
-        let m: Move = self
-            .choose_move()
-            .ok_or_else(|| color_eyre::eyre::eyre!("We couldn't find a move"))?
-            .0;
+        let (m, _depth, shout) = self
+            .choose_move_with_alternatives(shout_alternatives())
+            .ok_or_else(|| color_eyre::eyre::eyre!("We couldn't find a move"))?;
 
         Ok(MoveOutput {
             r#move: format!("{m}"),
-            shout: None,
+            shout,
         })
     }
+
+    fn explain_move(&self) -> Result<MoveExplanation> {
+        let (m, stats, root_options, leaf_breakdown) = self
+            .choose_move_with_root_options()
+            .ok_or_else(|| color_eyre::eyre::eyre!("We couldn't find a move"))?;
+
+        Ok(MoveExplanation {
+            output: MoveOutput {
+                r#move: format!("{m}"),
+                shout: None,
+            },
+            root_options: root_options
+                .into_iter()
+                .map(|(m, score)| (m, format!("{score:?}")))
+                .collect(),
+            depth: Some(stats.depth),
+            iterations: Some(stats.iterations),
+            leaf_breakdown,
+        })
+    }
+
+    fn make_move_with_summary(&self) -> Result<(MoveOutput, SearchSummary)> {
+        let (m, shout, stats, scored) = self
+            .choose_move_with_search(shout_alternatives())
+            .ok_or_else(|| color_eyre::eyre::eyre!("We couldn't find a move"))?;
+
+        Ok((
+            MoveOutput {
+                r#move: format!("{m}"),
+                shout,
+            },
+            SearchSummary::paranoid(&stats, &scored),
+        ))
+    }
 }
 
 impl<T, ScoreType, ScoreableType, const N_SNAKES: usize> BattlesnakeAI
@@ -272,12 +488,14 @@ where
         + VictorDeterminableGame
         + NeighborDeterminableGame
         + NeckQueryableGame
+        + SnakeBodyGettableGame
         // + ReasonableMoveDeterminableGame
         + SimulableGame<Instruments, N_SNAKES>
         + Clone
         + Sync
         + Copy
         + FoodGettableGame
+        + BoardHashable
         + Send
         + Eq
         + PartialEq
@@ -296,17 +514,24 @@ where
     }
 }
 
+/// Every snake we serve, with their moves checked by [move_guard::guard_move]
 pub fn all_factories() -> Vec<BoxedFactory> {
-    vec![
+    let factories: Vec<BoxedFactory> = vec![
         Box::new(AmphibiousArthurFactory {}),
-        Box::new(BombasticBobFactory {}),
-        Box::new(ConstantCarterFactory {}),
+        Box::new(BombasticBobFactory::from_env()),
+        Box::new(ConstantCarterFactory::from_env()),
         Box::new(devious_devin_eval::Factory {}),
+        Box::new(DuelingDanaFactory),
         Box::new(EremeticEricFactory {}),
         Box::new(FamishedFrankFactory {}),
         Box::new(GiganticGeorgeFactory {}),
         Box::new(JumpFloodingSnakeFactory {}),
         // Box::new(hovering_hobbs::Factory {}),
         Box::new(ImprobableIreneFactory {}),
-    ]
+    ];
+
+    factories
+        .into_iter()
+        .map(|f| Box::new(move_guard::GuardedFactory(f)) as BoxedFactory)
+        .collect()
 }
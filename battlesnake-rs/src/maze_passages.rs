@@ -0,0 +1,322 @@
+use std::{cell::RefCell, rc::Rc};
+
+use battlesnake_game_types::wire_representation::Position;
+
+use crate::a_prime::dist_between;
+use crate::*;
+
+/// The tunnels through the outer wall of a maze map, like the wrap passages in arcade maze
+///
+/// On maze maps the walls are hazards that kill you, and the only way across the edge of the board
+/// is through an opening in the wall that lines up with one on the opposite edge. Going through one
+/// of these takes a single move, no matter how far apart the two ends look.
+///
+/// This is read off the hazards on the edges of the board, so it only needs the board itself. The
+/// walls of a maze don't move during a game, so the searches use [MazePassages::for_board], which
+/// only reads them again once the edges look different
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MazePassages {
+    passages: Vec<(Position, Position)>,
+    /// Every end of every passage
+    ends: Vec<Position>,
+    /// The fewest moves it could take to get from one end to another, using any of the passages
+    /// along the way. Indexed the same way as `ends`
+    between_ends: Vec<Vec<i32>>,
+}
+
+/// The passages of the last board a thread asked for, along with which cells on its edges were open
+struct LastBoard {
+    width: i32,
+    height: i32,
+    open_edges: Vec<bool>,
+    passages: Option<Rc<MazePassages>>,
+}
+
+thread_local! {
+    static LAST_BOARD: RefCell<Option<LastBoard>> = RefCell::new(None);
+}
+
+/// Every cell on the edges of the board, which are all [MazePassages::from_board] looks at
+fn edge_cells(width: i32, height: i32) -> impl Iterator<Item = Position> {
+    let across =
+        (0..height).flat_map(move |y| [Position { x: 0, y }, Position { x: width - 1, y }]);
+    let up_and_down =
+        (0..width).flat_map(move |x| [Position { x, y: 0 }, Position { x, y: height - 1 }]);
+
+    across.chain(up_and_down)
+}
+
+impl MazePassages {
+    /// The same as [MazePassages::from_board], but each thread keeps the passages of the last board
+    /// it asked about and hands them out again while the edges of the board haven't changed
+    ///
+    /// Every board in a search comes from the same game, so the passages are only found once a
+    /// game instead of on every A* and flood fill
+    pub fn for_board<T>(board: &T) -> Option<Rc<Self>>
+    where
+        T: PositionGettableGame + SizeDeterminableGame + HazardQueryableGame,
+    {
+        let width = board.get_width() as i32;
+        let height = board.get_height() as i32;
+        let open_edges = || {
+            edge_cells(width, height).map(|pos| !board.is_hazard(&board.native_from_position(pos)))
+        };
+
+        LAST_BOARD.with(|last| {
+            if let Some(cached) = last.borrow().as_ref() {
+                if cached.width == width
+                    && cached.height == height
+                    && cached.open_edges.iter().copied().eq(open_edges())
+                {
+                    return cached.passages.clone();
+                }
+            }
+
+            let passages = Self::from_board(board).map(Rc::new);
+            *last.borrow_mut() = Some(LastBoard {
+                width,
+                height,
+                open_edges: open_edges().collect(),
+                passages: passages.clone(),
+            });
+
+            passages
+        })
+    }
+
+    /// Find the passages through the outer wall of this board
+    ///
+    /// Returns None unless the board looks like a maze: every cell on the edge has to be a wall,
+    /// except for single cell openings that line up with one on the opposite edge, and there has
+    /// to be at least one of those. Royale hazards on a wrapped board cover whole edges, so they
+    /// never look like a maze
+    pub fn from_board<T>(board: &T) -> Option<Self>
+    where
+        T: PositionGettableGame + SizeDeterminableGame + HazardQueryableGame,
+    {
+        let width = board.get_width() as i32;
+        let height = board.get_height() as i32;
+        let is_open = |pos: Position| {
+            (0..width).contains(&pos.x)
+                && (0..height).contains(&pos.y)
+                && !board.is_hazard(&board.native_from_position(pos))
+        };
+        // An opening in the wall has wall on both sides of it, along the edge it's on
+        let is_opening = |pos: Position, along_edge: Position| {
+            is_open(pos)
+                && !is_open(Position {
+                    x: pos.x + along_edge.x,
+                    y: pos.y + along_edge.y,
+                })
+                && !is_open(Position {
+                    x: pos.x - along_edge.x,
+                    y: pos.y - along_edge.y,
+                })
+        };
+
+        let across = (0..height).map(|y| {
+            (
+                Position { x: 0, y },
+                Position { x: width - 1, y },
+                Position { x: 0, y: 1 },
+            )
+        });
+        let up_and_down = (0..width).map(|x| {
+            (
+                Position { x, y: 0 },
+                Position { x, y: height - 1 },
+                Position { x: 1, y: 0 },
+            )
+        });
+
+        let mut passages = vec![];
+        for (a, b, along_edge) in across.chain(up_and_down) {
+            match (is_open(a), is_open(b)) {
+                (false, false) => {}
+                _ if is_opening(a, along_edge) && is_opening(b, along_edge) => {
+                    passages.push((a, b))
+                }
+                _ => return None,
+            }
+        }
+
+        if passages.is_empty() {
+            return None;
+        }
+
+        Some(Self::new(passages))
+    }
+
+    fn new(passages: Vec<(Position, Position)>) -> Self {
+        let ends: Vec<Position> = passages.iter().flat_map(|(a, b)| [*a, *b]).collect();
+
+        let mut between_ends: Vec<Vec<i32>> = ends
+            .iter()
+            .map(|from| ends.iter().map(|to| dist_between(from, to)).collect())
+            .collect();
+        for i in 0..passages.len() {
+            between_ends[2 * i][2 * i + 1] = 1;
+            between_ends[2 * i + 1][2 * i] = 1;
+        }
+        for via in 0..ends.len() {
+            for from in 0..ends.len() {
+                for to in 0..ends.len() {
+                    let through = between_ends[from][via] + between_ends[via][to];
+                    if through < between_ends[from][to] {
+                        between_ends[from][to] = through;
+                    }
+                }
+            }
+        }
+
+        Self {
+            passages,
+            ends,
+            between_ends,
+        }
+    }
+
+    /// The pairs of cells that are connected through the wall
+    pub fn passages(&self) -> &[(Position, Position)] {
+        &self.passages
+    }
+
+    /// If this cell is the end of a passage, the cell at its other end
+    pub fn other_end(&self, pos: &Position) -> Option<Position> {
+        self.passages.iter().find_map(|(a, b)| {
+            if a == pos {
+                Some(*b)
+            } else if b == pos {
+                Some(*a)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// The fewest moves it could possibly take to get from `a` to `b`
+    ///
+    /// This is the Manhattan distance, except it knows it can take the passages. It ignores the
+    /// walls inside the maze, so it never guesses too high, which is what A* needs from it
+    pub fn distance(&self, a: &Position, b: &Position) -> i32 {
+        let direct = dist_between(a, b);
+
+        let through_passages = self
+            .ends
+            .iter()
+            .enumerate()
+            .flat_map(|(from, entrance)| {
+                self.ends.iter().enumerate().map(move |(to, exit)| {
+                    dist_between(a, entrance) + self.between_ends[from][to] + dist_between(exit, b)
+                })
+            })
+            .min();
+
+        through_passages.map_or(direct, |d| d.min(direct))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use battlesnake_game_types::compact_representation::{dimensions::Custom, WrappedCellBoard};
+
+    use super::*;
+
+    fn arcade_maze() -> WrappedCellBoard<u16, Custom, { 19 * 21 }, 4> {
+        wrapped_board(include_str!("../../fixtures/arcade_maze_tunnel_race.json"))
+    }
+
+    fn wrapped_board(fixture: &str) -> WrappedCellBoard<u16, Custom, { 19 * 21 }, 4> {
+        let game: Game = serde_json::from_str(fixture).unwrap();
+        let id_map = build_snake_id_map(&game);
+
+        game.as_wrapped_cell_board(&id_map).unwrap()
+    }
+
+    #[test]
+    fn test_finds_the_arcade_maze_tunnels() {
+        let maze = MazePassages::from_board(&arcade_maze()).unwrap();
+
+        assert_eq!(
+            maze.passages(),
+            &[
+                (Position { x: 0, y: 11 }, Position { x: 18, y: 11 }),
+                (Position { x: 1, y: 0 }, Position { x: 1, y: 20 }),
+                (Position { x: 17, y: 0 }, Position { x: 17, y: 20 }),
+            ]
+        );
+        assert_eq!(
+            maze.other_end(&Position { x: 18, y: 11 }),
+            Some(Position { x: 0, y: 11 })
+        );
+    }
+
+    #[test]
+    fn test_boards_without_walls_are_not_mazes() {
+        let game: Game =
+            serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap();
+        let id_map = build_snake_id_map(&game);
+        let board = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+
+        assert_eq!(MazePassages::from_board(&board), None);
+    }
+
+    #[test]
+    fn test_distance_goes_through_the_tunnels() {
+        let maze = MazePassages::from_board(&arcade_maze()).unwrap();
+
+        assert_eq!(
+            maze.distance(&Position { x: 2, y: 11 }, &Position { x: 16, y: 11 }),
+            5
+        );
+        // Down through the bottom left tunnel comes out at the top of the board
+        assert_eq!(
+            maze.distance(&Position { x: 1, y: 1 }, &Position { x: 17, y: 19 }),
+            19
+        );
+        assert_eq!(
+            maze.distance(&Position { x: 5, y: 5 }, &Position { x: 6, y: 6 }),
+            2
+        );
+    }
+
+    #[test]
+    fn test_finds_the_tunnels_of_a_real_game() {
+        // Turn 639 of a duel we played on arcade maze, where the wall is the same as in every
+        // other game on the map
+        let board = wrapped_board(include_str!(
+            "../../fixtures/arcade_maze_end_game_duels.json"
+        ));
+        let maze = MazePassages::from_board(&board).unwrap();
+
+        assert_eq!(maze, MazePassages::from_board(&arcade_maze()).unwrap());
+        // Our head is at (2, 3), and down through the tunnel at (1, 0) is the quickest way to the
+        // top of the board
+        assert_eq!(
+            maze.distance(&Position { x: 2, y: 3 }, &Position { x: 4, y: 17 }),
+            11
+        );
+    }
+
+    #[test]
+    fn test_for_board_only_finds_the_passages_again_when_the_edges_change() {
+        let maze = arcade_maze();
+
+        let first = MazePassages::for_board(&maze).unwrap();
+        let again = MazePassages::for_board(&wrapped_board(include_str!(
+            "../../fixtures/arcade_maze_end_game_duels.json"
+        )))
+        .unwrap();
+        assert!(Rc::ptr_eq(&first, &again));
+
+        let game: Game =
+            serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap();
+        let id_map = build_snake_id_map(&game);
+        let open = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+        assert_eq!(MazePassages::for_board(&open), None);
+
+        let after = MazePassages::for_board(&maze).unwrap();
+        assert!(!Rc::ptr_eq(&first, &after));
+        assert_eq!(first, after);
+    }
+}
@@ -0,0 +1,2071 @@
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    cmp::Reverse,
+    collections::{hash_map::Entry, VecDeque},
+    hash::{Hash, Hasher},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use battlesnake_minimax::{
+    checked_simulation::check_simulation_moves, neck::neck_move, Instruments,
+};
+use decorum::{Infinite, Real, N64};
+use dotavious::{Dot, Edge, GraphBuilder};
+use itertools::Itertools;
+use rand::{distributions::WeightedIndex, prelude::Distribution, rngs::StdRng, Rng, SeedableRng};
+use rand_distr::Dirichlet;
+use rustc_hash::{FxHashMap, FxHasher};
+use typed_arena::Arena;
+
+use crate::{
+    a_prime::dist_between_new,
+    board_render::{describe_path, describe_position},
+    compensated_sum::CompensatedF64,
+    flood_fill::spread_from_head::Scores,
+    opponent_book::{OpponentModel, OpponentProfile},
+};
+
+use super::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum SomeonesMove {
+    MyMove(Move),
+    OtherMoves(Action<4>),
+}
+
+impl SomeonesMove {
+    pub(crate) fn my_move(&self) -> Move {
+        match self {
+            SomeonesMove::MyMove(m) => *m,
+            SomeonesMove::OtherMoves(_) => panic!("We aren't at a my move"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct TreeContext<'arena, T> {
+    pub(crate) parent: RefCell<&'arena Node<'arena, T>>,
+    pub(crate) snake_move: SomeonesMove,
+}
+
+/// Why the search doesn't need any more iterations to pick our move, see [Node::root_proof]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RootProof {
+    /// Every reply of the opponents to this move ends the game with us as the winner
+    ForcedWin(Move),
+    /// Every other move has a reply that kills us, so this is the only move that doesn't lose.
+    /// This includes it being our only move to begin with
+    OnlyMove(Move),
+}
+
+impl RootProof {
+    pub(crate) fn chosen(&self) -> Move {
+        match self {
+            RootProof::ForcedWin(m) | RootProof::OnlyMove(m) => *m,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Node<'arena, T> {
+    pub(crate) game_state: T,
+    /// These are compensated sums, since they take an add for every iteration that goes through
+    /// the node and [Node::score_variance] is very sensitive to them drifting
+    pub(crate) total_score: CompensatedF64,
+    pub(crate) sum_of_square_scores: CompensatedF64,
+    pub(crate) number_of_visits: AtomicUsize,
+    pub(crate) children: RefCell<Option<Vec<&'arena Node<'arena, T>>>>,
+    pub(crate) tree_context: Option<TreeContext<'arena, T>>,
+    pub(crate) depth: usize,
+    /// A cheap heuristic guess at how good this node is, relative to its parent
+    ///
+    /// This is used to bias the search towards promising moves before we have enough visits for
+    /// the real scores to mean much. See [Node::progressive_bias]
+    pub(crate) prior: N64,
+}
+
+/// The score we give to children that have never been visited
+///
+/// Plain UCB1 treats unvisited nodes as infinitely good which means we spend our early iterations
+/// trying every move once, even the obviously bad ones. Using a finite value here lets a child
+/// that is already doing well keep getting explored before we try all of its siblings.
+const FIRST_PLAY_URGENCY: f64 = 1.0;
+
+/// How much weight to give to the heuristic prior when picking the next child to explore
+const PROGRESSIVE_BIAS_WEIGHT: f64 = 1.0;
+
+/// How far, relative to their size, the running sums of a node can drift from each other before
+/// [Node::statistics_violations] calls them inconsistent. They're built up by adding floats, so
+/// they are never exact
+const STATISTICS_TOLERANCE: f64 = 1e-9;
+
+#[derive(Debug, Clone, Copy)]
+/// Tuning knobs for the MCTS snakes, like [crate::improbable_irene::ImprobableIrene]
+///
+/// The defaults (as implemented by [Default]) are the values we have been playing with, and
+/// [SnakeOptions::from_env] lets a deployment override any of them without a code change
+pub struct SnakeOptions {
+    /// How long should we 'reserve' for Network Latency
+    ///
+    /// We keep running iterations until the game timeout minus this padding
+    ///
+    /// Defaults to 120 milliseconds
+    pub network_latency_padding: Duration,
+    /// The most turns a single random rollout plays before we score the board
+    ///
+    /// Defaults to 25
+    pub max_rollout_turns: usize,
+    /// How much plain UCB1 favors exploring children we haven't visited much
+    ///
+    /// Defaults to 2.0
+    pub ucb1_constant: f64,
+    /// How much UCB1-Normal favors exploring children whose scores have a high variance
+    ///
+    /// Defaults to 16.0
+    pub ucb1_normal_constant: f64,
+    /// How much each kind of cell is worth in the flood fill we score boards with
+    ///
+    /// Defaults to 5 for food and empty cells and 1 for hazards
+    pub flood_fill_scores: Scores,
+    /// Rollouts that end the game within this many turns have their outcome remembered for the
+    /// states they passed through in those last turns. See [RolloutCache]
+    ///
+    /// Defaults to 3, and 0 turns the cache off
+    pub rollout_cache_turns: usize,
+    /// The most states the [RolloutCache] remembers while picking a single move
+    ///
+    /// Defaults to 50,000
+    pub rollout_cache_size: usize,
+    /// The deepest a node can be in the tree and still get expanded. Nodes at this depth are
+    /// scored directly instead of with a random rollout, so deep branches turn into evaluated
+    /// leaves and the iterations go into widening the tree instead
+    ///
+    /// Each turn is two levels of the tree, one for our move and one for our opponents'. Defaults
+    /// to None, which doesn't cap the tree
+    pub max_tree_depth: Option<usize>,
+    /// How much of a rollout's score comes from how long we can keep going on our health, instead
+    /// of the share of the board we control. See [crate::improbable_irene] for how that is scored
+    ///
+    /// Defaults to 0.25, and 0 scores rollouts on space alone
+    pub health_weight: f64,
+    /// Dirichlet noise to mix into the priors of our moves at the root, so self-play games don't
+    /// keep exploring the same lines. See [RootNoise]
+    ///
+    /// Defaults to None, which leaves the priors alone
+    pub root_noise: Option<RootNoise>,
+    /// How much randomness goes into picking the move we play once the search is done
+    ///
+    /// Above 0 we pick each move with a chance proportional to its visits to the power of
+    /// `1 / temperature`, so 1 follows the visits exactly and higher values flatten them out. At 0
+    /// we play the move with the highest average score.
+    ///
+    /// Defaults to 0
+    pub temperature: f64,
+}
+
+/// Dirichlet noise for the priors of our moves at the root, like AlphaZero uses in self-play
+///
+/// Each prior becomes `(1 - weight) * prior + weight * noise`, where the noise for all the moves
+/// is drawn from a symmetric Dirichlet distribution and adds up to 1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RootNoise {
+    /// The concentration of the Dirichlet distribution. Smaller values put most of the noise on a
+    /// single move
+    pub alpha: f64,
+    /// How much of each prior is replaced by noise, between 0 and 1
+    pub weight: f64,
+}
+
+impl RootNoise {
+    /// Draw the noise for `size` moves, or None if there aren't enough moves for it to matter
+    fn sample<R: Rng>(&self, size: usize, rng: &mut R) -> Option<Vec<f64>> {
+        let dirichlet = Dirichlet::new_with_size(self.alpha, size).ok()?;
+
+        Some(dirichlet.sample(rng))
+    }
+}
+
+impl Default for SnakeOptions {
+    fn default() -> Self {
+        Self {
+            network_latency_padding: Duration::from_millis(120),
+            max_rollout_turns: 25,
+            ucb1_constant: 2.0,
+            ucb1_normal_constant: 16.0,
+            flood_fill_scores: Scores {
+                food: 5,
+                hazard: 1,
+                empty: 5,
+            },
+            rollout_cache_turns: 3,
+            rollout_cache_size: 50_000,
+            max_tree_depth: None,
+            health_weight: 0.25,
+            root_noise: None,
+            temperature: 0.0,
+        }
+    }
+}
+
+impl SnakeOptions {
+    /// The defaults, with any of them overridden by the environment. See
+    /// [SnakeOptions::with_env_overrides]
+    pub fn from_env() -> Self {
+        Self::default().with_env_overrides()
+    }
+
+    /// These options, with any of them overridden by the `MCTS_NETWORK_LATENCY_PADDING_MS`,
+    /// `MCTS_MAX_ROLLOUT_TURNS`, `MCTS_UCB1_CONSTANT`, `MCTS_UCB1_NORMAL_CONSTANT`,
+    /// `MCTS_ROLLOUT_CACHE_TURNS`, `MCTS_ROLLOUT_CACHE_SIZE`, `MCTS_MAX_TREE_DEPTH` and
+    /// `MCTS_HEALTH_WEIGHT` environment variables. Setting `MCTS_MAX_TREE_DEPTH` to 0 removes the
+    /// cap
+    pub fn with_env_overrides(self) -> Self {
+        let defaults = self;
+
+        Self {
+            network_latency_padding: match std::env::var("MCTS_NETWORK_LATENCY_PADDING_MS")
+                .map(|x| x.parse())
+            {
+                Ok(Ok(x)) => Duration::from_millis(x),
+                _ => defaults.network_latency_padding,
+            },
+            max_rollout_turns: match std::env::var("MCTS_MAX_ROLLOUT_TURNS").map(|x| x.parse()) {
+                Ok(Ok(x)) => x,
+                _ => defaults.max_rollout_turns,
+            },
+            ucb1_constant: match std::env::var("MCTS_UCB1_CONSTANT").map(|x| x.parse()) {
+                Ok(Ok(x)) => x,
+                _ => defaults.ucb1_constant,
+            },
+            ucb1_normal_constant: match std::env::var("MCTS_UCB1_NORMAL_CONSTANT")
+                .map(|x| x.parse())
+            {
+                Ok(Ok(x)) => x,
+                _ => defaults.ucb1_normal_constant,
+            },
+            flood_fill_scores: defaults.flood_fill_scores,
+            rollout_cache_turns: match std::env::var("MCTS_ROLLOUT_CACHE_TURNS").map(|x| x.parse())
+            {
+                Ok(Ok(x)) => x,
+                _ => defaults.rollout_cache_turns,
+            },
+            rollout_cache_size: match std::env::var("MCTS_ROLLOUT_CACHE_SIZE").map(|x| x.parse()) {
+                Ok(Ok(x)) => x,
+                _ => defaults.rollout_cache_size,
+            },
+            max_tree_depth: match std::env::var("MCTS_MAX_TREE_DEPTH").map(|x| x.parse()) {
+                Ok(Ok(0)) => None,
+                Ok(Ok(x)) => Some(x),
+                _ => defaults.max_tree_depth,
+            },
+            health_weight: match std::env::var("MCTS_HEALTH_WEIGHT").map(|x| x.parse()) {
+                Ok(Ok(x)) => x,
+                _ => defaults.health_weight,
+            },
+            root_noise: defaults.root_noise,
+            temperature: defaults.temperature,
+        }
+    }
+}
+
+/// Remembers how rollouts ended from states that were close to the end of the game
+///
+/// Near the end of a game most moves are forced, so the rollouts from a state keep playing out
+/// the same few turns. Instead of simulating them again we reuse how they went. Only the last
+/// [SnakeOptions::rollout_cache_turns] states of a rollout that finished the game are stored, and
+/// we stop storing new ones once we have [SnakeOptions::rollout_cache_size] of them. A new cache is
+/// made for each move, so this never grows past that
+///
+/// Each state keeps the running mean of the rollouts that ended from it, and we keep rolling out
+/// fresh until [MIN_CACHED_ROLLOUTS] have. Reusing the first outcome alone would hand UCB1-Normal
+/// the same score over and over, and it would take that lack of variance as certainty
+#[derive(Debug, Default)]
+pub struct RolloutCache {
+    outcomes: FxHashMap<u64, RolloutOutcomes>,
+    capacity: usize,
+    hits: usize,
+    lookups: usize,
+}
+
+/// How many rollouts that finished the game a state needs before the [RolloutCache] stands in for
+/// more of them
+pub const MIN_CACHED_ROLLOUTS: u32 = 8;
+
+/// The rollouts that ended from a single state
+#[derive(Debug, Default, Clone, Copy)]
+struct RolloutOutcomes {
+    mean: f64,
+    count: u32,
+}
+
+impl RolloutCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ..Default::default()
+        }
+    }
+
+    /// The boards are only compared within a single move, so their derived [Hash] is stable
+    /// enough to key on
+    fn key<T: Hash>(board: &T) -> u64 {
+        let mut hasher = FxHasher::default();
+        board.hash(&mut hasher);
+
+        hasher.finish()
+    }
+
+    /// The mean outcome of the rollouts from this state, once there have been enough of them to
+    /// stand in for another
+    fn get(&mut self, key: u64) -> Option<N64> {
+        self.lookups += 1;
+
+        let outcomes = self.outcomes.get(&key)?;
+        if outcomes.count < MIN_CACHED_ROLLOUTS {
+            return None;
+        }
+
+        self.hits += 1;
+        Some(N64::from(outcomes.mean))
+    }
+
+    fn insert(&mut self, key: u64, score: N64) {
+        let at_capacity = self.outcomes.len() >= self.capacity;
+        let outcomes = match self.outcomes.entry(key) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(_) if at_capacity => return,
+            Entry::Vacant(entry) => entry.insert(Default::default()),
+        };
+
+        outcomes.count += 1;
+        outcomes.mean += (score.into_inner() - outcomes.mean) / outcomes.count as f64;
+    }
+
+    /// How many rollouts were cut short by finding a stored outcome
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// How many times we checked the cache for a state
+    pub fn lookups(&self) -> usize {
+        self.lookups
+    }
+
+    /// How many states we have outcomes stored for
+    pub fn entries(&self) -> usize {
+        self.outcomes.len()
+    }
+
+    /// The fraction of lookups that found a stored outcome, or None if we never looked
+    pub fn hit_rate(&self) -> Option<f64> {
+        if self.lookups == 0 {
+            return None;
+        }
+
+        Some(self.hits as f64 / self.lookups as f64)
+    }
+}
+
+/// Scratch space for [Node::expand], made once per move and reused by every expansion
+///
+/// Expanding sorts the next states into a bucket for each of our own moves. The buckets are
+/// emptied into the new nodes but keep their capacity, so on crowded boards, where every
+/// expansion simulates dozens of states, we aren't allocating and freeing the same buffers over
+/// and over
+///
+/// It also holds the RNG [SnakeOptions::root_noise] is drawn from, since the root is expanded
+/// with it like every other node
+#[derive(Debug)]
+pub struct ExpansionBuffers<BoardType> {
+    by_own_move: [Vec<(Action<4>, BoardType, N64)>; 4],
+    noise_rng: StdRng,
+    instruments: Instruments,
+}
+
+impl<BoardType> Default for ExpansionBuffers<BoardType> {
+    fn default() -> Self {
+        Self::with_noise_rng(StdRng::from_entropy())
+    }
+}
+
+impl<BoardType> ExpansionBuffers<BoardType> {
+    /// Buffers that draw the root noise from `noise_rng`, so a seeded search gets the same noise
+    /// every time
+    pub fn with_noise_rng(noise_rng: StdRng) -> Self {
+        Self {
+            by_own_move: Default::default(),
+            noise_rng,
+            instruments: Instruments::default(),
+        }
+    }
+
+    /// Record the simulations done while expanding with `instruments`
+    pub fn recording(self, instruments: Instruments) -> Self {
+        Self {
+            instruments,
+            ..self
+        }
+    }
+
+    /// How many next states the buckets can hold between them without growing
+    pub fn capacity(&self) -> usize {
+        self.by_own_move.iter().map(Vec::capacity).sum()
+    }
+}
+
+impl<'arena, T> Node<'arena, T> {
+    pub(crate) fn new(game_state: T) -> Self {
+        Self {
+            game_state,
+            total_score: CompensatedF64::new(0.0),
+            sum_of_square_scores: CompensatedF64::new(0.0),
+            number_of_visits: AtomicUsize::new(0),
+            children: RefCell::new(None),
+            tree_context: None,
+            depth: 0,
+            prior: 0.0.into(),
+        }
+    }
+
+    pub(crate) fn new_with_parent(
+        game_state: T,
+        parent: &'arena Self,
+        r#move: SomeonesMove,
+    ) -> Self {
+        Self {
+            game_state,
+            total_score: CompensatedF64::new(0.0),
+            sum_of_square_scores: CompensatedF64::new(0.0),
+            number_of_visits: AtomicUsize::new(0),
+            children: RefCell::new(None),
+            tree_context: Some(TreeContext {
+                parent: RefCell::new(parent),
+                snake_move: r#move,
+            }),
+            depth: parent.depth + 1,
+            prior: 0.0.into(),
+        }
+    }
+
+    /// The sample variance of the scores backed up through this node, or None until there are at
+    /// least two of them
+    ///
+    /// This comes from the running sums, and when the scores are all close together rounding can
+    /// leave it a hair below zero. It's clamped, since the square root UCB1-Normal takes of it
+    /// would be NaN otherwise
+    pub(crate) fn score_variance(&self) -> Option<f64> {
+        let number_of_visits = self.number_of_visits.load(Ordering::Relaxed);
+        if number_of_visits < 2 {
+            return None;
+        }
+
+        let number_of_visits = number_of_visits as f64;
+        let total_score = self.total_score.load(Ordering::Relaxed);
+        let sum_of_square_scores = self.sum_of_square_scores.load(Ordering::Relaxed);
+
+        let variance = (sum_of_square_scores - total_score.powi(2) / number_of_visits)
+            / (number_of_visits - 1.0);
+
+        Some(variance.max(0.0))
+    }
+
+    /// Anything about this node's visits and running sums that can't be true of scores that were
+    /// really backed up through it
+    ///
+    /// A node with no visits has to have empty sums, and the sum of squares can't be less than
+    /// the square of the total over the visits, or the variance would be negative. The sums are
+    /// floats, so we allow them to be off by a little
+    pub(crate) fn statistics_violations(&self) -> Vec<String> {
+        let number_of_visits = self.number_of_visits.load(Ordering::Relaxed);
+        let total_score = self.total_score.load(Ordering::Relaxed);
+        let sum_of_square_scores = self.sum_of_square_scores.load(Ordering::Relaxed);
+
+        let mut violations = vec![];
+
+        if !total_score.is_finite() || !sum_of_square_scores.is_finite() {
+            violations.push(format!(
+                "depth {}: the sums aren't finite, total {total_score} and sum of squares \
+                 {sum_of_square_scores}",
+                self.depth
+            ));
+        } else if number_of_visits == 0 {
+            if total_score != 0.0 || sum_of_square_scores != 0.0 {
+                violations.push(format!(
+                    "depth {}: no visits but a total of {total_score} and a sum of squares of \
+                     {sum_of_square_scores}",
+                    self.depth
+                ));
+            }
+        } else {
+            let smallest_sum_of_squares = total_score.powi(2) / number_of_visits as f64;
+            let tolerance = STATISTICS_TOLERANCE * smallest_sum_of_squares.max(1.0);
+
+            if sum_of_square_scores < smallest_sum_of_squares - tolerance {
+                violations.push(format!(
+                    "depth {}: a sum of squares of {sum_of_square_scores} is less than the \
+                     {smallest_sum_of_squares} that {number_of_visits} visits totalling \
+                     {total_score} need",
+                    self.depth
+                ));
+            }
+        }
+
+        violations
+    }
+
+    /// Check the statistics of every node in the tree below this one
+    ///
+    /// On top of [Node::statistics_violations] for each node, every visit to a child also visited
+    /// its parent, so a parent can never have fewer visits than its children put together. Run
+    /// this after anything that moves statistics around without going through
+    /// [Node::backpropagate], like reusing or merging parts of a tree
+    pub(crate) fn audit_statistics(&self) -> Vec<String> {
+        let mut violations = self.statistics_violations();
+
+        if let Some(children) = self.children.borrow().as_ref() {
+            let number_of_visits = self.number_of_visits.load(Ordering::Relaxed);
+            let children_visits: usize = children
+                .iter()
+                .map(|child| child.number_of_visits.load(Ordering::Relaxed))
+                .sum();
+
+            if children_visits > number_of_visits {
+                violations.push(format!(
+                    "depth {}: {number_of_visits} visits but its children have \
+                     {children_visits}",
+                    self.depth
+                ));
+            }
+
+            for child in children {
+                violations.extend(child.audit_statistics());
+            }
+        }
+
+        violations
+    }
+}
+
+pub trait Scorable<BoardType> {
+    type ScoreType;
+
+    fn score(board: &BoardType, options: &SnakeOptions) -> Self::ScoreType;
+}
+
+impl<'arena, BoardType> Node<'arena, BoardType>
+where
+    BoardType: SimulableGame<Instruments, 4>
+        + SnakeIDGettableGame<SnakeIDType = SnakeId>
+        + HealthGettableGame
+        + HeadGettableGame
+        + NeighborDeterminableGame
+        + NeckQueryableGame
+        + SnakeBodyGettableGame
+        + RandomReasonableMovesGame
+        + ReasonableMovesGame
+        + Clone
+        + VictorDeterminableGame
+        + YouDeterminableGame
+        + Hash,
+    Node<'arena, BoardType>: Scorable<BoardType, ScoreType = N64>,
+{
+    pub(crate) fn simulate<R: Rng>(
+        &self,
+        rng: &mut R,
+        options: &SnakeOptions,
+        cache: &mut RolloutCache,
+        opponents: &OpponentModel,
+        instruments: &Instruments,
+    ) -> N64
+    where
+        BoardType: NeighborDeterminableGame + FoodGettableGame + HeadGettableGame,
+    {
+        // Past the depth cap we back up the heuristic score, the same one a rollout ends with
+        if self.is_at_depth_cap(options) {
+            return Self::score(&self.game_state, options);
+        }
+
+        // Only the state we start from is looked up, the rest of the rollout is too far from
+        // the tree for any of its states to come up again
+        let start_key =
+            (options.rollout_cache_turns > 0).then(|| RolloutCache::key(&self.game_state));
+        if let Some(score) = start_key.and_then(|key| cache.get(key)) {
+            return score;
+        }
+
+        let mut current_state: Cow<BoardType> = Cow::Borrowed(&self.game_state);
+        let mut number_of_iterations = 0;
+        // The last few states this rollout passed through, so we can store the outcome for them
+        // if it ends the game. They're only hashed if it does
+        let mut recent = VecDeque::with_capacity(options.rollout_cache_turns);
+
+        while number_of_iterations < options.max_rollout_turns && !current_state.is_over() {
+            number_of_iterations += 1;
+
+            let mut moves = current_state
+                .random_reasonable_move_for_each_snake(rng)
+                .collect_vec();
+            if !opponents.is_empty() {
+                for (sid, mv) in moves.iter_mut() {
+                    if let Some(modeled) = opponents.profile(sid).and_then(|profile| {
+                        Self::modeled_move(current_state.as_ref(), sid, profile, rng)
+                    }) {
+                        *mv = modeled;
+                    }
+                }
+            }
+            let random_moves = moves.into_iter().map(|(sid, mv)| (sid, [mv]));
+
+            let next_state = {
+                let mut simulation_result =
+                    current_state.simulate_with_moves(instruments, random_moves);
+
+                // TODO: This unwrap might NOT be safe
+                simulation_result.next().unwrap().1
+            };
+
+            let previous = std::mem::replace(&mut current_state, Cow::Owned(next_state));
+            if let Cow::Owned(previous) = previous {
+                if options.rollout_cache_turns > 0 {
+                    if recent.len() == options.rollout_cache_turns {
+                        recent.pop_front();
+                    }
+                    recent.push_back(previous);
+                }
+            }
+        }
+
+        let score = Self::score(current_state.as_ref(), options);
+
+        if current_state.is_over() {
+            for board in &recent {
+                cache.insert(RolloutCache::key(board), score);
+            }
+            // The state we started from was only kept as its key
+            if let Some(key) =
+                start_key.filter(|_| number_of_iterations <= options.rollout_cache_turns)
+            {
+                cache.insert(key, score);
+            }
+        }
+
+        score
+    }
+
+    /// The move an opponent's [OpponentProfile] has it make in a rollout instead of a random one,
+    /// if it does. An aggressive snake heads for our head, and a hungry one for the closest food,
+    /// but only ever with one of its reasonable moves
+    fn modeled_move<R: Rng>(
+        state: &BoardType,
+        sid: &SnakeId,
+        profile: &OpponentProfile,
+        rng: &mut R,
+    ) -> Option<Move>
+    where
+        BoardType: NeighborDeterminableGame + FoodGettableGame + HeadGettableGame,
+    {
+        let targets = if rng.gen_bool(profile.aggression.clamp(0.0, 1.0)) {
+            vec![state.get_head_as_native_position(state.you_id())]
+        } else if rng.gen_bool(profile.food_priority.clamp(0.0, 1.0)) {
+            state.get_all_food_as_native_positions()
+        } else {
+            return None;
+        };
+
+        let (_, reasonable) = state
+            .reasonable_moves_for_each_snake()
+            .find(|(id, _)| id == sid)?;
+
+        state
+            .possible_moves(&state.get_head_as_native_position(sid))
+            .filter(|(m, _)| reasonable.contains(m))
+            .min_by_key(|(_, pos)| {
+                targets
+                    .iter()
+                    .map(|target| dist_between_new(state, pos, target))
+                    .min()
+                    .unwrap_or(i32::MAX)
+            })
+            .map(|(m, _)| m)
+    }
+
+    /// Our move, when the first turn already proves which one to make
+    ///
+    /// This only looks at the boards one turn out from an expanded root, one for each of our moves
+    /// and each reply of the opponents. Rollouts are random, so they can never prove anything
+    /// deeper than that. None if the root hasn't been expanded
+    pub(crate) fn root_proof(&self) -> Option<RootProof> {
+        let children = self.children.borrow();
+        let you_id = self.game_state.you_id();
+
+        let mut not_lost = vec![];
+        for child in children.as_ref()? {
+            let m = child.tree_context.as_ref()?.snake_move.my_move();
+            let replies = child.children.borrow();
+            let replies = replies.as_ref()?;
+
+            if !replies.is_empty()
+                && replies.iter().all(|reply| {
+                    reply.game_state.is_over() && reply.game_state.get_winner() == Some(*you_id)
+                })
+            {
+                return Some(RootProof::ForcedWin(m));
+            }
+            if replies
+                .iter()
+                .all(|reply| reply.game_state.is_alive(you_id))
+            {
+                not_lost.push(m);
+            }
+        }
+
+        match not_lost.as_slice() {
+            [only] => Some(RootProof::OnlyMove(*only)),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn has_been_expanded(&self) -> bool {
+        self.children.borrow().is_some()
+    }
+
+    /// Is this node as deep as [SnakeOptions::max_tree_depth] lets the tree grow
+    pub(crate) fn is_at_depth_cap(&self, options: &SnakeOptions) -> bool {
+        options
+            .max_tree_depth
+            .is_some_and(|max_depth| self.depth >= max_depth)
+    }
+
+    pub(crate) fn ucb1_score(
+        &self,
+        total_number_of_iterations: usize,
+        options: &SnakeOptions,
+    ) -> N64 {
+        let constant: N64 = options.ucb1_constant.into();
+
+        // TODO: This should be fine when we are single threaded
+        // But if/when we get to multi-threaded, we might want to think about if this wants
+        // to use the same visits value like this.
+        // Or do we need to re-load it for each usage?
+        let number_of_visits = self.number_of_visits.load(Ordering::Relaxed);
+        let total_score = self.total_score.load(Ordering::Relaxed);
+        let total_score: N64 = total_score.into();
+
+        if number_of_visits == 0 {
+            return N64::INFINITY;
+        }
+
+        let number_of_visits = number_of_visits as f64;
+        let number_of_visits: N64 = number_of_visits.into();
+
+        let average_score = total_score / number_of_visits;
+        let total_number_of_iterations: N64 = (total_number_of_iterations as f64).into();
+
+        let ln_total_number_of_iterations = total_number_of_iterations.ln();
+
+        let right_hand_side = constant * (ln_total_number_of_iterations / number_of_visits).sqrt();
+
+        average_score + right_hand_side
+    }
+
+    /// The heuristic prior for this node, which decays as we visit the node more and the real
+    /// scores become more trustworthy
+    pub(crate) fn progressive_bias(&self) -> N64 {
+        let number_of_visits = self.number_of_visits.load(Ordering::Relaxed) as f64;
+        let weight: N64 = PROGRESSIVE_BIAS_WEIGHT.into();
+
+        weight * self.prior / (number_of_visits + 1.0)
+    }
+
+    pub(crate) fn ucb1_normal_score(
+        &self,
+        total_number_of_iterations: usize,
+        options: &SnakeOptions,
+    ) -> N64 {
+        let constant: N64 = options.ucb1_normal_constant.into();
+
+        let number_of_visits = self.number_of_visits.load(Ordering::Relaxed);
+        let total_score = self.total_score.load(Ordering::Relaxed);
+        let total_score: N64 = total_score.into();
+
+        if number_of_visits == 0 {
+            return N64::from(FIRST_PLAY_URGENCY) + self.progressive_bias();
+        }
+
+        let number_of_visits = number_of_visits as f64;
+
+        // UCB1-Normal needs enough samples for the variance to be meaningful, so until we have
+        // them we fall back to plain UCB1
+        //
+        // TODO: Future Optimization
+        // We could re-work the surrounding code to do this eagerly so that we don't waste time on
+        // doing the rest of the math if we find a branch that matches this
+        if number_of_visits <= 8.0 * ((total_number_of_iterations) as f64).ln() {
+            return self.ucb1_score(total_number_of_iterations, options) + self.progressive_bias();
+        }
+
+        let number_of_visits: N64 = number_of_visits.into();
+
+        let average_score = total_score / number_of_visits;
+        let total_number_of_iterations_minus_one: N64 =
+            ((total_number_of_iterations - 1) as f64).into();
+
+        let ln_total_number_of_iterations_minus_one = total_number_of_iterations_minus_one.ln();
+
+        let Some(variance) = self.score_variance() else {
+            return self.ucb1_score(total_number_of_iterations, options) + self.progressive_bias();
+        };
+        let first_fraction: N64 = variance.into();
+        let second_fraction = ln_total_number_of_iterations_minus_one / number_of_visits;
+
+        let right_hand_side = (constant * first_fraction * second_fraction).sqrt();
+
+        average_score + right_hand_side + self.progressive_bias()
+    }
+
+    pub(crate) fn average_score(&self) -> Option<f64> {
+        let number_of_visits = self.number_of_visits.load(Ordering::Relaxed);
+        let total_score = self.total_score.load(Ordering::Relaxed);
+
+        if number_of_visits == 0 {
+            return None;
+        }
+
+        let number_of_visits = number_of_visits as f64;
+
+        let average_score = total_score / number_of_visits;
+        Some(average_score)
+    }
+
+    pub(crate) fn next_leaf_node(
+        &'arena self,
+        total_number_of_iterations: usize,
+        options: &SnakeOptions,
+    ) -> &'arena Node<'arena, BoardType> {
+        let mut best_node: &'arena Node<'arena, BoardType> = self;
+
+        while best_node.has_been_expanded() {
+            if let Some(next) = best_node.next_child_to_explore(total_number_of_iterations, options)
+            {
+                best_node = next;
+            } else {
+                break;
+            }
+        }
+
+        best_node
+    }
+
+    /// The child with the highest [Node::ucb1_normal_score]
+    ///
+    /// Children often tie, most of all before they've been visited. `max_by_key` would always hand
+    /// back the last of them, so every search would lean the same way. Instead we take turns
+    /// between the tied children based on how many times we've been through this node, which
+    /// spreads the visits evenly and still picks the same child every time for the same tree
+    pub(crate) fn next_child_to_explore(
+        &self,
+        total_number_of_iterations: usize,
+        options: &SnakeOptions,
+    ) -> Option<&'arena Node<BoardType>> {
+        debug_assert!(self.has_been_expanded());
+
+        let borrowed = self.children.borrow();
+        let children = borrowed
+            .as_ref()
+            .expect("We debug asserts that we are expanded already");
+
+        let scores = children
+            .iter()
+            .map(|child| child.ucb1_normal_score(total_number_of_iterations, options))
+            .collect_vec();
+        let best_score = *scores.iter().max()?;
+
+        let tied = children
+            .iter()
+            .zip(scores)
+            .filter(|(_, score)| *score == best_score)
+            .map(|(child, _)| *child)
+            .collect_vec();
+        let turn = self.number_of_visits.load(Ordering::Relaxed) % tied.len();
+
+        Some(tied[turn])
+    }
+
+    pub(crate) fn highest_average_score_child(&self) -> Option<&'arena Node<BoardType>> {
+        debug_assert!(self.has_been_expanded());
+        let borrowed = self.children.borrow();
+        let children = borrowed
+            .as_ref()
+            .expect("We debug asserts that we are expanded already");
+
+        children
+            .iter()
+            .cloned()
+            .max_by_key(|child| child.average_score().map(N64::from))
+    }
+
+    /// The child of the root we are going to play, which is the one with the highest average
+    /// score unless [SnakeOptions::temperature] asks us to sample by visits instead
+    pub(crate) fn choose_root_child<R: Rng>(
+        &self,
+        options: &SnakeOptions,
+        rng: &mut R,
+    ) -> Option<&'arena Node<BoardType>> {
+        if options.temperature <= 0.0 {
+            return self.highest_average_score_child();
+        }
+
+        debug_assert!(self.has_been_expanded());
+        let borrowed = self.children.borrow();
+        let children = borrowed
+            .as_ref()
+            .expect("We debug asserts that we are expanded already");
+
+        let weights = children.iter().map(|child| {
+            let number_of_visits = child.number_of_visits.load(Ordering::Relaxed) as f64;
+            number_of_visits.powf(1.0 / options.temperature)
+        });
+
+        // If none of the children have been visited there is nothing to sample by
+        match WeightedIndex::new(weights) {
+            Ok(index) => Some(children[index.sample(rng)]),
+            Err(_) => self.highest_average_score_child(),
+        }
+    }
+
+    pub(crate) fn expand(
+        &'arena self,
+        arena: &'arena Arena<Node<'arena, BoardType>>,
+        options: &SnakeOptions,
+        opponents: &OpponentModel,
+        buffers: &mut ExpansionBuffers<BoardType>,
+    ) {
+        debug_assert!(!self.has_been_expanded());
+
+        if self.game_state.is_over() {
+            self.children.replace(Some(vec![]));
+
+            return;
+        }
+
+        let moves_to_sim = self
+            .game_state
+            .reasonable_moves_for_each_snake()
+            .map(|(sid, mut moves)| {
+                // The reasonable moves can miss a neck that's across the edge of a wrapped board
+                if moves.len() > 1 {
+                    if let Some(neck) = neck_move(&self.game_state, &sid) {
+                        moves.retain(|m| *m != neck);
+                    }
+                }
+
+                (sid, moves)
+            })
+            .collect_vec();
+        if cfg!(debug_assertions) {
+            if let Err(e) = check_simulation_moves(&self.game_state, &moves_to_sim) {
+                panic!("MCTS tried to expand with impossible moves: {e}");
+            }
+        }
+
+        // The priors are the change in our flood fill score from the current state, so moves that
+        // grow our share of the board get explored first
+        let current_score = Self::score(&self.game_state, options);
+
+        // Each next state goes straight into the bucket for our own move, so we never hold every
+        // one of them in a single list
+        debug_assert!(buffers.by_own_move.iter().all(Vec::is_empty));
+        for (actions, state) in self
+            .game_state
+            .simulate_with_moves(&buffers.instruments, moves_to_sim)
+        {
+            let prior = Self::score(&state, options) - current_score;
+            buffers.by_own_move[actions.own_move().as_index()].push((actions, state, prior));
+        }
+
+        let own_move_count = buffers
+            .by_own_move
+            .iter()
+            .filter(|next_states| !next_states.is_empty())
+            .count();
+
+        // Only our moves at the root get noise, everything below them is searched as usual
+        let noise = options
+            .root_noise
+            .filter(|_| self.tree_context.is_none())
+            .and_then(|root_noise| {
+                let noise = root_noise.sample(own_move_count, &mut buffers.noise_rng)?;
+                Some((root_noise.weight, noise))
+            });
+
+        let mut children: Vec<&'arena _> = Vec::with_capacity(own_move_count);
+        for (own_move, next_states) in buffers.by_own_move.iter_mut().enumerate() {
+            if next_states.is_empty() {
+                continue;
+            }
+            let i = children.len();
+            let own_move = Move::from_index(own_move);
+
+            // TODO: Passing `game_state` here is WRONG
+            // Really self move nodes can't have a game state, since it depends on the opponent
+            // moves too. We are keeping the 'old' one around here since our types can't model
+            // the real shape of the tree
+            let mut new_node = Node::new_with_parent(
+                self.game_state.clone(),
+                self,
+                SomeonesMove::MyMove(own_move),
+            );
+            new_node.prior = next_states
+                .iter()
+                .fold(N64::from(0.0), |sum, (_, _, prior)| sum + *prior)
+                / N64::from(next_states.len() as f64);
+            if let Some((weight, noise)) = &noise {
+                new_node.prior = new_node.prior * (1.0 - weight) + N64::from(noise[i] * weight);
+            }
+            let new_node: &'arena _ = arena.alloc(new_node);
+            children.push(new_node);
+
+            // Opponents tend to keep going the way they were going, so the combinations where
+            // more of them repeat last turn's move go first. Children that tie on their score are
+            // explored in order, so these get tried before the rest
+            if opponents.has_last_moves() {
+                next_states
+                    .sort_by_key(|(actions, _, _)| Reverse(opponents.repeated_last_moves(actions)));
+            }
+
+            let new_node_children: Vec<&'arena _> = next_states
+                .drain(..)
+                .map(|(actions, state, prior)| {
+                    let mut newer_node =
+                        Node::new_with_parent(state, new_node, SomeonesMove::OtherMoves(actions));
+                    newer_node.prior = prior;
+
+                    &*arena.alloc(newer_node)
+                })
+                .collect_vec();
+
+            new_node.children.replace(Some(new_node_children));
+        }
+
+        debug_assert!(self.children.borrow().is_none());
+
+        self.children.replace(Some(children));
+    }
+
+    pub(crate) fn backpropagate(&self, score: N64) {
+        self.number_of_visits.fetch_add(1, Ordering::Relaxed);
+        {
+            let score: f64 = score.into();
+            self.total_score.fetch_add(score, Ordering::Relaxed);
+            self.sum_of_square_scores
+                .fetch_add(score.powi(2), Ordering::Relaxed);
+        }
+        debug_assert_eq!(self.statistics_violations(), Vec::<String>::new());
+
+        if let Some(tree_context) = &self.tree_context {
+            tree_context.parent.borrow().backpropagate(score)
+        }
+    }
+
+    pub(crate) fn graph(&self, total_number_of_iterations: usize, options: &SnakeOptions) -> Dot {
+        let mut builder = GraphBuilder::new_named_directed("example");
+        self.graph_with(
+            &mut builder,
+            0,
+            vec![],
+            None,
+            total_number_of_iterations,
+            options,
+        );
+
+        let graph = builder.build().unwrap();
+        Dot { graph }
+    }
+
+    // Takes in a builder and adds itself and all children as nodes in the graph
+    // Returns a string that corresponds to the name of the current node
+    //
+    // Our head is shown as x/y, along with where it came from when it moved since the parent, so
+    // a step across the edge of a wrapped board is called out instead of looking like a jump
+    fn graph_with(
+        &self,
+        builder: &mut GraphBuilder,
+        depth: usize,
+        child_id: Vec<usize>,
+        parent_head: Option<BoardType::NativePositionType>,
+        total_number_of_iterations: usize,
+        options: &SnakeOptions,
+    ) -> String {
+        let you_id = self.game_state.you_id();
+        let head = self
+            .game_state
+            .is_alive(you_id)
+            .then(|| self.game_state.get_head_as_native_position(you_id));
+        let you = match (&parent_head, &head) {
+            (Some(from), Some(to)) if from != to => {
+                describe_path(&self.game_state, &[from.clone(), to.clone()])
+            }
+            (_, Some(to)) => describe_position(&self.game_state, to),
+            (_, None) => "Dead".to_owned(),
+        };
+
+        // TODO: Submit a clippy bug report for this
+        #[allow(clippy::useless_asref)]
+        let me_id: String = format!(
+            "Depth: {depth}\nChild ID: {:?}\nMove: {:?}\nYou: {you}\nTotal Score: {:?}\nVisits: {:?}\nUCB1: {}\nAvg Score: {:?}\nIs Over: {:?}",
+            child_id,
+            &self.tree_context.as_ref().map(|t| t.snake_move.clone()),
+            self.total_score,
+            self.number_of_visits,
+            self.ucb1_normal_score(total_number_of_iterations, options),
+            self.average_score(),
+            self.game_state.is_over()
+        );
+
+        builder.add_node(dotavious::Node::new(me_id.as_str()));
+
+        let borrow = self.children.borrow();
+        let children = borrow.as_ref();
+
+        if let Some(children) = children {
+            for (i, child) in children.iter().enumerate() {
+                let mut new_child_id = child_id.clone();
+                new_child_id.push(i);
+                let child_id = child.graph_with(
+                    builder,
+                    depth + 1,
+                    new_child_id,
+                    head.clone(),
+                    total_number_of_iterations,
+                    options,
+                );
+
+                builder.add_edge(Edge::new(me_id.as_str(), child_id.as_str()));
+            }
+        }
+
+        me_id
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use battlesnake_game_types::compact_representation::standard::CellBoard4Snakes11x11;
+    use decorum::Infinite;
+    use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn test_ucb1_empty_score() {
+        let options = SnakeOptions::default();
+        let fixture = include_str!("../fixtures/start_of_game.json");
+        let game = serde_json::from_str::<Game>(fixture).unwrap();
+        let id_map = build_snake_id_map(&game);
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+        let n = Node::new(game);
+
+        assert_eq!(n.ucb1_score(1, &options), N64::INFINITY);
+        assert_eq!(n.ucb1_score(0, &options), N64::INFINITY);
+    }
+
+    #[test]
+    fn test_ucb1_non_empty() {
+        let options = SnakeOptions::default();
+        let fixture = include_str!("../fixtures/start_of_game.json");
+        let game = serde_json::from_str::<Game>(fixture).unwrap();
+        let id_map = build_snake_id_map(&game);
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+
+        let n = Node::new(game);
+        n.number_of_visits.store(1, Ordering::Relaxed);
+        n.total_score.store(10.0, Ordering::Relaxed);
+
+        assert_eq!(n.ucb1_score(1, &options), 10.0);
+        assert!(n.ucb1_score(2, &options) > 11.6);
+        assert!(n.ucb1_score(2, &options) < 11.7);
+    }
+
+    #[test]
+    fn test_ucb1_uses_configured_constant() {
+        let fixture = include_str!("../fixtures/start_of_game.json");
+        let game = serde_json::from_str::<Game>(fixture).unwrap();
+        let id_map = build_snake_id_map(&game);
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+
+        let n = Node::new(game);
+        n.number_of_visits.store(1, Ordering::Relaxed);
+        n.total_score.store(10.0, Ordering::Relaxed);
+
+        let greedy = SnakeOptions {
+            ucb1_constant: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(n.ucb1_score(2, &greedy), 10.0);
+
+        let curious = SnakeOptions {
+            ucb1_constant: 4.0,
+            ..Default::default()
+        };
+        assert!(n.ucb1_score(2, &curious) > 13.3);
+        assert!(n.ucb1_score(2, &curious) < 13.4);
+    }
+
+    #[test]
+    fn test_first_play_urgency_uses_prior() {
+        let options = SnakeOptions::default();
+        let fixture = include_str!("../fixtures/start_of_game.json");
+        let game = serde_json::from_str::<Game>(fixture).unwrap();
+        let id_map = build_snake_id_map(&game);
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+
+        let mut good = Node::new(game);
+        good.prior = 0.5.into();
+        let mut bad = Node::new(game);
+        bad.prior = (-0.5).into();
+
+        assert_eq!(good.ucb1_normal_score(10, &options), 1.5);
+        assert_eq!(bad.ucb1_normal_score(10, &options), 0.5);
+    }
+
+    #[test]
+    fn test_progressive_bias_decays_with_visits() {
+        let fixture = include_str!("../fixtures/start_of_game.json");
+        let game = serde_json::from_str::<Game>(fixture).unwrap();
+        let id_map = build_snake_id_map(&game);
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+
+        let mut n = Node::new(game);
+        n.prior = 1.0.into();
+        assert_eq!(n.progressive_bias(), 1.0);
+
+        n.number_of_visits.store(3, Ordering::Relaxed);
+        assert_eq!(n.progressive_bias(), 0.25);
+    }
+
+    #[test]
+    fn test_average_empty_score() {
+        let fixture = include_str!("../fixtures/start_of_game.json");
+        let game = serde_json::from_str::<Game>(fixture).unwrap();
+        let id_map = build_snake_id_map(&game);
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+        let n = Node::new(game);
+
+        assert_eq!(n.average_score(), None);
+    }
+
+    #[test]
+    fn test_average_non_empty() {
+        let fixture = include_str!("../fixtures/start_of_game.json");
+        let game = serde_json::from_str::<Game>(fixture).unwrap();
+        let id_map = build_snake_id_map(&game);
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+
+        let n = Node::new(game);
+        n.number_of_visits.store(1, Ordering::Relaxed);
+        n.total_score.store(10.0, Ordering::Relaxed);
+
+        assert_eq!(n.average_score(), Some(10.0));
+
+        n.number_of_visits.store(2, Ordering::Relaxed);
+        n.total_score.store(25.0, Ordering::Relaxed);
+
+        assert_eq!(n.average_score(), Some(12.5));
+    }
+
+    #[test]
+    fn test_backpropagate_root() {
+        let fixture = include_str!("../fixtures/start_of_game.json");
+        let game = serde_json::from_str::<Game>(fixture).unwrap();
+        let id_map = build_snake_id_map(&game);
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+        let n = Node::new(game);
+
+        n.backpropagate(10.0.into());
+
+        assert_eq!(n.number_of_visits.load(Ordering::Relaxed), 1);
+        assert_eq!(n.total_score.load(Ordering::Relaxed), 10.0);
+    }
+
+    #[test]
+    fn test_backpropagate_first_child() {
+        let fixture = include_str!("../fixtures/start_of_game.json");
+        let game = serde_json::from_str::<Game>(fixture).unwrap();
+        let id_map = build_snake_id_map(&game);
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+
+        let root = Node::new(game);
+
+        let child = Node::new_with_parent(game, &root, SomeonesMove::MyMove(Move::Up));
+
+        child.backpropagate(10.0.into());
+
+        assert_eq!(child.number_of_visits.load(Ordering::Relaxed), 1);
+        assert_eq!(child.total_score.load(Ordering::Relaxed), 10.0);
+
+        assert_eq!(root.number_of_visits.load(Ordering::Relaxed), 1);
+        assert_eq!(root.total_score.load(Ordering::Relaxed), 10.0);
+
+        let other_child = Node::new_with_parent(game, &root, SomeonesMove::MyMove(Move::Down));
+        other_child.backpropagate(20.0.into());
+
+        assert_eq!(other_child.number_of_visits.load(Ordering::Relaxed), 1);
+        assert_eq!(other_child.total_score.load(Ordering::Relaxed), 20.0);
+
+        assert_eq!(root.number_of_visits.load(Ordering::Relaxed), 2);
+        assert_eq!(root.total_score.load(Ordering::Relaxed), 30.0);
+    }
+
+    #[test]
+    fn test_board_repr() {
+        // This test was a sanity check to make sure the Board knew I died when running into my
+        // tail
+        // For somet reason MCTS is sometimes saying thats the best move, even though its instant
+        // death for me
+        let fixture = include_str!("../fixtures/check_board_doubled_up.json");
+        let game = serde_json::from_str::<Game>(fixture).unwrap();
+        let id_map = build_snake_id_map(&game);
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+
+        let you_id = game.you_id();
+        let body = game.get_snake_body_vec(you_id);
+
+        assert_eq!(body.len(), 5);
+
+        let other_id = game
+            .get_snake_ids()
+            .into_iter()
+            .find(|&id| id != *you_id)
+            .unwrap();
+
+        let result: Vec<_> = game
+            .simulate_with_moves(
+                &Instruments::default(),
+                [(*you_id, vec![Move::Up]), (other_id, vec![Move::Down])],
+            )
+            .collect();
+        assert_eq!(result.len(), 1);
+        let (_, new_state) = result[0];
+
+        assert!(new_state.is_over());
+        assert_eq!(new_state.get_winner(), Some(other_id));
+    }
+
+    #[test]
+    fn test_basic_expand() {
+        let game =
+            serde_json::from_str::<Game>(include_str!("../fixtures/start_of_game.json")).unwrap();
+
+        let id_map = build_snake_id_map(&game);
+        let game = CellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+
+        let arena = Arena::new();
+        let root_node = arena.alloc(Node::new(game));
+
+        assert!(!root_node.has_been_expanded());
+
+        root_node.expand(
+            &arena,
+            &SnakeOptions::default(),
+            &OpponentModel::default(),
+            &mut ExpansionBuffers::default(),
+        );
+
+        assert!(root_node.has_been_expanded());
+
+        let children = root_node.children.borrow();
+        let children = children.as_ref().unwrap();
+        assert_eq!(children.len(), 4);
+
+        let my_moves = children
+            .iter()
+            .map(|child| child.tree_context.as_ref().unwrap().snake_move.clone())
+            .collect::<Vec<_>>();
+
+        for m in Move::all() {
+            assert!(my_moves.contains(&SomeonesMove::MyMove(m)));
+        }
+
+        for child in children {
+            let m = child.tree_context.as_ref().unwrap().snake_move.clone();
+            let m = m.my_move();
+
+            let opponent_moves = child
+                .children
+                .borrow()
+                .as_ref()
+                .unwrap()
+                .iter()
+                .map(|child| child.tree_context.as_ref().unwrap().snake_move.clone())
+                .map(|m| {
+                    if let SomeonesMove::OtherMoves(m) = m {
+                        m
+                    } else {
+                        panic!("Expected an opponents move");
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            for v in Move::all_iter().permutations(2) {
+                let mut actions: [Option<Move>; 4] = Default::default();
+                actions[0] = Some(m);
+                actions[1] = Some(v[0]);
+                actions[2] = Some(v[1]);
+                let actions = Action::new(actions);
+
+                dbg!(&opponent_moves, &actions);
+                assert!(opponent_moves.contains(&actions));
+            }
+
+            let inner_childer = child.children.borrow();
+            let inner_childer = inner_childer.as_ref().unwrap();
+            assert_eq!(inner_childer.len(), 16);
+        }
+    }
+
+    #[test]
+    fn test_root_proof_when_only_one_move_survives() {
+        let mut game =
+            serde_json::from_str::<Game>(include_str!("../fixtures/start_of_game.json")).unwrap();
+        let expanded_proof = |game: Game| {
+            let id_map = build_snake_id_map(&game);
+            let game = CellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+
+            let arena = Arena::new();
+            let root_node = arena.alloc(Node::new(game));
+            assert_eq!(root_node.root_proof(), None);
+
+            root_node.expand(
+                &arena,
+                &SnakeOptions::default(),
+                &OpponentModel::default(),
+                &mut ExpansionBuffers::default(),
+            );
+            root_node.root_proof()
+        };
+
+        // Every move is open at the start of the game
+        assert_eq!(expanded_proof(game.clone()), None);
+
+        // Boxed into the corner by our own body, with the opponent too far away to matter
+        game.board.snakes.truncate(2);
+        let you = &mut game.board.snakes[0];
+        you.body = [(0, 0), (1, 0), (1, 1), (1, 2)]
+            .into_iter()
+            .map(|(x, y)| Position { x, y })
+            .collect();
+        you.head = you.body[0];
+        game.you = you.clone();
+
+        assert_eq!(expanded_proof(game), Some(RootProof::OnlyMove(Move::Up)));
+    }
+
+    #[test]
+    fn test_less_basic_expand() {
+        let game = serde_json::from_str::<Game>(include_str!(
+            "../../fixtures/less_basic_expand_mcts.json"
+        ))
+        .unwrap();
+
+        let id_map = build_snake_id_map(&game);
+        let game = CellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+
+        let arena = Arena::new();
+        let root_node = arena.alloc(Node::new(game));
+
+        assert!(!root_node.has_been_expanded());
+
+        root_node.expand(
+            &arena,
+            &SnakeOptions::default(),
+            &OpponentModel::default(),
+            &mut ExpansionBuffers::default(),
+        );
+
+        assert!(root_node.has_been_expanded());
+
+        let children = root_node.children.borrow();
+        let children = children.as_ref().unwrap();
+        assert_eq!(children.len(), 2);
+
+        let my_moves = children
+            .iter()
+            .map(|child| child.tree_context.as_ref().unwrap().snake_move.clone())
+            .collect::<Vec<_>>();
+
+        for m in [Move::Up, Move::Down] {
+            assert!(my_moves.contains(&SomeonesMove::MyMove(m)));
+        }
+
+        for child in children {
+            let m = child.tree_context.as_ref().unwrap().snake_move.clone();
+            let m = m.my_move();
+
+            let opponent_moves = child
+                .children
+                .borrow()
+                .as_ref()
+                .unwrap()
+                .iter()
+                .map(|child| child.tree_context.as_ref().unwrap().snake_move.clone())
+                .map(|m| {
+                    if let SomeonesMove::OtherMoves(m) = m {
+                        m
+                    } else {
+                        panic!("Expected an opponents move");
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            for v in [Move::Right, Move::Down].iter() {
+                let mut actions: [Option<Move>; 4] = Default::default();
+                actions[0] = Some(m);
+                actions[1] = Some(*v);
+                let actions = Action::new(actions);
+
+                dbg!(&opponent_moves, &actions);
+                assert!(opponent_moves.contains(&actions));
+            }
+
+            let inner_childer = child.children.borrow();
+            let inner_childer = inner_childer.as_ref().unwrap();
+            assert_eq!(inner_childer.len(), 2);
+        }
+    }
+
+    #[test]
+    fn test_rollout_cache_counts_hits() {
+        let mut cache = RolloutCache::new(10);
+        for _ in 0..MIN_CACHED_ROLLOUTS {
+            cache.insert(1, 0.5.into());
+        }
+
+        assert_eq!(cache.get(1), Some(0.5.into()));
+        assert_eq!(cache.get(2), None);
+        assert_eq!(cache.hits(), 1);
+        assert_eq!(cache.lookups(), 2);
+        assert_eq!(cache.hit_rate(), Some(0.5));
+    }
+
+    #[test]
+    fn test_rollout_cache_samples_fresh_until_it_has_a_mean() {
+        let mut cache = RolloutCache::new(10);
+        for i in 0..MIN_CACHED_ROLLOUTS {
+            assert_eq!(cache.get(1), None, "Reused after only {i} rollouts");
+            // Alternating wins and losses, so a single one is a long way from the mean
+            cache.insert(1, if i % 2 == 0 { 1.0 } else { 0.0 }.into());
+        }
+
+        assert_eq!(cache.get(1), Some(0.5.into()));
+        assert_eq!(cache.hits(), 1);
+    }
+
+    #[test]
+    fn test_rollout_cache_stops_growing_at_capacity() {
+        let mut cache = RolloutCache::new(2);
+        for key in 0..5 {
+            cache.insert(key, 1.0.into());
+        }
+
+        assert_eq!(cache.entries(), 2);
+        assert_eq!(cache.get(4), None);
+    }
+
+    #[test]
+    fn test_expanding_and_rollouts_are_both_counted() {
+        let fixture = include_str!("../fixtures/start_of_game.json");
+        let game = serde_json::from_str::<Game>(fixture).unwrap();
+        let id_map = build_snake_id_map(&game);
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+
+        let stats = std::sync::Arc::new(battlesnake_minimax::SimulationStats::default());
+        let instruments = Instruments::recording(stats.clone());
+
+        let arena = Arena::new();
+        let root_node = arena.alloc(Node::new(game));
+        root_node.expand(
+            &arena,
+            &SnakeOptions::default(),
+            &OpponentModel::default(),
+            &mut ExpansionBuffers::default().recording(instruments.clone()),
+        );
+        let expanded = stats.simulations();
+        assert!(expanded > 0);
+
+        root_node.simulate(
+            &mut rand::thread_rng(),
+            &SnakeOptions::default(),
+            &mut RolloutCache::new(10),
+            &OpponentModel::default(),
+            &instruments,
+        );
+        assert!(stats.simulations() > expanded);
+    }
+
+    #[test]
+    fn test_nodes_at_the_depth_cap_are_scored_without_a_rollout() {
+        let fixture = include_str!("../fixtures/start_of_game.json");
+        let game = serde_json::from_str::<Game>(fixture).unwrap();
+        let id_map = build_snake_id_map(&game);
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+
+        let arena = Arena::new();
+        let root_node = arena.alloc(Node::new(game));
+        let options = SnakeOptions {
+            max_tree_depth: Some(0),
+            ..Default::default()
+        };
+
+        assert!(root_node.is_at_depth_cap(&options));
+        assert!(!root_node.is_at_depth_cap(&SnakeOptions::default()));
+
+        let mut cache = RolloutCache::new(10);
+        let score = root_node.simulate(
+            &mut rand::thread_rng(),
+            &options,
+            &mut cache,
+            &OpponentModel::default(),
+            &Instruments::default(),
+        );
+
+        assert_eq!(
+            score,
+            Node::<StandardCellBoard4Snakes11x11>::score(&root_node.game_state, &options)
+        );
+        assert_eq!(cache.lookups(), 0);
+    }
+
+    #[test]
+    fn test_modeled_opponents_chase_their_targets() {
+        let fixture = include_str!("../fixtures/start_of_game.json");
+        let game = serde_json::from_str::<Game>(fixture).unwrap();
+        let id_map = build_snake_id_map(&game);
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+        type BoardNode<'a> = Node<'a, StandardCellBoard4Snakes11x11>;
+
+        // This snake starts at (5, 9) and we start at (9, 5)
+        let sid = id_map["#FF6c96"];
+        let mut rng = rand::thread_rng();
+
+        let aggressive = OpponentProfile {
+            aggression: 1.0,
+            food_priority: 0.0,
+        };
+        for _ in 0..10 {
+            let modeled = BoardNode::modeled_move(&game, &sid, &aggressive, &mut rng);
+            assert!(matches!(modeled, Some(Move::Right | Move::Down)));
+        }
+
+        let indifferent = OpponentProfile {
+            aggression: 0.0,
+            food_priority: 0.0,
+        };
+        assert_eq!(
+            BoardNode::modeled_move(&game, &sid, &indifferent, &mut rng),
+            None
+        );
+
+        let mut model = OpponentModel::default();
+        model.insert(sid, aggressive);
+        let arena = Arena::new();
+        let root_node = arena.alloc(Node::new(game));
+        let score = root_node.simulate(
+            &mut rng,
+            &SnakeOptions::default(),
+            &mut RolloutCache::new(10),
+            &model,
+            &Instruments::default(),
+        );
+        assert!((-1.0..=1.0).contains(&f64::from(score)));
+    }
+
+    #[test]
+    fn test_opponents_repeating_last_move_are_expanded_first() {
+        let fixture = include_str!("../fixtures/start_of_game.json");
+        let game = serde_json::from_str::<Game>(fixture).unwrap();
+        let id_map = build_snake_id_map(&game);
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+
+        let mut opponents = OpponentModel::default();
+        opponents.insert_last_move(id_map["#FF6c96"], Move::Down);
+        opponents.insert_last_move(id_map["#FF6444"], Move::Left);
+
+        let arena = Arena::new();
+        let root_node = arena.alloc(Node::new(game));
+        root_node.expand(
+            &arena,
+            &SnakeOptions::default(),
+            &opponents,
+            &mut ExpansionBuffers::default(),
+        );
+
+        for child in root_node.children.borrow().iter().flatten() {
+            let repeated = child
+                .children
+                .borrow()
+                .iter()
+                .flatten()
+                .map(
+                    |grandchild| match &grandchild.tree_context.as_ref().unwrap().snake_move {
+                        SomeonesMove::OtherMoves(actions) => opponents.repeated_last_moves(actions),
+                        SomeonesMove::MyMove(_) => panic!("Expected an opponents move"),
+                    },
+                )
+                .collect_vec();
+
+            assert_eq!(repeated.first(), Some(&2));
+            assert!(repeated.windows(2).all(|pair| pair[0] >= pair[1]));
+        }
+    }
+
+    #[test]
+    fn test_root_noise_only_changes_the_root_priors() {
+        let fixture = include_str!("../fixtures/start_of_game.json");
+        let game = serde_json::from_str::<Game>(fixture).unwrap();
+        let id_map = build_snake_id_map(&game);
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+
+        let arena = Arena::new();
+        let root_node = arena.alloc(Node::new(game));
+        // With all of the weight on the noise the priors are just the noise, which adds up to 1
+        let options = SnakeOptions {
+            root_noise: Some(RootNoise {
+                alpha: 0.3,
+                weight: 1.0,
+            }),
+            ..Default::default()
+        };
+        root_node.expand(
+            &arena,
+            &options,
+            &OpponentModel::default(),
+            &mut ExpansionBuffers::default(),
+        );
+
+        let children = root_node.children.borrow();
+        let children = children.as_ref().unwrap();
+        let total_prior: f64 = children
+            .iter()
+            .map(|child| -> f64 { child.prior.into() })
+            .sum();
+        assert!((total_prior - 1.0).abs() < 1e-9);
+
+        let grandchild = children[0].children.borrow().as_ref().unwrap()[0];
+        grandchild.expand(
+            &arena,
+            &options,
+            &OpponentModel::default(),
+            &mut ExpansionBuffers::default(),
+        );
+        let noiseless = arena.alloc(Node::new(grandchild.game_state.clone()));
+        noiseless.expand(
+            &arena,
+            &SnakeOptions::default(),
+            &OpponentModel::default(),
+            &mut ExpansionBuffers::default(),
+        );
+
+        let priors = |node: &Node<StandardCellBoard4Snakes11x11>| {
+            node.children
+                .borrow()
+                .as_ref()
+                .unwrap()
+                .iter()
+                .map(|child| child.prior)
+                .collect_vec()
+        };
+        assert_eq!(priors(grandchild), priors(noiseless));
+    }
+
+    #[test]
+    fn test_expansion_buffers_are_emptied_and_reused() {
+        let fixture = include_str!("../fixtures/start_of_game.json");
+        let game = serde_json::from_str::<Game>(fixture).unwrap();
+        let id_map = build_snake_id_map(&game);
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+        let options = SnakeOptions::default();
+
+        let arena = Arena::new();
+        let mut buffers = ExpansionBuffers::default();
+        let shared_root = arena.alloc(Node::new(game));
+        shared_root.expand(&arena, &options, &OpponentModel::default(), &mut buffers);
+        let capacity = buffers.capacity();
+        assert!(capacity > 0);
+
+        let fresh_root = arena.alloc(Node::new(game));
+        fresh_root.expand(
+            &arena,
+            &options,
+            &OpponentModel::default(),
+            &mut ExpansionBuffers::default(),
+        );
+        let reused_root = arena.alloc(Node::new(game));
+        reused_root.expand(&arena, &options, &OpponentModel::default(), &mut buffers);
+
+        assert_eq!(buffers.capacity(), capacity);
+        let grandchildren = |node: &Node<_>| {
+            node.children
+                .borrow()
+                .as_ref()
+                .unwrap()
+                .iter()
+                .map(|child| child.children.borrow().as_ref().unwrap().len())
+                .collect_vec()
+        };
+        assert_eq!(grandchildren(reused_root), grandchildren(fresh_root));
+    }
+
+    #[test]
+    fn test_temperature_samples_the_root_children_by_visits() {
+        let fixture = include_str!("../fixtures/start_of_game.json");
+        let game = serde_json::from_str::<Game>(fixture).unwrap();
+        let id_map = build_snake_id_map(&game);
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+
+        let arena = Arena::new();
+        let root_node = arena.alloc(Node::new(game));
+        root_node.expand(
+            &arena,
+            &SnakeOptions::default(),
+            &OpponentModel::default(),
+            &mut ExpansionBuffers::default(),
+        );
+
+        let children = root_node.children.borrow();
+        let children = children.as_ref().unwrap();
+        // The first child scores best but the second has nearly all of the visits
+        children[0].backpropagate(10.0.into());
+        for _ in 0..10 {
+            children[1].backpropagate(1.0.into());
+        }
+
+        let mut rng = rand::thread_rng();
+        let greedy = SnakeOptions::default();
+        // A low temperature sharpens the visits, so the second child is all but certain to be
+        // picked
+        let sampled = SnakeOptions {
+            temperature: 0.1,
+            ..Default::default()
+        };
+
+        assert!(std::ptr::eq(
+            root_node.choose_root_child(&greedy, &mut rng).unwrap(),
+            children[0]
+        ));
+        for _ in 0..20 {
+            assert!(std::ptr::eq(
+                root_node.choose_root_child(&sampled, &mut rng).unwrap(),
+                children[1]
+            ));
+        }
+    }
+
+    #[test]
+    fn test_tied_children_are_explored_evenly() {
+        let fixture = include_str!("../fixtures/start_of_game.json");
+        let game = serde_json::from_str::<Game>(fixture).unwrap();
+        let id_map = build_snake_id_map(&game);
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+        let options = SnakeOptions::default();
+
+        // None of the children are ever visited, so they tie on every pick
+        let arena = Arena::new();
+        let root_node: &Node<_> = arena.alloc(Node::new(game));
+        let children = (0..4).map(|_| &*arena.alloc(Node::new(game))).collect_vec();
+        root_node.children.replace(Some(children.clone()));
+
+        let mut picks = [0; 4];
+        for iteration in 1..=400 {
+            let next = root_node
+                .next_child_to_explore(iteration, &options)
+                .unwrap();
+            let index = children
+                .iter()
+                .position(|child| std::ptr::eq(*child, next))
+                .unwrap();
+            picks[index] += 1;
+
+            root_node.backpropagate(0.0.into());
+        }
+
+        assert_eq!(picks, [100; 4]);
+    }
+
+    #[test]
+    fn test_score_variance_is_never_negative() {
+        let fixture = include_str!("../fixtures/start_of_game.json");
+        let game = serde_json::from_str::<Game>(fixture).unwrap();
+        let id_map = build_snake_id_map(&game);
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+
+        let node = Node::new(game);
+        assert_eq!(node.score_variance(), None);
+
+        // Identical scores are where rounding in the running sums can push the variance below 0
+        for _ in 0..1000 {
+            node.backpropagate(0.1.into());
+        }
+
+        assert!(node.score_variance().unwrap() >= 0.0);
+        assert!(node.statistics_violations().is_empty());
+        // This is N64, so a NaN from the square root of a negative variance would panic
+        node.ucb1_normal_score(2, &SnakeOptions::default());
+    }
+
+    #[test]
+    fn test_random_backpropagation_keeps_the_statistics_consistent() {
+        let fixture = include_str!("../fixtures/start_of_game.json");
+        let game = serde_json::from_str::<Game>(fixture).unwrap();
+        let id_map = build_snake_id_map(&game);
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+        let options = SnakeOptions::default();
+
+        let mut rng = StdRng::seed_from_u64(4938);
+        for _ in 0..5 {
+            let arena = Arena::new();
+            let mut buffers = ExpansionBuffers::default();
+            let root_node: &Node<_> = arena.alloc(Node::new(game));
+            root_node.expand(&arena, &options, &OpponentModel::default(), &mut buffers);
+
+            let mut scores = vec![];
+            for _ in 0..100 {
+                // Walk down a random path, growing the tree the same way the search does
+                let mut node: &Node<_> = root_node;
+                loop {
+                    if !node.has_been_expanded() {
+                        if node.number_of_visits.load(Ordering::Relaxed) == 0 {
+                            break;
+                        }
+                        node.expand(&arena, &options, &OpponentModel::default(), &mut buffers);
+                    }
+
+                    let next = node
+                        .children
+                        .borrow()
+                        .as_ref()
+                        .unwrap()
+                        .choose(&mut rng)
+                        .copied();
+                    match next {
+                        Some(child) => node = child,
+                        None => break,
+                    }
+                }
+
+                let score: f64 = rng.gen_range(-1000.0..1000.0);
+                node.backpropagate(score.into());
+                scores.push(score);
+            }
+
+            assert_eq!(root_node.audit_statistics(), Vec::<String>::new());
+
+            let n = scores.len() as f64;
+            let mean = scores.iter().sum::<f64>() / n;
+            let expected_variance =
+                scores.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / (n - 1.0);
+            let variance = root_node.score_variance().unwrap();
+            assert!((variance - expected_variance).abs() <= 1e-6 * expected_variance);
+        }
+    }
+
+    #[test]
+    fn test_audit_finds_inconsistent_statistics() {
+        let fixture = include_str!("../fixtures/start_of_game.json");
+        let game = serde_json::from_str::<Game>(fixture).unwrap();
+        let id_map = build_snake_id_map(&game);
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+
+        let arena = Arena::new();
+        let root_node = arena.alloc(Node::new(game));
+        root_node.expand(
+            &arena,
+            &SnakeOptions::default(),
+            &OpponentModel::default(),
+            &mut ExpansionBuffers::default(),
+        );
+
+        let children = root_node.children.borrow();
+        let children = children.as_ref().unwrap();
+        children[0].backpropagate(3.0.into());
+        children[0].backpropagate(5.0.into());
+        assert_eq!(root_node.audit_statistics(), Vec::<String>::new());
+
+        // Visits that never reached the root, like a subtree merged in without its parent
+        children[1].number_of_visits.fetch_add(1, Ordering::Relaxed);
+        children[1]
+            .sum_of_square_scores
+            .fetch_add(1.0, Ordering::Relaxed);
+        children[1].total_score.fetch_add(1.0, Ordering::Relaxed);
+        assert_eq!(root_node.audit_statistics().len(), 1);
+
+        // A sum of squares too small for the total can't come from real scores
+        children[2].number_of_visits.fetch_add(2, Ordering::Relaxed);
+        children[2].total_score.fetch_add(10.0, Ordering::Relaxed);
+        assert_eq!(children[2].statistics_violations().len(), 1);
+    }
+
+    /// UCB1-Normal worked out from the exact sums of scores given in thousandths, to check the
+    /// running sums against
+    fn reference_ucb1_normal(
+        scores: &[i64],
+        total_number_of_iterations: usize,
+        options: &SnakeOptions,
+    ) -> (f64, f64) {
+        let n = scores.len() as i128;
+        let sum: i128 = scores.iter().map(|s| *s as i128).sum();
+        let sum_of_squares: i128 = scores.iter().map(|s| (*s as i128).pow(2)).sum();
+
+        let average = sum as f64 / 1_000.0 / n as f64;
+        let variance = (n * sum_of_squares - sum * sum) as f64 / (n * (n - 1)) as f64 / 1e6;
+        let exploration = (options.ucb1_normal_constant
+            * variance
+            * ((total_number_of_iterations - 1) as f64).ln()
+            / n as f64)
+            .sqrt();
+
+        (variance, average + exploration)
+    }
+
+    #[test]
+    fn test_long_runs_pick_the_same_child_as_exact_sums() {
+        let options = SnakeOptions::default();
+        let fixture = include_str!("../fixtures/start_of_game.json");
+        let game = serde_json::from_str::<Game>(fixture).unwrap();
+        let id_map = build_snake_id_map(&game);
+        let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
+
+        // Scores bunched up around the same average, where the variance is tiny next to the sums
+        // and adding them up naively leaves it off by about one part in a million
+        let runs: [Vec<i64>; 2] = [
+            (0..200_000).map(|i| 500 + (i * 7919) % 11).collect(),
+            (0..200_000).map(|i| 504 + (i * 104729) % 3).collect(),
+        ];
+        let total_number_of_iterations = 400_000;
+
+        let mut scores = vec![];
+        for run in &runs {
+            let node = Node::new(game);
+            for score in run {
+                node.backpropagate((*score as f64 / 1_000.0).into());
+            }
+
+            let (variance, reference) =
+                reference_ucb1_normal(run, total_number_of_iterations, &options);
+            let score: f64 = node
+                .ucb1_normal_score(total_number_of_iterations, &options)
+                .into();
+
+            assert!((node.score_variance().unwrap() - variance).abs() < 1e-9 * variance);
+            assert!((score - reference).abs() < 1e-12);
+            scores.push((score, reference));
+        }
+
+        assert_eq!(
+            scores[0].0 > scores[1].0,
+            scores[0].1 > scores[1].1,
+            "{scores:?}"
+        );
+    }
+}
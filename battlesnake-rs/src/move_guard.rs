@@ -0,0 +1,467 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use battlesnake_game_types::wire_representation::BattleSnake;
+use tracing::{error, warn};
+
+use crate::{
+    board_only::{you_on_board, BoardOnlySnake},
+    head_to_head::{equal_trade_risk, head_to_head_outcome, next_head, EqualTradeRisk, HeadToHead},
+    *,
+};
+
+/// Wraps another factory so that every move its snakes make goes through [guard_move]
+///
+/// When `you` isn't on the board the wrapped factory isn't asked for a snake at all, and we hand
+/// back a [BoardOnlySnake] instead
+pub struct GuardedFactory(pub BoxedFactory);
+
+impl BattlesnakeFactory for GuardedFactory {
+    fn name(&self) -> String {
+        self.0.name()
+    }
+
+    fn create_from_wire_game(&self, game: Game) -> BoxedSnake {
+        if !you_on_board(&game) {
+            return Box::new(BoardOnlySnake::new(game));
+        }
+
+        Box::new(GuardedSnake {
+            game: game.clone(),
+            inner: self.0.create_from_wire_game(game),
+        })
+    }
+
+    fn about(&self) -> AboutMe {
+        self.0.about()
+    }
+}
+
+struct GuardedSnake {
+    game: Game,
+    inner: BoxedSnake,
+}
+
+impl BattlesnakeAI for GuardedSnake {
+    fn end(&self) {
+        self.inner.end()
+    }
+
+    fn make_move(&self) -> Result<MoveOutput> {
+        let output = self.inner.make_move()?;
+        check_reasonable_move(&self.game, &output, None);
+
+        Ok(guard_move(&self.game, output))
+    }
+
+    fn explain_move(&self) -> Result<MoveExplanation> {
+        let explanation = self.inner.explain_move()?;
+        check_reasonable_move(&self.game, &explanation.output, None);
+
+        Ok(MoveExplanation {
+            output: guard_move(&self.game, explanation.output),
+            ..explanation
+        })
+    }
+
+    fn make_move_with_summary(&self) -> Result<(MoveOutput, SearchSummary)> {
+        let (output, summary) = self.inner.make_move_with_summary()?;
+        check_reasonable_move(&self.game, &output, Some(&summary));
+
+        Ok((guard_move(&self.game, output), summary))
+    }
+}
+
+/// Is this cell still going to be part of a snake after everyone moves
+///
+/// Tails move out of the way, unless they are stacked because the snake just ate. We don't count
+/// on any snake eating this turn, since that isn't forced
+fn is_occupied_next_turn(game: &Game, pos: Position) -> bool {
+    game.board.snakes.iter().any(|snake| {
+        let tail_moves = snake.body.len() < 2
+            || snake.body[snake.body.len() - 1] != snake.body[snake.body.len() - 2];
+        let staying = if tail_moves {
+            snake.body.len().saturating_sub(1)
+        } else {
+            snake.body.len()
+        };
+
+        snake.body.iter().take(staying).any(|p| *p == pos)
+    })
+}
+
+/// The moves that don't run this snake into a wall or a body
+fn reasonable_moves_for(game: &Game, snake: &BattleSnake) -> Vec<(Move, Position)> {
+    Move::all_iter()
+        .filter_map(|m| next_head(game, snake.head, m).map(|pos| (m, pos)))
+        .filter(|(_, pos)| !is_occupied_next_turn(game, *pos))
+        .collect()
+}
+
+/// How much health a snake loses for ending its move on `pos`, counting every hazard stacked there
+///
+/// Eating food on a hazard skips the damage, but we only use this where food keeps the snake
+/// alive anyway
+fn hazard_damage(game: &Game, pos: Position) -> i32 {
+    let damage_per_hazard = game
+        .game
+        .ruleset
+        .settings
+        .as_ref()
+        .map_or(0, |settings| settings.hazard_damage_per_turn);
+    let stacked = game.board.hazards.iter().filter(|h| **h == pos).count() as i32;
+
+    damage_per_hazard * stacked
+}
+
+/// The moves that don't kill this snake on their own, ignoring other snakes' heads
+///
+/// Every move costs a point of health, and moving into a hazard costs its damage on top of that.
+/// Eating restores the health, so a move onto food never starves us
+fn safe_moves(game: &Game, snake: &BattleSnake) -> Vec<(Move, Position)> {
+    reasonable_moves_for(game, snake)
+        .into_iter()
+        .filter(|(_, pos)| {
+            snake.health - 1 - hazard_damage(game, *pos) > 0 || game.board.food.contains(pos)
+        })
+        .collect()
+}
+
+/// Would we lose the head-to-head if we make `my_move` and the opponent makes `their_move`. Trading
+/// heads with a snake our length only counts as losing when the [EqualTradeRisk] says to avoid it
+fn loses_head_to_head(
+    game: &Game,
+    me: &BattleSnake,
+    my_move: Move,
+    opponent: &BattleSnake,
+    their_move: Move,
+    risk: EqualTradeRisk,
+) -> bool {
+    match head_to_head_outcome(game, me, opponent, my_move, their_move) {
+        HeadToHead::SecondWins => true,
+        HeadToHead::BothDie => risk == EqualTradeRisk::Avoid,
+        HeadToHead::NoCollision | HeadToHead::FirstWins => false,
+    }
+}
+
+fn opponents<'a>(game: &'a Game, me: &'a BattleSnake) -> impl Iterator<Item = &'a BattleSnake> {
+    game.board.snakes.iter().filter(move |s| s.id != me.id)
+}
+
+/// Is making this move fatal no matter what anyone else does
+///
+/// Besides walls, bodies and starving, this includes head-to-heads we'd lose where the opponent has
+/// no other safe move, so they are forced to take the collision. Whether that counts equal-length
+/// head-to-heads comes from [equal_trade_risk]
+pub fn is_fatal(game: &Game, me: &BattleSnake, m: Move) -> bool {
+    is_fatal_with_risk(game, me, m, equal_trade_risk(game, me))
+}
+
+/// [is_fatal] with the [EqualTradeRisk] already worked out, for checking every move at once
+pub fn is_fatal_with_risk(game: &Game, me: &BattleSnake, m: Move, risk: EqualTradeRisk) -> bool {
+    if !safe_moves(game, me).iter().any(|(safe, _)| *safe == m) {
+        return true;
+    }
+
+    opponents(game, me).any(|opponent| {
+        let theirs = safe_moves(game, opponent);
+
+        !theirs.is_empty()
+            && theirs
+                .iter()
+                .all(|(their_move, _)| loses_head_to_head(game, me, m, opponent, *their_move, risk))
+    })
+}
+
+/// How good an alternative move looks at a glance
+///
+/// We'd rather not risk a head-to-head we could lose, and after that prefer the move with the most
+/// open cells around it
+fn alternative_rank(game: &Game, me: &BattleSnake, m: Move, risk: EqualTradeRisk) -> (bool, usize) {
+    let risks_head_to_head = opponents(game, me).any(|opponent| {
+        safe_moves(game, opponent)
+            .iter()
+            .any(|(their_move, _)| loses_head_to_head(game, me, m, opponent, *their_move, risk))
+    });
+    let open_neighbors = next_head(game, me.head, m)
+        .map(|pos| {
+            Move::all_iter()
+                .filter_map(|m| next_head(game, pos, m))
+                .filter(|p| !is_occupied_next_turn(game, *p))
+                .count()
+        })
+        .unwrap_or_default();
+
+    (!risks_head_to_head, open_neighbors)
+}
+
+/// The last line of defence before a move goes back to the engine
+///
+/// If the chosen move kills us on the spot, by running into a wall, a body or our own neck, by
+/// starving or taking more hazard damage than we have health left, or into a head-to-head the opponent is forced into and we'd lose, we swap it for the best move that
+/// doesn't. If every move is fatal we leave the choice alone, since it doesn't matter anymore
+///
+/// Head-to-heads with a snake our length are only fatal when we're doing well enough to avoid
+/// them, see [equal_trade_risk]
+pub fn guard_move(game: &Game, output: MoveOutput) -> MoveOutput {
+    let me = match game.board.snakes.iter().find(|s| s.id == game.you.id) {
+        Some(me) => me,
+        None => return output,
+    };
+    let risk = equal_trade_risk(game, me);
+
+    let chosen = Move::all_iter().find(|m| m.to_string() == output.r#move);
+    let chosen_is_fatal = chosen
+        .map(|m| is_fatal_with_risk(game, me, m, risk))
+        .unwrap_or(true);
+    if !chosen_is_fatal {
+        return output;
+    }
+
+    let alternative = Move::all_iter()
+        .filter(|m| !is_fatal_with_risk(game, me, *m, risk))
+        .max_by_key(|m| alternative_rank(game, me, *m, risk));
+
+    match alternative {
+        Some(m) => {
+            error!(
+                game_id = %game.game.id,
+                turn = game.turn,
+                chosen = %output.r#move,
+                replacement = %m,
+                "The chosen move was immediately fatal, replacing it"
+            );
+
+            MoveOutput {
+                r#move: m.to_string(),
+                shout: output.shout,
+            }
+        }
+        None => {
+            warn!(
+                game_id = %game.game.id,
+                turn = game.turn,
+                chosen = %output.r#move,
+                "Every move is immediately fatal, keeping the chosen move"
+            );
+
+            output
+        }
+    }
+}
+
+/// Every move [check_reasonable_move] caught since this process started
+static UNREASONABLE_MOVES: AtomicUsize = AtomicUsize::new(0);
+
+/// How many of the moves our snakes came up with ran into a wall or a body, since this process
+/// started. Each of those is a bug, even when [guard_move] caught it before it was sent
+pub fn unreasonable_moves() -> usize {
+    UNREASONABLE_MOVES.load(Ordering::Relaxed)
+}
+
+/// Our moves that don't run into a wall or a body, wrapping across the edges in wrapped games
+///
+/// Empty when `you` isn't on the board
+pub fn reasonable_moves(game: &Game) -> Vec<Move> {
+    game.board
+        .snakes
+        .iter()
+        .find(|s| s.id == game.you.id)
+        .map(|me| {
+            reasonable_moves_for(game, me)
+                .into_iter()
+                .map(|(m, _)| m)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Check the move a snake came up with is one of our [reasonable_moves], before [guard_move] gets
+/// a chance to cover for it
+///
+/// No snake should ever pick a wall, a body or its own neck while there's anything else to pick,
+/// so that's always a bug, usually in how the board was converted. It's counted in
+/// [unreasonable_moves] and warned about along with the board and the `summary` of the search
+/// that picked it, and it fails a debug assertion so tests catch it right away. Returns whether
+/// the move was reasonable, which it always is when there's no reasonable move to make
+pub fn check_reasonable_move(
+    game: &Game,
+    output: &MoveOutput,
+    summary: Option<&SearchSummary>,
+) -> bool {
+    let reasonable = reasonable_moves(game);
+    if reasonable.is_empty() || reasonable.iter().any(|m| m.to_string() == output.r#move) {
+        return true;
+    }
+
+    let unreasonable_moves = UNREASONABLE_MOVES.fetch_add(1, Ordering::Relaxed) + 1;
+    warn!(
+        game_id = %game.game.id,
+        turn = game.turn,
+        chosen = %output.r#move,
+        reasonable = ?reasonable,
+        unreasonable_moves,
+        board = %serde_json::to_string(&game.board).unwrap_or_default(),
+        summary = ?summary,
+        "The chosen move runs into a wall or a body"
+    );
+    debug_assert!(
+        false,
+        "{} isn't one of the reasonable moves {reasonable:?} on turn {} of {}",
+        output.r#move, game.turn, game.game.id
+    );
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use battlesnake_game_types::wire_representation::Settings;
+
+    use super::*;
+
+    fn game_with_bodies(bodies: Vec<Vec<(i32, i32)>>) -> Game {
+        let mut game: Game =
+            serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap();
+        game.board.food = vec![];
+        game.board.snakes.truncate(bodies.len());
+
+        for (snake, body) in game.board.snakes.iter_mut().zip(bodies) {
+            snake.body = body
+                .into_iter()
+                .map(|(x, y)| Position { x, y })
+                .collect::<VecDeque<_>>();
+            snake.head = snake.body[0];
+        }
+        game.you = game.board.snakes[0].clone();
+
+        game
+    }
+
+    fn guarded(game: &Game, m: &str) -> String {
+        let output = MoveOutput {
+            r#move: m.to_owned(),
+            shout: None,
+        };
+
+        guard_move(game, output).r#move
+    }
+
+    #[test]
+    fn test_reasonable_moves_leave_out_walls_and_bodies() {
+        let game = game_with_bodies(vec![vec![(10, 5), (9, 5), (8, 5)], vec![(10, 6), (9, 6)]]);
+
+        assert_eq!(reasonable_moves(&game), vec![Move::Down]);
+
+        let output = MoveOutput {
+            r#move: "down".to_owned(),
+            shout: None,
+        };
+        assert!(check_reasonable_move(&game, &output, None));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "isn't one of the reasonable moves")]
+    fn test_unreasonable_moves_fail_the_debug_assertion() {
+        let game = game_with_bodies(vec![vec![(10, 5), (9, 5), (8, 5)]]);
+        let output = MoveOutput {
+            r#move: "right".to_owned(),
+            shout: None,
+        };
+
+        check_reasonable_move(&game, &output, None);
+    }
+
+    #[test]
+    fn test_keeps_safe_moves() {
+        let game = game_with_bodies(vec![vec![(5, 5), (5, 4), (5, 3)]]);
+
+        assert_eq!(guarded(&game, "left"), "left");
+        assert_eq!(guarded(&game, "up"), "up");
+    }
+
+    #[test]
+    fn test_replaces_moves_into_walls_and_necks() {
+        let game = game_with_bodies(vec![vec![(10, 5), (9, 5), (8, 5)]]);
+
+        assert_ne!(guarded(&game, "right"), "right");
+        assert_ne!(guarded(&game, "left"), "left");
+    }
+
+    #[test]
+    fn test_wrapped_constrictor_moves_wrap_across_the_edge() {
+        // Up and down run into the opponent, so going left across the edge is the only way out
+        let game: Game = serde_json::from_str(include_str!(
+            "../../fixtures/wrapped_constrictor_edge_escape.json"
+        ))
+        .unwrap();
+
+        assert_eq!(guarded(&game, "left"), "left");
+        assert_eq!(guarded(&game, "up"), "left");
+        assert_eq!(guarded(&game, "down"), "left");
+    }
+
+    #[test]
+    fn test_replaces_forced_head_to_head_losses() {
+        // Our body blocks the opponent in against the wall so their only move is down into
+        // (0, 0), and they are longer than us
+        let game = game_with_bodies(vec![
+            vec![(1, 0), (1, 1), (2, 1)],
+            vec![(0, 1), (0, 2), (0, 3), (0, 3)],
+        ]);
+
+        assert_eq!(guarded(&game, "left"), "right");
+    }
+
+    #[test]
+    fn test_equal_trades_depend_on_how_we_are_doing() {
+        // The opponent is forced down into (0, 0) again, but this time it's our length so going
+        // left kills us both. With the rest of the board to ourselves that's a terrible trade
+        let mut game = game_with_bodies(vec![
+            vec![(1, 0), (1, 1), (2, 1)],
+            vec![(0, 1), (0, 2), (0, 3)],
+        ]);
+        assert_eq!(guarded(&game, "left"), "right");
+
+        // A third snake that is much longer than both of us means we're losing this game anyway,
+        // and taking an opponent down with us is the best we'll do
+        let mut third = game.board.snakes[1].clone();
+        third.id = "third".to_owned();
+        third.body = [(8, 8), (8, 7), (8, 6), (8, 5), (8, 4), (8, 3)]
+            .into_iter()
+            .map(|(x, y)| Position { x, y })
+            .collect();
+        third.head = third.body[0];
+        game.board.snakes.push(third);
+        assert_eq!(guarded(&game, "left"), "left");
+    }
+
+    #[test]
+    fn test_replaces_moves_into_hazards_that_would_kill_us() {
+        let mut game = game_with_bodies(vec![vec![(5, 5), (5, 4), (5, 3)]]);
+        game.game.ruleset.settings = Some(Settings {
+            food_spawn_chance: 15,
+            minimum_food: 1,
+            hazard_damage_per_turn: 14,
+            hazard_map: None,
+            hazard_map_author: None,
+            royale: None,
+        });
+        game.board.hazards = vec![Position { x: 4, y: 5 }];
+        game.board.snakes[0].health = 16;
+        game.you = game.board.snakes[0].clone();
+
+        // 16 health is enough to take one hazard's damage and the point every move costs
+        assert_eq!(guarded(&game, "left"), "left");
+
+        // Once the hazard is stacked it's two lots of damage, which we won't survive
+        game.board.hazards.push(Position { x: 4, y: 5 });
+        assert_ne!(guarded(&game, "left"), "left");
+
+        // Food on the hazard keeps us alive
+        game.board.food.push(Position { x: 4, y: 5 });
+        assert_eq!(guarded(&game, "left"), "left");
+    }
+}
@@ -0,0 +1,308 @@
+//! The first couple of turns, before the snakes have done anything to tell the positions apart
+//!
+//! The standard rules spawn snakes on a fixed set of points and drop a piece of food next to each
+//! of them, so the only real question early on is which food we can get to first. That doesn't
+//! need the full search, a few A* distances answer it, and the answer makes a good first guess for
+//! the search to start from
+
+use std::{cmp::Reverse, fmt::Debug, time::Duration};
+
+use battlesnake_game_types::wire_representation::Position;
+use battlesnake_minimax::paranoid::{MinMaxReturn, WrappedScore};
+
+use crate::{
+    a_prime::{APrimeCalculable, APrimeOptions},
+    head_to_head::{equal_trade_risk, next_head},
+    move_guard::is_fatal_with_risk,
+    *,
+};
+
+/// The last turn the opening analysis is used for
+pub const LAST_OPENING_TURN: i32 = 1;
+
+/// Where a snake spawned, on the boards where the standard rules use fixed spawn points
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpawnPoint {
+    Corner,
+    Edge,
+}
+
+/// The fixed spawn point at `pos`, if there is one
+///
+/// The spawn points are one cell in from the walls, in the corners and in the middle of each side.
+/// Odd sized boards are the only ones with a middle, so the others only have corners
+pub fn spawn_point(width: u32, height: u32, pos: Position) -> Option<SpawnPoint> {
+    let (width, height) = (width as i32, height as i32);
+    let on_side = |v: i32, size: i32| v == 1 || v == size - 2;
+    let in_middle = |v: i32, size: i32| size % 2 == 1 && v == (size - 1) / 2;
+
+    match (on_side(pos.x, width), on_side(pos.y, height)) {
+        (true, true) => Some(SpawnPoint::Corner),
+        (true, false) if in_middle(pos.y, height) => Some(SpawnPoint::Edge),
+        (false, true) if in_middle(pos.x, width) => Some(SpawnPoint::Edge),
+        _ => None,
+    }
+}
+
+/// The spawn point of every snake, in the order they are on the board
+///
+/// A snake that isn't on a spawn point means the game was started with random spawns or some
+/// other map, and the layout is None for it
+pub fn spawn_layout(game: &Game) -> Vec<(String, Option<SpawnPoint>)> {
+    game.board
+        .snakes
+        .iter()
+        .map(|snake| {
+            (
+                snake.id.clone(),
+                spawn_point(game.board.width, game.board.height, snake.head),
+            )
+        })
+        .collect()
+}
+
+/// How quickly we and the closest opponent can get to a piece of food
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FoodRace {
+    pub food: Position,
+    pub our_distance: Option<i32>,
+    /// None when no opponent can reach the food
+    pub best_opponent_distance: Option<i32>,
+}
+
+impl FoodRace {
+    /// We get there first whatever the opponents do
+    ///
+    /// Everyone is the same length at the start, so getting there at the same time as an opponent
+    /// is a head-to-head that kills us both. Only being strictly closer counts
+    pub fn guaranteed(&self) -> bool {
+        match (self.our_distance, self.best_opponent_distance) {
+            (Some(ours), Some(theirs)) => ours < theirs,
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+}
+
+/// What the opening analysis decided
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpeningPlan {
+    pub layout: Vec<(String, Option<SpawnPoint>)>,
+    pub races: Vec<FoodRace>,
+    /// The closest food we are guaranteed to win, None if we aren't guaranteed any of them
+    pub target: Option<Position>,
+    /// Our moves that don't kill us on the spot, the one heading for the target first
+    pub ranked_moves: Vec<Move>,
+}
+
+/// A* distances without the extra cost for stepping over food, since eating on the way to the
+/// food we're racing for only helps
+const RACE_OPTIONS: APrimeOptions = APrimeOptions {
+    food_penalty: 0,
+    hazard_penalty: 1,
+};
+
+/// Work out the food races for the opening, None after [LAST_OPENING_TURN] or when `you` isn't on
+/// the board
+pub fn analyze_opening(game: &Game) -> Option<OpeningPlan> {
+    if game.turn > LAST_OPENING_TURN {
+        return None;
+    }
+
+    let me = game.board.snakes.iter().find(|s| s.id == game.you.id)?;
+
+    let races: Vec<FoodRace> = game
+        .board
+        .food
+        .iter()
+        .map(|food| FoodRace {
+            food: *food,
+            our_distance: game.shortest_distance(&me.head, &[*food], &RACE_OPTIONS),
+            best_opponent_distance: game
+                .board
+                .snakes
+                .iter()
+                .filter(|s| s.id != me.id)
+                .filter_map(|s| game.shortest_distance(&s.head, &[*food], &RACE_OPTIONS))
+                .min(),
+        })
+        .collect();
+
+    let target = races
+        .iter()
+        .filter(|race| race.guaranteed())
+        .min_by_key(|race| race.our_distance)
+        .map(|race| race.food);
+
+    let risk = equal_trade_risk(game, me);
+    let mut ranked_moves: Vec<(Move, Position)> = Move::all_iter()
+        .filter(|m| !is_fatal_with_risk(game, me, *m, risk))
+        .filter_map(|m| next_head(game, me.head, m).map(|pos| (m, pos)))
+        .collect();
+    if let Some(target) = target {
+        // The sort is stable, so moves that can't reach the target keep their usual order after
+        // the ones that can
+        ranked_moves.sort_by_cached_key(|(_, pos)| {
+            game.shortest_distance(pos, &[target], &RACE_OPTIONS)
+                .unwrap_or(i32::MAX)
+        });
+    }
+
+    Some(OpeningPlan {
+        layout: spawn_layout(game),
+        races,
+        target,
+        ranked_moves: ranked_moves.into_iter().map(|(m, _)| m).collect(),
+    })
+}
+
+impl OpeningPlan {
+    /// The move we'd make without searching, None if no food is ours for sure
+    pub fn chosen_move(&self) -> Option<Move> {
+        self.target.and(self.ranked_moves.first().copied())
+    }
+
+    /// A search result to start the first iteration of the search from, so it looks at our moves
+    /// in the order we ranked them
+    ///
+    /// Only the order of the options matters to the search, so they're scored with made up losses
+    /// that sort the way we ranked them
+    pub fn seed<GameType, ScoreType>(
+        &self,
+        you_id: &GameType::SnakeIDType,
+    ) -> Option<MinMaxReturn<GameType, ScoreType>>
+    where
+        GameType: SnakeIDGettableGame + Clone + Debug,
+        ScoreType: Clone + Debug + PartialOrd + Ord + Copy,
+    {
+        self.chosen_move()?;
+
+        let options: Vec<_> = self
+            .ranked_moves
+            .iter()
+            .enumerate()
+            .map(|(rank, m)| {
+                let score = WrappedScore::Lose(Reverse(u8::MAX), -(rank as i64));
+                (
+                    *m,
+                    MinMaxReturn::Leaf {
+                        score,
+                        breakdown: None,
+                    },
+                )
+            })
+            .collect();
+        let score = *options.first()?.1.score();
+
+        Some(MinMaxReturn::Node {
+            is_maximizing: true,
+            options,
+            moving_snake_id: you_id.clone(),
+            score,
+            alpha_beta_cutoff: false,
+            depth: 0,
+            alpha: WrappedScore::worst_possible_score(),
+            beta: WrappedScore::best_possible_score(),
+        })
+    }
+}
+
+/// Reads how long we search for in the opening when the analysis already picked a move, from
+/// `OPENING_SEARCH_BUDGET_MS`
+///
+/// The search only has to check that the move doesn't walk into something, so it doesn't need the
+/// whole timeout. Defaults to 200 milliseconds
+pub fn opening_search_budget() -> Duration {
+    match std::env::var("OPENING_SEARCH_BUDGET_MS").map(|x| x.parse()) {
+        Ok(Ok(x)) => Duration::from_millis(x),
+        _ => Duration::from_millis(200),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use battlesnake_game_types::compact_representation::StandardCellBoard4Snakes11x11;
+
+    use super::*;
+
+    fn compact(game: &Game) -> StandardCellBoard4Snakes11x11 {
+        let id_map = build_snake_id_map(game);
+
+        StandardCellBoard4Snakes11x11::convert_from_game(game.clone(), &id_map).unwrap()
+    }
+
+    fn opening_game() -> Game {
+        let mut game: Game =
+            serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap();
+        game.turn = 0;
+
+        game
+    }
+
+    #[test]
+    fn test_spawn_layout() {
+        let game = opening_game();
+
+        assert_eq!(
+            spawn_layout(&game),
+            vec![
+                ("you".to_owned(), Some(SpawnPoint::Edge)),
+                ("#FF6c96".to_owned(), Some(SpawnPoint::Edge)),
+                ("#FF6444".to_owned(), Some(SpawnPoint::Corner)),
+            ]
+        );
+        assert_eq!(spawn_point(11, 11, Position { x: 5, y: 5 }), None);
+        assert_eq!(spawn_point(12, 12, Position { x: 1, y: 5 }), None);
+    }
+
+    #[test]
+    fn test_tied_races_are_not_guaranteed() {
+        let game = opening_game();
+        let plan = analyze_opening(&game).unwrap();
+
+        let center = plan
+            .races
+            .iter()
+            .find(|race| race.food == Position { x: 5, y: 5 })
+            .unwrap();
+        assert_eq!(center.our_distance, Some(4));
+        assert_eq!(center.best_opponent_distance, Some(4));
+        assert!(!center.guaranteed());
+
+        assert_eq!(plan.target, None);
+        assert_eq!(plan.chosen_move(), None);
+        assert!(plan
+            .seed::<StandardCellBoard4Snakes11x11, i64>(compact(&game).you_id())
+            .is_none());
+    }
+
+    #[test]
+    fn test_heads_for_the_closest_guaranteed_food() {
+        let mut game = opening_game();
+        game.board.food.push(Position { x: 9, y: 8 });
+
+        let plan = analyze_opening(&game).unwrap();
+
+        assert_eq!(plan.target, Some(Position { x: 9, y: 8 }));
+        assert_eq!(plan.chosen_move(), Some(Move::Up));
+        assert_eq!(plan.ranked_moves.len(), 4);
+
+        let seed = plan
+            .seed::<StandardCellBoard4Snakes11x11, i64>(compact(&game).you_id())
+            .unwrap();
+        let MinMaxReturn::Node { options, .. } = seed else {
+            panic!("The seed should be a node");
+        };
+        let seeded_moves: Vec<_> = options.iter().map(|(m, _)| *m).collect();
+        assert_eq!(seeded_moves, plan.ranked_moves);
+        assert!(options[0].1.score() > options[1].1.score());
+    }
+
+    #[test]
+    fn test_only_the_opening() {
+        let mut game = opening_game();
+        game.turn = LAST_OPENING_TURN + 1;
+
+        assert!(analyze_opening(&game).is_none());
+    }
+}
@@ -0,0 +1,959 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    fs::{read_to_string, write},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+};
+
+use color_eyre::eyre::{Context, Result};
+use tracing::{debug, info, warn};
+
+use crate::{
+    a_prime::dist_between,
+    head_to_head::next_head,
+    shouts::{ObservedShout, ShoutLog},
+    *,
+};
+
+/// How close another snake's head has to be for a turn to count towards
+/// [OpponentStats::aggression_index]
+const AGGRESSION_RANGE: i32 = 3;
+
+/// About how often a snake moving at random gets closer to whatever it's near, since about half
+/// of its moves do. Rates at or below this don't tell us anything about the snake
+const RANDOM_RATE: f64 = 0.5;
+
+/// How many turns of a snake we have to see before we trust its rates halfway
+///
+/// Until then the [OpponentProfile] is pulled back towards moving at random, so a snake we've only
+/// seen once barely changes the rollouts and one we keep running into gets sharper every game
+const PRIOR_TURNS: f64 = 100.0;
+
+/// The most games we watch at once, see [observe_request]. Past that the game we heard from least
+/// recently stops being watched
+const MAX_SESSION_GAMES: usize = 64;
+
+/// How many turns in a row the engine has to tell us an opponent timed out before we believe it
+const REPORTED_TIMEOUT_STREAK: usize = 3;
+
+/// How many turns in a row an opponent has to make the move the engine makes for a snake that
+/// timed out before we believe it, when the engine doesn't tell us the latencies
+///
+/// Going straight is a fine move a lot of the time, so this takes a lot longer than
+/// [REPORTED_TIMEOUT_STREAK]
+const AUTOPILOT_STREAK: usize = 8;
+
+/// Everything we have seen a snake do, added up across every game we've watched it in
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpponentStats {
+    pub games: usize,
+    pub deaths: usize,
+    pub total_length_at_death: usize,
+    /// Turns where another snake's head was within [AGGRESSION_RANGE] of this one's
+    pub contested_turns: usize,
+    /// Contested turns where the snake moved towards the closest head
+    pub aggressive_turns: usize,
+    /// Turns where there was food on the board
+    pub food_turns: usize,
+    /// Turns with food where the snake moved towards the closest food
+    pub food_seeking_turns: usize,
+}
+
+impl OpponentStats {
+    /// How long the snake usually is when it dies, None if we've never seen it die
+    pub fn average_length_at_death(&self) -> Option<f64> {
+        (self.deaths > 0).then(|| self.total_length_at_death as f64 / self.deaths as f64)
+    }
+
+    /// How often the snake closes in on a snake that's near it, from 0 to 1
+    pub fn aggression_index(&self) -> Option<f64> {
+        (self.contested_turns > 0)
+            .then(|| self.aggressive_turns as f64 / self.contested_turns as f64)
+    }
+
+    /// How often the snake heads for the closest food, from 0 to 1
+    pub fn food_priority(&self) -> Option<f64> {
+        (self.food_turns > 0).then(|| self.food_seeking_turns as f64 / self.food_turns as f64)
+    }
+
+    fn merge(&mut self, other: &OpponentStats) {
+        self.games += other.games;
+        self.deaths += other.deaths;
+        self.total_length_at_death += other.total_length_at_death;
+        self.contested_turns += other.contested_turns;
+        self.aggressive_turns += other.aggressive_turns;
+        self.food_turns += other.food_turns;
+        self.food_seeking_turns += other.food_seeking_turns;
+    }
+}
+
+/// What we've seen every snake do, keyed by snake name since that's what stays the same from one
+/// game to the next
+///
+/// Sherlock's `opponent-book` command builds one from the archive, and [observe_request] keeps
+/// adding to one while we play
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpponentBook {
+    snakes: BTreeMap<String, OpponentStats>,
+}
+
+impl OpponentBook {
+    /// Read a book saved with [OpponentBook::save]. A book that hasn't been saved yet is empty
+    pub fn load(path: &Path) -> Result<Self> {
+        match read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .wrap_err_with(|| format!("Couldn't parse the opponent book {}", path.display())),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e)
+                .wrap_err_with(|| format!("Couldn't read the opponent book {}", path.display())),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        write(path, serde_json::to_string_pretty(self)?)
+            .wrap_err_with(|| format!("Couldn't write the opponent book {}", path.display()))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&OpponentStats> {
+        self.snakes.get(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &OpponentStats)> {
+        self.snakes.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.snakes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snakes.is_empty()
+    }
+
+    pub fn merge(&mut self, other: &OpponentBook) {
+        for (name, stats) in &other.snakes {
+            self.snakes.entry(name.clone()).or_default().merge(stats);
+        }
+    }
+
+    /// Add a whole game, given every turn of it in order
+    pub fn observe_game(&mut self, turns: &[Game]) {
+        let Some(first) = turns.first() else {
+            return;
+        };
+
+        self.start_game(first);
+        for pair in turns.windows(2) {
+            self.observe_turn(&pair[0], &pair[1]);
+        }
+    }
+
+    /// Count a game for every snake on its first turn
+    pub fn start_game(&mut self, first: &Game) {
+        for snake in &first.board.snakes {
+            self.snakes.entry(snake.name.clone()).or_default().games += 1;
+        }
+    }
+
+    /// Add what every snake did to get from `before` to the turn after it. Snakes that are missing
+    /// from `after` died that turn
+    pub fn observe_turn(&mut self, before: &Game, after: &Game) {
+        for snake in &before.board.snakes {
+            let stats = self.snakes.entry(snake.name.clone()).or_default();
+
+            let Some(next) = after.board.snakes.iter().find(|s| s.id == snake.id) else {
+                stats.deaths += 1;
+                stats.total_length_at_death += snake.body.len();
+
+                continue;
+            };
+
+            if let Some(food) = closest(&snake.head, before.board.food.iter()) {
+                stats.food_turns += 1;
+                if dist_between(&next.head, food) < dist_between(&snake.head, food) {
+                    stats.food_seeking_turns += 1;
+                }
+            }
+
+            let other_heads = before
+                .board
+                .snakes
+                .iter()
+                .filter(|other| other.id != snake.id)
+                .map(|other| &other.head);
+            if let Some(head) = closest(&snake.head, other_heads)
+                .filter(|head| dist_between(&snake.head, head) <= AGGRESSION_RANGE)
+            {
+                stats.contested_turns += 1;
+                if dist_between(&next.head, head) < dist_between(&snake.head, head) {
+                    stats.aggressive_turns += 1;
+                }
+            }
+        }
+    }
+}
+
+fn closest<'a>(
+    from: &Position,
+    targets: impl Iterator<Item = &'a Position>,
+) -> Option<&'a Position> {
+    targets.min_by_key(|target| dist_between(from, target))
+}
+
+/// How we expect an opponent to move in a rollout, built from its [OpponentStats]
+///
+/// Both are chances of playing the greedy move instead of a random reasonable one. They only count
+/// how much more often than [RANDOM_RATE] the snake did it, pulled back by [PRIOR_TURNS]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OpponentProfile {
+    /// Chance of moving towards our head
+    pub aggression: f64,
+    /// Chance of moving towards the closest food
+    pub food_priority: f64,
+}
+
+impl OpponentProfile {
+    pub fn from_stats(stats: &OpponentStats) -> Self {
+        let sharpen = |rate: Option<f64>, turns: usize| {
+            let rate = rate.unwrap_or_default();
+            let excess = ((rate - RANDOM_RATE) / (1.0 - RANDOM_RATE)).clamp(0.0, 1.0);
+
+            excess * turns as f64 / (turns as f64 + PRIOR_TURNS)
+        };
+
+        Self {
+            aggression: sharpen(stats.aggression_index(), stats.contested_turns),
+            food_priority: sharpen(stats.food_priority(), stats.food_turns),
+        }
+    }
+}
+
+/// The [OpponentProfile] of each opponent in a game, for the MCTS rollouts, and the move each of
+/// them made last turn, for ordering the opponents' moves when the tree is expanded
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OpponentModel {
+    profiles: [Option<OpponentProfile>; 4],
+    last_moves: [Option<Move>; 4],
+}
+
+impl OpponentModel {
+    /// Look up every snake in the game except us in the book
+    pub fn from_book(book: &OpponentBook, game: &Game, id_map: &HashMap<String, SnakeId>) -> Self {
+        let mut model = Self::default();
+
+        for snake in &game.board.snakes {
+            if snake.id == game.you.id {
+                continue;
+            }
+
+            if let (Some(sid), Some(stats)) = (id_map.get(&snake.id), book.get(&snake.name)) {
+                model.insert(*sid, OpponentProfile::from_stats(stats));
+            }
+        }
+
+        model
+    }
+
+    pub fn insert(&mut self, sid: SnakeId, profile: OpponentProfile) {
+        if let Some(slot) = self.profiles.get_mut(sid.as_usize()) {
+            *slot = Some(profile);
+        }
+    }
+
+    pub fn profile(&self, sid: &SnakeId) -> Option<&OpponentProfile> {
+        self.profiles.get(sid.as_usize())?.as_ref()
+    }
+
+    /// None of the opponents have a profile, so the rollouts can play them at random
+    pub fn is_empty(&self) -> bool {
+        self.profiles.iter().all(Option::is_none)
+    }
+
+    pub fn insert_last_move(&mut self, sid: SnakeId, m: Move) {
+        if let Some(slot) = self.last_moves.get_mut(sid.as_usize()) {
+            *slot = Some(m);
+        }
+    }
+
+    pub fn last_move(&self, sid: &SnakeId) -> Option<Move> {
+        *self.last_moves.get(sid.as_usize())?
+    }
+
+    pub fn has_last_moves(&self) -> bool {
+        self.last_moves.iter().any(Option::is_some)
+    }
+
+    /// How many snakes make the same move in `actions` as they did last turn
+    pub fn repeated_last_moves(&self, actions: &Action<4>) -> usize {
+        actions
+            .into_inner()
+            .iter()
+            .zip(&self.last_moves)
+            .filter(|(m, last)| m.is_some() && m == last)
+            .count()
+    }
+}
+
+/// How often an opponent made the same move as the turn before, which is the guess the MCTS
+/// explores first. See [OpponentModel::repeated_last_moves]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MovePredictions {
+    pub predictions: usize,
+    pub hits: usize,
+}
+
+impl MovePredictions {
+    pub fn hit_rate(&self) -> Option<f64> {
+        (self.predictions > 0).then(|| self.hits as f64 / self.predictions as f64)
+    }
+}
+
+/// The move every opponent made to get from `before` to `after`, by snake id. Snakes that died in
+/// between are left out
+pub fn observed_moves(before: &Game, after: &Game) -> HashMap<String, Move> {
+    before
+        .board
+        .snakes
+        .iter()
+        .filter(|snake| snake.id != before.you.id)
+        .filter_map(|snake| {
+            let next = after.board.snakes.iter().find(|s| s.id == snake.id)?;
+            let m =
+                Move::all_iter().find(|m| next_head(before, snake.head, *m) == Some(next.head))?;
+
+            Some((snake.id.clone(), m))
+        })
+        .collect()
+}
+
+/// How many turns in a row an opponent has looked like it's timing out
+///
+/// When a snake doesn't answer in time the engine moves it the same way it moved last turn, or up
+/// on the first turn, and reports its latency as 0
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct TimeoutStreak {
+    /// Turns the engine reported the snake timed out
+    reported: usize,
+    /// Turns the snake made the move the engine would have made for it
+    autopilot: usize,
+}
+
+impl TimeoutStreak {
+    fn timing_out(&self) -> bool {
+        self.reported >= REPORTED_TIMEOUT_STREAK || self.autopilot >= AUTOPILOT_STREAK
+    }
+}
+
+/// A game we're watching, from the last request we were sent for it
+#[derive(Debug, Clone)]
+struct WatchedGame {
+    last_request: Game,
+    /// The move each opponent made to get to `last_request`
+    last_moves: HashMap<String, Move>,
+    predictions: MovePredictions,
+    timeout_streaks: HashMap<String, TimeoutStreak>,
+    /// What the opponents have shouted so far, see [crate::shouts]
+    shouts: ShoutLog,
+}
+
+impl WatchedGame {
+    fn new(first: &Game) -> Self {
+        let mut watched = Self {
+            last_request: first.clone(),
+            last_moves: HashMap::new(),
+            predictions: MovePredictions::default(),
+            timeout_streaks: HashMap::new(),
+            shouts: ShoutLog::default(),
+        };
+        watched.observe_shouts(first);
+
+        watched
+    }
+
+    /// Record anything new the opponents shouted in this request
+    fn observe_shouts(&mut self, game: &Game) {
+        for snake in game.board.snakes.iter().filter(|s| s.id != game.you.id) {
+            let observed =
+                self.shouts
+                    .observe(game.turn, &snake.id, &snake.name, snake.shout.as_deref());
+            if let Some(shout) = observed {
+                debug!(
+                    game_id = %game.game.id,
+                    turn = shout.turn,
+                    snake_name = %shout.snake_name,
+                    shout = %shout.text,
+                    numbers = ?shout.numbers,
+                    "Opponent shouted"
+                );
+            }
+        }
+    }
+
+    /// Move on to the request for the next turn, checking the opponents' moves against the ones
+    /// they made the turn before
+    ///
+    /// `reported_timeouts` are the opponents the engine said timed out in this request, see
+    /// [observe_latencies]
+    fn advance(&mut self, next: &Game, reported_timeouts: &[String]) {
+        let moves = observed_moves(&self.last_request, next);
+        for (id, m) in &moves {
+            if let Some(last) = self.last_moves.get(id) {
+                self.predictions.predictions += 1;
+                if last == m {
+                    self.predictions.hits += 1;
+                }
+            }
+        }
+
+        let mut timeout_streaks = HashMap::new();
+        for (id, m) in &moves {
+            let engine_move = match self.last_moves.get(id) {
+                Some(last) => Some(*last),
+                None if self.last_request.turn == 0 => Some(Move::Up),
+                None => None,
+            };
+            let previous = self.timeout_streaks.get(id).copied().unwrap_or_default();
+            let streak = TimeoutStreak {
+                reported: if reported_timeouts.contains(id) {
+                    previous.reported + 1
+                } else {
+                    0
+                },
+                autopilot: if engine_move == Some(*m) {
+                    previous.autopilot + 1
+                } else {
+                    0
+                },
+            };
+
+            if streak.timing_out() && !previous.timing_out() {
+                info!(
+                    game_id = %next.game.id,
+                    turn = next.turn,
+                    snake_id = %id,
+                    reported = streak.reported,
+                    autopilot = streak.autopilot,
+                    "Opponent looks like it's timing out, playing for survival"
+                );
+            }
+            timeout_streaks.insert(id.clone(), streak);
+        }
+
+        self.last_request = next.clone();
+        self.last_moves = moves;
+        self.timeout_streaks = timeout_streaks;
+        self.observe_shouts(next);
+    }
+
+    fn opponent_timing_out(&self) -> bool {
+        self.timeout_streaks.values().any(TimeoutStreak::timing_out)
+    }
+}
+
+/// The opponents the engine said timed out in a request, before we've watched the turn
+#[derive(Debug, Clone)]
+struct ReportedTimeouts {
+    game_id: String,
+    turn: i32,
+    snake_ids: Vec<String>,
+}
+
+/// The book we are adding to while we play, and the games we're watching
+#[derive(Debug, Default)]
+struct Session {
+    book: OpponentBook,
+    path: Option<PathBuf>,
+    games: Vec<WatchedGame>,
+    reported_timeouts: Vec<ReportedTimeouts>,
+}
+
+static SESSION: OnceLock<Mutex<Session>> = OnceLock::new();
+
+/// The session starts from the book at `OPPONENT_BOOK_PATH`, and saves back to it after every
+/// game. Without the variable the session book only lives in memory
+fn session() -> &'static Mutex<Session> {
+    SESSION.get_or_init(|| {
+        let path = std::env::var("OPPONENT_BOOK_PATH").ok().map(PathBuf::from);
+        let book = match &path {
+            Some(path) => OpponentBook::load(path).unwrap_or_else(|e| {
+                warn!(error = ?e, "Starting with an empty opponent book");
+                OpponentBook::default()
+            }),
+            None => OpponentBook::default(),
+        };
+
+        Mutex::new(Session {
+            book,
+            path,
+            games: vec![],
+            reported_timeouts: vec![],
+        })
+    })
+}
+
+fn with_session<R>(f: impl FnOnce(&mut Session) -> R) -> R {
+    let mut session = session().lock().unwrap_or_else(|e| e.into_inner());
+
+    f(&mut session)
+}
+
+/// Add a request we were sent to the session book
+///
+/// The first request of a game counts the game, and each request after that is compared to the
+/// one for the turn before it. Requests we've already seen, or that skip a turn, are ignored
+pub fn observe_request(game: &Game) {
+    with_session(|session| {
+        let previous = session
+            .games
+            .iter()
+            .position(|g| g.last_request.game.id == game.game.id);
+
+        match previous {
+            None => {
+                session.book.start_game(game);
+                if session.games.len() >= MAX_SESSION_GAMES {
+                    session.games.remove(0);
+                }
+                session.games.push(WatchedGame::new(game));
+            }
+            Some(i) if session.games[i].last_request.turn + 1 == game.turn => {
+                // The games are kept in the order we last heard from them, so the one evicted
+                // when we're watching too many is the one least likely to still be going
+                let watched = session.games.remove(i);
+                session.games.push(watched);
+                let i = session.games.len() - 1;
+
+                let reported_timeouts = session
+                    .reported_timeouts
+                    .iter()
+                    .position(|r| r.game_id == game.game.id && r.turn == game.turn)
+                    .map(|i| session.reported_timeouts.remove(i).snake_ids)
+                    .unwrap_or_default();
+
+                session
+                    .book
+                    .observe_turn(&session.games[i].last_request, game);
+                session.games[i].advance(game, &reported_timeouts);
+            }
+            Some(_) => {}
+        }
+    });
+}
+
+#[derive(Deserialize)]
+struct LatencyGame<'a> {
+    #[serde(borrow)]
+    id: Cow<'a, str>,
+}
+
+#[derive(Deserialize)]
+struct LatencySnake<'a> {
+    #[serde(borrow)]
+    id: Cow<'a, str>,
+    #[serde(default)]
+    latency: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct LatencyBoard<'a> {
+    #[serde(borrow)]
+    snakes: Vec<LatencySnake<'a>>,
+}
+
+/// Only the parts of a request body we need for the latencies, which the wire `Game` leaves out
+///
+/// The ids borrow from the body, so most bodies only allocate for the snakes that timed out
+#[derive(Deserialize)]
+struct LatencyBody<'a> {
+    #[serde(borrow)]
+    game: LatencyGame<'a>,
+    turn: i32,
+    #[serde(borrow)]
+    board: LatencyBoard<'a>,
+    #[serde(borrow)]
+    you: LatencyGame<'a>,
+}
+
+/// Whether the engine's latency for a snake says it didn't answer in time. It sends them as
+/// strings, and a snake that timed out gets "0" or nothing at all
+fn reported_timeout(latency: &serde_json::Value) -> bool {
+    match latency {
+        serde_json::Value::String(s) => s.trim().is_empty() || s.trim() == "0",
+        serde_json::Value::Number(n) => n.as_u64() == Some(0),
+        _ => false,
+    }
+}
+
+/// Remember which opponents the engine said timed out in a raw request body, for
+/// [observe_request] to pick up when it watches the same turn
+///
+/// This has to be called with the raw body, since the latencies don't make it into a [Game].
+/// Bodies that aren't game states are ignored
+pub fn observe_latencies(body: &[u8]) {
+    let Ok(body) = serde_json::from_slice::<LatencyBody>(body) else {
+        return;
+    };
+
+    let snake_ids: Vec<String> = body
+        .board
+        .snakes
+        .into_iter()
+        .filter(|snake| snake.id != body.you.id)
+        .filter(|snake| snake.latency.as_ref().is_some_and(reported_timeout))
+        .map(|snake| snake.id.into_owned())
+        .collect();
+    if snake_ids.is_empty() {
+        return;
+    }
+
+    with_session(|session| {
+        if session.reported_timeouts.len() >= MAX_SESSION_GAMES {
+            session.reported_timeouts.remove(0);
+        }
+        session.reported_timeouts.push(ReportedTimeouts {
+            game_id: body.game.id.into_owned(),
+            turn: body.turn,
+            snake_ids,
+        });
+    });
+}
+
+/// How many games the session is watching, see [observe_request]
+pub fn watched_games() -> usize {
+    with_session(|session| session.games.len())
+}
+
+/// Whether an opponent in this game has been timing out for a while, so we can win by just
+/// staying alive. See [crate::tuning::profiles]
+pub fn opponent_timing_out(game: &Game) -> bool {
+    with_session(|session| {
+        session
+            .games
+            .iter()
+            .find(|g| g.last_request.game.id == game.game.id && g.last_request.turn == game.turn)
+            .is_some_and(WatchedGame::opponent_timing_out)
+    })
+}
+
+/// Everything the opponents have shouted in a game we're watching, oldest first. Empty for a game
+/// we aren't watching
+pub fn opponent_shouts(game_id: &str) -> Vec<ObservedShout> {
+    with_session(|session| {
+        session
+            .games
+            .iter()
+            .find(|g| g.last_request.game.id == game_id)
+            .map(|g| g.shouts.shouts().to_vec())
+            .unwrap_or_default()
+    })
+}
+
+/// Stop watching a game, and save the session book if it has somewhere to go
+///
+/// Returns how well the opponents' last moves predicted their next ones over the game, None if we
+/// weren't watching it
+pub fn finish_game(game_id: &str) -> Option<MovePredictions> {
+    with_session(|session| {
+        let finished = session
+            .games
+            .iter()
+            .position(|g| g.last_request.game.id == game_id)
+            .map(|i| session.games.remove(i).predictions);
+
+        if let Some(path) = &session.path {
+            if let Err(e) = session.book.save(path) {
+                warn!(error = ?e, "Couldn't save the opponent book");
+            }
+        }
+
+        finished
+    })
+}
+
+/// The [OpponentModel] for a game, from everything in the session book and the moves the
+/// opponents made last turn
+pub fn session_model(game: &Game, id_map: &HashMap<String, SnakeId>) -> OpponentModel {
+    with_session(|session| {
+        let mut model = OpponentModel::from_book(&session.book, game, id_map);
+
+        let watched = session
+            .games
+            .iter()
+            .find(|g| g.last_request.game.id == game.game.id && g.last_request.turn == game.turn);
+        for (id, m) in watched.iter().flat_map(|g| &g.last_moves) {
+            if let Some(sid) = id_map.get(id) {
+                model.insert_last_move(*sid, *m);
+            }
+        }
+
+        model
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn fixture() -> Game {
+        serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap()
+    }
+
+    /// Move the head of the snake with this id by one cell, dragging its body along behind it
+    fn step(game: &Game, id: &str, dx: i32, dy: i32) -> Game {
+        let mut next = game.clone();
+        next.turn += 1;
+
+        let snake = next.board.snakes.iter_mut().find(|s| s.id == id).unwrap();
+        snake.head = Position {
+            x: snake.head.x + dx,
+            y: snake.head.y + dy,
+        };
+        snake.body.push_front(snake.head);
+        snake.body.pop_back();
+
+        next
+    }
+
+    #[test]
+    fn test_deaths_record_the_length_they_died_at() {
+        let before = fixture();
+        let mut after = before.clone();
+        let dead = after.board.snakes.remove(1);
+
+        let mut book = OpponentBook::default();
+        book.observe_game(&[before, after]);
+
+        let stats = book.get(&dead.name).unwrap();
+        assert_eq!(stats.games, 1);
+        assert_eq!(stats.deaths, 1);
+        assert_eq!(
+            stats.average_length_at_death(),
+            Some(dead.body.len() as f64)
+        );
+    }
+
+    #[test]
+    fn test_moving_towards_food_counts_as_food_seeking() {
+        let mut before = fixture();
+        let you = before.you.clone();
+        before.board.snakes.retain(|s| s.id == you.id);
+        before.board.food = vec![Position {
+            x: you.head.x + 3,
+            y: you.head.y,
+        }];
+
+        let mut book = OpponentBook::default();
+        let towards = step(&before, &you.id, 1, 0);
+        book.observe_turn(&before, &towards);
+        let away = step(&before, &you.id, -1, 0);
+        book.observe_turn(&before, &away);
+
+        let stats = book.get(&you.name).unwrap();
+        assert_eq!(stats.food_turns, 2);
+        assert_eq!(stats.food_priority(), Some(0.5));
+        assert_eq!(stats.aggression_index(), None);
+    }
+
+    #[test]
+    fn test_profiles_sharpen_as_we_see_more_turns() {
+        let few = OpponentStats {
+            contested_turns: 10,
+            aggressive_turns: 10,
+            ..Default::default()
+        };
+        let many = OpponentStats {
+            contested_turns: 1000,
+            aggressive_turns: 1000,
+            ..Default::default()
+        };
+        let random = OpponentStats {
+            contested_turns: 1000,
+            aggressive_turns: 500,
+            ..Default::default()
+        };
+
+        let few = OpponentProfile::from_stats(&few);
+        let many = OpponentProfile::from_stats(&many);
+        assert!(few.aggression < many.aggression);
+        assert!(many.aggression > 0.9);
+        assert_eq!(OpponentProfile::from_stats(&random).aggression, 0.0);
+        assert_eq!(many.food_priority, 0.0);
+    }
+
+    #[test]
+    fn test_the_model_leaves_us_out() {
+        let game = fixture();
+        let mut book = OpponentBook::default();
+        book.observe_game(&[game.clone()]);
+
+        let id_map = build_snake_id_map(&game);
+        let model = OpponentModel::from_book(&book, &game, &id_map);
+
+        assert!(model.profile(&id_map[&game.you.id]).is_none());
+        for snake in game.board.snakes.iter().filter(|s| s.id != game.you.id) {
+            assert!(model.profile(&id_map[&snake.id]).is_some());
+        }
+    }
+
+    #[test]
+    fn test_last_moves_predict_the_next_ones() {
+        let first = fixture();
+        let opponent = first.board.snakes[1].id.clone();
+
+        let mut watched = WatchedGame::new(&first);
+        let second = step(&first, &opponent, 0, -1);
+        watched.advance(&second, &[]);
+        assert_eq!(watched.last_moves[&opponent], Move::Down);
+        assert_eq!(watched.predictions.hit_rate(), None);
+
+        let third = step(&second, &opponent, 0, -1);
+        watched.advance(&third, &[]);
+        let fourth = step(&third, &opponent, 1, 0);
+        watched.advance(&fourth, &[]);
+
+        assert_eq!(watched.last_moves[&opponent], Move::Right);
+        assert_eq!(
+            watched.predictions,
+            MovePredictions {
+                predictions: 2,
+                hits: 1
+            }
+        );
+        assert_eq!(watched.predictions.hit_rate(), Some(0.5));
+        assert!(!watched.last_moves.contains_key(&first.you.id));
+    }
+
+    #[test]
+    fn test_reported_timeouts_flag_an_opponent_quickly() {
+        let mut current = fixture();
+        let opponent = current.board.snakes[1].id.clone();
+        let mut watched = WatchedGame::new(&current);
+
+        for turn in 0..REPORTED_TIMEOUT_STREAK {
+            assert!(!watched.opponent_timing_out(), "Flagged after {turn} turns");
+
+            // Alternating directions, so it's only the latencies that give it away
+            let next = step(&current, &opponent, (turn % 2) as i32 * 2 - 1, 0);
+            watched.advance(&next, &[opponent.clone()]);
+            current = next;
+        }
+
+        assert!(watched.opponent_timing_out());
+
+        let next = step(&current, &opponent, 0, -1);
+        watched.advance(&next, &[]);
+        assert!(!watched.opponent_timing_out());
+    }
+
+    #[test]
+    fn test_going_straight_for_long_enough_looks_like_a_timeout() {
+        let mut current = fixture();
+        current.turn = 0;
+        // The one in the bottom corner, so it has room to go up
+        let opponent = current.board.snakes[2].id.clone();
+        let mut watched = WatchedGame::new(&current);
+
+        // A snake that times out from the start goes up and keeps going
+        for _ in 0..AUTOPILOT_STREAK - 1 {
+            current = step(&current, &opponent, 0, 1);
+            watched.advance(&current, &[]);
+        }
+        assert!(!watched.opponent_timing_out());
+
+        current = step(&current, &opponent, 0, 1);
+        watched.advance(&current, &[]);
+        assert!(watched.opponent_timing_out());
+    }
+
+    #[test]
+    fn test_the_session_switches_profiles_once_the_engine_reports_timeouts() {
+        let mut current = fixture();
+        current.game.id = "session-reported-timeouts".to_owned();
+        let opponent = current.board.snakes[1].id.clone();
+        observe_request(&current);
+
+        for turn in 0..REPORTED_TIMEOUT_STREAK {
+            assert_ne!(
+                crate::tuning::profile_for(&current).name,
+                "opponent-timing-out"
+            );
+
+            let next = step(&current, &opponent, (turn % 2) as i32 * 2 - 1, 0);
+            let mut body = serde_json::to_value(&next).unwrap();
+            for snake in body["board"]["snakes"].as_array_mut().unwrap() {
+                let latency = if snake["id"] == opponent.as_str() {
+                    "0"
+                } else {
+                    "84"
+                };
+                snake["latency"] = serde_json::json!(latency);
+            }
+            observe_latencies(body.to_string().as_bytes());
+            observe_request(&next);
+            current = next;
+        }
+
+        assert!(opponent_timing_out(&current));
+        assert_eq!(
+            crate::tuning::profile_for(&current).name,
+            "opponent-timing-out"
+        );
+        finish_game(&current.game.id);
+    }
+
+    #[test]
+    fn test_the_session_keeps_what_the_opponents_shouted() {
+        let mut current = fixture();
+        current.game.id = "session-shouts".to_owned();
+        let opponent = current.board.snakes[1].id.clone();
+        let you = current.you.id.clone();
+        observe_request(&current);
+
+        for shout in ["eval 0.5", "eval 0.5", "eval -3"] {
+            let mut next = step(&current, &opponent, 0, -1);
+            for snake in next.board.snakes.iter_mut() {
+                snake.shout = Some(shout.to_owned());
+            }
+            observe_request(&next);
+            current = next;
+        }
+
+        let shouts = opponent_shouts(&current.game.id);
+        assert!(shouts.iter().all(|s| s.snake_id != you));
+        let from_opponent: Vec<&[f64]> = shouts
+            .iter()
+            .filter(|s| s.snake_id == opponent)
+            .map(|s| s.numbers.as_slice())
+            .collect();
+        assert_eq!(from_opponent, vec![&[0.5][..], &[-3.0][..]]);
+
+        finish_game(&current.game.id);
+        assert!(opponent_shouts(&current.game.id).is_empty());
+    }
+
+    #[test]
+    fn test_latencies_of_zero_or_nothing_are_timeouts() {
+        assert!(reported_timeout(&serde_json::json!("0")));
+        assert!(reported_timeout(&serde_json::json!("")));
+        assert!(reported_timeout(&serde_json::json!(0)));
+        assert!(!reported_timeout(&serde_json::json!("84")));
+        assert!(!reported_timeout(&serde_json::json!(451)));
+    }
+
+    #[test]
+    fn test_round_trips_through_a_file() {
+        let game = fixture();
+        let mut book = OpponentBook::default();
+        book.observe_game(&[game.clone(), step(&game, &game.you.id, 0, 1)]);
+
+        let path = std::env::temp_dir().join(format!("opponent_book_{}.json", std::process::id()));
+        book.save(&path).unwrap();
+        let loaded = OpponentBook::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, book);
+        assert!(OpponentBook::load(&path).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,360 @@
+//! Shares the time between our moves out between every game that has something to ponder
+//!
+//! A snake that wants to ponder hands its [Ponder] over with [schedule], and takes it back with
+//! [take] once the next request for the game comes in. A single thread steps the ponders a short
+//! slice at a time, always picking the game that matters most, see [PonderPriority], and the one
+//! whose next request is due the soonest among those. Pondering only uses time nobody else needs,
+//! so it stops for as long as any real move is being searched, see [real_move]
+//!
+//! [Ponder]: battlesnake_minimax::lazy_smp::Ponder
+
+use std::{
+    any::Any,
+    cmp::Reverse,
+    sync::{Condvar, Mutex, MutexGuard, Once, OnceLock},
+    time::{Duration, Instant},
+};
+
+use battlesnake_game_types::wire_representation::NestedGame;
+use battlesnake_minimax::{SearchStatus, SearchStepper};
+
+/// How long a ponder is stepped before the scheduler looks for a real move and picks a game again,
+/// which is also about as long as a real move can have to wait for the thread
+const PONDER_SLICE: Duration = Duration::from_millis(10);
+
+/// Ponders that weren't taken back this long after their game's next request was due are for
+/// games that ended without telling us
+const STALE_AFTER: Duration = Duration::from_secs(10 * 60);
+
+/// How much a game's pondering matters, the games that count for the most go first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PonderPriority {
+    /// Challenges, custom games and anything else that doesn't say where it came from
+    Casual,
+    /// Ladder and arena games, which move our rating
+    Ladder,
+    /// League games
+    League,
+    /// Tournament games, where a single loss can knock us out
+    Tournament,
+}
+
+impl PonderPriority {
+    /// The priority for a game, from its source
+    pub fn for_game(game_info: &NestedGame) -> Self {
+        match game_info.source.as_deref() {
+            Some("tournament") => PonderPriority::Tournament,
+            Some("league") => PonderPriority::League,
+            Some("ladder" | "arena") => PonderPriority::Ladder,
+            _ => PonderPriority::Casual,
+        }
+    }
+}
+
+/// A [SearchStepper] we can give back as what it was when it was scheduled
+trait Scheduled: SearchStepper + Send {
+    fn into_any(self: Box<Self>) -> Box<dyn Any + Send>;
+}
+
+impl<T: SearchStepper + Send + 'static> Scheduled for T {
+    fn into_any(self: Box<Self>) -> Box<dyn Any + Send> {
+        self
+    }
+}
+
+struct Pondering {
+    game_id: String,
+    priority: PonderPriority,
+    /// When the next request for the game is due, after which pondering it doesn't help
+    until: Instant,
+    /// None while the scheduler is stepping it
+    ponder: Option<Box<dyn Scheduled>>,
+    done: bool,
+}
+
+impl Pondering {
+    fn runnable(&self, now: Instant) -> bool {
+        self.ponder.is_some() && !self.done && self.until > now
+    }
+}
+
+#[derive(Default)]
+struct Schedule {
+    pondering: Vec<Pondering>,
+    /// Real moves being searched right now, nothing is pondered while there are any
+    moves_in_flight: usize,
+}
+
+/// The game to step next, the one that matters most and then the one whose next request is due
+/// the soonest
+fn next_to_step(pondering: &[Pondering], now: Instant) -> Option<usize> {
+    pondering
+        .iter()
+        .enumerate()
+        .filter(|(_, p)| p.runnable(now))
+        .max_by_key(|(_, p)| (p.priority, Reverse(p.until)))
+        .map(|(i, _)| i)
+}
+
+struct Scheduler {
+    schedule: Mutex<Schedule>,
+    /// Signalled whenever there might be something new to step, or a ponder was put back
+    changed: Condvar,
+}
+
+fn scheduler() -> &'static Scheduler {
+    static SCHEDULER: OnceLock<Scheduler> = OnceLock::new();
+    static STARTED: Once = Once::new();
+
+    let scheduler = SCHEDULER.get_or_init(|| Scheduler {
+        schedule: Default::default(),
+        changed: Condvar::new(),
+    });
+    STARTED.call_once(|| {
+        std::thread::Builder::new()
+            .name("ponder-scheduler".to_owned())
+            .spawn(move || scheduler.run())
+            .expect("Couldn't start the ponder scheduler");
+    });
+
+    scheduler
+}
+
+impl Scheduler {
+    fn lock(&self) -> MutexGuard<'_, Schedule> {
+        self.schedule.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn run(&self) {
+        let mut schedule = self.lock();
+
+        loop {
+            let now = Instant::now();
+            schedule
+                .pondering
+                .retain(|p| now.saturating_duration_since(p.until) < STALE_AFTER);
+
+            let next = (schedule.moves_in_flight == 0)
+                .then(|| next_to_step(&schedule.pondering, now))
+                .flatten();
+            let Some(index) = next else {
+                schedule = self
+                    .changed
+                    .wait_timeout(schedule, Duration::from_secs(1))
+                    .unwrap_or_else(|e| e.into_inner())
+                    .0;
+                continue;
+            };
+
+            let entry = &mut schedule.pondering[index];
+            let game_id = entry.game_id.clone();
+            let slice = PONDER_SLICE.min(entry.until.saturating_duration_since(now));
+            let mut ponder = entry
+                .ponder
+                .take()
+                .expect("Only runnable ponders are stepped");
+            drop(schedule);
+
+            let status = ponder.step(slice);
+
+            schedule = self.lock();
+            // Scheduling the game again while we were stepping replaced this ponder, so it's
+            // dropped here instead of put back
+            if let Some(entry) = schedule
+                .pondering
+                .iter_mut()
+                .find(|p| p.game_id == game_id && p.ponder.is_none())
+            {
+                entry.ponder = Some(ponder);
+                entry.done = status == SearchStatus::Done;
+            }
+            self.changed.notify_all();
+        }
+    }
+}
+
+/// Ponder for the game until `until`, when the next request for it is due, or until the game
+/// takes it back with [take]. This replaces anything the game was already pondering
+pub fn schedule<P>(game_id: &str, priority: PonderPriority, until: Instant, ponder: P)
+where
+    P: SearchStepper + Send + 'static,
+{
+    let scheduler = scheduler();
+    let mut schedule = scheduler.lock();
+
+    schedule.pondering.retain(|p| p.game_id != game_id);
+    schedule.pondering.push(Pondering {
+        game_id: game_id.to_owned(),
+        priority,
+        until,
+        ponder: Some(Box::new(ponder)),
+        done: false,
+    });
+    scheduler.changed.notify_all();
+}
+
+/// Stop pondering the game and give back what it was pondering, None if it isn't pondering or
+/// it's pondering something other than a `P`
+///
+/// If the ponder is being stepped right now, this waits for the end of the slice
+pub fn take<P: 'static>(game_id: &str) -> Option<P> {
+    let scheduler = scheduler();
+    let mut schedule = scheduler.lock();
+
+    loop {
+        let index = schedule
+            .pondering
+            .iter()
+            .position(|p| p.game_id == game_id)?;
+        if schedule.pondering[index].ponder.is_some() {
+            let ponder = schedule.pondering.remove(index).ponder?;
+
+            return ponder.into_any().downcast().ok().map(|ponder| *ponder);
+        }
+
+        schedule = scheduler
+            .changed
+            .wait(schedule)
+            .unwrap_or_else(|e| e.into_inner());
+    }
+}
+
+/// Stop pondering the game, without waiting for a slice that's being stepped right now
+pub fn cancel(game_id: &str) {
+    scheduler()
+        .lock()
+        .pondering
+        .retain(|p| p.game_id != game_id);
+}
+
+/// How many games have something pondering, or waiting to be taken back
+pub fn pondering_games() -> usize {
+    scheduler().lock().pondering.len()
+}
+
+/// Pauses all pondering for as long as it's held, see [real_move]
+#[derive(Debug)]
+pub struct RealMove(());
+
+impl Drop for RealMove {
+    fn drop(&mut self) {
+        let scheduler = scheduler();
+        scheduler.lock().moves_in_flight -= 1;
+        scheduler.changed.notify_all();
+    }
+}
+
+/// Hold the returned guard while searching a move the engine is waiting on, so no pondering
+/// competes with it. Pondering stops within a [PONDER_SLICE] and picks up again once every guard
+/// is dropped
+pub fn real_move() -> RealMove {
+    scheduler().lock().moves_in_flight += 1;
+
+    RealMove(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use battlesnake_game_types::types::Move;
+
+    use super::*;
+
+    /// Counts how many times it's stepped, and never finishes
+    struct Counting(Arc<AtomicUsize>);
+
+    impl SearchStepper for Counting {
+        fn step(&mut self, _budget: Duration) -> SearchStatus {
+            self.0.fetch_add(1, Ordering::Relaxed);
+
+            SearchStatus::InProgress
+        }
+
+        fn best_move(&self) -> Option<Move> {
+            None
+        }
+    }
+
+    fn pondering(game_id: &str, priority: PonderPriority, until: Instant) -> Pondering {
+        Pondering {
+            game_id: game_id.to_owned(),
+            priority,
+            until,
+            ponder: Some(Box::new(Counting(Default::default()))),
+            done: false,
+        }
+    }
+
+    #[test]
+    fn test_priority_goes_first_then_the_closest_deadline() {
+        let now = Instant::now();
+        let soon = now + Duration::from_millis(100);
+        let later = now + Duration::from_millis(400);
+
+        let mut games = vec![
+            pondering("ladder-soon", PonderPriority::Ladder, soon),
+            pondering("tournament-later", PonderPriority::Tournament, later),
+            pondering("tournament-soon", PonderPriority::Tournament, soon),
+        ];
+        assert_eq!(next_to_step(&games, now), Some(2));
+
+        // Ponders that are being stepped, have finished or ran out of time aren't stepped
+        games[2].ponder = None;
+        assert_eq!(next_to_step(&games, now), Some(1));
+        games[1].done = true;
+        assert_eq!(next_to_step(&games, now), Some(0));
+        assert_eq!(next_to_step(&games, soon), None);
+    }
+
+    #[test]
+    fn test_priority_comes_from_the_source() {
+        let mut game_info: NestedGame =
+            serde_json::from_str::<crate::Game>(include_str!("../fixtures/start_of_game.json"))
+                .unwrap()
+                .game;
+
+        game_info.source = Some("tournament".to_owned());
+        assert_eq!(
+            PonderPriority::for_game(&game_info),
+            PonderPriority::Tournament
+        );
+        game_info.source = Some("arena".to_owned());
+        assert_eq!(PonderPriority::for_game(&game_info), PonderPriority::Ladder);
+        game_info.source = None;
+        assert_eq!(PonderPriority::for_game(&game_info), PonderPriority::Casual);
+    }
+
+    #[test]
+    fn test_real_moves_pause_pondering_and_take_gives_it_back() {
+        let steps = Arc::new(AtomicUsize::new(0));
+        let game_id = "test-real-moves-pause-pondering";
+        let until = Instant::now() + Duration::from_secs(10);
+
+        let real_move = real_move();
+        schedule(
+            game_id,
+            PonderPriority::Casual,
+            until,
+            Counting(steps.clone()),
+        );
+        std::thread::sleep(PONDER_SLICE * 5);
+        assert_eq!(steps.load(Ordering::Relaxed), 0);
+
+        // Other tests can be searching real moves of their own, so this can take a while
+        drop(real_move);
+        let waiting_since = Instant::now();
+        while steps.load(Ordering::Relaxed) == 0 && waiting_since.elapsed() < Duration::from_secs(5)
+        {
+            std::thread::sleep(PONDER_SLICE);
+        }
+        assert!(steps.load(Ordering::Relaxed) > 0);
+
+        let taken = take::<Counting>(game_id).unwrap();
+        assert!(Arc::ptr_eq(&taken.0, &steps));
+        assert!(take::<Counting>(game_id).is_none());
+    }
+}
@@ -0,0 +1,156 @@
+use std::sync::Arc;
+
+use battlesnake_minimax::{board_hash::BoardHashable, paranoid::Scorable};
+use decorum::N64;
+use rustc_hash::FxHashMap;
+
+use crate::{hovering_hobbs::Score, *};
+
+/// How many times we need to have seen the same position before we call the game a stalemate
+const STALEMATE_REPETITIONS: usize = 3;
+
+/// How much of the board a position is worth giving up for each time we've already been in it
+const REPETITION_PENALTY: f64 = 0.05;
+
+/// Once we are in a stalemate, how much it's worth for each opponent we push into a hazard
+const STALEMATE_HAZARD_BONUS: f64 = 0.02;
+
+/// Every position we have been in this game, and how many times
+///
+/// Positions are compared with [BoardHashable::position_hash], so snakes going around in the same
+/// loop count as a repeat even though their health keeps going down
+#[derive(Debug, Clone, Default)]
+pub struct PositionHistory {
+    seen: FxHashMap<u64, usize>,
+}
+
+impl PositionHistory {
+    /// Remember that we were in this position, returning how many times we have been in it now
+    pub fn record<T: BoardHashable>(&mut self, board: &T) -> usize {
+        let count = self.seen.entry(board.position_hash()).or_default();
+        *count += 1;
+
+        *count
+    }
+
+    /// How many times we have been in the position with this [BoardHashable::position_hash]
+    pub fn times_seen(&self, position_hash: u64) -> usize {
+        self.seen.get(&position_hash).copied().unwrap_or_default()
+    }
+
+    /// Have we been in the same position enough times that the snakes are stuck in a loop
+    pub fn is_stalemate(&self) -> bool {
+        self.seen
+            .values()
+            .any(|count| *count >= STALEMATE_REPETITIONS)
+    }
+}
+
+/// Wraps a score function to steer the search out of loops
+///
+/// Positions we have already been in score a little worse for every time we've seen them, so when
+/// a different line is nearly as good we take it instead of going around again. Once the game has
+/// become a stalemate we also reward pushing opponents into hazards, since that's what usually
+/// breaks a tail chase in our favor
+#[derive(Debug, Clone)]
+pub struct AntiRepetition<ScorableType> {
+    inner: ScorableType,
+    history: Arc<PositionHistory>,
+    stalemate: bool,
+}
+
+impl<ScorableType> AntiRepetition<ScorableType> {
+    pub fn new(inner: ScorableType, history: Arc<PositionHistory>) -> Self {
+        let stalemate = history.is_stalemate();
+
+        Self {
+            inner,
+            history,
+            stalemate,
+        }
+    }
+}
+
+impl<BoardType, ScorableType> Scorable<BoardType, Score> for AntiRepetition<ScorableType>
+where
+    ScorableType: Scorable<BoardType, Score>,
+    BoardType: BoardHashable
+        + SnakeIDGettableGame
+        + YouDeterminableGame
+        + HeadGettableGame
+        + HazardQueryableGame,
+{
+    fn score(&self, board: &BoardType) -> Score {
+        let score = self.inner.score(board);
+
+        let times_seen = self.history.times_seen(board.position_hash());
+        let mut nudge = -REPETITION_PENALTY * times_seen as f64;
+
+        if self.stalemate {
+            let me = board.you_id();
+            let opponents_in_hazard = board
+                .get_snake_ids()
+                .iter()
+                .filter(|sid| *sid != me)
+                .filter(|sid| board.is_hazard(&board.get_head_as_native_position(sid)))
+                .count();
+
+            nudge += STALEMATE_HAZARD_BONUS * opponents_in_hazard as f64;
+        }
+
+        if nudge == 0.0 {
+            return score;
+        }
+
+        score.nudged(N64::from(nudge))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start_of_game() -> StandardCellBoard4Snakes11x11 {
+        let game: Game =
+            serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap();
+        let id_map = build_snake_id_map(&game);
+
+        StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap()
+    }
+
+    fn flat_score(_: &StandardCellBoard4Snakes11x11) -> Score {
+        Score::FloodFill(N64::from(0.5))
+    }
+
+    #[test]
+    fn test_stalemate_after_repeating_a_position() {
+        let board = start_of_game();
+        let mut history = PositionHistory::default();
+
+        assert_eq!(history.record(&board), 1);
+        assert_eq!(history.record(&board), 2);
+        assert!(!history.is_stalemate());
+
+        assert_eq!(history.record(&board), 3);
+        assert!(history.is_stalemate());
+    }
+
+    #[test]
+    fn test_new_positions_are_not_nudged() {
+        let board = start_of_game();
+        let scorer = AntiRepetition::new(&flat_score, Arc::new(PositionHistory::default()));
+
+        assert_eq!(scorer.score(&board), flat_score(&board));
+    }
+
+    #[test]
+    fn test_repeated_positions_score_worse() {
+        let board = start_of_game();
+        let mut history = PositionHistory::default();
+        history.record(&board);
+
+        let scorer = AntiRepetition::new(&flat_score, Arc::new(history));
+
+        assert!(scorer.score(&board) < flat_score(&board));
+    }
+}
@@ -0,0 +1,421 @@
+//! Everything about answering the engine that doesn't depend on which web framework we're running
+//! in, so axum, rocket and lambda can't drift apart
+//!
+//! Each host only needs to turn its request into a method, path, body and headers, pass them to
+//! [handle_request], and turn the [Response] back into its own. web-axum has routes and snakes of
+//! its own on top of these, but it still finds factories with [factory_for], reports errors with
+//! [Response::internal_error] and answers moves it can't read with [unreadable_game_move]
+
+use std::fmt::Display;
+
+use color_eyre::eyre::Report;
+use serde_json::{json, Value};
+
+use crate::{
+    branding::branding_for,
+    deadline::apply_deadline,
+    failure_injection::Failure,
+    hovering_hobbs,
+    move_guard::GuardedFactory,
+    opponent_book, ponder_scheduler,
+    request_tracing::{parse_game, request_span, GameCorrelation},
+    *,
+};
+
+/// The request from the engine a path asks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Info,
+    Start,
+    Move,
+    End,
+}
+
+/// A response any host can send back
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    /// None for responses without a body, which are all `204 No Content`
+    pub body: Option<Value>,
+}
+
+impl Response {
+    fn json(body: Value) -> Self {
+        Self {
+            status: 200,
+            body: Some(body),
+        }
+    }
+
+    fn no_content() -> Self {
+        Self {
+            status: 204,
+            body: None,
+        }
+    }
+
+    fn error(status: u16, error: &str) -> Self {
+        Self {
+            status,
+            body: Some(json!({ "error": error })),
+        }
+    }
+
+    /// The response for a request that failed on our side, with the error logged
+    pub fn internal_error(error: &Report) -> Self {
+        tracing::error!(error = ?error, "Request failed");
+
+        Self {
+            status: 500,
+            body: Some(json!({ "error": "Things Broke", "details": error.to_string() })),
+        }
+    }
+}
+
+/// The factory for the snake with this name, if we have one
+///
+/// This is every snake in [all_factories], and Hovering Hobbs. The hosts without a place to keep
+/// state between moves get the plain [hovering_hobbs::Factory], web-axum serves its own Hobbs
+/// before it ever asks for this one
+pub fn factory_for(snake_name: &str) -> Option<BoxedFactory> {
+    let hobbs: BoxedFactory = Box::new(GuardedFactory(Box::new(hovering_hobbs::Factory)));
+
+    all_factories()
+        .into_iter()
+        .chain(std::iter::once(hobbs))
+        .find(|factory| factory.name() == snake_name)
+}
+
+/// Split a path into the snake it's for and what it asks for
+///
+/// Errors are the response to send back, a 404 for a path we don't serve and a 405 for the wrong
+/// method
+pub fn parse_route<'path>(
+    method: &str,
+    path: &'path str,
+) -> Result<(&'path str, Action), Response> {
+    let parts: Vec<&str> = path.split('/').filter(|part| !part.is_empty()).collect();
+
+    let (snake_name, action) = match parts.as_slice() {
+        [snake_name] => (*snake_name, Action::Info),
+        [snake_name, "start"] => (*snake_name, Action::Start),
+        [snake_name, "move"] => (*snake_name, Action::Move),
+        [snake_name, "end"] => (*snake_name, Action::End),
+        _ => return Err(Response::error(404, "Not found")),
+    };
+
+    let expected_method = match action {
+        Action::Info => "GET",
+        Action::Start | Action::Move | Action::End => "POST",
+    };
+    if !method.eq_ignore_ascii_case(expected_method) {
+        return Err(Response::error(405, "Method not allowed"));
+    }
+
+    Ok((snake_name, action))
+}
+
+/// The move we answer with when we can't read the game out of a move request
+///
+/// Without a board there is no way to tell which moves are safe, so this is always up. That is
+/// still a legal answer, and better than the engine moving us for not answering at all
+pub fn unreadable_game_move(error: &dyn Display) -> MoveOutput {
+    tracing::warn!(error = %error, "Couldn't read the game, answering with a fallback move");
+
+    MoveOutput {
+        r#move: Move::Up.to_string(),
+        shout: None,
+    }
+}
+
+/// Answer a request from the engine
+///
+/// The request is handled in a [request_span], tagged with the game from the body when there is
+/// one. `deadline` is the value of the [crate::deadline::DEADLINE_HEADER] and `failure` the value
+/// of the [crate::failure_injection::FAILURE_HEADER], if the request had them
+pub fn handle_request(
+    method: &str,
+    path: &str,
+    body: &[u8],
+    deadline: Option<&str>,
+    failure: Option<&str>,
+) -> Response {
+    handle_with_failure(method, path, body, deadline, Failure::requested(failure))
+}
+
+fn handle_with_failure(
+    method: &str,
+    path: &str,
+    body: &[u8],
+    deadline: Option<&str>,
+    failure: Option<Failure>,
+) -> Response {
+    let span = request_span(method, path);
+
+    let remaining_deadline;
+    let (body, deadline) = match failure {
+        Some(failure) => {
+            span.in_scope(|| tracing::warn!(failure = ?failure, "Injecting a failure"));
+            std::thread::sleep(failure.delay());
+            remaining_deadline = failure.remaining_deadline(deadline, body);
+
+            (
+                failure.apply_to_body(body),
+                remaining_deadline.as_deref().or(deadline),
+            )
+        }
+        None => (body, deadline),
+    };
+
+    if let Some(correlation) = GameCorrelation::from_body(body) {
+        correlation.record(&span);
+    }
+    opponent_book::observe_latencies(body);
+
+    span.in_scope(|| respond(method, path, body, deadline))
+}
+
+fn respond(method: &str, path: &str, body: &[u8], deadline: Option<&str>) -> Response {
+    let (snake_name, action) = match parse_route(method, path) {
+        Ok(route) => route,
+        Err(response) => return response,
+    };
+    let Some(factory) = factory_for(snake_name) else {
+        return Response::error(404, "No snake with that name");
+    };
+
+    if action == Action::Info {
+        return Response::json(json!(factory.about().branded(branding_for(snake_name))));
+    }
+    if action == Action::Start {
+        return Response::no_content();
+    }
+
+    let mut game = match parse_game(body) {
+        Ok(game) => game,
+        Err(e) if action == Action::Move => {
+            return Response::json(json!(unreadable_game_move(&e)));
+        }
+        Err(e) => {
+            return Response {
+                status: 400,
+                body: Some(json!({ "error": "Invalid game", "details": e.to_string() })),
+            }
+        }
+    };
+    apply_deadline(&mut game, deadline);
+    let snake = factory.create_from_wire_game(game);
+
+    match action {
+        Action::Move => {
+            let _real_move = ponder_scheduler::real_move();
+
+            match snake.make_move() {
+                Ok(output) => Response::json(json!(output)),
+                Err(e) => Response::internal_error(&e),
+            }
+        }
+        Action::End => {
+            snake.end();
+
+            Response::no_content()
+        }
+        Action::Info | Action::Start => unreachable!("Handled before we parsed the game"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    const START_OF_GAME: &str = include_str!("../fixtures/start_of_game.json");
+
+    #[test]
+    fn test_routes() {
+        assert_eq!(
+            parse_route("GET", "/improbable-irene/"),
+            Ok(("improbable-irene", Action::Info))
+        );
+        assert_eq!(
+            parse_route("post", "/hovering-hobbs/move"),
+            Ok(("hovering-hobbs", Action::Move))
+        );
+        assert_eq!(
+            parse_route("GET", "/hovering-hobbs/move")
+                .unwrap_err()
+                .status,
+            405
+        );
+        assert_eq!(
+            parse_route("POST", "/hovering-hobbs/graph")
+                .unwrap_err()
+                .status,
+            404
+        );
+        assert_eq!(parse_route("GET", "/").unwrap_err().status, 404);
+    }
+
+    #[test]
+    fn test_every_host_can_find_hobbs() {
+        assert!(factory_for("hovering-hobbs").is_some());
+        assert!(factory_for("improbable-irene").is_some());
+        assert!(factory_for("nobody").is_none());
+    }
+
+    #[test]
+    fn test_unknown_snakes_are_not_found() {
+        let response = handle_request("POST", "/nobody/move", START_OF_GAME.as_bytes(), None, None);
+
+        assert_eq!(response.status, 404);
+    }
+
+    #[test]
+    fn test_a_bad_body_is_a_bad_request() {
+        let response = handle_request("POST", "/constant-carter/end", b"{}", None, None);
+
+        assert_eq!(response.status, 400);
+    }
+
+    #[test]
+    fn test_a_move_we_cant_read_still_gets_a_move() {
+        let response = handle_request("POST", "/constant-carter/move", b"{}", None, None);
+
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body.unwrap()["move"], "up");
+    }
+
+    /// Every failure still gets a legal move back before the deadline
+    #[test]
+    fn test_injected_failures_degrade_to_a_move_in_time() {
+        let body = START_OF_GAME.as_bytes();
+        let deadline = Duration::from_millis(400);
+        let failures = [
+            Failure::Delay(Duration::from_millis(150)),
+            Failure::DropBody,
+            Failure::MalformedBody,
+        ];
+
+        for failure in failures {
+            let started_at = Instant::now();
+            let response = handle_with_failure(
+                "POST",
+                "/hovering-hobbs/move",
+                body,
+                Some(&deadline.as_millis().to_string()),
+                Some(failure),
+            );
+            let elapsed = started_at.elapsed();
+
+            assert_eq!(response.status, 200, "{failure:?}");
+            let chosen = response.body.unwrap()["move"].as_str().unwrap().to_owned();
+            assert!(
+                Move::all_iter().any(|m| m.to_string() == chosen),
+                "{failure:?} answered {chosen}"
+            );
+            assert!(elapsed < deadline, "{failure:?} took {elapsed:?}");
+        }
+    }
+
+    #[test]
+    fn test_moves_and_game_lifecycle() {
+        let body = START_OF_GAME.as_bytes();
+
+        assert_eq!(
+            handle_request("POST", "/constant-carter/start", body, None, None),
+            Response::no_content()
+        );
+
+        let response = handle_request("POST", "/constant-carter/move", body, Some("250"), None);
+        assert_eq!(response.status, 200);
+        assert!(response.body.unwrap()["move"].is_string());
+
+        assert_eq!(
+            handle_request("POST", "/constant-carter/end", body, None, None),
+            Response::no_content()
+        );
+    }
+
+    /// Every snake any host serves, in the order the snapshots list them
+    fn snake_names() -> Vec<String> {
+        all_factories()
+            .iter()
+            .map(|factory| factory.name())
+            .chain(std::iter::once("hovering-hobbs".to_owned()))
+            .collect()
+    }
+
+    fn snapshot_of(snake: &str, response: Response) -> Value {
+        json!({ "snake": snake, "status": response.status, "body": response.body })
+    }
+
+    // The snapshots pin down the exact JSON the engine gets from us, so a change to the casing or
+    // fields of `AboutMe` or `MoveOutput` shows up as a snapshot diff. If the change was on
+    // purpose, review and accept it with `cargo insta review`
+
+    #[test]
+    fn test_info_responses_snapshot() {
+        let responses: Vec<Value> = snake_names()
+            .iter()
+            .map(|name| {
+                let mut response = handle_request("GET", &format!("/{name}"), &[], None, None);
+                // This is only there once some game used a ruleset version we haven't validated,
+                // which depends on what else ran in this process first
+                if let Some(body) = response.body.as_mut().and_then(Value::as_object_mut) {
+                    body.remove("unvalidated_ruleset_versions");
+                }
+
+                snapshot_of(name, response)
+            })
+            .collect();
+
+        insta::assert_json_snapshot!("info_responses", responses);
+    }
+
+    #[test]
+    fn test_move_responses_snapshot() {
+        let body = START_OF_GAME.as_bytes();
+        let responses: Vec<Value> = snake_names()
+            .iter()
+            .map(|name| {
+                let response =
+                    handle_request("POST", &format!("/{name}/move"), body, Some("250"), None);
+
+                snapshot_of(name, response)
+            })
+            .collect();
+
+        // Which move a search picks isn't part of the contract, only that it's one of the four
+        insta::assert_json_snapshot!("move_responses", responses, {
+            "[].body.move" => insta::dynamic_redaction(|value, _| {
+                let chosen = value.as_str().expect("Moves are strings");
+                assert!(
+                    Move::all_iter().any(|m| m.to_string() == chosen),
+                    "{chosen} isn't a move"
+                );
+
+                "[move]"
+            }),
+            "[].body.shout" => insta::dynamic_redaction(|value, _| match value.as_str() {
+                Some(_) => "[shout]".into(),
+                None => value,
+            }),
+        });
+    }
+
+    #[test]
+    fn test_end_responses_snapshot() {
+        let body = START_OF_GAME.as_bytes();
+        let responses: Vec<Value> = snake_names()
+            .iter()
+            .map(|name| {
+                let response = handle_request("POST", &format!("/{name}/end"), body, None, None);
+
+                snapshot_of(name, response)
+            })
+            .collect();
+
+        insta::assert_json_snapshot!("end_responses", responses);
+    }
+}
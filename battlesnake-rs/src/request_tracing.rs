@@ -0,0 +1,182 @@
+use std::{borrow::Cow, time::Instant};
+
+use tracing::{field::Empty, Span};
+
+use crate::{
+    extra_settings::{self, ExtraSettings},
+    feature_flags::feature_flags,
+    game_seed::game_seed,
+    ruleset_version::check_ruleset_version,
+    Game,
+};
+
+#[derive(Deserialize, Default)]
+struct RulesetField<'a> {
+    #[serde(default, borrow)]
+    name: Cow<'a, str>,
+    #[serde(default, borrow)]
+    version: Cow<'a, str>,
+}
+
+#[derive(Deserialize)]
+struct GameIdField<'a> {
+    #[serde(borrow)]
+    id: Cow<'a, str>,
+    #[serde(default, borrow)]
+    ruleset: RulesetField<'a>,
+}
+
+#[derive(Deserialize)]
+struct YouNameField<'a> {
+    #[serde(borrow)]
+    name: Cow<'a, str>,
+}
+
+/// Only the parts of a request body we need, so we don't pay for parsing the whole board just to
+/// label a span
+///
+/// The strings borrow from the body unless they have escapes in them, so skipping over the board
+/// is most of the work
+#[derive(Deserialize)]
+struct CorrelationBody<'a> {
+    #[serde(borrow)]
+    game: GameIdField<'a>,
+    turn: i32,
+    #[serde(borrow)]
+    you: YouNameField<'a>,
+}
+
+/// The fields we use to follow a single game across all of its requests
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameCorrelation {
+    pub game_id: String,
+    pub turn: i32,
+    pub snake_name: String,
+    pub ruleset_name: String,
+    pub ruleset_version: String,
+}
+
+impl GameCorrelation {
+    /// Pull the correlation fields out of a raw request body
+    ///
+    /// Returns None for bodies that aren't game states, like the info request
+    pub fn from_body(body: &[u8]) -> Option<Self> {
+        let body: CorrelationBody = serde_json::from_slice(body).ok()?;
+
+        Some(Self {
+            game_id: body.game.id.into_owned(),
+            turn: body.turn,
+            snake_name: body.you.name.into_owned(),
+            ruleset_name: body.game.ruleset.name.into_owned(),
+            ruleset_version: body.game.ruleset.version.into_owned(),
+        })
+    }
+
+    /// Record these fields on a span created by [request_span]
+    ///
+    /// This is also where we check the ruleset version, since it's the one place every host sees
+    /// every game state, see [check_ruleset_version]
+    pub fn record(&self, span: &Span) {
+        span.record("game_id", self.game_id.as_str());
+        span.record("turn", self.turn);
+        span.record("snake_name", self.snake_name.as_str());
+        span.record("ruleset_version", self.ruleset_version.as_str());
+        span.record("game_seed", game_seed(&self.game_id));
+
+        let _entered = span.enter();
+        let validated = check_ruleset_version(
+            &self.ruleset_name,
+            &self.ruleset_version,
+            &self.game_id,
+            self.turn,
+        );
+        span.record("ruleset_validated", validated);
+    }
+}
+
+/// Parse the game out of a request body, and record how big it was and how long that took on
+/// the current span as `payload_bytes` and `parse_us`
+///
+/// The arcade maze boards with 8 snakes are big enough that parsing them shows up in how long we
+/// take to answer, and every microsecond spent here is one the search doesn't get
+///
+/// This is also where we keep the [ExtraSettings] the wire types drop, so they're only read from
+/// bodies that turned out to be a game
+pub fn parse_game(body: &[u8]) -> serde_json::Result<Game> {
+    let started_at = Instant::now();
+    let game = serde_json::from_slice(body);
+    if game.is_ok() {
+        if let Some((game_id, settings)) = ExtraSettings::from_request(body) {
+            extra_settings::observe(&game_id, settings);
+        }
+    }
+
+    let span = Span::current();
+    span.record("payload_bytes", body.len());
+    span.record("parse_us", started_at.elapsed().as_micros() as u64);
+
+    game
+}
+
+/// The span every request is handled in, on every host
+///
+/// The game fields start out empty and are filled in by [GameCorrelation::record] once the body
+/// has been read. That includes the game's [game_seed], so a replay can draw the same numbers.
+/// Everything that happens while handling the request, including errors, is nested under this
+/// span so they all share the same game fields. The experimental features that are turned on are
+/// recorded up front, see [feature_flags]
+pub fn request_span(method: &str, path: &str) -> Span {
+    tracing::info_span!(
+        "request",
+        method,
+        path,
+        game_id = Empty,
+        turn = Empty,
+        snake_name = Empty,
+        ruleset_version = Empty,
+        ruleset_validated = Empty,
+        game_seed = Empty,
+        payload_bytes = Empty,
+        parse_us = Empty,
+        feature_flags = %feature_flags(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_correlation_from_game_body() {
+        let fixture = include_str!("../fixtures/start_of_game.json");
+
+        let correlation = GameCorrelation::from_body(fixture.as_bytes()).unwrap();
+
+        assert_eq!(
+            correlation,
+            GameCorrelation {
+                game_id: "813456".to_owned(),
+                turn: 200,
+                snake_name: "#22aa34".to_owned(),
+                ruleset_name: "standard".to_owned(),
+                ruleset_version: "v.1.2.3".to_owned(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_correlation_from_empty_body() {
+        assert_eq!(GameCorrelation::from_body(b""), None);
+    }
+
+    #[test]
+    fn test_correlation_with_escapes_in_its_strings() {
+        let body = br#"{"game": {"id": "a\"b"}, "turn": 3, "you": {"name": "caf\u00e9"}}"#;
+
+        let correlation = GameCorrelation::from_body(body).unwrap();
+
+        assert_eq!(correlation.game_id, "a\"b");
+        assert_eq!(correlation.snake_name, "café");
+        assert_eq!(correlation.ruleset_name, "");
+    }
+}
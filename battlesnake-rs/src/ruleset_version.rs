@@ -0,0 +1,134 @@
+//! Which versions of the engine's rules our simulation has been checked against
+//!
+//! Every so often the engine changes its rules, like how hazard damage stacks or how food spawns.
+//! Our simulation copies those rules, so in a game from a version we haven't checked it might not
+//! agree with the engine. Nothing fails when that happens, we just quietly lose games, so instead
+//! we warn about every version we haven't validated and list them in [crate::AboutMe]
+
+use std::{
+    collections::BTreeSet,
+    sync::{Mutex, OnceLock},
+};
+
+use tracing::warn;
+
+/// The ruleset versions the simulation was validated against when this was built
+///
+/// `VALIDATED_RULESET_VERSIONS` adds to these, separated by commas, for versions that were
+/// validated since
+pub const VALIDATED_RULESET_VERSIONS: &[&str] = &["v1.2.3", "Mojave/3.5.2"];
+
+/// Stop remembering new unvalidated versions after this many, so a client sending garbage
+/// versions can't grow the list forever
+const MAX_REMEMBERED_VERSIONS: usize = 32;
+
+/// Some engines write `v.1.2.3` for `v1.2.3`
+fn normalize(version: &str) -> String {
+    let version = version.trim();
+
+    match version.strip_prefix("v.") {
+        Some(rest) => format!("v{rest}"),
+        None => version.to_owned(),
+    }
+}
+
+fn parse_versions<'a>(built_in: &[&str], extra: impl Iterator<Item = &'a str>) -> Vec<String> {
+    built_in
+        .iter()
+        .copied()
+        .chain(extra)
+        .map(normalize)
+        .filter(|version| !version.is_empty())
+        .collect()
+}
+
+static VALIDATED: OnceLock<Vec<String>> = OnceLock::new();
+
+fn validated_versions() -> &'static [String] {
+    VALIDATED.get_or_init(|| {
+        let extra = std::env::var("VALIDATED_RULESET_VERSIONS").unwrap_or_default();
+
+        parse_versions(VALIDATED_RULESET_VERSIONS, extra.split(','))
+    })
+}
+
+/// Whether the simulation has been validated against this ruleset version
+pub fn is_validated(version: &str) -> bool {
+    validated_versions().contains(&normalize(version))
+}
+
+static UNVALIDATED_SEEN: OnceLock<Mutex<BTreeSet<String>>> = OnceLock::new();
+
+fn unvalidated_seen() -> &'static Mutex<BTreeSet<String>> {
+    UNVALIDATED_SEEN.get_or_init(Default::default)
+}
+
+/// Check the ruleset version a game is being played with
+///
+/// Versions we haven't validated are warned about on the first turn of each game, and the first
+/// time we see them at all, so we still hear about games we joined late. Returns whether the
+/// version was validated
+pub fn check_ruleset_version(ruleset_name: &str, version: &str, game_id: &str, turn: i32) -> bool {
+    if is_validated(version) {
+        return true;
+    }
+
+    let first_sighting = {
+        let mut seen = unvalidated_seen().lock().unwrap_or_else(|e| e.into_inner());
+        seen.len() < MAX_REMEMBERED_VERSIONS && seen.insert(normalize(version))
+    };
+
+    if first_sighting || turn == 0 {
+        warn!(
+            ruleset_name,
+            ruleset_version = version,
+            game_id,
+            turn,
+            validated_versions = ?validated_versions(),
+            "This game uses a ruleset version our simulation hasn't been validated against, so its rules may have drifted from ours"
+        );
+    }
+
+    false
+}
+
+/// Every unvalidated ruleset version we've played with since starting, for [crate::AboutMe]
+pub fn unvalidated_versions_seen() -> Vec<String> {
+    unvalidated_seen()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_versions_are_normalized() {
+        let versions = parse_versions(&["v1.2.3"], " v.1.3.0 ,,Mojave/3.6.0".split(','));
+
+        assert_eq!(versions, vec!["v1.2.3", "v1.3.0", "Mojave/3.6.0"]);
+    }
+
+    #[test]
+    fn test_built_in_versions_are_validated() {
+        assert!(is_validated("v1.2.3"));
+        assert!(is_validated("v.1.2.3"));
+        assert!(is_validated("Mojave/3.5.2"));
+    }
+
+    #[test]
+    fn test_unknown_versions_are_remembered() {
+        assert!(!check_ruleset_version(
+            "standard",
+            "v99.0.0-test",
+            "some-game",
+            3
+        ));
+
+        assert!(unvalidated_versions_seen().contains(&"v99.0.0-test".to_owned()));
+    }
+}
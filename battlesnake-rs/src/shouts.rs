@@ -0,0 +1,155 @@
+//! What the other snakes shout, kept purely as analysis metadata
+//!
+//! The engine passes every snake's shout from its last move along to everyone else in the next
+//! request. Most of it is noise, but some snakes print their own evaluation, which is signal we'd
+//! otherwise throw away. Nothing here feeds back into how we play, it's only recorded, see
+//! [crate::opponent_book::opponent_shouts] and Sherlock's archives and reports
+
+use std::collections::HashMap;
+
+/// The most shouts a [ShoutLog] keeps, so a snake that shouts something new every turn of a very
+/// long game can't grow it forever
+const MAX_SHOUTS: usize = 2_000;
+
+/// One snake's shout on one turn
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ObservedShout {
+    pub turn: i32,
+    pub snake_id: String,
+    pub snake_name: String,
+    pub text: String,
+    /// Every number in the shout in order, since that's how snakes tend to print an eval. See
+    /// [numbers_in]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub numbers: Vec<f64>,
+}
+
+impl ObservedShout {
+    /// None for a shout that's empty or only whitespace
+    pub fn new(turn: i32, snake_id: &str, snake_name: &str, text: &str) -> Option<Self> {
+        let text = text.trim();
+        if text.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            turn,
+            snake_id: snake_id.to_owned(),
+            snake_name: snake_name.to_owned(),
+            text: text.to_owned(),
+            numbers: numbers_in(text),
+        })
+    }
+}
+
+/// Every number in a shout, like the `0.73` in `eval: 0.73` or the `-12` in `score=-12`
+///
+/// A `-` only makes a number negative when it isn't stuck to the end of a word, so the `3` in
+/// `depth-3` stays positive
+pub fn numbers_in(text: &str) -> Vec<f64> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut numbers = vec![];
+
+    let mut i = 0;
+    while i < chars.len() {
+        let negative = chars[i] == '-'
+            && chars.get(i + 1).is_some_and(char::is_ascii_digit)
+            && (i == 0 || !chars[i - 1].is_alphanumeric());
+        if !negative && !chars[i].is_ascii_digit() {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        if negative {
+            i += 1;
+        }
+        while chars.get(i).is_some_and(char::is_ascii_digit) {
+            i += 1;
+        }
+        if chars.get(i) == Some(&'.') && chars.get(i + 1).is_some_and(char::is_ascii_digit) {
+            i += 1;
+            while chars.get(i).is_some_and(char::is_ascii_digit) {
+                i += 1;
+            }
+        }
+
+        let token: String = chars[start..i].iter().collect();
+        if let Ok(number) = token.parse() {
+            numbers.push(number);
+        }
+    }
+
+    numbers
+}
+
+/// The shouts of a game turn by turn, leaving out a snake repeating what it shouted last time
+#[derive(Debug, Clone, Default)]
+pub struct ShoutLog {
+    shouts: Vec<ObservedShout>,
+    /// What each snake shouted the last time we saw it, empty when it was quiet
+    last_text: HashMap<String, String>,
+}
+
+impl ShoutLog {
+    /// Add what a snake shouted on a turn, which has to come after every turn already in the log
+    ///
+    /// Returns the shout when it was recorded, which is when it isn't empty and the snake wasn't
+    /// already shouting the same thing
+    pub fn observe(
+        &mut self,
+        turn: i32,
+        snake_id: &str,
+        snake_name: &str,
+        shout: Option<&str>,
+    ) -> Option<&ObservedShout> {
+        let text = shout.unwrap_or_default().trim();
+        let last = self.last_text.insert(snake_id.to_owned(), text.to_owned());
+        if last.as_deref() == Some(text) || self.shouts.len() >= MAX_SHOUTS {
+            return None;
+        }
+
+        self.shouts
+            .push(ObservedShout::new(turn, snake_id, snake_name, text)?);
+
+        self.shouts.last()
+    }
+
+    pub fn shouts(&self) -> &[ObservedShout] {
+        &self.shouts
+    }
+
+    pub fn into_shouts(self) -> Vec<ObservedShout> {
+        self.shouts
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_numbers_are_pulled_out_of_shouts() {
+        assert_eq!(numbers_in("eval: 0.73 depth 12"), vec![0.73, 12.0]);
+        assert_eq!(numbers_in("score=-12"), vec![-12.0]);
+        assert_eq!(numbers_in("depth-3, -0.5!"), vec![3.0, -0.5]);
+        assert_eq!(numbers_in("v1.2."), vec![1.2]);
+        assert!(numbers_in("hiss").is_empty());
+    }
+
+    #[test]
+    fn test_repeated_and_empty_shouts_are_left_out() {
+        let mut log = ShoutLog::default();
+
+        assert!(log.observe(0, "a", "Alice", Some("eval 3")).is_some());
+        assert!(log.observe(1, "a", "Alice", Some("eval 3 ")).is_none());
+        assert!(log.observe(1, "b", "Bob", Some("   ")).is_none());
+        assert!(log.observe(2, "a", "Alice", None).is_none());
+        // Going quiet in between makes the same shout new again
+        assert!(log.observe(3, "a", "Alice", Some("eval 3")).is_some());
+
+        let turns: Vec<i32> = log.shouts().iter().map(|s| s.turn).collect();
+        assert_eq!(turns, vec![0, 3]);
+        assert_eq!(log.shouts()[0].numbers, vec![3.0]);
+    }
+}
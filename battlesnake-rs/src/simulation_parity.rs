@@ -0,0 +1,233 @@
+//! Check at startup that the compact boards still simulate a turn the way our wire level
+//! [MoveableGame] does
+//!
+//! The compact simulation lives in `battlesnake-game-types`, so bumping that crate can change how
+//! a turn plays out without any of our code changing. [verify_simulation_parity] plays every
+//! combination of moves from a few embedded positions both ways and compares what comes out, so a
+//! deploy with a different simulation fails before it gets to a tournament game
+
+use std::{collections::HashMap, fmt::Display};
+
+use battlesnake_game_types::{
+    compact_representation::WrappedCellBoard4Snakes11x11,
+    wire_representation::{BattleSnake, Position},
+};
+use battlesnake_minimax::Instruments;
+use itertools::Itertools;
+
+use crate::{head_to_head::next_head, wire_game::ToWireGame, *};
+
+/// The positions we check. None of them have hazards, since the wire [MoveableGame] takes a fixed
+/// amount of damage for a hazard instead of what the ruleset says
+const FIXTURES: &[(&str, &str)] = &[
+    (
+        "start_of_game",
+        include_str!("../fixtures/start_of_game.json"),
+    ),
+    (
+        "forced_corridor",
+        include_str!("../../fixtures/forced_corridor.json"),
+    ),
+    (
+        "three_snakes_mid_search_elimination",
+        include_str!("../../fixtures/three_snakes_mid_search_elimination.json"),
+    ),
+    (
+        "four_snakes",
+        include_str!("../../fixtures/65401e8f-a92a-445f-9617-94770044e117.json"),
+    ),
+    (
+        "wrapped_mojave",
+        include_str!("../../fixtures/mojave_12_18_12_34.json"),
+    ),
+    (
+        "wrapped_late_game",
+        include_str!("../../fixtures/b6a045ae-abf2-4f6f-b04c-a80ace7881b4_399.json"),
+    ),
+];
+
+/// A turn where the compact board and the wire game didn't agree
+#[derive(Debug, Clone)]
+pub struct Mismatch {
+    pub fixture: &'static str,
+    /// The move each snake made, by its id in the fixture
+    pub moves: Vec<(String, Move)>,
+    pub difference: String,
+}
+
+impl Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let moves = self
+            .moves
+            .iter()
+            .map(|(id, m)| format!("{id} {m}"))
+            .join(", ");
+
+        write!(f, "{} with [{moves}]: {}", self.fixture, self.difference)
+    }
+}
+
+/// Should we check the simulation before taking games
+///
+/// This is on unless `SKIP_SIMULATION_PARITY` is set, for when we need to deploy a build we
+/// already know disagrees
+pub fn parity_check_enabled() -> bool {
+    std::env::var("SKIP_SIMULATION_PARITY").is_err()
+}
+
+/// Play every combination of moves from each embedded position on both the compact boards and the
+/// wire game, and return every turn where they ended up different
+///
+/// The wire game doesn't eliminate anyone, so we only compare the snakes the compact board kept
+/// alive. A snake the compact board kept alive after running into a wall is a mismatch too
+pub fn verify_simulation_parity() -> Vec<Mismatch> {
+    FIXTURES
+        .iter()
+        .flat_map(|(name, json)| {
+            let game: Game = serde_json::from_str(json)
+                .unwrap_or_else(|e| panic!("The {name} fixture should be a valid game: {e}"));
+            let id_map = build_snake_id_map(&game);
+
+            if wire_game::is_wrapped(&game.game) {
+                let board = WrappedCellBoard4Snakes11x11::convert_from_game(game.clone(), &id_map)
+                    .expect("The fixtures all fit on an 11x11 board with 4 snakes");
+                compare_every_turn(name, &game, &id_map, board)
+            } else {
+                let board = StandardCellBoard4Snakes11x11::convert_from_game(game.clone(), &id_map)
+                    .expect("The fixtures all fit on an 11x11 board with 4 snakes");
+                compare_every_turn(name, &game, &id_map, board)
+            }
+        })
+        .collect()
+}
+
+fn compare_every_turn<BoardType>(
+    fixture: &'static str,
+    game: &Game,
+    id_map: &HashMap<String, SnakeId>,
+    board: BoardType,
+) -> Vec<Mismatch>
+where
+    BoardType:
+        SimulableGame<Instruments, 4> + SnakeIDGettableGame<SnakeIDType = SnakeId> + ToWireGame,
+{
+    let all_moves = board
+        .get_snake_ids()
+        .into_iter()
+        .map(|sid| (sid, Move::all()));
+
+    board
+        .simulate_with_moves(&Instruments::default(), all_moves)
+        .filter_map(|(action, next)| {
+            let actions = action.into_inner();
+            let moves: Vec<(String, Move)> = id_map
+                .iter()
+                .filter_map(|(id, sid)| Some((id.clone(), actions[sid.as_usize()]?)))
+                .sorted()
+                .collect();
+
+            let compact = next.to_wire_game(id_map, &game.game, game.turn + 1);
+            let (wire, off_board) = wire_turn(game, &moves);
+
+            difference(&compact, &wire, &off_board).map(|difference| Mismatch {
+                fixture,
+                moves,
+                difference,
+            })
+        })
+        .collect()
+}
+
+/// Play the moves on the wire game, and return it along with the snakes that moved off the board
+fn wire_turn(game: &Game, moves: &[(String, Move)]) -> (Game, Vec<String>) {
+    let mut wire = game.clone();
+    let mut off_board = vec![];
+
+    for (id, m) in moves {
+        let head = game
+            .board
+            .snakes
+            .iter()
+            .find(|s| &s.id == id)
+            .expect("Every moved snake is on the board")
+            .head;
+
+        match next_head(game, head, *m) {
+            Some(pos) => {
+                wire.move_to(&pos, id);
+            }
+            None => off_board.push(id.clone()),
+        }
+    }
+    wire.nature_move();
+
+    (wire, off_board)
+}
+
+fn difference(compact: &Game, wire: &Game, off_board: &[String]) -> Option<String> {
+    let wire_snake =
+        |id: &str| -> Option<&BattleSnake> { wire.board.snakes.iter().find(|s| s.id == id) };
+
+    for snake in &compact.board.snakes {
+        if off_board.contains(&snake.id) {
+            return Some(format!(
+                "{} moved off the board but is still alive",
+                snake.id
+            ));
+        }
+        let Some(expected) = wire_snake(&snake.id) else {
+            return Some(format!("{} is only on the compact board", snake.id));
+        };
+
+        let body: Vec<Position> = snake.body.iter().copied().collect();
+        let expected_body: Vec<Position> = expected.body.iter().copied().collect();
+        if body != expected_body {
+            return Some(format!(
+                "{} has body {body:?} instead of {expected_body:?}",
+                snake.id
+            ));
+        }
+        if snake.health != expected.health {
+            return Some(format!(
+                "{} has {} health instead of {}",
+                snake.id, snake.health, expected.health
+            ));
+        }
+    }
+
+    let food = |game: &Game| game.board.food.iter().copied().sorted().collect_vec();
+    let (food, expected_food) = (food(compact), food(wire));
+    if food != expected_food {
+        return Some(format!("food is {food:?} instead of {expected_food:?}"));
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compact_and_wire_simulations_agree() {
+        let mismatches = verify_simulation_parity();
+
+        assert!(
+            mismatches.is_empty(),
+            "{}",
+            mismatches.iter().map(ToString::to_string).join("\n")
+        );
+    }
+
+    #[test]
+    fn test_a_changed_outcome_is_a_mismatch() {
+        let game: Game =
+            serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap();
+        let mut compact = game.clone();
+        compact.board.snakes[0].health -= 1;
+
+        let difference = difference(&compact, &game, &[]).unwrap();
+
+        assert!(difference.contains("health"), "{difference}");
+    }
+}
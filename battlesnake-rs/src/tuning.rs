@@ -0,0 +1,218 @@
+use std::time::Duration;
+
+use crate::{
+    flood_fill::spread_from_head::Scores,
+    mcts::SnakeOptions,
+    opponent_book::opponent_timing_out,
+    wire_game::{is_wrapped, WRAPPED_CONSTRICTOR},
+    Game,
+};
+
+/// A named set of tuning values for a kind of game
+///
+/// Profiles are picked from [profiles()] by [profile_for()], so the list doubles as a registry of
+/// every tuning we run with
+#[derive(Debug, Clone)]
+pub struct TuningProfile {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Whether this profile is meant for the given game
+    pub applies_to: fn(&Game) -> bool,
+    /// The options for our MCTS snakes
+    pub mcts: SnakeOptions,
+}
+
+fn is_wrapped_duel(game: &Game) -> bool {
+    is_wrapped(&game.game) && game.board.snakes.len() == 2
+}
+
+fn is_wrapped_constrictor(game: &Game) -> bool {
+    game.game.ruleset.name == WRAPPED_CONSTRICTOR
+}
+
+/// Every profile we know about, in the order they are checked
+///
+/// The last profile applies to every game, so [profile_for()] always finds one
+pub fn profiles() -> Vec<TuningProfile> {
+    vec![
+        TuningProfile {
+            name: "opponent-timing-out",
+            description: "Games where an opponent keeps timing out, so we win by surviving",
+            applies_to: opponent_timing_out,
+            // A snake that isn't answering drives itself into a wall or starves soon enough, so
+            // there's nothing to gain from fighting it. The low exploration constant sticks to the
+            // lines that are safe, the longer rollouts check we stay safe long enough for it to
+            // die, and the extra health weight keeps us fed while we wait
+            mcts: SnakeOptions {
+                network_latency_padding: Duration::from_millis(120),
+                max_rollout_turns: 40,
+                ucb1_constant: 1.0,
+                ucb1_normal_constant: 16.0,
+                flood_fill_scores: Scores {
+                    food: 5,
+                    hazard: 1,
+                    empty: 5,
+                },
+                rollout_cache_turns: 3,
+                rollout_cache_size: 50_000,
+                max_tree_depth: None,
+                health_weight: 0.5,
+                root_noise: None,
+                temperature: 0.0,
+            },
+        },
+        TuningProfile {
+            name: "wrapped-constrictor",
+            description: "Wrapped constrictor games, like the community wrapped constrictor arena",
+            applies_to: is_wrapped_constrictor,
+            // Nobody ever gets hungry and there is no food to fight over, so health is left out of
+            // the rollouts and food cells count the same as empty ones. Everyone's tail stays
+            // where it is, so the board only fills up and the games are decided by who walls the
+            // other in. Longer rollouts see that happen, and the lower exploration constant keeps
+            // us on the lines that hold the most space
+            mcts: SnakeOptions {
+                network_latency_padding: Duration::from_millis(120),
+                max_rollout_turns: 40,
+                ucb1_constant: 1.5,
+                ucb1_normal_constant: 10.0,
+                flood_fill_scores: Scores {
+                    food: 5,
+                    hazard: 1,
+                    empty: 5,
+                },
+                rollout_cache_turns: 3,
+                rollout_cache_size: 50_000,
+                max_tree_depth: None,
+                health_weight: 0.0,
+                root_noise: None,
+                temperature: 0.0,
+            },
+        },
+        TuningProfile {
+            name: "wrapped-duel",
+            description: "Wrapped games with two snakes left, like the wrapped duels arena",
+            applies_to: is_wrapped_duel,
+            // With only two snakes the rollouts are cheap, so we can afford to play them out
+            // further. There are no walls to get trapped against, so we lean on exploiting the
+            // moves that are working and care more about open space than food
+            mcts: SnakeOptions {
+                network_latency_padding: Duration::from_millis(120),
+                max_rollout_turns: 40,
+                ucb1_constant: 1.5,
+                ucb1_normal_constant: 10.0,
+                flood_fill_scores: Scores {
+                    food: 3,
+                    hazard: 1,
+                    empty: 5,
+                },
+                rollout_cache_turns: 3,
+                rollout_cache_size: 50_000,
+                max_tree_depth: None,
+                health_weight: 0.25,
+                root_noise: None,
+                temperature: 0.0,
+            },
+        },
+        TuningProfile {
+            name: "default",
+            description: "Everything else",
+            applies_to: |_| true,
+            mcts: SnakeOptions::default(),
+        },
+    ]
+}
+
+/// The first profile from [profiles()] that applies to this game
+pub fn profile_for(game: &Game) -> TuningProfile {
+    profiles()
+        .into_iter()
+        .find(|p| (p.applies_to)(game))
+        .expect("The default profile applies to every game")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile_name(fixture: &'static str) -> &'static str {
+        let game: Game = serde_json::from_str(fixture).unwrap();
+
+        profile_for(&game).name
+    }
+
+    #[test]
+    fn test_wrapped_duels_use_the_duel_profile() {
+        assert_eq!(
+            profile_name(include_str!(
+                "../../fixtures/b6a045ae-abf2-4f6f-b04c-a80ace7881b4_399.json"
+            )),
+            "wrapped-duel"
+        );
+        assert_eq!(
+            profile_name(include_str!(
+                "../../fixtures/c2aee0d9-30dc-47ee-bd25-38e67e0fee9d_96.json"
+            )),
+            "wrapped-duel"
+        );
+        assert_eq!(
+            profile_name(include_str!(
+                "../../fixtures/4f198c01-d613-4109-b8b9-226208cde009_505.json"
+            )),
+            "wrapped-duel"
+        );
+    }
+
+    #[test]
+    fn test_wrapped_constrictor_games_use_their_own_profile() {
+        // Two snakes, which would otherwise be a wrapped duel
+        assert_eq!(
+            profile_name(include_str!(
+                "../../fixtures/wrapped_constrictor_edge_escape.json"
+            )),
+            "wrapped-constrictor"
+        );
+        assert_eq!(
+            profile_name(include_str!(
+                "../../fixtures/wrapped_constrictor_opening.json"
+            )),
+            "wrapped-constrictor"
+        );
+
+        let profile = profiles()
+            .into_iter()
+            .find(|p| p.name == "wrapped-constrictor")
+            .unwrap();
+        assert_eq!(profile.mcts.health_weight, 0.0);
+        assert_eq!(
+            profile.mcts.flood_fill_scores.food,
+            profile.mcts.flood_fill_scores.empty
+        );
+    }
+
+    #[test]
+    fn test_other_games_use_the_default_profile() {
+        // Wrapped, but with four snakes
+        assert_eq!(
+            profile_name(include_str!(
+                "../../fixtures/095b30fa-f2c7-4826-ac93-90b4dde6b785_5.json"
+            )),
+            "default"
+        );
+        // Two snakes, but standard
+        assert_eq!(
+            profile_name(include_str!(
+                "../../fixtures/7a02e19b-f658-4639-8ace-ece46629a6ed_192.json"
+            )),
+            "default"
+        );
+    }
+
+    #[test]
+    fn test_profile_names_are_unique() {
+        let mut names: Vec<_> = profiles().into_iter().map(|p| p.name).collect();
+        names.sort();
+        names.dedup();
+
+        assert_eq!(names.len(), profiles().len());
+    }
+}
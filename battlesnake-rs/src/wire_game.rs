@@ -0,0 +1,245 @@
+use std::collections::{HashMap, VecDeque};
+
+use battlesnake_game_types::wire_representation::{BattleSnake, Board, NestedGame, Position};
+
+use itertools::Itertools;
+
+use crate::*;
+
+/// Turn a board back into the `Game` JSON the engine sends, so tools built for the wire format can
+/// show it. Like the Battlesnake board viewer, or the fixtures our tests and benches load
+pub trait ToWireGame {
+    /// Build the wire game for this board
+    ///
+    /// `id_map` has to be the map the board was converted with, it's how we get the snakes'
+    /// string ids back. The boards don't know the game info or the turn, so those are passed in
+    /// too. Compact boards don't remember snake names or shouts either, so every snake is named
+    /// with its id and has no shout. Hazards are a yes or no for each cell on compact boards, so
+    /// stacked hazards come back as a single one
+    fn to_wire_game(
+        &self,
+        id_map: &HashMap<String, SnakeId>,
+        game_info: &NestedGame,
+        turn: i32,
+    ) -> Game;
+}
+
+/// The snakes in `game` that aren't in `id_map`, in the order they are on the board
+///
+/// Snake ids from [build_snake_id_map] depend on the order of the snakes in the request, so the
+/// map made at the start of a game is the one to keep using for it. That only works as long as the
+/// engine keeps sending the same snakes, and this finds the ones it didn't start with
+pub fn unknown_snake_ids<'a>(game: &'a Game, id_map: &HashMap<String, SnakeId>) -> Vec<&'a str> {
+    game.board
+        .snakes
+        .iter()
+        .map(|snake| snake.id.as_str())
+        .chain(std::iter::once(game.you.id.as_str()))
+        .filter(|id| !id_map.contains_key(*id))
+        .unique()
+        .collect()
+}
+
+/// The ruleset the community wrapped constrictor arena plays, which is wrapped and constrictor at
+/// the same time
+pub const WRAPPED_CONSTRICTOR: &str = "wrapped_constrictor";
+
+/// Whether the board wraps around at the edges, so we need the wrapped boards for it
+pub fn is_wrapped(game_info: &NestedGame) -> bool {
+    matches!(
+        game_info.ruleset.name.as_str(),
+        "wrapped" | WRAPPED_CONSTRICTOR
+    )
+}
+
+/// Whether snakes grow every turn and never lose health, so food doesn't matter
+pub fn is_constrictor(game_info: &NestedGame) -> bool {
+    matches!(
+        game_info.ruleset.name.as_str(),
+        "constrictor" | WRAPPED_CONSTRICTOR
+    )
+}
+
+impl<T> ToWireGame for T
+where
+    T: SnakeIDGettableGame<SnakeIDType = SnakeId>
+        + YouDeterminableGame
+        + SnakeBodyGettableGame
+        + HealthGettableGame
+        + FoodGettableGame
+        + HazardQueryableGame
+        + PositionGettableGame
+        + SizeDeterminableGame,
+{
+    fn to_wire_game(
+        &self,
+        id_map: &HashMap<String, SnakeId>,
+        game_info: &NestedGame,
+        turn: i32,
+    ) -> Game {
+        let mut ids: Vec<(&String, &SnakeId)> = id_map.iter().collect();
+        ids.sort_by_key(|(_, sid)| sid.as_usize());
+
+        let to_wire_snake = |id: &str, sid: &SnakeId| {
+            let body: VecDeque<Position> = self
+                .get_snake_body_vec(sid)
+                .into_iter()
+                .map(|pos| self.position_from_native(pos))
+                .collect();
+
+            BattleSnake {
+                id: id.to_owned(),
+                name: id.to_owned(),
+                head: body.front().copied().unwrap_or(Position { x: 0, y: 0 }),
+                actual_length: Some(body.len() as i32),
+                body,
+                health: self.get_health_i64(sid) as i32,
+                shout: None,
+            }
+        };
+
+        let snakes: Vec<BattleSnake> = ids
+            .iter()
+            .filter(|(_, sid)| self.is_alive(sid))
+            .map(|(id, sid)| to_wire_snake(id, sid))
+            .collect();
+
+        // If we're dead we aren't on the board anymore, but the engine still tells us about
+        // ourselves, so we do the same
+        let you = ids
+            .iter()
+            .find(|(_, sid)| *sid == self.you_id())
+            .map(|(id, sid)| to_wire_snake(id, sid))
+            .expect("The id map should have an id for every snake on the board");
+
+        let width = self.get_width() as i32;
+        let height = self.get_height() as i32;
+        let hazards = (0..height)
+            .flat_map(|y| (0..width).map(move |x| Position { x, y }))
+            .filter(|pos| self.is_hazard(&self.native_from_position(*pos)))
+            .collect();
+
+        let food = self
+            .get_all_food_as_native_positions()
+            .into_iter()
+            .map(|pos| self.position_from_native(pos))
+            .collect();
+
+        Game {
+            game: game_info.clone(),
+            turn,
+            you,
+            board: Board {
+                height: height as u32,
+                width: width as u32,
+                food,
+                hazards,
+                snakes,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use battlesnake_game_types::compact_representation::WrappedCellBoard4Snakes11x11;
+    use itertools::Itertools;
+
+    use super::*;
+
+    /// Everything about a snake the compact boards keep track of
+    fn snake_state(snake: &BattleSnake) -> (String, Vec<Position>, i32) {
+        (
+            snake.id.clone(),
+            snake.body.iter().copied().collect(),
+            snake.health,
+        )
+    }
+
+    fn assert_round_trips(original: Game, converted: Game) {
+        assert_eq!(converted.game.id, original.game.id);
+        assert_eq!(converted.turn, original.turn);
+        assert_eq!(converted.board.width, original.board.width);
+        assert_eq!(converted.board.height, original.board.height);
+        assert_eq!(snake_state(&converted.you), snake_state(&original.you));
+
+        let snakes = |game: &Game| {
+            game.board
+                .snakes
+                .iter()
+                .map(snake_state)
+                .sorted()
+                .collect_vec()
+        };
+        assert_eq!(snakes(&converted), snakes(&original));
+
+        let cells = |cells: &[Position]| cells.iter().copied().sorted().dedup().collect_vec();
+        assert_eq!(cells(&converted.board.food), cells(&original.board.food));
+        assert_eq!(
+            cells(&converted.board.hazards),
+            cells(&original.board.hazards)
+        );
+    }
+
+    #[test]
+    fn test_wrapped_constrictor_is_wrapped_and_constrictor() {
+        let game: Game = serde_json::from_str(include_str!(
+            "../../fixtures/wrapped_constrictor_opening.json"
+        ))
+        .unwrap();
+        assert!(is_wrapped(&game.game));
+        assert!(is_constrictor(&game.game));
+
+        let mut game_info = game.game;
+        game_info.ruleset.name = "wrapped".to_owned();
+        assert!(is_wrapped(&game_info));
+        assert!(!is_constrictor(&game_info));
+
+        game_info.ruleset.name = "constrictor".to_owned();
+        assert!(!is_wrapped(&game_info));
+        assert!(is_constrictor(&game_info));
+    }
+
+    #[test]
+    fn test_reordered_snakes_are_still_known() {
+        let start: Game =
+            serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap();
+        let id_map = build_snake_id_map(&start);
+
+        let mut reordered = start.clone();
+        reordered.board.snakes.reverse();
+        assert!(unknown_snake_ids(&reordered, &id_map).is_empty());
+
+        let mut renamed = start;
+        renamed.board.snakes[2].id = "newcomer".to_owned();
+        assert_eq!(unknown_snake_ids(&renamed, &id_map), vec!["newcomer"]);
+    }
+
+    #[test]
+    fn test_standard_board_round_trips() {
+        let original: Game =
+            serde_json::from_str(include_str!("../fixtures/start_of_game.json")).unwrap();
+        let id_map = build_snake_id_map(&original);
+        let board =
+            StandardCellBoard4Snakes11x11::convert_from_game(original.clone(), &id_map).unwrap();
+
+        let converted = board.to_wire_game(&id_map, &original.game, original.turn);
+
+        assert_round_trips(original, converted);
+    }
+
+    #[test]
+    fn test_wrapped_board_with_hazards_round_trips() {
+        let original: Game = serde_json::from_str(include_str!(
+            "../../fixtures/4f198c01-d613-4109-b8b9-226208cde009_505.json"
+        ))
+        .unwrap();
+        let id_map = build_snake_id_map(&original);
+        let board =
+            WrappedCellBoard4Snakes11x11::convert_from_game(original.clone(), &id_map).unwrap();
+
+        let converted = board.to_wire_game(&id_map, &original.game, original.turn);
+
+        assert_round_trips(original, converted);
+    }
+}
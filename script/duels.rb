@@ -28,17 +28,29 @@ end
 class Snake
   attr_reader :name
 
-  def initialize(name)
+  def initialize(name, url = nil)
     @name = name
+    @url = url
   end
 
   def url
-    "#{BASE_URL}/#{name}"
+    @url || "#{BASE_URL}/#{name}"
+  end
+
+  # Snakes can be given as `name` to use the default server, or as `name=url` to point at a
+  # different server. This lets us compare two configurations of the same snake, for example:
+  #
+  #   OPPONENT_DEPTH_REDUCTION=1 PORT=8001 cargo run --release --bin web-axum
+  #   ruby script/duels.rb hovering-hobbs reduced-hobbs=http://localhost:8001/hovering-hobbs
+  def self.parse(arg)
+    name, url = arg.split('=', 2)
+
+    new(name, url)
   end
 end
 
-snake_names = %w[hovering-hobbs devious-devin]
-snakes = snake_names.map { |n| Snake.new(n) }
+snake_cli_args = ARGV.empty? ? %w[hovering-hobbs devious-devin] : ARGV
+snakes = snake_cli_args.map { |arg| Snake.parse(arg) }
 
 snake_args = snakes.map { |s| "-n #{s.name} -u #{s.url}" }.join ' '
 
@@ -0,0 +1,100 @@
+use std::{fs::read_to_string, path::PathBuf, time::Instant};
+
+use battlesnake_rs::{
+    all_factories, hovering_hobbs, BattlesnakeAI, BattlesnakeFactory, BoxedFactory, Game,
+};
+use clap::{Parser, Subcommand};
+use color_eyre::eyre::{eyre, Result};
+use colored::Colorize;
+
+/// Run our snakes locally, without going through a server
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Ask a snake for its move on a fixture, and print how it picked it
+    Run(Run),
+}
+
+#[derive(clap::Args, Debug)]
+struct Run {
+    /// Name of the snake to run, the same as in its URL
+    #[clap(short, long, value_parser)]
+    snake: String,
+
+    /// Path to a game state, like the ones in `fixtures/`
+    #[clap(short, long, value_parser)]
+    fixture: PathBuf,
+
+    /// Move timeout in milliseconds to give the snake, instead of the one in the fixture. The snake
+    /// still keeps its usual padding for network latency out of this
+    #[clap(short, long, value_parser)]
+    budget_ms: Option<i64>,
+}
+
+/// Every snake we can run, including the ones that have their own routes in the server
+fn factories() -> Vec<BoxedFactory> {
+    let mut factories = all_factories();
+    factories.push(Box::new(hovering_hobbs::Factory));
+
+    factories
+}
+
+impl Run {
+    fn run(self) -> Result<()> {
+        let mut game: Game = serde_json::from_str(&read_to_string(&self.fixture)?)?;
+        if let Some(budget_ms) = self.budget_ms {
+            game.game.timeout = budget_ms;
+        }
+        let timeout = game.game.timeout;
+
+        let factory = factories()
+            .into_iter()
+            .find(|f| f.name() == self.snake)
+            .ok_or_else(|| eyre!("There is no snake named {}", self.snake))?;
+
+        let started_at = Instant::now();
+        let snake = factory.create_from_wire_game(game);
+        let explanation = snake.explain_move()?;
+        let elapsed = started_at.elapsed();
+
+        println!(
+            "{}",
+            format!("{} chose {}", self.snake, explanation.output.r#move).green()
+        );
+        for (m, score) in &explanation.root_options {
+            println!("  {m}: {score}");
+        }
+        if let Some(depth) = explanation.depth {
+            println!("Depth: {depth}");
+        }
+        if let Some(iterations) = explanation.iterations {
+            println!("Iterations: {iterations}");
+        }
+
+        let timing = format!("Took {}ms of the {timeout}ms timeout", elapsed.as_millis());
+        if elapsed.as_millis() > timeout.try_into().unwrap_or_default() {
+            println!("{}", timing.red());
+        } else {
+            println!("{timing}");
+        }
+
+        Ok(())
+    }
+}
+
+fn main() -> Result<()> {
+    color_eyre::install()?;
+    let args = Args::parse();
+
+    match args.command {
+        Command::Run(r) => r.run()?,
+    }
+
+    Ok(())
+}
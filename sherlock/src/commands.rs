@@ -1,15 +1,35 @@
 pub mod archive;
 pub mod archive_snake;
 pub mod archive_user;
+pub mod arena;
+pub mod compact;
+pub mod compare_maxn;
 pub mod fixture;
+pub mod fuzz;
+pub mod import;
+pub mod move_bias;
+pub mod opponent_book;
+pub mod ratings;
 pub mod replay;
+pub mod report;
+pub mod self_play;
 pub mod solve;
 
 use archive::Archive;
 use archive_snake::ArchiveSnake;
 use archive_user::ArchiveUser;
+use arena::Arena;
+use compact::Compact;
+use compare_maxn::CompareMaxn;
 use fixture::Fixture;
+use fuzz::Fuzz;
+use import::Import;
+use move_bias::MoveBias;
+use opponent_book::OpponentBookCommand;
+use ratings::RatingsCommand;
 use replay::Replay;
+use report::Report;
+use self_play::SelfPlay;
 use solve::Solve;
 
 use clap::Subcommand;
@@ -21,8 +41,37 @@ pub(crate) enum Command {
     Fixture(Fixture),
     Archive(Archive),
     Replay(Replay),
+    /// Have one of our snakes evaluate every turn of an archived game, and write it all to a
+    /// standalone HTML file with the boards, the moves and any blunders
+    Report(Report),
     ArchiveSnake(ArchiveSnake),
     ArchiveUser(ArchiveUser),
+    /// Archive a batch of games and record their outcomes in the local results database
+    Import(Import),
+    /// Play games locally with the official Battlesnake CLI and record their outcomes in the
+    /// results database
+    Arena(Arena),
+    /// Gzip and eventually prune the raw frames of old games, keeping a daily summary of the
+    /// pruned games, and drop duplicates from the results database
+    Compact(Compact),
+    /// Play every registered snake in random games against Bombastic Bob and Constant Carter,
+    /// saving any game state where a snake panics, times out or returns something that isn't a
+    /// move
+    Fuzz(Fuzz),
+    /// Sample the random reasonable moves our MCTS rollouts use on fixture boards, and show how
+    /// often each snake goes each way and how often it picks a move we know is fatal
+    MoveBias(MoveBias),
+    /// Have Improbable Irene play itself with root noise and a temperature, and write every move
+    /// out with the search's visits and who won, as training data
+    SelfPlay(SelfPlay),
+    /// Show the Elo rating of every snake in the results database
+    Ratings(RatingsCommand),
+    /// Build the opponent book Improbable Irene models her opponents with, from every game in the
+    /// archive that still has its frames
+    OpponentBook(OpponentBookCommand),
+    /// Search every 3 and 4 snake fixture with both Hobbs' paranoid search and maxn, and show
+    /// where they would move differently
+    CompareMaxn(CompareMaxn),
 }
 
 impl Command {
@@ -32,8 +81,18 @@ impl Command {
             Command::Fixture(f) => f.run()?,
             Command::Archive(a) => a.run()?,
             Command::Replay(r) => r.run()?,
+            Command::Report(r) => r.run()?,
             Command::ArchiveSnake(a) => a.run()?,
             Command::ArchiveUser(a) => a.run()?,
+            Command::Import(i) => i.run()?,
+            Command::Arena(a) => a.run()?,
+            Command::Compact(c) => c.run()?,
+            Command::Fuzz(f) => f.run()?,
+            Command::MoveBias(m) => m.run()?,
+            Command::SelfPlay(s) => s.run()?,
+            Command::Ratings(r) => r.run()?,
+            Command::OpponentBook(o) => o.run()?,
+            Command::CompareMaxn(c) => c.run()?,
         }
 
         Ok(())
@@ -1,11 +1,20 @@
-use std::{fs::File, io::Write, path::PathBuf};
+use std::{
+    fs::{read_to_string, remove_file, rename, File, OpenOptions},
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
 
+use battlesnake_rs::shouts::ShoutLog;
 use color_eyre::eyre::Result;
 use colored::Colorize;
+use flate2::{read::MultiGzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use ureq::Error;
 
-use crate::{unofficial_api::get_frames_for_game, websockets::get_raw_messages_from_game};
+use crate::{
+    unofficial_api::{get_frames, get_game_details, EngineApiError},
+    websockets::get_raw_messages_from_game,
+};
 
 #[derive(clap::Args, Debug)]
 pub(crate) struct Archive {
@@ -21,11 +30,122 @@ pub(crate) struct Archive {
 pub(crate) struct ArchiveShared {
     /// Directory to archive games to
     #[clap(short, long, value_parser, default_value = "archive")]
-    archive_dir: PathBuf,
+    pub(crate) archive_dir: PathBuf,
 
     /// Ignores local results and overwrite. Defaults to false
     #[clap(long, action, default_value = "false")]
-    force: bool,
+    pub(crate) force: bool,
+}
+
+/// How many turns of frames are fetched and written to the archive at a time
+const FRAMES_PER_CHUNK: usize = 200;
+
+/// Read a file from an archived game, even if it was archived gzipped or `sherlock compact` has
+/// since gzipped it
+pub(crate) fn read_archived(path: &Path) -> io::Result<String> {
+    match read_to_string(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let mut contents = String::new();
+            open_gzipped(path)?.read_to_string(&mut contents)?;
+
+            Ok(contents)
+        }
+        result => result,
+    }
+}
+
+/// Open the `.gz` next to `path`
+///
+/// The frames are archived a chunk at a time, each as its own gzip member, so this reads every
+/// member one after the other instead of stopping after the first
+fn open_gzipped(path: &Path) -> io::Result<impl BufRead> {
+    let mut gzipped = path.as_os_str().to_owned();
+    gzipped.push(".gz");
+
+    Ok(BufReader::new(MultiGzDecoder::new(File::open(gzipped)?)))
+}
+
+/// How far a download of the frames got, saved next to them so an interrupted archive can pick
+/// up where it left off
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct FramesProgress {
+    /// The first turn that isn't in the file yet
+    next_turn: usize,
+    /// How long the file is up to the end of the last chunk that was written completely
+    bytes: u64,
+}
+
+/// Writes a game's frames to `frames.jsonl.gz` as they're downloaded, a frame per line
+///
+/// They go to `frames.jsonl.gz.partial` until the last one is in, so nothing ever reads half a
+/// game. After every chunk the progress is saved to `frames.progress.json`, and if the download
+/// is interrupted the next run cuts off anything past the last complete chunk and carries on from
+/// there
+struct FramesWriter {
+    file: File,
+    partial_path: PathBuf,
+    progress_path: PathBuf,
+    progress: FramesProgress,
+}
+
+impl FramesWriter {
+    /// Pick up an interrupted download in `game_dir`, or start a new one when there isn't one or
+    /// `resume` is false
+    fn open(game_dir: &Path, resume: bool) -> Result<Self> {
+        let partial_path = game_dir.join("frames.jsonl.gz.partial");
+        let progress_path = game_dir.join("frames.progress.json");
+
+        let progress = match read_to_string(&progress_path) {
+            Ok(progress) if resume && partial_path.is_file() => serde_json::from_str(&progress)?,
+            _ => FramesProgress::default(),
+        };
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .open(&partial_path)?;
+        file.set_len(progress.bytes)?;
+        file.seek(SeekFrom::End(0))?;
+
+        Ok(Self {
+            file,
+            partial_path,
+            progress_path,
+            progress,
+        })
+    }
+
+    /// Add the frames for the turns up to `end_turn` to the file, as a gzip member of their own
+    fn append(&mut self, frames: &[Value], end_turn: usize) -> Result<()> {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        for frame in frames {
+            serde_json::to_writer(&mut encoder, frame)?;
+            encoder.write_all(b"\n")?;
+        }
+        let chunk = encoder.finish()?;
+
+        self.file.write_all(&chunk)?;
+        self.file.sync_data()?;
+
+        self.progress.next_turn = end_turn;
+        self.progress.bytes += chunk.len() as u64;
+        std::fs::write(&self.progress_path, serde_json::to_string(&self.progress)?)?;
+
+        Ok(())
+    }
+
+    /// Move the finished file to `frames.jsonl.gz`, replacing any frames archived before
+    fn finish(self, game_dir: &Path) -> Result<()> {
+        let plain_path = game_dir.join("frames.jsonl");
+        if plain_path.is_file() {
+            remove_file(plain_path)?;
+        }
+
+        rename(&self.partial_path, game_dir.join("frames.jsonl.gz"))?;
+        remove_file(&self.progress_path)?;
+
+        Ok(())
+    }
 }
 
 impl Archive {
@@ -52,47 +172,60 @@ impl Archive {
             format!("⏳ Archive in progress for {game_id}").yellow()
         );
 
-        let game_details_resp =
-            ureq::get(format!("https://engine.battlesnake.com/games/{game_id}").as_str()).call();
+        let game_details: Value = match get_game_details(&game_id) {
+            Ok(game_details) => game_details,
+            Err(EngineApiError::NotFound { .. }) => {
+                t.cursor_up()?;
+                t.delete_line()?;
+                println!(
+                    "{}",
+                    "❌ Game does not exist in engine (likely already deleted)".yellow()
+                );
 
-        let game_details: Value = match game_details_resp {
-            Ok(game_details) => game_details.into_json()?,
-            Err(Error::Status(code, response)) => {
-                if code == 404 {
-                    t.cursor_up()?;
-                    t.delete_line()?;
-                    println!(
-                        "{}",
-                        "❌ Game does not exist in engine (likely already deleted)".yellow()
-                    );
-
-                    return Ok(());
-                }
-
-                return Err(Error::Status(code, response).into());
+                return Ok(());
             }
             Err(e) => return Err(e.into()),
         };
 
         let last_turn = game_details["LastFrame"]["Turn"].as_i64().unwrap() as usize;
 
-        let frames = get_frames_for_game(&game_id, last_turn)?;
-
         std::fs::create_dir_all(game_dir.as_path())?;
 
-        // Archive the Info 'raw' from the API
+        // Archive the Frames 'raw' from the API, a chunk at a time so a long game never has to
+        // fit in memory
         {
-            let contents = serde_json::to_string(&game_details)?;
-            let mut file = File::create(game_info_path)?;
-            file.write_all(contents.as_bytes())?;
+            let mut writer = FramesWriter::open(&game_dir, !self.shared.force)?;
+            if writer.progress.next_turn > 0 {
+                println!(
+                    "⏩ Resuming from turn {} of {last_turn}",
+                    writer.progress.next_turn
+                );
+            }
+
+            while writer.progress.next_turn <= last_turn {
+                let start = writer.progress.next_turn;
+                let end = (start + FRAMES_PER_CHUNK).min(last_turn + 1);
+
+                writer.append(&get_frames(&game_id, start..end)?, end)?;
+            }
+
+            writer.finish(&game_dir)?;
         }
 
-        // Archive the Frames 'raw' from the API
+        // Archive what every snake shouted, so reports don't need the raw frames to show it
         {
-            let frame_document: Result<String, _> =
-                frames.iter().map(|g| serde_json::to_string(&g)).collect();
-            let mut file = File::create(game_dir.join("frames.jsonl"))?;
-            file.write_all(frame_document?.as_bytes())?;
+            let mut log = ShoutLog::default();
+            for line in open_gzipped(&game_dir.join("frames.jsonl"))?.lines() {
+                observe_shouts(&mut log, &serde_json::from_str(&line?)?);
+            }
+
+            let shouts: Result<String, _> = log
+                .into_shouts()
+                .iter()
+                .map(|s| serde_json::to_string(s).map(|line| line + "\n"))
+                .collect();
+            let mut file = File::create(game_dir.join("shouts.jsonl"))?;
+            file.write_all(shouts?.as_bytes())?;
         }
 
         // Archive the 'raw' WebSockets messages
@@ -104,6 +237,14 @@ impl Archive {
             file.write_all(document.as_bytes())?;
         }
 
+        // Archive the Info 'raw' from the API. This goes last, since a game without it counts as
+        // not archived yet
+        {
+            let contents = serde_json::to_string(&game_details)?;
+            let mut file = File::create(game_info_path)?;
+            file.write_all(contents.as_bytes())?;
+        }
+
         t.cursor_up()?;
         t.delete_line()?;
         println!(
@@ -114,3 +255,18 @@ impl Archive {
         Ok(())
     }
 }
+
+/// Add the shouts in one of a game's frames to the `log`. See [ShoutLog] for what counts as new
+fn observe_shouts(log: &mut ShoutLog, frame: &Value) {
+    let Some(turn) = frame["Turn"].as_i64().and_then(|t| t.try_into().ok()) else {
+        return;
+    };
+
+    for snake in frame["Snakes"].as_array().into_iter().flatten() {
+        let (Some(id), Some(name)) = (snake["ID"].as_str(), snake["Name"].as_str()) else {
+            continue;
+        };
+
+        log.observe(turn, id, name, snake["Shout"].as_str());
+    }
+}
@@ -0,0 +1,251 @@
+use std::{
+    fs::{create_dir_all, read_to_string},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    thread::sleep,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use color_eyre::eyre::{eyre, Result};
+use colored::Colorize;
+use itertools::Itertools;
+use serde::Deserialize;
+
+use crate::{
+    ratings::Ratings,
+    results::{GameResult, ResultsDatabase},
+};
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct Arena {
+    /// Snakes to play, given as `name=url`. Pass the same server more than once for self play.
+    /// With `--server` the url can be just a path on it, like `hobbs=/hovering-hobbs`
+    #[clap(
+        short,
+        long = "snake",
+        value_parser,
+        required_unless_present = "config"
+    )]
+    snakes: Vec<String>,
+
+    /// A JSON file listing more snakes to play, like `{"snakes": [{"name": "...", "url": "..."}]}`
+    ///
+    /// Ratings are tracked by name, so give each configuration you are tuning its own name
+    #[clap(short, long, value_parser)]
+    config: Option<PathBuf>,
+
+    /// Rulesets to play. Each ruleset gets its own set of games
+    #[clap(short, long = "ruleset", value_parser, default_value = "standard")]
+    rulesets: Vec<String>,
+
+    /// Map to play on, defaults to the engine's default for the ruleset
+    #[clap(short, long, value_parser)]
+    map: Option<String>,
+
+    /// Number of games to play for each ruleset
+    #[clap(short, long, value_parser, default_value = "10")]
+    games: usize,
+
+    #[clap(long, value_parser, default_value = "11")]
+    width: u32,
+
+    #[clap(long, value_parser, default_value = "11")]
+    height: u32,
+
+    /// Move timeout in milliseconds
+    #[clap(long, value_parser, default_value = "500")]
+    timeout: u32,
+
+    /// Path to the official Battlesnake CLI
+    #[clap(long, value_parser, default_value = "battlesnake")]
+    engine: PathBuf,
+
+    /// Directory to write the engine output and results database to
+    #[clap(short, long, value_parser, default_value = "archive")]
+    archive_dir: PathBuf,
+
+    /// A locally built snake server to start before the games and stop once they're done, like
+    /// `target/release/web-axum`
+    #[clap(long, value_parser)]
+    server: Option<PathBuf>,
+
+    /// The port to start `--server` on
+    #[clap(long, value_parser, default_value = "8000")]
+    port: u16,
+}
+
+/// How long the `--server` gets to start answering requests
+const SERVER_STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A snake server the arena started, which is stopped when this is dropped
+struct LocalServer {
+    child: Child,
+    url: String,
+}
+
+impl LocalServer {
+    /// Start the server on the port and wait until it answers
+    fn start(path: &Path, port: u16) -> Result<Self> {
+        let child = Command::new(path)
+            .env("PORT", port.to_string())
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|e| eyre!("Couldn't start {}: {e}", path.display()))?;
+        let mut server = Self {
+            child,
+            url: format!("http://localhost:{port}"),
+        };
+
+        let started_at = Instant::now();
+        while ureq::get(&format!("{}/", server.url)).call().is_err() {
+            if let Some(status) = server.child.try_wait()? {
+                return Err(eyre!("{} exited with {status}", path.display()));
+            }
+            if started_at.elapsed() > SERVER_STARTUP_TIMEOUT {
+                return Err(eyre!(
+                    "{} didn't answer on {} within {SERVER_STARTUP_TIMEOUT:?}",
+                    path.display(),
+                    server.url
+                ));
+            }
+            sleep(Duration::from_millis(250));
+        }
+
+        Ok(server)
+    }
+}
+
+impl Drop for LocalServer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// The snakes to play in an arena, as read from `--config`
+#[derive(Deserialize, Debug)]
+struct ArenaConfig {
+    snakes: Vec<ArenaSnake>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ArenaSnake {
+    name: String,
+    url: String,
+}
+
+impl Arena {
+    pub(crate) fn run(self) -> Result<()> {
+        let mut snakes = self
+            .snakes
+            .iter()
+            .map(|s| {
+                s.split_once('=')
+                    .map(|(name, url)| (name.to_string(), url.to_string()))
+                    .ok_or_else(|| eyre!("Snakes must be given as name=url, got {s}"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if let Some(config) = &self.config {
+            let config: ArenaConfig = serde_json::from_str(&read_to_string(config)?)?;
+            snakes.extend(config.snakes.into_iter().map(|s| (s.name, s.url)));
+        }
+
+        let server = self
+            .server
+            .as_deref()
+            .map(|path| {
+                println!(
+                    "{}",
+                    format!("⏳ Starting {} on port {}", path.display(), self.port).yellow()
+                );
+                LocalServer::start(path, self.port)
+            })
+            .transpose()?;
+        if let Some(server) = &server {
+            for (_, url) in &mut snakes {
+                if url.starts_with('/') {
+                    *url = format!("{}{url}", server.url);
+                }
+            }
+        } else if let Some((name, _)) = snakes.iter().find(|(_, url)| url.starts_with('/')) {
+            return Err(eyre!(
+                "{name} is only a path, which needs a --server to be on"
+            ));
+        }
+
+        let output_dir = self.archive_dir.join("arena");
+        create_dir_all(&output_dir)?;
+
+        let db = ResultsDatabase::for_archive_dir(&self.archive_dir);
+
+        for ruleset in &self.rulesets {
+            let mut results = Vec::with_capacity(self.games);
+
+            for i in 0..self.games {
+                let started_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+                let output_path = output_dir.join(format!("{ruleset}-{started_at}-{i}.jsonl"));
+
+                println!(
+                    "{}",
+                    format!("⏳ Playing {ruleset} game {} of {}", i + 1, self.games).yellow()
+                );
+
+                let mut command = Command::new(&self.engine);
+                command
+                    .arg("play")
+                    .args(["-W", &self.width.to_string()])
+                    .args(["-H", &self.height.to_string()])
+                    .args(["-t", &self.timeout.to_string()])
+                    .args(["-g", ruleset])
+                    .arg("--output")
+                    .arg(&output_path);
+                if let Some(map) = &self.map {
+                    command.args(["-m", map]);
+                }
+                for (name, url) in &snakes {
+                    command.args(["-n", name.as_str(), "-u", url.as_str()]);
+                }
+
+                let output = command.output()?;
+                if !output.status.success() {
+                    return Err(eyre!(
+                        "The engine exited with {}\n{}",
+                        output.status,
+                        String::from_utf8_lossy(&output.stderr)
+                    ));
+                }
+
+                let result = GameResult::from_cli_output(&read_to_string(&output_path)?)?;
+                println!(
+                    "{}",
+                    format!(
+                        "✔️ {} after {} turns",
+                        result
+                            .winner
+                            .as_ref()
+                            .map(|w| format!("{w} won"))
+                            .unwrap_or_else(|| "Draw".to_string()),
+                        result.turns
+                    )
+                    .green()
+                );
+                results.push(result);
+            }
+
+            let written = db.insert_all(&results)?;
+            println!(
+                "{}",
+                format!("✔️ Recorded {written} {ruleset} games in the results database").green()
+            );
+        }
+
+        // The ratings include every game in the database, not just this run, so the snakes are
+        // compared against everything they have played before too
+        let ratings = Ratings::from_results(&db.all()?);
+        let names = snakes.into_iter().map(|(name, _)| name).collect_vec();
+        println!();
+        ratings.print_standings(&names);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,320 @@
+use std::{
+    collections::BTreeMap,
+    fs::{read_dir, remove_file, File},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use color_eyre::eyre::Result;
+use colored::Colorize;
+use flate2::{write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    commands::archive::read_archived,
+    results::{GameResult, ResultsDatabase},
+};
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct Compact {
+    /// Directory the games were archived to
+    #[clap(short, long, value_parser, default_value = "archive")]
+    archive_dir: PathBuf,
+
+    /// Gzip the raw frames of games archived more than this many days ago
+    #[clap(long, value_parser, default_value_t = 7)]
+    gzip_after_days: u64,
+
+    /// Delete the raw frames of games archived more than this many days ago, after adding them
+    /// to the daily stats
+    #[clap(long, value_parser, default_value_t = 90)]
+    prune_after_days: u64,
+
+    /// Print what would be done without touching anything
+    #[clap(long, action, default_value = "false")]
+    dry_run: bool,
+}
+
+/// The raw files we keep for each archived game. The `info.json` is small and is all the results
+/// database needs, and `shouts.jsonl` is small and all that's left of the shouts once the frames
+/// are pruned, so neither is ever compacted
+const RAW_ARCHIVE_FILES: [&str; 2] = ["frames.jsonl", "websockets.jsonl"];
+
+/// One row of `daily_stats.jsonl`, which is what's left of a day's games once their raw frames
+/// have been pruned
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct DailyStats {
+    /// The day the games were archived, as `YYYY-MM-DD` in UTC
+    date: String,
+    games: usize,
+    turns: i64,
+    snakes: BTreeMap<String, SnakeDailyStats>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct SnakeDailyStats {
+    games: usize,
+    wins: usize,
+    /// How many times the snake died of each cause. Games from the arena don't know the cause, so
+    /// those are counted as `unknown`
+    deaths: BTreeMap<String, usize>,
+}
+
+impl DailyStats {
+    fn add(&mut self, result: &GameResult) {
+        self.games += 1;
+        self.turns += result.turns;
+
+        for snake in &result.snakes {
+            let stats = self.snakes.entry(snake.name.clone()).or_default();
+            stats.games += 1;
+            if result.winner.as_ref() == Some(&snake.name) {
+                stats.wins += 1;
+            }
+            if snake.death_turn.is_some() {
+                let cause = snake.death_cause.as_deref().unwrap_or("unknown");
+                *stats.deaths.entry(cause.to_owned()).or_default() += 1;
+            }
+        }
+    }
+}
+
+/// What should happen to a game's raw files
+enum Action {
+    Keep,
+    Gzip,
+    Prune,
+}
+
+impl Compact {
+    /// Compacts the archive so it doesn't outgrow the small volume it lives on
+    ///
+    /// Raw frames get gzipped once they're `--gzip-after-days` old, and deleted once they're
+    /// `--prune-after-days` old. Before a game's frames are deleted its outcome is added to
+    /// `daily_stats.jsonl`. Duplicate rows are also dropped from the results database. This is
+    /// safe to run as often as you like, so it's meant to be run from cron
+    pub(crate) fn run(self) -> Result<()> {
+        let now = SystemTime::now();
+        let stats_path = self.archive_dir.join("daily_stats.jsonl");
+        let mut stats = read_daily_stats(&stats_path)?;
+
+        let mut gzipped = 0;
+        let mut pruned = 0;
+
+        // Games archived from the engine, each in their own directory
+        for entry in read_dir(&self.archive_dir)? {
+            let game_dir = entry?.path();
+            let info_path = game_dir.join("info.json");
+            if !info_path.is_file() {
+                continue;
+            }
+
+            let raw_files: Vec<PathBuf> = RAW_ARCHIVE_FILES
+                .iter()
+                .flat_map(|name| [game_dir.join(name), game_dir.join(format!("{name}.gz"))])
+                .filter(|path| path.is_file())
+                .collect();
+            if raw_files.is_empty() {
+                continue;
+            }
+
+            match self.action_for(&info_path, now)? {
+                Action::Keep => {}
+                Action::Gzip => {
+                    for path in raw_files.iter().filter(|p| !is_gzipped(p)) {
+                        self.gzip(path)?;
+                        gzipped += 1;
+                    }
+                }
+                Action::Prune => {
+                    let result = GameResult::from_archive(&game_dir)?;
+                    self.prune(&raw_files, &result, &info_path, &mut stats)?;
+                    pruned += 1;
+                }
+            }
+        }
+
+        // Games from `sherlock arena`, where the whole game is in a single file
+        let arena_dir = self.archive_dir.join("arena");
+        if arena_dir.is_dir() {
+            for entry in read_dir(&arena_dir)? {
+                let path = entry?.path();
+                let is_engine_output = path.extension().map_or(false, |ext| ext == "jsonl");
+                if !is_engine_output && !is_gzipped(&path) {
+                    continue;
+                }
+
+                match self.action_for(&path, now)? {
+                    Action::Keep => {}
+                    Action::Gzip => {
+                        if !is_gzipped(&path) {
+                            self.gzip(&path)?;
+                            gzipped += 1;
+                        }
+                    }
+                    Action::Prune => {
+                        let plain_path = if is_gzipped(&path) {
+                            path.with_extension("")
+                        } else {
+                            path.clone()
+                        };
+                        let output = read_archived(&plain_path)?;
+                        let result = GameResult::from_cli_output(&output)?;
+                        self.prune(&[path.clone()], &result, &path, &mut stats)?;
+                        pruned += 1;
+                    }
+                }
+            }
+        }
+
+        let dropped_results = if self.dry_run {
+            0
+        } else {
+            ResultsDatabase::for_archive_dir(&self.archive_dir).compact()?
+        };
+
+        if !self.dry_run && pruned > 0 {
+            write_daily_stats(&stats_path, &stats)?;
+        }
+
+        println!(
+            "{}",
+            format!(
+                "✔️ Gzipped {gzipped} files, pruned {pruned} games and dropped {dropped_results} \
+                 duplicate results"
+            )
+            .green()
+        );
+
+        Ok(())
+    }
+
+    fn action_for(&self, path: &Path, now: SystemTime) -> Result<Action> {
+        let age = now
+            .duration_since(path.metadata()?.modified()?)
+            .unwrap_or_default();
+
+        Ok(if age > days(self.prune_after_days) {
+            Action::Prune
+        } else if age > days(self.gzip_after_days) {
+            Action::Gzip
+        } else {
+            Action::Keep
+        })
+    }
+
+    /// Replace the file with a gzipped copy, keeping its modified time so it still ages out
+    fn gzip(&self, path: &Path) -> Result<()> {
+        println!("{}", format!("🗜️ Gzipping {}", path.display()).dimmed());
+        if self.dry_run {
+            return Ok(());
+        }
+
+        let modified = path.metadata()?.modified()?;
+        let mut gzipped_path = path.as_os_str().to_owned();
+        gzipped_path.push(".gz");
+
+        let mut encoder = GzEncoder::new(File::create(&gzipped_path)?, Compression::default());
+        io::copy(&mut File::open(path)?, &mut encoder)?;
+        encoder.finish()?.set_modified(modified)?;
+        remove_file(path)?;
+
+        Ok(())
+    }
+
+    /// Fold the result into the stats for the day it was archived, then delete the raw files
+    fn prune(
+        &self,
+        raw_files: &[PathBuf],
+        result: &GameResult,
+        dated_by: &Path,
+        stats: &mut BTreeMap<String, DailyStats>,
+    ) -> Result<()> {
+        println!(
+            "{}",
+            format!("🧹 Pruning the raw frames for {}", result.game_id).dimmed()
+        );
+        if self.dry_run {
+            return Ok(());
+        }
+
+        let date = utc_date(dated_by.metadata()?.modified()?);
+        stats
+            .entry(date.clone())
+            .or_insert_with(|| DailyStats {
+                date,
+                ..Default::default()
+            })
+            .add(result);
+
+        for path in raw_files {
+            remove_file(path)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn is_gzipped(path: &Path) -> bool {
+    path.extension().map_or(false, |ext| ext == "gz")
+}
+
+fn days(n: u64) -> Duration {
+    Duration::from_secs(n * 24 * 60 * 60)
+}
+
+fn read_daily_stats(path: &Path) -> Result<BTreeMap<String, DailyStats>> {
+    if !path.is_file() {
+        return Ok(BTreeMap::new());
+    }
+
+    BufReader::new(File::open(path)?)
+        .lines()
+        .filter(|line| !matches!(line, Ok(l) if l.trim().is_empty()))
+        .map(|line| {
+            let stats: DailyStats = serde_json::from_str(&line?)?;
+            Ok((stats.date.clone(), stats))
+        })
+        .collect()
+}
+
+/// Rewrite the stats file, with one row per day in date order
+fn write_daily_stats(path: &Path, stats: &BTreeMap<String, DailyStats>) -> Result<()> {
+    let compacted_path = path.with_extension("jsonl.compacting");
+    {
+        let mut file = File::create(&compacted_path)?;
+        for day in stats.values() {
+            writeln!(file, "{}", serde_json::to_string(day)?)?;
+        }
+    }
+    std::fs::rename(&compacted_path, path)?;
+
+    Ok(())
+}
+
+/// Format the time as a `YYYY-MM-DD` date in UTC
+///
+/// This is the days to civil date algorithm from
+/// <http://howardhinnant.github.io/date_algorithms.html>, so we don't need a date library just
+/// for this
+fn utc_date(time: SystemTime) -> String {
+    let days_since_epoch = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+        / (24 * 60 * 60);
+
+    let z = days_since_epoch + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!("{year:04}-{month:02}-{day:02}")
+}
@@ -0,0 +1,151 @@
+use std::{path::PathBuf, time::Duration};
+
+use battlesnake_game_types::{
+    compact_representation::StandardCellBoard4Snakes11x11,
+    types::{build_snake_id_map, Move},
+    wire_representation::Game,
+};
+use battlesnake_minimax::{
+    maxn::MaxnSnake,
+    paranoid::{RootStrategy, SnakeOptions},
+    ParanoidMinimaxSnake,
+};
+use battlesnake_rs::{
+    board_only::you_on_board,
+    hovering_hobbs::{maxn_score, standard_score},
+    wire_game::is_wrapped,
+};
+use color_eyre::eyre::{eyre, Result};
+use colored::Colorize;
+
+use super::move_bias::fixture_paths;
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct CompareMaxn {
+    /// Fixture files to search, or directories of them
+    #[clap(value_parser, default_value = "fixtures")]
+    fixtures: Vec<PathBuf>,
+
+    /// How long each search gets on every board, in milliseconds
+    #[clap(long, value_parser, default_value_t = 200)]
+    budget_ms: i64,
+}
+
+/// What one of the searches made of a board
+struct Searched {
+    chosen: Option<Move>,
+    turns: usize,
+}
+
+impl CompareMaxn {
+    /// Searches every 3 or 4 snake fixture with both Hobbs' paranoid search and maxn, each with
+    /// the same time budget, and prints the move each of them would make. The boards where they
+    /// disagree are the ones to look at, to see whether paranoid is being too defensive
+    pub(crate) fn run(self) -> Result<()> {
+        let mut compared = 0;
+        let mut disagreed = 0;
+
+        for path in fixture_paths(&self.fixtures)? {
+            let mut game: Game = match std::fs::read_to_string(&path)
+                .map_err(|e| eyre!(e))
+                .and_then(|json| serde_json::from_str(&json).map_err(|e| eyre!(e)))
+            {
+                Ok(game) => game,
+                Err(e) => {
+                    println!("{}", format!("Skipping {}: {e}", path.display()).dimmed());
+                    continue;
+                }
+            };
+
+            // With two snakes maxn and paranoid are the same search, so only bigger games are
+            // worth comparing
+            let snakes = game.board.snakes.len();
+            if game.board.width != 11
+                || game.board.height != 11
+                || !(3..=4).contains(&snakes)
+                || is_wrapped(&game.game)
+                || !you_on_board(&game)
+            {
+                println!(
+                    "{}",
+                    format!(
+                        "Skipping {}: not a standard 11x11 game with 3 or 4 snakes",
+                        path.display()
+                    )
+                    .dimmed()
+                );
+                continue;
+            }
+
+            game.game.timeout = self.budget_ms;
+            let options = SnakeOptions {
+                network_latency_padding: Duration::ZERO,
+                // Maxn only ever searches one snake at a time, so paranoid does too
+                root_strategy: RootStrategy::Paranoid,
+                ..Default::default()
+            };
+
+            let id_map = build_snake_id_map(&game);
+            let game_info = game.game.clone();
+            let turn = game.turn;
+            let board = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map)
+                .map_err(|e| eyre!("Couldn't convert {}: {e}", path.display()))?;
+
+            let paranoid = ParanoidMinimaxSnake::new(
+                board,
+                game_info.clone(),
+                turn,
+                &standard_score,
+                "hovering-hobbs",
+                options.clone(),
+            )
+            .choose_move();
+            let paranoid = Searched {
+                chosen: paranoid.map(|(m, _)| m),
+                // The paranoid search counts its depth in levels of the tree, one for each snake
+                turns: paranoid.map_or(0, |(_, depth)| depth / snakes),
+            };
+
+            let maxn = MaxnSnake::new(
+                board,
+                game_info,
+                turn,
+                maxn_score::<_, _, 4>,
+                "maxn",
+                options,
+            )
+            .choose_move();
+            let maxn = Searched {
+                chosen: maxn.map(|(m, _)| m),
+                turns: maxn.map_or(0, |(_, turns)| turns),
+            };
+
+            compared += 1;
+            let line = format!(
+                "{}  paranoid {} ({} turns)  maxn {} ({} turns)",
+                path.display(),
+                describe(paranoid.chosen),
+                paranoid.turns,
+                describe(maxn.chosen),
+                maxn.turns,
+            );
+            if paranoid.chosen == maxn.chosen {
+                println!("{line}");
+            } else {
+                disagreed += 1;
+                println!("{}", line.yellow());
+            }
+        }
+
+        println!(
+            "{}",
+            format!("Paranoid and maxn disagreed on {disagreed} of {compared} boards").yellow()
+        );
+
+        Ok(())
+    }
+}
+
+fn describe(chosen: Option<Move>) -> String {
+    chosen.map_or_else(|| "nothing".to_owned(), |m| m.to_string())
+}
@@ -1,9 +1,16 @@
-use std::fs::File;
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::Write,
+    ops::Range,
+};
 
-use color_eyre::eyre::Result;
+use battlesnake_game_types::wire_representation::Game;
+use color_eyre::eyre::{eyre, Result};
+use serde::Serialize;
 use serde_json::Value;
 
-use crate::unofficial_api::{frame_to_game, get_frame_for_turn};
+use crate::unofficial_api::{frame_to_game, get_frame_for_turn, get_frames, get_game_details};
 
 #[derive(clap::Args, Debug)]
 pub struct Fixture {
@@ -16,26 +23,138 @@ pub struct Fixture {
     you_name: String,
 
     /// Turn to make a fixture for
-    #[clap(short, long, value_parser)]
+    #[clap(
+        short,
+        long,
+        value_parser,
+        required_unless_present = "turns",
+        conflicts_with = "turns"
+    )]
+    turn: Option<i32>,
+
+    /// Range of turns to make fixtures for, like `10..20` or `10..=20`
+    #[clap(long, value_parser = parse_turn_range)]
+    turns: Option<Range<i32>>,
+
+    /// Rename every snake except "you", so the fixtures don't include other competitors' snake
+    /// names
+    #[clap(long, value_parser)]
+    anonymize: bool,
+}
+
+fn parse_turn_range(s: &str) -> Result<Range<i32>, String> {
+    let (start, end, inclusive) = if let Some((start, end)) = s.split_once("..=") {
+        (start, end, true)
+    } else if let Some((start, end)) = s.split_once("..") {
+        (start, end, false)
+    } else {
+        return Err(format!("Expected a range like 10..20, got {s}"));
+    };
+
+    let start: i32 = start.parse().map_err(|e| format!("Bad start turn: {e}"))?;
+    let end: i32 = end.parse().map_err(|e| format!("Bad end turn: {e}"))?;
+    let end = if inclusive { end + 1 } else { end };
+
+    if end <= start {
+        return Err(format!("The range {s} doesn't include any turns"));
+    }
+
+    Ok(start..end)
+}
+
+/// One line in `fixtures/manifest.jsonl`, describing where a fixture came from
+#[derive(Serialize, Debug)]
+struct ManifestEntry {
+    file: String,
+    game_id: String,
     turn: i32,
+    ruleset: String,
+    map: Option<String>,
+    anonymized: bool,
+}
+
+/// Gives every snake other than "you" a stable made up name, so the same snake has the same name
+/// in every fixture from a game
+#[derive(Debug, Default)]
+struct Anonymizer {
+    names: HashMap<String, String>,
+}
+
+impl Anonymizer {
+    fn anonymize(&mut self, game: &mut Game) {
+        let you_id = game.you.id.clone();
+
+        for snake in game.board.snakes.iter_mut() {
+            if snake.id == you_id {
+                continue;
+            }
+
+            let next_name = format!("snake-{}", self.names.len() + 1);
+            snake.name = self
+                .names
+                .entry(snake.id.clone())
+                .or_insert(next_name)
+                .clone();
+            snake.shout = None;
+        }
+    }
 }
 
 impl Fixture {
     pub fn run(self) -> Result<()> {
-        let game_id = self.game_id;
-        let turn = self.turn;
+        let game_id = &self.game_id;
+
+        let body = get_game_details(game_id)?;
+
+        let frames = match (self.turn, &self.turns) {
+            (Some(turn), _) => vec![get_frame_for_turn(game_id, turn)?],
+            (None, Some(turns)) => get_frames_in_range(game_id, turns.clone())?,
+            (None, None) => return Err(eyre!("Either --turn or --turns is required")),
+        };
 
-        let body: Value =
-            ureq::get(format!("https://engine.battlesnake.com/games/{game_id}").as_str())
-                .call()?
-                .into_json()?;
+        let mut anonymizer = Anonymizer::default();
+        let mut manifest = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open("./fixtures/manifest.jsonl")?;
 
-        let frame = get_frame_for_turn(&game_id, self.turn)?;
-        let wire_game = frame_to_game(&frame, &body["Game"], &self.you_name).unwrap();
+        for frame in frames {
+            let mut wire_game = frame_to_game(&frame, &body["Game"], &self.you_name)?;
+            if self.anonymize {
+                anonymizer.anonymize(&mut wire_game);
+            }
 
-        let file = File::create(format!("./fixtures/{game_id}_{turn}.json"))?;
-        serde_json::to_writer_pretty(file, &wire_game)?;
+            let turn = wire_game.turn;
+            let file_name = format!("{game_id}_{turn}.json");
+            let file = File::create(format!("./fixtures/{file_name}"))?;
+            serde_json::to_writer_pretty(file, &wire_game)?;
+
+            let entry = ManifestEntry {
+                file: file_name,
+                game_id: game_id.clone(),
+                turn,
+                ruleset: wire_game.game.ruleset.name.clone(),
+                map: wire_game.game.map.clone(),
+                anonymized: self.anonymize,
+            };
+            writeln!(manifest, "{}", serde_json::to_string(&entry)?)?;
+        }
 
         Ok(())
     }
 }
+
+fn get_frames_in_range(game_id: &str, turns: Range<i32>) -> Result<Vec<Value>> {
+    let start: usize = turns.start.try_into()?;
+    let end: usize = turns.end.try_into()?;
+
+    let frames = get_frames(game_id, start..end)?;
+
+    if frames.is_empty() {
+        return Err(eyre!(
+            "The game doesn't have any frames for turns {turns:?}"
+        ));
+    }
+
+    Ok(frames)
+}
@@ -0,0 +1,924 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{create_dir_all, File},
+    panic::{catch_unwind, AssertUnwindSafe},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use battlesnake_game_types::{
+    compact_representation::{
+        dimensions::Custom, StandardCellBoard4Snakes11x11, WrappedCellBoard,
+        WrappedCellBoard4Snakes11x11,
+    },
+    types::{
+        build_snake_id_map, HealthGettableGame, Move, PositionGettableGame, SimulableGame,
+        SnakeBodyGettableGame, SnakeIDGettableGame, SnakeId,
+    },
+    wire_representation::{BattleSnake, Board, Game, NestedGame, Position, Ruleset, Settings},
+};
+use battlesnake_minimax::Instruments;
+use battlesnake_rs::{
+    all_factories,
+    bombastic_bob::BombasticBobFactory,
+    constant_carter::ConstantCarterFactory,
+    extra_settings::{self, ExtraSettings},
+    BattlesnakeAI, BattlesnakeFactory, BoxedFactory,
+};
+use color_eyre::eyre::{eyre, Result};
+use colored::Colorize;
+use itertools::Itertools;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct Fuzz {
+    /// Only fuzz the snakes with these names, defaults to every registered snake
+    #[clap(short, long = "snake", value_parser)]
+    snakes: Vec<String>,
+
+    /// Number of random games to play for each snake
+    #[clap(short, long, value_parser, default_value = "20")]
+    games: usize,
+
+    /// The longest we let a single game go on for
+    #[clap(long, value_parser, default_value = "100")]
+    max_turns: i32,
+
+    /// Move timeout in milliseconds, every snake needs to respond within this
+    #[clap(long, value_parser, default_value = "500")]
+    timeout: u64,
+
+    /// Seed for the random games, so a failing run can be repeated
+    #[clap(long, value_parser)]
+    seed: Option<u64>,
+
+    /// Directory to write the shrunk failing game states to
+    #[clap(short, long, value_parser, default_value = "fixtures/fuzz")]
+    output_dir: PathBuf,
+
+    /// Only generate games with these rulesets, defaults to all of them
+    #[clap(long = "ruleset", value_enum)]
+    rulesets: Vec<FuzzRuleset>,
+}
+
+/// The kinds of games we generate, the ones our snakes actually get played in
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum FuzzRuleset {
+    /// 11x11 standard games
+    Standard,
+    /// 11x11 games where the edges of the board wrap around
+    Wrapped,
+    /// 11x11 standard games with hazards closing in from the edges
+    Royale,
+    /// The 19x21 arcade maze, a wrapped board whose walls are hazards that kill on contact
+    ArcadeMaze,
+}
+
+impl FuzzRuleset {
+    const ALL: [FuzzRuleset; 4] = [
+        FuzzRuleset::Standard,
+        FuzzRuleset::Wrapped,
+        FuzzRuleset::Royale,
+        FuzzRuleset::ArcadeMaze,
+    ];
+}
+
+const STARTING_LENGTH: usize = 3;
+const FOOD_SPAWN_CHANCE: f64 = 0.15;
+const ROYALE_HAZARD_DAMAGE: i32 = 14;
+const ROYALE_SHRINK_EVERY_N_TURNS: i32 = 25;
+const ARCADE_MAZE_HAZARD_DAMAGE: i32 = 100;
+
+/// The walls of the arcade maze are the hazards of a real game played on it
+const ARCADE_MAZE_FIXTURE: &str = include_str!("../../../fixtures/arcade_maze_should_win.json");
+
+impl Fuzz {
+    /// Every registered snake plays random games as the first snake on the board, against
+    /// opponents driven by Bombastic Bob and Constant Carter. Those two are simple enough that we
+    /// trust them to keep the games moving, so any invariant that breaks is the snake under test's
+    /// fault
+    pub(crate) fn run(self) -> Result<()> {
+        let seed = self.seed.unwrap_or_else(rand::random);
+        println!("{}", format!("🎲 Fuzzing with seed {seed}").yellow());
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let factories: Vec<BoxedFactory> = all_factories()
+            .into_iter()
+            .filter(|f| self.snakes.is_empty() || self.snakes.contains(&f.name()))
+            .collect();
+        if factories.is_empty() {
+            return Err(eyre!("None of the snakes {:?} are registered", self.snakes));
+        }
+
+        let rulesets = if self.rulesets.is_empty() {
+            FuzzRuleset::ALL.to_vec()
+        } else {
+            self.rulesets.clone()
+        };
+
+        let mut failures = 0;
+        for factory in &factories {
+            for i in 0..self.games {
+                let ruleset = rulesets[i % rulesets.len()];
+                let game_id = format!("fuzz-{seed}-{i}");
+                let game = random_game(&mut rng, game_id, self.timeout, ruleset);
+
+                if let Some(failure) = self.play(factory.as_ref(), game, &mut rng)? {
+                    failures += 1;
+                    let path = self.persist(factory.as_ref(), &failure)?;
+
+                    println!(
+                        "{}",
+                        format!(
+                            "❌ {} {} on turn {}, wrote the shrunk game to {}",
+                            factory.name(),
+                            failure.reason,
+                            failure.game.turn,
+                            path.display()
+                        )
+                        .red()
+                    );
+                }
+            }
+
+            println!(
+                "{}",
+                format!("✅ Finished fuzzing {}", factory.name()).green()
+            );
+        }
+
+        if failures > 0 {
+            return Err(eyre!("Found {failures} failing games"));
+        }
+
+        Ok(())
+    }
+
+    /// Play a single game, stopping at the first turn that breaks an invariant
+    fn play(
+        &self,
+        factory: &dyn BattlesnakeFactory,
+        mut game: Game,
+        rng: &mut StdRng,
+    ) -> Result<Option<Failure>> {
+        let oracles: HashMap<String, Box<dyn BattlesnakeFactory>> = game.board.snakes[1..]
+            .iter()
+            .map(|snake| {
+                // Bob is seeded from our RNG so the whole game can be replayed from the seed
+                let oracle: Box<dyn BattlesnakeFactory> = if rng.gen_bool(0.5) {
+                    Box::new(BombasticBobFactory::seeded(rng.gen_range(0..=u64::MAX)))
+                } else {
+                    Box::new(ConstantCarterFactory::new())
+                };
+
+                (snake.id.clone(), oracle)
+            })
+            .collect();
+        let timeout = Duration::from_millis(self.timeout);
+
+        while game.turn < self.max_turns && game.board.snakes.len() > 1 {
+            let my_move = match check_invariants(factory, &game, timeout) {
+                Ok(m) => m,
+                Err(reason) => {
+                    return Ok(Some(Failure {
+                        game: shrink(factory, game, timeout),
+                        reason,
+                    }))
+                }
+            };
+
+            let mut moves = vec![my_move];
+            for opponent in &game.board.snakes[1..] {
+                let oracle = &oracles[&opponent.id];
+                let opponent_game = Game {
+                    you: opponent.clone(),
+                    ..game.clone()
+                };
+
+                let m = check_invariants(oracle.as_ref(), &opponent_game, timeout)
+                    .map_err(|reason| eyre!("The {} oracle {reason}", oracle.name()))?;
+                moves.push(m);
+            }
+
+            let before = game.clone();
+            advance(&mut game, &moves);
+            if let Err(reason) = compare_with_compact(&before, &moves, &game) {
+                let moves = moves.iter().map(|m| m.to_string()).join(", ");
+                return Ok(Some(Failure {
+                    game: before,
+                    reason: format!("diverged from the compact simulation of [{moves}]: {reason}"),
+                }));
+            }
+            spawn(&mut game, rng);
+
+            if !game.board.snakes.iter().any(|s| s.id == game.you.id) {
+                break;
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn persist(&self, factory: &dyn BattlesnakeFactory, failure: &Failure) -> Result<PathBuf> {
+        create_dir_all(&self.output_dir)?;
+
+        let path = self.output_dir.join(format!(
+            "{}_{}_{}.json",
+            factory.name(),
+            failure.game.game.id,
+            failure.game.turn
+        ));
+        serde_json::to_writer_pretty(File::create(&path)?, &failure.game)?;
+
+        Ok(path)
+    }
+}
+
+/// A game state that made a snake break one of our invariants
+struct Failure {
+    game: Game,
+    reason: String,
+}
+
+/// Ask the snake for a move, making sure it doesn't panic, returns a real direction and responds
+/// within the timeout
+fn check_invariants(
+    factory: &dyn BattlesnakeFactory,
+    game: &Game,
+    timeout: Duration,
+) -> Result<Move, String> {
+    let started_at = Instant::now();
+    let output = catch_unwind(AssertUnwindSafe(|| {
+        factory.create_from_wire_game(game.clone()).make_move()
+    }));
+    let elapsed = started_at.elapsed();
+
+    let output = match output {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => return Err(format!("returned an error: {e}")),
+        Err(_) => return Err("panicked".to_owned()),
+    };
+
+    if elapsed > timeout {
+        return Err(format!(
+            "took {}ms which is over the {}ms timeout",
+            elapsed.as_millis(),
+            timeout.as_millis()
+        ));
+    }
+
+    Move::all_iter()
+        .find(|m| m.to_string() == output.r#move)
+        .ok_or_else(|| format!("returned {:?} which isn't a move", output.r#move))
+}
+
+/// Make the failing game as small as we can while it still fails, by removing opponents, food and
+/// hazards and shortening snakes one piece at a time
+fn shrink(factory: &dyn BattlesnakeFactory, mut game: Game, timeout: Duration) -> Game {
+    let still_fails = |g: &Game| check_invariants(factory, g, timeout).is_err();
+
+    loop {
+        let smaller = candidates(&game).into_iter().find(|g| still_fails(g));
+
+        match smaller {
+            Some(smaller) => game = smaller,
+            None => return game,
+        }
+    }
+}
+
+/// Every game that is one step smaller than the given one
+fn candidates(game: &Game) -> Vec<Game> {
+    let mut candidates = vec![];
+
+    for i in 1..game.board.snakes.len() {
+        let mut smaller = game.clone();
+        smaller.board.snakes.remove(i);
+        candidates.push(smaller);
+    }
+
+    for i in 0..game.board.food.len() {
+        let mut smaller = game.clone();
+        smaller.board.food.remove(i);
+        candidates.push(smaller);
+    }
+
+    for i in 0..game.board.hazards.len() {
+        let mut smaller = game.clone();
+        smaller.board.hazards.remove(i);
+        candidates.push(smaller);
+    }
+
+    for i in 0..game.board.snakes.len() {
+        if game.board.snakes[i].body.len() > 1 {
+            let mut smaller = game.clone();
+            let snake = &mut smaller.board.snakes[i];
+            snake.body.pop_back();
+            snake.actual_length = Some(snake.body.len() as i32);
+            smaller.you = smaller.board.snakes[0].clone();
+            candidates.push(smaller);
+        }
+    }
+
+    candidates
+}
+
+pub(crate) fn random_game(
+    rng: &mut StdRng,
+    id: String,
+    timeout: u64,
+    ruleset: FuzzRuleset,
+) -> Game {
+    let (width, height): (i32, i32) = match ruleset {
+        FuzzRuleset::ArcadeMaze => (19, 21),
+        _ => (11, 11),
+    };
+    let hazards = match ruleset {
+        FuzzRuleset::ArcadeMaze => arcade_maze_walls(),
+        _ => vec![],
+    };
+
+    let mut cells: Vec<Position> = (0..width)
+        .flat_map(|x| (0..height).map(move |y| Position { x, y }))
+        .filter(|p| !hazards.contains(p))
+        .collect();
+    cells.shuffle(rng);
+
+    let snake_count = rng.gen_range(2..=4);
+    let snakes: Vec<BattleSnake> = cells
+        .drain(..snake_count)
+        .enumerate()
+        .map(|(i, head)| BattleSnake {
+            id: format!("snake-{i}"),
+            name: format!("snake-{i}"),
+            head,
+            body: VecDeque::from(vec![head; STARTING_LENGTH]),
+            health: 100,
+            shout: None,
+            actual_length: Some(STARTING_LENGTH as i32),
+        })
+        .collect();
+    let food = cells.drain(..snake_count).collect();
+
+    let (name, map, hazard_damage_per_turn) = match ruleset {
+        FuzzRuleset::Standard => ("standard", None, 0),
+        FuzzRuleset::Wrapped => ("wrapped", None, 0),
+        FuzzRuleset::Royale => ("royale", None, ROYALE_HAZARD_DAMAGE),
+        FuzzRuleset::ArcadeMaze => (
+            "wrapped",
+            Some("arcade_maze".to_owned()),
+            ARCADE_MAZE_HAZARD_DAMAGE,
+        ),
+    };
+
+    let mut game = Game {
+        turn: 0,
+        game: NestedGame {
+            id,
+            map,
+            source: Some("fuzz".to_owned()),
+            timeout: timeout as i64,
+            ruleset: Ruleset {
+                name: name.to_owned(),
+                version: "fuzz".to_owned(),
+                settings: Some(Settings {
+                    food_spawn_chance: (FOOD_SPAWN_CHANCE * 100.0) as i32,
+                    minimum_food: 1,
+                    hazard_damage_per_turn,
+                    hazard_map: None,
+                    hazard_map_author: None,
+                    royale: None,
+                }),
+            },
+        },
+        you: snakes[0].clone(),
+        board: Board {
+            height: height as u32,
+            width: width as u32,
+            food,
+            hazards,
+            snakes,
+        },
+    };
+
+    // Start royale games part of the way through, so the snakes see the hazards from the first turn
+    if ruleset == FuzzRuleset::Royale {
+        extra_settings::observe(
+            &game.game.id,
+            ExtraSettings {
+                shrink_every_n_turns: Some(ROYALE_SHRINK_EVERY_N_TURNS),
+                ..Default::default()
+            },
+        );
+        for _ in 0..rng.gen_range(1..=3) {
+            shrink_royale(&mut game, rng);
+        }
+    }
+
+    game
+}
+
+/// The hazards that make up the walls of the arcade maze
+fn arcade_maze_walls() -> Vec<Position> {
+    let game: Game =
+        serde_json::from_str(ARCADE_MAZE_FIXTURE).expect("The arcade maze fixture is a valid game");
+
+    game.board.hazards
+}
+
+fn is_wrapped(game: &Game) -> bool {
+    battlesnake_rs::wire_game::is_wrapped(&game.game)
+}
+
+/// Cover the next row or column in from a random edge with hazards, like the royale map does every
+/// few turns
+fn shrink_royale(game: &mut Game, rng: &mut StdRng) {
+    let width = game.board.width as i32;
+    let height = game.board.height as i32;
+    let is_hazard = |p: &Position| game.board.hazards.contains(p);
+
+    let lines: [Vec<Position>; 4] = [
+        (0..width)
+            .filter_map(|x| {
+                (0..height)
+                    .map(|y| Position { x, y })
+                    .find(|p| !is_hazard(p))
+            })
+            .collect(),
+        (0..width)
+            .filter_map(|x| {
+                (0..height)
+                    .rev()
+                    .map(|y| Position { x, y })
+                    .find(|p| !is_hazard(p))
+            })
+            .collect(),
+        (0..height)
+            .filter_map(|y| {
+                (0..width)
+                    .map(|x| Position { x, y })
+                    .find(|p| !is_hazard(p))
+            })
+            .collect(),
+        (0..height)
+            .filter_map(|y| {
+                (0..width)
+                    .rev()
+                    .map(|x| Position { x, y })
+                    .find(|p| !is_hazard(p))
+            })
+            .collect(),
+    ];
+
+    if let Some(line) = lines.choose(rng) {
+        game.board.hazards.extend(line);
+    }
+}
+
+/// Advance the game a turn, where `moves` lines up with the snakes on the board
+pub(crate) fn step(game: &mut Game, moves: &[Move], rng: &mut StdRng) {
+    advance(game, moves);
+    spawn(game, rng);
+}
+
+/// Everything about a turn that doesn't involve chance, so it can be compared against the compact
+/// simulations. This follows the order of the official rules: move, reduce health, hazard damage,
+/// feed and then eliminate
+fn advance(game: &mut Game, moves: &[Move]) {
+    let width = game.board.width as i32;
+    let height = game.board.height as i32;
+    let wrapped = is_wrapped(game);
+    let hazard_damage = game
+        .game
+        .ruleset
+        .settings
+        .as_ref()
+        .map_or(0, |s| s.hazard_damage_per_turn);
+
+    for (snake, m) in game.board.snakes.iter_mut().zip(moves) {
+        let (dx, dy) = match m {
+            Move::Up => (0, 1),
+            Move::Down => (0, -1),
+            Move::Left => (-1, 0),
+            Move::Right => (1, 0),
+        };
+        let (mut x, mut y) = (snake.head.x + dx, snake.head.y + dy);
+        if wrapped {
+            x = x.rem_euclid(width);
+            y = y.rem_euclid(height);
+        }
+        snake.head = Position { x, y };
+        snake.body.push_front(snake.head);
+        snake.body.pop_back();
+        snake.health -= 1;
+    }
+
+    // Snakes the hazards finish off are eliminated right away, so they don't get to eat
+    let mut killed_by_hazards = vec![];
+    for snake in game.board.snakes.iter_mut() {
+        // Eating food in a hazard saves the snake from the damage
+        if game.board.food.contains(&snake.head) {
+            continue;
+        }
+
+        let stacked = game
+            .board
+            .hazards
+            .iter()
+            .filter(|h| **h == snake.head)
+            .count() as i32;
+        if stacked > 0 {
+            snake.health = (snake.health - stacked * hazard_damage).max(0);
+            if snake.health == 0 {
+                killed_by_hazards.push(snake.id.clone());
+            }
+        }
+    }
+
+    for snake in game.board.snakes.iter_mut() {
+        if killed_by_hazards.contains(&snake.id) {
+            continue;
+        }
+        if let Some(i) = game.board.food.iter().position(|f| *f == snake.head) {
+            game.board.food.remove(i);
+            snake.health = 100;
+            snake.body.push_back(*snake.body.back().unwrap());
+        }
+        snake.actual_length = Some(snake.body.len() as i32);
+    }
+
+    // Snakes that starved or left the board are gone before anyone can collide with them
+    game.board.snakes.retain(|snake| {
+        let in_bounds = (0..width).contains(&snake.head.x) && (0..height).contains(&snake.head.y);
+
+        in_bounds && snake.health > 0
+    });
+
+    let before = game.board.snakes.clone();
+    game.board.snakes.retain(|snake| {
+        let hit_body = before
+            .iter()
+            .any(|other| other.body.iter().skip(1).any(|p| *p == snake.head));
+        let lost_head_to_head = before.iter().any(|other| {
+            other.id != snake.id && other.head == snake.head && other.body.len() >= snake.body.len()
+        });
+
+        !hit_body && !lost_head_to_head
+    });
+
+    if let Some(me) = game.board.snakes.iter().find(|s| s.id == game.you.id) {
+        game.you = me.clone();
+    }
+}
+
+/// The part of a turn that is left to chance: spawning food and closing in the royale hazards
+fn spawn(game: &mut Game, rng: &mut StdRng) {
+    let width = game.board.width as i32;
+    let height = game.board.height as i32;
+
+    if game.board.food.is_empty() || rng.gen_bool(FOOD_SPAWN_CHANCE) {
+        // The arcade maze never spawns food in its walls, the other maps can spawn it anywhere
+        let avoid_hazards = game.game.map.is_some();
+        let empty: Vec<Position> = (0..width)
+            .flat_map(|x| (0..height).map(move |y| Position { x, y }))
+            .filter(|p| !game.board.food.contains(p))
+            .filter(|p| !avoid_hazards || !game.board.hazards.contains(p))
+            .filter(|p| game.board.snakes.iter().all(|s| !s.body.contains(p)))
+            .collect();
+        if let Some(p) = empty.choose(rng) {
+            game.board.food.push(*p);
+        }
+    }
+
+    game.turn += 1;
+    let shrink_every_n_turns = extra_settings::for_game(&game.game).shrink_every_n_turns();
+    if game.game.ruleset.name == "royale" && game.turn % shrink_every_n_turns == 0 {
+        shrink_royale(game, rng);
+    }
+}
+
+/// Simulate the same moves on the compact representation our snakes would search this game with,
+/// and check it ends up with the same snakes as our rules did. Food spawns and new hazards are left
+/// out, since the compact boards never simulate those
+fn compare_with_compact(before: &Game, moves: &[Move], after: &Game) -> Result<(), String> {
+    let id_map = build_snake_id_map(before);
+
+    if before.is_arcade_maze_map() {
+        let board: WrappedCellBoard<u16, Custom, { 19 * 21 }, 4> = before
+            .as_wrapped_cell_board(&id_map)
+            .map_err(|e| format!("couldn't be converted to a compact board: {e}"))?;
+
+        compare_simulation(board, &id_map, before, moves, after)
+    } else if is_wrapped(before) {
+        let board = WrappedCellBoard4Snakes11x11::convert_from_game(before.clone(), &id_map)
+            .map_err(|e| format!("couldn't be converted to a compact board: {e}"))?;
+
+        compare_simulation(board, &id_map, before, moves, after)
+    } else {
+        let board = StandardCellBoard4Snakes11x11::convert_from_game(before.clone(), &id_map)
+            .map_err(|e| format!("couldn't be converted to a compact board: {e}"))?;
+
+        compare_simulation(board, &id_map, before, moves, after)
+    }
+}
+
+fn compare_simulation<T>(
+    board: T,
+    id_map: &HashMap<String, SnakeId>,
+    before: &Game,
+    moves: &[Move],
+    after: &Game,
+) -> Result<(), String>
+where
+    T: SimulableGame<Instruments, 4>
+        + SnakeIDGettableGame<SnakeIDType = SnakeId>
+        + HealthGettableGame
+        + SnakeBodyGettableGame
+        + PositionGettableGame,
+{
+    let compact_moves = before
+        .board
+        .snakes
+        .iter()
+        .zip(moves)
+        .map(|(snake, m)| (id_map[&snake.id], vec![*m]))
+        .collect_vec();
+    let simulated = board
+        .simulate_with_moves(&Instruments::default(), compact_moves)
+        .next()
+        .ok_or_else(|| "the compact board didn't simulate a turn".to_owned())?
+        .1;
+
+    for snake in &before.board.snakes {
+        let sid = id_map[&snake.id];
+        let alive = simulated.is_alive(&sid);
+
+        match after.board.snakes.iter().find(|s| s.id == snake.id) {
+            None if alive => return Err(format!("{} survived on the compact board", snake.id)),
+            None => {}
+            Some(_) if !alive => return Err(format!("{} died on the compact board", snake.id)),
+            Some(expected) => {
+                let health = simulated.get_health_i64(&sid);
+                if health != expected.health as i64 {
+                    return Err(format!(
+                        "{} has {health} health on the compact board instead of {}",
+                        snake.id, expected.health
+                    ));
+                }
+
+                let body = simulated
+                    .get_snake_body_vec(&sid)
+                    .into_iter()
+                    .map(|p| simulated.position_from_native(p))
+                    .collect_vec();
+                if !body.iter().eq(expected.body.iter()) {
+                    return Err(format!(
+                        "{} is at {body:?} on the compact board instead of {:?}",
+                        snake.id, expected.body
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use battlesnake_rs::MoveOutput;
+
+    use super::*;
+
+    fn pos(x: i32, y: i32) -> Position {
+        Position { x, y }
+    }
+
+    fn snake(id: &str, body: &[(i32, i32)], health: i32) -> BattleSnake {
+        let body: VecDeque<Position> = body.iter().map(|&(x, y)| pos(x, y)).collect();
+
+        BattleSnake {
+            id: id.to_owned(),
+            name: id.to_owned(),
+            head: body[0],
+            actual_length: Some(body.len() as i32),
+            body,
+            health,
+            shout: None,
+        }
+    }
+
+    /// A generated game of the given ruleset with our own snakes and food swapped in
+    fn game_with(
+        id: &str,
+        ruleset: FuzzRuleset,
+        snakes: Vec<BattleSnake>,
+        food: Vec<Position>,
+    ) -> Game {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut game = random_game(&mut rng, id.to_owned(), 500, ruleset);
+
+        game.you = snakes[0].clone();
+        game.board.snakes = snakes;
+        game.board.food = food;
+
+        game
+    }
+
+    fn alive(game: &Game) -> Vec<&str> {
+        game.board.snakes.iter().map(|s| s.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_step_longer_snake_wins_a_head_to_head() {
+        let mut game = game_with(
+            "head-to-head",
+            FuzzRuleset::Standard,
+            vec![
+                snake("long", &[(5, 5), (4, 5), (3, 5), (2, 5)], 90),
+                snake("short", &[(7, 5), (8, 5), (9, 5)], 90),
+            ],
+            vec![pos(0, 10)],
+        );
+
+        step(
+            &mut game,
+            &[Move::Right, Move::Left],
+            &mut StdRng::seed_from_u64(0),
+        );
+
+        assert_eq!(alive(&game), vec!["long"]);
+        assert_eq!(game.board.snakes[0].head, pos(6, 5));
+    }
+
+    #[test]
+    fn test_step_walls_only_kill_when_the_board_does_not_wrap() {
+        let snakes = vec![
+            snake("edge", &[(0, 5), (1, 5), (2, 5)], 90),
+            snake("other", &[(8, 8), (8, 7), (8, 6)], 90),
+        ];
+
+        let mut standard = game_with(
+            "wall-standard",
+            FuzzRuleset::Standard,
+            snakes.clone(),
+            vec![pos(0, 10)],
+        );
+        step(
+            &mut standard,
+            &[Move::Left, Move::Up],
+            &mut StdRng::seed_from_u64(0),
+        );
+        assert_eq!(alive(&standard), vec!["other"]);
+
+        let mut wrapped = game_with(
+            "wall-wrapped",
+            FuzzRuleset::Wrapped,
+            snakes,
+            vec![pos(0, 10)],
+        );
+        step(
+            &mut wrapped,
+            &[Move::Left, Move::Up],
+            &mut StdRng::seed_from_u64(0),
+        );
+        assert_eq!(alive(&wrapped), vec!["edge", "other"]);
+        assert_eq!(wrapped.board.snakes[0].head, pos(10, 5));
+    }
+
+    #[test]
+    fn test_step_snakes_die_running_into_themselves() {
+        let mut game = game_with(
+            "self-collision",
+            FuzzRuleset::Standard,
+            vec![
+                snake("curled", &[(5, 5), (5, 6), (4, 6), (4, 5), (4, 4)], 90),
+                snake("other", &[(9, 9), (9, 8), (9, 7)], 90),
+            ],
+            vec![pos(0, 10)],
+        );
+
+        step(
+            &mut game,
+            &[Move::Left, Move::Up],
+            &mut StdRng::seed_from_u64(0),
+        );
+
+        assert_eq!(alive(&game), vec!["other"]);
+    }
+
+    #[test]
+    fn test_step_eating_grows_the_snake_and_restores_health() {
+        let mut game = game_with(
+            "eating",
+            FuzzRuleset::Standard,
+            vec![
+                snake("hungry", &[(5, 5), (5, 4), (5, 3)], 50),
+                snake("other", &[(9, 9), (9, 8), (9, 7)], 50),
+            ],
+            vec![pos(5, 6)],
+        );
+
+        step(
+            &mut game,
+            &[Move::Up, Move::Up],
+            &mut StdRng::seed_from_u64(0),
+        );
+
+        let hungry = &game.board.snakes[0];
+        assert_eq!(hungry.health, 100);
+        assert_eq!(
+            hungry.body,
+            VecDeque::from(vec![pos(5, 6), pos(5, 5), pos(5, 4), pos(5, 4)])
+        );
+        assert_eq!(hungry.actual_length, Some(4));
+        assert_eq!(game.you.health, 100);
+        assert!(!game.board.food.contains(&pos(5, 6)));
+
+        assert_eq!(game.board.snakes[1].health, 49);
+    }
+
+    #[test]
+    fn test_step_royale_closes_in_a_line_on_shrink_turns() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut game = random_game(
+            &mut rng,
+            "royale-shrink".to_owned(),
+            500,
+            FuzzRuleset::Royale,
+        );
+        game.turn = ROYALE_SHRINK_EVERY_N_TURNS - 1;
+        let hazards_before = game.board.hazards.clone();
+        let moves = vec![Move::Up; game.board.snakes.len()];
+
+        step(&mut game, &moves, &mut rng);
+
+        assert_eq!(game.turn, ROYALE_SHRINK_EVERY_N_TURNS);
+        let new_hazards = &game.board.hazards[hazards_before.len()..];
+        assert_eq!(new_hazards.len(), 11);
+        assert!(new_hazards.iter().all(|h| !hazards_before.contains(h)));
+
+        let moves = vec![Move::Up; game.board.snakes.len()];
+        let hazards_before = game.board.hazards.len();
+        step(&mut game, &moves, &mut rng);
+        assert_eq!(game.board.hazards.len(), hazards_before);
+    }
+
+    /// Errors whenever there is food on the given cell, and makes a move otherwise
+    struct FailsWithFoodAt(Position);
+
+    struct FoodChecker {
+        has_food: bool,
+    }
+
+    impl BattlesnakeFactory for FailsWithFoodAt {
+        fn name(&self) -> String {
+            "fails-with-food".to_owned()
+        }
+
+        fn create_from_wire_game(&self, game: Game) -> battlesnake_rs::BoxedSnake {
+            Box::new(FoodChecker {
+                has_food: game.board.food.contains(&self.0),
+            })
+        }
+    }
+
+    impl BattlesnakeAI for FoodChecker {
+        fn make_move(&self) -> Result<MoveOutput> {
+            if self.has_food {
+                return Err(eyre!("There is food on the cell"));
+            }
+
+            Ok(MoveOutput {
+                r#move: Move::Up.to_string(),
+                shout: None,
+            })
+        }
+    }
+
+    #[test]
+    fn test_shrink_keeps_only_what_the_failure_needs() {
+        let factory = FailsWithFoodAt(pos(3, 3));
+        let mut game = game_with(
+            "shrink",
+            FuzzRuleset::Royale,
+            vec![
+                snake("me", &[(5, 5), (5, 4), (5, 3), (5, 2)], 80),
+                snake("two", &[(8, 8), (8, 7), (8, 6)], 80),
+                snake("three", &[(1, 9), (2, 9), (3, 9)], 80),
+            ],
+            vec![pos(0, 0), pos(3, 3), pos(10, 10)],
+        );
+        assert!(!game.board.hazards.is_empty());
+
+        let timeout = Duration::from_millis(500);
+        game = shrink(&factory, game, timeout);
+
+        assert_eq!(alive(&game), vec!["me"]);
+        assert_eq!(game.board.food, vec![pos(3, 3)]);
+        assert!(game.board.hazards.is_empty());
+        assert_eq!(game.board.snakes[0].body, VecDeque::from(vec![pos(5, 5)]));
+        assert_eq!(game.you.body.len(), 1);
+        assert!(check_invariants(&factory, &game, timeout).is_err());
+    }
+}
@@ -0,0 +1,131 @@
+use std::{fs::read_to_string, path::PathBuf};
+
+use color_eyre::eyre::{eyre, Result};
+use colored::Colorize;
+
+use crate::{
+    commands::archive::{Archive, ArchiveShared},
+    results::{GameResult, ResultsDatabase},
+};
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct Import {
+    /// Game IDs or URLs to import. Accepts both play.battlesnake.com and engine URLs
+    #[clap(value_parser)]
+    games: Vec<String>,
+
+    /// A file containing game IDs or URLs to import, one per line
+    #[clap(short, long, value_parser)]
+    file: Option<PathBuf>,
+
+    #[clap(flatten)]
+    shared: ArchiveShared,
+}
+
+/// Pull the game id out of anything that looks like a link to a game
+///
+/// We support the following, with or without trailing slashes:
+/// - `GAME_ID`
+/// - `https://play.battlesnake.com/g/GAME_ID/`
+/// - `https://engine.battlesnake.com/games/GAME_ID`
+/// - `https://board.battlesnake.com/?engine=...&game=GAME_ID`
+pub(crate) fn game_id_from_url(input: &str) -> Result<String> {
+    let input = input.trim();
+
+    if let Some((_, query)) = input.split_once('?') {
+        if let Some(game_id) = query
+            .split('&')
+            .find_map(|param| param.strip_prefix("game="))
+        {
+            return Ok(game_id.to_string());
+        }
+    }
+
+    let game_id = input
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .filter(|id| !id.is_empty())
+        .ok_or_else(|| eyre!("Couldn't find a game id in {input}"))?;
+
+    Ok(game_id.to_string())
+}
+
+impl Import {
+    pub(crate) fn run(self) -> Result<()> {
+        let mut inputs = self.games;
+        if let Some(file) = &self.file {
+            inputs.extend(
+                read_to_string(file)?
+                    .lines()
+                    .map(|l| l.trim())
+                    .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                    .map(|l| l.to_string()),
+            );
+        }
+
+        let game_ids = inputs
+            .iter()
+            .map(|i| game_id_from_url(i.as_str()))
+            .collect::<Result<Vec<_>>>()?;
+
+        let db = ResultsDatabase::for_archive_dir(&self.shared.archive_dir);
+        let already_imported = db.game_ids()?;
+
+        let mut results = vec![];
+        for game_id in game_ids {
+            if already_imported.contains(&game_id) && !self.shared.force {
+                println!("🎉 Results already imported for {game_id}");
+                continue;
+            }
+
+            Archive::new(game_id.clone(), self.shared.clone()).run()?;
+
+            let game_dir = self.shared.archive_dir.join(&game_id);
+            if !game_dir.join("info.json").is_file() {
+                // The engine didn't have this game anymore, the archive already told the user
+                continue;
+            }
+
+            results.push(GameResult::from_archive(&game_dir)?);
+        }
+
+        // Forcing a game to be imported again is how its results get fixed, so those replace the
+        // rows that are already there
+        let written = if self.shared.force {
+            db.upsert_all(&results)?
+        } else {
+            db.insert_all(&results)?
+        };
+        println!(
+            "{}",
+            format!("✔️ Imported {written} game results into the results database").green()
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_game_id_from_url() {
+        let game_id = "6d9cd0b1-6829-4430-926c-562918397774";
+
+        for input in [
+            game_id.to_owned(),
+            format!("  {game_id}\n"),
+            format!("https://play.battlesnake.com/g/{game_id}/"),
+            format!("https://play.battlesnake.com/g/{game_id}"),
+            format!("https://engine.battlesnake.com/games/{game_id}"),
+            format!("https://board.battlesnake.com/?engine=https%3A%2F%2Fengine&game={game_id}"),
+            format!("https://board.battlesnake.com/?game={game_id}&autoplay=true"),
+        ] {
+            assert_eq!(game_id_from_url(&input).unwrap(), game_id, "{input}");
+        }
+
+        assert!(game_id_from_url("").is_err());
+    }
+}
@@ -0,0 +1,184 @@
+use std::{collections::HashMap, fs::read_dir, path::PathBuf};
+
+use battlesnake_game_types::{
+    compact_representation::{StandardCellBoard4Snakes11x11, WrappedCellBoard4Snakes11x11},
+    types::{build_snake_id_map, Move, RandomReasonableMovesGame, SnakeIDGettableGame, SnakeId},
+    wire_representation::Game,
+};
+use battlesnake_rs::move_guard::is_fatal;
+use color_eyre::eyre::{eyre, Result};
+use colored::Colorize;
+use rand::{rngs::StdRng, SeedableRng};
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct MoveBias {
+    /// Fixture files to sample, or directories of them
+    #[clap(value_parser, default_value = "fixtures")]
+    fixtures: Vec<PathBuf>,
+
+    /// Number of times to ask for a move for every snake on each board
+    #[clap(short, long, value_parser, default_value = "1000")]
+    samples: usize,
+
+    /// Seed for the random moves, so a run can be repeated
+    #[clap(long, value_parser)]
+    seed: Option<u64>,
+}
+
+/// How many times each move was picked, in the order of [Move::all_iter]
+type MoveCounts = [usize; 4];
+
+impl MoveBias {
+    /// Samples `random_reasonable_move_for_each_snake` on every fixture, the same way the MCTS
+    /// rollouts do, and prints how often each snake went each way. Any move the move guard can
+    /// prove is fatal is counted, and called out when the snake had a move that wasn't
+    pub(crate) fn run(self) -> Result<()> {
+        let seed = self.seed.unwrap_or_else(rand::random);
+        println!("{}", format!("🎲 Sampling with seed {seed}").yellow());
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let mut total_picks = 0;
+        let mut fatal_picks = 0;
+        let mut avoidable_fatal_picks = 0;
+
+        for path in fixture_paths(&self.fixtures)? {
+            let game: Game = match std::fs::read_to_string(&path)
+                .map_err(|e| eyre!(e))
+                .and_then(|json| serde_json::from_str(&json).map_err(|e| eyre!(e)))
+            {
+                Ok(game) => game,
+                Err(e) => {
+                    println!("{}", format!("Skipping {}: {e}", path.display()).dimmed());
+                    continue;
+                }
+            };
+
+            // The rollouts only run on the compact 11x11 boards, so that's all we sample
+            if game.board.width != 11 || game.board.height != 11 || game.board.snakes.len() > 4 {
+                println!(
+                    "{}",
+                    format!("Skipping {}: not an 11x11 board", path.display()).dimmed()
+                );
+                continue;
+            }
+
+            let id_map = build_snake_id_map(&game);
+            let counts = if battlesnake_rs::wire_game::is_wrapped(&game.game) {
+                let board = WrappedCellBoard4Snakes11x11::convert_from_game(game.clone(), &id_map)
+                    .map_err(|e| eyre!("Couldn't convert {}: {e}", path.display()))?;
+                sample(&board, self.samples, &mut rng)
+            } else {
+                let board = StandardCellBoard4Snakes11x11::convert_from_game(game.clone(), &id_map)
+                    .map_err(|e| eyre!("Couldn't convert {}: {e}", path.display()))?;
+                sample(&board, self.samples, &mut rng)
+            };
+
+            println!("{}", path.display().to_string().bold());
+            for snake in &game.board.snakes {
+                let snake_counts = counts.get(&id_map[&snake.id]).copied().unwrap_or_default();
+                let picks: usize = snake_counts.iter().sum();
+
+                let fatal: Vec<bool> = Move::all_iter()
+                    .map(|m| is_fatal(&game, snake, m))
+                    .collect();
+                let snake_fatal_picks: usize = snake_counts
+                    .iter()
+                    .zip(&fatal)
+                    .filter(|(_, fatal)| **fatal)
+                    .map(|(count, _)| count)
+                    .sum();
+                let had_a_safe_move = fatal.iter().any(|fatal| !fatal);
+
+                total_picks += picks;
+                fatal_picks += snake_fatal_picks;
+                if had_a_safe_move {
+                    avoidable_fatal_picks += snake_fatal_picks;
+                }
+
+                let distribution = Move::all_iter()
+                    .zip(snake_counts)
+                    .zip(&fatal)
+                    .map(|((m, count), fatal)| {
+                        let share = format!("{m} {:5.1}%", percent(count, picks));
+                        if *fatal {
+                            share.red().to_string()
+                        } else {
+                            share
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join("  ");
+                let fatal_summary = format!("fatal {:5.1}%", percent(snake_fatal_picks, picks));
+                let fatal_summary = if had_a_safe_move && snake_fatal_picks > 0 {
+                    fatal_summary.red()
+                } else {
+                    fatal_summary.normal()
+                };
+
+                println!("  {:<24} {distribution}  {fatal_summary}", snake.name);
+            }
+        }
+
+        println!(
+            "{}",
+            format!(
+                "Picked a fatal move {:.1}% of the time, and {:.1}% of the time when there was a \
+                 move that wasn't",
+                percent(fatal_picks, total_picks),
+                percent(avoidable_fatal_picks, total_picks)
+            )
+            .yellow()
+        );
+
+        Ok(())
+    }
+}
+
+/// The fixture files we were given, with directories expanded to the JSON files in them
+pub(crate) fn fixture_paths(fixtures: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut paths = vec![];
+
+    for path in fixtures {
+        if path.is_dir() {
+            let mut in_dir: Vec<PathBuf> = read_dir(path)?
+                .map(|entry| entry.map(|e| e.path()))
+                .collect::<Result<_, _>>()?;
+            in_dir.retain(|p| p.extension().map_or(false, |ext| ext == "json"));
+            in_dir.sort();
+
+            paths.extend(in_dir);
+        } else {
+            paths.push(path.clone());
+        }
+    }
+
+    Ok(paths)
+}
+
+/// Ask for a random reasonable move for every snake `samples` times
+fn sample<T>(board: &T, samples: usize, rng: &mut StdRng) -> HashMap<SnakeId, MoveCounts>
+where
+    T: RandomReasonableMovesGame + SnakeIDGettableGame<SnakeIDType = SnakeId>,
+{
+    let mut counts: HashMap<SnakeId, MoveCounts> = HashMap::new();
+
+    for _ in 0..samples {
+        for (sid, m) in board.random_reasonable_move_for_each_snake(rng) {
+            let index = Move::all_iter()
+                .position(|candidate| candidate == m)
+                .expect("Every move is in Move::all_iter");
+
+            counts.entry(sid).or_default()[index] += 1;
+        }
+    }
+
+    counts
+}
+
+fn percent(count: usize, total: usize) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+
+    100.0 * count as f64 / total as f64
+}
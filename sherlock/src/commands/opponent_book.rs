@@ -0,0 +1,169 @@
+use std::{
+    fs::read_dir,
+    path::{Path, PathBuf},
+};
+
+use battlesnake_game_types::wire_representation::Game;
+use battlesnake_rs::opponent_book::OpponentBook;
+use color_eyre::eyre::{Result, WrapErr};
+use colored::Colorize;
+use serde_json::Value;
+
+use crate::{commands::archive::read_archived, unofficial_api::frame_to_game};
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct OpponentBookCommand {
+    /// Directory the games were archived to
+    #[clap(short, long, value_parser, default_value = "archive")]
+    archive_dir: PathBuf,
+
+    /// Where to write the book. Defaults to `opponent_book.json` in the archive directory
+    #[clap(short, long, value_parser)]
+    output: Option<PathBuf>,
+
+    /// Only show these snakes in the summary
+    #[clap(short, long = "snake", value_parser)]
+    snakes: Vec<String>,
+}
+
+impl OpponentBookCommand {
+    /// Builds the book from scratch out of every archived and arena game that still has its
+    /// frames, so running it again doesn't count any game twice. Pruned games only have their
+    /// outcome left, so they aren't in the book
+    pub(crate) fn run(self) -> Result<()> {
+        let mut book = OpponentBook::default();
+        let mut games = 0;
+
+        // Games archived from the engine, each in their own directory
+        for entry in read_dir(&self.archive_dir)? {
+            let game_dir = entry?.path();
+            let frames_path = game_dir.join("frames.jsonl");
+            let has_frames =
+                frames_path.is_file() || frames_path.with_extension("jsonl.gz").is_file();
+            if !game_dir.join("info.json").is_file() || !has_frames {
+                continue;
+            }
+
+            book.observe_game(&archived_turns(&game_dir)?);
+            games += 1;
+        }
+
+        // Games from `sherlock arena`, where the whole game is in a single file
+        let arena_dir = self.archive_dir.join("arena");
+        if arena_dir.is_dir() {
+            for entry in read_dir(&arena_dir)? {
+                let path = entry?.path();
+                let plain_path = match path.extension().and_then(|ext| ext.to_str()) {
+                    Some("jsonl") => path,
+                    Some("gz") => path.with_extension(""),
+                    _ => continue,
+                };
+
+                book.observe_game(&arena_turns(&read_archived(&plain_path)?)?);
+                games += 1;
+            }
+        }
+
+        let output = self
+            .output
+            .clone()
+            .unwrap_or_else(|| self.archive_dir.join("opponent_book.json"));
+        book.save(&output)?;
+
+        self.print_summary(&book);
+        println!(
+            "{}",
+            format!(
+                "✔️ Wrote {} snakes from {games} games to {}",
+                book.len(),
+                output.display()
+            )
+            .green()
+        );
+
+        Ok(())
+    }
+
+    fn print_summary(&self, book: &OpponentBook) {
+        let rate = |rate: Option<f64>| {
+            rate.map(|r| format!("{:.0}%", r * 100.0))
+                .unwrap_or_else(|| "-".to_owned())
+        };
+
+        println!(
+            "{:<32} {:>6} {:>7} {:>11} {:>10}",
+            "Snake", "Games", "Length", "Aggression", "Food"
+        );
+        for (name, stats) in book.iter() {
+            if !self.snakes.is_empty() && !self.snakes.contains(name) {
+                continue;
+            }
+
+            println!(
+                "{:<32} {:>6} {:>7} {:>11} {:>10}",
+                name,
+                stats.games,
+                stats
+                    .average_length_at_death()
+                    .map(|l| format!("{l:.1}"))
+                    .unwrap_or_else(|| "-".to_owned()),
+                rate(stats.aggression_index()),
+                rate(stats.food_priority()),
+            );
+        }
+    }
+}
+
+/// Every turn of a game archived from the engine
+///
+/// The engine keeps dead snakes in its frames, which we drop, so the frame after the last snake
+/// dies is an empty board
+fn archived_turns(game_dir: &Path) -> Result<Vec<Game>> {
+    let info: Value = serde_json::from_str(&read_archived(&game_dir.join("info.json"))?)?;
+    // The archive writes the frames back to back, without newlines between them
+    let frames_document = read_archived(&game_dir.join("frames.jsonl"))?;
+
+    let mut turns: Vec<Game> = vec![];
+    for frame in serde_json::Deserializer::from_str(&frames_document).into_iter::<Value>() {
+        let frame = frame?;
+        let anyone_alive = frame["Snakes"]
+            .as_array()
+            .map_or(false, |snakes| snakes.iter().any(|s| s["Death"].is_null()));
+
+        let turn = if anyone_alive {
+            frame_to_game(&frame, &info["Game"], "")
+                .wrap_err_with(|| format!("Couldn't read a frame of {}", game_dir.display()))?
+        } else {
+            let Some(last) = turns.last() else {
+                break;
+            };
+            let mut empty = last.clone();
+            empty.turn += 1;
+            empty.board.snakes.clear();
+
+            empty
+        };
+
+        let game_over = turn.board.snakes.is_empty();
+        turns.push(turn);
+        if game_over {
+            break;
+        }
+    }
+
+    Ok(turns)
+}
+
+/// Every turn of a game played by `sherlock arena`. The first line is the game info and the last
+/// is the result, and every line in between is the request for a turn
+fn arena_turns(output: &str) -> Result<Vec<Game>> {
+    let lines: Vec<&str> = output.lines().filter(|l| !l.trim().is_empty()).collect();
+    let Some(turns) = lines.get(1..lines.len().saturating_sub(1)) else {
+        return Ok(vec![]);
+    };
+
+    turns
+        .iter()
+        .map(|line| Ok(serde_json::from_str::<Game>(line)?))
+        .collect()
+}
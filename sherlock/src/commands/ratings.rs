@@ -0,0 +1,40 @@
+use std::path::PathBuf;
+
+use color_eyre::eyre::Result;
+
+use crate::{
+    ratings::{expected_score, Ratings},
+    results::ResultsDatabase,
+};
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct RatingsCommand {
+    /// Only show these snakes. With exactly two, also show how likely the first is to beat the
+    /// second
+    #[clap(short, long = "snake", value_parser)]
+    snakes: Vec<String>,
+
+    /// Directory the results database is in
+    #[clap(short, long, value_parser, default_value = "archive")]
+    archive_dir: PathBuf,
+}
+
+impl RatingsCommand {
+    pub(crate) fn run(self) -> Result<()> {
+        let db = ResultsDatabase::for_archive_dir(&self.archive_dir);
+        let ratings = Ratings::from_results(&db.all()?);
+
+        ratings.print_standings(&self.snakes);
+
+        if let [first, second] = self.snakes.as_slice() {
+            let expected = expected_score(ratings.get(first).elo, ratings.get(second).elo);
+            println!();
+            println!(
+                "{first} is expected to score {:.1}% against {second}",
+                expected * 100.0
+            );
+        }
+
+        Ok(())
+    }
+}
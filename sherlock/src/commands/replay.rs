@@ -37,7 +37,7 @@ use axum::{
 use serde_json::Value;
 use tower_http::cors::CorsLayer;
 
-use crate::websockets::rules_format_to_websocket;
+use crate::{commands::archive::read_archived, websockets::rules_format_to_websocket};
 
 async fn game_handler(Path(game_id): Path<String>) -> Response {
     println!("We got a game for {game_id}");
@@ -81,7 +81,9 @@ async fn websocket_handler(
 
         Ok(lines.join("\n"))
     } else {
-        read_to_string(format!("./archive/{game_id}/websockets.jsonl"))
+        read_archived(&PathBuf::from(format!(
+            "./archive/{game_id}/websockets.jsonl"
+        )))
     };
     match game_lines {
         Ok(l) => match ws {
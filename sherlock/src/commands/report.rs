@@ -0,0 +1,489 @@
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    fs::{create_dir_all, read_to_string, File},
+    io::Write,
+    path::PathBuf,
+};
+
+use battlesnake_game_types::{types::Move, wire_representation::Game};
+use battlesnake_rs::{
+    all_factories, hovering_hobbs,
+    move_guard::is_fatal,
+    shouts::{ObservedShout, ShoutLog},
+    BattlesnakeAI, BattlesnakeFactory, BoxedFactory,
+};
+use color_eyre::eyre::{eyre, Result};
+use colored::Colorize;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    commands::archive::{read_archived, Archive, ArchiveShared},
+    unofficial_api::frame_to_game,
+};
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct Report {
+    /// Game ID to report on
+    #[clap(short, long, value_parser)]
+    game_id: String,
+
+    /// The name of the snake to report on
+    #[clap(short, long, value_parser)]
+    you_name: String,
+
+    /// Which of our snakes evaluates each turn, the same name as in its URL
+    #[clap(short, long, value_parser, default_value = "hovering-hobbs")]
+    snake: String,
+
+    /// How long the evaluating snake gets for each turn, in milliseconds. It still keeps its usual
+    /// padding for network latency out of this
+    #[clap(short, long, value_parser, default_value_t = 300)]
+    budget_ms: i64,
+
+    /// Where to write the report, defaults to `reports/GAME_ID.html` or `reports/GAME_ID.json`
+    #[clap(short, long, value_parser)]
+    output: Option<PathBuf>,
+
+    /// Write an HTML page to read, or the same turns and blunders as JSON for other tooling
+    #[clap(long, value_enum, default_value_t = ReportFormat::Html)]
+    format: ReportFormat,
+
+    #[clap(flatten)]
+    shared: ArchiveShared,
+}
+
+/// How `report` writes out the turns it evaluated
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ReportFormat {
+    /// A standalone page with the boards drawn out
+    Html,
+    /// A [JsonReport]
+    Json,
+}
+
+/// The whole report as it's written out by `--format json`
+#[derive(Debug, Serialize)]
+struct JsonReport<'a> {
+    game_id: &'a str,
+    you_name: &'a str,
+    snake: &'a str,
+    budget_ms: i64,
+    /// The turns with a blunder, which are also annotated in `turns`
+    blunder_turns: Vec<i32>,
+    turns: &'a [TurnReport],
+}
+
+/// Snake colors, in the order the snakes show up in the first frame
+const SNAKE_COLORS: [&str; 8] = [
+    "#da8a1a", "#3b5998", "#2e8b57", "#b22222", "#8a2be2", "#ff69b4", "#20b2aa", "#808000",
+];
+const CELL_SIZE: i32 = 24;
+
+/// Everything we know about one turn, this is also embedded in the report as JSON
+#[derive(Debug, Serialize)]
+struct TurnReport {
+    turn: i32,
+    game: Game,
+    /// The move the snake actually made, if it survived long enough for us to see it
+    played: Option<String>,
+    /// The move our snake would have made
+    suggested: String,
+    /// Every move our snake considered, with its score
+    root_options: Vec<(String, String)>,
+    depth: Option<usize>,
+    /// The moves that weren't fatal on their own, see [is_fatal]
+    safe_moves: Vec<String>,
+    annotations: Vec<Annotation>,
+    /// What the opponents shouted this turn that they weren't already shouting, only as
+    /// context for the reader
+    shouts: Vec<ObservedShout>,
+}
+
+#[derive(Debug, Serialize)]
+struct Annotation {
+    blunder: bool,
+    text: String,
+}
+
+impl Report {
+    /// Archive the game if we haven't already, then have one of our snakes evaluate every turn the
+    /// snake we're reporting on was alive for, and write it all to a single HTML file
+    pub(crate) fn run(self) -> Result<()> {
+        Archive::new(self.game_id.clone(), self.shared.clone()).run()?;
+
+        let game_dir = self.shared.archive_dir.join(&self.game_id);
+        let info: Value = serde_json::from_str(
+            &read_to_string(game_dir.join("info.json"))
+                .map_err(|e| eyre!("Couldn't read the archive for {}: {e}", self.game_id))?,
+        )?;
+        // The archive writes the frames back to back, without newlines between them
+        let frames_document = read_archived(&game_dir.join("frames.jsonl"))?;
+        let frames = serde_json::Deserializer::from_str(&frames_document)
+            .into_iter::<Value>()
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let factory = factories()
+            .into_iter()
+            .find(|f| f.name() == self.snake)
+            .ok_or_else(|| eyre!("There is no snake named {}", self.snake))?;
+
+        let colors: HashMap<String, &str> = frames
+            .first()
+            .and_then(|f| f["Snakes"].as_array())
+            .map(|snakes| {
+                snakes
+                    .iter()
+                    .filter_map(|s| s["ID"].as_str())
+                    .zip(SNAKE_COLORS.iter().cycle())
+                    .map(|(id, color)| (id.to_owned(), *color))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut shout_log = ShoutLog::default();
+        let mut turns = vec![];
+        for (i, frame) in frames.iter().enumerate() {
+            let alive = frame["Snakes"].as_array().map_or(false, |snakes| {
+                snakes
+                    .iter()
+                    .any(|s| s["Name"] == self.you_name.as_str() && s["Death"].is_null())
+            });
+            if !alive {
+                break;
+            }
+
+            let mut game = frame_to_game(frame, &info["Game"], &self.you_name)?;
+            game.game.timeout = self.budget_ms;
+
+            let shouts = game
+                .board
+                .snakes
+                .iter()
+                .filter(|s| s.id != game.you.id)
+                .filter_map(|s| {
+                    shout_log
+                        .observe(game.turn, &s.id, &s.name, s.shout.as_deref())
+                        .cloned()
+                })
+                .collect();
+
+            println!("{}", format!("⏳ Evaluating turn {}", game.turn).yellow());
+            turns.push(self.evaluate(factory.as_ref(), game, frames.get(i + 1), shouts)?);
+        }
+
+        let extension = match self.format {
+            ReportFormat::Html => "html",
+            ReportFormat::Json => "json",
+        };
+        let output = self
+            .output
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(format!("reports/{}.{extension}", self.game_id)));
+        if let Some(parent) = output.parent() {
+            create_dir_all(parent)?;
+        }
+        let contents = match self.format {
+            ReportFormat::Html => self.render(&turns, &colors)?,
+            ReportFormat::Json => self.render_json(&turns)?,
+        };
+        File::create(&output)?.write_all(contents.as_bytes())?;
+
+        let blunders = blunder_turns(&turns).len();
+        println!(
+            "{}",
+            format!(
+                "✔️ Wrote the report for {} turns with {blunders} blunders to {}",
+                turns.len(),
+                output.display()
+            )
+            .green()
+        );
+
+        Ok(())
+    }
+
+    fn evaluate(
+        &self,
+        factory: &dyn BattlesnakeFactory,
+        game: Game,
+        next_frame: Option<&Value>,
+        shouts: Vec<ObservedShout>,
+    ) -> Result<TurnReport> {
+        let explanation = factory.create_from_wire_game(game.clone()).explain_move()?;
+        let played = next_frame.and_then(|f| played_move(&game, f));
+
+        let safe_moves: Vec<Move> = Move::all_iter()
+            .filter(|m| !is_fatal(&game, &game.you, *m))
+            .collect();
+
+        let mut annotations = vec![];
+        if let Some(played) = played {
+            if !safe_moves.contains(&played) && !safe_moves.is_empty() {
+                let safe_moves = safe_moves.iter().map(|m| m.to_string()).collect::<Vec<_>>();
+                annotations.push(Annotation {
+                    blunder: true,
+                    text: format!(
+                        "Blunder: {played} was fatal, but {} wasn't",
+                        safe_moves.join(" or ")
+                    ),
+                });
+            }
+            if played.to_string() != explanation.output.r#move {
+                annotations.push(Annotation {
+                    blunder: false,
+                    text: format!(
+                        "{} would have played {} instead of {played}",
+                        self.snake, explanation.output.r#move
+                    ),
+                });
+            }
+        } else {
+            annotations.push(Annotation {
+                blunder: false,
+                text: "Last turn alive".to_owned(),
+            });
+        }
+
+        Ok(TurnReport {
+            turn: game.turn,
+            played: played.map(|m| m.to_string()),
+            suggested: explanation.output.r#move,
+            root_options: explanation
+                .root_options
+                .into_iter()
+                .map(|(m, score)| (m.to_string(), score))
+                .collect(),
+            depth: explanation.depth,
+            safe_moves: safe_moves.iter().map(|m| m.to_string()).collect(),
+            annotations,
+            shouts,
+            game,
+        })
+    }
+
+    fn render_json(&self, turns: &[TurnReport]) -> Result<String> {
+        let report = JsonReport {
+            game_id: &self.game_id,
+            you_name: &self.you_name,
+            snake: &self.snake,
+            budget_ms: self.budget_ms,
+            blunder_turns: blunder_turns(turns),
+            turns,
+        };
+
+        Ok(serde_json::to_string_pretty(&report)?)
+    }
+
+    fn render(&self, turns: &[TurnReport], colors: &HashMap<String, &str>) -> Result<String> {
+        let mut html = String::new();
+
+        writeln!(
+            html,
+            "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{} in {}</title>",
+            escape(&self.you_name),
+            escape(&self.game_id)
+        )?;
+        writeln!(html, "<style>{STYLE}</style></head><body>")?;
+        writeln!(
+            html,
+            "<h1>{} in {}</h1><p>Evaluated by {} with {}ms per turn</p>",
+            escape(&self.you_name),
+            escape(&self.game_id),
+            escape(&self.snake),
+            self.budget_ms
+        )?;
+
+        let flagged: Vec<&TurnReport> = turns
+            .iter()
+            .filter(|t| t.annotations.iter().any(|a| a.blunder))
+            .collect();
+        if !flagged.is_empty() {
+            write!(html, "<p>Blunders on turns:")?;
+            for turn in flagged {
+                write!(html, " <a href=\"#turn-{0}\">{0}</a>", turn.turn)?;
+            }
+            writeln!(html, "</p>")?;
+        }
+
+        for turn in turns {
+            writeln!(
+                html,
+                "<section id=\"turn-{0}\"><h2>Turn {0}</h2>",
+                turn.turn
+            )?;
+            writeln!(html, "{}", board_svg(&turn.game, colors))?;
+            writeln!(html, "<div class=\"details\">")?;
+            writeln!(
+                html,
+                "<p>Played <b>{}</b>, {} suggests <b>{}</b>{}</p>",
+                turn.played.as_deref().unwrap_or("nothing"),
+                escape(&self.snake),
+                escape(&turn.suggested),
+                turn.depth
+                    .map(|d| format!(" at depth {d}"))
+                    .unwrap_or_default()
+            )?;
+            if !turn.root_options.is_empty() {
+                writeln!(html, "<table>")?;
+                for (m, score) in &turn.root_options {
+                    writeln!(html, "<tr><td>{m}</td><td>{}</td></tr>", escape(score))?;
+                }
+                writeln!(html, "</table>")?;
+            }
+            for annotation in &turn.annotations {
+                let class = if annotation.blunder {
+                    "blunder"
+                } else {
+                    "note"
+                };
+                writeln!(
+                    html,
+                    "<p class=\"{class}\">{}</p>",
+                    escape(&annotation.text)
+                )?;
+            }
+            for shout in &turn.shouts {
+                writeln!(
+                    html,
+                    "<p class=\"shout\">{}: {}</p>",
+                    escape(&shout.snake_name),
+                    escape(&shout.text)
+                )?;
+            }
+            writeln!(html, "</div></section>")?;
+        }
+
+        // Keep the raw data in the page, so anyone with the report can dig into it further
+        let data = serde_json::to_string(turns)?.replace("</", "<\\/");
+        writeln!(
+            html,
+            "<script type=\"application/json\" id=\"report-data\">{data}</script>"
+        )?;
+        writeln!(html, "</body></html>")?;
+
+        Ok(html)
+    }
+}
+
+fn blunder_turns(turns: &[TurnReport]) -> Vec<i32> {
+    turns
+        .iter()
+        .filter(|t| t.annotations.iter().any(|a| a.blunder))
+        .map(|t| t.turn)
+        .collect()
+}
+
+/// Every snake we can evaluate with, including the ones that have their own routes in the server
+fn factories() -> Vec<BoxedFactory> {
+    let mut factories = all_factories();
+    factories.push(Box::new(hovering_hobbs::Factory));
+
+    factories
+}
+
+/// Work out which way our snake went, from where its head is in the next frame
+///
+/// Dead snakes are still in the frames, so this works for the move that killed us too
+fn played_move(game: &Game, next_frame: &Value) -> Option<Move> {
+    let next_head = next_frame["Snakes"]
+        .as_array()?
+        .iter()
+        .find(|s| s["ID"].as_str() == Some(game.you.id.as_str()))?["Body"][0]
+        .clone();
+    let (x, y) = (next_head["X"].as_i64()?, next_head["Y"].as_i64()?);
+
+    let width = game.board.width as i64;
+    let height = game.board.height as i64;
+    // Moves across the edge of a wrapped board come out as the width or height minus one
+    let dx = (x - game.you.head.x as i64 + width) % width;
+    let dy = (y - game.you.head.y as i64 + height) % height;
+
+    match (dx, dy) {
+        (1, 0) => Some(Move::Right),
+        (0, 1) => Some(Move::Up),
+        (dx, 0) if dx == width - 1 => Some(Move::Left),
+        (0, dy) if dy == height - 1 => Some(Move::Down),
+        _ => None,
+    }
+}
+
+/// Draw the board, with the origin in the bottom left like the Battlesnake board viewer
+fn board_svg(game: &Game, colors: &HashMap<String, &str>) -> String {
+    let width = game.board.width as i32;
+    let height = game.board.height as i32;
+    let to_svg = |x: i32, y: i32| (x * CELL_SIZE, (height - 1 - y) * CELL_SIZE);
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">",
+        width * CELL_SIZE,
+        height * CELL_SIZE
+    );
+
+    for x in 0..width {
+        for y in 0..height {
+            let (sx, sy) = to_svg(x, y);
+            let _ = write!(
+                svg,
+                "<rect x=\"{sx}\" y=\"{sy}\" width=\"{CELL_SIZE}\" height=\"{CELL_SIZE}\" \
+                 class=\"cell\"/>"
+            );
+        }
+    }
+    for hazard in &game.board.hazards {
+        let (sx, sy) = to_svg(hazard.x, hazard.y);
+        let _ = write!(
+            svg,
+            "<rect x=\"{sx}\" y=\"{sy}\" width=\"{CELL_SIZE}\" height=\"{CELL_SIZE}\" \
+             class=\"hazard\"/>"
+        );
+    }
+    for food in &game.board.food {
+        let (sx, sy) = to_svg(food.x, food.y);
+        let _ = write!(
+            svg,
+            "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" class=\"food\"/>",
+            sx + CELL_SIZE / 2,
+            sy + CELL_SIZE / 2,
+            CELL_SIZE / 4
+        );
+    }
+    for snake in &game.board.snakes {
+        let color = colors.get(&snake.id).copied().unwrap_or("#777777");
+        let _ = write!(svg, "<g><title>{}</title>", escape(&snake.name));
+        for (i, segment) in snake.body.iter().enumerate() {
+            let (sx, sy) = to_svg(segment.x, segment.y);
+            let inset = if i == 0 { 0 } else { 2 };
+            let _ = write!(
+                svg,
+                "<rect x=\"{}\" y=\"{}\" width=\"{2}\" height=\"{2}\" fill=\"{color}\"{3}/>",
+                sx + inset,
+                sy + inset,
+                CELL_SIZE - 2 * inset,
+                if i == 0 { " class=\"head\"" } else { "" }
+            );
+        }
+        let _ = write!(svg, "</g>");
+    }
+
+    svg.push_str("</svg>");
+    svg
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+const STYLE: &str = "body { font-family: sans-serif; max-width: 60em; margin: auto; } \
+    section { display: flex; gap: 1em; align-items: flex-start; border-top: 1px solid #ccc; } \
+    .cell { fill: #f4f4f4; stroke: #ddd; } \
+    .hazard { fill: #000; opacity: 0.3; } \
+    .food { fill: #e23d3d; } \
+    .head { stroke: #000; stroke-width: 2; } \
+    .blunder { color: #b22222; font-weight: bold; } \
+    .note { color: #555; } \
+    .shout { color: #555; font-style: italic; } \
+    td { padding: 0 0.5em; }";
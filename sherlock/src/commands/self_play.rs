@@ -0,0 +1,181 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use battlesnake_game_types::{
+    compact_representation::StandardCellBoard4Snakes11x11,
+    types::{build_snake_id_map, Move},
+    wire_representation::Game,
+};
+use battlesnake_rs::{
+    improbable_irene::{ImprobableIrene, SearchPolicy},
+    mcts::{RootNoise, SnakeOptions},
+};
+use color_eyre::eyre::{eyre, Result};
+use colored::Colorize;
+use rand::{rngs::StdRng, SeedableRng};
+use serde::Serialize;
+
+use crate::commands::fuzz::{random_game, step, FuzzRuleset};
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct SelfPlay {
+    /// Number of games to play
+    #[clap(short, long, value_parser, default_value = "10")]
+    games: usize,
+
+    /// MCTS iterations each snake gets for every move
+    #[clap(short, long, value_parser, default_value = "2000")]
+    iterations: usize,
+
+    /// The longest we let a single game go on for
+    #[clap(long, value_parser, default_value = "300")]
+    max_turns: i32,
+
+    /// Concentration of the Dirichlet noise mixed into the root priors
+    #[clap(long, value_parser, default_value = "0.3")]
+    dirichlet_alpha: f64,
+
+    /// How much of each root prior is replaced by noise, 0 turns the noise off
+    #[clap(long, value_parser, default_value = "0.25")]
+    noise_weight: f64,
+
+    /// Temperature for picking the move to play from the visits, 0 plays the best move
+    #[clap(long, value_parser, default_value = "1.0")]
+    temperature: f64,
+
+    /// Seed for the starting positions and food, so the same games can be set up again
+    #[clap(long, value_parser)]
+    seed: Option<u64>,
+
+    /// File to append the training samples to
+    #[clap(short, long, value_parser, default_value = "self_play.jsonl")]
+    output: PathBuf,
+}
+
+/// One line of the output, for a single snake's move
+#[derive(Serialize, Debug)]
+struct Sample {
+    /// The board as the snake that moved saw it
+    game: Game,
+    /// How many times the search visited each move
+    visits: BTreeMap<String, usize>,
+    chosen_move: String,
+    /// The last snake standing, or None if the game was a draw or ran out of turns
+    winner: Option<String>,
+    won: bool,
+}
+
+impl SelfPlay {
+    /// Improbable Irene plays random standard games against itself, and every move is written out
+    /// with the visits the search gave each option and who went on to win. Root noise and a
+    /// temperature are turned on so the games don't all play out the same way.
+    ///
+    /// The seed covers the starting positions and the food, the searches themselves aren't seeded
+    pub(crate) fn run(self) -> Result<()> {
+        let seed = self.seed.unwrap_or_else(rand::random);
+        println!("{}", format!("🎲 Playing with seed {seed}").yellow());
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let root_noise = (self.noise_weight > 0.0).then_some(RootNoise {
+            alpha: self.dirichlet_alpha,
+            weight: self.noise_weight,
+        });
+        let options = SnakeOptions {
+            root_noise,
+            temperature: self.temperature,
+            ..SnakeOptions::default()
+        };
+
+        let mut output = BufWriter::new(
+            File::options()
+                .create(true)
+                .append(true)
+                .open(&self.output)?,
+        );
+        let mut total_samples = 0;
+
+        for i in 0..self.games {
+            let mut game = random_game(
+                &mut rng,
+                format!("self-play-{seed}-{i}"),
+                500,
+                FuzzRuleset::Standard,
+            );
+            let mut samples = vec![];
+
+            while game.turn < self.max_turns && game.board.snakes.len() > 1 {
+                let mut moves = vec![];
+                for snake in &game.board.snakes {
+                    let view = Game {
+                        you: snake.clone(),
+                        ..game.clone()
+                    };
+                    let policy = search(&view, options, self.iterations)?;
+
+                    moves.push(policy.chosen_move);
+                    samples.push(Sample {
+                        game: view,
+                        visits: Move::all_iter()
+                            .map(|m| (m.to_string(), policy.visits[m.as_index()]))
+                            .collect(),
+                        chosen_move: policy.chosen_move.to_string(),
+                        winner: None,
+                        won: false,
+                    });
+                }
+
+                step(&mut game, &moves, &mut rng);
+            }
+
+            let winner = match game.board.snakes.as_slice() {
+                [winner] => Some(winner.name.clone()),
+                _ => None,
+            };
+            for mut sample in samples {
+                sample.won = winner.as_ref() == Some(&sample.game.you.name);
+                sample.winner = winner.clone();
+
+                writeln!(output, "{}", serde_json::to_string(&sample)?)?;
+                total_samples += 1;
+            }
+
+            println!(
+                "{}",
+                format!(
+                    "Game {} ended on turn {}, won by {}",
+                    i + 1,
+                    game.turn,
+                    winner.as_deref().unwrap_or("nobody")
+                )
+                .dimmed()
+            );
+        }
+
+        output.flush()?;
+        println!(
+            "{}",
+            format!(
+                "✔️ Wrote {total_samples} samples from {} games to {}",
+                self.games,
+                self.output.display()
+            )
+            .green()
+        );
+
+        Ok(())
+    }
+}
+
+/// Run Irene's search for `you` on this board
+fn search(game: &Game, options: SnakeOptions, iterations: usize) -> Result<SearchPolicy> {
+    let id_map = build_snake_id_map(game);
+    let board = StandardCellBoard4Snakes11x11::convert_from_game(game.clone(), &id_map)
+        .map_err(|e| eyre!("Couldn't convert the board: {e}"))?;
+
+    ImprobableIrene::new_with_options(board, game.game.clone(), game.turn, options)
+        .search_policy(iterations)
+}
@@ -1,4 +1,9 @@
-use std::{collections::HashMap, fmt::Debug};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Debug,
+    fs::write,
+    path::PathBuf,
+};
 
 use battlesnake_game_types::{
     compact_representation::{dimensions::Square, WrappedCellBoard},
@@ -7,9 +12,11 @@ use battlesnake_game_types::{
 use battlesnake_minimax::paranoid::{MinMaxReturn, MinimaxSnake, WrappedScore};
 use color_eyre::eyre::Result;
 use itertools::Itertools;
-use serde_json::Value;
+use serde::Serialize;
 
-use crate::unofficial_api::{frame_to_game, get_frame_for_turn};
+use crate::unofficial_api::{
+    frame_to_game, get_frame_for_turn, get_frames_for_game, get_game_details,
+};
 
 #[derive(clap::Args, Debug)]
 pub(crate) struct Solve {
@@ -28,19 +35,83 @@ pub(crate) struct Solve {
     /// Turn to start looking back from. Uses the last turn of the game if not specified
     #[clap(short, long, value_parser)]
     search_starting_turn: Option<i32>,
+
+    /// Write the search tree from the decision point to this file as a DOT graph
+    #[clap(long, value_parser)]
+    dot: Option<PathBuf>,
+
+    /// How many levels of the search tree to include in the DOT graph
+    #[clap(long, value_parser, default_value_t = 8)]
+    dot_depth: usize,
+
+    /// Print notes as we go, or a single JSON document at the end for other tooling
+    #[clap(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+}
+
+/// How `solve` writes out what it found
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OutputFormat {
+    /// Notes for a person, printed as the search works back through the game
+    Text,
+    /// A single [SolveReport] on stdout once the search is done, for other tooling to read
+    Json,
+}
+
+/// Everything `solve` found, as it's written out by `--format json`
+#[derive(Debug, Serialize)]
+struct SolveReport {
+    game_id: String,
+    you_name: String,
+    ending_turn: i32,
+    last_living_turn: i32,
+    /// The turns we worked back through where every move already lost
+    turns_without_safe_options: Vec<i32>,
+    /// The turn the search stopped at, the latest one where we could still have won or survived
+    decision_turn: i32,
+    outcome: Outcome,
+    /// The moves that didn't lose, when this is a decision point
+    safe_moves: Vec<String>,
+    /// The moves that won, when the game was winnable
+    winning_moves: Vec<String>,
+    /// The line the search expects after each of the safe or winning moves
+    principal_variations: Vec<PrincipalVariation>,
+    /// Where the search tree was written, when it was asked for with `--dot`
+    dot: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Outcome {
+    /// We could have won from the decision turn
+    Winnable,
+    /// We could have survived from the decision turn, but not won
+    DecisionPoint,
+}
+
+/// The moves every snake makes after one of our moves, according to the search
+#[derive(Debug, Serialize)]
+struct PrincipalVariation {
+    r#move: String,
+    /// How many turns ahead the line goes
+    lookahead: usize,
+    /// Each snake's moves along the line, by the snake's ID in the game
+    moves: BTreeMap<String, Vec<String>>,
 }
 
 impl Solve {
     pub(crate) fn run(self) -> Result<()> {
-        let body: Value =
-            ureq::get(format!("https://engine.battlesnake.com/games/{}", self.game_id).as_str())
-                .call()?
-                .into_json()?;
+        let text = self.format == OutputFormat::Text;
+        let body = get_game_details(&self.game_id)?;
 
         let last_frame = &body["LastFrame"];
         let last_turn = last_frame["Turn"].as_i64().expect("Missing Turn") as i32;
         let mut current_turn = self.search_starting_turn.unwrap_or(last_turn - 1);
 
+        // We walk backwards through the game a turn at a time, so download it all up front. After
+        // this every frame comes out of the cache
+        get_frames_for_game(&self.game_id, last_turn.try_into()?)?;
+
         loop {
             let current_frame = get_frame_for_turn(&self.game_id, current_turn)?;
             let wire_game = frame_to_game(&current_frame, &body["Game"], &self.you_name);
@@ -48,7 +119,9 @@ impl Solve {
             if wire_game.is_ok() {
                 break;
             }
-            println!("You were not alive at turn {current_turn} moving backwards");
+            if text {
+                println!("You were not alive at turn {current_turn} moving backwards");
+            }
 
             current_turn -= 1;
 
@@ -59,10 +132,13 @@ impl Solve {
 
         let last_living_turn = current_turn;
 
-        println!("Ending Turn {}", &last_frame["Turn"]);
-        println!("Last Living Turn {last_living_turn}");
+        if text {
+            println!("Ending Turn {last_turn}");
+            println!("Last Living Turn {last_living_turn}");
+        }
 
-        loop {
+        let mut turns_without_safe_options = vec![];
+        let report = loop {
             let current_frame = get_frame_for_turn(&self.game_id, current_turn)?;
             let wire_game = frame_to_game(&current_frame, &body["Game"], &self.you_name).unwrap();
 
@@ -71,6 +147,10 @@ impl Solve {
             }
 
             let snake_ids = build_snake_id_map(&wire_game);
+            let snake_names: HashMap<SnakeId, String> = snake_ids
+                .iter()
+                .map(|(id, sid)| (*sid, id.clone()))
+                .collect();
             let game_info = wire_game.game.clone();
             let game: WrappedCellBoard<u16, Square, { 11 * 11 }, 8> =
                 wire_game.as_wrapped_cell_board(&snake_ids).unwrap();
@@ -84,11 +164,30 @@ impl Solve {
             let result = explorer_snake.deepend_minimax_to_turn(max_turns);
 
             let score = *result.score();
+            let mut report = SolveReport {
+                game_id: self.game_id.clone(),
+                you_name: self.you_name.clone(),
+                ending_turn: last_turn,
+                last_living_turn,
+                turns_without_safe_options: turns_without_safe_options.clone(),
+                decision_turn: current_turn,
+                outcome: Outcome::DecisionPoint,
+                safe_moves: vec![],
+                winning_moves: vec![],
+                principal_variations: vec![],
+                dot: None,
+            };
 
             if matches!(score, WrappedScore::Lose(..) | WrappedScore::Tie(..)) {
-                println!("At turn {current_turn}, there were no safe options");
+                if text {
+                    println!("At turn {current_turn}, there were no safe options");
+                }
+                turns_without_safe_options.push(current_turn);
             } else if matches!(score, WrappedScore::Win(_)) {
-                println!("At turn {current_turn}, you could have won!");
+                report.outcome = Outcome::Winnable;
+                if text {
+                    println!("At turn {current_turn}, you could have won!");
+                }
                 if let MinMaxReturn::Node { options, .. } = &result {
                     let winning_moves = options
                         .iter()
@@ -96,10 +195,17 @@ impl Solve {
                         .map(|(m, _)| *m)
                         .collect_vec();
 
-                    println!("At turn {current_turn}, the winning moves were {winning_moves:?}",);
-                    print_moves(&result, current_turn, winning_moves[0]);
+                    let variation = principal_variation(&result, winning_moves[0], &snake_names);
+                    if text {
+                        println!(
+                            "At turn {current_turn}, the winning moves were {winning_moves:?}",
+                        );
+                        print_variation(&variation, current_turn);
+                    }
+                    report.winning_moves = winning_moves.iter().map(Move::to_string).collect();
+                    report.principal_variations.push(variation);
                 }
-                break;
+                break report;
             } else if let MinMaxReturn::Node {
                 options,
                 moving_snake_id,
@@ -113,67 +219,95 @@ impl Solve {
                     .collect_vec();
                 let safe_moves = safe_options.iter().map(|(m, _)| *m).collect_vec();
 
-                println!("At turn {current_turn}, the safe options were {safe_moves:?}",);
-                println!("Turn {current_turn} is the decision point");
-
-                for m in safe_moves {
-                    print_moves(&result, current_turn, m);
+                if text {
+                    println!("At turn {current_turn}, the safe options were {safe_moves:?}",);
+                    println!("Turn {current_turn} is the decision point");
                 }
 
-                // let mut file = File::create("tmp.dot").unwrap();
-                // file.write_all(format!("{}", result.to_dot_graph(you_id)).as_bytes())
-                //     .unwrap();
+                for m in &safe_moves {
+                    let variation = principal_variation(&result, *m, &snake_names);
+                    if text {
+                        print_variation(&variation, current_turn);
+                    }
+                    report.principal_variations.push(variation);
+                }
+                report.safe_moves = safe_moves.iter().map(Move::to_string).collect();
 
-                // Command::new("dot")
-                //     .arg("-Tsvg")
-                //     .arg("-O")
-                //     .arg("tmp.dot")
-                //     .output()
-                //     .unwrap();
-                // Command::new("open").arg("tmp.dot.svg").output().unwrap();
+                if let Some(dot) = &self.dot {
+                    let graph = result.to_dot_graph_to_depth(you_id, self.dot_depth);
+                    write(dot, format!("{graph}"))?;
+                    if text {
+                        println!("Wrote the search tree to {}", dot.display());
+                    }
+                    report.dot = Some(dot.clone());
+                }
 
-                break;
+                break report;
             } else {
                 panic!("We shouldn't ever have a leaf here")
             }
 
             current_turn -= 1;
+        };
+
+        if !text {
+            println!("{}", serde_json::to_string_pretty(&report)?);
         }
 
         Ok(())
     }
 }
 
-fn print_moves<GameType, ScoreType>(
+/// The line the search expects after we make `m`, split up by snake
+///
+/// This is `m` followed by the route through the search tree below it, which is the principal
+/// variation when `m` is the move the search picked
+fn principal_variation<GameType, ScoreType>(
     result: &MinMaxReturn<GameType, ScoreType>,
-    current_turn: i32,
     m: Move,
-) where
+    snake_names: &HashMap<SnakeId, String>,
+) -> PrincipalVariation
+where
     GameType: SnakeIDGettableGame<SnakeIDType = SnakeId> + Debug + Clone,
     ScoreType: Copy + Ord + Debug,
 {
-    let all_snake_path = result.chosen_route();
-    let sids = all_snake_path
-        .iter()
-        .map(|(sid, _)| sid)
-        .unique()
-        .collect_vec();
-    let mut paths_per_snake: HashMap<SnakeId, Vec<Move>> = HashMap::new();
-    for &sid in &sids {
-        let path = all_snake_path
-            .iter()
-            .filter(|(s, _)| s == sid)
-            .map(|(_, p)| p)
+    let mut route = vec![];
+    if let MinMaxReturn::Node {
+        options,
+        moving_snake_id,
+        ..
+    } = result
+    {
+        if let Some((_, below)) = options.iter().find(|(option, _)| *option == m) {
+            route.push((*moving_snake_id, m));
+            route.extend(below.chosen_route());
+        }
+    }
+
+    let snakes = route.iter().map(|(sid, _)| sid).unique().count();
+    let mut moves: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (sid, m) in &route {
+        let name = snake_names
+            .get(sid)
             .cloned()
-            .collect_vec();
-        paths_per_snake.insert(*sid, path);
+            .unwrap_or_else(|| format!("{sid:?}"));
+        moves.entry(name).or_default().push(m.to_string());
     }
+
+    PrincipalVariation {
+        r#move: m.to_string(),
+        lookahead: route.len() / snakes.max(1),
+        moves,
+    }
+}
+
+fn print_variation(variation: &PrincipalVariation, current_turn: i32) {
     println!(
-        "At turn {current_turn}, the {m} path takes {} turn lookahead:",
-        all_snake_path.len() / sids.len()
+        "At turn {current_turn}, the {} path takes {} turn lookahead:",
+        variation.r#move, variation.lookahead
     );
-    for (sid, path) in paths_per_snake {
-        println!("{sid:?}: {}", path.iter().join(", "));
+    for (snake, path) in &variation.moves {
+        println!("{snake}: {}", path.join(", "));
     }
     println!()
 }
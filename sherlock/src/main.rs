@@ -1,6 +1,8 @@
 #![feature(let_chains)]
 
 mod commands;
+mod ratings;
+mod results;
 mod unofficial_api;
 mod websockets;
 
@@ -0,0 +1,154 @@
+use std::{cmp::Ordering, collections::HashMap};
+
+use itertools::Itertools;
+
+use crate::results::{GameResult, SnakeResult};
+
+/// Where every snake starts before they have played any games
+pub(crate) const INITIAL_RATING: f64 = 1500.0;
+
+/// How far a single game can move a rating
+///
+/// In games with more than two snakes each snake plays a matchup against every other, so this is
+/// split between those matchups to keep big games from counting for more than duels
+const K_FACTOR: f64 = 32.0;
+
+/// The Elo rating of a single snake, along with the games it is based on
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Rating {
+    pub elo: f64,
+    pub games: usize,
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+}
+
+impl Default for Rating {
+    fn default() -> Self {
+        Self {
+            elo: INITIAL_RATING,
+            games: 0,
+            wins: 0,
+            draws: 0,
+            losses: 0,
+        }
+    }
+}
+
+/// Elo ratings for every snake in the results database, keyed by the snake's name
+///
+/// Snakes are identified only by their name, so give each configuration you are tuning its own
+/// name (like `hobbs-candidate-7`) to rate them separately. The ratings aren't stored on their own,
+/// they are rebuilt from the results database in the order the games were recorded, so they carry
+/// over between runs for as long as the results do
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Ratings {
+    ratings: HashMap<String, Rating>,
+}
+
+/// The chance that a snake rated `rating` beats one rated `opponent`
+pub(crate) fn expected_score(rating: f64, opponent: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent - rating) / 400.0))
+}
+
+/// Compare how two snakes did in a game. Greater means `a` did better
+///
+/// The winner beats everyone, then snakes that survived to the end beat the ones that died, and
+/// otherwise the snake that died later did better
+fn placement(result: &GameResult, a: &SnakeResult, b: &SnakeResult) -> Ordering {
+    let is_winner = |s: &SnakeResult| result.winner.as_ref() == Some(&s.name);
+    let survived_until = |s: &SnakeResult| s.death_turn.unwrap_or(i64::MAX);
+
+    is_winner(a)
+        .cmp(&is_winner(b))
+        .then_with(|| survived_until(a).cmp(&survived_until(b)))
+}
+
+impl Ratings {
+    pub(crate) fn from_results(results: &[GameResult]) -> Self {
+        let mut ratings = Self::default();
+        for result in results {
+            ratings.record(result);
+        }
+
+        ratings
+    }
+
+    /// Update the ratings with the outcome of a single game
+    pub(crate) fn record(&mut self, result: &GameResult) {
+        // The same server can play itself, but a snake can't gain or lose rating against itself
+        let snakes = result.snakes.iter().unique_by(|s| &s.name).collect_vec();
+        if snakes.len() < 2 {
+            return;
+        }
+
+        let k = K_FACTOR / (snakes.len() - 1) as f64;
+        let before: HashMap<&str, f64> = snakes
+            .iter()
+            .map(|s| (s.name.as_str(), self.get(&s.name).elo))
+            .collect();
+
+        for snake in &snakes {
+            let mut change = 0.0;
+            let mut points = 0.0;
+
+            for opponent in snakes.iter().filter(|o| o.name != snake.name) {
+                let actual = match placement(result, snake, opponent) {
+                    Ordering::Greater => 1.0,
+                    Ordering::Equal => 0.5,
+                    Ordering::Less => 0.0,
+                };
+                let expected =
+                    expected_score(before[snake.name.as_str()], before[opponent.name.as_str()]);
+
+                change += k * (actual - expected);
+                points += actual;
+            }
+
+            let rating = self.ratings.entry(snake.name.clone()).or_default();
+            rating.elo += change;
+            rating.games += 1;
+
+            // Across all the matchups we count it as a win if we did better than half of the
+            // field, so a duel maps to a plain win, draw or loss
+            let half = (snakes.len() - 1) as f64 / 2.0;
+            match points.partial_cmp(&half) {
+                Some(Ordering::Greater) => rating.wins += 1,
+                Some(Ordering::Less) => rating.losses += 1,
+                _ => rating.draws += 1,
+            }
+        }
+    }
+
+    /// The rating for the given snake, or the initial rating if it hasn't played yet
+    pub(crate) fn get(&self, name: &str) -> Rating {
+        self.ratings.get(name).cloned().unwrap_or_default()
+    }
+
+    /// Every rated snake, best first
+    pub(crate) fn standings(&self) -> Vec<(&str, &Rating)> {
+        self.ratings
+            .iter()
+            .map(|(name, rating)| (name.as_str(), rating))
+            .sorted_by(|(_, a), (_, b)| b.elo.total_cmp(&a.elo))
+            .collect()
+    }
+
+    /// Print the standings as a table, limited to the given snakes if there are any
+    pub(crate) fn print_standings(&self, only: &[String]) {
+        println!(
+            "{:<32} {:>7} {:>6} {:>6} {:>6} {:>6}",
+            "Snake", "Elo", "Games", "Wins", "Draws", "Losses"
+        );
+        for (name, rating) in self.standings() {
+            if !only.is_empty() && !only.iter().any(|o| o == name) {
+                continue;
+            }
+
+            println!(
+                "{:<32} {:>7.1} {:>6} {:>6} {:>6} {:>6}",
+                name, rating.elo, rating.games, rating.wins, rating.draws, rating.losses
+            );
+        }
+    }
+}
@@ -0,0 +1,478 @@
+use std::{
+    collections::HashSet,
+    fs::{read_to_string, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The outcome for a single snake in a game
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SnakeResult {
+    pub name: String,
+    pub id: String,
+    /// The turn this snake was eliminated on, None if they survived to the end
+    pub death_turn: Option<i64>,
+    /// Why this snake was eliminated, as reported by the engine
+    pub death_cause: Option<String>,
+}
+
+/// A single row in the results database
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct GameResult {
+    pub game_id: String,
+    pub ruleset: String,
+    pub map: Option<String>,
+    pub width: i64,
+    pub height: i64,
+    pub turns: i64,
+    /// The name of the winning snake. None when the game was a draw
+    pub winner: Option<String>,
+    pub snakes: Vec<SnakeResult>,
+}
+
+impl GameResult {
+    /// Derive the outcome of a game from the game details the engine returns from
+    /// `/games/{game_id}`
+    pub(crate) fn from_game_details(details: &Value) -> Result<Self> {
+        let game = &details["Game"];
+        let last_frame = &details["LastFrame"];
+
+        let game_id = game["ID"]
+            .as_str()
+            .ok_or_else(|| eyre!("Missing Game ID"))?
+            .to_string();
+        let ruleset = game["Ruleset"]["name"]
+            .as_str()
+            .ok_or_else(|| eyre!("Missing Ruleset Name"))?
+            .to_string();
+        let map = game["Map"].as_str().map(|x| x.to_string());
+        let width = game["Width"]
+            .as_i64()
+            .ok_or_else(|| eyre!("Missing Width"))?;
+        let height = game["Height"]
+            .as_i64()
+            .ok_or_else(|| eyre!("Missing Height"))?;
+        let turns = last_frame["Turn"]
+            .as_i64()
+            .ok_or_else(|| eyre!("Missing Turn in LastFrame"))?;
+
+        let snakes = last_frame["Snakes"]
+            .as_array()
+            .ok_or_else(|| eyre!("Missing Snakes in LastFrame"))?
+            .iter()
+            .map(|snake| {
+                Ok(SnakeResult {
+                    name: snake["Name"]
+                        .as_str()
+                        .ok_or_else(|| eyre!("Missing Snake Name"))?
+                        .to_string(),
+                    id: snake["ID"]
+                        .as_str()
+                        .ok_or_else(|| eyre!("Missing Snake ID"))?
+                        .to_string(),
+                    death_turn: snake["Death"]["Turn"].as_i64(),
+                    death_cause: snake["Death"]["Cause"].as_str().map(|x| x.to_string()),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut survivors = snakes.iter().filter(|s| s.death_turn.is_none());
+        let winner = match (survivors.next(), survivors.next()) {
+            (Some(only_survivor), None) => Some(only_survivor.name.clone()),
+            _ => None,
+        };
+
+        Ok(Self {
+            game_id,
+            ruleset,
+            map,
+            width,
+            height,
+            turns,
+            winner,
+            snakes,
+        })
+    }
+
+    /// Derive the outcome of a game from an archive created by `sherlock archive`
+    pub(crate) fn from_archive(game_dir: &Path) -> Result<Self> {
+        let info = read_to_string(game_dir.join("info.json"))
+            .wrap_err_with(|| format!("Couldn't read the archive in {}", game_dir.display()))?;
+        let details: Value = serde_json::from_str(&info)?;
+
+        Self::from_game_details(&details)
+    }
+
+    /// Derive the outcome of a game from the `--output` file of the official Battlesnake CLI
+    ///
+    /// The first line is the game info, then there is one line per turn with the request sent to
+    /// the snakes and the last line is the result of the game
+    pub(crate) fn from_cli_output(output: &str) -> Result<Self> {
+        let lines = output
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(serde_json::from_str::<Value>)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let (game, rest) = lines
+            .split_first()
+            .ok_or_else(|| eyre!("The engine output was empty"))?;
+        let (outcome, frames) = rest
+            .split_last()
+            .ok_or_else(|| eyre!("The engine output didn't include a result"))?;
+        let first_frame = frames
+            .first()
+            .ok_or_else(|| eyre!("The engine output didn't include any turns"))?;
+        let last_frame = frames.last().unwrap_or(first_frame);
+
+        let game_id = game["id"]
+            .as_str()
+            .ok_or_else(|| eyre!("Missing Game ID"))?
+            .to_string();
+        let ruleset = game["ruleset"]["name"]
+            .as_str()
+            .ok_or_else(|| eyre!("Missing Ruleset Name"))?
+            .to_string();
+        let map = game["map"].as_str().map(|x| x.to_string());
+        let width = last_frame["board"]["width"]
+            .as_i64()
+            .ok_or_else(|| eyre!("Missing Width"))?;
+        let height = last_frame["board"]["height"]
+            .as_i64()
+            .ok_or_else(|| eyre!("Missing Height"))?;
+        let turns = last_frame["turn"]
+            .as_i64()
+            .ok_or_else(|| eyre!("Missing Turn in the last frame"))?;
+
+        // The engine only includes living snakes in each frame, so a snake died on the first turn
+        // it's missing from
+        let alive_on_turn = |frame: &Value, id: &str| {
+            frame["board"]["snakes"]
+                .as_array()
+                .map(|snakes| snakes.iter().any(|s| s["id"].as_str() == Some(id)))
+                .unwrap_or(false)
+        };
+        let snakes = first_frame["board"]["snakes"]
+            .as_array()
+            .ok_or_else(|| eyre!("Missing Snakes in the first frame"))?
+            .iter()
+            .map(|snake| {
+                let id = snake["id"]
+                    .as_str()
+                    .ok_or_else(|| eyre!("Missing Snake ID"))?;
+                let death_turn = frames
+                    .iter()
+                    .find(|frame| !alive_on_turn(frame, id))
+                    .and_then(|frame| frame["turn"].as_i64());
+
+                Ok(SnakeResult {
+                    name: snake["name"]
+                        .as_str()
+                        .ok_or_else(|| eyre!("Missing Snake Name"))?
+                        .to_string(),
+                    id: id.to_string(),
+                    death_turn,
+                    death_cause: None,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let winner = if outcome["isDraw"].as_bool().unwrap_or(false) {
+            None
+        } else {
+            outcome["winnerName"]
+                .as_str()
+                .filter(|name| !name.is_empty())
+                .map(|x| x.to_string())
+        };
+
+        Ok(Self {
+            game_id,
+            ruleset,
+            map,
+            width,
+            height,
+            turns,
+            winner,
+            snakes,
+        })
+    }
+}
+
+/// A very small local database of game results
+///
+/// This is stored as a JSONL file, one [GameResult] per line, so it is easy to append to and easy
+/// to poke at with `jq`
+#[derive(Debug, Clone)]
+pub(crate) struct ResultsDatabase {
+    path: PathBuf,
+}
+
+impl ResultsDatabase {
+    /// The results database lives alongside the archived games
+    pub(crate) fn for_archive_dir(archive_dir: &Path) -> Self {
+        Self {
+            path: archive_dir.join("results.jsonl"),
+        }
+    }
+
+    pub(crate) fn all(&self) -> Result<Vec<GameResult>> {
+        if !self.path.is_file() {
+            return Ok(vec![]);
+        }
+
+        let file = File::open(&self.path)?;
+        BufReader::new(file)
+            .lines()
+            .filter(|line| !matches!(line, Ok(l) if l.trim().is_empty()))
+            .map(|line| Ok(serde_json::from_str(&line?)?))
+            .collect()
+    }
+
+    pub(crate) fn game_ids(&self) -> Result<HashSet<String>> {
+        Ok(self.all()?.into_iter().map(|r| r.game_id).collect())
+    }
+
+    /// Append the given results, skipping any games that are already in the database
+    ///
+    /// Returns the number of results that were written
+    pub(crate) fn insert_all(&self, results: &[GameResult]) -> Result<usize> {
+        let mut existing = self.game_ids()?;
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+
+        let mut written = 0;
+        for result in results {
+            if !existing.insert(result.game_id.clone()) {
+                continue;
+            }
+
+            writeln!(file, "{}", serde_json::to_string(result)?)?;
+            written += 1;
+        }
+
+        Ok(written)
+    }
+
+    /// Write the given results, replacing the rows of any games that are already in the database
+    /// instead of skipping them, for when a game is imported again on purpose
+    ///
+    /// Returns the number of results that were written
+    pub(crate) fn upsert_all(&self, results: &[GameResult]) -> Result<usize> {
+        let mut rows = self.all()?;
+
+        for result in results {
+            match rows.iter_mut().find(|r| r.game_id == result.game_id) {
+                Some(row) => *row = result.clone(),
+                None => rows.push(result.clone()),
+            }
+        }
+        self.replace_with(&rows)?;
+
+        Ok(results.len())
+    }
+
+    /// Rewrite the database without any duplicate games, if there are any
+    ///
+    /// Returns the number of rows that were dropped
+    pub(crate) fn compact(&self) -> Result<usize> {
+        let all = self.all()?;
+
+        let mut seen = HashSet::new();
+        let unique: Vec<&GameResult> = all
+            .iter()
+            .filter(|r| seen.insert(r.game_id.as_str()))
+            .collect();
+        let dropped = all.len() - unique.len();
+        if dropped == 0 {
+            return Ok(0);
+        }
+
+        self.replace_with(unique)?;
+
+        Ok(dropped)
+    }
+
+    /// Rewrite the database with exactly these rows
+    ///
+    /// The new file is written next to the old one and then moved over it, so the database is
+    /// never left half written
+    fn replace_with<'a>(&self, rows: impl IntoIterator<Item = &'a GameResult>) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let compacted_path = self.path.with_extension("jsonl.compacting");
+        {
+            let mut file = File::create(&compacted_path)?;
+            for result in rows {
+                writeln!(file, "{}", serde_json::to_string(result)?)?;
+            }
+        }
+        std::fs::rename(&compacted_path, &self.path)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    /// What the engine returns from `/games/{game_id}` for a duel `hovering-hobbs` won on turn 87
+    fn game_details(game_id: &str) -> Value {
+        json!({
+            "Game": {
+                "ID": game_id,
+                "Status": "complete",
+                "Width": 11,
+                "Height": 11,
+                "Ruleset": { "name": "standard", "foodSpawnChance": "15", "minimumFood": "1" },
+                "SnakeTimeout": 500,
+                "Source": "ladder",
+                "Map": "standard",
+            },
+            "LastFrame": {
+                "Turn": 87,
+                "Snakes": [
+                    {
+                        "ID": "gs_hobbs",
+                        "Name": "hovering-hobbs",
+                        "Health": 72,
+                        "Death": null,
+                    },
+                    {
+                        "ID": "gs_other",
+                        "Name": "other-snake",
+                        "Health": 0,
+                        "Death": { "Cause": "head-collision", "Turn": 87, "EliminatedBy": "gs_hobbs" },
+                    },
+                ],
+            },
+        })
+    }
+
+    #[test]
+    fn test_from_game_details() {
+        let result = GameResult::from_game_details(&game_details("a-duel")).unwrap();
+
+        assert_eq!(
+            result,
+            GameResult {
+                game_id: "a-duel".to_owned(),
+                ruleset: "standard".to_owned(),
+                map: Some("standard".to_owned()),
+                width: 11,
+                height: 11,
+                turns: 87,
+                winner: Some("hovering-hobbs".to_owned()),
+                snakes: vec![
+                    SnakeResult {
+                        name: "hovering-hobbs".to_owned(),
+                        id: "gs_hobbs".to_owned(),
+                        death_turn: None,
+                        death_cause: None,
+                    },
+                    SnakeResult {
+                        name: "other-snake".to_owned(),
+                        id: "gs_other".to_owned(),
+                        death_turn: Some(87),
+                        death_cause: Some("head-collision".to_owned()),
+                    },
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn test_from_game_details_without_a_survivor_is_a_draw() {
+        let mut details = game_details("a-draw");
+        details["LastFrame"]["Snakes"][0]["Death"] =
+            json!({ "Cause": "head-collision", "Turn": 87, "EliminatedBy": "gs_other" });
+
+        let result = GameResult::from_game_details(&details).unwrap();
+        assert_eq!(result.winner, None);
+
+        details["Game"]["Width"] = Value::Null;
+        assert!(GameResult::from_game_details(&details).is_err());
+    }
+
+    #[test]
+    fn test_from_cli_output() {
+        // The `--output` of `battlesnake play` for a duel where devious-devin ran off the board on
+        // turn 2
+        let output = include_str!("../fixtures/arena_duel.jsonl");
+        let result = GameResult::from_cli_output(output).unwrap();
+
+        assert_eq!(
+            result,
+            GameResult {
+                game_id: "4e1f3c8a-0d5b-4b7e-9a51-2f6c9d1e7b30".to_owned(),
+                ruleset: "standard".to_owned(),
+                map: Some("standard".to_owned()),
+                width: 11,
+                height: 11,
+                turns: 2,
+                winner: Some("hovering-hobbs".to_owned()),
+                snakes: vec![
+                    SnakeResult {
+                        name: "hovering-hobbs".to_owned(),
+                        id: "gs_hobbs_6a2f".to_owned(),
+                        death_turn: None,
+                        death_cause: None,
+                    },
+                    SnakeResult {
+                        name: "devious-devin".to_owned(),
+                        id: "gs_devin_91c4".to_owned(),
+                        death_turn: Some(2),
+                        death_cause: None,
+                    },
+                ],
+            }
+        );
+
+        let draw = output.replace(
+            r#"{"winnerId":"gs_hobbs_6a2f","winnerName":"hovering-hobbs","isDraw":false}"#,
+            r#"{"winnerId":"","winnerName":"","isDraw":true}"#,
+        );
+        assert_eq!(GameResult::from_cli_output(&draw).unwrap().winner, None);
+
+        assert!(GameResult::from_cli_output("").is_err());
+    }
+
+    #[test]
+    fn test_upsert_replaces_the_rows_insert_skips() {
+        let dir = std::env::temp_dir().join(format!("sherlock-results-{}", std::process::id()));
+        let db = ResultsDatabase::for_archive_dir(&dir);
+        let _ = std::fs::remove_file(&db.path);
+
+        let first = GameResult::from_game_details(&game_details("first")).unwrap();
+        let second = GameResult::from_game_details(&game_details("second")).unwrap();
+        assert_eq!(db.insert_all(&[first.clone(), second.clone()]).unwrap(), 2);
+
+        let fixed = GameResult {
+            winner: None,
+            ..first.clone()
+        };
+        assert_eq!(db.insert_all(&[fixed.clone()]).unwrap(), 0);
+        assert_eq!(db.all().unwrap(), vec![first.clone(), second.clone()]);
+
+        let third = GameResult::from_game_details(&game_details("third")).unwrap();
+        assert_eq!(db.upsert_all(&[fixed.clone(), third.clone()]).unwrap(), 2);
+        assert_eq!(db.all().unwrap(), vec![fixed, second, third]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,11 +1,27 @@
-use color_eyre::eyre::{eyre, Result, WrapErr};
+use std::{
+    collections::BTreeMap,
+    fs::{create_dir_all, read_to_string, write},
+    ops::Range,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread::{self, sleep},
+    time::Duration,
+};
 
-use serde::{Deserialize, Serialize};
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use itertools::Itertools;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value;
 
 use battlesnake_game_types::wire_representation::{
     BattleSnake, Board, Game, NestedGame, Position, Ruleset, Settings,
 };
+use battlesnake_rs::extra_settings::{self, ExtraSettings, SquadSettings};
+
+/// The engine keeps every ruleset setting as a string, these are the ones that are flags
+fn ruleset_flag(game: &Value, name: &str) -> bool {
+    game["Ruleset"][name].as_str() == Some("true")
+}
 
 fn frame_to_nested_game(game: &Value) -> Result<NestedGame> {
     let id = game["ID"]
@@ -47,6 +63,24 @@ fn frame_to_nested_game(game: &Value) -> Result<NestedGame> {
         royale: None,
     };
 
+    // Settings has nowhere to keep these, so they go where our snakes look for them
+    let squad = SquadSettings {
+        allow_body_collisions: ruleset_flag(game, "allowBodyCollisions"),
+        shared_elimination: ruleset_flag(game, "sharedElimination"),
+        shared_health: ruleset_flag(game, "sharedHealth"),
+        shared_length: ruleset_flag(game, "sharedLength"),
+    };
+    extra_settings::observe(
+        &id,
+        ExtraSettings {
+            shrink_every_n_turns: game["Ruleset"]["shrinkEveryNTurns"]
+                .as_str()
+                .and_then(|n| n.parse().ok())
+                .filter(|n| *n > 0),
+            squad: (ruleset_name == "squad" || squad != SquadSettings::default()).then_some(squad),
+        },
+    );
+
     let ruleset = Ruleset {
         name: ruleset_name,
         version: ruleset_version,
@@ -176,15 +210,158 @@ pub(crate) fn frame_to_game(frame: &Value, game: &Value, you_name: &str) -> Resu
     })
 }
 
+/// The engine won't give us more frames than this in one request
+const FRAMES_PER_REQUEST: usize = 100;
+
+/// How long we wait before the first retry, it doubles after every retry after that
+const RETRY_BACKOFF: Duration = Duration::from_millis(250);
+
+/// What went wrong talking to the engine API, once we've given up on retrying
+#[derive(Debug)]
+pub(crate) enum EngineApiError {
+    /// The engine doesn't know about it, usually because the game has been deleted
+    NotFound { url: String },
+    /// The engine answered with an error
+    Status { url: String, code: u16 },
+    /// We never got an answer
+    Transport { url: String, message: String },
+    /// The engine answered with something we couldn't parse
+    InvalidBody { url: String, message: String },
+}
+
+impl EngineApiError {
+    /// Whether trying again later might work
+    fn is_transient(&self) -> bool {
+        match self {
+            EngineApiError::Status { code, .. } => *code == 429 || *code >= 500,
+            EngineApiError::Transport { .. } => true,
+            EngineApiError::NotFound { .. } | EngineApiError::InvalidBody { .. } => false,
+        }
+    }
+}
+
+impl std::fmt::Display for EngineApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EngineApiError::NotFound { url } => write!(f, "{url} was not found"),
+            EngineApiError::Status { url, code } => write!(f, "{url} responded with {code}"),
+            EngineApiError::Transport { url, message } => {
+                write!(f, "Couldn't reach {url}: {message}")
+            }
+            EngineApiError::InvalidBody { url, message } => {
+                write!(f, "{url} responded with an invalid body: {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for EngineApiError {}
+
+/// Reads how many times to retry a request to the engine from `ENGINE_API_RETRIES`, defaulting to 4
+fn engine_api_retries() -> usize {
+    match std::env::var("ENGINE_API_RETRIES").map(|x| x.parse()) {
+        Ok(Ok(x)) => x,
+        _ => 4,
+    }
+}
+
+/// Reads how many requests for frames we make at once from `ENGINE_API_CONCURRENCY`, defaulting
+/// to 4
+fn engine_api_concurrency() -> usize {
+    match std::env::var("ENGINE_API_CONCURRENCY").map(|x| x.parse()) {
+        Ok(Ok(x)) if x > 0 => x,
+        _ => 4,
+    }
+}
+
+/// GET something from the engine, retrying with backoff when the failure looks transient
+fn engine_get<T: DeserializeOwned>(url: &str) -> Result<T, EngineApiError> {
+    let mut backoff = RETRY_BACKOFF;
+    let mut retries_left = engine_api_retries();
+
+    loop {
+        let error = match ureq::get(url).call() {
+            Ok(response) => {
+                return response
+                    .into_json()
+                    .map_err(|e| EngineApiError::InvalidBody {
+                        url: url.to_owned(),
+                        message: e.to_string(),
+                    })
+            }
+            Err(ureq::Error::Status(404, _)) => EngineApiError::NotFound {
+                url: url.to_owned(),
+            },
+            Err(ureq::Error::Status(code, _)) => EngineApiError::Status {
+                url: url.to_owned(),
+                code,
+            },
+            Err(ureq::Error::Transport(transport)) => EngineApiError::Transport {
+                url: url.to_owned(),
+                message: transport.to_string(),
+            },
+        };
+
+        if !error.is_transient() || retries_left == 0 {
+            return Err(error);
+        }
+
+        eprintln!("{error}, retrying in {backoff:?}");
+        sleep(backoff);
+        backoff *= 2;
+        retries_left -= 1;
+    }
+}
+
+/// The details of a game, which includes its last frame
+pub(crate) fn get_game_details(game_id: &str) -> Result<Value, EngineApiError> {
+    engine_get(&format!("https://engine.battlesnake.com/games/{game_id}"))
+}
+
+/// The frames we've already downloaded, so running another command against the same game doesn't
+/// download them again
+///
+/// Frames don't change once they're played, so they can be kept forever. They live in
+/// `SHERLOCK_FRAME_CACHE`, which defaults to `sherlock-frames` in the temp dir, with a directory
+/// for each game
+struct FrameCache {
+    dir: PathBuf,
+}
+
+impl FrameCache {
+    fn new(game_id: &str) -> Self {
+        let root = std::env::var("SHERLOCK_FRAME_CACHE")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| std::env::temp_dir().join("sherlock-frames"));
+
+        Self {
+            dir: root.join(game_id),
+        }
+    }
+
+    fn path(&self, turn: usize) -> PathBuf {
+        self.dir.join(format!("{turn}.json"))
+    }
+
+    fn read(&self, turn: usize) -> Option<Value> {
+        let contents = read_to_string(self.path(turn)).ok()?;
+
+        serde_json::from_str(&contents).ok()
+    }
+
+    fn write(&self, turn: usize, frame: &Value) -> Result<()> {
+        create_dir_all(&self.dir)?;
+        write(self.path(turn), serde_json::to_string(frame)?)?;
+
+        Ok(())
+    }
+}
+
 pub(crate) fn get_frame_for_turn(game_id: &str, turn: i32) -> Result<Value> {
-    let body: Value = ureq::get(
-        format!("https://engine.battlesnake.com/games/{game_id}/frames?offset={turn}&limit=1",)
-            .as_str(),
-    )
-    .call()?
-    .into_json()?;
+    let turn: usize = turn.try_into().wrap_err("Turn can't be negative")?;
+    let frames = get_frames(game_id, turn..turn + 1)?;
 
-    Ok(body["Frames"][0].clone())
+    Ok(frames.into_iter().next().unwrap_or_default())
 }
 
 #[derive(Serialize, Deserialize)]
@@ -198,28 +375,92 @@ pub(crate) fn get_batch_of_frames_for_games(
     offset: usize,
     limit: usize,
 ) -> Result<Option<Vec<Value>>> {
-    Ok(ureq::get(&format!(
+    Ok(engine_get::<FrameResponse>(&format!(
         "https://engine.battlesnake.com/games/{game_id}/frames?offset={offset}&limit={limit}",
-    ))
-    .call()?
-    .into_json::<FrameResponse>()?
+    ))?
     .frames)
 }
 
-pub(crate) fn get_frames_for_game(game_id: &str, end_turn: usize) -> Result<Vec<Value>> {
-    const LIMIT: usize = 100;
-    let mut offset = 0;
+/// Split the turns we don't have into requests of consecutive turns, as `(offset, limit)`
+fn missing_batches(missing: impl Iterator<Item = usize>) -> Vec<(usize, usize)> {
+    let mut batches: Vec<(usize, usize)> = vec![];
+
+    for turn in missing {
+        match batches.last_mut() {
+            Some((offset, limit)) if *offset + *limit == turn && *limit < FRAMES_PER_REQUEST => {
+                *limit += 1
+            }
+            _ => batches.push((turn, 1)),
+        }
+    }
 
-    let mut all_frames: Vec<Value> = Vec::with_capacity(end_turn);
+    batches
+}
+
+/// Download the batches on [engine_api_concurrency] threads
+fn fetch_batches(game_id: &str, batches: &[(usize, usize)]) -> Result<Vec<Value>> {
+    let next_batch = AtomicUsize::new(0);
+
+    thread::scope(|scope| {
+        let workers = (0..engine_api_concurrency().min(batches.len()))
+            .map(|_| {
+                scope.spawn(|| -> Result<Vec<Value>> {
+                    let mut frames = vec![];
+
+                    while let Some((offset, limit)) =
+                        batches.get(next_batch.fetch_add(1, Ordering::Relaxed))
+                    {
+                        frames.extend(
+                            get_batch_of_frames_for_games(game_id, *offset, *limit)?
+                                .unwrap_or_default(),
+                        );
+                    }
+
+                    Ok(frames)
+                })
+            })
+            .collect_vec();
+
+        let mut frames = vec![];
+        for worker in workers {
+            frames.extend(worker.join().expect("A frame fetching thread panicked")?);
+        }
+
+        Ok(frames)
+    })
+}
 
-    while let Some(frames) = get_batch_of_frames_for_games(game_id, offset, LIMIT)?
-        && !frames.is_empty()
-    {
-        all_frames.extend(frames.iter().cloned());
-        offset += LIMIT;
+/// The frames for the given turns, sorted by turn
+///
+/// Frames come from the [FrameCache] when we have them, and the rest are downloaded in batches at
+/// the same time. Turns the game doesn't have yet are left out
+pub(crate) fn get_frames(game_id: &str, turns: Range<usize>) -> Result<Vec<Value>> {
+    let cache = FrameCache::new(game_id);
+
+    let mut frames: BTreeMap<usize, Value> = turns
+        .clone()
+        .filter_map(|turn| cache.read(turn).map(|frame| (turn, frame)))
+        .collect();
+    let batches = missing_batches(turns.clone().filter(|turn| !frames.contains_key(turn)));
+
+    for frame in fetch_batches(game_id, &batches)? {
+        let Some(turn) = frame["Turn"].as_u64().map(|t| t as usize) else {
+            continue;
+        };
+        if !turns.contains(&turn) {
+            continue;
+        }
+
+        // A frame we couldn't cache is just downloaded again next time
+        if let Err(e) = cache.write(turn, &frame) {
+            eprintln!("Couldn't cache frame {turn} of {game_id}: {e}");
+        }
+        frames.insert(turn, frame);
     }
 
-    all_frames.sort_by_key(|f| f["Turn"].as_u64());
+    Ok(frames.into_values().collect())
+}
 
-    Ok(all_frames)
+pub(crate) fn get_frames_for_game(game_id: &str, end_turn: usize) -> Result<Vec<Value>> {
+    get_frames(game_id, 0..end_turn + 1)
 }
@@ -0,0 +1,176 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        OnceLock,
+    },
+};
+
+use serde::Serialize;
+use tokio::sync::Notify;
+
+use crate::*;
+
+/// How many records each writer holds before it starts dropping the oldest ones
+///
+/// This is read from `BACKGROUND_WRITER_CAPACITY` and defaults to 1,000
+fn writer_capacity() -> usize {
+    match std::env::var("BACKGROUND_WRITER_CAPACITY").map(|x| x.parse()) {
+        Ok(Ok(x)) => x,
+        _ => 1_000,
+    }
+}
+
+/// How a single [BackgroundWriter] is keeping up, served from `/debug/writers`
+#[derive(Debug, Default)]
+pub(crate) struct WriterMetrics {
+    queued: AtomicUsize,
+    written: AtomicUsize,
+    failed: AtomicUsize,
+    dropped: AtomicUsize,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub(crate) struct WriterMetricsSnapshot {
+    /// Records waiting to be written
+    pub queued: usize,
+    pub written: usize,
+    /// Records the write function returned an error for
+    pub failed: usize,
+    /// Records that were pushed out of a full queue before they could be written
+    pub dropped: usize,
+}
+
+impl WriterMetrics {
+    fn snapshot(&self) -> WriterMetricsSnapshot {
+        WriterMetricsSnapshot {
+            queued: self.queued.load(Ordering::Relaxed),
+            written: self.written.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Every writer that has been started, by name, so their metrics can be served together
+static WRITER_METRICS: OnceLock<Mutex<BTreeMap<&'static str, Arc<WriterMetrics>>>> =
+    OnceLock::new();
+
+fn writer_metrics() -> &'static Mutex<BTreeMap<&'static str, Arc<WriterMetrics>>> {
+    WRITER_METRICS.get_or_init(Default::default)
+}
+
+/// Writes records out on a background task, so a move handler never waits on the disk
+///
+/// Sending a record only pushes it onto a bounded queue. When the queue is full the oldest record
+/// is dropped to make room, since a writer that has fallen that far behind is better off with the
+/// records from right now, and the drop is counted in its [WriterMetrics]. The write function
+/// itself runs on the blocking pool, one batch of records at a time
+pub(crate) struct BackgroundWriter<T> {
+    name: &'static str,
+    queue: Arc<Mutex<VecDeque<T>>>,
+    capacity: usize,
+    wake: Arc<Notify>,
+    metrics: Arc<WriterMetrics>,
+}
+
+impl<T: Send + 'static> BackgroundWriter<T> {
+    /// Start the task that writes the records. This needs to be called from inside the runtime
+    pub fn spawn<F>(name: &'static str, write: F) -> Self
+    where
+        F: Fn(T) -> Result<()> + Send + Sync + 'static,
+    {
+        let writer = Self {
+            name,
+            queue: Default::default(),
+            capacity: writer_capacity(),
+            wake: Default::default(),
+            metrics: Default::default(),
+        };
+        writer_metrics().lock().insert(name, writer.metrics.clone());
+
+        let queue = writer.queue.clone();
+        let wake = writer.wake.clone();
+        let metrics = writer.metrics.clone();
+        let write = Arc::new(write);
+        tokio::spawn(async move {
+            loop {
+                wake.notified().await;
+
+                loop {
+                    let batch: Vec<T> = queue.lock().drain(..).collect();
+                    if batch.is_empty() {
+                        break;
+                    }
+                    metrics.queued.fetch_sub(batch.len(), Ordering::Relaxed);
+
+                    let write = write.clone();
+                    let metrics = metrics.clone();
+                    let written = tokio::task::spawn_blocking(move || {
+                        for record in batch {
+                            match write(record) {
+                                Ok(()) => metrics.written.fetch_add(1, Ordering::Relaxed),
+                                Err(e) => {
+                                    tracing::error!(
+                                        writer = name,
+                                        error = %e,
+                                        "Couldn't write a record"
+                                    );
+                                    metrics.failed.fetch_add(1, Ordering::Relaxed)
+                                }
+                            };
+                        }
+                    })
+                    .await;
+
+                    if let Err(e) = written {
+                        tracing::error!(writer = name, error = %e, "A background write panicked");
+                    }
+                }
+            }
+        });
+
+        writer
+    }
+
+    /// Queue a record to be written, dropping the oldest queued record if the queue is full
+    pub fn send(&self, record: T) {
+        if self.capacity == 0 {
+            self.metrics.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+
+        {
+            let mut queue = self.queue.lock();
+            if queue.len() >= self.capacity {
+                queue.pop_front();
+                let dropped = self.metrics.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+
+                // Only every so often, a writer that is behind would otherwise log on every send
+                if dropped.is_power_of_two() {
+                    tracing::warn!(
+                        writer = self.name,
+                        dropped,
+                        "Background writer is full, dropped the oldest record"
+                    );
+                }
+            } else {
+                self.metrics.queued.fetch_add(1, Ordering::Relaxed);
+            }
+            queue.push_back(record);
+        }
+
+        self.wake.notify_one();
+    }
+}
+
+/// The metrics of every background writer that has been started
+pub(crate) async fn route_writer_metrics() -> Json<BTreeMap<&'static str, WriterMetricsSnapshot>> {
+    Json(
+        writer_metrics()
+            .lock()
+            .iter()
+            .map(|(name, metrics)| (*name, metrics.snapshot()))
+            .collect(),
+    )
+}
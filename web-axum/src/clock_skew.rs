@@ -0,0 +1,169 @@
+use std::time::{Duration, Instant};
+
+use crate::*;
+
+/// Gaps up to this long are a normal network round trip, which the snakes' own latency padding
+/// already covers
+///
+/// This is read from `CLOCK_SKEW_ALLOWANCE_MS` and defaults to 50ms
+fn allowance() -> Duration {
+    match std::env::var("CLOCK_SKEW_ALLOWANCE_MS").map(|x| x.parse()) {
+        Ok(Ok(x)) => Duration::from_millis(x),
+        _ => Duration::from_millis(50),
+    }
+}
+
+/// The most we'll ever shave off a game's timeout, so one slow turn from the engine can't starve
+/// the search for the rest of the game
+///
+/// This is read from `CLOCK_SKEW_MAX_SHAVE_MS` and defaults to 150ms
+fn max_shave() -> Duration {
+    match std::env::var("CLOCK_SKEW_MAX_SHAVE_MS").map(|x| x.parse()) {
+        Ok(Ok(x)) => Duration::from_millis(x),
+        _ => Duration::from_millis(150),
+    }
+}
+
+/// Games we haven't answered in this long are assumed to be over, even without an end request
+const STALE_AFTER: Duration = Duration::from_secs(10 * 60);
+
+/// What we've seen of the engine's timing in a single game
+#[derive(Debug, Clone, Copy)]
+struct GameClock {
+    turn: i32,
+    responded_at: Instant,
+    /// The shortest gap we've seen between answering a turn and getting the request for the next
+    overhead: Option<Duration>,
+}
+
+/// Per game estimates of how much of the timeout the engine uses up itself
+///
+/// Between our answer to one turn and the request for the next, the answer has to reach the
+/// engine, the engine has to wait for every snake and build the next board, and the next request
+/// has to reach us. Waiting on slow opponents makes most of those gaps long, but the shortest gap
+/// in a game is a good estimate of the overhead that is always there. Some engines start their
+/// clock before that overhead, so whatever is over a normal round trip comes out of our search
+/// budget for the rest of the game.
+///
+/// Games are keyed by the snake's name as well as the game id, like [AppState]'s game states
+#[derive(Debug, Default)]
+pub(crate) struct EngineClocks {
+    games: GameStore<GameClock>,
+}
+
+impl EngineClocks {
+    /// Note that the request for `turn` arrived `now`, and return how much to shave off its timeout
+    pub fn request_arrived(
+        &mut self,
+        snake_name: &str,
+        game_id: &str,
+        turn: i32,
+        now: Instant,
+    ) -> Duration {
+        let Some(clock) = self.games.get_mut(snake_name, game_id) else {
+            return Duration::ZERO;
+        };
+
+        // Retried or skipped turns don't tell us anything about the gap between two turns
+        if turn == clock.turn + 1 {
+            let gap = now.saturating_duration_since(clock.responded_at);
+            clock.overhead = Some(clock.overhead.map_or(gap, |overhead| overhead.min(gap)));
+        }
+
+        let Some(overhead) = clock.overhead else {
+            return Duration::ZERO;
+        };
+        let shave = overhead.saturating_sub(allowance()).min(max_shave());
+
+        tracing::info!(
+            game_id,
+            turn,
+            engine_overhead_ms = overhead.as_millis() as u64,
+            shaved_ms = shave.as_millis() as u64,
+            "Estimated the engine overhead for this game"
+        );
+
+        shave
+    }
+
+    /// Note that we answered the request for `turn` at `now`
+    pub fn responded(&mut self, snake_name: &str, game_id: &str, turn: i32, now: Instant) {
+        self.games
+            .retain(|clock| now.saturating_duration_since(clock.responded_at) < STALE_AFTER);
+
+        match self.games.get_mut(snake_name, game_id) {
+            Some(clock) => {
+                clock.turn = turn;
+                clock.responded_at = now;
+            }
+            None => self.games.insert(
+                snake_name,
+                game_id,
+                GameClock {
+                    turn,
+                    responded_at: now,
+                    overhead: None,
+                },
+            ),
+        }
+    }
+
+    /// How many games we're keeping clocks for
+    pub fn games(&self) -> usize {
+        self.games.len()
+    }
+
+    pub fn end_game(&mut self, snake_name: &str, game_id: &str) {
+        self.games.remove(snake_name, game_id);
+    }
+}
+
+/// The shortest we'll ever shave a timeout down to
+///
+/// Every snake takes its network latency padding off the timeout, and the largest padding any of
+/// them uses by default is 120ms. This leaves them that and some time to search on top of it
+const MIN_SHAVED_TIMEOUT_MS: i64 = 200;
+
+/// Take `shave` off the game's timeout, never going below [MIN_SHAVED_TIMEOUT_MS]
+///
+/// A timeout that was already shorter than that is left alone
+pub(crate) fn shave_timeout(game: &mut Game, shave: Duration) {
+    let shave: i64 = shave.as_millis().try_into().unwrap_or(i64::MAX);
+    let floor = game.game.timeout.min(MIN_SHAVED_TIMEOUT_MS);
+
+    game.game.timeout = game.game.timeout.saturating_sub(shave).max(floor);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start_of_game() -> Game {
+        serde_json::from_str(include_str!(
+            "../../battlesnake-rs/fixtures/start_of_game.json"
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn shaving_takes_the_overhead_off_the_timeout() {
+        let mut game = start_of_game();
+        shave_timeout(&mut game, Duration::from_millis(120));
+
+        assert_eq!(game.game.timeout, 380);
+    }
+
+    #[test]
+    fn shaving_leaves_room_for_the_padding() {
+        let mut game = start_of_game();
+        shave_timeout(&mut game, Duration::from_millis(499));
+
+        assert_eq!(game.game.timeout, MIN_SHAVED_TIMEOUT_MS);
+
+        let mut game = start_of_game();
+        game.game.timeout = 120;
+        shave_timeout(&mut game, Duration::from_millis(100));
+
+        assert_eq!(game.game.timeout, 120);
+    }
+}
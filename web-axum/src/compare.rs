@@ -0,0 +1,122 @@
+use battlesnake_rs::{move_guard::GuardedFactory, BattlesnakeFactory};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::*;
+
+/// How long each snake gets to pick its move in `/compare` when the request doesn't say
+const DEFAULT_COMPARE_BUDGET_MS: i64 = 200;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct CompareParams {
+    budget_ms: Option<i64>,
+}
+
+/// What one snake made of the board
+#[derive(Debug, Serialize)]
+pub(crate) struct SnakeComparison {
+    snake: String,
+    /// None if the snake errored or panicked, see `error`
+    r#move: Option<String>,
+    /// The score the snake gave the move it picked. Only snakes that search have one
+    score: Option<String>,
+    depth: Option<usize>,
+    iterations: Option<usize>,
+    /// The terms of the score at the end of the line the snake expects, when the server runs with
+    /// `FEATURE_LEAF_BREAKDOWN=true`
+    leaf_breakdown: Option<Vec<(&'static str, f64)>>,
+    elapsed_ms: u128,
+    error: Option<String>,
+}
+
+/// Every snake we can compare, which is every registered snake plus Hobbs. Hobbs is served as a
+/// [ConfiguredSnake] so it isn't in [all_factories], but it's the snake we most want to compare
+/// against
+fn compared_factories() -> Vec<BoxedFactory> {
+    let mut factories = all_factories();
+    factories.push(Box::new(GuardedFactory(Box::new(Factory {}))));
+
+    factories
+}
+
+/// Ask every snake which move it would make on this board, for finding which snakes get a fixture
+/// wrong
+///
+/// Each snake gets `budget_ms` as its timeout, from the query string or
+/// [DEFAULT_COMPARE_BUDGET_MS]. They run one after the other so they aren't fighting each other for
+/// the CPU, which means the whole request takes about the budget times the number of snakes
+pub(crate) async fn route_compare(
+    Query(params): Query<CompareParams>,
+    Json(mut game): Json<Game>,
+) -> JsonResponse<Vec<SnakeComparison>> {
+    game.game.timeout = params.budget_ms.unwrap_or(DEFAULT_COMPARE_BUDGET_MS);
+    let request = serde_json::to_value(&game).map_err(Report::from)?;
+
+    let slot = acquire_search_slot("compare")
+        .await
+        .ok_or_else(|| Report::new(SearchesSaturated))?;
+    let root = span!(tracing::Level::INFO, "compare_snakes");
+    let comparisons = spawn_blocking_with_tracing(move || {
+        let _slot = slot;
+        compared_factories()
+            .iter()
+            .map(|factory| compare_snake(factory.as_ref(), &game, &request))
+            .collect()
+    })
+    .instrument(root)
+    .await?;
+
+    Ok(Json(comparisons))
+}
+
+/// Run a single snake on the board. Errors and panics end up in the comparison instead of failing
+/// the whole request, since a snake that breaks on a board is exactly what we're looking for
+fn compare_snake(
+    factory: &dyn BattlesnakeFactory,
+    game: &Game,
+    request: &Value,
+) -> SnakeComparison {
+    let search_config = json!({
+        "snake": factory.name(),
+        "route": "compare",
+        "timeout": game.game.timeout,
+    });
+
+    let started_at = Instant::now();
+    let explanation = capture_search_panics(request, search_config, || {
+        factory.create_from_wire_game(game.clone()).explain_move()
+    });
+    let elapsed_ms = started_at.elapsed().as_millis();
+
+    match explanation {
+        Ok(explanation) => {
+            let chosen_move = explanation.output.r#move;
+            let score = explanation
+                .root_options
+                .into_iter()
+                .find(|(m, _)| m.to_string() == chosen_move)
+                .map(|(_, score)| score);
+
+            SnakeComparison {
+                snake: factory.name(),
+                r#move: Some(chosen_move),
+                score,
+                depth: explanation.depth,
+                iterations: explanation.iterations,
+                leaf_breakdown: explanation.leaf_breakdown,
+                elapsed_ms,
+                error: None,
+            }
+        }
+        Err(e) => SnakeComparison {
+            snake: factory.name(),
+            r#move: None,
+            score: None,
+            depth: None,
+            iterations: None,
+            leaf_breakdown: None,
+            elapsed_ms,
+            error: Some(e.to_string()),
+        },
+    }
+}
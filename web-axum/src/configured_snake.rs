@@ -0,0 +1,175 @@
+use std::any::Any;
+
+use std::collections::BTreeMap;
+
+use battlesnake_rs::AboutMe;
+use serde::Deserialize;
+
+use crate::*;
+
+/// A snake that needs more than a [battlesnake_rs::BattlesnakeFactory] can give it, like state
+/// that lives for a whole game or a score function and options picked for this server
+///
+/// They are served from the same `/:snake_name` routes as every other snake, see [ExtractSnake],
+/// so a new one only needs to be added to [configured_snakes]
+#[async_trait]
+pub(crate) trait ConfiguredSnake: Send + Sync {
+    fn name(&self) -> String;
+
+    fn about(&self) -> AboutMe;
+
+    /// Make the state this snake keeps for a game. It's stored when the game starts, and the snake
+    /// can get it back with [AppState::game_state_mut]
+    fn start_game(&self, game: &Game) -> Box<dyn Any + Send>;
+
+    /// Make the state for a game that's already going, when a move comes in for a game we never
+    /// saw start. That's what happens when the server restarts in the middle of a game, since the
+    /// states only live in memory. By default this is [ConfiguredSnake::start_game] with the board
+    /// from the move
+    fn resume_game(&self, game: &Game) -> Box<dyn Any + Send> {
+        self.start_game(game)
+    }
+
+    async fn make_move(
+        &self,
+        state: Arc<Mutex<AppState>>,
+        headers: HeaderMap,
+        game: Game,
+    ) -> HttpResponse<AnsweredMove>;
+
+    /// Called once the game is over, with the state that was made for it
+    fn end_game(&self, _game: &Game, _game_state: Box<dyn Any + Send>) {}
+
+    /// Run the same search as `/move`, and return the search tree as a DOT graph instead of a
+    /// move. None if this snake can't be graphed
+    async fn graph(
+        &self,
+        _params: GraphParams,
+        _headers: HeaderMap,
+        _game: Game,
+    ) -> Option<HttpResponse<String>> {
+        None
+    }
+}
+
+/// Every snake that is served through [ConfiguredSnake] instead of its factory
+pub(crate) fn configured_snakes() -> Vec<Arc<dyn ConfiguredSnake>> {
+    vec![Arc::new(Hobbs)]
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct GraphParams {
+    pub depth: Option<usize>,
+}
+
+/// The state every [ConfiguredSnake] keeps for the games it's in, and the [RecentRequests],
+/// [EngineClocks], [RetriedMoves], [ArenaStatus] and [DarkLaunchResults] of every snake
+///
+/// The game states are keyed by the snake's name as well as the game id, since the same server can
+/// have more than one of its snakes in a game. Like everything else kept per game, they're in a
+/// [GameStore] so a game we never see end can't keep them around forever
+pub(crate) struct AppState {
+    game_states: GameStore<Box<dyn Any + Send>>,
+    pub recent_requests: RecentRequests,
+    pub engine_clocks: EngineClocks,
+    pub retried_moves: RetriedMoves,
+    pub status: ArenaStatus,
+    pub dark_launch: DarkLaunchResults,
+    pub turn_histograms: TurnHistograms,
+    /// The latest [MemorySample], None until the first one is taken
+    pub memory: Option<MemorySample>,
+}
+
+impl AppState {
+    pub fn new() -> Self {
+        Self {
+            game_states: GameStore::default(),
+            recent_requests: RecentRequests::from_env(),
+            engine_clocks: EngineClocks::default(),
+            retried_moves: RetriedMoves::default(),
+            status: ArenaStatus::from_env(),
+            dark_launch: DarkLaunchResults::from_env(),
+            turn_histograms: TurnHistograms::default(),
+            memory: None,
+        }
+    }
+
+    /// How much every store that grows with the games we play is holding, for the [MemorySample]s
+    pub fn store_sizes(&self) -> BTreeMap<&'static str, usize> {
+        BTreeMap::from([
+            ("game_states", self.game_states.len()),
+            ("engine_clocks", self.engine_clocks.games()),
+            ("extra_settings_games", extra_settings::observed_games()),
+            ("retried_moves", self.retried_moves.games()),
+            ("active_games", self.status.active_games()),
+            ("recent_requests", self.recent_requests.len()),
+            ("opponent_book_games", opponent_book::watched_games()),
+            ("dueling_dana_games", dueling_dana::kept_games()),
+            ("irene_trees", improbable_irene::games_with_trees()),
+            ("pondering_games", ponder_scheduler::pondering_games()),
+        ])
+    }
+
+    pub fn start_game(&mut self, snake_name: String, game_id: String, state: Box<dyn Any + Send>) {
+        self.game_states.insert(&snake_name, &game_id, state);
+    }
+
+    /// The state the snake made for this game, None if the game never started, has ended or was
+    /// evicted, or if the state is a different type
+    pub fn game_state_mut<T: 'static>(
+        &mut self,
+        snake_name: &str,
+        game_id: &str,
+    ) -> Option<&mut T> {
+        self.game_states
+            .get_mut(snake_name, game_id)?
+            .downcast_mut()
+    }
+
+    /// [AppState::game_state_mut], but a game we never saw start is resumed from this move with
+    /// [ConfiguredSnake::resume_game] first, and counted in the [TurnHistograms]
+    pub fn game_state_or_resume<T: 'static>(
+        &mut self,
+        snake: &dyn ConfiguredSnake,
+        game: &Game,
+    ) -> Option<&mut T> {
+        let (name, game_id) = (snake.name(), &game.game.id);
+        if !self.game_states.contains(&name, game_id) {
+            tracing::warn!(
+                snake = %name,
+                game_id = %game_id,
+                turn = game.turn,
+                "Got a move for a game we never saw start, resuming it from this turn"
+            );
+            self.turn_histograms.record_resumed_game(&name);
+            self.game_states
+                .insert(&name, game_id, snake.resume_game(game));
+        }
+
+        self.game_states.get_mut(&name, game_id)?.downcast_mut()
+    }
+
+    pub fn end_game(&mut self, snake_name: &str, game_id: &str) -> Option<Box<dyn Any + Send>> {
+        self.game_states.remove(snake_name, game_id)
+    }
+}
+
+/// Serve `/:snake_name/graph` for the configured snakes that support it
+pub(crate) async fn route_configured_graph(
+    ExtractSnake(snake): ExtractSnake,
+    Query(params): Query<GraphParams>,
+    headers: HeaderMap,
+    Json(game): Json<Game>,
+) -> Result<String, Response> {
+    let not_found = || (StatusCode::NOT_FOUND, "This snake can't be graphed").into_response();
+
+    let Snake::Configured(snake) = snake else {
+        return Err(not_found());
+    };
+
+    snake
+        .graph(params, headers, game)
+        .await
+        .ok_or_else(not_found)?
+        .map_err(IntoResponse::into_response)
+}
@@ -0,0 +1,274 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::OnceLock,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::*;
+
+/// How long past the game timeout we wait for the candidate before counting its move as failed
+const CANDIDATE_SLACK: Duration = Duration::from_millis(200);
+
+/// A second deployment that gets a copy of every request we're sent, so we can see how often it
+/// would have played the same move as us before we switch the arena over to it
+///
+/// It's read from `DARK_LAUNCH_URL`, the base URL the candidate serves its snakes from, and
+/// without it nothing is forwarded. The candidate's answers never change what we respond with
+pub(crate) struct Candidate {
+    client: reqwest::Client,
+    base_url: String,
+}
+
+static CANDIDATE: OnceLock<Option<Candidate>> = OnceLock::new();
+
+fn candidate() -> Option<&'static Candidate> {
+    CANDIDATE
+        .get_or_init(|| {
+            let base_url = std::env::var("DARK_LAUNCH_URL").ok()?;
+            tracing::info!(base_url = %base_url, "Dark launching against a candidate deployment");
+
+            Some(Candidate {
+                client: reqwest::Client::new(),
+                base_url: base_url.trim_end_matches('/').to_owned(),
+            })
+        })
+        .as_ref()
+}
+
+impl Candidate {
+    fn url(&self, snake: &str, endpoint: &str) -> String {
+        format!("{}/{snake}/{endpoint}", self.base_url)
+    }
+
+    async fn post(
+        &self,
+        snake: &str,
+        endpoint: &str,
+        headers: &HeaderMap,
+        request: &Value,
+        timeout: Duration,
+    ) -> Result<reqwest::Response> {
+        let mut builder = self
+            .client
+            .post(self.url(snake, endpoint))
+            .timeout(timeout)
+            .json(request);
+        if let Some(deadline) = deadline_header(headers) {
+            builder = builder.header(DEADLINE_HEADER, deadline);
+        }
+
+        Ok(builder.send().await?.error_for_status()?)
+    }
+}
+
+/// Send the candidate a copy of a `/start` or `/end` request, so it keeps the same games going
+/// that we do. Nothing waits on it and failures are only logged
+pub(crate) fn forward(snake: &str, endpoint: &'static str, game: &Game) {
+    let Some(candidate) = candidate() else {
+        return;
+    };
+    let Ok(request) = serde_json::to_value(game) else {
+        return;
+    };
+    let snake = snake.to_owned();
+
+    tokio::spawn(async move {
+        let timeout = Duration::from_millis(game_timeout(&request)) + CANDIDATE_SLACK;
+        if let Err(e) = candidate
+            .post(&snake, endpoint, &HeaderMap::new(), &request, timeout)
+            .await
+        {
+            tracing::warn!(snake = %snake, endpoint, error = %e, "The dark launch candidate failed");
+        }
+    });
+}
+
+fn game_timeout(request: &Value) -> u64 {
+    request
+        .pointer("/game/timeout")
+        .and_then(Value::as_u64)
+        .unwrap_or(500)
+}
+
+/// The only part of the candidate's `/move` response we look at
+#[derive(Debug, Deserialize)]
+struct CandidateMove {
+    r#move: String,
+}
+
+/// Send the candidate a copy of a `/move` request, right as it comes in so it has the same time to
+/// answer that we do. None when there is no candidate
+pub(crate) fn forward_move(
+    snake: &str,
+    headers: &HeaderMap,
+    request: &Value,
+) -> Option<JoinHandle<Result<String>>> {
+    let candidate = candidate()?;
+    let snake = snake.to_owned();
+    let headers = headers.clone();
+    let request = request.clone();
+
+    Some(tokio::spawn(async move {
+        let timeout = Duration::from_millis(game_timeout(&request)) + CANDIDATE_SLACK;
+        let output: CandidateMove = candidate
+            .post(&snake, "move", &headers, &request, timeout)
+            .await?
+            .json()
+            .await?;
+
+        Ok(output.r#move)
+    }))
+}
+
+/// A turn where the candidate didn't play the move we did
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct Divergence {
+    snake: String,
+    game_id: String,
+    turn: i32,
+    our_move: String,
+    candidate_move: String,
+    /// The request exactly as the engine sent it, so the position can be replayed
+    request: Value,
+}
+
+/// How often the candidate agreed with one of our snakes
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub(crate) struct Agreement {
+    compared: usize,
+    agreed: usize,
+    /// Moves the candidate errored on or didn't answer in time
+    candidate_failed: usize,
+    agreement_rate: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct DarkLaunchResponse {
+    candidate: Option<String>,
+    agreement: BTreeMap<String, Agreement>,
+    divergences: Vec<Divergence>,
+}
+
+/// How the candidate's moves compared to ours, for `/debug/dark-launch`
+///
+/// `DARK_LAUNCH_DIVERGENCES` sets how many of the latest divergences are kept and defaults to 100
+#[derive(Debug)]
+pub(crate) struct DarkLaunchResults {
+    agreement: HashMap<String, Agreement>,
+    divergences: VecDeque<Divergence>,
+    capacity: usize,
+}
+
+impl DarkLaunchResults {
+    pub fn from_env() -> Self {
+        let capacity = match std::env::var("DARK_LAUNCH_DIVERGENCES").map(|x| x.parse()) {
+            Ok(Ok(x)) => x,
+            _ => 100,
+        };
+
+        Self {
+            agreement: HashMap::new(),
+            divergences: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    fn record(
+        &mut self,
+        snake: &str,
+        game_id: String,
+        turn: i32,
+        request: Value,
+        our_move: String,
+        candidate_move: Result<String>,
+    ) {
+        let agreement = self.agreement.entry(snake.to_owned()).or_default();
+
+        let candidate_move = match candidate_move {
+            Ok(m) => m,
+            Err(e) => {
+                agreement.candidate_failed += 1;
+                tracing::warn!(snake, game_id = %game_id, turn, error = %e, "The dark launch candidate failed");
+
+                return;
+            }
+        };
+
+        let agreed = candidate_move == our_move;
+        agreement.compared += 1;
+        if agreed {
+            agreement.agreed += 1;
+        }
+        agreement.agreement_rate = Some(agreement.agreed as f64 / agreement.compared as f64);
+        if agreed {
+            return;
+        }
+
+        tracing::info!(
+            snake,
+            game_id = %game_id,
+            turn,
+            our_move = %our_move,
+            candidate_move = %candidate_move,
+            "The dark launch candidate diverged"
+        );
+        if self.capacity == 0 {
+            return;
+        }
+        if self.divergences.len() >= self.capacity {
+            self.divergences.pop_back();
+        }
+        self.divergences.push_front(Divergence {
+            snake: snake.to_owned(),
+            game_id,
+            turn,
+            our_move,
+            candidate_move,
+            request,
+        });
+    }
+
+    fn snapshot(&self) -> DarkLaunchResponse {
+        DarkLaunchResponse {
+            candidate: candidate().map(|c| c.base_url.clone()),
+            agreement: self
+                .agreement
+                .iter()
+                .map(|(snake, agreement)| (snake.clone(), *agreement))
+                .collect(),
+            divergences: self.divergences.iter().cloned().collect(),
+        }
+    }
+}
+
+/// Wait for the candidate's move in the background and compare it to the one we answered with
+pub(crate) fn compare_move(
+    state: Arc<Mutex<AppState>>,
+    snake: String,
+    game_id: String,
+    turn: i32,
+    request: Value,
+    our_move: String,
+    candidate_move: JoinHandle<Result<String>>,
+) {
+    tokio::spawn(async move {
+        let candidate_move = candidate_move
+            .await
+            .unwrap_or_else(|e| Err(eyre!(e).wrap_err("The dark launch request panicked")));
+
+        state
+            .lock()
+            .dark_launch
+            .record(&snake, game_id, turn, request, our_move, candidate_move);
+    });
+}
+
+/// How often the candidate deployment agreed with each of our snakes, and the latest positions
+/// where it didn't, newest first
+pub(crate) async fn route_dark_launch(
+    State(state): State<Arc<Mutex<AppState>>>,
+) -> Json<DarkLaunchResponse> {
+    Json(state.lock().dark_launch.snapshot())
+}
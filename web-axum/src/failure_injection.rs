@@ -0,0 +1,41 @@
+use axum::http::HeaderValue;
+use battlesnake_rs::failure_injection::{Failure, FAILURE_HEADER};
+
+use crate::*;
+
+/// Applies the [Failure] a request asks for with the [FAILURE_HEADER] before any handler sees it
+///
+/// This does nothing unless `FAILURE_INJECTION` is set, see [battlesnake_rs::failure_injection].
+/// A delay also takes the time it waited off the request's deadline header, the same way a slow
+/// proxy in front of us would
+pub(crate) async fn inject_failures(
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Result<Response, Response> {
+    let failure = Failure::requested(
+        request
+            .headers()
+            .get(FAILURE_HEADER)
+            .and_then(|v| v.to_str().ok()),
+    );
+    let Some(failure) = failure else {
+        return Ok(next.run(request).await);
+    };
+    tracing::warn!(failure = ?failure, "Injecting a failure");
+
+    let (mut parts, body) = request.into_parts();
+    let bytes = Bytes::from_request(Request::new(body), &())
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    tokio::time::sleep(failure.delay()).await;
+    if let Some(remaining) = failure.remaining_deadline(deadline_header(&parts.headers), &bytes) {
+        parts.headers.insert(
+            DEADLINE_HEADER,
+            HeaderValue::from_str(&remaining).expect("A number is always a valid header value"),
+        );
+    }
+    let body = Bytes::copy_from_slice(failure.apply_to_body(&bytes));
+
+    Ok(next.run(Request::from_parts(parts, Body::from(body))).await)
+}
@@ -1,25 +1,48 @@
-use battlesnake_game_types::types::Move;
+use std::any::Any;
+
+use battlesnake_game_types::{types::Move, wire_representation::NestedGame};
 use battlesnake_minimax::{dashmap::DashMap, types::types::SnakeIDGettableGame};
-use battlesnake_rs::{HeadGettableGame, HealthGettableGame, Vector};
+use battlesnake_rs::{
+    board_backends::is_11x11,
+    board_only::you_on_board,
+    feature_flags::feature_flags,
+    hovering_hobbs::{
+        constrictor_score, forced_move_extension, opponent_depth_reduction, root_strategy,
+    },
+    move_guard::{check_reasonable_move, guard_move, GuardedFactory},
+    opening::{analyze_opening, opening_search_budget},
+    repetition::{AntiRepetition, PositionHistory},
+    shout_alternatives, shout_explanation,
+    wire_game::{is_constrictor, is_wrapped, unknown_snake_ids},
+    AboutMe, BattlesnakeFactory, HeadGettableGame, HealthGettableGame, Vector,
+};
 use fxhash::FxBuildHasher;
 use parking_lot::Mutex;
 
 use crate::*;
 
 #[derive(Debug)]
-#[allow(dead_code)]
-pub(crate) struct AppState {
-    pub game_states: HashMap<String, GameState>,
-}
-
-#[derive(Debug, Clone)]
 pub(crate) struct GameState {
-    pub last_move: Option<LastMoveState>,
-    pub id_map: HashMap<String, SnakeId>,
+    pub last_move: Option<Arc<LastMoveState>>,
+    pub id_map: Arc<HashMap<String, SnakeId>>,
+    pub move_timings: Vec<MoveTiming>,
+    pub position_history: Arc<PositionHistory>,
     #[allow(dead_code)]
     pub score_map: Arc<DashMap<StandardCellBoard4Snakes11x11, Score, FxBuildHasher>>,
 }
 
+/// What a move needs from its [GameState], taken while the state is locked
+///
+/// Everything in here is behind an [Arc], so taking a snapshot is cheap and the search can hold on
+/// to it without keeping the state locked. Later moves replace the values in the [GameState]
+/// instead of changing them, so a snapshot never changes under the search that is using it
+#[derive(Debug, Clone)]
+pub(crate) struct GameSnapshot {
+    pub last_move: Option<Arc<LastMoveState>>,
+    pub id_map: Arc<HashMap<String, SnakeId>>,
+    pub position_history: Arc<PositionHistory>,
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct LastMoveState {
     pub last_return: MinMaxReturn<StandardCellBoard4Snakes11x11, Score>,
@@ -31,63 +54,203 @@ impl GameState {
     pub fn new(id_map: HashMap<String, SnakeId>) -> Self {
         Self {
             last_move: None,
-            id_map,
+            id_map: Arc::new(id_map),
+            move_timings: vec![],
+            position_history: Arc::new(PositionHistory::default()),
             score_map: Arc::new(DashMap::with_capacity_and_hasher(
                 10_000_000,
                 Default::default(),
             )),
         }
     }
+
+    pub fn snapshot(&self) -> GameSnapshot {
+        GameSnapshot {
+            last_move: self.last_move.clone(),
+            id_map: self.id_map.clone(),
+            position_history: self.position_history.clone(),
+        }
+    }
 }
-pub(crate) async fn route_hobbs_info() -> impl IntoResponse {
-    Json(Factory {}.about())
+fn search_options() -> SnakeOptions {
+    SnakeOptions {
+        network_latency_padding: Duration::from_millis(150),
+        move_ordering: MoveOrdering::BestFirst,
+        opponent_depth_reduction: opponent_depth_reduction(),
+        food_spawn_chance: 0.0,
+        forced_move_extension: forced_move_extension(),
+        root_strategy: root_strategy(),
+        simultaneous_root_depth: 1,
+        features: feature_flags(),
+    }
 }
-pub(crate) async fn route_hobbs_start(
-    State(state): State<Arc<Mutex<AppState>>>,
-    Json(game): Json<Game>,
-) -> impl IntoResponse {
-    let id_map = build_snake_id_map(&game);
-    let mut state = state.lock();
-    state
-        .game_states
-        .insert(game.game.id, GameState::new(id_map));
-    StatusCode::NO_CONTENT
+
+/// How many levels of the search tree `/graph` includes when the request doesn't say
+const DEFAULT_GRAPH_DEPTH: usize = 8;
+
+/// Hovering Hobbs, which remembers its last search and the positions it has been in for the whole
+/// game, and searches with its own score function and options
+pub(crate) struct Hobbs;
+
+#[async_trait]
+impl ConfiguredSnake for Hobbs {
+    fn name(&self) -> String {
+        Factory {}.name()
+    }
+
+    fn about(&self) -> AboutMe {
+        Factory {}.about()
+    }
+
+    fn start_game(&self, game: &Game) -> Box<dyn Any + Send> {
+        Box::new(GameState::new(build_snake_id_map(game)))
+    }
+
+    async fn make_move(
+        &self,
+        state: Arc<Mutex<AppState>>,
+        headers: HeaderMap,
+        game: Game,
+    ) -> HttpResponse<AnsweredMove> {
+        hobbs_move(state, headers, game).await
+    }
+
+    fn end_game(&self, game: &Game, game_state: Box<dyn Any + Send>) {
+        if let Some(summary) = game_state
+            .downcast_ref::<GameState>()
+            .and_then(|s| GameMoveSummary::from_moves(&s.move_timings))
+        {
+            summary.log(&game.game.id);
+        }
+    }
+
+    async fn graph(
+        &self,
+        params: GraphParams,
+        headers: HeaderMap,
+        game: Game,
+    ) -> Option<HttpResponse<String>> {
+        Some(hobbs_graph(params, headers, game).await)
+    }
 }
-pub(crate) async fn route_hobbs_end(
-    State(state): State<Arc<Mutex<AppState>>>,
-    Json(game): Json<Game>,
-) -> impl IntoResponse {
-    let mut state = state.lock();
-    state.game_states.remove(&game.game.id);
-    StatusCode::NO_CONTENT
+
+/// How Hobbs scores the boards we keep between turns. Constrictor games never have food, so
+/// they're scored on space alone
+fn score_for(game_info: &NestedGame) -> fn(&StandardCellBoard4Snakes11x11) -> Score {
+    if is_constrictor(game_info) {
+        constrictor_score::<StandardCellBoard4Snakes11x11, _, 4>
+    } else {
+        standard_score::<StandardCellBoard4Snakes11x11, _, 4>
+    }
 }
 
-pub(crate) async fn route_hobbs_move(
-    State(state): State<Arc<Mutex<AppState>>>,
-    Json(game): Json<Game>,
-) -> impl IntoResponse {
+/// The depth is counted in levels of the tree, so each snake moving is one level
+async fn hobbs_graph(
+    params: GraphParams,
+    headers: HeaderMap,
+    mut game: Game,
+) -> HttpResponse<String> {
+    apply_deadline(&mut game, deadline_header(&headers));
+    let game_info = game.game.clone();
+    let turn = game.turn;
+    let id_map = build_snake_id_map(&game);
+
+    let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map)
+        .map_err(|e| eyre!("Could not convert the game: {e}"))?;
+    let you_id = *game.you_id();
+
+    let score = score_for(&game_info);
+    let snake = ParanoidMinimaxSnake::new(
+        game,
+        game_info,
+        turn,
+        score,
+        "hovering-hobbs",
+        search_options(),
+    );
+
+    let slot = acquire_search_slot("hovering-hobbs-graph")
+        .await
+        .ok_or_else(|| Report::new(SearchesSaturated))?;
+    let root = span!(tracing::Level::INFO, "graph_move");
+    let (_, scored) = spawn_blocking_with_tracing(move || {
+        let _slot = slot;
+        snake.choose_move_with_stats(None)
+    })
+    .instrument(root)
+    .await?;
+
+    let depth = params.depth.unwrap_or(DEFAULT_GRAPH_DEPTH);
+
+    Ok(format!("{}", scored.to_dot_graph_to_depth(&you_id, depth)))
+}
+
+async fn hobbs_move(
+    state: Arc<Mutex<AppState>>,
+    headers: HeaderMap,
+    mut game: Game,
+) -> HttpResponse<AnsweredMove> {
+    let started_at = Instant::now();
+    if !you_on_board(&game) {
+        // We're already dead, so there is nothing to search. Our board conversion also needs to
+        // find `you`, so we can't even try
+        return Ok(MoveOutput {
+            r#move: format!("{}", Move::Up),
+            shout: None,
+        }
+        .into());
+    }
+    // Everything we keep between turns is for a standard 11x11 board, so any other size and
+    // wrapped games are played by the factory instead. It also knows what to do with a board too
+    // big for every compact board
+    if !is_11x11(&game) || is_wrapped(&game.game) {
+        return factory_move(Box::new(GuardedFactory(Box::new(Factory))), headers, game).await;
+    }
+    apply_deadline(&mut game, deadline_header(&headers));
+    let request = serde_json::to_value(&game).map_err(Report::from)?;
     let game_info = game.game.clone();
     let game_id = game_info.id.to_string();
+    let timeout = Duration::from_millis(game_info.timeout.try_into().unwrap_or_default());
     let turn = game.turn;
 
     let name = "hovering-hobbs";
-
-    let options: SnakeOptions = SnakeOptions {
-        network_latency_padding: Duration::from_millis(150),
-        move_ordering: MoveOrdering::BestFirst,
-    };
+    let options = search_options();
 
     let game_state = {
-        let state_guard = state.lock();
+        let mut state_guard = state.lock();
+
+        // A resumed game maps its snake ids from this turn and starts with empty caches. There's
+        // no search from last turn to start from either, so in the opening the food races seed
+        // the search the same way they do on the first turn
+        let game_state = state_guard
+            .game_state_or_resume::<GameState>(&Hobbs, &game)
+            .expect("Hobbs always keeps a GameState");
+
+        // The snake ids have to stay the same for the whole game, or the search we kept from last
+        // turn would be about the wrong snakes. If the engine sends a snake we didn't start with
+        // there's no id to give it, so we start over with a new map and forget the old search
+        let unknown = unknown_snake_ids(&game, &game_state.id_map);
+        if !unknown.is_empty() {
+            tracing::warn!(
+                game_id = %game_id,
+                turn,
+                unknown_snake_ids = ?unknown,
+                "The request has snakes that weren't in the game when it started, remapping the snake ids"
+            );
+            game_state.id_map = Arc::new(build_snake_id_map(&game));
+            game_state.last_move = None;
+        }
 
-        state_guard
-            .game_states
-            .get(&game_id)
-            .expect("If we hit the start endpoint we should have a game state already")
-            .clone()
+        Arc::make_mut(&mut game_state.position_history).record(&game);
+
+        game_state.snapshot()
     };
     let last_move = &game_state.last_move;
 
+    let wire_game = game.clone();
+    let Some(slot) = acquire_search_slot(name).await else {
+        return Ok(saturated_fallback_move(&wire_game).into());
+    };
     let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &game_state.id_map)
         .expect("TODO: We need to work on our error handling");
 
@@ -167,40 +330,110 @@ pub(crate) async fn route_hobbs_move(
         None
     };
 
-    let score = &standard_score::<StandardCellBoard4Snakes11x11, _, 4>;
+    // There's no earlier search to start from in the opening, so the food races stand in for one.
+    // When they already picked our move the search only has to check it, so it gets less time
+    let opening = if initial_return.is_none() {
+        analyze_opening(&wire_game)
+    } else {
+        None
+    };
+    let opening_move = opening.as_ref().and_then(|plan| plan.chosen_move());
+    let initial_return = initial_return.or_else(|| opening.as_ref()?.seed(you_id));
+    let mut options = options;
+    if opening_move.is_some() {
+        options.network_latency_padding = options
+            .network_latency_padding
+            .max(timeout.saturating_sub(opening_search_budget()));
+    }
+
+    let stalemate = game_state.position_history.is_stalemate();
+    let score = AntiRepetition::new(score_for(&game_info), game_state.position_history.clone());
     // let score = CachedScore::new(score, game_state.score_map);
 
+    let search_config = json!({
+        "snake": name,
+        "ruleset": game_info.ruleset.name,
+        "timeout": game_info.timeout,
+        "options": format!("{options:?}"),
+        "reused_previous_search": initial_return.is_some() && opening.is_none(),
+        "opening_move": opening_move.map(|m| m.to_string()),
+        "stalemate": stalemate,
+    });
+
     let my_id = game.you_id();
-    let snake = ParanoidMinimaxSnake::new(game, game_info, turn, score, name, options);
+    let snake = ParanoidMinimaxSnake::new(game, game_info, turn, score, name, options)
+        .with_feature_snapshot(standard_features::<StandardCellBoard4Snakes11x11, _, 4>);
 
-    let (_depth, scored) =
-        spawn_blocking_with_tracing(move || snake.choose_move_inner(initial_return))
-            .await
-            .unwrap();
+    let (search_stats, scored) = spawn_blocking_with_tracing(move || {
+        let _slot = slot;
+        capture_search_panics(&request, search_config, || {
+            Ok(snake.choose_move_with_stats(initial_return))
+        })
+    })
+    .await??;
 
     let scored_options = scored.first_options_for_snake(my_id).unwrap();
     let output = scored_options.first().unwrap().0;
+    let summary = SearchSummary::paranoid(&search_stats, &scored);
+
+    let reasoning = scored.move_reasoning(my_id);
+    if let Some(reasoning) = &reasoning {
+        tracing::info!(game_id = %game_id, turn, explanation = %reasoning, "Explained the move");
+    }
+
+    let alternatives = shout_alternatives();
+    let shout = if alternatives > 0 {
+        scored.alternatives_summary(my_id, alternatives)
+    } else if shout_explanation() {
+        reasoning.map(|r| r.shout())
+    } else {
+        None
+    };
 
     {
         let mut state = state.lock();
 
-        let game_state = state
-            .game_states
-            .get_mut(&game_id)
-            .expect("If we hit the start endpoint we should have a game state already");
-
-        let last_move = LastMoveState {
-            last_return: scored,
-            last_board: game,
-            turn,
-        };
-        game_state.last_move = Some(last_move);
+        // The game can end, or be evicted to make room for a new one, while we're searching. Then
+        // there's no next turn to keep this search for, so we only answer with it
+        if let Some(game_state) = state.game_state_mut::<GameState>(name, &game_id) {
+            let last_move = LastMoveState {
+                last_return: scored,
+                last_board: game,
+                turn,
+            };
+            game_state.last_move = Some(Arc::new(last_move));
+            game_state.move_timings.push(MoveTiming {
+                search: search_stats,
+                handler_time: started_at.elapsed(),
+                timeout,
+            });
+        } else {
+            tracing::warn!(
+                game_id = %game_id,
+                turn,
+                "The game was gone by the time we finished searching, not keeping the search"
+            );
+        }
     }
 
     let output: MoveOutput = MoveOutput {
         r#move: format!("{output}"),
-        shout: None,
+        shout,
     };
+    let chosen = output.r#move.clone();
+    check_reasonable_move(&wire_game, &output, Some(&summary));
+    let guarded = guard_move(&wire_game, output);
+
+    if search_stats.time_used > search_stats.budget {
+        record_post_mortem(&wire_game, PostMortemReason::OverBudget);
+    }
+    if guarded.r#move != chosen {
+        record_post_mortem(&wire_game, PostMortemReason::FallbackMove);
+    }
 
-    Json(output)
+    Ok(AnsweredMove {
+        output: guarded,
+        search: Some(search_stats),
+        summary: Some(summary),
+    })
 }
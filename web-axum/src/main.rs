@@ -3,22 +3,37 @@
 
 use axum::{
     async_trait,
-    extract::{FromRequestParts, Path, State},
-    http::StatusCode,
-    response::IntoResponse,
+    body::{Body, Bytes},
+    extract::{FromRequest, FromRequestParts, Path, Query, State},
+    http::{HeaderMap, Request, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
 use battlesnake_minimax::{
-    paranoid::{move_ordering::MoveOrdering, MinMaxReturn, SnakeOptions},
+    core_pinning::game_cores,
+    paranoid::{move_ordering::MoveOrdering, MinMaxReturn, SearchStats, SnakeOptions},
     types::types::YouDeterminableGame,
     ParanoidMinimaxSnake,
 };
 use battlesnake_rs::{
-    all_factories, build_snake_id_map,
-    hovering_hobbs::{standard_score, Factory, Score},
-    improbable_irene::{Arena, ImprobableIrene},
-    BoxedFactory, Game, MoveOutput, SnakeId, StandardCellBoard4Snakes11x11,
+    all_factories,
+    api_version::{ApiVersion, WireFormat, API_VERSION_HEADER},
+    board_backends::{backend_for, benchmark_at_startup, benchmark_budget},
+    branding::branding_for,
+    build_snake_id_map,
+    deadline::{apply_deadline, DEADLINE_HEADER},
+    dueling_dana, extra_settings,
+    game_store::{evicted_games, GameStore},
+    hovering_hobbs::{standard_features, standard_score, Factory, Score},
+    improbable_irene::{self, Arena, ImprobableIrene, MAX_ROLLOUT_SNAKES},
+    opponent_book, ponder_scheduler,
+    request_handler::{self, factory_for, unreadable_game_move},
+    request_tracing::{request_span, GameCorrelation},
+    simulation_parity::{parity_check_enabled, verify_simulation_parity},
+    wire_game::is_wrapped,
+    AboutMe, BoxedFactory, Game, MoveOutput, SearchSummary, SnakeId, StandardCellBoard4Snakes11x11,
 };
 use color_eyre::{
     eyre::{eyre, Result},
@@ -41,12 +56,53 @@ use tracing_subscriber::layer::Layer;
 use tracing_subscriber::{prelude::*, registry::Registry};
 use tracing_tree::HierarchicalLayer;
 
-use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Duration};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// One of our snakes, either built fresh from its factory for every request or one of the
+/// [configured_snakes] that need more than that
+enum Snake {
+    Configured(Arc<dyn ConfiguredSnake>),
+    Factory(BoxedFactory),
+}
 
-struct ExtractSnakeFactory(BoxedFactory);
+impl Snake {
+    fn name(&self) -> String {
+        match self {
+            Snake::Configured(snake) => snake.name(),
+            Snake::Factory(factory) => factory.name(),
+        }
+    }
+}
+
+/// The move we answered a request with, and how the search behind it went for the snakes that can
+/// tell us
+struct AnsweredMove {
+    output: MoveOutput,
+    search: Option<SearchStats>,
+    /// What the snake told us about its search, which is logged and kept with the recent requests
+    summary: Option<SearchSummary>,
+}
+
+impl From<MoveOutput> for AnsweredMove {
+    fn from(output: MoveOutput) -> Self {
+        Self {
+            output,
+            search: None,
+            summary: None,
+        }
+    }
+}
+
+struct ExtractSnake(Snake);
 
 #[async_trait]
-impl<State: Send + Sync> FromRequestParts<State> for ExtractSnakeFactory {
+impl<State: Send + Sync> FromRequestParts<State> for ExtractSnake {
     type Rejection = (StatusCode, &'static str);
 
     async fn from_request_parts(
@@ -57,26 +113,32 @@ impl<State: Send + Sync> FromRequestParts<State> for ExtractSnakeFactory {
             .await
             .map_err(|_err| (StatusCode::NOT_FOUND, "Couldn't extract snake name"))?;
 
-        let factories = all_factories();
-        let factory = factories
+        if let Some(snake) = configured_snakes()
             .into_iter()
-            .find(|f| f.name() == snake_name)
-            .ok_or((StatusCode::NOT_FOUND, "No factory found"))?;
+            .find(|s| s.name() == snake_name)
+        {
+            return Ok(Self(Snake::Configured(snake)));
+        }
+
+        let factory =
+            factory_for(&snake_name).ok_or((StatusCode::NOT_FOUND, "No factory found"))?;
 
-        Ok(Self(factory))
+        Ok(Self(Snake::Factory(factory)))
     }
 }
 
-#[tokio::main(flavor = "multi_thread", worker_threads = 10)]
-async fn main() -> Result<()> {
-    color_eyre::install()?;
-
-    let git_commit = std::option_env!("CIRCLE_SHA1");
-    let release_name = if let Some(git_commit) = git_commit {
+/// The git commit we were built from, which is how both Sentry and our metrics tell deploys apart
+fn release_name() -> Cow<'static, str> {
+    if let Some(git_commit) = std::option_env!("CIRCLE_SHA1") {
         git_commit.into()
     } else {
         sentry::release_name!().unwrap_or_else(|| "dev".into())
-    };
+    }
+}
+
+#[tokio::main(flavor = "multi_thread", worker_threads = 10)]
+async fn main() -> Result<()> {
+    color_eyre::install()?;
 
     let _guard = if let Ok(sentry_dsn) = std::env::var("SENTRY_DSN") {
         println!("Sentry enabled");
@@ -85,7 +147,7 @@ async fn main() -> Result<()> {
             sentry_dsn,
             sentry::ClientOptions {
                 traces_sample_rate: 0.0,
-                release: Some(release_name),
+                release: Some(release_name()),
                 ..Default::default()
             },
         )))
@@ -153,27 +215,58 @@ async fn main() -> Result<()> {
         .with(sentry_tracing::layer())
         .try_init()?;
 
-    let state = AppState {
-        game_states: HashMap::new(),
-    };
-    let state = Mutex::new(state);
+    // Refuse to start if the compact boards no longer simulate turns the way we expect, so a
+    // types crate upgrade that changes the rules fails the deploy instead of a tournament game
+    if parity_check_enabled() {
+        let mismatches = verify_simulation_parity();
+        for mismatch in &mismatches {
+            tracing::error!(mismatch = %mismatch, "Compact simulation disagrees with the wire game");
+        }
+        if !mismatches.is_empty() {
+            return Err(eyre!(
+                "{} simulated turns disagree between the compact and wire games",
+                mismatches.len()
+            ));
+        }
+        tracing::info!("Compact simulation matches the wire game");
+    }
+
+    // Time the board backends before we take any games, so they don't compete with a search
+    let backends = benchmark_at_startup(benchmark_budget());
+    tracing::info!(
+        measurements = backends.measurements.len(),
+        "Benchmarked board backends"
+    );
+
+    let state = Mutex::new(AppState::new());
     let state = Arc::new(state);
+    watch_memory(state.clone());
 
     let app = Router::new()
         .route("/", get(root))
-        .route("/hovering-hobbs", get(route_hobbs_info))
-        .route("/hovering-hobbs/start", post(route_hobbs_start))
-        .route("/hovering-hobbs/move", post(route_hobbs_move))
-        .route("/hovering-hobbs/end", post(route_hobbs_end))
+        .route("/compare", post(route_compare))
+        .route("/debug/recent", get(route_recent_requests))
+        .route("/debug/writers", get(route_writer_metrics))
+        .route("/debug/search-pool", get(route_search_pool_metrics))
+        .route("/debug/dark-launch", get(route_dark_launch))
+        .route("/status", get(route_status))
+        .route("/metrics", get(route_metrics))
         .route("/:snake_name", get(route_info))
+        .route("/:snake_name/customizations", get(route_customizations))
         .route("/:snake_name/start", post(route_start))
         .route("/:snake_name/move", post(route_move))
         .route("/improbable-irene/graph", post(route_graph))
+        .route("/:snake_name/graph", post(route_configured_graph))
         .route("/:snake_name/end", post(route_end))
+        .layer(middleware::from_fn(record_game_correlation))
+        .layer(middleware::from_fn(inject_failures))
         .layer(sentry_tower::SentryHttpLayer::with_transaction())
         .layer(NewSentryLayer::new_from_top())
         .layer(
             TraceLayer::new_for_http()
+                .make_span_with(|request: &Request<Body>| {
+                    request_span(request.method().as_str(), request.uri().path())
+                })
                 .on_request(DefaultOnRequest::new().level(Level::INFO))
                 .on_response(
                     DefaultOnResponse::new()
@@ -212,9 +305,19 @@ impl From<JoinError> for HttpError {
 
 impl IntoResponse for HttpError {
     fn into_response(self) -> axum::response::Response {
+        if let Some(saturated) = self.0.downcast_ref::<SearchesSaturated>() {
+            return (
+                StatusCode::TOO_MANY_REQUESTS,
+                Json(json!({ "error": saturated.to_string() })),
+            )
+                .into_response();
+        }
+
+        let response = request_handler::Response::internal_error(&self.0);
+
         (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({ "error": "Things Broke", "details": self.0.to_string()})),
+            StatusCode::from_u16(response.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(response.body),
         )
             .into_response()
     }
@@ -223,14 +326,73 @@ impl IntoResponse for HttpError {
 type HttpResponse<T> = Result<T, HttpError>;
 type JsonResponse<JsonType> = HttpResponse<Json<JsonType>>;
 
+/// Reads the game out of the request body so the request span can be tagged with the game id, turn
+/// and snake name before any handler runs
+async fn record_game_correlation(
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Result<Response, Response> {
+    let (parts, body) = request.into_parts();
+    let bytes = Bytes::from_request(Request::new(body), &())
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    if let Some(correlation) = GameCorrelation::from_body(&bytes) {
+        correlation.record(&tracing::Span::current());
+    }
+    // The latencies don't make it into the parsed game, so this is the one place we can see them
+    opponent_book::observe_latencies(&bytes);
+
+    Ok(next
+        .run(Request::from_parts(parts, Body::from(bytes)))
+        .await)
+}
+
 async fn root() -> &'static str {
     "Hello, World!"
 }
 
-async fn route_info(ExtractSnakeFactory(factory): ExtractSnakeFactory) -> impl IntoResponse {
-    let carter_info = factory.about();
+/// What the snake says about itself, with any branding this deployment gives it. See
+/// [branding_for]
+fn branded_about(snake: Snake) -> AboutMe {
+    let branding = branding_for(&snake.name());
+    let info = match snake {
+        Snake::Configured(snake) => snake.about(),
+        Snake::Factory(factory) => factory.about(),
+    };
+
+    info.branded(branding)
+}
+
+/// The [WireFormat] of the API version the request asked for, see [ApiVersion::negotiate]
+fn negotiated_format(headers: &HeaderMap) -> &'static dyn WireFormat {
+    let requested = headers
+        .get(API_VERSION_HEADER)
+        .and_then(|v| v.to_str().ok());
 
-    Json(carter_info)
+    ApiVersion::negotiate(requested).wire_format()
+}
+
+/// Tells the engine which API version we answered in
+fn with_api_version(format: &dyn WireFormat, body: serde_json::Value) -> Response {
+    (
+        [(API_VERSION_HEADER, format.version().to_string())],
+        Json(body),
+    )
+        .into_response()
+}
+
+/// The snake's `/` response in the API version the request asked for
+async fn route_info(ExtractSnake(snake): ExtractSnake, headers: HeaderMap) -> Response {
+    let format = negotiated_format(&headers);
+
+    with_api_version(format, format.info(branded_about(snake)))
+}
+
+/// Just the customizations from the snake's `/` response, which are the same in every API
+/// version
+async fn route_customizations(ExtractSnake(snake): ExtractSnake) -> impl IntoResponse {
+    Json(branded_about(snake).customizations())
 }
 
 fn spawn_blocking_with_tracing<F, R>(f: F) -> JoinHandle<R>
@@ -242,32 +404,184 @@ where
     tokio::task::spawn_blocking(move || current_span.in_scope(f))
 }
 
+/// The deadline header from the request, if it was set and valid UTF-8
+fn deadline_header(headers: &HeaderMap) -> Option<&str> {
+    headers.get(DEADLINE_HEADER).and_then(|v| v.to_str().ok())
+}
+
 async fn route_move(
-    ExtractSnakeFactory(factory): ExtractSnakeFactory,
-    Json(game): Json<Game>,
-) -> JsonResponse<MoveOutput> {
-    let snake = factory.create_from_wire_game(game);
+    State(state): State<Arc<Mutex<AppState>>>,
+    ExtractSnake(snake): ExtractSnake,
+    headers: HeaderMap,
+    body: Bytes,
+) -> HttpResponse<Response> {
+    let format = negotiated_format(&headers);
+    let mut game = match format.parse_game(&body) {
+        Ok(game) => game,
+        Err(error) => {
+            return Ok(with_api_version(
+                format,
+                format.move_response(&unreadable_game_move(&error)),
+            ))
+        }
+    };
+    let started_at = Instant::now();
+    let name = snake.name();
+    let request = serde_json::to_value(&game).map_err(Report::from)?;
+    let game_id = game.game.id.clone();
+    let ruleset = game.game.ruleset.name.clone();
+    let turn = game.turn;
 
-    let output = spawn_blocking_with_tracing(move || snake.make_move()).await??;
+    // The engine sent this turn again, so it gets the answer it missed instead of a second search
+    {
+        let mut state = state.lock();
+        if let Some(output) = state.retried_moves.answered(&name, &game_id, turn) {
+            tracing::info!(
+                game_id = %game_id,
+                turn,
+                "Answered a retried move with the first answer"
+            );
+            state.turn_histograms.record_retried_move(&name);
+
+            return Ok(with_api_version(format, format.move_response(&output)));
+        }
+    }
 
-    Ok(Json(output))
+    // A deadline header replaces the timeout later on, and since the proxy that sets it already
+    // knows how much time is left, it wins over our estimate
+    let shave = state
+        .lock()
+        .engine_clocks
+        .request_arrived(&name, &game_id, turn, Instant::now());
+    shave_timeout(&mut game, shave);
+
+    let candidate_move = dark_launch::forward_move(&name, &headers, &request);
+
+    // Nothing ponders while we're searching a move the engine is waiting on
+    let real_move = ponder_scheduler::real_move();
+    let answered = match snake {
+        Snake::Configured(snake) => snake.make_move(state.clone(), headers, game).await?,
+        Snake::Factory(factory) => factory_move(factory, headers, game).await?,
+    };
+
+    drop(real_move);
+
+    if let Some(candidate_move) = candidate_move {
+        dark_launch::compare_move(
+            state.clone(),
+            name.clone(),
+            game_id.clone(),
+            turn,
+            request.clone(),
+            answered.output.r#move.clone(),
+            candidate_move,
+        );
+    }
+
+    if let Some(summary) = &answered.summary {
+        tracing::info!(
+            game_id = %game_id,
+            turn,
+            chosen_move = %answered.output.r#move,
+            summary = %json!(summary),
+            "Answered with a search"
+        );
+    }
+
+    let mut state = state.lock();
+    let now = Instant::now();
+    state.engine_clocks.responded(&name, &game_id, turn, now);
+    state
+        .retried_moves
+        .responded(&name, &game_id, turn, &answered.output, now);
+    state
+        .status
+        .record_move(&name, &game_id, turn, answered.search, now);
+    state.turn_histograms.record_move(
+        &name,
+        &ruleset,
+        now.duration_since(started_at),
+        answered.search,
+    );
+    state.recent_requests.record(
+        &name,
+        RecentRequest {
+            game_id,
+            turn,
+            request,
+            chosen_move: answered.output.r#move.clone(),
+            depth: answered.search.map(|s| s.depth),
+            summary: answered.summary,
+        },
+    );
+
+    Ok(with_api_version(
+        format,
+        format.move_response(&answered.output),
+    ))
 }
 
-async fn route_graph(Json(game): Json<Game>) -> JsonResponse<MoveOutput> {
+async fn factory_move(
+    factory: BoxedFactory,
+    headers: HeaderMap,
+    mut game: Game,
+) -> HttpResponse<AnsweredMove> {
+    apply_deadline(&mut game, deadline_header(&headers));
+    let request = serde_json::to_value(&game).map_err(Report::from)?;
+    let search_config = json!({
+        "snake": factory.name(),
+        "ruleset": game.game.ruleset.name,
+        "timeout": game.game.timeout,
+        "board_backend": backend_for(&game).map(|b| format!("{b:?}")),
+    });
+    let started_at = Instant::now();
+    let timeout = Duration::from_millis(game.game.timeout.try_into().unwrap_or_default());
+    let Some(slot) = acquire_search_slot(&factory.name()).await else {
+        return Ok(saturated_fallback_move(&game).into());
+    };
+    let snake = factory.create_from_wire_game(game.clone());
+
+    let (output, summary) = spawn_blocking_with_tracing(move || {
+        let _slot = slot;
+        capture_search_panics(&request, search_config, || snake.make_move_with_summary())
+    })
+    .await??;
+
+    if started_at.elapsed() > timeout {
+        record_post_mortem(&game, PostMortemReason::OverBudget);
+    }
+
+    Ok(AnsweredMove {
+        output,
+        search: None,
+        summary: summary.algorithm.is_some().then_some(summary),
+    })
+}
+
+async fn route_graph(headers: HeaderMap, Json(mut game): Json<Game>) -> JsonResponse<MoveOutput> {
+    apply_deadline(&mut game, deadline_header(&headers));
     let game_info = game.game.clone();
     let id_map = build_snake_id_map(&game);
     let turn = game.turn;
 
-    assert_ne!(
-        game_info.ruleset.name, "wrapped",
-        "Graphing does not currently support wrapped games"
-    );
+    if is_wrapped(&game_info) {
+        return Err(eyre!("Graphing does not currently support wrapped games").into());
+    }
+    if game.board.snakes.len() > MAX_ROLLOUT_SNAKES {
+        return Err(
+            eyre!("Graphing only supports games with up to {MAX_ROLLOUT_SNAKES} snakes").into(),
+        );
+    }
     let game = StandardCellBoard4Snakes11x11::convert_from_game(game, &id_map).unwrap();
 
     let snake = ImprobableIrene::new(game, game_info, turn);
 
+    let slot = acquire_search_slot("graph")
+        .await
+        .ok_or_else(|| Report::new(SearchesSaturated))?;
     let root = span!(tracing::Level::INFO, "graph_move");
     let output = spawn_blocking_with_tracing(move || {
+        let _slot = slot;
         let mut arena = Arena::new();
         snake
             .graph_move(&mut arena)
@@ -279,19 +593,102 @@ async fn route_graph(Json(game): Json<Game>) -> JsonResponse<MoveOutput> {
     Ok(Json(output))
 }
 
-async fn route_start() -> impl IntoResponse {
+async fn route_start(
+    State(state): State<Arc<Mutex<AppState>>>,
+    ExtractSnake(snake): ExtractSnake,
+    Json(game): Json<Game>,
+) -> impl IntoResponse {
+    dark_launch::forward(&snake.name(), "start", &game);
+
+    if let Snake::Configured(snake) = snake {
+        let game_state = snake.start_game(&game);
+        state
+            .lock()
+            .start_game(snake.name(), game.game.id, game_state);
+    }
+
     StatusCode::NO_CONTENT
 }
 async fn route_end(
-    ExtractSnakeFactory(factory): ExtractSnakeFactory,
+    State(state): State<Arc<Mutex<AppState>>>,
+    ExtractSnake(snake): ExtractSnake,
     Json(game): Json<Game>,
 ) -> impl IntoResponse {
-    let snake = factory.create_from_wire_game(game);
+    dark_launch::forward(&snake.name(), "end", &game);
 
-    snake.end();
+    {
+        let mut state = state.lock();
+        state.engine_clocks.end_game(&snake.name(), &game.game.id);
+        state.retried_moves.end_game(&snake.name(), &game.game.id);
+        state.status.end_game(&snake.name(), &game);
+    }
+    game_cores().release(&game.game.id);
+
+    match snake {
+        Snake::Configured(snake) => {
+            let game_state = state.lock().end_game(&snake.name(), &game.game.id);
+            if let Some(game_state) = game_state {
+                snake.end_game(&game, game_state);
+            }
+        }
+        Snake::Factory(factory) => {
+            let snake = factory.create_from_wire_game(game);
+
+            snake.end();
+        }
+    }
 
     StatusCode::NO_CONTENT
 }
 
+mod configured_snake;
+use configured_snake::{
+    configured_snakes, route_configured_graph, AppState, ConfiguredSnake, GraphParams,
+};
+
 mod hobbs;
-use hobbs::*;
+use hobbs::Hobbs;
+
+mod clock_skew;
+use clock_skew::{shave_timeout, EngineClocks};
+
+mod compare;
+use compare::route_compare;
+
+mod recent_requests;
+use recent_requests::{route_recent_requests, RecentRequest, RecentRequests};
+
+mod panic_capture;
+use panic_capture::capture_search_panics;
+
+mod failure_injection;
+use failure_injection::inject_failures;
+
+mod move_stats;
+use move_stats::{GameMoveSummary, MoveTiming};
+
+mod post_mortem;
+use post_mortem::{record_post_mortem, PostMortemReason};
+
+mod dark_launch;
+use dark_launch::{route_dark_launch, DarkLaunchResults};
+
+mod background_writer;
+use background_writer::{route_writer_metrics, BackgroundWriter};
+
+mod search_slots;
+use search_slots::{
+    acquire_search_slot, route_search_pool_metrics, saturated_fallback_move, SearchesSaturated,
+};
+
+mod retried_moves;
+use retried_moves::RetriedMoves;
+
+mod memory_watch;
+use memory_watch::{watch_memory, MemorySample};
+
+mod status;
+use status::{route_status, ArenaStatus};
+
+mod turn_histograms;
+use turn_histograms::{route_metrics, TurnHistograms};
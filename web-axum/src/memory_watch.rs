@@ -0,0 +1,89 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::*;
+
+/// Reads how often we sample the memory from `MEMORY_SAMPLE_INTERVAL_SECS`, defaulting to 60
+fn sample_interval() -> Duration {
+    match std::env::var("MEMORY_SAMPLE_INTERVAL_SECS").map(|x| x.parse()) {
+        Ok(Ok(x)) if x > 0 => Duration::from_secs(x),
+        _ => Duration::from_secs(60),
+    }
+}
+
+/// How big the process and everything we keep per game were at one point
+///
+/// A store that keeps growing from one sample to the next while the games going stay the same is
+/// a leak, and so is an RSS that keeps climbing when the stores don't
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct MemorySample {
+    /// How long the deployment had been up when this was taken
+    uptime_secs: u64,
+    /// The resident set size of the process, None where we can't read it
+    rss_bytes: Option<u64>,
+    /// The largest the resident set has been since the process started
+    peak_rss_bytes: Option<u64>,
+    /// How many games, or moves for [RecentRequests], each store is holding
+    stores: BTreeMap<&'static str, usize>,
+    /// Games dropped from a full [GameStore] since this deployment started
+    evicted_games: u64,
+    /// How often Dana pondered the board the engine went on to send, None until Dana has pondered
+    dana_ponder_hit_rate: Option<f64>,
+}
+
+impl MemorySample {
+    fn take(state: &AppState, started_at: Instant) -> Self {
+        let (rss_bytes, peak_rss_bytes) = process_rss();
+
+        Self {
+            uptime_secs: started_at.elapsed().as_secs(),
+            rss_bytes,
+            peak_rss_bytes,
+            stores: state.store_sizes(),
+            evicted_games: evicted_games(),
+            dana_ponder_hit_rate: dueling_dana::ponder_hit_rate(),
+        }
+    }
+}
+
+/// The current and peak resident set size of this process, from `/proc/self/status`
+fn process_rss() -> (Option<u64>, Option<u64>) {
+    let Ok(status) = std::fs::read_to_string("/proc/self/status") else {
+        return (None, None);
+    };
+
+    let field = |name: &str| {
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix(name))
+            .and_then(|value| value.trim().strip_suffix("kB"))
+            .and_then(|kb| kb.trim().parse::<u64>().ok())
+            .map(|kb| kb * 1024)
+    };
+
+    (field("VmRSS:"), field("VmHWM:"))
+}
+
+/// Sample the memory every `MEMORY_SAMPLE_INTERVAL_SECS` for as long as the server runs, logging
+/// each sample and keeping the latest in the [AppState] for `/metrics`
+pub(crate) fn watch_memory(state: Arc<Mutex<AppState>>) {
+    let started_at = Instant::now();
+    let interval = sample_interval();
+
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(interval).await;
+
+            let sample = MemorySample::take(&state.lock(), started_at);
+            tracing::info!(
+                rss_bytes = ?sample.rss_bytes,
+                peak_rss_bytes = ?sample.peak_rss_bytes,
+                stores = %json!(sample.stores),
+                evicted_games = sample.evicted_games,
+                "Sampled memory"
+            );
+            state.lock().memory = Some(sample);
+        }
+    });
+}
@@ -0,0 +1,101 @@
+use battlesnake_minimax::paranoid::SearchStats;
+
+use crate::*;
+
+/// How close to the game timeout a move can be answered before we count it as a near timeout
+const NEAR_TIMEOUT_MARGIN: Duration = Duration::from_millis(50);
+
+/// Everything we remember about how a single move went
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct MoveTiming {
+    pub search: SearchStats,
+    /// Time from the handler starting until we had a move to respond with
+    pub handler_time: Duration,
+    /// The timeout the engine gave us for this turn
+    pub timeout: Duration,
+}
+
+impl MoveTiming {
+    fn is_near_timeout(&self) -> bool {
+        self.handler_time + NEAR_TIMEOUT_MARGIN >= self.timeout
+    }
+}
+
+/// The min, median and 95th percentile of some per turn value
+#[derive(Debug, Clone, Copy)]
+struct Spread<T> {
+    min: T,
+    median: T,
+    p95: T,
+}
+
+impl<T: Ord + Copy> Spread<T> {
+    fn of(values: impl IntoIterator<Item = T>) -> Option<Self> {
+        let mut values: Vec<T> = values.into_iter().collect();
+        values.sort();
+        let min = *values.first()?;
+
+        // Nearest rank, so every value we report is one we actually saw
+        let rank = |percentile: usize| values[(values.len() * percentile).div_ceil(100).max(1) - 1];
+
+        Some(Self {
+            min,
+            median: rank(50),
+            p95: rank(95),
+        })
+    }
+}
+
+/// A summary of how every move in a game went, logged once when the game ends
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct GameMoveSummary {
+    turns: usize,
+    depth: Spread<usize>,
+    iterations: Spread<usize>,
+    time_used: Spread<Duration>,
+    budget: Spread<Duration>,
+    near_timeouts: usize,
+    pv_flips: usize,
+    score_memo_lookups: usize,
+    score_memo_hits: usize,
+}
+
+impl GameMoveSummary {
+    pub fn from_moves(moves: &[MoveTiming]) -> Option<Self> {
+        Some(Self {
+            turns: moves.len(),
+            depth: Spread::of(moves.iter().map(|m| m.search.depth))?,
+            iterations: Spread::of(moves.iter().map(|m| m.search.iterations))?,
+            time_used: Spread::of(moves.iter().map(|m| m.search.time_used))?,
+            budget: Spread::of(moves.iter().map(|m| m.search.budget))?,
+            near_timeouts: moves.iter().filter(|m| m.is_near_timeout()).count(),
+            pv_flips: moves.iter().map(|m| m.search.pv_flips).sum(),
+            score_memo_lookups: moves.iter().map(|m| m.search.score_memo_lookups).sum(),
+            score_memo_hits: moves.iter().map(|m| m.search.score_memo_hits).sum(),
+        })
+    }
+
+    pub fn log(&self, game_id: &str) {
+        tracing::info!(
+            game_id,
+            turns = self.turns,
+            depth_min = self.depth.min,
+            depth_median = self.depth.median,
+            depth_p95 = self.depth.p95,
+            iterations_min = self.iterations.min,
+            iterations_median = self.iterations.median,
+            iterations_p95 = self.iterations.p95,
+            time_used_ms_min = self.time_used.min.as_millis(),
+            time_used_ms_median = self.time_used.median.as_millis(),
+            time_used_ms_p95 = self.time_used.p95.as_millis(),
+            budget_ms_min = self.budget.min.as_millis(),
+            budget_ms_median = self.budget.median.as_millis(),
+            budget_ms_p95 = self.budget.p95.as_millis(),
+            near_timeouts = self.near_timeouts,
+            pv_flips = self.pv_flips,
+            score_memo_lookups = self.score_memo_lookups,
+            score_memo_hits = self.score_memo_hits,
+            "Move time statistics for the game"
+        );
+    }
+}
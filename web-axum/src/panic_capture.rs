@@ -0,0 +1,42 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use serde_json::Value;
+
+use crate::*;
+
+/// Run a search, making sure that if it panics we have everything we need to reproduce it
+///
+/// The request body and the search configuration are attached to the Sentry scope, so the event
+/// the panic integration sends includes them. We also catch the panic and log it as a structured
+/// error with the same fields, so it shows up in our traces and the request gets a normal error
+/// response instead of a dropped task
+pub(crate) fn capture_search_panics<T>(
+    request: &Value,
+    search_config: Value,
+    search: impl FnOnce() -> Result<T>,
+) -> Result<T> {
+    let result = sentry::with_scope(
+        |scope| {
+            scope.set_extra("request", request.clone());
+            scope.set_extra("search_config", search_config.clone());
+        },
+        || catch_unwind(AssertUnwindSafe(search)),
+    );
+
+    result.unwrap_or_else(|panic| {
+        let message = panic
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "Unknown panic".to_owned());
+
+        tracing::error!(
+            panic = %message,
+            request = %request,
+            search_config = %search_config,
+            "The search panicked"
+        );
+
+        Err(eyre!("The search panicked: {message}"))
+    })
+}
@@ -0,0 +1,65 @@
+use std::{fmt::Display, path::PathBuf, sync::OnceLock};
+
+use crate::*;
+
+/// Why we saved a request as a post-mortem fixture
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PostMortemReason {
+    /// The search, or the whole handler, ran past the time it was given
+    OverBudget,
+    /// The move guard had to replace the move the search picked
+    FallbackMove,
+}
+
+impl Display for PostMortemReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PostMortemReason::OverBudget => write!(f, "over_budget"),
+            PostMortemReason::FallbackMove => write!(f, "fallback_move"),
+        }
+    }
+}
+
+/// Where post-mortem fixtures are written
+///
+/// This is read from `POST_MORTEM_DIR` and defaults to `post_mortems` in the working directory
+fn post_mortem_dir() -> PathBuf {
+    std::env::var("POST_MORTEM_DIR")
+        .unwrap_or_else(|_| "post_mortems".to_owned())
+        .into()
+}
+
+static POST_MORTEMS: OnceLock<BackgroundWriter<(Game, PostMortemReason)>> = OnceLock::new();
+
+/// Save the request for a move that went wrong, so we can replay it later
+///
+/// The file is named like the ones in `fixtures/`, `<game id>_<turn>`, with the reason on the end,
+/// and holds the request exactly as we searched it. That means the deadline is already applied,
+/// so running it through a bench or `snake-cli` gives the search the same budget it had here.
+///
+/// The file is written by a [BackgroundWriter], so the move is never held up by the disk. Failing
+/// to write the file is logged and otherwise ignored, a post-mortem is never worth failing the
+/// move over
+pub(crate) fn record_post_mortem(game: &Game, reason: PostMortemReason) {
+    POST_MORTEMS
+        .get_or_init(|| BackgroundWriter::spawn("post_mortems", write_post_mortem))
+        .send((game.clone(), reason));
+}
+
+fn write_post_mortem((game, reason): (Game, PostMortemReason)) -> Result<()> {
+    let dir = post_mortem_dir();
+    let path = dir.join(format!("{}_{}_{reason}.json", game.game.id, game.turn));
+
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(&path, serde_json::to_string_pretty(&game)?)?;
+
+    tracing::warn!(
+        game_id = %game.game.id,
+        turn = game.turn,
+        reason = %reason,
+        path = %path.display(),
+        "Saved the request as a post-mortem fixture"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,85 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::*;
+
+/// A move we answered, kept so `/debug/recent` can show it
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct RecentRequest {
+    pub game_id: String,
+    pub turn: i32,
+    /// The request exactly as the engine sent it
+    pub request: Value,
+    pub chosen_move: String,
+    /// How deep the search went, for the snakes that can tell us
+    pub depth: Option<usize>,
+    /// Everything else the snake told us about its search, see [SearchSummary]
+    pub summary: Option<SearchSummary>,
+}
+
+/// The last few moves each snake answered, for when something weird happens in the arena and we
+/// want the boards right away instead of waiting for the traces to land
+///
+/// `RECENT_REQUESTS_PER_SNAKE` sets how many moves each snake keeps and defaults to 50. Setting it
+/// to 0 keeps nothing
+#[derive(Debug)]
+pub(crate) struct RecentRequests {
+    per_snake: HashMap<String, VecDeque<RecentRequest>>,
+    capacity: usize,
+}
+
+impl RecentRequests {
+    pub fn from_env() -> Self {
+        let capacity = match std::env::var("RECENT_REQUESTS_PER_SNAKE").map(|x| x.parse()) {
+            Ok(Ok(x)) => x,
+            _ => 50,
+        };
+
+        Self {
+            per_snake: HashMap::new(),
+            capacity,
+        }
+    }
+
+    /// Remember a move, forgetting the snake's oldest one if it already has as many as it keeps
+    pub fn record(&mut self, snake: &str, request: RecentRequest) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let recent = self.per_snake.entry(snake.to_owned()).or_default();
+        if recent.len() >= self.capacity {
+            recent.pop_front();
+        }
+        recent.push_back(request);
+    }
+
+    /// How many moves are kept, across every snake
+    pub fn len(&self) -> usize {
+        self.per_snake.values().map(VecDeque::len).sum()
+    }
+
+    /// Every snake's moves, oldest first
+    fn dump(&self, only: Option<&str>) -> BTreeMap<String, Vec<RecentRequest>> {
+        self.per_snake
+            .iter()
+            .filter(|(snake, _)| only.map_or(true, |only| only == snake.as_str()))
+            .map(|(snake, recent)| (snake.clone(), recent.iter().cloned().collect()))
+            .collect()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct RecentParams {
+    snake: Option<String>,
+}
+
+/// Dump the moves every snake answered most recently, or just the ones for `?snake=`
+pub(crate) async fn route_recent_requests(
+    State(state): State<Arc<Mutex<AppState>>>,
+    Query(params): Query<RecentParams>,
+) -> Json<BTreeMap<String, Vec<RecentRequest>>> {
+    Json(state.lock().recent_requests.dump(params.snake.as_deref()))
+}
@@ -0,0 +1,69 @@
+use std::time::{Duration, Instant};
+
+use crate::*;
+
+/// Games we haven't answered in this long are assumed to be over, even without an end request
+const STALE_AFTER: Duration = Duration::from_secs(10 * 60);
+
+/// The move we last answered a game with
+#[derive(Debug, Clone)]
+struct LastAnswer {
+    turn: i32,
+    output: MoveOutput,
+    answered_at: Instant,
+}
+
+/// The last move every snake answered in every game, so a `/move` the engine sends again gets the
+/// same answer
+///
+/// The engine retries a move when the request or our answer got lost on the way, and searching
+/// the turn again would spend the whole budget a second time and could come up with a different
+/// move. Only the last turn of each game is kept, since that's the only one the engine retries.
+///
+/// Games are keyed by the snake's name as well as the game id, like [AppState]'s game states
+#[derive(Debug, Default)]
+pub(crate) struct RetriedMoves {
+    games: GameStore<LastAnswer>,
+}
+
+impl RetriedMoves {
+    /// The move we already answered this turn with, if this request is a retry
+    pub fn answered(&self, snake_name: &str, game_id: &str, turn: i32) -> Option<MoveOutput> {
+        self.games
+            .get(snake_name, game_id)
+            .filter(|answer| answer.turn == turn)
+            .map(|answer| answer.output.clone())
+    }
+
+    /// Note that we answered `turn` with `output` at `now`
+    pub fn responded(
+        &mut self,
+        snake_name: &str,
+        game_id: &str,
+        turn: i32,
+        output: &MoveOutput,
+        now: Instant,
+    ) {
+        self.games
+            .retain(|answer| now.saturating_duration_since(answer.answered_at) < STALE_AFTER);
+
+        self.games.insert(
+            snake_name,
+            game_id,
+            LastAnswer {
+                turn,
+                output: output.clone(),
+                answered_at: now,
+            },
+        );
+    }
+
+    /// How many games we're keeping the last answer for
+    pub fn games(&self) -> usize {
+        self.games.len()
+    }
+
+    pub fn end_game(&mut self, snake_name: &str, game_id: &str) {
+        self.games.remove(snake_name, game_id);
+    }
+}
@@ -0,0 +1,139 @@
+use std::sync::OnceLock;
+
+use battlesnake_game_types::types::Move;
+use battlesnake_minimax::search_pool::search_pool;
+use battlesnake_rs::move_guard::guard_move;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::*;
+
+/// Limits how many searches each route runs at once
+///
+/// Every search runs on the blocking pool, which will happily take on as many as we throw at it.
+/// During a burst of games that means every search gets a slice of the CPU and they all run past
+/// their deadlines. Instead each route gets a fixed number of slots, and a request that can't get
+/// one quickly doesn't search at all
+struct SearchSlots {
+    routes: Mutex<HashMap<String, Arc<Semaphore>>>,
+    slots_per_route: usize,
+    max_wait: Duration,
+}
+
+static SEARCH_SLOTS: OnceLock<SearchSlots> = OnceLock::new();
+
+impl SearchSlots {
+    /// `MAX_CONCURRENT_SEARCHES` sets how many searches each route can run at once, and defaults
+    /// to the number of CPUs. `MAX_SEARCH_QUEUE_WAIT_MS` is how long a request waits for a slot
+    /// before giving up, and defaults to 50 milliseconds
+    fn from_env() -> Self {
+        let slots_per_route = match std::env::var("MAX_CONCURRENT_SEARCHES").map(|x| x.parse()) {
+            Ok(Ok(x)) => x,
+            _ => std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+        };
+        let max_wait = match std::env::var("MAX_SEARCH_QUEUE_WAIT_MS").map(|x| x.parse()) {
+            Ok(Ok(x)) => Duration::from_millis(x),
+            _ => Duration::from_millis(50),
+        };
+
+        Self {
+            routes: Mutex::new(HashMap::new()),
+            slots_per_route,
+            max_wait,
+        }
+    }
+
+    fn semaphore(&self, route: &str) -> Arc<Semaphore> {
+        self.routes
+            .lock()
+            .entry(route.to_owned())
+            .or_insert_with(|| Arc::new(Semaphore::new(self.slots_per_route)))
+            .clone()
+    }
+}
+
+/// A slot to run a search in. Move it into the search so the slot is held until the search
+/// actually finishes, even if the request has already been answered
+pub(crate) struct SearchSlot {
+    _permit: OwnedSemaphorePermit,
+}
+
+/// Wait for a free search slot on this route
+///
+/// Returns None if every slot stayed busy for longer than we are willing to wait. How long we
+/// waited is logged either way, so we can see when we're close to saturated
+pub(crate) async fn acquire_search_slot(route: &str) -> Option<SearchSlot> {
+    let slots = SEARCH_SLOTS.get_or_init(SearchSlots::from_env);
+    let semaphore = slots.semaphore(route);
+
+    let started_at = Instant::now();
+    let permit = tokio::time::timeout(slots.max_wait, semaphore.acquire_owned()).await;
+    let queue_wait_ms = started_at.elapsed().as_millis();
+
+    match permit {
+        Ok(Ok(permit)) => {
+            tracing::info!(
+                route,
+                queue_wait_ms,
+                available_slots = semaphore.available_permits(),
+                "Got a search slot"
+            );
+
+            Some(SearchSlot { _permit: permit })
+        }
+        _ => {
+            tracing::warn!(
+                route,
+                queue_wait_ms,
+                slots = slots.slots_per_route,
+                "Every search slot is busy, skipping the search"
+            );
+
+            None
+        }
+    }
+}
+
+/// The error for routes that have no move to fall back on when every search slot is busy. It's
+/// turned into a `429 Too Many Requests` response
+#[derive(Debug)]
+pub(crate) struct SearchesSaturated;
+
+impl std::fmt::Display for SearchesSaturated {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Every search slot is busy, try again later")
+    }
+}
+
+impl std::error::Error for SearchesSaturated {}
+
+/// The move to make when we couldn't search at all
+///
+/// This is whatever [guard_move] picks over a move it will throw away if it's fatal, so it's at
+/// least a move that doesn't kill us on the spot
+pub(crate) fn saturated_fallback_move(game: &Game) -> MoveOutput {
+    guard_move(
+        game,
+        MoveOutput {
+            r#move: format!("{}", Move::Up),
+            shout: None,
+        },
+    )
+}
+
+/// How the search pool the searches run their deepening on is keeping up, served from
+/// `/debug/search-pool`
+///
+/// A growing queue delay means the pool has fewer threads than we have searches to run
+pub(crate) async fn route_search_pool_metrics() -> Json<serde_json::Value> {
+    let stats = search_pool().stats();
+
+    Json(json!({
+        "threads": stats.threads,
+        "tasks_started": stats.tasks_started,
+        "tasks_expired": stats.tasks_expired,
+        "max_queue_delay_ms": stats.max_queue_delay.as_millis() as u64,
+        "mean_queue_delay_ms": stats.mean_queue_delay.map(|delay| delay.as_millis() as u64),
+    }))
+}
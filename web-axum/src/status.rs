@@ -0,0 +1,169 @@
+use std::collections::VecDeque;
+
+use battlesnake_minimax::paranoid::SearchStats;
+use battlesnake_rs::board_only::you_on_board;
+use serde::Serialize;
+
+use crate::*;
+
+/// Games we haven't had a move for in this long are dropped from `/status`, in case the engine
+/// never sent `/end` for them
+const STALE_GAME: Duration = Duration::from_secs(10 * 60);
+
+/// A game one of our snakes is playing right now
+#[derive(Debug, Clone)]
+struct ActiveGame {
+    turn: i32,
+    last_search: Option<SearchStats>,
+    last_move_at: Instant,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Outcome {
+    Won,
+    Lost,
+    /// Everyone died on the same turn, or the game was stopped with more than one snake left
+    Draw,
+}
+
+impl Outcome {
+    /// Read the outcome off the final board the engine sends to `/end`
+    fn of(game: &Game) -> Self {
+        if !you_on_board(game) {
+            Outcome::Lost
+        } else if game.board.snakes.len() == 1 {
+            Outcome::Won
+        } else {
+            Outcome::Draw
+        }
+    }
+}
+
+/// A game that ended, newest first in `/status`
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct FinishedGame {
+    snake: String,
+    game_id: String,
+    turns: i32,
+    outcome: Outcome,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ActiveGameStatus {
+    snake: String,
+    game_id: String,
+    turn: i32,
+    last_depth: Option<usize>,
+    last_iterations: Option<usize>,
+    /// How much of its budget the last search used, from 0 to 1 and above 1 when it ran over
+    budget_utilization: Option<f64>,
+    ms_since_last_move: u128,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct StatusResponse {
+    active: Vec<ActiveGameStatus>,
+    finished: Vec<FinishedGame>,
+}
+
+/// What the deployment is doing right now, for `/status`
+///
+/// `STATUS_FINISHED_GAMES` sets how many finished games are kept and defaults to 50
+#[derive(Debug)]
+pub(crate) struct ArenaStatus {
+    active: GameStore<ActiveGame>,
+    finished: VecDeque<FinishedGame>,
+    capacity: usize,
+}
+
+impl ArenaStatus {
+    pub fn from_env() -> Self {
+        let capacity = match std::env::var("STATUS_FINISHED_GAMES").map(|x| x.parse()) {
+            Ok(Ok(x)) => x,
+            _ => 50,
+        };
+
+        Self {
+            active: GameStore::default(),
+            finished: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    pub fn record_move(
+        &mut self,
+        snake: &str,
+        game_id: &str,
+        turn: i32,
+        search: Option<SearchStats>,
+        now: Instant,
+    ) {
+        self.active
+            .retain(|game| now.duration_since(game.last_move_at) < STALE_GAME);
+
+        self.active.insert(
+            snake,
+            game_id,
+            ActiveGame {
+                turn,
+                last_search: search,
+                last_move_at: now,
+            },
+        );
+    }
+
+    pub fn active_games(&self) -> usize {
+        self.active.len()
+    }
+
+    pub fn end_game(&mut self, snake: &str, game: &Game) {
+        self.active.remove(snake, &game.game.id);
+
+        if self.capacity == 0 {
+            return;
+        }
+        if self.finished.len() >= self.capacity {
+            self.finished.pop_back();
+        }
+        self.finished.push_front(FinishedGame {
+            snake: snake.to_owned(),
+            game_id: game.game.id.clone(),
+            turns: game.turn,
+            outcome: Outcome::of(game),
+        });
+    }
+
+    fn snapshot(&self, now: Instant) -> StatusResponse {
+        let mut active: Vec<ActiveGameStatus> = self
+            .active
+            .iter()
+            .map(|((snake, game_id), game)| ActiveGameStatus {
+                snake: snake.clone(),
+                game_id: game_id.clone(),
+                turn: game.turn,
+                last_depth: game.last_search.map(|s| s.depth),
+                last_iterations: game.last_search.map(|s| s.iterations),
+                budget_utilization: game
+                    .last_search
+                    .filter(|s| !s.budget.is_zero())
+                    .map(|s| s.time_used.as_secs_f64() / s.budget.as_secs_f64()),
+                ms_since_last_move: now.saturating_duration_since(game.last_move_at).as_millis(),
+            })
+            .collect();
+        active.sort_by(|a, b| (&a.snake, &a.game_id).cmp(&(&b.snake, &b.game_id)));
+
+        StatusResponse {
+            active,
+            finished: self.finished.iter().cloned().collect(),
+        }
+    }
+}
+
+/// The games our snakes are in right now and the ones that finished most recently, for a
+/// dashboard to poll
+pub(crate) async fn route_status(
+    State(state): State<Arc<Mutex<AppState>>>,
+) -> Json<StatusResponse> {
+    Json(state.lock().status.snapshot(Instant::now()))
+}
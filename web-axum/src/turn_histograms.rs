@@ -0,0 +1,189 @@
+use std::{borrow::Cow, collections::BTreeMap};
+
+use serde::Serialize;
+
+use crate::*;
+
+/// Upper bounds of the handler latency buckets in milliseconds, with everything slower counted in
+/// a last overflow bucket
+const LATENCY_BUCKETS_MS: &[u64] = &[
+    25, 50, 100, 150, 200, 250, 300, 350, 400, 450, 500, 600, 750, 1000,
+];
+
+/// Upper bounds of the depth buckets. Depths past the last one land in the overflow bucket
+const DEPTH_BUCKETS: &[u64] = &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 12, 14, 16, 20, 25, 30];
+
+/// Counts of values in fixed buckets, cheap enough to update on every move
+#[derive(Debug, Clone)]
+struct Histogram {
+    bounds: &'static [u64],
+    /// One count for each bound, and one more for the values above the last bound
+    counts: Vec<u64>,
+    count: u64,
+    sum: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [u64]) -> Self {
+        Self {
+            bounds,
+            counts: vec![0; bounds.len() + 1],
+            count: 0,
+            sum: 0,
+        }
+    }
+
+    fn record(&mut self, value: u64) {
+        let bucket = self.bounds.partition_point(|bound| *bound < value);
+        self.counts[bucket] += 1;
+        self.count += 1;
+        self.sum += value;
+    }
+
+    fn snapshot(&self) -> HistogramSnapshot {
+        let buckets = self
+            .bounds
+            .iter()
+            .map(|bound| Some(*bound))
+            .chain(std::iter::once(None))
+            .zip(&self.counts)
+            .map(|(le, count)| Bucket { le, count: *count })
+            .collect();
+
+        HistogramSnapshot {
+            buckets,
+            count: self.count,
+            sum: self.sum,
+            mean: (self.count > 0).then(|| self.sum as f64 / self.count as f64),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Bucket {
+    /// The largest value counted in this bucket, None for the overflow bucket
+    le: Option<u64>,
+    count: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct HistogramSnapshot {
+    buckets: Vec<Bucket>,
+    count: u64,
+    sum: u64,
+    mean: Option<f64>,
+}
+
+/// How the turns for one snake in one ruleset went
+#[derive(Debug, Clone)]
+struct TurnHistogram {
+    handler_latency_ms: Histogram,
+    /// Only moves that searched, so snakes that don't search have an empty one
+    depth: Histogram,
+}
+
+impl Default for TurnHistogram {
+    fn default() -> Self {
+        Self {
+            handler_latency_ms: Histogram::new(LATENCY_BUCKETS_MS),
+            depth: Histogram::new(DEPTH_BUCKETS),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct TurnHistogramSnapshot {
+    snake: String,
+    ruleset: String,
+    handler_latency_ms: HistogramSnapshot,
+    depth: HistogramSnapshot,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct MetricsResponse {
+    /// The git commit this deployment was built from, the same release we report to Sentry
+    version: Cow<'static, str>,
+    histograms: Vec<TurnHistogramSnapshot>,
+    /// How many games each snake got a move for without seeing them start, which is how often a
+    /// restart landed in the middle of a live game. See [AppState::game_state_or_resume]
+    resumed_games: BTreeMap<String, u64>,
+    /// How many moves each snake answered again for a request the engine retried, without
+    /// searching again. See [RetriedMoves]
+    retried_moves: BTreeMap<String, u64>,
+    /// Moves our snakes came up with that ran into a wall or a body, see
+    /// [battlesnake_rs::move_guard::check_reasonable_move]. Anything but 0 is a bug
+    unreasonable_moves: usize,
+    /// The latest sample of the process's memory and the per-game stores, see [watch_memory]
+    memory: Option<MemorySample>,
+}
+
+/// Handler latency and search depth for every move since this deployment started, split up by
+/// snake and ruleset
+///
+/// They're tagged with the deployment's version, so comparing the histograms from before and after
+/// a deploy shows when it made the search slower
+#[derive(Debug, Default)]
+pub(crate) struct TurnHistograms {
+    histograms: BTreeMap<(String, String), TurnHistogram>,
+    resumed_games: BTreeMap<String, u64>,
+    retried_moves: BTreeMap<String, u64>,
+}
+
+impl TurnHistograms {
+    pub fn record_move(
+        &mut self,
+        snake: &str,
+        ruleset: &str,
+        handler_time: Duration,
+        search: Option<SearchStats>,
+    ) {
+        let histogram = self
+            .histograms
+            .entry((snake.to_owned(), ruleset.to_owned()))
+            .or_default();
+
+        histogram
+            .handler_latency_ms
+            .record(handler_time.as_millis().try_into().unwrap_or(u64::MAX));
+        if let Some(search) = search {
+            histogram.depth.record(search.depth as u64);
+        }
+    }
+
+    pub fn record_resumed_game(&mut self, snake: &str) {
+        *self.resumed_games.entry(snake.to_owned()).or_default() += 1;
+    }
+
+    pub fn record_retried_move(&mut self, snake: &str) {
+        *self.retried_moves.entry(snake.to_owned()).or_default() += 1;
+    }
+
+    fn snapshot(&self, memory: Option<MemorySample>) -> MetricsResponse {
+        MetricsResponse {
+            version: release_name(),
+            histograms: self
+                .histograms
+                .iter()
+                .map(|((snake, ruleset), histogram)| TurnHistogramSnapshot {
+                    snake: snake.clone(),
+                    ruleset: ruleset.clone(),
+                    handler_latency_ms: histogram.handler_latency_ms.snapshot(),
+                    depth: histogram.depth.snapshot(),
+                })
+                .collect(),
+            resumed_games: self.resumed_games.clone(),
+            retried_moves: self.retried_moves.clone(),
+            unreasonable_moves: battlesnake_rs::move_guard::unreasonable_moves(),
+            memory,
+        }
+    }
+}
+
+/// The turn time and depth histograms for this deployment, for the dashboards to scrape
+pub(crate) async fn route_metrics(
+    State(state): State<Arc<Mutex<AppState>>>,
+) -> Json<MetricsResponse> {
+    let state = state.lock();
+
+    Json(state.turn_histograms.snapshot(state.memory.clone()))
+}
@@ -3,56 +3,98 @@
 #[macro_use]
 extern crate rocket;
 
+use std::io::Read;
+
 use tracing::Subscriber;
 use tracing_subscriber::layer::SubscriberExt;
 
-use rocket::http::Status;
-
-use battlesnake_rs::{all_factories, AboutMe, BoxedFactory, Game, MoveOutput};
+use rocket::{
+    http::Status,
+    request::{self, FromRequest},
+    response::{self, Responder},
+    Data, Outcome,
+};
 
-use rocket::State;
+use battlesnake_rs::{
+    deadline::DEADLINE_HEADER,
+    failure_injection::FAILURE_HEADER,
+    request_handler::{handle_request, Response},
+};
 
 use rocket_contrib::json::Json;
 
-#[post("/<_snake>/start")]
-fn api_start(_snake: String) -> Status {
-    Status::NoContent
+/// The parts of a request [handle_request] needs besides the body
+struct EngineRequest {
+    method: String,
+    path: String,
+    deadline: Option<String>,
+    failure: Option<String>,
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for EngineRequest {
+    type Error = ();
+
+    fn from_request(request: &'a rocket::Request<'r>) -> request::Outcome<Self, Self::Error> {
+        let header = |name| request.headers().get_one(name).map(str::to_owned);
+
+        Outcome::Success(EngineRequest {
+            method: request.method().as_str().to_owned(),
+            path: request.uri().path().to_owned(),
+            deadline: header(DEADLINE_HEADER),
+            failure: header(FAILURE_HEADER),
+        })
+    }
 }
 
-#[post("/<snake>/end", data = "<game_state>")]
-fn api_end(
-    snake: String,
-    factories: State<Vec<BoxedFactory>>,
-    game_state: Json<Game>,
-) -> Option<Status> {
-    let snake_ai = factories
-        .iter()
-        .find(|s| s.name() == snake)?
-        .create_from_wire_game(game_state.into_inner());
-    snake_ai.end();
-
-    Some(Status::NoContent)
+impl EngineRequest {
+    fn handle(&self, body: &[u8]) -> EngineResponse {
+        EngineResponse(handle_request(
+            &self.method,
+            &self.path,
+            body,
+            self.deadline.as_deref(),
+            self.failure.as_deref(),
+        ))
+    }
+}
+
+struct EngineResponse(Response);
+
+impl<'r> Responder<'r> for EngineResponse {
+    fn respond_to(self, request: &rocket::Request) -> response::Result<'r> {
+        let status = Status::from_code(self.0.status).unwrap_or(Status::InternalServerError);
+
+        let mut response = match self.0.body {
+            Some(body) => Json(body).respond_to(request)?,
+            None => rocket::Response::new(),
+        };
+        response.set_status(status);
+
+        Ok(response)
+    }
 }
 
-#[post("/<snake>/move", data = "<game_state>")]
-fn api_move(
-    snake: String,
-    factories: State<Vec<BoxedFactory>>,
-    game_state: Json<Game>,
-) -> Option<Json<MoveOutput>> {
-    let snake_ai = factories
-        .iter()
-        .find(|s| s.name() == snake)?
-        .create_from_wire_game(game_state.into_inner());
-    let m = snake_ai.make_move().ok()?;
-
-    Some(Json(m))
+#[get("/<_snake>")]
+fn api_about(_snake: String, request: EngineRequest) -> EngineResponse {
+    request.handle(&[])
 }
 
-#[get("/<snake>")]
-fn api_about(snake: String, factories: State<Vec<BoxedFactory>>) -> Option<Json<AboutMe>> {
-    let factory = factories.iter().find(|s| s.name() == snake)?;
-    Some(Json(factory.about()))
+#[post("/<_snake>/<_action>", data = "<data>")]
+fn api_engine(
+    _snake: String,
+    _action: String,
+    request: EngineRequest,
+    data: Data,
+) -> EngineResponse {
+    let mut body = vec![];
+    if data.open().read_to_end(&mut body).is_err() {
+        return EngineResponse(Response {
+            status: 400,
+            body: Some(serde_json::json!({ "error": "Couldn't read the body" })),
+        });
+    }
+
+    request.handle(&body)
 }
 
 fn main() {
@@ -80,8 +122,7 @@ fn main() {
     let cors = rocket_cors::CorsOptions::default().to_cors().unwrap();
 
     rocket::ignite()
-        .manage(all_factories())
         .attach(cors)
-        .mount("/", routes![api_start, api_end, api_move, api_about])
+        .mount("/", routes![api_about, api_engine])
         .launch();
 }